@@ -0,0 +1,93 @@
+/*
+ * Copyright 2024 Lance Developers.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package com.lancedb;
+
+import java.util.Arrays;
+import java.util.List;
+import org.apache.arrow.c.ArrowArrayStream;
+import org.apache.arrow.c.Data;
+import org.apache.arrow.memory.BufferAllocator;
+import org.apache.arrow.vector.ipc.ArrowReader;
+
+/**
+ * A connection to a LanceDB database.
+ *
+ * <p>Tables opened through the connection stay open after the connection is closed.
+ */
+public class Connection implements AutoCloseable {
+  static {
+    NativeLibrary.load();
+  }
+
+  private long handle;
+
+  private Connection(long handle) {
+    this.handle = handle;
+  }
+
+  /** Connect to the database at the given URI, e.g. a local directory. */
+  public static Connection connect(String uri) {
+    return new Connection(nativeConnect(uri));
+  }
+
+  /** The names of the tables in the database, in lexical order. */
+  public List<String> tableNames() {
+    return Arrays.asList(nativeTableNames(handle));
+  }
+
+  /** Open an existing table. */
+  public Table openTable(String name) {
+    return new Table(nativeOpenTable(handle, name));
+  }
+
+  /**
+   * Create a table with the data of a reader.
+   *
+   * <p>The reader is consumed and closed.
+   */
+  public Table createTable(String name, ArrowReader data, BufferAllocator allocator) {
+    try (ArrowArrayStream stream = ArrowArrayStream.allocateNew(allocator)) {
+      Data.exportArrayStream(allocator, data, stream);
+      return new Table(nativeCreateTable(handle, name, stream.memoryAddress()));
+    }
+  }
+
+  /** Drop a table and delete its data. */
+  public void dropTable(String name) {
+    nativeDropTable(handle, name);
+  }
+
+  @Override
+  public void close() {
+    if (handle != 0) {
+      nativeClose(handle);
+      handle = 0;
+    }
+  }
+
+  private static native long nativeConnect(String uri);
+
+  private native void nativeClose(long handle);
+
+  private native String[] nativeTableNames(long handle);
+
+  private native long nativeOpenTable(long handle, String name);
+
+  private native long nativeCreateTable(long handle, String name, long streamAddress);
+
+  private native void nativeDropTable(long handle, String name);
+}
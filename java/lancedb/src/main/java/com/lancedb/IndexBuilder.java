@@ -0,0 +1,95 @@
+/*
+ * Copyright 2024 Lance Developers.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package com.lancedb;
+
+/**
+ * Builds an index on the columns of a table.
+ *
+ * <p>By default the type of the index is chosen from the type of the column: an IVF_PQ index for
+ * vector columns and a BTree index for scalar columns.
+ */
+public class IndexBuilder {
+  private final Table table;
+  private final String[] columns;
+  private String indexType = "auto";
+  private boolean replace = true;
+  private String distanceType;
+  private int numPartitions = 0;
+  private int numSubVectors = 0;
+
+  IndexBuilder(Table table, String[] columns) {
+    this.table = table;
+    this.columns = columns;
+  }
+
+  /** Build a BTree index on a scalar column. */
+  public IndexBuilder btree() {
+    this.indexType = "btree";
+    return this;
+  }
+
+  /** Build an IVF_PQ index on a vector column. */
+  public IndexBuilder ivfPq() {
+    this.indexType = "ivf_pq";
+    return this;
+  }
+
+  /** Whether to replace an existing index on the columns, true by default. */
+  public IndexBuilder replace(boolean replace) {
+    this.replace = replace;
+    return this;
+  }
+
+  /** The distance type of an IVF_PQ index: "l2", "cosine" or "dot". */
+  public IndexBuilder distanceType(String distanceType) {
+    this.distanceType = distanceType;
+    return this;
+  }
+
+  /** The number of partitions of an IVF_PQ index. */
+  public IndexBuilder numPartitions(int numPartitions) {
+    this.numPartitions = numPartitions;
+    return this;
+  }
+
+  /** The number of sub vectors of an IVF_PQ index. */
+  public IndexBuilder numSubVectors(int numSubVectors) {
+    this.numSubVectors = numSubVectors;
+    return this;
+  }
+
+  /** Build the index. */
+  public void execute() {
+    if (!indexType.equals("ivf_pq")
+        && (distanceType != null || numPartitions != 0 || numSubVectors != 0)) {
+      throw new IllegalStateException("the IVF_PQ parameters require ivfPq()");
+    }
+    table.runWithHandle(
+        handle ->
+            nativeCreateIndex(
+                handle, columns, indexType, replace, distanceType, numPartitions, numSubVectors));
+  }
+
+  private native void nativeCreateIndex(
+      long tableHandle,
+      String[] columns,
+      String indexType,
+      boolean replace,
+      String distanceType,
+      int numPartitions,
+      int numSubVectors);
+}
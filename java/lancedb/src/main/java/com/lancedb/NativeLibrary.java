@@ -0,0 +1,35 @@
+/*
+ * Copyright 2024 Lance Developers.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package com.lancedb;
+
+/** Loads the native library built from the Rust crate {@code lancedb-jni}. */
+final class NativeLibrary {
+  private static volatile boolean loaded = false;
+
+  private NativeLibrary() {}
+
+  static void load() {
+    if (!loaded) {
+      synchronized (NativeLibrary.class) {
+        if (!loaded) {
+          System.loadLibrary("lancedb_jni");
+          loaded = true;
+        }
+      }
+    }
+  }
+}
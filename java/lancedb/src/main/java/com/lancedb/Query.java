@@ -0,0 +1,108 @@
+/*
+ * Copyright 2024 Lance Developers.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package com.lancedb;
+
+import org.apache.arrow.c.ArrowArrayStream;
+import org.apache.arrow.c.Data;
+import org.apache.arrow.memory.BufferAllocator;
+import org.apache.arrow.vector.ipc.ArrowReader;
+
+/**
+ * A query of a table.
+ *
+ * <p>A query with a vector, see {@link #nearestTo}, is a vector search.
+ */
+public class Query {
+  private final Table table;
+  private String filter;
+  private String[] columns;
+  private long limit = -1;
+  private float[] vector;
+  private String column;
+  private int nprobes = 0;
+
+  Query(Table table) {
+    this.table = table;
+  }
+
+  /** Only return the rows that match an SQL filter. */
+  public Query where(String filter) {
+    this.filter = filter;
+    return this;
+  }
+
+  /** Only return the given columns. */
+  public Query select(String... columns) {
+    this.columns = columns;
+    return this;
+  }
+
+  /** Return at most the given number of rows. */
+  public Query limit(long limit) {
+    if (limit < 0) {
+      throw new IllegalArgumentException("the limit must not be negative");
+    }
+    this.limit = limit;
+    return this;
+  }
+
+  /** Search for the nearest neighbors of a vector. */
+  public Query nearestTo(float[] vector) {
+    this.vector = vector;
+    return this;
+  }
+
+  /** The vector column to search, if the table has more than one. */
+  public Query column(String column) {
+    this.column = column;
+    return this;
+  }
+
+  /** The number of partitions of the vector index to search. */
+  public Query nprobes(int nprobes) {
+    this.nprobes = nprobes;
+    return this;
+  }
+
+  /**
+   * Run the query.
+   *
+   * <p>The reader must be closed. Reading it blocks until the next batch is available.
+   */
+  public ArrowReader execute(BufferAllocator allocator) {
+    if (vector == null && (column != null || nprobes != 0)) {
+      throw new IllegalStateException("column and nprobes require nearestTo");
+    }
+    try (ArrowArrayStream stream = ArrowArrayStream.allocateNew(allocator)) {
+      table.runWithHandle(
+          handle ->
+              nativeExecute(
+                  handle, filter, columns, limit, vector, column, nprobes, stream.memoryAddress()));
+      return Data.importArrayStream(allocator, stream);
+    }
+  }
+
+  private native void nativeExecute(
+      long tableHandle,
+      String filter,
+      String[] columns,
+      long limit,
+      float[] vector,
+      String column,
+      int nprobes,
+      long streamAddress);
+}
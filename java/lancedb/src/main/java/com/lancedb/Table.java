@@ -0,0 +1,150 @@
+/*
+ * Copyright 2024 Lance Developers.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package com.lancedb;
+
+import java.util.concurrent.locks.ReentrantReadWriteLock;
+import java.util.function.LongConsumer;
+import java.util.function.LongFunction;
+import org.apache.arrow.c.ArrowArrayStream;
+import org.apache.arrow.c.ArrowSchema;
+import org.apache.arrow.c.Data;
+import org.apache.arrow.memory.BufferAllocator;
+import org.apache.arrow.vector.ipc.ArrowReader;
+import org.apache.arrow.vector.types.pojo.Schema;
+
+/**
+ * A table of a LanceDB database.
+ *
+ * <p>A table can be used from several threads. Closing it waits for the calls in progress and
+ * fails the calls made afterwards.
+ */
+public class Table implements AutoCloseable {
+  static {
+    NativeLibrary.load();
+  }
+
+  // Native calls hold the read lock so that close can not free the handle during a call
+  private final ReentrantReadWriteLock lock = new ReentrantReadWriteLock();
+  private long handle;
+
+  Table(long handle) {
+    this.handle = handle;
+  }
+
+  /** Make a native call with the handle, which stays open until the call returns. */
+  <T> T withHandle(LongFunction<T> call) {
+    lock.readLock().lock();
+    try {
+      if (handle == 0) {
+        throw new IllegalStateException("the table is closed");
+      }
+      return call.apply(handle);
+    } finally {
+      lock.readLock().unlock();
+    }
+  }
+
+  /** Make a native call that returns nothing, see {@link #withHandle}. */
+  void runWithHandle(LongConsumer call) {
+    withHandle(
+        handle -> {
+          call.accept(handle);
+          return null;
+        });
+  }
+
+  /** The version of the table, which increases with every write. */
+  public long version() {
+    return withHandle(handle -> nativeVersion(handle));
+  }
+
+  /** The schema of the table. */
+  public Schema schema(BufferAllocator allocator) {
+    try (ArrowSchema schema = ArrowSchema.allocateNew(allocator)) {
+      runWithHandle(handle -> nativeSchema(handle, schema.memoryAddress()));
+      return Data.importSchema(allocator, schema, null);
+    }
+  }
+
+  /** Count the rows of the table. */
+  public long countRows() {
+    return withHandle(handle -> nativeCountRows(handle, null));
+  }
+
+  /** Count the rows that match an SQL filter. */
+  public long countRows(String filter) {
+    return withHandle(handle -> nativeCountRows(handle, filter));
+  }
+
+  /**
+   * Append the data of a reader to the table.
+   *
+   * <p>The reader is consumed and closed.
+   */
+  public void add(ArrowReader data, BufferAllocator allocator) {
+    try (ArrowArrayStream stream = ArrowArrayStream.allocateNew(allocator)) {
+      Data.exportArrayStream(allocator, data, stream);
+      runWithHandle(handle -> nativeAdd(handle, stream.memoryAddress()));
+    }
+  }
+
+  /** Delete the rows that match an SQL predicate. */
+  public void delete(String predicate) {
+    runWithHandle(handle -> nativeDelete(handle, predicate));
+  }
+
+  /** Start building an index on the given columns. */
+  public IndexBuilder createIndex(String... columns) {
+    return new IndexBuilder(this, columns);
+  }
+
+  /** Start building a query of the table. */
+  public Query query() {
+    return new Query(this);
+  }
+
+  /** Start building a search for the nearest neighbors of a vector. */
+  public Query vectorSearch(float[] vector) {
+    return new Query(this).nearestTo(vector);
+  }
+
+  /** Close the table, waiting for the calls in progress. Closing it again does nothing. */
+  @Override
+  public void close() {
+    lock.writeLock().lock();
+    try {
+      if (handle != 0) {
+        nativeClose(handle);
+        handle = 0;
+      }
+    } finally {
+      lock.writeLock().unlock();
+    }
+  }
+
+  private native void nativeClose(long handle);
+
+  private native long nativeVersion(long handle);
+
+  private native void nativeSchema(long handle, long schemaAddress);
+
+  private native long nativeCountRows(long handle, String filter);
+
+  private native void nativeAdd(long handle, long streamAddress);
+
+  private native void nativeDelete(long handle, String predicate);
+}
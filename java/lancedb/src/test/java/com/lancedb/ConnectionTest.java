@@ -0,0 +1,118 @@
+/*
+ * Copyright 2024 Lance Developers.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package com.lancedb;
+
+import static org.junit.jupiter.api.Assertions.assertEquals;
+import static org.junit.jupiter.api.Assertions.assertThrows;
+
+import java.io.ByteArrayOutputStream;
+import java.nio.file.Path;
+import java.util.ArrayList;
+import java.util.Collections;
+import java.util.List;
+import org.apache.arrow.memory.BufferAllocator;
+import org.apache.arrow.memory.RootAllocator;
+import org.apache.arrow.vector.IntVector;
+import org.apache.arrow.vector.VectorSchemaRoot;
+import org.apache.arrow.vector.ipc.ArrowReader;
+import org.apache.arrow.vector.ipc.ArrowStreamReader;
+import org.apache.arrow.vector.ipc.ArrowStreamWriter;
+import org.apache.arrow.vector.types.pojo.ArrowType;
+import org.apache.arrow.vector.types.pojo.Field;
+import org.apache.arrow.vector.types.pojo.Schema;
+import org.apache.arrow.vector.util.ByteArrayReadableSeekableByteChannel;
+import org.junit.jupiter.api.Test;
+import org.junit.jupiter.api.io.TempDir;
+
+class ConnectionTest {
+  private static ArrowReader ids(BufferAllocator allocator, int start, int count)
+      throws Exception {
+    Schema schema =
+        new Schema(
+            Collections.singletonList(Field.notNullable("id", new ArrowType.Int(32, true))));
+    ByteArrayOutputStream out = new ByteArrayOutputStream();
+    try (VectorSchemaRoot root = VectorSchemaRoot.create(schema, allocator);
+        ArrowStreamWriter writer = new ArrowStreamWriter(root, null, out)) {
+      IntVector id = (IntVector) root.getVector("id");
+      id.allocateNew(count);
+      for (int i = 0; i < count; i++) {
+        id.set(i, start + i);
+      }
+      root.setRowCount(count);
+      writer.writeBatch();
+    }
+    return new ArrowStreamReader(
+        new ByteArrayReadableSeekableByteChannel(out.toByteArray()), allocator);
+  }
+
+  @Test
+  void roundTrip(@TempDir Path dir) throws Exception {
+    try (BufferAllocator allocator = new RootAllocator();
+        Connection conn = Connection.connect(dir.toString());
+        Table table = conn.createTable("test", ids(allocator, 0, 10), allocator)) {
+      assertEquals(List.of("test"), conn.tableNames());
+      assertEquals(10, table.countRows());
+      table.add(ids(allocator, 10, 10), allocator);
+      assertEquals(5, table.countRows("id >= 15"));
+      assertEquals(1, table.schema(allocator).getFields().size());
+
+      long rows = 0;
+      try (ArrowReader reader = table.query().where("id < 12").limit(100).execute(allocator)) {
+        while (reader.loadNextBatch()) {
+          rows += reader.getVectorSchemaRoot().getRowCount();
+        }
+      }
+      assertEquals(12, rows);
+
+      table.createIndex("id").btree().execute();
+      table.delete("id < 5");
+      assertEquals(15, table.countRows());
+
+      assertThrows(LanceDbException.class, () -> conn.openTable("missing"));
+      assertThrows(LanceDbException.class, () -> table.countRows("not a filter ("));
+    }
+  }
+
+  @Test
+  void closeTableWhileInUse(@TempDir Path dir) throws Exception {
+    try (BufferAllocator allocator = new RootAllocator();
+        Connection conn = Connection.connect(dir.toString())) {
+      conn.createTable("test", ids(allocator, 0, 10), allocator).close();
+      Table table = conn.openTable("test");
+      List<Thread> threads = new ArrayList<>();
+      for (int i = 0; i < 4; i++) {
+        threads.add(
+            new Thread(
+                () -> {
+                  try {
+                    while (true) {
+                      table.countRows();
+                    }
+                  } catch (IllegalStateException closed) {
+                    // The table was closed, calls in progress were not interrupted
+                  }
+                }));
+      }
+      threads.forEach(Thread::start);
+      table.close();
+      for (Thread thread : threads) {
+        thread.join();
+      }
+      table.close();
+      assertThrows(IllegalStateException.class, table::version);
+    }
+  }
+}
@@ -0,0 +1,111 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use jni::objects::{JClass, JObject, JString};
+use jni::sys::{jlong, jobjectArray};
+use jni::JNIEnv;
+use lancedb::{Connection, Table};
+
+use crate::error::{JavaResultExt, Result};
+use crate::{free_handle, from_handle, get_string, into_handle, new_string_array, runtime};
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Connection_nativeConnect(
+    mut env: JNIEnv,
+    _class: JClass,
+    uri: JString,
+) -> jlong {
+    let result = (|| -> Result<jlong> {
+        let uri = get_string(&mut env, &uri)?;
+        let connection = runtime()?.block_on(lancedb::connect(&uri).execute())?;
+        Ok(into_handle(connection))
+    })();
+    result.or_throw(&mut env, 0)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Connection_nativeClose(
+    _env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+) {
+    unsafe { free_handle::<Connection>(handle) }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Connection_nativeTableNames(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+) -> jobjectArray {
+    let result = (|| -> Result<jobjectArray> {
+        let connection = unsafe { from_handle::<Connection>(handle)? };
+        let names = runtime()?.block_on(connection.table_names().execute())?;
+        Ok(new_string_array(&mut env, &names)?.into_raw())
+    })();
+    result.or_throw(&mut env, std::ptr::null_mut())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Connection_nativeOpenTable(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+    name: JString,
+) -> jlong {
+    let result = (|| -> Result<jlong> {
+        let connection = unsafe { from_handle::<Connection>(handle)? };
+        let name = get_string(&mut env, &name)?;
+        let table = runtime()?.block_on(connection.open_table(name).execute())?;
+        Ok(into_handle::<Table>(table))
+    })();
+    result.or_throw(&mut env, 0)
+}
+
+/// The stream at `stream_address` is consumed, even if the call fails
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Connection_nativeCreateTable(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+    name: JString,
+    stream_address: jlong,
+) -> jlong {
+    let result = (|| -> Result<jlong> {
+        let data = unsafe {
+            ArrowArrayStreamReader::from_raw(stream_address as *mut FFI_ArrowArrayStream)?
+        };
+        let connection = unsafe { from_handle::<Connection>(handle)? };
+        let name = get_string(&mut env, &name)?;
+        let table = runtime()?.block_on(connection.create_table(name, data).execute())?;
+        Ok(into_handle::<Table>(table))
+    })();
+    result.or_throw(&mut env, 0)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Connection_nativeDropTable(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+    name: JString,
+) {
+    let result = (|| -> Result<()> {
+        let connection = unsafe { from_handle::<Connection>(handle)? };
+        let name = get_string(&mut env, &name)?;
+        Ok(runtime()?.block_on(connection.drop_table(name))?)
+    })();
+    result.or_throw(&mut env, ())
+}
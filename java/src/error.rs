@@ -0,0 +1,77 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow_schema::ArrowError;
+use jni::JNIEnv;
+use snafu::Snafu;
+
+/// The Java class of the exceptions thrown by the binding
+const EXCEPTION_CLASS: &str = "com/lancedb/LanceDbException";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{message}"))]
+    LanceDB { message: String },
+    #[snafu(display("{message}"))]
+    Jni { message: String },
+    #[snafu(display("{message}"))]
+    InvalidArgument { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<lancedb::error::Error> for Error {
+    fn from(e: lancedb::error::Error) -> Self {
+        Self::LanceDB {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<ArrowError> for Error {
+    fn from(e: ArrowError) -> Self {
+        Self::LanceDB {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<jni::errors::Error> for Error {
+    fn from(e: jni::errors::Error) -> Self {
+        Self::Jni {
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Converts a [`Result`] into the value returned to Java
+pub trait JavaResultExt<T> {
+    /// Throw a `LanceDbException` on error and return `default` instead
+    fn or_throw(self, env: &mut JNIEnv, default: T) -> T;
+}
+
+impl<T> JavaResultExt<T> for Result<T> {
+    fn or_throw(self, env: &mut JNIEnv, default: T) -> T {
+        match self {
+            Ok(value) => value,
+            Err(err) => {
+                // A pending exception (e.g. from a failed JNI call) is kept
+                if !env.exception_check().unwrap_or(true) {
+                    let _ = env.throw_new(EXCEPTION_CLASS, err.to_string());
+                }
+                default
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jni::objects::{JObject, JObjectArray, JString};
+use jni::sys::{jboolean, jint, jlong, JNI_TRUE};
+use jni::JNIEnv;
+use lancedb::index::scalar::BTreeIndexBuilder;
+use lancedb::index::vector::IvfPqIndexBuilder;
+use lancedb::index::Index;
+use lancedb::{DistanceType, Table};
+
+use crate::error::{Error, JavaResultExt, Result};
+use crate::{from_handle, get_opt_string, get_string, get_string_array, runtime};
+
+fn parse_distance_type(distance_type: &str) -> Result<DistanceType> {
+    match distance_type.to_lowercase().as_str() {
+        "l2" => Ok(DistanceType::L2),
+        "cosine" => Ok(DistanceType::Cosine),
+        "dot" => Ok(DistanceType::Dot),
+        _ => Err(Error::InvalidArgument {
+            message: format!("Invalid distance type '{}'", distance_type),
+        }),
+    }
+}
+
+/// Create an index, the parameters that are 0 or null use the default
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_IndexBuilder_nativeCreateIndex(
+    mut env: JNIEnv,
+    _obj: JObject,
+    table_handle: jlong,
+    columns: JObjectArray,
+    index_type: JString,
+    replace: jboolean,
+    distance_type: JString,
+    num_partitions: jint,
+    num_sub_vectors: jint,
+) {
+    let result = (|| -> Result<()> {
+        let table = unsafe { from_handle::<Table>(table_handle)? };
+        let columns = get_string_array(&mut env, &columns)?.unwrap_or_default();
+        let index = match get_string(&mut env, &index_type)?.as_str() {
+            "auto" => Index::Auto,
+            "btree" => Index::BTree(BTreeIndexBuilder::default()),
+            "ivf_pq" => {
+                let mut builder = IvfPqIndexBuilder::default();
+                if let Some(distance_type) = get_opt_string(&mut env, &distance_type)? {
+                    builder = builder.distance_type(parse_distance_type(&distance_type)?);
+                }
+                if num_partitions > 0 {
+                    builder = builder.num_partitions(num_partitions as u32);
+                }
+                if num_sub_vectors > 0 {
+                    builder = builder.num_sub_vectors(num_sub_vectors as u32);
+                }
+                Index::IvfPq(builder)
+            }
+            other => {
+                return Err(Error::InvalidArgument {
+                    message: format!("{} is not a valid index type", other),
+                })
+            }
+        };
+        let builder = table
+            .create_index(&columns, index)
+            .replace(replace == JNI_TRUE);
+        Ok(runtime()?.block_on(builder.execute())?)
+    })();
+    result.or_throw(&mut env, ())
+}
@@ -0,0 +1,121 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The JNI binding of LanceDB
+//!
+//! The Java classes in `lancedb/` hold the address of a boxed Rust object
+//! (connection or table) as a `long` handle and call the `native*` functions
+//! of this crate with it.  Arrow data crosses the boundary through the Arrow
+//! C data interface: Java allocates an `ArrowArrayStream` or `ArrowSchema`
+//! with `org.apache.arrow.c` and passes its memory address.
+//!
+//! Errors are thrown as `com.lancedb.LanceDbException`.
+
+use jni::objects::{JObject, JObjectArray, JString};
+use jni::sys::jlong;
+use jni::JNIEnv;
+use once_cell::sync::OnceCell;
+use tokio::runtime::Runtime;
+
+use crate::error::{Error, Result};
+
+mod connection;
+mod error;
+mod index;
+mod query;
+mod table;
+
+fn runtime() -> Result<&'static Runtime> {
+    static RUNTIME: OnceCell<Runtime> = OnceCell::new();
+    RUNTIME.get_or_try_init(|| {
+        Runtime::new().map_err(|e| Error::LanceDB {
+            message: e.to_string(),
+        })
+    })
+}
+
+/// Move a value to the heap and return its address as a handle for Java
+fn into_handle<T>(value: T) -> jlong {
+    Box::into_raw(Box::new(value)) as jlong
+}
+
+/// Borrow the value behind a handle
+///
+/// # Safety
+///
+/// The handle must be 0 or have been created by [`into_handle`] with the same
+/// type and not freed yet.
+unsafe fn from_handle<'a, T>(handle: jlong) -> Result<&'a T> {
+    (handle as *const T)
+        .as_ref()
+        .ok_or_else(|| Error::InvalidArgument {
+            message: "the object is closed".to_string(),
+        })
+}
+
+/// Drop the value behind a handle
+///
+/// # Safety
+///
+/// The handle must be 0 or have been created by [`into_handle`] with the same
+/// type and not freed yet.
+unsafe fn free_handle<T>(handle: jlong) {
+    if handle != 0 {
+        drop(Box::from_raw(handle as *mut T));
+    }
+}
+
+fn get_string(env: &mut JNIEnv, value: &JString) -> Result<String> {
+    if value.is_null() {
+        return Err(Error::InvalidArgument {
+            message: "the string must not be null".to_string(),
+        });
+    }
+    Ok(env.get_string(value)?.into())
+}
+
+/// Like [`get_string`] but null is allowed and means None
+fn get_opt_string(env: &mut JNIEnv, value: &JString) -> Result<Option<String>> {
+    if value.is_null() {
+        Ok(None)
+    } else {
+        get_string(env, value).map(Some)
+    }
+}
+
+/// Read a `String[]`, null means None
+fn get_string_array(env: &mut JNIEnv, values: &JObjectArray) -> Result<Option<Vec<String>>> {
+    if values.is_null() {
+        return Ok(None);
+    }
+    let len = env.get_array_length(values)?;
+    let mut strings = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let value = JString::from(env.get_object_array_element(values, i)?);
+        strings.push(get_string(env, &value)?);
+    }
+    Ok(Some(strings))
+}
+
+fn new_string_array<'local>(
+    env: &mut JNIEnv<'local>,
+    values: &[String],
+) -> Result<JObjectArray<'local>> {
+    let array = env.new_object_array(values.len() as i32, "java/lang/String", JObject::null())?;
+    for (i, value) in values.iter().enumerate() {
+        let value = env.new_string(value)?;
+        env.set_object_array_element(&array, i as i32, value)?;
+    }
+    Ok(array)
+}
@@ -0,0 +1,76 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use jni::objects::{JFloatArray, JObject, JObjectArray, JString};
+use jni::sys::{jint, jlong};
+use jni::JNIEnv;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use lancedb::Table;
+
+use crate::error::{JavaResultExt, Result};
+use crate::{from_handle, get_opt_string, get_string_array, runtime};
+
+/// Run a query and export the results into the `ArrowArrayStream` at
+/// `stream_address`
+///
+/// The query is described by the parameters, the ones that are null or
+/// negative are not set.  A query with a vector is a vector search.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Query_nativeExecute(
+    mut env: JNIEnv,
+    _obj: JObject,
+    table_handle: jlong,
+    filter: JString,
+    columns: JObjectArray,
+    limit: jlong,
+    vector: JFloatArray,
+    column: JString,
+    nprobes: jint,
+    stream_address: jlong,
+) {
+    let result = (|| -> Result<()> {
+        let table = unsafe { from_handle::<Table>(table_handle)? };
+        let mut query = table.query();
+        if let Some(filter) = get_opt_string(&mut env, &filter)? {
+            query = query.only_if(filter);
+        }
+        if let Some(columns) = get_string_array(&mut env, &columns)? {
+            query = query.select(Select::Columns(columns));
+        }
+        if limit >= 0 {
+            query = query.limit(limit as usize);
+        }
+        let runtime = runtime()?;
+        let reader = if vector.is_null() {
+            runtime.block_on(query.execute_into_reader())?
+        } else {
+            let mut values = vec![0.0; env.get_array_length(&vector)? as usize];
+            env.get_float_array_region(&vector, 0, &mut values)?;
+            let mut query = query.nearest_to(values)?;
+            if let Some(column) = get_opt_string(&mut env, &column)? {
+                query = query.column(&column);
+            }
+            if nprobes > 0 {
+                query = query.nprobes(nprobes as usize);
+            }
+            runtime.block_on(query.execute_into_reader())?
+        };
+        let stream = FFI_ArrowArrayStream::new(reader);
+        unsafe { std::ptr::write(stream_address as *mut FFI_ArrowArrayStream, stream) };
+        Ok(())
+    })();
+    result.or_throw(&mut env, ())
+}
@@ -0,0 +1,111 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::ffi::FFI_ArrowSchema;
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use jni::objects::{JObject, JString};
+use jni::sys::jlong;
+use jni::JNIEnv;
+use lancedb::Table;
+
+use crate::error::{JavaResultExt, Result};
+use crate::{free_handle, from_handle, get_opt_string, get_string, runtime};
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Table_nativeClose(
+    _env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+) {
+    unsafe { free_handle::<Table>(handle) }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Table_nativeVersion(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+) -> jlong {
+    let result = (|| -> Result<jlong> {
+        let table = unsafe { from_handle::<Table>(handle)? };
+        Ok(runtime()?.block_on(table.version())? as jlong)
+    })();
+    result.or_throw(&mut env, 0)
+}
+
+/// Export the schema into the `ArrowSchema` at `schema_address`
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Table_nativeSchema(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+    schema_address: jlong,
+) {
+    let result = (|| -> Result<()> {
+        let table = unsafe { from_handle::<Table>(handle)? };
+        let schema = runtime()?.block_on(table.schema())?;
+        let schema = FFI_ArrowSchema::try_from(schema.as_ref())?;
+        unsafe { std::ptr::write(schema_address as *mut FFI_ArrowSchema, schema) };
+        Ok(())
+    })();
+    result.or_throw(&mut env, ())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Table_nativeCountRows(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+    filter: JString,
+) -> jlong {
+    let result = (|| -> Result<jlong> {
+        let table = unsafe { from_handle::<Table>(handle)? };
+        let filter = get_opt_string(&mut env, &filter)?;
+        Ok(runtime()?.block_on(table.count_rows(filter))? as jlong)
+    })();
+    result.or_throw(&mut env, 0)
+}
+
+/// The stream at `stream_address` is consumed, even if the call fails
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Table_nativeAdd(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+    stream_address: jlong,
+) {
+    let result = (|| -> Result<()> {
+        let data = unsafe {
+            ArrowArrayStreamReader::from_raw(stream_address as *mut FFI_ArrowArrayStream)?
+        };
+        let table = unsafe { from_handle::<Table>(handle)? };
+        Ok(runtime()?.block_on(table.add(data).execute())?)
+    })();
+    result.or_throw(&mut env, ())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_Table_nativeDelete(
+    mut env: JNIEnv,
+    _obj: JObject,
+    handle: jlong,
+    predicate: JString,
+) {
+    let result = (|| -> Result<()> {
+        let table = unsafe { from_handle::<Table>(handle)? };
+        let predicate = get_string(&mut env, &predicate)?;
+        Ok(runtime()?.block_on(table.delete(&predicate))?)
+    })();
+    result.or_throw(&mut env, ())
+}
@@ -83,6 +83,31 @@ describe("Given a table", () => {
     expect(await table.countRows("id == 7")).toBe(1);
     expect(await table.countRows("id == 10")).toBe(1);
   });
+
+  it("should let me update with an update spec", async () => {
+    await table.add([{ id: 1 }, { id: 2 }, { id: 3 }]);
+    await table.update({ where: "id > 1", values: { id: "id + 10" } });
+    expect(await table.countRows("id > 10")).toBe(2);
+    await table.update({ id: "0" });
+    expect(await table.countRows("id == 0")).toBe(3);
+  });
+
+  it("should let me merge insert", async () => {
+    await table.add([{ id: 1 }, { id: 2 }, { id: 3 }]);
+    await table
+      .mergeInsert("id")
+      .whenMatchedUpdateAll()
+      .whenNotMatchedInsertAll()
+      .execute([{ id: 3 }, { id: 4 }]);
+    expect(await table.countRows()).toBe(4);
+
+    await table
+      .mergeInsert(["id"])
+      .whenNotMatchedBySourceDelete({ where: "id > 1" })
+      .execute([{ id: 2 }]);
+    expect(await table.countRows()).toBe(2);
+    expect(await table.countRows("id == 1")).toBe(1);
+  });
 });
 
 describe("When creating an index", () => {
@@ -182,6 +207,24 @@ describe("When creating an index", () => {
     }
   });
 
+  test("should stream query results", async () => {
+    let numRows = 0;
+    const stream = tbl.query().filter("id >= 100").select(["id"]).execute();
+    for await (const batch of stream) {
+      expect(batch.schema.names).toEqual(["id"]);
+      numRows += batch.numRows;
+    }
+    expect(numRows).toBe(200);
+
+    const results = await tbl
+      .query()
+      .nearestTo(queryVec)
+      .filter("id < 10")
+      .limit(5)
+      .toArrow();
+    expect(results.numRows).toBe(5);
+  });
+
   // TODO: Move this test to the query API test (making sure we can reject queries
   // when the dimension is incorrect)
   test("two columns with different dimensions", async () => {
@@ -44,7 +44,14 @@ export {
   RecordBatchIterator,
 } from "./query";
 export { Index, IndexOptions, IvfPqOptions } from "./indices";
-export { Table, AddDataOptions, IndexConfig, UpdateOptions } from "./table";
+export {
+  Table,
+  AddDataOptions,
+  IndexConfig,
+  UpdateOptions,
+  UpdateSpec,
+} from "./table";
+export { MergeInsertBuilder } from "./merge";
 export * as embedding from "./embedding";
 
 /**
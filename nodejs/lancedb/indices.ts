@@ -111,6 +111,16 @@ export interface IvfPqOptions {
    * The default value is 256.
    */
   sampleRate?: number;
+
+  /**
+   * The number of bits used to encode each sub-vector.
+   *
+   * Each sub-vector is replaced by the id of the closest of `2^num_bits` centroids.
+   * Fewer bits make the index smaller but less accurate.
+   *
+   * The value must be between 1 and 8.  The default value is 8.
+   */
+  numBits?: number;
 }
 
 export class Index {
@@ -149,6 +159,7 @@ export class Index {
         options?.numSubVectors,
         options?.maxIterations,
         options?.sampleRate,
+        options?.numBits,
       ),
     );
   }
@@ -0,0 +1,84 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+import { NativeMergeInsertBuilder } from "./native";
+import { Data, fromDataToBuffer } from "./arrow";
+
+/** A builder used to create and run a merge insert operation */
+export class MergeInsertBuilder {
+  /** Construct a MergeInsertBuilder. Internal use only. */
+  constructor(private readonly inner: NativeMergeInsertBuilder) {}
+
+  /**
+   * Rows that exist in both the source table (new data) and
+   * the target table (old data) will be updated, replacing
+   * the old row with the corresponding matching row.
+   *
+   * If there are multiple matches then the behavior is undefined.
+   * Currently this causes multiple copies of the row to be created
+   * but that behavior is subject to change.
+   *
+   * An optional condition may be specified.  If it is, then only
+   * matched rows that satisfy the condtion will be updated.  Any
+   * rows that do not satisfy the condition will be left as they
+   * are.  Failing to satisfy the condition does not cause a
+   * "matched row" to become a "not matched" row.
+   *
+   * The condition should be an SQL string.  Use the prefix
+   * target. to refer to rows in the target table (old data)
+   * and the prefix source. to refer to rows in the source
+   * table (new data).
+   *
+   * For example, "target.last_update < source.last_update"
+   */
+  whenMatchedUpdateAll(options?: { where?: string }): MergeInsertBuilder {
+    this.inner.whenMatchedUpdateAll(options?.where);
+    return this;
+  }
+
+  /**
+   * Rows that exist only in the source table (new data) should
+   * be inserted into the target table.
+   */
+  whenNotMatchedInsertAll(): MergeInsertBuilder {
+    this.inner.whenNotMatchedInsertAll();
+    return this;
+  }
+
+  /**
+   * Rows that exist only in the target table (old data) will be
+   * deleted.  An optional condition can be provided to limit what
+   * data is deleted.
+   * @param {string} options.where - If not provided then all such rows
+   * will be deleted.  Otherwise the condition will be used as an SQL
+   * filter to limit what rows are deleted.
+   */
+  whenNotMatchedBySourceDelete(options?: {
+    where?: string;
+  }): MergeInsertBuilder {
+    this.inner.whenNotMatchedBySourceDelete(options?.where);
+    return this;
+  }
+
+  /**
+   * Executes the merge insert operation
+   *
+   * Nothing is returned but the table is updated.
+   * @param {Data} data The new data (the source table)
+   */
+  async execute(data: Data): Promise<void> {
+    const buffer = await fromDataToBuffer(data);
+    await this.inner.execute(buffer);
+  }
+}
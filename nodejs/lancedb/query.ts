@@ -20,7 +20,9 @@ import {
   VectorQuery as NativeVectorQuery,
 } from "./native";
 import { type IvfPqOptions } from "./indices";
-export class RecordBatchIterator implements AsyncIterator<RecordBatch> {
+export class RecordBatchIterator
+  implements AsyncIterator<RecordBatch>, AsyncIterable<RecordBatch>
+{
   private promisedInner?: Promise<NativeBatchIterator>;
   private inner?: NativeBatchIterator;
 
@@ -47,6 +49,10 @@ export class RecordBatchIterator implements AsyncIterator<RecordBatch> {
     }
     return Promise.resolve({ done: false, value: tbl.batches[0] });
   }
+
+  [Symbol.asyncIterator](): AsyncIterator<RecordBatch> {
+    return this;
+  }
 }
 /* eslint-enable */
 
@@ -75,6 +81,15 @@ export class QueryBase<
     return this as unknown as QueryType;
   }
 
+  /**
+   * A filter statement to be applied to this query.
+   *
+   * This is an alias for {@link QueryBase#where}
+   */
+  filter(predicate: string): QueryType {
+    return this.where(predicate);
+  }
+
   /**
    * Return only the specified columns.
    *
@@ -145,8 +160,14 @@ export class QueryBase<
    * stream is consumed slowly (this constrains the maximum memory used by a
    * single query)
    *
+   * Each batch is only read when it is requested, the query does not collect
+   * the results in memory.
+   * @example
+   * for await (const batch of table.query().limit(1000).execute()) {
+   *   console.log(batch.numRows);
+   * }
    */
-  protected execute(): RecordBatchIterator {
+  execute(): RecordBatchIterator {
     return new RecordBatchIterator(this.nativeExecute());
   }
 
@@ -22,6 +22,7 @@ import {
 import { Query, VectorQuery } from "./query";
 import { IndexOptions } from "./indices";
 import { Data, fromDataToBuffer } from "./arrow";
+import { MergeInsertBuilder } from "./merge";
 
 export { IndexConfig } from "./native";
 /**
@@ -50,6 +51,16 @@ export interface UpdateOptions {
   where: string;
 }
 
+/**
+ * An update, the alternative form of the arguments of {@link Table#update}
+ */
+export interface UpdateSpec extends Partial<UpdateOptions> {
+  /**
+   * The columns to update and their new values, as SQL expressions
+   */
+  values: Map<string, string> | Record<string, string>;
+}
+
 /**
  * A Table is a collection of Records in a LanceDB Database.
  *
@@ -133,21 +144,60 @@ export class Table {
    * based on the row being updated (e.g. "my_col + 1")
    * @param {Partial<UpdateOptions>} options - additional options to control
    * the update behavior
+   *
+   * The updates and the options can also be passed as a single object, e.g.
+   * `table.update({ where: "id == 7", values: { price: "price * 2" } })`
    */
   async update(
     updates: Map<string, string> | Record<string, string>,
     options?: Partial<UpdateOptions>,
-  ) {
-    const onlyIf = options?.where;
+  ): Promise<void>;
+  async update(spec: UpdateSpec): Promise<void>;
+  async update(
+    updates: Map<string, string> | Record<string, string> | UpdateSpec,
+    options?: Partial<UpdateOptions>,
+  ): Promise<void> {
+    let onlyIf = options?.where;
+    // The values of a column update are strings, so an object with an
+    // object as `values` is an UpdateSpec
+    if (
+      !(updates instanceof Map) &&
+      typeof updates.values === "object" &&
+      updates.values !== null
+    ) {
+      const spec = updates as UpdateSpec;
+      onlyIf = spec.where;
+      updates = spec.values;
+    }
     let columns: [string, string][];
     if (updates instanceof Map) {
       columns = Array.from(updates.entries());
     } else {
-      columns = Object.entries(updates);
+      columns = Object.entries(updates as Record<string, string>);
     }
     await this.inner.update(onlyIf, columns);
   }
 
+  /**
+   * Create a builder for a merge insert operation
+   *
+   * A merge insert ("upsert") combines new data with the data in the table
+   * by matching rows on the `on` columns.  Use the builder to choose what
+   * happens to matched rows, new rows and rows missing from the new data.
+   * @example
+   * // Update the rows with an existing id and insert the others
+   * await table
+   *   .mergeInsert("id")
+   *   .whenMatchedUpdateAll()
+   *   .whenNotMatchedInsertAll()
+   *   .execute([{ id: 1, name: "a" }, { id: 7, name: "b" }]);
+   * @param {string | string[]} on The columns to match rows on
+   */
+  mergeInsert(on: string | string[]): MergeInsertBuilder {
+    const columns = Array.isArray(on) ? on : [on];
+    return new MergeInsertBuilder(this.inner.mergeInsert(columns));
+  }
+
   /** Count the total number of rows in the dataset. */
   async countRows(filter?: string): Promise<number> {
     return await this.inner.countRows(filter);
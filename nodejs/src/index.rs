@@ -47,6 +47,7 @@ impl Index {
         num_sub_vectors: Option<u32>,
         max_iterations: Option<u32>,
         sample_rate: Option<u32>,
+        num_bits: Option<u32>,
     ) -> napi::Result<Self> {
         let mut ivf_pq_builder = IvfPqIndexBuilder::default();
         if let Some(distance_type) = distance_type {
@@ -65,6 +66,9 @@ impl Index {
         if let Some(sample_rate) = sample_rate {
             ivf_pq_builder = ivf_pq_builder.sample_rate(sample_rate);
         }
+        if let Some(num_bits) = num_bits {
+            ivf_pq_builder = ivf_pq_builder.num_bits(num_bits);
+        }
         Ok(Self {
             inner: Mutex::new(Some(LanceDbIndex::IvfPq(ivf_pq_builder))),
         })
@@ -19,6 +19,7 @@ mod connection;
 mod error;
 mod index;
 mod iterator;
+mod merge;
 mod query;
 mod table;
 mod util;
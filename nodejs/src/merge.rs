@@ -0,0 +1,81 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use lancedb::ipc::ipc_file_to_batches;
+use lancedb::table::Table as LanceDbTable;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::error::NapiErrorExt;
+
+/// The options of a merge insert
+///
+/// The Rust builder can only be executed once, so the options are collected
+/// here and the builder is created when the operation runs.
+#[napi]
+pub struct NativeMergeInsertBuilder {
+    table: LanceDbTable,
+    on: Vec<String>,
+    when_matched_update_all: Option<Option<String>>,
+    when_not_matched_insert_all: bool,
+    when_not_matched_by_source_delete: Option<Option<String>>,
+}
+
+impl NativeMergeInsertBuilder {
+    pub(crate) fn new(table: LanceDbTable, on: Vec<String>) -> Self {
+        Self {
+            table,
+            on,
+            when_matched_update_all: None,
+            when_not_matched_insert_all: false,
+            when_not_matched_by_source_delete: None,
+        }
+    }
+}
+
+#[napi]
+impl NativeMergeInsertBuilder {
+    #[napi]
+    pub fn when_matched_update_all(&mut self, condition: Option<String>) {
+        self.when_matched_update_all = Some(condition);
+    }
+
+    #[napi]
+    pub fn when_not_matched_insert_all(&mut self) {
+        self.when_not_matched_insert_all = true;
+    }
+
+    #[napi]
+    pub fn when_not_matched_by_source_delete(&mut self, filter: Option<String>) {
+        self.when_not_matched_by_source_delete = Some(filter);
+    }
+
+    #[napi]
+    pub async fn execute(&self, buf: Buffer) -> napi::Result<()> {
+        let batches = ipc_file_to_batches(buf.to_vec())
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read IPC file: {}", e)))?;
+        let on = self.on.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut builder = self.table.merge_insert(&on);
+        if let Some(condition) = &self.when_matched_update_all {
+            builder.when_matched_update_all(condition.clone());
+        }
+        if self.when_not_matched_insert_all {
+            builder.when_not_matched_insert_all();
+        }
+        if let Some(filter) = &self.when_not_matched_by_source_delete {
+            builder.when_not_matched_by_source_delete(filter.clone());
+        }
+        builder.execute(Box::new(batches)).await.default_error()
+    }
+}
@@ -10,6 +10,7 @@ class Index:
         num_sub_vectors: Optional[int],
         max_iterations: Optional[int],
         sample_rate: Optional[int],
+        num_bits: Optional[int],
     ) -> Index: ...
     @staticmethod
     def btree() -> Index: ...
@@ -40,6 +41,12 @@ class Table:
     async def checkout_latest(self): ...
     async def restore(self): ...
     async def list_indices(self) -> List[IndexConfig]: ...
+    async def index_stats(self, column: str) -> IndexStatistics: ...
+    async def optimize(
+        self,
+        cleanup_older_than_secs: Optional[int],
+        delete_unverified: Optional[bool],
+    ) -> OptimizeStats: ...
     def query(self) -> Query: ...
     def vector_search(self) -> VectorQuery: ...
 
@@ -47,6 +54,28 @@ class IndexConfig:
     index_type: str
     columns: List[str]
 
+class IndexStatistics:
+    name: str
+    index_type: str
+    columns: List[str]
+    num_indexed_rows: Optional[int]
+    num_unindexed_rows: Optional[int]
+    table_version: int
+
+class CompactionStats:
+    fragments_removed: int
+    fragments_added: int
+    files_removed: int
+    files_added: int
+
+class RemovalStats:
+    bytes_removed: int
+    old_versions_removed: int
+
+class OptimizeStats:
+    compaction: CompactionStats
+    prune: RemovalStats
+
 async def connect(
     uri: str,
     api_key: Optional[str],
@@ -65,9 +94,11 @@ class Query:
     def limit(self, limit: int): ...
     def nearest_to(self, query_vec: pa.Array) -> VectorQuery: ...
     async def execute(self) -> RecordBatchStream: ...
+    async def execute_into_reader(self) -> pa.RecordBatchReader: ...
 
 class VectorQuery:
     async def execute(self) -> RecordBatchStream: ...
+    async def execute_into_reader(self) -> pa.RecordBatchReader: ...
     def where(self, filter: str): ...
     def select(self, columns: List[str]): ...
     def select_with_projection(self, columns: Tuple[str, str]): ...
@@ -64,6 +64,7 @@ class IvfPq(object):
         num_sub_vectors: Optional[int] = None,
         max_iterations: Optional[int] = None,
         sample_rate: Optional[int] = None,
+        num_bits: Optional[int] = None,
     ):
         """
         Create an IVF PQ index config
@@ -150,6 +151,13 @@ class IvfPq(object):
             cases the default should be sufficient.
 
             The default value is 256.
+        num_bits: int, default 8
+            The number of bits used to encode each sub-vector.
+
+            Each sub-vector is replaced by the id of the closest of `2^num_bits`
+            centroids.  Fewer bits make the index smaller but less accurate.
+
+            The value must be between 1 and 8.
         """
         self._inner = LanceDbIndex.ivf_pq(
             distance_type=distance_type,
@@ -157,6 +165,7 @@ class IvfPq(object):
             num_sub_vectors=num_sub_vectors,
             max_iterations=max_iterations,
             sample_rate=sample_rate,
+            num_bits=num_bits,
         )
 
 
@@ -1110,6 +1110,18 @@ class AsyncQueryBase(object):
         """
         return AsyncRecordBatchReader(await self._inner.execute())
 
+    async def to_reader(self) -> pa.RecordBatchReader:
+        """
+        Execute the query and return the results as a pyarrow RecordBatchReader.
+
+        The results are passed through the Arrow C stream interface, so the
+        reader can be handed to any library that accepts a pyarrow reader.
+        The query keeps running in the background and reading the next batch
+        blocks until it is available, so the reader should not be read from
+        the thread running the event loop (e.g. use `loop.run_in_executor`).
+        """
+        return await self._inner.execute_into_reader()
+
     async def to_arrow(self) -> pa.Table:
         """
         Execute the query and collect the results into an Apache Arrow Table.
@@ -59,6 +59,7 @@ if TYPE_CHECKING:
     import PIL
     from lance.dataset import CleanupStats, ReaderLike
 
+    from ._lancedb import IndexStatistics, OptimizeStats
     from ._lancedb import Table as LanceDBTable
     from .db import LanceDBConnection
     from .index import BTree, IndexConfig, IvfPq
@@ -2367,3 +2368,61 @@ class AsyncTable:
         List all indices that have been created with Self::create_index
         """
         return await self._inner.list_indices()
+
+    async def index_stats(self, column: str) -> IndexStatistics:
+        """
+        Retrieve the statistics of the index on the given column
+
+        Parameters
+        ----------
+        column: str
+            The column the index was created on
+
+        Returns
+        -------
+        IndexStatistics
+            The name and type of the index and, if the index reports them, the
+            number of rows that are covered by the index
+            (``num_indexed_rows``) and the number of rows that were added
+            since the index was last optimized (``num_unindexed_rows``).
+        """
+        return await self._inner.index_stats(column)
+
+    async def optimize(
+        self,
+        *,
+        cleanup_older_than: Optional[timedelta] = None,
+        delete_unverified: bool = False,
+    ) -> OptimizeStats:
+        """
+        Optimize the on-disk data and indices for better performance.
+
+        Optimization covers three operations:
+
+        * Compaction: Merges small files into larger ones
+        * Prune: Removes old versions of the dataset
+        * Index: Optimizes the indices, adding new data to existing indices
+
+        It is safe to optimize while queries are running.
+
+        Parameters
+        ----------
+        cleanup_older_than: timedelta, optional, default 7 days
+            All versions older than this will be removed.  Set this to 0 to
+            remove all versions except the latest.
+        delete_unverified: bool, default False
+            Because they may be part of an in-progress transaction, files newer
+            than 7 days old are not deleted by default. If you are sure that
+            there are no in-progress transactions, then you can set this to True
+            to delete all files older than `cleanup_older_than`.
+
+        Returns
+        -------
+        OptimizeStats
+            The number of fragments and files removed and added by the
+            compaction and the number of versions and bytes removed by the
+            prune.
+        """
+        if cleanup_older_than is not None:
+            cleanup_older_than = round(cleanup_older_than.total_seconds())
+        return await self._inner.optimize(cleanup_older_than, delete_unverified)
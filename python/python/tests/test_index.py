@@ -67,3 +67,22 @@ async def test_create_vector_index(some_table: AsyncTable):
     assert len(indices) == 1
     assert indices[0].index_type == "IvfPq"
     assert indices[0].columns == ["vector"]
+
+
+@pytest.mark.asyncio
+async def test_index_stats(some_table: AsyncTable):
+    await some_table.create_index("vector", config=IvfPq(num_partitions=2))
+    await some_table.add(
+        pa.Table.from_pydict(
+            {"id": [256], "vector": sample_fixed_size_list_array(1, DIM)}
+        )
+    )
+    stats = await some_table.index_stats("vector")
+    assert stats.columns == ["vector"]
+    assert stats.num_indexed_rows == NROWS
+    assert stats.num_unindexed_rows == 1
+
+    await some_table.optimize()
+    stats = await some_table.index_stats("vector")
+    assert stats.num_indexed_rows == NROWS + 1
+    assert stats.num_unindexed_rows == 0
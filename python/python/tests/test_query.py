@@ -11,6 +11,7 @@
 #  See the License for the specific language governing permissions and
 #  limitations under the License.
 
+import asyncio
 import unittest.mock as mock
 from datetime import timedelta
 from typing import Optional
@@ -333,3 +334,19 @@ async def test_query_to_pandas_async(table_async: AsyncTable):
 
     df = await table_async.query().where("id < 0").to_pandas()
     assert df.shape == (0, 4)
+
+
+@pytest.mark.asyncio
+async def test_query_to_reader_async(table_async: AsyncTable):
+    # The reader blocks, so it is read from another thread
+    loop = asyncio.get_running_loop()
+    reader = await table_async.query().where("id > 1").select(["id"]).to_reader()
+    assert isinstance(reader, pa.RecordBatchReader)
+    assert reader.schema.names == ["id"]
+    table = await loop.run_in_executor(None, reader.read_all)
+    assert table.num_rows == 1
+
+    reader = await table_async.vector_search([1, 2]).nprobes(10).limit(1).to_reader()
+    table = await loop.run_in_executor(None, reader.read_all)
+    assert table.num_rows == 1
+    assert "_distance" in table.column_names
@@ -1025,3 +1025,17 @@ async def test_time_travel(db_async: AsyncConnection):
     # Can't use restore if not checked out
     with pytest.raises(ValueError, match="checkout before running restore"):
         await table.restore()
+
+
+@pytest.mark.asyncio
+async def test_optimize(db_async: AsyncConnection):
+    table = await db_async.create_table("some_table", data=[{"id": 0}])
+    await table.add([{"id": 1}])
+    await table.add([{"id": 2}])
+    stats = await table.optimize(
+        cleanup_older_than=timedelta(0), delete_unverified=True
+    )
+    assert stats.compaction.fragments_removed == 3
+    assert stats.compaction.fragments_added == 1
+    assert stats.prune.old_versions_removed > 0
+    assert await table.count_rows() == 3
@@ -13,7 +13,10 @@
 // limitations under the License.
 
 use pyo3::{
-    exceptions::{PyIOError, PyNotImplementedError, PyOSError, PyRuntimeError, PyValueError},
+    exceptions::{
+        PyFileNotFoundError, PyIOError, PyMemoryError, PyNotImplementedError, PyOSError,
+        PyPermissionError, PyRuntimeError, PyValueError,
+    },
     PyResult,
 };
 
@@ -43,6 +46,18 @@ impl<T> PythonErrorExt<T> for std::result::Result<T, LanceError> {
                 LanceError::TableAlreadyExists { .. } => self.runtime_error(),
                 LanceError::ObjectStore { .. } => Err(PyIOError::new_err(err.to_string())),
                 LanceError::Lance { .. } => self.runtime_error(),
+                LanceError::Overloaded { .. } => self.runtime_error(),
+                LanceError::MemoryLimitExceeded { .. } => {
+                    Err(PyMemoryError::new_err(err.to_string()))
+                }
+                LanceError::CommitConflict { .. } => self.runtime_error(),
+                LanceError::NotFound { .. } => Err(PyFileNotFoundError::new_err(err.to_string())),
+                LanceError::InvalidFilter { .. } => self.value_error(),
+                LanceError::PermissionDenied { .. } => {
+                    Err(PyPermissionError::new_err(err.to_string()))
+                }
+                LanceError::IndexNotFound { .. } => self.value_error(),
+                LanceError::Io { .. } => Err(PyIOError::new_err(err.to_string())),
                 LanceError::Runtime { .. } => self.runtime_error(),
                 LanceError::Http { .. } => self.runtime_error(),
                 LanceError::Arrow { .. } => self.runtime_error(),
@@ -47,6 +47,7 @@ impl Index {
         num_sub_vectors: Option<u32>,
         max_iterations: Option<u32>,
         sample_rate: Option<u32>,
+        num_bits: Option<u32>,
     ) -> PyResult<Self> {
         let mut ivf_pq_builder = IvfPqIndexBuilder::default();
         if let Some(distance_type) = distance_type {
@@ -73,6 +74,9 @@ impl Index {
         if let Some(sample_rate) = sample_rate {
             ivf_pq_builder = ivf_pq_builder.sample_rate(sample_rate);
         }
+        if let Some(num_bits) = num_bits {
+            ivf_pq_builder = ivf_pq_builder.num_bits(num_bits);
+        }
         Ok(Self {
             inner: Mutex::new(Some(LanceDbIndex::IvfPq(ivf_pq_builder))),
         })
@@ -98,6 +102,40 @@ pub struct IndexConfig {
     pub columns: Vec<String>,
 }
 
+#[pyclass(get_all)]
+/// Statistics about an index, as reported by the index
+pub struct IndexStatistics {
+    pub name: String,
+    /// The type of the index
+    pub index_type: String,
+    pub columns: Vec<String>,
+    /// The number of rows covered by the index
+    ///
+    /// This is None if the index does not report it
+    pub num_indexed_rows: Option<u64>,
+    /// The number of rows added since the index was last optimized
+    ///
+    /// This is None if the index does not report it
+    pub num_unindexed_rows: Option<u64>,
+    /// The version of the table the statistics were computed at
+    pub table_version: u64,
+}
+
+impl From<lancedb::index::metadata::IndexMetadata> for IndexStatistics {
+    fn from(value: lancedb::index::metadata::IndexMetadata) -> Self {
+        let num_indexed_rows = value.statistics.get("num_indexed_rows");
+        let num_unindexed_rows = value.statistics.get("num_unindexed_rows");
+        Self {
+            num_indexed_rows: num_indexed_rows.and_then(|v| v.as_u64()),
+            num_unindexed_rows: num_unindexed_rows.and_then(|v| v.as_u64()),
+            name: value.name,
+            index_type: value.index_type,
+            columns: value.columns,
+            table_version: value.table_version,
+        }
+    }
+}
+
 impl From<lancedb::index::IndexConfig> for IndexConfig {
     fn from(value: lancedb::index::IndexConfig) -> Self {
         let index_type = format!("{:?}", value.index_type);
@@ -15,10 +15,10 @@
 use arrow::RecordBatchStream;
 use connection::{connect, Connection};
 use env_logger::Env;
-use index::{Index, IndexConfig};
+use index::{Index, IndexConfig, IndexStatistics};
 use pyo3::{pymodule, types::PyModule, wrap_pyfunction, PyResult, Python};
 use query::{Query, VectorQuery};
-use table::Table;
+use table::{CompactionStats, OptimizeStats, RemovalStats, Table};
 
 pub mod arrow;
 pub mod connection;
@@ -38,6 +38,10 @@ pub fn _lancedb(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Table>()?;
     m.add_class::<Index>()?;
     m.add_class::<IndexConfig>()?;
+    m.add_class::<IndexStatistics>()?;
+    m.add_class::<OptimizeStats>()?;
+    m.add_class::<CompactionStats>()?;
+    m.add_class::<RemovalStats>()?;
     m.add_class::<Query>()?;
     m.add_class::<VectorQuery>()?;
     m.add_class::<RecordBatchStream>()?;
@@ -14,7 +14,7 @@
 
 use arrow::array::make_array;
 use arrow::array::ArrayData;
-use arrow::pyarrow::FromPyArrow;
+use arrow::pyarrow::{FromPyArrow, IntoPyArrow};
 use lancedb::query::{
     ExecutableQuery, Query as LanceDbQuery, QueryBase, Select, VectorQuery as LanceDbVectorQuery,
 };
@@ -23,6 +23,7 @@ use pyo3::pymethods;
 use pyo3::PyAny;
 use pyo3::PyRef;
 use pyo3::PyResult;
+use pyo3::Python;
 use pyo3_asyncio::tokio::future_into_py;
 
 use crate::arrow::RecordBatchStream;
@@ -68,6 +69,18 @@ impl Query {
             Ok(RecordBatchStream::new(inner_stream))
         })
     }
+
+    /// Execute the query and return a `pyarrow.RecordBatchReader`
+    ///
+    /// The results are exported through the Arrow C stream interface.  Reading
+    /// the reader blocks while the next batch is computed.
+    pub fn execute_into_reader(self_: PyRef<'_, Self>) -> PyResult<&PyAny> {
+        let inner = self_.inner.clone();
+        future_into_py(self_.py(), async move {
+            let reader = inner.execute_into_reader().await.infer_error()?;
+            Python::with_gil(|py| reader.into_pyarrow(py))
+        })
+    }
 }
 
 #[pyclass]
@@ -122,4 +135,16 @@ impl VectorQuery {
             Ok(RecordBatchStream::new(inner_stream))
         })
     }
+
+    /// Execute the query and return a `pyarrow.RecordBatchReader`
+    ///
+    /// The results are exported through the Arrow C stream interface.  Reading
+    /// the reader blocks while the next batch is computed.
+    pub fn execute_into_reader(self_: PyRef<'_, Self>) -> PyResult<&PyAny> {
+        let inner = self_.inner.clone();
+        future_into_py(self_.py(), async move {
+            let reader = inner.execute_into_reader().await.infer_error()?;
+            Python::with_gil(|py| reader.into_pyarrow(py))
+        })
+    }
 }
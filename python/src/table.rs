@@ -2,7 +2,10 @@ use arrow::{
     ffi_stream::ArrowArrayStreamReader,
     pyarrow::{FromPyArrow, ToPyArrow},
 };
-use lancedb::table::{AddDataMode, Table as LanceDbTable};
+use chrono::Duration;
+use lance::dataset::optimize::CompactionOptions;
+use lance_index::optimize::OptimizeOptions;
+use lancedb::table::{AddDataMode, OptimizeAction, Table as LanceDbTable};
 use pyo3::{
     exceptions::{PyRuntimeError, PyValueError},
     pyclass, pymethods,
@@ -13,10 +16,35 @@ use pyo3_asyncio::tokio::future_into_py;
 
 use crate::{
     error::PythonErrorExt,
-    index::{Index, IndexConfig},
+    index::{Index, IndexConfig, IndexStatistics},
     query::Query,
 };
 
+/// Statistics about an [`Table::optimize`] operation
+#[pyclass(get_all)]
+pub struct OptimizeStats {
+    /// Statistics about the compaction of the data files
+    pub compaction: CompactionStats,
+    /// Statistics about the removal of old versions
+    pub prune: RemovalStats,
+}
+
+#[pyclass(get_all)]
+#[derive(Clone)]
+pub struct CompactionStats {
+    pub fragments_removed: u64,
+    pub fragments_added: u64,
+    pub files_removed: u64,
+    pub files_added: u64,
+}
+
+#[pyclass(get_all)]
+#[derive(Clone)]
+pub struct RemovalStats {
+    pub bytes_removed: u64,
+    pub old_versions_removed: u64,
+}
+
 #[pyclass]
 pub struct Table {
     // We keep a copy of the name to use if the inner table is dropped
@@ -188,7 +216,69 @@ impl Table {
         )
     }
 
-    pub fn query(&self) -> Query {
-        Query::new(self.inner_ref().unwrap().query())
+    /// Compact the data files, remove old versions and optimize the indices
+    ///
+    /// Versions older than `cleanup_older_than_secs` are removed, or versions
+    /// older than 7 days if it is not set.
+    pub fn optimize(
+        self_: PyRef<'_, Self>,
+        cleanup_older_than_secs: Option<i64>,
+        delete_unverified: Option<bool>,
+    ) -> PyResult<&PyAny> {
+        let inner = self_.inner_ref()?.clone();
+        let older_than = match cleanup_older_than_secs {
+            Some(secs) => Duration::try_seconds(secs).ok_or_else(|| {
+                PyValueError::new_err(format!("Invalid cleanup_older_than: {}s", secs))
+            })?,
+            None => Duration::try_days(7).unwrap(),
+        };
+        future_into_py(self_.py(), async move {
+            let compaction = inner
+                .optimize(OptimizeAction::Compact {
+                    options: CompactionOptions::default(),
+                    remap_options: None,
+                })
+                .await
+                .infer_error()?
+                .compaction
+                .unwrap_or_default();
+            let prune = inner
+                .optimize(OptimizeAction::Prune {
+                    older_than,
+                    delete_unverified,
+                })
+                .await
+                .infer_error()?
+                .prune
+                .unwrap_or_default();
+            inner
+                .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+                .await
+                .infer_error()?;
+            Ok(OptimizeStats {
+                compaction: CompactionStats {
+                    fragments_removed: compaction.fragments_removed as u64,
+                    fragments_added: compaction.fragments_added as u64,
+                    files_removed: compaction.files_removed as u64,
+                    files_added: compaction.files_added as u64,
+                },
+                prune: RemovalStats {
+                    bytes_removed: prune.bytes_removed,
+                    old_versions_removed: prune.old_versions,
+                },
+            })
+        })
+    }
+
+    pub fn index_stats(self_: PyRef<'_, Self>, column: String) -> PyResult<&PyAny> {
+        let inner = self_.inner_ref()?.clone();
+        future_into_py(self_.py(), async move {
+            let metadata = inner.index_metadata(&column).await.infer_error()?;
+            Ok(IndexStatistics::from(metadata))
+        })
+    }
+
+    pub fn query(&self) -> PyResult<Query> {
+        Ok(Query::new(self.inner_ref()?.query()))
     }
 }
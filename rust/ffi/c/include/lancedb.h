@@ -0,0 +1,151 @@
+/*
+ * Copyright 2024 Lance Developers.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/*
+ * The C API of LanceDB
+ *
+ * Every function returns a status.  If it is not LANCEDB_STATUS_OK the
+ * message of the error can be read with lancedb_last_error.  Handles must be
+ * released with their _free function.  Calls block until the operation is
+ * complete.
+ *
+ * Data is exchanged with the Arrow C data and C stream interfaces.
+ *
+ * The functions are defined in the lancedb_ffi library (liblancedb_ffi.so,
+ * liblancedb_ffi.dylib, lancedb_ffi.dll, or liblancedb_ffi.a to link
+ * statically), link with -llancedb_ffi.
+ */
+
+#ifndef LANCEDB_H
+#define LANCEDB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#ifndef ARROW_C_DATA_INTERFACE
+#define ARROW_C_DATA_INTERFACE
+
+#define ARROW_FLAG_DICTIONARY_ORDERED 1
+#define ARROW_FLAG_NULLABLE 2
+#define ARROW_FLAG_MAP_KEYS_SORTED 4
+
+struct ArrowSchema {
+  const char* format;
+  const char* name;
+  const char* metadata;
+  int64_t flags;
+  int64_t n_children;
+  struct ArrowSchema** children;
+  struct ArrowSchema* dictionary;
+  void (*release)(struct ArrowSchema*);
+  void* private_data;
+};
+
+struct ArrowArray {
+  int64_t length;
+  int64_t null_count;
+  int64_t offset;
+  int64_t n_buffers;
+  int64_t n_children;
+  const void** buffers;
+  struct ArrowArray** children;
+  struct ArrowArray* dictionary;
+  void (*release)(struct ArrowArray*);
+  void* private_data;
+};
+
+#endif /* ARROW_C_DATA_INTERFACE */
+
+#ifndef ARROW_C_STREAM_INTERFACE
+#define ARROW_C_STREAM_INTERFACE
+
+struct ArrowArrayStream {
+  int (*get_schema)(struct ArrowArrayStream*, struct ArrowSchema* out);
+  int (*get_next)(struct ArrowArrayStream*, struct ArrowArray* out);
+  const char* (*get_last_error)(struct ArrowArrayStream*);
+  void (*release)(struct ArrowArrayStream*);
+  void* private_data;
+};
+
+#endif /* ARROW_C_STREAM_INTERFACE */
+
+typedef enum LanceDbStatus {
+  LANCEDB_STATUS_OK = 0,
+  LANCEDB_STATUS_INVALID_ARGUMENT = 1,
+  LANCEDB_STATUS_NOT_FOUND = 2,
+  LANCEDB_STATUS_ALREADY_EXISTS = 3,
+  LANCEDB_STATUS_NOT_SUPPORTED = 4,
+  LANCEDB_STATUS_ERROR = 5,
+  LANCEDB_STATUS_PANIC = 6,
+} LanceDbStatus;
+
+typedef struct LanceDbConnection LanceDbConnection;
+typedef struct LanceDbTable LanceDbTable;
+typedef struct LanceDbQuery LanceDbQuery;
+
+/* The message of the last failed call on this thread, or NULL */
+const char* lancedb_last_error(void);
+
+void lancedb_strings_free(char** strings, size_t len);
+
+/* Connections */
+
+LanceDbStatus lancedb_connect(const char* uri, LanceDbConnection** out);
+void lancedb_connection_free(LanceDbConnection* conn);
+LanceDbStatus lancedb_connection_table_names(const LanceDbConnection* conn, char*** out,
+                                             size_t* out_len);
+LanceDbStatus lancedb_connection_open_table(const LanceDbConnection* conn, const char* name,
+                                            LanceDbTable** out);
+/* The stream is consumed, even if the call fails */
+LanceDbStatus lancedb_connection_create_table(const LanceDbConnection* conn, const char* name,
+                                              struct ArrowArrayStream* data, LanceDbTable** out);
+LanceDbStatus lancedb_connection_drop_table(const LanceDbConnection* conn, const char* name);
+
+/* Tables */
+
+void lancedb_table_free(LanceDbTable* table);
+LanceDbStatus lancedb_table_version(const LanceDbTable* table, uint64_t* out);
+LanceDbStatus lancedb_table_schema(const LanceDbTable* table, struct ArrowSchema* out);
+/* filter may be NULL to count all rows */
+LanceDbStatus lancedb_table_count_rows(const LanceDbTable* table, const char* filter,
+                                       uint64_t* out);
+/* The stream is consumed, even if the call fails */
+LanceDbStatus lancedb_table_add(const LanceDbTable* table, struct ArrowArrayStream* data);
+LanceDbStatus lancedb_table_delete(const LanceDbTable* table, const char* predicate);
+LanceDbStatus lancedb_table_create_index(const LanceDbTable* table, const char* column);
+LanceDbStatus lancedb_table_query(const LanceDbTable* table, LanceDbQuery** out);
+
+/* Queries */
+
+void lancedb_query_free(LanceDbQuery* query);
+LanceDbStatus lancedb_query_limit(LanceDbQuery* query, size_t limit);
+LanceDbStatus lancedb_query_only_if(LanceDbQuery* query, const char* filter);
+LanceDbStatus lancedb_query_select(LanceDbQuery* query, const char* const* columns, size_t len);
+LanceDbStatus lancedb_query_nearest_to(LanceDbQuery* query, const float* vector, size_t dim);
+/* Only valid after lancedb_query_nearest_to */
+LanceDbStatus lancedb_query_column(LanceDbQuery* query, const char* column);
+LanceDbStatus lancedb_query_nprobes(LanceDbQuery* query, size_t nprobes);
+LanceDbStatus lancedb_query_execute(const LanceDbQuery* query, struct ArrowArrayStream* out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LANCEDB_H */
@@ -0,0 +1,151 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::c_char;
+
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use lancedb::connection::Connection;
+
+use crate::table::LanceDbTable;
+use crate::{
+    ffi_call, handle, handle_mut, runtime, str_arg, to_c_string, write_out, LanceDbStatus,
+};
+
+/// A connection to a database, see [`lancedb::Connection`]
+pub struct LanceDbConnection {
+    inner: Connection,
+}
+
+/// Connect to the database at the given URI
+///
+/// # Safety
+///
+/// `uri` must be a nul terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_connect(
+    uri: *const c_char,
+    out: *mut *mut LanceDbConnection,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let uri = str_arg(uri, "uri")?;
+        let inner = runtime()?.block_on(lancedb::connect(uri).execute())?;
+        write_out(
+            out,
+            "out",
+            Box::into_raw(Box::new(LanceDbConnection { inner })),
+        )
+    })
+}
+
+/// Close a connection
+///
+/// Tables opened through the connection stay valid.
+///
+/// # Safety
+///
+/// `conn` must be null or a connection that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_connection_free(conn: *mut LanceDbConnection) {
+    if !conn.is_null() {
+        drop(Box::from_raw(conn));
+    }
+}
+
+/// List the names of the tables in the database
+///
+/// The list must be released with `lancedb_strings_free`.
+///
+/// # Safety
+///
+/// `conn` must be a live connection, `out` and `out_len` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_connection_table_names(
+    conn: *const LanceDbConnection,
+    out: *mut *mut *mut c_char,
+    out_len: *mut usize,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let conn = handle(conn, "conn")?;
+        let names = runtime()?.block_on(conn.inner.table_names().execute())?;
+        let names = names
+            .into_iter()
+            .map(to_c_string)
+            .collect::<lancedb::Result<Box<[_]>>>()?;
+        write_out(out_len, "out_len", names.len())?;
+        write_out(out, "out", Box::into_raw(names) as *mut *mut c_char)
+    })
+}
+
+/// Open an existing table
+///
+/// # Safety
+///
+/// `conn` must be a live connection, `name` a nul terminated string and
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_connection_open_table(
+    conn: *const LanceDbConnection,
+    name: *const c_char,
+    out: *mut *mut LanceDbTable,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let conn = handle(conn, "conn")?;
+        let name = str_arg(name, "name")?;
+        let table = runtime()?.block_on(conn.inner.open_table(name).execute())?;
+        write_out(out, "out", LanceDbTable::new(table))
+    })
+}
+
+/// Create a table with the data of an Arrow stream
+///
+/// The stream is consumed, it is released by the library even if the call
+/// fails.
+///
+/// # Safety
+///
+/// `conn` must be a live connection, `name` a nul terminated string, `data`
+/// a valid Arrow stream and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_connection_create_table(
+    conn: *const LanceDbConnection,
+    name: *const c_char,
+    data: *mut FFI_ArrowArrayStream,
+    out: *mut *mut LanceDbTable,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let data = ArrowArrayStreamReader::from_raw(handle_mut(data, "data")?)?;
+        let conn = handle(conn, "conn")?;
+        let name = str_arg(name, "name")?;
+        let table = runtime()?.block_on(conn.inner.create_table(name, data).execute())?;
+        write_out(out, "out", LanceDbTable::new(table))
+    })
+}
+
+/// Drop a table and delete its data
+///
+/// # Safety
+///
+/// `conn` must be a live connection and `name` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_connection_drop_table(
+    conn: *const LanceDbConnection,
+    name: *const c_char,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let conn = handle(conn, "conn")?;
+        let name = str_arg(name, "name")?;
+        runtime()?.block_on(conn.inner.drop_table(name))
+    })
+}
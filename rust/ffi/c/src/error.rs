@@ -0,0 +1,81 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::ptr;
+
+use lancedb::error::Error;
+
+/// The result of every function of the C API
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanceDbStatus {
+    Ok = 0,
+    /// An argument is null, not valid UTF-8 or otherwise invalid
+    InvalidArgument = 1,
+    /// The table does not exist
+    NotFound = 2,
+    /// The table already exists
+    AlreadyExists = 3,
+    NotSupported = 4,
+    /// Any other error, see [`lancedb_last_error`]
+    Error = 5,
+    /// A bug in LanceDB, the handles passed to the call should not be used
+    /// anymore
+    Panic = 6,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_message(message: String) {
+    // Error messages may contain nul bytes, e.g. from invalid input
+    let message = CString::new(message.replace('\0', "\\0")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+pub(crate) fn set_last_error(err: &Error) -> LanceDbStatus {
+    set_message(err.to_string());
+    match err {
+        Error::InvalidInput { .. }
+        | Error::InvalidTableName { .. }
+        | Error::InvalidFilter { .. }
+        | Error::Schema { .. } => LanceDbStatus::InvalidArgument,
+        Error::TableNotFound { .. } | Error::IndexNotFound { .. } => LanceDbStatus::NotFound,
+        Error::TableAlreadyExists { .. } => LanceDbStatus::AlreadyExists,
+        Error::NotSupported { .. } => LanceDbStatus::NotSupported,
+        _ => LanceDbStatus::Error,
+    }
+}
+
+pub(crate) fn set_panic() -> LanceDbStatus {
+    set_message("LanceDB panicked, this is a bug".to_string());
+    LanceDbStatus::Panic
+}
+
+/// The message of the last error on the calling thread
+///
+/// Returns null if no call failed on this thread yet.  The string is owned by
+/// the library and is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn lancedb_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map(|message| message.as_ptr())
+            .unwrap_or(ptr::null())
+    })
+}
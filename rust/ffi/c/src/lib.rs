@@ -0,0 +1,249 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A C API for LanceDB
+//!
+//! This crate builds a shared and a static library with a stable C ABI that
+//! can be embedded from any language with a C FFI (Go, Java, C++, ...).  The
+//! functions are declared in `include/lancedb.h`.
+//!
+//! Data is exchanged with the [Arrow C data interface]: new data is passed
+//! as an `ArrowArrayStream`, query results are returned as an
+//! `ArrowArrayStream` and schemas as an `ArrowSchema`.
+//!
+//! Every function returns a [`LanceDbStatus`].  If it is not
+//! `LANCEDB_STATUS_OK` the message of the error can be read with
+//! [`lancedb_last_error`].  Connections, tables and queries are opaque
+//! handles that must be released with their `_free` function.  The calls
+//! block the calling thread until the operation is complete.
+//!
+//! [Arrow C data interface]: https://arrow.apache.org/docs/format/CDataInterface.html
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use lancedb::error::{Error, Result};
+use once_cell::sync::OnceCell;
+use tokio::runtime::Runtime;
+
+pub use crate::connection::*;
+pub use crate::error::*;
+pub use crate::query::*;
+pub use crate::table::*;
+
+mod connection;
+mod error;
+mod query;
+mod table;
+
+fn runtime() -> Result<&'static Runtime> {
+    static RUNTIME: OnceCell<Runtime> = OnceCell::new();
+    RUNTIME.get_or_try_init(|| {
+        Runtime::new().map_err(|e| Error::Runtime {
+            message: e.to_string(),
+        })
+    })
+}
+
+/// Run the body of an exported function
+///
+/// Errors and panics are stored as the last error of the thread and turned
+/// into a status, panics must not unwind into the caller.
+fn ffi_call(f: impl FnOnce() -> Result<()>) -> LanceDbStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => LanceDbStatus::Ok,
+        Ok(Err(err)) => set_last_error(&err),
+        Err(_) => set_panic(),
+    }
+}
+
+/// Borrow a handle passed by the caller
+///
+/// # Safety
+///
+/// The pointer must be null or point to a live value of the type.
+unsafe fn handle<'a, T>(ptr: *const T, name: &str) -> Result<&'a T> {
+    ptr.as_ref().ok_or_else(|| Error::InvalidInput {
+        message: format!("{} must not be null", name),
+    })
+}
+
+/// Mutably borrow a handle passed by the caller
+///
+/// # Safety
+///
+/// The pointer must be null or point to a live value of the type.
+unsafe fn handle_mut<'a, T>(ptr: *mut T, name: &str) -> Result<&'a mut T> {
+    ptr.as_mut().ok_or_else(|| Error::InvalidInput {
+        message: format!("{} must not be null", name),
+    })
+}
+
+/// Borrow a nul terminated UTF-8 string passed by the caller
+///
+/// # Safety
+///
+/// The pointer must be null or point to a nul terminated string.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(Error::InvalidInput {
+            message: format!("{} must not be null", name),
+        });
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| Error::InvalidInput {
+            message: format!("{} is not valid UTF-8", name),
+        })
+}
+
+/// Like [`str_arg`] but null is allowed and means None
+///
+/// # Safety
+///
+/// The pointer must be null or point to a nul terminated string.
+unsafe fn opt_str_arg<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        str_arg(ptr, name).map(Some)
+    }
+}
+
+/// Write an output value, which the caller must have allocated
+///
+/// # Safety
+///
+/// The pointer must be null or valid for writes.
+unsafe fn write_out<T>(ptr: *mut T, name: &str, value: T) -> Result<()> {
+    if ptr.is_null() {
+        return Err(Error::InvalidInput {
+            message: format!("{} must not be null", name),
+        });
+    }
+    ptr.write(value);
+    Ok(())
+}
+
+fn to_c_string(value: String) -> Result<*mut c_char> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|_| Error::InvalidInput {
+            message: "the string contains a nul byte".to_string(),
+        })
+}
+
+/// Free a list of strings returned by the library
+///
+/// # Safety
+///
+/// `strings` must have been returned by the library together with `len`.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_strings_free(strings: *mut *mut c_char, len: usize) {
+    if strings.is_null() {
+        return;
+    }
+    let strings = Box::from_raw(std::ptr::slice_from_raw_parts_mut(strings, len));
+    for string in strings.iter().copied() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, RecordBatchReader};
+    use arrow_schema::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn c_string(value: &str) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = c_string(tmp_dir.path().to_str().unwrap());
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut stream = FFI_ArrowArrayStream::new(Box::new(reader));
+
+        unsafe {
+            let mut conn = ptr::null_mut();
+            assert_eq!(lancedb_connect(uri.as_ptr(), &mut conn), LanceDbStatus::Ok);
+            let mut table = ptr::null_mut();
+            let name = c_string("test");
+            assert_eq!(
+                lancedb_connection_create_table(conn, name.as_ptr(), &mut stream, &mut table),
+                LanceDbStatus::Ok
+            );
+
+            let mut names = ptr::null_mut();
+            let mut len = 0;
+            assert_eq!(
+                lancedb_connection_table_names(conn, &mut names, &mut len),
+                LanceDbStatus::Ok
+            );
+            assert_eq!(len, 1);
+            assert_eq!(CStr::from_ptr(*names).to_str().unwrap(), "test");
+            lancedb_strings_free(names, len);
+
+            let mut count = 0;
+            let filter = c_string("id >= 5");
+            assert_eq!(
+                lancedb_table_count_rows(table, filter.as_ptr(), &mut count),
+                LanceDbStatus::Ok
+            );
+            assert_eq!(count, 5);
+
+            let mut query = ptr::null_mut();
+            assert_eq!(lancedb_table_query(table, &mut query), LanceDbStatus::Ok);
+            assert_eq!(lancedb_query_limit(query, 3), LanceDbStatus::Ok);
+            let mut results = FFI_ArrowArrayStream::empty();
+            assert_eq!(
+                lancedb_query_execute(query, &mut results),
+                LanceDbStatus::Ok
+            );
+            lancedb_query_free(query);
+            let reader = ArrowArrayStreamReader::try_new(results).unwrap();
+            assert_eq!(reader.schema().fields().len(), 1);
+            let rows = reader.map(|b| b.unwrap().num_rows()).sum::<usize>();
+            assert_eq!(rows, 3);
+
+            let missing = c_string("missing");
+            let mut other = ptr::null_mut();
+            assert_eq!(
+                lancedb_connection_open_table(conn, missing.as_ptr(), &mut other),
+                LanceDbStatus::NotFound
+            );
+            assert!(!lancedb_last_error().is_null());
+            assert_eq!(
+                lancedb_table_count_rows(ptr::null(), ptr::null(), &mut count),
+                LanceDbStatus::InvalidArgument
+            );
+
+            lancedb_table_free(table);
+            lancedb_connection_free(conn);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::c_char;
+use std::slice;
+
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use lancedb::error::{Error, Result};
+use lancedb::query::{ExecutableQuery, Query, QueryBase, Select, VectorQuery};
+
+use crate::{ffi_call, handle, handle_mut, runtime, str_arg, write_out, LanceDbStatus};
+
+enum QueryKind {
+    Plain(Query),
+    Vector(VectorQuery),
+}
+
+/// A query of a table, see [`lancedb::query::Query`]
+///
+/// A query becomes a vector search when `lancedb_query_nearest_to` is called.
+pub struct LanceDbQuery {
+    inner: Option<QueryKind>,
+}
+
+impl LanceDbQuery {
+    pub(crate) fn new(query: Query) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            inner: Some(QueryKind::Plain(query)),
+        }))
+    }
+
+    fn get(&self) -> Result<&QueryKind> {
+        // The query is only missing if a previous update panicked
+        self.inner.as_ref().ok_or_else(|| Error::Runtime {
+            message: "the query is not usable after a panic".to_string(),
+        })
+    }
+
+    /// Replace the query with a modified one
+    fn update(&mut self, f: impl FnOnce(QueryKind) -> QueryKind) -> Result<()> {
+        self.get()?;
+        self.inner = self.inner.take().map(f);
+        Ok(())
+    }
+
+    /// Apply a method of [`QueryBase`] to either kind of query
+    fn update_base(
+        &mut self,
+        plain: impl FnOnce(Query) -> Query,
+        vector: impl FnOnce(VectorQuery) -> VectorQuery,
+    ) -> Result<()> {
+        self.update(|query| match query {
+            QueryKind::Plain(query) => QueryKind::Plain(plain(query)),
+            QueryKind::Vector(query) => QueryKind::Vector(vector(query)),
+        })
+    }
+
+    /// Apply a method of [`VectorQuery`], which fails for a plain query
+    fn update_vector(
+        &mut self,
+        function: &str,
+        f: impl FnOnce(VectorQuery) -> VectorQuery,
+    ) -> Result<()> {
+        if let QueryKind::Plain(_) = self.get()? {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "{} requires lancedb_query_nearest_to to be called first",
+                    function
+                ),
+            });
+        }
+        self.update(|query| match query {
+            QueryKind::Vector(query) => QueryKind::Vector(f(query)),
+            plain => plain,
+        })
+    }
+}
+
+/// Release a query
+///
+/// # Safety
+///
+/// `query` must be null or a query that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_free(query: *mut LanceDbQuery) {
+    if !query.is_null() {
+        drop(Box::from_raw(query));
+    }
+}
+
+/// Limit the number of results
+///
+/// # Safety
+///
+/// `query` must be a live query.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_limit(
+    query: *mut LanceDbQuery,
+    limit: usize,
+) -> LanceDbStatus {
+    ffi_call(|| handle_mut(query, "query")?.update_base(|q| q.limit(limit), |q| q.limit(limit)))
+}
+
+/// Only return the rows that match an SQL filter
+///
+/// # Safety
+///
+/// `query` must be a live query and `filter` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_only_if(
+    query: *mut LanceDbQuery,
+    filter: *const c_char,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let filter = str_arg(filter, "filter")?;
+        handle_mut(query, "query")?.update_base(|q| q.only_if(filter), |q| q.only_if(filter))
+    })
+}
+
+/// Only return the given columns
+///
+/// # Safety
+///
+/// `query` must be a live query and `columns` must point to `len` nul
+/// terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_select(
+    query: *mut LanceDbQuery,
+    columns: *const *const c_char,
+    len: usize,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        if columns.is_null() && len > 0 {
+            return Err(Error::InvalidInput {
+                message: "columns must not be null".to_string(),
+            });
+        }
+        let columns = if len == 0 {
+            Vec::new()
+        } else {
+            slice::from_raw_parts(columns, len)
+                .iter()
+                .map(|column| str_arg(*column, "column").map(str::to_string))
+                .collect::<Result<Vec<_>>>()?
+        };
+        let select = Select::Columns(columns);
+        handle_mut(query, "query")?.update_base(|q| q.select(select.clone()), |q| q.select(select))
+    })
+}
+
+/// Turn the query into a search for the nearest neighbors of a vector
+///
+/// # Safety
+///
+/// `query` must be a live query and `vector` must point to `dim` floats.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_nearest_to(
+    query: *mut LanceDbQuery,
+    vector: *const f32,
+    dim: usize,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        if vector.is_null() || dim == 0 {
+            return Err(Error::InvalidInput {
+                message: "vector must not be null or empty".to_string(),
+            });
+        }
+        let vector = slice::from_raw_parts(vector, dim).to_vec();
+        let query = handle_mut(query, "query")?;
+        let QueryKind::Plain(plain) = query.get()? else {
+            return Err(Error::InvalidInput {
+                message: "lancedb_query_nearest_to was already called".to_string(),
+            });
+        };
+        query.inner = Some(QueryKind::Vector(plain.clone().nearest_to(vector)?));
+        Ok(())
+    })
+}
+
+/// Set the vector column to search, for a vector search
+///
+/// # Safety
+///
+/// `query` must be a live query and `column` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_column(
+    query: *mut LanceDbQuery,
+    column: *const c_char,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let column = str_arg(column, "column")?;
+        handle_mut(query, "query")?.update_vector("lancedb_query_column", |q| q.column(column))
+    })
+}
+
+/// Set the number of partitions to search, for a vector search
+///
+/// # Safety
+///
+/// `query` must be a live query.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_nprobes(
+    query: *mut LanceDbQuery,
+    nprobes: usize,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        handle_mut(query, "query")?.update_vector("lancedb_query_nprobes", |q| q.nprobes(nprobes))
+    })
+}
+
+/// Run the query and return the results as an Arrow stream
+///
+/// The query can be executed again.  The stream must be released by calling
+/// its `release` callback.  Reading the stream blocks until the next batch is
+/// available.
+///
+/// # Safety
+///
+/// `query` must be a live query and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_query_execute(
+    query: *const LanceDbQuery,
+    out: *mut FFI_ArrowArrayStream,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let query = handle(query, "query")?;
+        let runtime = runtime()?;
+        let reader = match query.get()? {
+            QueryKind::Plain(query) => runtime.block_on(query.execute_into_reader())?,
+            QueryKind::Vector(query) => runtime.block_on(query.execute_into_reader())?,
+        };
+        write_out(out, "out", FFI_ArrowArrayStream::new(reader))
+    })
+}
@@ -0,0 +1,181 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::c_char;
+
+use arrow::ffi::FFI_ArrowSchema;
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use lancedb::index::Index;
+use lancedb::Table;
+
+use crate::query::LanceDbQuery;
+use crate::{
+    ffi_call, handle, handle_mut, opt_str_arg, runtime, str_arg, write_out, LanceDbStatus,
+};
+
+/// A table, see [`lancedb::Table`]
+pub struct LanceDbTable {
+    pub(crate) inner: Table,
+}
+
+impl LanceDbTable {
+    pub(crate) fn new(inner: Table) -> *mut Self {
+        Box::into_raw(Box::new(Self { inner }))
+    }
+}
+
+/// Close a table
+///
+/// # Safety
+///
+/// `table` must be null or a table that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_free(table: *mut LanceDbTable) {
+    if !table.is_null() {
+        drop(Box::from_raw(table));
+    }
+}
+
+/// Get the version of the table
+///
+/// # Safety
+///
+/// `table` must be a live table and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_version(
+    table: *const LanceDbTable,
+    out: *mut u64,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let table = handle(table, "table")?;
+        let version = runtime()?.block_on(table.inner.version())?;
+        write_out(out, "out", version)
+    })
+}
+
+/// Get the schema of the table
+///
+/// The schema must be released by calling its `release` callback.
+///
+/// # Safety
+///
+/// `table` must be a live table and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_schema(
+    table: *const LanceDbTable,
+    out: *mut FFI_ArrowSchema,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let table = handle(table, "table")?;
+        let schema = runtime()?.block_on(table.inner.schema())?;
+        write_out(out, "out", FFI_ArrowSchema::try_from(schema.as_ref())?)
+    })
+}
+
+/// Count the rows of the table that match a filter
+///
+/// `filter` is an SQL filter, or null to count all rows.
+///
+/// # Safety
+///
+/// `table` must be a live table, `filter` null or a nul terminated string
+/// and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_count_rows(
+    table: *const LanceDbTable,
+    filter: *const c_char,
+    out: *mut u64,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let table = handle(table, "table")?;
+        let filter = opt_str_arg(filter, "filter")?.map(str::to_string);
+        let count = runtime()?.block_on(table.inner.count_rows(filter))?;
+        write_out(out, "out", count as u64)
+    })
+}
+
+/// Append the data of an Arrow stream to the table
+///
+/// The stream is consumed, it is released by the library even if the call
+/// fails.
+///
+/// # Safety
+///
+/// `table` must be a live table and `data` a valid Arrow stream.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_add(
+    table: *const LanceDbTable,
+    data: *mut FFI_ArrowArrayStream,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let data = ArrowArrayStreamReader::from_raw(handle_mut(data, "data")?)?;
+        let table = handle(table, "table")?;
+        runtime()?.block_on(table.inner.add(data).execute())
+    })
+}
+
+/// Delete the rows that match an SQL predicate
+///
+/// # Safety
+///
+/// `table` must be a live table and `predicate` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_delete(
+    table: *const LanceDbTable,
+    predicate: *const c_char,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let table = handle(table, "table")?;
+        let predicate = str_arg(predicate, "predicate")?;
+        runtime()?.block_on(table.inner.delete(predicate))
+    })
+}
+
+/// Create an index on a column
+///
+/// The type of the index is chosen from the type of the column, as with
+/// [`Index::Auto`].
+///
+/// # Safety
+///
+/// `table` must be a live table and `column` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_create_index(
+    table: *const LanceDbTable,
+    column: *const c_char,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let table = handle(table, "table")?;
+        let column = str_arg(column, "column")?;
+        runtime()?.block_on(table.inner.create_index(&[column], Index::Auto).execute())
+    })
+}
+
+/// Start a query of the table
+///
+/// The query must be released with `lancedb_query_free`.
+///
+/// # Safety
+///
+/// `table` must be a live table and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lancedb_table_query(
+    table: *const LanceDbTable,
+    out: *mut *mut LanceDbQuery,
+) -> LanceDbStatus {
+    ffi_call(|| {
+        let table = handle(table, "table")?;
+        write_out(out, "out", LanceDbQuery::new(table.inner.query()))
+    })
+}
@@ -83,6 +83,7 @@ impl JsTable {
                 .create_table(&table_name, batch_reader)
                 .write_options(WriteOptions {
                     lance_write_params: Some(params),
+                    ..Default::default()
                 })
                 .execute()
                 .await;
@@ -129,6 +130,7 @@ impl JsTable {
                 .add(batch_reader)
                 .write_options(WriteOptions {
                     lance_write_params: Some(params),
+                    ..Default::default()
                 })
                 .execute()
                 .await;
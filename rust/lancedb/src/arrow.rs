@@ -15,10 +15,16 @@
 use std::{pin::Pin, sync::Arc};
 
 pub use arrow_array;
+use arrow_array::{Array, RecordBatch, RecordBatchOptions};
 pub use arrow_schema;
+use arrow_schema::ArrowError;
 use futures::{Stream, StreamExt};
 
 use crate::error::Result;
+use crate::runtime;
+
+/// The number of batches buffered by [`stream_into_reader`]
+const READER_BUFFER_SIZE: usize = 2;
 
 /// An iterator of batches that also has a schema
 pub trait RecordBatchReader: Iterator<Item = Result<arrow_array::RecordBatch>> {
@@ -102,6 +108,74 @@ impl<S: Stream<Item = Result<arrow_array::RecordBatch>>> RecordBatchStream
     }
 }
 
+/// A synchronous Arrow reader fed by a [`SendableRecordBatchStream`]
+///
+/// The stream is polled by a background task which sends the batches to the
+/// reader through a bounded channel, so a slow reader still applies
+/// backpressure to the stream.
+struct StreamReader {
+    schema: Arc<arrow_schema::Schema>,
+    batches: tokio::sync::mpsc::Receiver<Result<arrow_array::RecordBatch>>,
+}
+
+impl Iterator for StreamReader {
+    type Item = std::result::Result<arrow_array::RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.batches
+            .blocking_recv()
+            .map(|batch| batch.map_err(|err| ArrowError::ExternalError(Box::new(err))))
+    }
+}
+
+impl arrow_array::RecordBatchReader for StreamReader {
+    fn schema(&self) -> Arc<arrow_schema::Schema> {
+        self.schema.clone()
+    }
+}
+
+/// Take the rows at `indices` from every column of `batch`
+///
+/// The same as `arrow::compute::take_record_batch`, which arrow 50 does not
+/// have yet.
+pub(crate) fn take_record_batch(
+    batch: &RecordBatch,
+    indices: &dyn Array,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|column| arrow::compute::take(column.as_ref(), indices, None))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    RecordBatch::try_new_with_options(
+        batch.schema(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(indices.len())),
+    )
+}
+
+/// Convert a stream into a standard (synchronous) Arrow reader
+///
+/// Must be called from within the async runtime.  The reader blocks the
+/// current thread while waiting for a batch and must not be read from an
+/// async context.  Dropping the reader stops the stream.
+pub(crate) fn stream_into_reader(
+    mut stream: SendableRecordBatchStream,
+) -> Box<dyn arrow_array::RecordBatchReader + Send> {
+    let schema = stream.schema();
+    let (sender, batches) = tokio::sync::mpsc::channel(READER_BUFFER_SIZE);
+    runtime::spawn(async move {
+        while let Some(batch) = stream.next().await {
+            let failed = batch.is_err();
+            // The reader was dropped
+            if sender.send(batch).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    Box::new(StreamReader { schema, batches })
+}
+
 /// A trait for converting incoming data to Arrow
 ///
 /// Integrations should implement this trait to allow data to be
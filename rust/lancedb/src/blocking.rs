@@ -0,0 +1,486 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A blocking API for applications that don't use async
+//!
+//! The types in this module wrap their async counterparts and run every call
+//! to completion on a tokio runtime that is owned by the [`Connection`].  The
+//! tables and queries created from a connection share its runtime.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+//! # use arrow_schema::{DataType, Field, Schema};
+//! use lancedb::query::QueryBase;
+//!
+//! # let tmpdir = tempfile::tempdir().unwrap();
+//! let db = lancedb::blocking::connect(tmpdir.path().to_str().unwrap()).unwrap();
+//! # let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+//! # let batch = RecordBatch::try_new(
+//! #     schema.clone(),
+//! #     vec![Arc::new(Int32Array::from_iter_values(0..10))],
+//! # ).unwrap();
+//! # let data = RecordBatchIterator::new(vec![Ok(batch)], schema);
+//! let table = db.create_table("my_table", data).unwrap();
+//! let batches = table.query().only_if("id > 4").collect().unwrap();
+//! assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+//! ```
+//!
+//! Options that are not exposed here can be set with the async builders and
+//! then run with [`Connection::block_on`] or [`Table::block_on`].
+//!
+//! The blocking API must not be used from within an async context (e.g. from
+//! inside a tokio task), doing so will panic.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use futures::{StreamExt, TryStreamExt};
+use tokio::runtime::Runtime;
+
+use crate::arrow::{IntoArrow, RecordBatchReader, SendableRecordBatchStream};
+use crate::connection::ConnectBuilder;
+use crate::error::{Error, Result};
+use crate::index::{Index, IndexConfig};
+use crate::query::{ExecutableQuery, HasQuery, IntoQueryVector, Query as AsyncQuery, VectorQuery};
+use crate::table::{ColumnAlteration, NewColumnTransform, OptimizeAction, OptimizeStats};
+use crate::DistanceType;
+
+fn new_runtime() -> Result<Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| Error::Runtime {
+            message: format!("failed to start the tokio runtime: {}", e),
+        })
+}
+
+/// Connect to a database with the default options
+///
+/// Use [`Connection::new`] to configure the connection.
+pub fn connect(uri: &str) -> Result<Connection> {
+    Connection::new(crate::connect(uri))
+}
+
+/// A blocking wrapper around [`crate::Connection`]
+#[derive(Clone)]
+pub struct Connection {
+    inner: crate::Connection,
+    runtime: Arc<Runtime>,
+}
+
+impl Connection {
+    /// Connect using an async [`ConnectBuilder`], on a new runtime
+    pub fn new(builder: ConnectBuilder) -> Result<Self> {
+        Self::with_runtime(builder, Arc::new(new_runtime()?))
+    }
+
+    /// Connect using an async [`ConnectBuilder`], on the given runtime
+    pub fn with_runtime(builder: ConnectBuilder, runtime: Arc<Runtime>) -> Result<Self> {
+        let inner = runtime.block_on(builder.execute())?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// The async connection that this wraps
+    pub fn as_async(&self) -> &crate::Connection {
+        &self.inner
+    }
+
+    /// Run a future to completion on this connection's runtime
+    ///
+    /// This can be used to run async builders that take options which are not
+    /// exposed by the blocking API.  Use [`Self::wrap_table`] to convert a
+    /// table created this way.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Wrap an async table so it runs on this connection's runtime
+    pub fn wrap_table(&self, table: crate::Table) -> Table {
+        Table {
+            inner: table,
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Get the URI of the connection
+    pub fn uri(&self) -> &str {
+        self.inner.uri()
+    }
+
+    /// Get the names of all tables in the database, in ascending order
+    pub fn table_names(&self) -> Result<Vec<String>> {
+        self.block_on(self.inner.table_names().execute())
+    }
+
+    /// Create a new table from data, see [`crate::Connection::create_table`]
+    pub fn create_table<T: IntoArrow>(
+        &self,
+        name: impl Into<String>,
+        initial_data: T,
+    ) -> Result<Table> {
+        let table = self.block_on(self.inner.create_table(name, initial_data).execute())?;
+        Ok(self.wrap_table(table))
+    }
+
+    /// Create an empty table with a given schema
+    pub fn create_empty_table(&self, name: impl Into<String>, schema: SchemaRef) -> Result<Table> {
+        let table = self.block_on(self.inner.create_empty_table(name, schema).execute())?;
+        Ok(self.wrap_table(table))
+    }
+
+    /// Open an existing table in the database
+    pub fn open_table(&self, name: impl Into<String>) -> Result<Table> {
+        let table = self.block_on(self.inner.open_table(name).execute())?;
+        Ok(self.wrap_table(table))
+    }
+
+    /// Drop a table in the database
+    pub fn drop_table(&self, name: impl AsRef<str>) -> Result<()> {
+        self.block_on(self.inner.drop_table(name))
+    }
+
+    /// Drop the database, this is the same as dropping all of the tables
+    pub fn drop_db(&self) -> Result<()> {
+        self.block_on(self.inner.drop_db())
+    }
+}
+
+impl std::fmt::Display for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// A blocking wrapper around [`crate::Table`]
+#[derive(Clone)]
+pub struct Table {
+    inner: crate::Table,
+    runtime: Arc<Runtime>,
+}
+
+impl Table {
+    /// The async table that this wraps
+    pub fn as_async(&self) -> &crate::Table {
+        &self.inner
+    }
+
+    /// Run a future to completion on this table's runtime
+    ///
+    /// For example, to run an update:
+    ///
+    /// ```no_run
+    /// # let db = lancedb::blocking::connect("data/sample-lancedb").unwrap();
+    /// # let table = db.open_table("my_table").unwrap();
+    /// table
+    ///     .block_on(table.as_async().update().column("id", "id + 1").execute())
+    ///     .unwrap();
+    /// ```
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Get the name of the table
+    pub fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// Get the arrow schema of the table
+    pub fn schema(&self) -> Result<SchemaRef> {
+        self.block_on(self.inner.schema())
+    }
+
+    /// Count the number of rows, optionally only those matching `filter`
+    pub fn count_rows(&self, filter: Option<String>) -> Result<usize> {
+        self.block_on(self.inner.count_rows(filter))
+    }
+
+    /// Append data to the table
+    pub fn add<T: IntoArrow>(&self, data: T) -> Result<()> {
+        self.block_on(self.inner.add(data).execute())
+    }
+
+    /// Delete the rows that match the predicate
+    pub fn delete(&self, predicate: &str) -> Result<()> {
+        self.block_on(self.inner.delete(predicate))
+    }
+
+    /// Create an index on the given columns, replacing any existing index
+    pub fn create_index(&self, columns: &[impl AsRef<str>], index: Index) -> Result<()> {
+        self.block_on(self.inner.create_index(columns, index).execute())
+    }
+
+    /// List the indices on the table
+    pub fn list_indices(&self) -> Result<Vec<IndexConfig>> {
+        self.block_on(self.inner.list_indices())
+    }
+
+    /// Optimize the table, see [`crate::Table::optimize`]
+    pub fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats> {
+        self.block_on(self.inner.optimize(action))
+    }
+
+    /// Add new columns, see [`crate::Table::add_columns`]
+    pub fn add_columns(
+        &self,
+        transforms: NewColumnTransform,
+        read_columns: Option<Vec<String>>,
+    ) -> Result<()> {
+        self.block_on(self.inner.add_columns(transforms, read_columns))
+    }
+
+    /// Rename columns or change their nullability
+    pub fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()> {
+        self.block_on(self.inner.alter_columns(alterations))
+    }
+
+    /// Remove columns from the table
+    pub fn drop_columns(&self, columns: &[&str]) -> Result<()> {
+        self.block_on(self.inner.drop_columns(columns))
+    }
+
+    /// Get the version of the table
+    pub fn version(&self) -> Result<u64> {
+        self.block_on(self.inner.version())
+    }
+
+    /// Check out an older version of the table, see [`crate::Table::checkout`]
+    pub fn checkout(&self, version: u64) -> Result<()> {
+        self.block_on(self.inner.checkout(version))
+    }
+
+    /// Return to the latest version of the table
+    pub fn checkout_latest(&self) -> Result<()> {
+        self.block_on(self.inner.checkout_latest())
+    }
+
+    /// Make the checked out version the latest version
+    pub fn restore(&self) -> Result<()> {
+        self.block_on(self.inner.restore())
+    }
+
+    /// Create a query of the table
+    pub fn query(&self) -> Query<AsyncQuery> {
+        Query {
+            inner: self.inner.query(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Create a vector search of the table, see [`crate::Table::vector_search`]
+    pub fn vector_search(&self, query: impl IntoQueryVector) -> Result<Query<VectorQuery>> {
+        self.query().nearest_to(query)
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// A blocking wrapper around a query, either a [`AsyncQuery`] or a [`VectorQuery`]
+///
+/// The methods of [`crate::query::QueryBase`] (e.g. `limit` and `only_if`)
+/// are available on both.
+#[derive(Clone)]
+pub struct Query<Q> {
+    inner: Q,
+    runtime: Arc<Runtime>,
+}
+
+impl<Q: HasQuery> HasQuery for Query<Q> {
+    fn mut_query(&mut self) -> &mut AsyncQuery {
+        self.inner.mut_query()
+    }
+}
+
+impl<Q: ExecutableQuery> Query<Q> {
+    /// The async query that this wraps
+    pub fn into_async(self) -> Q {
+        self.inner
+    }
+
+    /// Run the query, returning an iterator of the results
+    pub fn execute(&self) -> Result<RecordBatchIter> {
+        let stream = self.runtime.block_on(self.inner.execute())?;
+        Ok(RecordBatchIter {
+            stream,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// Run the query and collect all of the results
+    pub fn collect(&self) -> Result<Vec<RecordBatch>> {
+        self.runtime
+            .block_on(async { self.inner.execute().await?.try_collect().await })
+    }
+}
+
+impl Query<AsyncQuery> {
+    /// Find the nearest vectors to the given query vector
+    ///
+    /// See [`AsyncQuery::nearest_to`]
+    pub fn nearest_to(self, vector: impl IntoQueryVector) -> Result<Query<VectorQuery>> {
+        Ok(Query {
+            inner: self.inner.nearest_to(vector)?,
+            runtime: self.runtime,
+        })
+    }
+}
+
+impl Query<VectorQuery> {
+    /// Set the vector column to query, see [`VectorQuery::column`]
+    pub fn column(mut self, column: &str) -> Self {
+        self.inner = self.inner.column(column);
+        self
+    }
+
+    /// Set the number of partitions to search, see [`VectorQuery::nprobes`]
+    pub fn nprobes(mut self, nprobes: usize) -> Self {
+        self.inner = self.inner.nprobes(nprobes);
+        self
+    }
+
+    /// See [`VectorQuery::refine_factor`]
+    pub fn refine_factor(mut self, refine_factor: u32) -> Self {
+        self.inner = self.inner.refine_factor(refine_factor);
+        self
+    }
+
+    /// Set the distance metric, see [`VectorQuery::distance_type`]
+    pub fn distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.inner = self.inner.distance_type(distance_type);
+        self
+    }
+
+    /// Apply the filter after the vector search, see [`VectorQuery::postfilter`]
+    pub fn postfilter(mut self) -> Self {
+        self.inner = self.inner.postfilter();
+        self
+    }
+
+    /// Search without using the vector index, see [`VectorQuery::bypass_vector_index`]
+    pub fn bypass_vector_index(mut self) -> Self {
+        self.inner = self.inner.bypass_vector_index();
+        self
+    }
+}
+
+/// The results of a blocking query
+///
+/// Each batch is read when [`Iterator::next`] is called.
+pub struct RecordBatchIter {
+    stream: SendableRecordBatchStream,
+    runtime: Arc<Runtime>,
+}
+
+impl Iterator for RecordBatchIter {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+impl RecordBatchReader for RecordBatchIter {
+    fn schema(&self) -> SchemaRef {
+        self.stream.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{types::Float32Type, FixedSizeListArray, Int32Array, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::query::{QueryBase, Select};
+
+    fn make_data(start: i32) -> impl arrow_array::RecordBatchReader + Send + 'static {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(start..start + 10)),
+                Arc::new(
+                    FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                        (start..start + 10).map(|i| Some(vec![Some(i as f32), Some(i as f32)])),
+                        2,
+                    ),
+                ),
+            ],
+        )
+        .unwrap();
+        RecordBatchIterator::new(vec![Ok(batch)], schema)
+    }
+
+    #[test]
+    fn test_blocking_api() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let db = connect(tmp_dir.path().to_str().unwrap()).unwrap();
+
+        let table = db.create_table("test", make_data(0)).unwrap();
+        assert_eq!(db.table_names().unwrap(), vec!["test"]);
+        table.add(make_data(10)).unwrap();
+        assert_eq!(table.count_rows(None).unwrap(), 20);
+        assert_eq!(table.version().unwrap(), 2);
+
+        let table = db.open_table("test").unwrap();
+        let batches = table
+            .query()
+            .only_if("id >= 15")
+            .select(Select::columns(&["id"]))
+            .collect()
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+        assert_eq!(batches[0].num_columns(), 1);
+
+        let results = table
+            .vector_search(&[3.0_f32, 3.0])
+            .unwrap()
+            .limit(1)
+            .execute()
+            .unwrap();
+        assert!(results.schema().field_with_name("_distance").is_ok());
+        let batches = results.collect::<Result<Vec<_>>>().unwrap();
+        let ids = batches[0]["id"]
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(ids.len(), 1);
+        assert_eq!(ids.value(0), 3);
+
+        table.delete("id < 5").unwrap();
+        assert_eq!(table.count_rows(None).unwrap(), 15);
+
+        // Builders that are not wrapped can be run on the same runtime
+        table
+            .block_on(table.as_async().update().column("id", "id + 100").execute())
+            .unwrap();
+        assert_eq!(table.count_rows(Some("id >= 100".to_string())).unwrap(), 15);
+
+        db.drop_table("test").unwrap();
+        assert!(db.table_names().unwrap().is_empty());
+    }
+}
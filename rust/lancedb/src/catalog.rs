@@ -0,0 +1,58 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Catalogs of tables
+//!
+//! By default a connection finds its tables by listing the directory of the
+//! database.  A [`Catalog`] set with
+//! [`crate::connection::ConnectBuilder::catalog`] replaces the listing: the
+//! connection asks the catalog for the names and the locations of the
+//! tables, registers the tables it creates and removes the tables it drops.
+//! Tables registered in a catalog can be stored anywhere, e.g. in another
+//! bucket, and are added with [`crate::Connection::register_table`].
+//!
+//! Two catalogs are included: [`filesystem::FileSystemCatalog`] keeps the
+//! entries next to the data on any object store, and [`rest::RestCatalog`]
+//! talks to a catalog service over HTTP, in the style of the Iceberg and
+//! Unity Catalog REST APIs.
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+
+pub mod filesystem;
+#[cfg(feature = "remote")]
+pub mod rest;
+
+/// Where a connection finds its tables
+///
+/// Implementations must be safe to call from many tasks at the same time.
+#[async_trait]
+pub trait Catalog: Send + Sync + std::fmt::Debug {
+    /// The names of the registered tables, sorted
+    async fn list_tables(&self) -> Result<Vec<String>>;
+
+    /// The URI of the data of a table, or None if it is not registered
+    async fn table_location(&self, name: &str) -> Result<Option<String>>;
+
+    /// Register the table `name`, stored at `location`
+    ///
+    /// Returns [`crate::Error::TableAlreadyExists`] if the name is taken.
+    async fn register_table(&self, name: &str, location: &str) -> Result<()>;
+
+    /// Remove the table `name` from the catalog, but not its data
+    ///
+    /// Returns [`crate::Error::TableNotFound`] if it is not registered.
+    async fn deregister_table(&self, name: &str) -> Result<()>;
+}
@@ -0,0 +1,179 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A catalog stored as files on an object store
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use lance::io::ObjectStore;
+use object_store::path::Path;
+use object_store::{PutMode, PutOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::utils::validate_table_name;
+
+use super::Catalog;
+
+const ENTRY_EXTENSION: &str = "json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CatalogEntry {
+    location: String,
+}
+
+/// A catalog that keeps one small JSON file per table in a directory
+///
+/// The directory can be on any object store supported by Lance, so several
+/// processes can share the catalog.  A table is registered by creating its
+/// file, which fails if the file already exists, so two processes can not
+/// register the same name.
+#[derive(Debug)]
+pub struct FileSystemCatalog {
+    object_store: ObjectStore,
+    base_path: Path,
+}
+
+impl FileSystemCatalog {
+    /// Open the catalog in the directory at `uri`
+    pub async fn open(uri: &str) -> Result<Self> {
+        let (object_store, base_path) = ObjectStore::from_uri(uri).await?;
+        Ok(Self {
+            object_store,
+            base_path,
+        })
+    }
+
+    fn entry_path(&self, name: &str) -> Result<Path> {
+        validate_table_name(name)?;
+        Ok(self
+            .base_path
+            .child(format!("{}.{}", name, ENTRY_EXTENSION)))
+    }
+}
+
+#[async_trait]
+impl Catalog for FileSystemCatalog {
+    async fn list_tables(&self) -> Result<Vec<String>> {
+        // Only the files directly in the directory, it may also hold the tables
+        let listing = self
+            .object_store
+            .inner
+            .list_with_delimiter(Some(&self.base_path))
+            .await?;
+        let mut names = listing
+            .objects
+            .iter()
+            .filter_map(|object| {
+                object
+                    .location
+                    .filename()?
+                    .strip_suffix(&format!(".{}", ENTRY_EXTENSION))
+                    .map(String::from)
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    async fn table_location(&self, name: &str) -> Result<Option<String>> {
+        let path = self.entry_path(name)?;
+        let bytes = match self.object_store.inner.get(&path).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let entry: CatalogEntry = serde_json::from_slice(&bytes).map_err(|e| Error::Runtime {
+            message: format!("the catalog entry of '{}' is invalid: {}", name, e),
+        })?;
+        Ok(Some(entry.location))
+    }
+
+    async fn register_table(&self, name: &str, location: &str) -> Result<()> {
+        let path = self.entry_path(name)?;
+        let entry = CatalogEntry {
+            location: location.to_string(),
+        };
+        let bytes = serde_json::to_vec(&entry).map_err(|e| Error::Runtime {
+            message: format!("failed to serialize the catalog entry: {}", e),
+        })?;
+        let options = PutOptions {
+            mode: PutMode::Create,
+            ..Default::default()
+        };
+        match self
+            .object_store
+            .inner
+            .put_opts(&path, Bytes::from(bytes), options)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(object_store::Error::AlreadyExists { .. }) => Err(Error::TableAlreadyExists {
+                name: name.to_string(),
+            }),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn deregister_table(&self, name: &str) -> Result<()> {
+        let path = self.entry_path(name)?;
+        if self.table_location(name).await?.is_none() {
+            return Err(Error::TableNotFound {
+                name: name.to_string(),
+            });
+        }
+        self.object_store.inner.delete(&path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_filesystem_catalog() {
+        let tmp_dir = tempdir().unwrap();
+        let catalog = FileSystemCatalog::open(tmp_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(catalog.list_tables().await.unwrap().is_empty());
+        assert_eq!(catalog.table_location("a").await.unwrap(), None);
+
+        catalog
+            .register_table("b", "s3://bucket/b.lance")
+            .await
+            .unwrap();
+        catalog.register_table("a", "/data/a.lance").await.unwrap();
+        assert!(matches!(
+            catalog.register_table("a", "/other/a.lance").await,
+            Err(Error::TableAlreadyExists { .. })
+        ));
+        assert_eq!(catalog.list_tables().await.unwrap(), vec!["a", "b"]);
+        assert_eq!(
+            catalog.table_location("b").await.unwrap().as_deref(),
+            Some("s3://bucket/b.lance")
+        );
+
+        catalog.deregister_table("a").await.unwrap();
+        assert_eq!(catalog.list_tables().await.unwrap(), vec!["b"]);
+        assert!(matches!(
+            catalog.deregister_table("a").await,
+            Err(Error::TableNotFound { .. })
+        ));
+        assert!(catalog.register_table("not/valid", "/x").await.is_err());
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A catalog served over HTTP
+//!
+//! [`RestCatalog`] expects a JSON API in the style of the Iceberg and Unity
+//! Catalog REST APIs:
+//!
+//! * `GET /v1/tables` returns `{"tables": ["name", ...]}`
+//! * `GET /v1/tables/{name}` returns `{"location": "s3://..."}`, or 404
+//! * `POST /v1/tables` with `{"name": ..., "location": ...}` registers a
+//!   table, or returns 409 if the name is taken
+//! * `DELETE /v1/tables/{name}` removes a table, or returns 404
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::utils::validate_table_name;
+
+use super::Catalog;
+
+#[derive(Deserialize)]
+struct ListTablesResponse {
+    tables: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TableEntry {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    name: Option<String>,
+    location: String,
+}
+
+/// A catalog that registers the tables with a catalog service
+#[derive(Debug, Clone)]
+pub struct RestCatalog {
+    client: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+}
+
+impl RestCatalog {
+    /// Use the catalog service at `base_url`, e.g. `https://catalog.example.com/`
+    pub fn new(base_url: &str) -> Result<Self> {
+        let mut base_url = Url::parse(base_url)?;
+        // Make the endpoints relative to the whole path of the URL
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        Ok(Self {
+            client: reqwest::Client::new(),
+            base_url,
+            token: None,
+        })
+    }
+
+    /// Authenticate the requests with a bearer token
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    fn tables_url(&self, name: Option<&str>) -> Result<Url> {
+        let path = match name {
+            Some(name) => {
+                validate_table_name(name)?;
+                format!("v1/tables/{}", name)
+            }
+            None => "v1/tables".to_string(),
+        };
+        Ok(self.base_url.join(&path)?)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        Ok(request.send().await?)
+    }
+
+    async fn check_response(response: Response) -> Result<Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let message = format!("the catalog returned {}: {}", status, body);
+        if status.is_client_error() {
+            Err(Error::InvalidInput { message })
+        } else {
+            Err(Error::Http { message })
+        }
+    }
+}
+
+#[async_trait]
+impl Catalog for RestCatalog {
+    async fn list_tables(&self) -> Result<Vec<String>> {
+        let request = self.client.get(self.tables_url(None)?);
+        let response = Self::check_response(self.send(request).await?).await?;
+        let mut tables = response.json::<ListTablesResponse>().await?.tables;
+        tables.sort();
+        Ok(tables)
+    }
+
+    async fn table_location(&self, name: &str) -> Result<Option<String>> {
+        let request = self.client.get(self.tables_url(Some(name))?);
+        let response = self.send(request).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = Self::check_response(response).await?;
+        Ok(Some(response.json::<TableEntry>().await?.location))
+    }
+
+    async fn register_table(&self, name: &str, location: &str) -> Result<()> {
+        validate_table_name(name)?;
+        let entry = TableEntry {
+            name: Some(name.to_string()),
+            location: location.to_string(),
+        };
+        let request = self.client.post(self.tables_url(None)?).json(&entry);
+        let response = self.send(request).await?;
+        if response.status() == StatusCode::CONFLICT {
+            return Err(Error::TableAlreadyExists {
+                name: name.to_string(),
+            });
+        }
+        Self::check_response(response).await?;
+        Ok(())
+    }
+
+    async fn deregister_table(&self, name: &str) -> Result<()> {
+        let request = self.client.delete(self.tables_url(Some(name))?);
+        let response = self.send(request).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::TableNotFound {
+                name: name.to_string(),
+            });
+        }
+        Self::check_response(response).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_url() {
+        let catalog = RestCatalog::new("https://catalog.example.com/api").unwrap();
+        assert_eq!(
+            catalog.tables_url(None).unwrap().as_str(),
+            "https://catalog.example.com/api/v1/tables"
+        );
+        assert_eq!(
+            catalog.tables_url(Some("items")).unwrap().as_str(),
+            "https://catalog.example.com/api/v1/tables/items"
+        );
+        assert!(catalog.tables_url(Some("../admin")).is_err());
+        assert!(RestCatalog::new("not a url").is_err());
+    }
+}
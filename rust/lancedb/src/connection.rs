@@ -19,8 +19,9 @@ use std::path::Path;
 use std::sync::Arc;
 
 use arrow_array::{RecordBatchIterator, RecordBatchReader};
-use arrow_schema::SchemaRef;
-use lance::dataset::{ReadParams, WriteMode};
+use arrow_schema::{Schema, SchemaRef};
+use futures::TryStreamExt;
+use lance::dataset::{Dataset, ReadParams, WriteMode};
 use lance::io::{ObjectStore, ObjectStoreParams, WrappingObjectStore};
 use object_store::{
     aws::AwsCredential, local::LocalFileSystem, CredentialProvider, StaticCredentialProvider,
@@ -28,12 +29,37 @@ use object_store::{
 use snafu::prelude::*;
 
 use crate::arrow::IntoArrow;
+use crate::catalog::Catalog;
+use crate::connection::admission::{AdmissionConfig, AdmissionController, AdmissionMetrics};
+use crate::connection::auth::AuthProvider;
+use crate::connection::client_config::ClientConfig;
+use crate::connection::memory::{MemoryPool, MemoryPoolConfig, MemoryPoolMetrics};
+use crate::data::sort::sort_by_column;
 use crate::error::{CreateDirSnafu, Error, InvalidTableNameSnafu, Result};
+use crate::index::{Index, PendingIndex, PendingIndices, DEFAULT_PENDING_INDEX_THRESHOLD};
+use crate::io::disk_cache::{DiskCacheConfig, DiskCacheWrapper};
+use crate::io::encryption::{EncryptionWrapper, KeyProvider};
 use crate::io::object_store::MirroringObjectStoreWrapper;
+use crate::io::read_write::ReadWriteStoreWrapper;
+use crate::io::throttle::{IoThrottleConfig, ThrottleWrapper};
+use crate::io::tiering::{Tiering, TieringPolicy, TieringWrapper};
+use crate::query::filter_cache::FilterCacheConfig;
+use crate::query::ExecutableQuery;
+use crate::table::hooks::CommitHook;
+use crate::table::interceptor::QueryInterceptor;
+use crate::table::schema_diff::SchemaDiff;
+use crate::table::spec::TableSpec;
+use crate::table::view::{read_view, write_view, StoredView, ViewDefinition, ViewTable};
 use crate::table::{NativeTable, WriteOptions};
+use crate::units::IntoDuration;
 use crate::utils::validate_table_name;
 use crate::Table;
 
+pub mod admission;
+pub mod auth;
+pub mod client_config;
+pub mod memory;
+
 pub const LANCE_FILE_EXTENSION: &str = "lance";
 
 pub type TableBuilderCallback = Box<dyn FnOnce(OpenTableBuilder) -> OpenTableBuilder + Send>;
@@ -134,6 +160,9 @@ pub struct CreateTableBuilder<const HAS_DATA: bool, T: IntoArrow> {
     pub(crate) schema: Option<SchemaRef>,
     pub(crate) mode: CreateTableMode,
     pub(crate) write_options: WriteOptions,
+    pub(crate) pending_indices: Option<PendingIndices>,
+    pub(crate) idempotency_token: Option<String>,
+    pub(crate) sort_by: Option<String>,
 }
 
 // Builder methods that only apply when we have initial data
@@ -146,6 +175,9 @@ impl<T: IntoArrow> CreateTableBuilder<true, T> {
             schema: None,
             mode: CreateTableMode::default(),
             write_options: WriteOptions::default(),
+            pending_indices: None,
+            idempotency_token: None,
+            sort_by: None,
         }
     }
 
@@ -155,6 +187,18 @@ impl<T: IntoArrow> CreateTableBuilder<true, T> {
         self
     }
 
+    /// Sort the initial data by a column (ascending, nulls last) before writing it
+    ///
+    /// Time series and other data that is mostly filtered on one column scan
+    /// much faster when each fragment covers a narrow range of that column,
+    /// since fragments that cannot match the filter are skipped.  All of the
+    /// initial data is read into memory to sort it.  Later writes can be
+    /// sorted with [`crate::table::AddDataBuilder::sort_by`].
+    pub fn sort_by(mut self, column: impl Into<String>) -> Self {
+        self.sort_by = Some(column.into());
+        self
+    }
+
     /// Execute the create table operation
     pub async fn execute(self) -> Result<Table> {
         let parent = self.parent.clone();
@@ -168,7 +212,10 @@ impl<T: IntoArrow> CreateTableBuilder<true, T> {
         Box<dyn RecordBatchReader + Send>,
         CreateTableBuilder<false, NoData>,
     )> {
-        let data = self.data.take().unwrap().into_arrow()?;
+        let mut data = self.data.take().unwrap().into_arrow()?;
+        if let Some(column) = &self.sort_by {
+            data = sort_by_column(data, column)?;
+        }
         let builder = CreateTableBuilder::<false, NoData> {
             parent: self.parent,
             name: self.name,
@@ -176,6 +223,9 @@ impl<T: IntoArrow> CreateTableBuilder<true, T> {
             schema: self.schema,
             mode: self.mode,
             write_options: self.write_options,
+            pending_indices: self.pending_indices,
+            idempotency_token: self.idempotency_token,
+            sort_by: self.sort_by,
         };
         Ok((data, builder))
     }
@@ -191,11 +241,59 @@ impl CreateTableBuilder<false, NoData> {
             schema: Some(schema),
             mode: CreateTableMode::default(),
             write_options: WriteOptions::default(),
+            pending_indices: None,
+            idempotency_token: None,
+            sort_by: None,
         }
     }
 
+    /// Declare an index that should be created once the table has enough data
+    ///
+    /// Many indices (e.g. IVF PQ) cannot be trained on an empty table.  Declared
+    /// indices are recorded in the table's metadata and built automatically by
+    /// [`crate::Table::add`] once the table has at least
+    /// [`Self::pending_index_threshold`] rows.  A declared index is considered
+    /// satisfied once any index exists on the same columns.  The index is
+    /// built after the data of the add is committed, so a failure to build it
+    /// is logged rather than returned by the add, and the next add tries again.
+    ///
+    /// This method can be called multiple times to declare multiple indices.
+    pub fn declare_index(mut self, columns: &[impl AsRef<str>], index: Index) -> Self {
+        let pending = self.pending_indices.get_or_insert_with(|| PendingIndices {
+            threshold: DEFAULT_PENDING_INDEX_THRESHOLD,
+            indices: Vec::new(),
+        });
+        pending.indices.push(PendingIndex {
+            columns: columns.iter().map(|c| c.as_ref().to_string()).collect(),
+            index,
+        });
+        self
+    }
+
+    /// The number of rows the table must reach before declared indices are built
+    ///
+    /// The default is [`DEFAULT_PENDING_INDEX_THRESHOLD`].  This has no effect
+    /// unless [`Self::declare_index`] is also called.
+    pub fn pending_index_threshold(mut self, threshold: usize) -> Self {
+        if let Some(pending) = self.pending_indices.as_mut() {
+            pending.threshold = threshold;
+        } else {
+            self.pending_indices = Some(PendingIndices {
+                threshold,
+                indices: Vec::new(),
+            });
+        }
+        self
+    }
+
     /// Execute the create table operation
-    pub async fn execute(self) -> Result<Table> {
+    pub async fn execute(mut self) -> Result<Table> {
+        if let Some(pending) = self.pending_indices.take() {
+            if !pending.indices.is_empty() {
+                let schema = self.schema.as_ref().unwrap();
+                self.schema = Some(pending.apply_to_schema(schema)?);
+            }
+        }
         self.parent.clone().do_create_empty_table(self).await
     }
 }
@@ -208,6 +306,227 @@ impl<const HAS_DATA: bool, T: IntoArrow> CreateTableBuilder<HAS_DATA, T> {
         self.mode = mode;
         self
     }
+
+    /// Make the create table operation safe to retry
+    ///
+    /// The token is stored with the table.  If a table with the same name
+    /// already exists and was created with the same token then the existing
+    /// table is returned instead of [`Error::TableAlreadyExists`].  This lets
+    /// a caller retry a create that may or may not have succeeded (e.g.
+    /// because the request timed out) without having to use
+    /// [`CreateTableMode::ExistOk`], which would also accept a table that was
+    /// created by someone else.
+    ///
+    /// Use a new, unique, token for each logical create operation.  Requests
+    /// to LanceDb Cloud always send a token, one is generated if none is set,
+    /// so that the client's own retries are safe.
+    pub fn idempotency_token(mut self, token: impl Into<String>) -> Self {
+        self.idempotency_token = Some(token.into());
+        self
+    }
+}
+
+/// The initial data, or the schema, of a table created with
+/// [`Connection::create_table_if_not_exists`]
+pub enum TableSource {
+    /// Create the table with this data
+    Data(Box<dyn RecordBatchReader + Send>),
+    /// Create an empty table with this schema
+    Schema(SchemaRef),
+}
+
+impl TableSource {
+    fn schema(&self) -> SchemaRef {
+        match self {
+            Self::Data(data) => data.schema(),
+            Self::Schema(schema) => schema.clone(),
+        }
+    }
+}
+
+impl From<Box<dyn RecordBatchReader + Send>> for TableSource {
+    fn from(data: Box<dyn RecordBatchReader + Send>) -> Self {
+        Self::Data(data)
+    }
+}
+
+impl From<SchemaRef> for TableSource {
+    fn from(schema: SchemaRef) -> Self {
+        Self::Schema(schema)
+    }
+}
+
+/// A builder for configuring a [`Connection::create_table_if_not_exists`] operation
+pub struct CreateTableIfNotExistsBuilder {
+    parent: Arc<dyn ConnectionInternal>,
+    name: String,
+    source: TableSource,
+    check_schema: bool,
+}
+
+impl CreateTableIfNotExistsBuilder {
+    fn new(parent: Arc<dyn ConnectionInternal>, name: String, source: TableSource) -> Self {
+        Self {
+            parent,
+            name,
+            source,
+            check_schema: false,
+        }
+    }
+
+    /// Fail if the existing table does not have the fields of the schema
+    ///
+    /// Each field of the data, or of the schema, must be in the table with
+    /// the same type.  The table may have other fields.  If the check fails
+    /// [`Error::Schema`] is returned, listing every field that does not
+    /// match.  See [`crate::table::schema_diff`] for how fields are compared.
+    pub fn check_schema(mut self) -> Self {
+        self.check_schema = true;
+        self
+    }
+
+    /// Execute the operation, returning the created or the existing table
+    pub async fn execute(self) -> Result<Table> {
+        let expected = self.source.schema();
+        let mode = || CreateTableMode::exist_ok(|builder| builder);
+        let table = match self.source {
+            TableSource::Data(data) => {
+                CreateTableBuilder::<true, _>::new(self.parent, self.name, data)
+                    .mode(mode())
+                    .execute()
+                    .await?
+            }
+            TableSource::Schema(schema) => {
+                CreateTableBuilder::<false, NoData>::new(self.parent, self.name, schema)
+                    .mode(mode())
+                    .execute()
+                    .await?
+            }
+        };
+        if self.check_schema {
+            let schema = table.schema().await?;
+            check_fields(table.name(), &expected, &schema)?;
+        }
+        Ok(table)
+    }
+}
+
+/// Check that the table has every field of the expected schema
+fn check_fields(name: &str, expected: &Schema, actual: &Schema) -> Result<()> {
+    let diff = SchemaDiff::between(expected, actual);
+    if diff.extra_columns.is_empty() && diff.type_mismatches.is_empty() {
+        return Ok(());
+    }
+    // The table may have other columns
+    let diff = SchemaDiff {
+        missing_columns: Vec::new(),
+        nullability_issues: Vec::new(),
+        ..diff
+    };
+    Err(Error::Schema {
+        message: format!("the table '{}' does not match the schema: {}", name, diff),
+    })
+}
+
+/// A builder for configuring a [`Connection::create_view`] operation
+pub struct CreateViewBuilder {
+    parent: Arc<dyn ConnectionInternal>,
+    name: String,
+    base_table: String,
+    definition: ViewDefinition,
+    materialized: bool,
+}
+
+impl CreateViewBuilder {
+    fn new(
+        parent: Arc<dyn ConnectionInternal>,
+        name: String,
+        base_table: String,
+        definition: ViewDefinition,
+    ) -> Self {
+        Self {
+            parent,
+            name,
+            base_table,
+            definition,
+            materialized: false,
+        }
+    }
+
+    /// Store a copy of the rows of the view in a table
+    ///
+    /// The copy is faster to query than a virtual view but only changes when
+    /// the view is refreshed with [`Connection::refresh_view`].
+    pub fn materialized(mut self) -> Self {
+        self.materialized = true;
+        self
+    }
+
+    /// Create the view, returning it opened
+    pub async fn execute(self) -> Result<Table> {
+        validate_table_name(&self.name)?;
+        if self.parent.load_view(&self.name).await?.is_some() {
+            return Err(Error::TableAlreadyExists { name: self.name });
+        }
+        match OpenTableBuilder::new(self.parent.clone(), self.name.clone())
+            .execute()
+            .await
+        {
+            Ok(_) => return Err(Error::TableAlreadyExists { name: self.name }),
+            Err(Error::TableNotFound { .. }) => {}
+            Err(err) => return Err(err),
+        }
+        let base = OpenTableBuilder::new(self.parent.clone(), self.base_table.clone())
+            .execute()
+            .await?;
+        let schema = base.schema().await?;
+        self.definition.validate(&schema)?;
+
+        let view = StoredView {
+            base_table: self.base_table,
+            definition: self.definition,
+            materialized: self.materialized,
+        };
+        let table = if view.materialized {
+            materialize(
+                &self.parent,
+                &self.name,
+                &base,
+                &view,
+                CreateTableMode::Create,
+            )
+            .await?
+        } else {
+            Table::new(Arc::new(ViewTable::new(
+                self.name.clone(),
+                base.inner.clone(),
+                view.definition.clone(),
+            )))
+        };
+        self.parent.save_view(&self.name, Some(&view)).await?;
+        Ok(table)
+    }
+}
+
+/// Write the rows of a view to the table `name`
+async fn materialize(
+    parent: &Arc<dyn ConnectionInternal>,
+    name: &str,
+    base: &Table,
+    view: &StoredView,
+    mode: CreateTableMode,
+) -> Result<Table> {
+    let mut query = view.definition.apply(&base.query())?;
+    // The copy has every column of the view
+    query.include_vectors = Some(true);
+    let stream = query.execute().await?;
+    let schema = stream.schema();
+    let batches = stream.try_collect::<Vec<_>>().await?;
+    let data = RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+    CreateTableBuilder::<true, _>::new(parent.clone(), name.to_string(), data)
+        .mode(mode)
+        .execute()
+        .await
 }
 
 #[derive(Clone, Debug)]
@@ -216,6 +535,10 @@ pub struct OpenTableBuilder {
     name: String,
     index_cache_size: u32,
     lance_read_params: Option<ReadParams>,
+    unmasked: bool,
+    commit_hooks: Vec<Arc<dyn CommitHook>>,
+    exclude_vectors: Option<bool>,
+    audit_context: Option<String>,
 }
 
 impl OpenTableBuilder {
@@ -225,6 +548,10 @@ impl OpenTableBuilder {
             name,
             index_cache_size: 256,
             lance_read_params: None,
+            unmasked: false,
+            commit_hooks: Vec::new(),
+            exclude_vectors: None,
+            audit_context: None,
         }
     }
 
@@ -252,6 +579,46 @@ impl OpenTableBuilder {
         self
     }
 
+    /// Read the original values of masked columns
+    ///
+    /// By default the masking policies of the table (see
+    /// [`crate::table::masking`]) are applied to every query.  A table opened
+    /// with unmasked access returns the original values and can change the
+    /// masking policies.  Applications should only set this for callers that
+    /// are privileged to see the sensitive columns.
+    pub fn unmasked(mut self) -> Self {
+        self.unmasked = true;
+        self
+    }
+
+    /// Call the hook after each commit made through the opened table
+    ///
+    /// The hook runs after the hooks of the connection (see
+    /// [`ConnectBuilder::commit_hook`]).  This only affects LanceDB OSS.
+    pub fn commit_hook(mut self, hook: Arc<dyn CommitHook>) -> Self {
+        self.commit_hooks.push(hook);
+        self
+    }
+
+    /// Leave the vector columns out of the results of queries by default
+    ///
+    /// This overrides [`ConnectBuilder::exclude_vectors`] for the opened table.
+    /// This only affects LanceDB OSS.
+    pub fn exclude_vectors(mut self, exclude: bool) -> Self {
+        self.exclude_vectors = Some(exclude);
+        self
+    }
+
+    /// Describe who is making the changes through the opened table
+    ///
+    /// The context, e.g. a user name or a job id, is passed to the commit
+    /// hooks with every commit and recorded by the audit log (see
+    /// [`crate::table::audit`]).  This only affects LanceDB OSS.
+    pub fn audit_context(mut self, context: impl Into<String>) -> Self {
+        self.audit_context = Some(context.into());
+        self
+    }
+
     /// Open the table
     pub async fn execute(self) -> Result<Table> {
         self.parent.clone().do_open_table(self).await
@@ -263,6 +630,7 @@ pub(crate) trait ConnectionInternal:
     Send + Sync + std::fmt::Debug + std::fmt::Display + 'static
 {
     async fn table_names(&self, options: TableNamesBuilder) -> Result<Vec<String>>;
+    async fn register_table(&self, name: &str, location: &str) -> Result<()>;
     async fn do_create_table(
         &self,
         options: CreateTableBuilder<false, NoData>,
@@ -271,6 +639,10 @@ pub(crate) trait ConnectionInternal:
     async fn do_open_table(&self, options: OpenTableBuilder) -> Result<Table>;
     async fn drop_table(&self, name: &str) -> Result<()>;
     async fn drop_db(&self) -> Result<()>;
+    /// The definition of the view `name`, None if there is no such view
+    async fn load_view(&self, name: &str) -> Result<Option<StoredView>>;
+    /// Store (or, if `view` is None, remove) the definition of the view `name`
+    async fn save_view(&self, name: &str, view: Option<&StoredView>) -> Result<()>;
 
     async fn do_create_empty_table(
         &self,
@@ -289,6 +661,8 @@ pub(crate) trait ConnectionInternal:
 pub struct Connection {
     uri: String,
     internal: Arc<dyn ConnectionInternal>,
+    admission: Option<Arc<AdmissionController>>,
+    memory_pool: Option<Arc<MemoryPool>>,
 }
 
 impl std::fmt::Display for Connection {
@@ -340,6 +714,61 @@ impl Connection {
         CreateTableBuilder::<false, NoData>::new(self.internal.clone(), name.into(), schema)
     }
 
+    /// Create an empty table from a [`TableSpec`]
+    ///
+    /// The indices in the spec are declared (see
+    /// [`CreateTableBuilder::declare_index`]) and are built once the table has
+    /// enough data.  Returns an error if the spec contains an invalid schema.
+    pub fn create_table_from_spec(
+        &self,
+        spec: &TableSpec,
+    ) -> Result<CreateTableBuilder<false, NoData>> {
+        let mut builder = self.create_empty_table(spec.name.clone(), spec.schema()?);
+        for index in &spec.indices {
+            builder = builder.declare_index(&index.columns, index.index.clone());
+        }
+        if let Some(threshold) = spec.pending_index_threshold {
+            builder = builder.pending_index_threshold(threshold);
+        }
+        Ok(builder)
+    }
+
+    /// Create a table, or open it if it already exists
+    ///
+    /// The table is created with the given data, or empty with the given
+    /// schema.  If a table with the name already exists it is opened
+    /// instead and the data is not written.  Unlike checking whether the
+    /// table exists before creating it, this is safe when several jobs
+    /// start at the same time: one of them creates the table and the others
+    /// open it.  See [`CreateTableIfNotExistsBuilder::check_schema`] to
+    /// also check the schema of an existing table.
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use arrow_schema::{DataType, Field, Schema};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let db = lancedb::connect("/tmp").execute().await.unwrap();
+    /// let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+    /// let table = db
+    ///     .create_table_if_not_exists("events", schema)
+    ///     .check_schema()
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub fn create_table_if_not_exists(
+        &self,
+        name: impl Into<String>,
+        data_or_schema: impl Into<TableSource>,
+    ) -> CreateTableIfNotExistsBuilder {
+        CreateTableIfNotExistsBuilder::new(
+            self.internal.clone(),
+            name.into(),
+            data_or_schema.into(),
+        )
+    }
+
     /// Open an existing table in the database
     ///
     /// # Arguments
@@ -359,12 +788,135 @@ impl Connection {
         self.internal.drop_table(name.as_ref()).await
     }
 
+    /// Add an existing table, stored at `location`, to the catalog of the connection
+    ///
+    /// The table can be stored anywhere, e.g. in another bucket.  This
+    /// requires a catalog, see [`ConnectBuilder::catalog`].
+    pub async fn register_table(
+        &self,
+        name: impl AsRef<str>,
+        location: impl AsRef<str>,
+    ) -> Result<()> {
+        self.internal
+            .register_table(name.as_ref(), location.as_ref())
+            .await
+    }
+
+    /// Create a view of the rows of `base_table` that match a filter
+    ///
+    /// The view is opened with [`Self::open_table`] and queried like a table
+    /// but is read only.  A virtual view (the default) adds its filter and
+    /// projection to every query of the base table, see
+    /// [`CreateViewBuilder::materialized`] to store a copy of the rows
+    /// instead.  See [`crate::table::view`] for more details.
+    ///
+    /// ```no_run
+    /// # use lancedb::table::view::ViewDefinition;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let db = lancedb::connect("/tmp").execute().await.unwrap();
+    /// let definition = ViewDefinition {
+    ///     filter: Some("tenant_id = 42".to_string()),
+    ///     projection: Some(vec!["id".to_string(), "vector".to_string()]),
+    /// };
+    /// let view = db
+    ///     .create_view("tenant_42", "documents", definition)
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub fn create_view(
+        &self,
+        name: impl Into<String>,
+        base_table: impl Into<String>,
+        definition: ViewDefinition,
+    ) -> CreateViewBuilder {
+        CreateViewBuilder::new(
+            self.internal.clone(),
+            name.into(),
+            base_table.into(),
+            definition,
+        )
+    }
+
+    /// Copy the current rows of the base table into a materialized view
+    ///
+    /// The rows of the view are replaced in a new version of its table.
+    pub async fn refresh_view(&self, name: impl AsRef<str>) -> Result<()> {
+        let name = name.as_ref();
+        let view = self.view(name).await?;
+        if !view.materialized {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the view '{}' is virtual, it always shows the current rows of its base table",
+                    name
+                ),
+            });
+        }
+        let base = self.open_table(&view.base_table).execute().await?;
+        materialize(
+            &self.internal,
+            name,
+            &base,
+            &view,
+            CreateTableMode::Overwrite,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Get the base table and the definition of a view
+    pub async fn view_definition(&self, name: impl AsRef<str>) -> Result<(String, ViewDefinition)> {
+        let view = self.view(name.as_ref()).await?;
+        Ok((view.base_table, view.definition))
+    }
+
+    /// Drop a view, and the copy of its rows if it is materialized
+    ///
+    /// The base table is not changed.
+    pub async fn drop_view(&self, name: impl AsRef<str>) -> Result<()> {
+        let name = name.as_ref();
+        let view = self.view(name).await?;
+        if view.materialized {
+            match self.internal.drop_table(name).await {
+                Ok(()) | Err(Error::TableNotFound { .. }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        self.internal.save_view(name, None).await
+    }
+
+    async fn view(&self, name: &str) -> Result<StoredView> {
+        self.internal
+            .load_view(name)
+            .await?
+            .ok_or_else(|| Error::TableNotFound {
+                name: name.to_string(),
+            })
+    }
+
     /// Drop the database
     ///
     /// This is the same as dropping all of the tables
     pub async fn drop_db(&self) -> Result<()> {
         self.internal.drop_db().await
     }
+
+    /// Get the current queue depths and counters of the admission controller
+    ///
+    /// Returns None if admission control was not configured on this connection.
+    /// See [`ConnectBuilder::admission_control`]
+    pub fn admission_metrics(&self) -> Option<AdmissionMetrics> {
+        self.admission.as_ref().map(|admission| admission.metrics())
+    }
+
+    /// Get the reserved memory and counters of the memory pool
+    ///
+    /// Returns None if no memory pool was configured on this connection.
+    /// See [`ConnectBuilder::memory_pool`]
+    pub fn memory_metrics(&self) -> Option<MemoryPoolMetrics> {
+        self.memory_pool.as_ref().map(|pool| pool.metrics())
+    }
 }
 
 #[derive(Debug)]
@@ -380,6 +932,8 @@ pub struct ConnectBuilder {
 
     /// LanceDB Cloud API key, required if using Lance Cloud
     api_key: Option<String>,
+    /// Authenticates requests to LanceDB Cloud, takes precedence over the api key
+    auth_provider: Option<Arc<dyn AuthProvider>>,
     /// LanceDB Cloud region, required if using Lance Cloud
     region: Option<String>,
     /// LanceDB Cloud host override, only required if using an on-premises Lance Cloud instance
@@ -399,6 +953,59 @@ pub struct ConnectBuilder {
     /// consistency only applies to read operations. Write operations are
     /// always consistent.
     read_consistency_interval: Option<std::time::Duration>,
+
+    /// Limits on the number of concurrent operations, if any
+    admission_config: Option<AdmissionConfig>,
+
+    /// Limits on the memory of the operations, if any
+    memory_pool_config: Option<MemoryPoolConfig>,
+
+    /// Keep the versions read by live queries when pruning old versions
+    pin_query_versions: bool,
+
+    /// Called after each commit to a table of the connection
+    commit_hooks: Vec<Arc<dyn CommitHook>>,
+
+    /// The number of batches decoded concurrently by a scan, if not the Lance default
+    decode_parallelism: Option<usize>,
+
+    /// Object store requests slower than this are logged, if not the default
+    slow_request_threshold: Option<std::time::Duration>,
+
+    /// Caches the rows selected by the filters of vector searches, if set
+    filter_cache: Option<FilterCacheConfig>,
+
+    /// Leave the vector columns out of query results by default
+    exclude_vectors: bool,
+
+    /// Adds mandatory filters and masks to the operations on every table
+    query_interceptor: Option<Arc<dyn QueryInterceptor>>,
+
+    /// Configuration of the HTTP client, only used for LanceDB Cloud
+    client_config: ClientConfig,
+
+    /// Wraps the object store when reading, e.g. for caching or metrics
+    read_store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
+    /// Wraps the object store when writing
+    write_store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
+
+    /// Provides the keys to encrypt the tables with, if set
+    encryption: Option<Arc<dyn KeyProvider>>,
+
+    /// Limits on the object store requests of the tables, if set
+    io_throttle: Option<IoThrottleConfig>,
+
+    /// Caches the files of the tables on the local disk, if set
+    disk_cache: Option<DiskCacheConfig>,
+
+    /// Keeps the hot data files of the tables on the local disk, if set
+    tiering: Option<TieringPolicy>,
+
+    /// Finds the tables instead of listing the directory, if set
+    catalog: Option<Arc<dyn Catalog>>,
+
+    /// The first invalid option, reported by [`Self::execute`]
+    option_error: Option<Error>,
 }
 
 impl ConnectBuilder {
@@ -407,10 +1014,29 @@ impl ConnectBuilder {
         Self {
             uri: uri.to_string(),
             api_key: None,
+            auth_provider: None,
             region: None,
             host_override: None,
             aws_creds: None,
             read_consistency_interval: None,
+            admission_config: None,
+            memory_pool_config: None,
+            pin_query_versions: true,
+            commit_hooks: Vec::new(),
+            decode_parallelism: None,
+            slow_request_threshold: None,
+            filter_cache: None,
+            exclude_vectors: false,
+            query_interceptor: None,
+            client_config: ClientConfig::default(),
+            read_store_wrapper: None,
+            write_store_wrapper: None,
+            encryption: None,
+            io_throttle: None,
+            disk_cache: None,
+            tiering: None,
+            catalog: None,
+            option_error: None,
         }
     }
 
@@ -419,6 +1045,15 @@ impl ConnectBuilder {
         self
     }
 
+    /// Authenticate requests to LanceDB Cloud with a custom [`AuthProvider`]
+    ///
+    /// This is needed when LanceDB Cloud is deployed behind a gateway that does
+    /// not accept api keys.  If this is set then [`Self::api_key`] is ignored.
+    pub fn auth_provider(mut self, auth_provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_provider = Some(auth_provider);
+        self
+    }
+
     pub fn region(mut self, region: &str) -> Self {
         self.region = Some(region.to_string());
         self
@@ -429,6 +1064,14 @@ impl ConnectBuilder {
         self
     }
 
+    /// Configure timeouts, retries and connection pooling of the HTTP client
+    ///
+    /// This only affects LanceDB Cloud.  See [`ClientConfig`] for the defaults.
+    pub fn client_config(mut self, client_config: ClientConfig) -> Self {
+        self.client_config = client_config;
+        self
+    }
+
     /// [`AwsCredential`] to use when connecting to S3.
     pub fn aws_creds(mut self, aws_creds: AwsCredential) -> Self {
         self.aws_creds = Some(aws_creds);
@@ -450,11 +1093,264 @@ impl ConnectBuilder {
     ///
     /// LanceDB Cloud uses eventual consistency under the hood, and is not
     /// currently configurable.
+    ///
+    /// The interval can also be a string such as `"500ms"`, see [`crate::units`].
+    /// An invalid string is reported when the connection is established.
     pub fn read_consistency_interval(
         mut self,
-        read_consistency_interval: std::time::Duration,
+        read_consistency_interval: impl IntoDuration,
     ) -> Self {
-        self.read_consistency_interval = Some(read_consistency_interval);
+        match read_consistency_interval.into_duration() {
+            Ok(interval) => self.read_consistency_interval = Some(interval),
+            Err(err) => {
+                self.option_error.get_or_insert(err);
+            }
+        }
+        self
+    }
+
+    /// Limit the number of queries and writes that can run concurrently on
+    /// tables opened through this connection.
+    ///
+    /// Operations beyond the limit wait in a bounded queue.  If the queue is full
+    /// the operation fails immediately with [`Error::Overloaded`], and if a queue
+    /// timeout is set operations that wait longer fail the same way.  Index
+    /// searches can be limited separately from scans, see
+    /// [`AdmissionConfig::max_concurrent_searches`].  This is useful
+    /// when embedding LanceDb in a multi-tenant server, where bursty traffic could
+    /// otherwise exhaust memory.
+    ///
+    /// By default there is no limit.  The current queue depths can be retrieved
+    /// with [`Connection::admission_metrics`].
+    ///
+    /// This only affects LanceDB OSS.
+    pub fn admission_control(mut self, config: AdmissionConfig) -> Self {
+        self.admission_config = Some(config);
+        self
+    }
+
+    /// Limit the memory used by queries and index builds
+    ///
+    /// Queries that collect their results before returning them (ordered and
+    /// rescored queries) and index builds reserve their memory from a pool
+    /// shared by all the tables opened through this connection.  An operation
+    /// that would exceed its own budget or the memory left in the pool fails
+    /// with [`Error::MemoryLimitExceeded`] instead of running the process out
+    /// of memory.  See [`memory`] for what is counted.
+    ///
+    /// By default there is no limit.  The reserved memory can be retrieved
+    /// with [`Connection::memory_metrics`].
+    ///
+    /// This only affects LanceDB OSS.
+    pub fn memory_pool(mut self, config: MemoryPoolConfig) -> Self {
+        self.memory_pool_config = Some(config);
+        self
+    }
+
+    /// Protect live queries from concurrent optimization
+    ///
+    /// When enabled (the default) every query stream pins the table version it
+    /// reads.  Compaction never removes files, and pruning old versions (see
+    /// [`crate::table::OptimizeAction::Prune`] and [`crate::table::OptimizeAction::All`])
+    /// keeps the oldest pinned version and every version after it, so running
+    /// [`crate::Table::optimize`] concurrently with queries never fails the
+    /// queries.  The pruning of the pinned versions is deferred to a later call.
+    ///
+    /// Pins are only known to the process that runs the queries.  Queries
+    /// running in other processes are not protected.
+    ///
+    /// This only affects LanceDB OSS.
+    pub fn pin_query_versions(mut self, enabled: bool) -> Self {
+        self.pin_query_versions = enabled;
+        self
+    }
+
+    /// Call the hook after each commit to a table opened or created through
+    /// this connection
+    ///
+    /// This can be used to keep an external catalog in sync with the versions
+    /// of the tables.  The hook receives the new version and a summary of the
+    /// operation (see [`crate::table::hooks::CommitInfo`]).  Hooks run in the
+    /// order they were added, after the commit has succeeded.  If a hook fails
+    /// the operation returns an error, but the commit is not undone.
+    ///
+    /// This only affects LanceDB OSS.
+    pub fn commit_hook(mut self, hook: Arc<dyn CommitHook>) -> Self {
+        self.commit_hooks.push(hook);
+        self
+    }
+
+    /// The number of batches that queries read and decode concurrently
+    ///
+    /// Decoding is usually limited by memory bandwidth rather than by the
+    /// number of cores.  On machines with many cores a lower value can give
+    /// the same throughput with less memory traffic and less memory use.  A
+    /// single query can override this with
+    /// [`crate::query::QueryExecutionOptions::decode_parallelism`].
+    ///
+    /// By default the Lance default is used.  This only affects LanceDB OSS.
+    pub fn decode_parallelism(mut self, decode_parallelism: usize) -> Self {
+        self.decode_parallelism = Some(decode_parallelism);
+        self
+    }
+
+    /// Log a warning for the object store requests slower than `threshold`
+    ///
+    /// The slow requests are also counted by [`crate::Table::io_stats`].  The
+    /// default is [`crate::io::metrics::DEFAULT_SLOW_REQUEST_THRESHOLD`].
+    ///
+    /// The threshold can also be a string such as `"500ms"`, see
+    /// [`crate::units`].  This only affects LanceDB OSS.
+    pub fn slow_request_threshold(mut self, threshold: impl IntoDuration) -> Self {
+        match threshold.into_duration() {
+            Ok(threshold) => self.slow_request_threshold = Some(threshold),
+            Err(err) => {
+                self.option_error.get_or_insert(err);
+            }
+        }
+        self
+    }
+
+    /// Leave the vector columns out of the results of queries by default
+    ///
+    /// Vector columns (all `FixedSizeList` columns) are usually the largest
+    /// columns of a table, and most applications only need the ids and
+    /// metadata of the results.  When enabled, queries that do not select
+    /// their columns do not return the vector columns, unless they call
+    /// [`crate::query::QueryBase::with_vectors`].  A table can override this
+    /// with [`OpenTableBuilder::exclude_vectors`].
+    ///
+    /// Disabled by default.  This only affects LanceDB OSS.
+    pub fn exclude_vectors(mut self, exclude: bool) -> Self {
+        self.exclude_vectors = exclude;
+        self
+    }
+
+    /// Cache the rows selected by the filters of prefiltered vector searches
+    ///
+    /// Repeated searches with the same selective filter (e.g. the tenant of a
+    /// multi-tenant table) then skip evaluating the filter.  See
+    /// [`crate::query::filter_cache`] for the details and
+    /// [`crate::Table::filter_cache_metrics`] for the hit rate.  Each table
+    /// handle has its own cache.
+    ///
+    /// Disabled by default.  This only affects LanceDB OSS.
+    pub fn filter_cache(mut self, config: FilterCacheConfig) -> Self {
+        self.filter_cache = Some(config);
+        self
+    }
+
+    /// Restrict every query, count, update and delete on the tables of the connection
+    ///
+    /// The interceptor can add a mandatory filter (e.g. `tenant_id = 42`) and
+    /// column masks to each operation, so that a multi-tenant service can
+    /// enforce isolation in one place.  See [`crate::table::interceptor`] for
+    /// the details.
+    ///
+    /// This only affects LanceDB OSS.
+    pub fn query_interceptor(mut self, interceptor: Arc<dyn QueryInterceptor>) -> Self {
+        self.query_interceptor = Some(interceptor);
+        self
+    }
+
+    /// Wrap the object store of every table with the given wrapper
+    ///
+    /// The wrapper is applied to both reads (scans, index lookups) and writes.
+    /// Use [`Self::read_store_wrapper`] and [`Self::write_store_wrapper`] to
+    /// wrap only one of them.  This only affects LanceDB OSS.
+    pub fn store_wrapper(mut self, wrapper: Arc<dyn WrappingObjectStore>) -> Self {
+        self.read_store_wrapper = Some(wrapper.clone());
+        self.write_store_wrapper = Some(wrapper);
+        self
+    }
+
+    /// Wrap the object store with the given wrapper when reading
+    ///
+    /// Reads include scans, vector searches and index lookups.  Caching or
+    /// metrics wrappers usually belong here.  This only affects LanceDB OSS.
+    pub fn read_store_wrapper(mut self, wrapper: Arc<dyn WrappingObjectStore>) -> Self {
+        self.read_store_wrapper = Some(wrapper);
+        self
+    }
+
+    /// Wrap the object store with the given wrapper when writing
+    ///
+    /// This can't be combined with the `mirroredStore` uri parameter.  This
+    /// only affects LanceDB OSS.
+    pub fn write_store_wrapper(mut self, wrapper: Arc<dyn WrappingObjectStore>) -> Self {
+        self.write_store_wrapper = Some(wrapper);
+        self
+    }
+
+    /// Encrypt the files of every table with AES-256-GCM
+    ///
+    /// The key of each table is requested from the provider.  Any other store
+    /// wrappers are applied beneath the encryption, so they only see encrypted
+    /// files.  See [`crate::io::encryption`] for the details.  Tables written
+    /// without encryption can not be read with it, and vice versa.
+    ///
+    /// This only affects LanceDB OSS.
+    pub fn encryption(mut self, provider: Arc<dyn KeyProvider>) -> Self {
+        self.encryption = Some(provider);
+        self
+    }
+
+    /// Limit the object store requests made by the tables of the connection
+    ///
+    /// Caps the number of requests in flight and the number of requests
+    /// started per second, across all of the tables and operations of the
+    /// connection.  Use it to keep background jobs such as
+    /// [`crate::Table::optimize`] from saturating the store and slowing down
+    /// the queries of other services.  See [`crate::io::throttle`] for
+    /// details.
+    ///
+    /// By default there is no limit.  This only affects LanceDB OSS.
+    pub fn io_throttle(mut self, config: IoThrottleConfig) -> Self {
+        self.io_throttle = Some(config);
+        self
+    }
+
+    /// Cache the files of the tables on the local disk
+    ///
+    /// Reads of data files, deletion files and index files go through a
+    /// cache in the given directory, which keeps the most recently used
+    /// parts of the files up to the given capacity.  This makes repeated
+    /// queries of the same partitions much faster when the tables are on S3
+    /// or GCS.  The cache is kept between runs.  See [`crate::io::disk_cache`]
+    /// for details.
+    ///
+    /// The cache is not used for tables on the local file system.  This only
+    /// affects LanceDB OSS.
+    pub fn disk_cache(mut self, config: DiskCacheConfig) -> Self {
+        self.disk_cache = Some(config);
+        self
+    }
+
+    /// Keep the hot data files of the tables in a local tier
+    ///
+    /// Every data file stays in the object store, and the files that were
+    /// written or read often recently, as set by the policy, are also copied
+    /// to a local directory and read from there.  Use
+    /// [`crate::Table::tiering_report`] to see which fragments are local.  See
+    /// [`crate::io::tiering`] for details.
+    ///
+    /// Tiering is not used for tables on the local file system.  This only
+    /// affects LanceDB OSS.
+    pub fn tiering(mut self, policy: TieringPolicy) -> Self {
+        self.tiering = Some(policy);
+        self
+    }
+
+    /// Find the tables through a catalog instead of listing the directory
+    ///
+    /// The names and locations of the tables come from the catalog, created
+    /// tables are registered with it and dropped tables are removed from it.
+    /// New tables are still stored in the directory of the database.  See
+    /// [`crate::catalog`] for the included catalogs.
+    ///
+    /// This only affects LanceDB OSS.
+    pub fn catalog(mut self, catalog: Arc<dyn Catalog>) -> Self {
+        self.catalog = Some(catalog);
         self
     }
 
@@ -463,18 +1359,28 @@ impl ConnectBuilder {
         let region = self.region.ok_or_else(|| Error::InvalidInput {
             message: "A region is required when connecting to LanceDb Cloud".to_string(),
         })?;
-        let api_key = self.api_key.ok_or_else(|| Error::InvalidInput {
-            message: "An api_key is required when connecting to LanceDb Cloud".to_string(),
-        })?;
+        let auth =
+            match (self.auth_provider, self.api_key) {
+                (Some(auth_provider), _) => auth_provider,
+                (None, Some(api_key)) => Arc::new(auth::ApiKeyAuth::new(api_key)),
+                (None, None) => return Err(Error::InvalidInput {
+                    message:
+                        "An api_key or auth_provider is required when connecting to LanceDb Cloud"
+                            .to_string(),
+                }),
+            };
         let internal = Arc::new(crate::remote::db::RemoteDatabase::try_new(
             &self.uri,
-            &api_key,
+            auth,
             &region,
             self.host_override,
+            self.client_config,
         )?);
         Ok(Connection {
             internal,
             uri: self.uri,
+            admission: None,
+            memory_pool: None,
         })
     }
 
@@ -487,14 +1393,21 @@ impl ConnectBuilder {
     }
 
     /// Establishes a connection to the database
-    pub async fn execute(self) -> Result<Connection> {
+    pub async fn execute(mut self) -> Result<Connection> {
+        if let Some(err) = self.option_error.take() {
+            return Err(err);
+        }
         if self.uri.starts_with("db") {
             self.execute_remote()
         } else {
             let internal = Arc::new(Database::connect_with_options(&self).await?);
+            let admission = internal.admission.clone();
+            let memory_pool = internal.memory_pool.clone();
             Ok(Connection {
                 internal,
                 uri: self.uri,
+                admission,
+                memory_pool,
             })
         }
     }
@@ -518,10 +1431,43 @@ struct Database {
     pub(crate) uri: String,
     pub(crate) base_path: object_store::path::Path,
 
-    // the object store wrapper to use on write path
+    // wraps the object store on both the read and the write path
     pub(crate) store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
 
     read_consistency_interval: Option<std::time::Duration>,
+
+    // shared by all tables opened through this database
+    admission: Option<Arc<AdmissionController>>,
+
+    // shared by all tables opened through this database, see ConnectBuilder::memory_pool
+    memory_pool: Option<Arc<MemoryPool>>,
+
+    // whether queries pin the version they read, see ConnectBuilder::pin_query_versions
+    pin_query_versions: bool,
+
+    // called after each commit to a table, see ConnectBuilder::commit_hook
+    commit_hooks: Vec<Arc<dyn CommitHook>>,
+
+    // the default of QueryExecutionOptions::decode_parallelism for every table
+    decode_parallelism: Option<usize>,
+
+    // see ConnectBuilder::slow_request_threshold
+    slow_request_threshold: Option<std::time::Duration>,
+
+    // the filter cache of every table, see ConnectBuilder::filter_cache
+    filter_cache: Option<FilterCacheConfig>,
+
+    // restricts the operations on every table, see ConnectBuilder::query_interceptor
+    query_interceptor: Option<Arc<dyn QueryInterceptor>>,
+
+    // the default of every table, see ConnectBuilder::exclude_vectors
+    exclude_vectors: bool,
+
+    // finds the tables instead of the directory listing, see ConnectBuilder::catalog
+    catalog: Option<Arc<dyn Catalog>>,
+
+    // the tiers of the data files of every table, see ConnectBuilder::tiering
+    tiering: Option<Arc<Tiering>>,
 }
 
 impl std::fmt::Display for Database {
@@ -542,6 +1488,24 @@ impl std::fmt::Display for Database {
     }
 }
 
+/// The schema metadata key used to store the token of [`CreateTableBuilder::idempotency_token`]
+pub(crate) const IDEMPOTENCY_TOKEN_KEY: &str = "lancedb:idempotency_token";
+
+/// Add the idempotency token to the schema of the initial data
+fn with_idempotency_token(
+    data: Box<dyn RecordBatchReader + Send>,
+    token: &str,
+) -> Box<dyn RecordBatchReader + Send> {
+    let mut metadata = data.schema().metadata().clone();
+    metadata.insert(IDEMPOTENCY_TOKEN_KEY.to_string(), token.to_string());
+    let schema = Arc::new(data.schema().as_ref().clone().with_metadata(metadata));
+    let batch_schema = schema.clone();
+    Box::new(RecordBatchIterator::new(
+        data.map(move |batch| batch.and_then(|batch| batch.with_schema(batch_schema.clone()))),
+        schema,
+    ))
+}
+
 const LANCE_EXTENSION: &str = "lance";
 const ENGINE: &str = "engine";
 const MIRRORED_STORE: &str = "mirroredStore";
@@ -549,6 +1513,73 @@ const MIRRORED_STORE: &str = "mirroredStore";
 /// A connection to LanceDB
 impl Database {
     async fn connect_with_options(options: &ConnectBuilder) -> Result<Self> {
+        let mut database = Self::connect_with_uri(options).await?;
+        let write_store_wrapper =
+            match (database.store_wrapper.take(), &options.write_store_wrapper) {
+                (Some(_), Some(_)) => {
+                    return Err(Error::InvalidInput {
+                        message: "a write store wrapper can not be used with a mirrored store"
+                            .to_string(),
+                    })
+                }
+                (mirrored, wrapper) => mirrored.or_else(|| wrapper.clone()),
+            };
+        database.store_wrapper =
+            ReadWriteStoreWrapper::new(options.read_store_wrapper.clone(), write_store_wrapper)
+                .into_wrapper();
+        // The throttle only sees the requests that miss the disk cache and the
+        // local tier, which only see encrypted files
+        if let Some(config) = &options.io_throttle {
+            config.validate()?;
+            let wrapper = ThrottleWrapper::new(config).around(database.store_wrapper.take());
+            database.store_wrapper = Some(Arc::new(wrapper));
+        }
+        if let Some(config) = &options.disk_cache {
+            // A local table is already on disk
+            if !database.object_store.is_local() {
+                let wrapper = DiskCacheWrapper::open(config)?.around(database.store_wrapper.take());
+                database.store_wrapper = Some(Arc::new(wrapper));
+            }
+        }
+        if let Some(policy) = &options.tiering {
+            if !database.object_store.is_local() {
+                let tiering = Arc::new(Tiering::open(policy)?);
+                let wrapper =
+                    TieringWrapper::new(tiering.clone()).around(database.store_wrapper.take());
+                database.store_wrapper = Some(Arc::new(wrapper));
+                database.tiering = Some(tiering);
+            }
+        }
+        if let Some(provider) = &options.encryption {
+            let wrapper =
+                EncryptionWrapper::new(provider.clone()).around(database.store_wrapper.take());
+            database.store_wrapper = Some(Arc::new(wrapper));
+        }
+        database.admission = options
+            .admission_config
+            .clone()
+            .map(|config| Arc::new(AdmissionController::new(config)));
+        database.memory_pool = options
+            .memory_pool_config
+            .clone()
+            .map(|config| Arc::new(MemoryPool::new(config)));
+        database.pin_query_versions = options.pin_query_versions;
+        database.commit_hooks = options.commit_hooks.clone();
+        if options.decode_parallelism == Some(0) {
+            return Err(Error::InvalidInput {
+                message: "the decode parallelism must be at least 1".to_string(),
+            });
+        }
+        database.decode_parallelism = options.decode_parallelism;
+        database.slow_request_threshold = options.slow_request_threshold;
+        database.filter_cache = options.filter_cache.clone();
+        database.query_interceptor = options.query_interceptor.clone();
+        database.exclude_vectors = options.exclude_vectors;
+        database.catalog = options.catalog.clone();
+        Ok(database)
+    }
+
+    async fn connect_with_uri(options: &ConnectBuilder) -> Result<Self> {
         let uri = &options.uri;
         let parse_res = url::Url::parse(uri);
 
@@ -641,6 +1672,17 @@ impl Database {
                     object_store,
                     store_wrapper: write_store_wrapper,
                     read_consistency_interval: options.read_consistency_interval,
+                    admission: None,
+                    memory_pool: None,
+                    pin_query_versions: true,
+                    commit_hooks: Vec::new(),
+                    decode_parallelism: None,
+                    slow_request_threshold: None,
+                    filter_cache: None,
+                    query_interceptor: None,
+                    exclude_vectors: false,
+                    catalog: None,
+                    tiering: None,
                 })
             }
             Err(_) => Self::open_path(uri, options.read_consistency_interval).await,
@@ -662,6 +1704,17 @@ impl Database {
             object_store,
             store_wrapper: None,
             read_consistency_interval,
+            admission: None,
+            memory_pool: None,
+            pin_query_versions: true,
+            commit_hooks: Vec::new(),
+            decode_parallelism: None,
+            slow_request_threshold: None,
+            filter_cache: None,
+            query_interceptor: None,
+            exclude_vectors: false,
+            catalog: None,
+            tiering: None,
         })
     }
 
@@ -703,6 +1756,16 @@ impl Database {
 #[async_trait::async_trait]
 impl ConnectionInternal for Database {
     async fn table_names(&self, options: TableNamesBuilder) -> Result<Vec<String>> {
+        if let Some(catalog) = &self.catalog {
+            let mut names = catalog.list_tables().await?;
+            if let Some(start_after) = options.start_after {
+                names.retain(|name| name.as_str() > start_after.as_str());
+            }
+            if let Some(limit) = options.limit {
+                names.truncate(limit as usize);
+            }
+            return Ok(names);
+        }
         let mut f = self
             .object_store
             .read_dir(self.base_path.clone())
@@ -732,17 +1795,49 @@ impl ConnectionInternal for Database {
         Ok(f)
     }
 
+    async fn register_table(&self, name: &str, location: &str) -> Result<()> {
+        let Some(catalog) = &self.catalog else {
+            return Err(Error::InvalidInput {
+                message: "registering a table requires a catalog, see ConnectBuilder::catalog"
+                    .to_string(),
+            });
+        };
+        // Check that there is a table at the location
+        Dataset::open(location).await?;
+        catalog.register_table(name, location).await
+    }
+
     async fn do_create_table(
         &self,
         options: CreateTableBuilder<false, NoData>,
         data: Box<dyn RecordBatchReader + Send>,
     ) -> Result<Table> {
-        let table_uri = self.table_uri(&options.name)?;
+        let registered = match &self.catalog {
+            Some(catalog) => catalog.table_location(&options.name).await?,
+            None => None,
+        };
+        let needs_registration = self.catalog.is_some() && registered.is_none();
+        let table_uri = match registered {
+            Some(location) => location,
+            None => self.table_uri(&options.name)?,
+        };
+        if options.write_options.bulk_load {
+            return Err(Error::InvalidInput {
+                message: "a bulk load adds to an existing table, create the table first and \
+                          stage the data with Table::add"
+                    .to_string(),
+            });
+        }
 
+        let commit_metadata = options.write_options.commit_metadata;
         let mut write_params = options.write_options.lance_write_params.unwrap_or_default();
         if matches!(&options.mode, CreateTableMode::Overwrite) {
             write_params.mode = WriteMode::Overwrite;
         }
+        let data = match &options.idempotency_token {
+            Some(token) => with_idempotency_token(data, token),
+            None => data,
+        };
 
         match NativeTable::create(
             &table_uri,
@@ -754,9 +1849,44 @@ impl ConnectionInternal for Database {
         )
         .await
         {
-            Ok(table) => Ok(Table::new(Arc::new(table))),
+            Ok(table) => {
+                let table = table
+                    .with_admission_controller(self.admission.clone())
+                    .with_memory_pool(self.memory_pool.clone())
+                    .with_version_pinning(self.pin_query_versions)
+                    .with_commit_hooks(self.commit_hooks.clone())
+                    .with_decode_parallelism(self.decode_parallelism)
+                    .with_slow_request_threshold(self.slow_request_threshold)
+                    .with_tiering(self.tiering.clone())
+                    .with_filter_cache(self.filter_cache.clone())
+                    .with_query_interceptor(self.query_interceptor.clone())
+                    .with_vectors_excluded(self.exclude_vectors);
+                if let (true, Some(catalog)) = (needs_registration, &self.catalog) {
+                    catalog.register_table(&options.name, &table_uri).await?;
+                }
+                let version = table.dataset.get().await?.version().version;
+                table
+                    .record_commit_metadata(version, &commit_metadata)
+                    .await;
+                table.run_commit_hooks("create", version, None).await?;
+                Ok(Table::new(Arc::new(table)))
+            }
             Err(Error::TableAlreadyExists { name }) => match options.mode {
-                CreateTableMode::Create => Err(Error::TableAlreadyExists { name }),
+                CreateTableMode::Create => {
+                    let Some(token) = options.idempotency_token else {
+                        return Err(Error::TableAlreadyExists { name });
+                    };
+                    // A retry of a create that already succeeded
+                    let table = OpenTableBuilder::new(options.parent, options.name)
+                        .execute()
+                        .await?;
+                    let schema = table.schema().await?;
+                    if schema.metadata().get(IDEMPOTENCY_TOKEN_KEY) == Some(&token) {
+                        Ok(table)
+                    } else {
+                        Err(Error::TableAlreadyExists { name })
+                    }
+                }
                 CreateTableMode::ExistOk(callback) => {
                     let builder = OpenTableBuilder::new(options.parent, options.name);
                     let builder = (callback)(builder);
@@ -769,21 +1899,84 @@ impl ConnectionInternal for Database {
     }
 
     async fn do_open_table(&self, options: OpenTableBuilder) -> Result<Table> {
-        let table_uri = self.table_uri(&options.name)?;
+        let table_uri = match &self.catalog {
+            Some(catalog) => catalog
+                .table_location(&options.name)
+                .await?
+                .ok_or_else(|| Error::TableNotFound {
+                    name: options.name.clone(),
+                })?,
+            None => self.table_uri(&options.name)?,
+        };
+        let native_table = match NativeTable::open_with_params(
+            &table_uri,
+            &options.name,
+            self.store_wrapper.clone(),
+            options.lance_read_params.clone(),
+            self.read_consistency_interval,
+        )
+        .await
+        {
+            Ok(table) => table,
+            // A virtual view has no table, it reads its base table
+            Err(Error::TableNotFound { name }) => {
+                return match self.load_view(&options.name).await? {
+                    Some(view) if !view.materialized => {
+                        let mut base = options.clone();
+                        base.name = view.base_table;
+                        let base = self.do_open_table(base).await?;
+                        Ok(Table::new(Arc::new(ViewTable::new(
+                            options.name,
+                            base.inner,
+                            view.definition,
+                        ))))
+                    }
+                    _ => Err(Error::TableNotFound { name }),
+                };
+            }
+            Err(err) => return Err(err),
+        };
         let native_table = Arc::new(
-            NativeTable::open_with_params(
-                &table_uri,
-                &options.name,
-                self.store_wrapper.clone(),
-                options.lance_read_params,
-                self.read_consistency_interval,
-            )
-            .await?,
+            native_table
+                .with_admission_controller(self.admission.clone())
+                .with_memory_pool(self.memory_pool.clone())
+                .with_version_pinning(self.pin_query_versions)
+                .with_unmasked_access(options.unmasked)
+                .with_decode_parallelism(self.decode_parallelism)
+                .with_slow_request_threshold(self.slow_request_threshold)
+                .with_tiering(self.tiering.clone())
+                .with_filter_cache(self.filter_cache.clone())
+                .with_query_interceptor(self.query_interceptor.clone())
+                .with_vectors_excluded(options.exclude_vectors.unwrap_or(self.exclude_vectors))
+                .with_audit_context(options.audit_context)
+                .with_commit_hooks(
+                    self.commit_hooks
+                        .iter()
+                        .chain(&options.commit_hooks)
+                        .cloned()
+                        .collect(),
+                ),
         );
         Ok(Table::new(native_table))
     }
 
     async fn drop_table(&self, name: &str) -> Result<()> {
+        if let Some(catalog) = &self.catalog {
+            let location =
+                catalog
+                    .table_location(name)
+                    .await?
+                    .ok_or_else(|| Error::TableNotFound {
+                        name: name.to_owned(),
+                    })?;
+            let (object_store, path) = ObjectStore::from_uri(&location).await?;
+            match object_store.remove_dir_all(path).await {
+                // The data may already be gone
+                Ok(()) | Err(lance::Error::NotFound { .. }) => {}
+                Err(err) => return Err(err.into()),
+            }
+            return catalog.deregister_table(name).await;
+        }
         let dir_name = format!("{}.{}", name, LANCE_EXTENSION);
         let full_path = self.base_path.child(dir_name.clone());
         self.object_store
@@ -803,6 +1996,14 @@ impl ConnectionInternal for Database {
     async fn drop_db(&self) -> Result<()> {
         todo!()
     }
+
+    async fn load_view(&self, name: &str) -> Result<Option<StoredView>> {
+        read_view(&self.object_store, &self.base_path, name).await
+    }
+
+    async fn save_view(&self, name: &str, view: Option<&StoredView>) -> Result<()> {
+        write_view(&self.object_store, &self.base_path, name, view).await
+    }
 }
 
 #[cfg(test)]
@@ -821,6 +2022,26 @@ mod tests {
         assert_eq!(db.uri, uri);
     }
 
+    #[tokio::test]
+    async fn test_connect_with_string_options() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let db = connect(uri)
+            .read_consistency_interval("500ms")
+            .execute()
+            .await
+            .unwrap();
+        assert!(db.to_string().contains("read_consistency_interval=0.5s"));
+
+        let err = connect(uri)
+            .read_consistency_interval("soon")
+            .execute()
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{:?}", err);
+    }
+
     #[cfg(not(windows))]
     #[tokio::test]
     async fn test_connect_relative() {
@@ -842,6 +2063,172 @@ mod tests {
         assert_eq!(db.uri, relative_uri.to_str().unwrap().to_string());
     }
 
+    #[tokio::test]
+    async fn test_create_table_if_not_exists() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let db = connect(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, false)]));
+
+        // Concurrent creates all succeed with the same table
+        let (first, second) = futures::join!(
+            db.create_table_if_not_exists("test", schema.clone())
+                .execute(),
+            db.create_table_if_not_exists("test", schema.clone())
+                .execute(),
+        );
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(db.table_names().execute().await.unwrap(), vec!["test"]);
+
+        // The data is not written to an existing table
+        let data: Box<dyn RecordBatchReader + Send> = Box::new(RecordBatchIterator::new(
+            vec![Ok(arrow_array::RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(arrow_array::Int32Array::from(vec![1, 2]))],
+            )
+            .unwrap())],
+            schema.clone(),
+        ));
+        let table = db
+            .create_table_if_not_exists("test", data)
+            .check_schema()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 0);
+
+        let other = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Int64, false),
+            Field::new("y", DataType::Utf8, true),
+        ]));
+        let err = db
+            .create_table_if_not_exists("test", other.clone())
+            .check_schema()
+            .execute()
+            .await
+            .err()
+            .unwrap();
+        match err {
+            Error::Schema { message } => {
+                assert!(
+                    message.contains("'x' is Int64 in the data but Int32"),
+                    "{}",
+                    message
+                );
+                assert!(message.contains("'y' is not in the table"), "{}", message);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+        // Without the check the existing table is returned as is
+        db.create_table_if_not_exists("test", other)
+            .execute()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_views() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let db = connect(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("tenant", DataType::Utf8, false),
+        ]));
+        let data = |ids: std::ops::Range<i32>| {
+            let tenants = ids
+                .clone()
+                .map(|id| if id % 2 == 0 { "a" } else { "b" })
+                .collect::<Vec<_>>();
+            RecordBatchIterator::new(
+                vec![Ok(arrow_array::RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(arrow_array::Int32Array::from_iter_values(ids)),
+                        Arc::new(arrow_array::StringArray::from(tenants)),
+                    ],
+                )
+                .unwrap())],
+                schema.clone(),
+            )
+        };
+        let base = db
+            .create_table("docs", data(0..10))
+            .execute()
+            .await
+            .unwrap();
+        let definition = ViewDefinition {
+            filter: Some("tenant = 'a'".to_string()),
+            projection: Some(vec!["id".to_string()]),
+        };
+
+        db.create_view("tenant_a", "docs", definition.clone())
+            .execute()
+            .await
+            .unwrap();
+        let view = db.open_table("tenant_a").execute().await.unwrap();
+        assert_eq!(view.schema().await.unwrap().fields().len(), 1);
+        assert_eq!(view.count_rows(None).await.unwrap(), 5);
+        assert_eq!(
+            view.count_rows(Some("id > 4".to_string())).await.unwrap(),
+            3
+        );
+        let batches = view
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].num_columns(), 1);
+        assert!(matches!(
+            view.add(data(10..12)).execute().await,
+            Err(Error::NotSupported { .. })
+        ));
+        // A virtual view shows the current rows of the base table
+        base.add(data(10..20)).execute().await.unwrap();
+        assert_eq!(view.count_rows(None).await.unwrap(), 10);
+
+        db.create_view("tenant_a_copy", "docs", definition.clone())
+            .materialized()
+            .execute()
+            .await
+            .unwrap();
+        base.add(data(20..30)).execute().await.unwrap();
+        let copy = db.open_table("tenant_a_copy").execute().await.unwrap();
+        assert_eq!(copy.count_rows(None).await.unwrap(), 10);
+        db.refresh_view("tenant_a_copy").await.unwrap();
+        let copy = db.open_table("tenant_a_copy").execute().await.unwrap();
+        assert_eq!(copy.count_rows(None).await.unwrap(), 15);
+        assert!(matches!(
+            db.refresh_view("tenant_a").await,
+            Err(Error::InvalidInput { .. })
+        ));
+
+        assert!(matches!(
+            db.create_view("tenant_a", "docs", definition.clone())
+                .execute()
+                .await,
+            Err(Error::TableAlreadyExists { .. })
+        ));
+        assert!(matches!(
+            db.create_view("docs", "docs", definition).execute().await,
+            Err(Error::TableAlreadyExists { .. })
+        ));
+        let (base_table, _) = db.view_definition("tenant_a").await.unwrap();
+        assert_eq!(base_table, "docs");
+
+        db.drop_view("tenant_a").await.unwrap();
+        db.drop_view("tenant_a_copy").await.unwrap();
+        assert!(matches!(
+            db.open_table("tenant_a").execute().await,
+            Err(Error::TableNotFound { .. })
+        ));
+        assert_eq!(db.table_names().execute().await.unwrap(), vec!["docs"]);
+    }
+
     #[tokio::test]
     async fn test_table_names() {
         let tmp_dir = tempdir().unwrap();
@@ -935,6 +2322,128 @@ mod tests {
         assert_eq!(tables.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_catalog() {
+        let tmp_dir = tempdir().unwrap();
+        let db_uri = tmp_dir.path().join("db");
+        let catalog_uri = tmp_dir.path().join("catalog");
+        let catalog = Arc::new(
+            crate::catalog::filesystem::FileSystemCatalog::open(catalog_uri.to_str().unwrap())
+                .await
+                .unwrap(),
+        );
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, false)]));
+
+        // A table outside of the database directory
+        let other = connect(tmp_dir.path().join("other").to_str().unwrap())
+            .execute()
+            .await
+            .unwrap();
+        other
+            .create_empty_table("elsewhere", schema.clone())
+            .execute()
+            .await
+            .unwrap();
+
+        let db = connect(db_uri.to_str().unwrap())
+            .catalog(catalog.clone())
+            .execute()
+            .await
+            .unwrap();
+        db.create_empty_table("local", schema.clone())
+            .execute()
+            .await
+            .unwrap();
+        // A table in the directory but not in the catalog is not found
+        create_dir_all(db_uri.join("unlisted.lance")).unwrap();
+        assert_eq!(db.table_names().execute().await.unwrap(), vec!["local"]);
+        assert!(matches!(
+            db.open_table("unlisted").execute().await,
+            Err(Error::TableNotFound { .. })
+        ));
+
+        let location = tmp_dir.path().join("other").join("elsewhere.lance");
+        db.register_table("remote", location.to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(db
+            .register_table("missing", "/does/not/exist")
+            .await
+            .is_err());
+        assert_eq!(
+            db.table_names().execute().await.unwrap(),
+            vec!["local", "remote"]
+        );
+        let table = db.open_table("remote").execute().await.unwrap();
+        assert_eq!(table.schema().await.unwrap().fields(), schema.fields());
+
+        db.drop_table("remote").await.unwrap();
+        assert_eq!(catalog.list_tables().await.unwrap(), vec!["local"]);
+        assert!(other.open_table("elsewhere").execute().await.is_err());
+
+        // Without a catalog the connection lists the directory
+        let db = connect(db_uri.to_str().unwrap()).execute().await.unwrap();
+        assert_eq!(
+            db.table_names().execute().await.unwrap(),
+            vec!["local", "unlisted"]
+        );
+        assert!(db.register_table("remote", "/x").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_io_throttle() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let invalid = IoThrottleConfig {
+            max_concurrent_requests: Some(0),
+            ..Default::default()
+        };
+        assert!(connect(uri).io_throttle(invalid).execute().await.is_err());
+
+        let db = connect(uri)
+            .io_throttle(IoThrottleConfig {
+                max_concurrent_requests: Some(1),
+                max_requests_per_second: Some(1000.0),
+            })
+            .execute()
+            .await
+            .unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, false)]));
+        let table = db
+            .create_empty_table("test", schema)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_admission_metrics() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let db = connect(uri).execute().await.unwrap();
+        assert!(db.admission_metrics().is_none());
+
+        let db = connect(uri)
+            .admission_control(AdmissionConfig::default())
+            .execute()
+            .await
+            .unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, false)]));
+        let table = db
+            .create_empty_table("test", schema)
+            .execute()
+            .await
+            .unwrap();
+        table.delete("x > 0").await.unwrap();
+
+        let metrics = db.admission_metrics().unwrap();
+        assert_eq!(metrics.writes.admitted_total, 1);
+        assert_eq!(metrics.writes.in_flight, 0);
+    }
+
     #[tokio::test]
     async fn test_create_table_already_exists() {
         let tmp_dir = tempdir().unwrap();
@@ -968,4 +2477,44 @@ mod tests {
             .unwrap();
         assert_eq!(other_schema, overwritten.schema().await.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_create_table_idempotency_token() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let db = connect(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, false)]));
+        db.create_empty_table("test", schema.clone())
+            .idempotency_token("token-1")
+            .execute()
+            .await
+            .unwrap();
+
+        // A retry with the same token returns the existing table
+        let table = db
+            .create_empty_table("test", schema.clone())
+            .idempotency_token("token-1")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 0);
+        // The token is not part of the table properties
+        assert!(table.to_spec().await.unwrap().properties.is_empty());
+
+        let err = db
+            .create_empty_table("test", schema.clone())
+            .idempotency_token("token-2")
+            .execute()
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::TableAlreadyExists { .. }));
+        let err = db
+            .create_empty_table("test", schema)
+            .execute()
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::TableAlreadyExists { .. }));
+    }
 }
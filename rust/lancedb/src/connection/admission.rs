@@ -0,0 +1,428 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Admission control for operations issued through a [`super::Connection`]
+//!
+//! When LanceDb is embedded in a server it is possible for bursty traffic to
+//! issue more concurrent operations than the process can handle.  The admission
+//! controller limits the number of queries and writes that can run at once.
+//! Operations beyond that limit wait in a bounded queue and, once the queue is
+//! full, are rejected with [`crate::Error::Overloaded`].  Operations that wait
+//! longer than the queue timeout, if set, are rejected the same way.
+//!
+//! Index searches (vector queries) can be given their own limit.  A search
+//! holds little memory compared to a scan, which decodes every matching row,
+//! so a service can run many searches while only allowing a few scans.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+use crate::runtime;
+
+/// Configuration for connection-level admission control
+///
+/// See [`super::ConnectBuilder::admission_control`]
+#[derive(Debug, Clone)]
+pub struct AdmissionConfig {
+    /// The maximum number of queries that may run at the same time
+    ///
+    /// A query is considered to be running until its result stream is dropped.
+    /// This includes the index searches unless [`Self::max_concurrent_searches`]
+    /// is set.
+    pub max_concurrent_queries: usize,
+    /// The maximum number of index searches (vector queries) that may run at
+    /// the same time, if they are limited separately from the other queries
+    pub max_concurrent_searches: Option<usize>,
+    /// The maximum number of writes (add, update, delete, merge_insert) that may
+    /// run at the same time
+    pub max_concurrent_writes: usize,
+    /// The maximum number of operations (of each kind) that may wait for a slot
+    ///
+    /// Operations that arrive when the queue is full are rejected immediately.
+    pub max_queued: usize,
+    /// The maximum time an operation may wait for a slot, if limited
+    ///
+    /// Operations that wait longer are rejected.
+    pub queue_timeout: Option<Duration>,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_queries: 64,
+            max_concurrent_searches: None,
+            max_concurrent_writes: 8,
+            max_queued: 256,
+            queue_timeout: None,
+        }
+    }
+}
+
+/// The kind of operation being admitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    Query,
+    /// A query that searches an index, e.g. a vector query
+    Search,
+    Write,
+}
+
+impl std::fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Query => write!(f, "query"),
+            Self::Search => write!(f, "search"),
+            Self::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// A snapshot of the state of one lane (queries, searches or writes) of the controller
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LaneMetrics {
+    /// Number of operations currently holding a slot
+    pub in_flight: usize,
+    /// Number of operations currently waiting for a slot
+    pub queued: usize,
+    /// Total number of operations that have been admitted
+    pub admitted_total: u64,
+    /// Total number of operations that were rejected because the queue was full
+    pub rejected_total: u64,
+    /// Total number of operations that were rejected because they waited
+    /// longer than the queue timeout
+    pub timed_out_total: u64,
+}
+
+/// A snapshot of the admission controller metrics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdmissionMetrics {
+    pub queries: LaneMetrics,
+    /// Empty if the searches are counted with the queries
+    pub searches: LaneMetrics,
+    pub writes: LaneMetrics,
+}
+
+#[derive(Debug)]
+struct Lane {
+    kind: OperationKind,
+    max_concurrent: usize,
+    max_queued: usize,
+    queue_timeout: Option<Duration>,
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+    admitted_total: AtomicU64,
+    rejected_total: AtomicU64,
+    timed_out_total: AtomicU64,
+}
+
+impl Lane {
+    fn new(kind: OperationKind, max_concurrent: usize, config: &AdmissionConfig) -> Self {
+        Self {
+            kind,
+            max_concurrent,
+            max_queued: config.max_queued,
+            queue_timeout: config.queue_timeout,
+            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+            queued: AtomicUsize::new(0),
+            admitted_total: AtomicU64::new(0),
+            rejected_total: AtomicU64::new(0),
+            timed_out_total: AtomicU64::new(0),
+        }
+    }
+
+    async fn acquire(&self) -> Result<AdmissionPermit> {
+        let permit = match self.semaphore.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let queued = self.queued.fetch_add(1, Ordering::SeqCst);
+                let in_queue = QueuedGuard(&self.queued);
+                if queued >= self.max_queued {
+                    drop(in_queue);
+                    self.rejected_total.fetch_add(1, Ordering::Relaxed);
+                    return Err(Error::Overloaded {
+                        message: format!(
+                            "too many concurrent {} operations ({} running, {} queued)",
+                            self.kind, self.max_concurrent, queued
+                        ),
+                    });
+                }
+                let acquire = self.semaphore.clone().acquire_owned();
+                let permit = match self.queue_timeout {
+                    Some(timeout) => runtime::timeout(timeout, acquire).await,
+                    None => Ok(acquire.await),
+                };
+                drop(in_queue);
+                let Ok(permit) = permit else {
+                    self.timed_out_total.fetch_add(1, Ordering::Relaxed);
+                    return Err(Error::Overloaded {
+                        message: format!(
+                            "timed out after {:?} waiting to run a {} operation",
+                            self.queue_timeout.unwrap_or_default(),
+                            self.kind
+                        ),
+                    });
+                };
+                permit.map_err(|_| Error::Runtime {
+                    message: "the admission controller was closed".to_string(),
+                })?
+            }
+        };
+        self.admitted_total.fetch_add(1, Ordering::Relaxed);
+        Ok(AdmissionPermit { _permit: permit })
+    }
+
+    fn metrics(&self) -> LaneMetrics {
+        LaneMetrics {
+            in_flight: self.max_concurrent - self.semaphore.available_permits(),
+            queued: self.queued.load(Ordering::SeqCst),
+            admitted_total: self.admitted_total.load(Ordering::Relaxed),
+            rejected_total: self.rejected_total.load(Ordering::Relaxed),
+            timed_out_total: self.timed_out_total.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Removes an operation from the queue of its lane when dropped
+///
+/// The future of a waiting operation can be dropped before the wait is over
+/// (by a timeout or a `select!` in the caller, for example) so the queue depth
+/// is restored on drop rather than after the wait.
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A slot granted by the [`AdmissionController`]
+///
+/// The slot is released when this is dropped.
+#[derive(Debug)]
+pub struct AdmissionPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Limits the number of concurrent operations issued through a connection
+#[derive(Debug)]
+pub struct AdmissionController {
+    queries: Lane,
+    // None if the searches share the lane of the queries
+    searches: Option<Lane>,
+    writes: Lane,
+}
+
+impl AdmissionController {
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self {
+            queries: Lane::new(OperationKind::Query, config.max_concurrent_queries, &config),
+            searches: config
+                .max_concurrent_searches
+                .map(|max| Lane::new(OperationKind::Search, max, &config)),
+            writes: Lane::new(OperationKind::Write, config.max_concurrent_writes, &config),
+        }
+    }
+
+    /// Wait for a slot to run an operation of the given kind
+    ///
+    /// Returns [`Error::Overloaded`] if the wait queue is full or the wait
+    /// exceeds the queue timeout.
+    pub async fn acquire(&self, kind: OperationKind) -> Result<AdmissionPermit> {
+        match (kind, &self.searches) {
+            (OperationKind::Search, Some(searches)) => searches.acquire().await,
+            (OperationKind::Query | OperationKind::Search, _) => self.queries.acquire().await,
+            (OperationKind::Write, _) => self.writes.acquire().await,
+        }
+    }
+
+    /// Get a snapshot of the current queue depths and counters
+    pub fn metrics(&self) -> AdmissionMetrics {
+        AdmissionMetrics {
+            queries: self.queries.metrics(),
+            searches: self
+                .searches
+                .as_ref()
+                .map(|searches| searches.metrics())
+                .unwrap_or_default(),
+            writes: self.writes.metrics(),
+        }
+    }
+}
+
+/// Acquire a permit if there is a controller, otherwise do nothing
+pub(crate) async fn maybe_acquire(
+    controller: &Option<Arc<AdmissionController>>,
+    kind: OperationKind,
+) -> Result<Option<AdmissionPermit>> {
+    match controller {
+        Some(controller) => Ok(Some(controller.acquire(kind).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Keep the permit (if any) alive until the stream is dropped
+pub(crate) fn hold_while_streaming(
+    stream: SendableRecordBatchStream,
+    permit: Option<AdmissionPermit>,
+) -> SendableRecordBatchStream {
+    match permit {
+        None => stream,
+        Some(permit) => {
+            let schema = stream.schema();
+            Box::pin(SimpleRecordBatchStream {
+                schema,
+                stream: stream.map(move |batch| {
+                    let _permit = &permit;
+                    batch
+                }),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_admission_rejects_when_queue_full() {
+        let controller = Arc::new(AdmissionController::new(AdmissionConfig {
+            max_concurrent_queries: 1,
+            max_concurrent_writes: 1,
+            max_queued: 1,
+            ..Default::default()
+        }));
+
+        let first = controller.acquire(OperationKind::Query).await.unwrap();
+        assert_eq!(controller.metrics().queries.in_flight, 1);
+
+        // The second query waits in the queue
+        let waiter = {
+            let controller = controller.clone();
+            tokio::spawn(async move { controller.acquire(OperationKind::Query).await })
+        };
+        while controller.metrics().queries.queued == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // The third query is rejected
+        assert!(matches!(
+            controller.acquire(OperationKind::Query).await,
+            Err(Error::Overloaded { .. })
+        ));
+
+        // Writes are tracked separately
+        let write = controller.acquire(OperationKind::Write).await.unwrap();
+
+        drop(first);
+        let second = waiter.await.unwrap().unwrap();
+
+        let metrics = controller.metrics();
+        assert_eq!(metrics.queries.in_flight, 1);
+        assert_eq!(metrics.queries.queued, 0);
+        assert_eq!(metrics.queries.admitted_total, 2);
+        assert_eq!(metrics.queries.rejected_total, 1);
+        assert_eq!(metrics.writes.in_flight, 1);
+
+        drop(second);
+        drop(write);
+        assert_eq!(controller.metrics().queries.in_flight, 0);
+        assert_eq!(controller.metrics().writes.in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_admission_queue_timeout() {
+        let controller = AdmissionController::new(AdmissionConfig {
+            max_concurrent_queries: 1,
+            queue_timeout: Some(Duration::from_millis(20)),
+            ..Default::default()
+        });
+
+        let first = controller.acquire(OperationKind::Query).await.unwrap();
+        assert!(matches!(
+            controller.acquire(OperationKind::Query).await,
+            Err(Error::Overloaded { .. })
+        ));
+        let metrics = controller.metrics().queries;
+        assert_eq!(metrics.timed_out_total, 1);
+        assert_eq!(metrics.rejected_total, 0);
+        assert_eq!(metrics.queued, 0);
+
+        drop(first);
+        controller.acquire(OperationKind::Query).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admission_dropped_waiter() {
+        let controller = AdmissionController::new(AdmissionConfig {
+            max_concurrent_queries: 1,
+            max_queued: 1,
+            ..Default::default()
+        });
+
+        let first = controller.acquire(OperationKind::Query).await.unwrap();
+        // The caller gives up while the operation is queued, more times than
+        // there are places in the queue
+        for _ in 0..3 {
+            let waiter = runtime::timeout(
+                Duration::from_millis(10),
+                controller.acquire(OperationKind::Query),
+            );
+            assert!(waiter.await.is_err());
+            assert_eq!(controller.metrics().queries.queued, 0);
+        }
+        assert_eq!(controller.metrics().queries.rejected_total, 0);
+
+        drop(first);
+        controller.acquire(OperationKind::Query).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admission_search_lane() {
+        let controller = AdmissionController::new(AdmissionConfig {
+            max_concurrent_queries: 1,
+            max_concurrent_searches: Some(2),
+            max_queued: 0,
+            ..Default::default()
+        });
+
+        // Scans and searches are limited separately
+        let _scan = controller.acquire(OperationKind::Query).await.unwrap();
+        let _first = controller.acquire(OperationKind::Search).await.unwrap();
+        let _second = controller.acquire(OperationKind::Search).await.unwrap();
+        assert!(controller.acquire(OperationKind::Search).await.is_err());
+        assert!(controller.acquire(OperationKind::Query).await.is_err());
+
+        let metrics = controller.metrics();
+        assert_eq!(metrics.queries.in_flight, 1);
+        assert_eq!(metrics.searches.in_flight, 2);
+        assert_eq!(metrics.searches.rejected_total, 1);
+
+        // Without their own limit searches count as queries
+        let controller = AdmissionController::new(AdmissionConfig {
+            max_concurrent_queries: 1,
+            max_queued: 0,
+            ..Default::default()
+        });
+        let _search = controller.acquire(OperationKind::Search).await.unwrap();
+        assert!(controller.acquire(OperationKind::Query).await.is_err());
+        assert_eq!(controller.metrics().searches, LaneMetrics::default());
+    }
+}
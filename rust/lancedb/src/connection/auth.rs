@@ -0,0 +1,560 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Authentication of requests to LanceDb Cloud
+//!
+//! By default requests are authenticated with the API key given to
+//! [`super::ConnectBuilder::api_key`].  Deployments behind a gateway that
+//! requires a different scheme can provide their own [`AuthProvider`] with
+//! [`super::ConnectBuilder::auth_provider`].
+
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+
+use crate::error::Result;
+
+/// The parts of a request that an [`AuthProvider`] may need to sign it
+#[derive(Debug)]
+pub struct AuthRequest<'a> {
+    pub method: &'a str,
+    pub url: &'a url::Url,
+    /// The body of the request, None if the body is empty or streamed
+    pub body: Option<&'a [u8]>,
+}
+
+/// Provides the credentials for requests made to LanceDb Cloud
+#[async_trait]
+pub trait AuthProvider: std::fmt::Debug + Send + Sync {
+    /// The headers to add to the request
+    ///
+    /// This is called before every attempt of every request, so implementations
+    /// should cache any credentials that are expensive to obtain.
+    async fn headers(&self, request: &AuthRequest<'_>) -> Result<Vec<(String, String)>>;
+
+    /// Called when the server rejects a request with a 401 status
+    ///
+    /// Return true if the credentials were refreshed and the request should be
+    /// sent again.  A request is only resent once.  The default returns false.
+    async fn on_unauthorized(&self) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Authenticates with a static API key in the `x-api-key` header
+///
+/// This is what [`super::ConnectBuilder::api_key`] uses.
+#[derive(Debug, Clone)]
+pub struct ApiKeyAuth {
+    api_key: String,
+}
+
+impl ApiKeyAuth {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for ApiKeyAuth {
+    async fn headers(&self, _request: &AuthRequest<'_>) -> Result<Vec<(String, String)>> {
+        Ok(vec![("x-api-key".to_string(), self.api_key.clone())])
+    }
+}
+
+/// A bearer token returned by the refresh callback of [`BearerTokenAuth`]
+#[derive(Debug, Clone)]
+pub struct BearerToken {
+    pub token: String,
+    /// When the token expires, None if it does not expire
+    pub expires_at: Option<SystemTime>,
+}
+
+/// The callback used by [`BearerTokenAuth`] to obtain a new token
+pub type TokenRefresh = Box<dyn Fn() -> BoxFuture<'static, Result<BearerToken>> + Send + Sync>;
+
+/// Authenticates with a bearer token in the `Authorization` header
+///
+/// The token is obtained from a callback, e.g. from an OAuth identity
+/// provider.  It is refreshed shortly before it expires and whenever the server
+/// rejects it.
+pub struct BearerTokenAuth {
+    refresh: TokenRefresh,
+    // Tokens are refreshed this long before they expire
+    refresh_margin: Duration,
+    token: tokio::sync::Mutex<Option<BearerToken>>,
+}
+
+impl std::fmt::Debug for BearerTokenAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BearerTokenAuth")
+            .field("refresh_margin", &self.refresh_margin)
+            .finish()
+    }
+}
+
+impl BearerTokenAuth {
+    pub fn new<F>(refresh: F) -> Self
+    where
+        F: Fn() -> BoxFuture<'static, Result<BearerToken>> + Send + Sync + 'static,
+    {
+        Self {
+            refresh: Box::new(refresh),
+            refresh_margin: Duration::from_secs(30),
+            token: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// How long before the expiry of a token a new one is requested
+    ///
+    /// The default is 30 seconds.
+    pub fn refresh_margin(mut self, refresh_margin: Duration) -> Self {
+        self.refresh_margin = refresh_margin;
+        self
+    }
+
+    fn is_fresh(&self, token: &BearerToken) -> bool {
+        match token.expires_at {
+            Some(expires_at) => SystemTime::now() + self.refresh_margin < expires_at,
+            None => true,
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for BearerTokenAuth {
+    async fn headers(&self, _request: &AuthRequest<'_>) -> Result<Vec<(String, String)>> {
+        let mut token = self.token.lock().await;
+        let current = match token.as_ref() {
+            Some(current) if self.is_fresh(current) => current.token.clone(),
+            _ => {
+                let fresh = (self.refresh)().await?;
+                let value = fresh.token.clone();
+                *token = Some(fresh);
+                value
+            }
+        };
+        Ok(vec![(
+            "Authorization".to_string(),
+            format!("Bearer {}", current),
+        )])
+    }
+
+    async fn on_unauthorized(&self) -> Result<bool> {
+        // Force a refresh on the next attempt
+        self.token.lock().await.take();
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "remote")]
+pub use self::sigv4::SigV4Auth;
+
+#[cfg(feature = "remote")]
+mod sigv4 {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use hmac::{Hmac, Mac};
+    use object_store::aws::{AwsCredential, AwsCredentialProvider};
+    use object_store::StaticCredentialProvider;
+    use sha2::{Digest, Sha256};
+
+    use super::{AuthProvider, AuthRequest};
+    use crate::error::Result;
+
+    const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+    /// Signs requests with AWS Signature Version 4
+    ///
+    /// This is used when LanceDb Cloud is deployed behind an AWS gateway (e.g.
+    /// API Gateway with IAM authorization).  The `host`, `x-amz-date` and
+    /// `x-amz-content-sha256` headers are signed.
+    ///
+    /// The path is encoded as the service expects: once for `s3` and twice
+    /// for every other service.
+    #[derive(Debug, Clone)]
+    pub struct SigV4Auth {
+        credentials: AwsCredentialProvider,
+        region: String,
+        service: String,
+    }
+
+    impl SigV4Auth {
+        /// Create a signer for the given AWS region and service (e.g. `execute-api`)
+        ///
+        /// The credential is used as it is, use [`Self::with_credential_provider`]
+        /// for temporary credentials.
+        pub fn new(
+            credential: AwsCredential,
+            region: impl Into<String>,
+            service: impl Into<String>,
+        ) -> Self {
+            Self::with_credential_provider(
+                Arc::new(StaticCredentialProvider::new(credential)),
+                region,
+                service,
+            )
+        }
+
+        /// Create a signer that gets the credential from a provider
+        ///
+        /// The provider is asked for the credential before every request, so
+        /// it can refresh temporary credentials (e.g. from STS or an instance
+        /// profile) before they expire.  It should cache the credential until
+        /// then.
+        pub fn with_credential_provider(
+            credentials: AwsCredentialProvider,
+            region: impl Into<String>,
+            service: impl Into<String>,
+        ) -> Self {
+            Self {
+                credentials,
+                region: region.into(),
+                service: service.into(),
+            }
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+        mac.update(data.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        hex(&Sha256::digest(data))
+    }
+
+    // Percent encode everything except the RFC 3986 unreserved characters
+    fn uri_encode(value: impl AsRef<[u8]>) -> String {
+        value
+            .as_ref()
+            .iter()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (*b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    }
+
+    // Undo the percent encoding of a segment of a URL path
+    fn uri_decode(value: &str) -> Vec<u8> {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = match bytes[i] {
+                b'%' => bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                _ => None,
+            };
+            match escaped {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                None => {
+                    decoded.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        decoded
+    }
+
+    /// The path of the request as it is signed
+    ///
+    /// Every segment is encoded once for S3.  Every other service signs the
+    /// path with each segment encoded twice.
+    pub(super) fn canonical_uri(url: &url::Url, service: &str) -> String {
+        url.path()
+            .split('/')
+            .map(|segment| {
+                let encoded = uri_encode(uri_decode(segment));
+                if service == "s3" {
+                    encoded
+                } else {
+                    uri_encode(encoded)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn canonical_query(url: &url::Url) -> String {
+        let mut pairs = url
+            .query_pairs()
+            .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        pairs
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Calculate the signed headers and signature of a request
+    ///
+    /// `headers` are the headers to sign, they must include `host` and
+    /// `x-amz-date`.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn sign(
+        method: &str,
+        url: &url::Url,
+        headers: &[(String, String)],
+        payload_hash: &str,
+        amz_date: &str,
+        region: &str,
+        service: &str,
+        secret_key: &str,
+    ) -> (String, String) {
+        let mut headers = headers
+            .iter()
+            .map(|(k, v)| (k.to_lowercase(), v.trim().to_string()))
+            .collect::<Vec<_>>();
+        headers.sort();
+        let canonical_headers = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v))
+            .collect::<String>();
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            canonical_uri(url, service),
+            canonical_query(url),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+        let key = hmac(format!("AWS4{}", secret_key).as_bytes(), date);
+        let key = hmac(&key, region);
+        let key = hmac(&key, service);
+        let key = hmac(&key, "aws4_request");
+        (signed_headers, hex(&hmac(&key, &string_to_sign)))
+    }
+
+    #[async_trait]
+    impl AuthProvider for SigV4Auth {
+        async fn headers(&self, request: &AuthRequest<'_>) -> Result<Vec<(String, String)>> {
+            let credential = self.credentials.get_credential().await?;
+            let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+            let payload_hash = match request.body {
+                Some(body) => sha256_hex(body),
+                None if request.method == "GET" => sha256_hex(&[]),
+                None => UNSIGNED_PAYLOAD.to_string(),
+            };
+            let mut host = request.url.host_str().unwrap_or_default().to_string();
+            if let Some(port) = request.url.port() {
+                host = format!("{}:{}", host, port);
+            }
+            let mut headers = vec![
+                ("host".to_string(), host),
+                ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+                ("x-amz-date".to_string(), amz_date.clone()),
+            ];
+            if let Some(token) = &credential.token {
+                headers.push(("x-amz-security-token".to_string(), token.clone()));
+            }
+            let (signed_headers, signature) = sign(
+                request.method,
+                request.url,
+                &headers,
+                &payload_hash,
+                &amz_date,
+                &self.region,
+                &self.service,
+                &credential.secret_key,
+            );
+            // The host header is set by the HTTP client
+            headers.remove(0);
+            headers.push((
+                "Authorization".to_string(),
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}/{}/{}/aws4_request, SignedHeaders={}, Signature={}",
+                    credential.key_id,
+                    &amz_date[..8],
+                    self.region,
+                    self.service,
+                    signed_headers,
+                    signature
+                ),
+            ));
+            Ok(headers)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use futures::FutureExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bearer_token_refresh() {
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let counter = refreshes.clone();
+        let auth = BearerTokenAuth::new(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                Ok(BearerToken {
+                    token: format!("token-{}", n),
+                    expires_at: Some(SystemTime::now() + Duration::from_secs(3600)),
+                })
+            }
+            .boxed()
+        });
+        let url = url::Url::parse("https://example.com/v1/table/").unwrap();
+        let request = AuthRequest {
+            method: "GET",
+            url: &url,
+            body: None,
+        };
+
+        let headers = auth.headers(&request).await.unwrap();
+        assert_eq!(headers[0].1, "Bearer token-0");
+        // The cached token is reused
+        auth.headers(&request).await.unwrap();
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+        assert!(auth.on_unauthorized().await.unwrap());
+        let headers = auth.headers(&request).await.unwrap();
+        assert_eq!(headers[0].1, "Bearer token-1");
+
+        // Tokens within the refresh margin are refreshed
+        let auth = auth.refresh_margin(Duration::from_secs(7200));
+        auth.headers(&request).await.unwrap();
+        auth.headers(&request).await.unwrap();
+        assert_eq!(refreshes.load(Ordering::SeqCst), 4);
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn test_sigv4_signature() {
+        // The example from the AWS Signature Version 4 documentation
+        let url = url::Url::parse("https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08")
+            .unwrap();
+        let headers = vec![
+            (
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded; charset=utf-8".to_string(),
+            ),
+            ("Host".to_string(), "iam.amazonaws.com".to_string()),
+            ("X-Amz-Date".to_string(), "20150830T123600Z".to_string()),
+        ];
+        let (signed_headers, signature) = sigv4::sign(
+            "GET",
+            &url,
+            &headers,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "20150830T123600Z",
+            "us-east-1",
+            "iam",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+        );
+        assert_eq!(signed_headers, "content-type;host;x-amz-date");
+        assert_eq!(
+            signature,
+            "5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+
+        let url = url::Url::parse("https://example.com/v1/table/my%20table/a:b/").unwrap();
+        assert_eq!(
+            sigv4::canonical_uri(&url, "execute-api"),
+            "/v1/table/my%2520table/a%253Ab/"
+        );
+        assert_eq!(
+            sigv4::canonical_uri(&url, "s3"),
+            "/v1/table/my%20table/a%3Ab/"
+        );
+        let url = url::Url::parse("https://example.com").unwrap();
+        assert_eq!(sigv4::canonical_uri(&url, "execute-api"), "/");
+    }
+
+    #[cfg(feature = "remote")]
+    #[tokio::test]
+    async fn test_sigv4_credential_provider() {
+        #[derive(Debug, Default)]
+        struct Rotating(AtomicUsize);
+
+        #[async_trait]
+        impl object_store::CredentialProvider for Rotating {
+            type Credential = object_store::aws::AwsCredential;
+
+            async fn get_credential(&self) -> object_store::Result<Arc<Self::Credential>> {
+                let n = self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(Arc::new(object_store::aws::AwsCredential {
+                    key_id: format!("key-{}", n),
+                    secret_key: "secret".to_string(),
+                    token: Some("session".to_string()),
+                }))
+            }
+        }
+
+        let auth = SigV4Auth::with_credential_provider(
+            Arc::new(Rotating::default()),
+            "us-east-1",
+            "execute-api",
+        );
+        let url = url::Url::parse("https://example.com/v1/table/").unwrap();
+        let request = AuthRequest {
+            method: "GET",
+            url: &url,
+            body: None,
+        };
+        for n in 0..2 {
+            let headers = auth.headers(&request).await.unwrap();
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.clone())
+                    .unwrap()
+            };
+            assert_eq!(header("x-amz-security-token"), "session");
+            assert!(header("Authorization").contains(&format!("Credential=key-{}/", n)));
+        }
+    }
+}
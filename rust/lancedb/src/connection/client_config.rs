@@ -0,0 +1,207 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Configuration of the HTTP client used to connect to LanceDb Cloud
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::units::IntoDuration;
+
+/// Configures how requests to LanceDb Cloud are retried
+///
+/// Requests are retried if the connection fails, the request times out, or the
+/// server responds with a 5xx or 429 status.  The delay between attempts grows
+/// exponentially: `initial_backoff * backoff_factor ^ (attempt - 1)`, capped at
+/// `max_backoff`.  If the server responds with a `Retry-After` header (in
+/// seconds) then that delay is used instead.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// The maximum number of retries, 0 disables retries
+    pub max_retries: u32,
+    /// The delay before the first retry
+    pub initial_backoff: Duration,
+    /// The multiplier applied to the delay after each retry, at least 1
+    pub backoff_factor: f64,
+    /// The maximum delay between two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            backoff_factor: 2.0,
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The delay before the given retry (1 is the first retry)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let factor = self.backoff_factor.powi(exponent);
+        // Computed in floating point so that a large retry can not overflow,
+        // `min` also replaces a NaN with the maximum
+        let seconds = (self.initial_backoff.as_secs_f64() * factor)
+            .min(self.max_backoff.as_secs_f64())
+            .max(0.0);
+        Duration::from_secs_f64(seconds)
+    }
+
+    /// Check that the backoff factor is a finite number of at least 1
+    pub fn validate(&self) -> Result<()> {
+        if !self.backoff_factor.is_finite() || self.backoff_factor < 1.0 {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the retry backoff factor must be a finite number of at least 1, got {}",
+                    self.backoff_factor
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether a response with the given status should be retried
+    pub fn is_retryable_status(status: u16) -> bool {
+        status == 429 || (500..600).contains(&status)
+    }
+}
+
+/// A hook that is called around every request made to LanceDb Cloud
+///
+/// This can be used for logging, metrics or tracing.  Both methods have empty
+/// default implementations.  Each retry is reported as a separate attempt.
+pub trait RequestHook: std::fmt::Debug + Send + Sync {
+    /// Called before a request is sent
+    fn on_request(&self, _method: &str, _url: &str, _attempt: u32) {}
+
+    /// Called once a request completes
+    ///
+    /// `status` is None if no response was received (e.g. the connection failed
+    /// or the request timed out).
+    fn on_response(
+        &self,
+        _method: &str,
+        _url: &str,
+        _attempt: u32,
+        _status: Option<u16>,
+        _elapsed: Duration,
+    ) {
+    }
+}
+
+/// Configuration of the HTTP client used to connect to LanceDb Cloud
+///
+/// Set with [`super::ConnectBuilder::client_config`].  This has no effect on
+/// connections to LanceDb OSS.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// The timeout for an entire request, including reading the response
+    pub timeout: Duration,
+    /// The timeout for establishing a connection
+    pub connect_timeout: Duration,
+    pub retry: RetryConfig,
+    /// How long an idle connection is kept in the pool, None keeps it forever
+    pub pool_idle_timeout: Option<Duration>,
+    /// The maximum number of idle connections kept in the pool
+    pub pool_max_idle: usize,
+    /// The interval of TCP keep-alive probes, None disables them
+    pub tcp_keepalive: Option<Duration>,
+    /// Called around every request
+    pub hook: Option<Arc<dyn RequestHook>>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            retry: RetryConfig::default(),
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle: 32,
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            hook: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Set [`Self::timeout`], the timeout can also be a string such as `"30s"`
+    pub fn with_timeout(mut self, timeout: impl IntoDuration) -> Result<Self> {
+        self.timeout = timeout.into_duration()?;
+        Ok(self)
+    }
+
+    /// Set [`Self::connect_timeout`], the timeout can also be a string such as `"5s"`
+    pub fn with_connect_timeout(mut self, connect_timeout: impl IntoDuration) -> Result<Self> {
+        self.connect_timeout = connect_timeout.into_duration()?;
+        Ok(self)
+    }
+
+    /// Set [`Self::retry`], see [`RetryConfig::validate`]
+    pub fn with_retry(mut self, retry: RetryConfig) -> Result<Self> {
+        retry.validate()?;
+        self.retry = retry;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let retry = RetryConfig {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            backoff_factor: 2.0,
+            max_backoff: Duration::from_millis(500),
+        };
+        assert_eq!(retry.backoff(1), Duration::from_millis(100));
+        assert_eq!(retry.backoff(2), Duration::from_millis(200));
+        assert_eq!(retry.backoff(3), Duration::from_millis(400));
+        assert_eq!(retry.backoff(4), Duration::from_millis(500));
+        assert_eq!(retry.backoff(u32::MAX), Duration::from_millis(500));
+
+        for factor in [0.5, f64::NAN, f64::INFINITY] {
+            let invalid = RetryConfig {
+                backoff_factor: factor,
+                ..retry.clone()
+            };
+            assert!(ClientConfig::default().with_retry(invalid).is_err());
+        }
+        assert!(ClientConfig::default().with_retry(retry).is_ok());
+
+        assert!(RetryConfig::is_retryable_status(503));
+        assert!(RetryConfig::is_retryable_status(429));
+        assert!(!RetryConfig::is_retryable_status(404));
+    }
+
+    #[test]
+    fn test_string_timeouts() {
+        let config = ClientConfig::default()
+            .with_timeout("1m")
+            .unwrap()
+            .with_connect_timeout(Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(config.timeout, Duration::from_secs(60));
+        assert_eq!(config.connect_timeout, Duration::from_secs(2));
+        assert!(ClientConfig::default().with_timeout("1 fortnight").is_err());
+    }
+}
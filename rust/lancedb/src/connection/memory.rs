@@ -0,0 +1,284 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Memory budgets for the operations issued through a [`super::Connection`]
+//!
+//! Some operations hold a lot of data in memory: queries that order or
+//! rescore their results collect them before returning the first row, and
+//! training a vector index loads a sample of the vectors.  With a
+//! [`MemoryPool`] each of these operations reserves the memory it needs as
+//! it goes, and fails with [`crate::Error::MemoryLimitExceeded`] as soon as
+//! the reservation would exceed the budget of the operation or the memory
+//! left in the pool, instead of running the process out of memory.
+//!
+//! Index builds reserve an estimate of the memory needed to train the index
+//! before they start, so they fail before any work is done.  The memory of
+//! the streams returned to the caller, and of the buffers of Lance itself, is
+//! not counted.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::RecordBatch;
+use futures::TryStreamExt;
+
+use crate::arrow::SendableRecordBatchStream;
+use crate::error::{Error, Result};
+
+/// Configuration of the memory pool of a connection
+///
+/// See [`super::ConnectBuilder::memory_pool`]
+#[derive(Debug, Clone)]
+pub struct MemoryPoolConfig {
+    /// The memory that all the operations together may reserve
+    pub max_bytes: usize,
+    /// The memory that one query may reserve, if limited separately
+    pub max_query_bytes: Option<usize>,
+    /// The memory that one index build may reserve, if limited separately
+    pub max_index_build_bytes: Option<usize>,
+}
+
+impl MemoryPoolConfig {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            max_query_bytes: None,
+            max_index_build_bytes: None,
+        }
+    }
+}
+
+/// The kind of operation reserving memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryConsumer {
+    Query,
+    IndexBuild,
+}
+
+impl std::fmt::Display for MemoryConsumer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Query => write!(f, "query"),
+            Self::IndexBuild => write!(f, "index build"),
+        }
+    }
+}
+
+/// A snapshot of the state of a [`MemoryPool`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryPoolMetrics {
+    /// The memory currently reserved by all the operations
+    pub reserved: usize,
+    /// The most memory that was reserved at the same time
+    pub peak_reserved: usize,
+    /// Total number of operations that failed because they ran out of memory
+    pub rejected_total: u64,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    reserved: usize,
+    peak_reserved: usize,
+}
+
+/// Limits the memory reserved by the operations of a connection
+#[derive(Debug)]
+pub struct MemoryPool {
+    config: MemoryPoolConfig,
+    state: Mutex<PoolState>,
+    rejected_total: AtomicU64,
+}
+
+impl MemoryPool {
+    pub fn new(config: MemoryPoolConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::default(),
+            rejected_total: AtomicU64::new(0),
+        }
+    }
+
+    /// Start an empty reservation for an operation
+    pub fn reserve(self: &Arc<Self>, consumer: MemoryConsumer) -> MemoryReservation {
+        MemoryReservation {
+            pool: self.clone(),
+            consumer,
+            size: 0,
+        }
+    }
+
+    /// Get a snapshot of the reserved memory and counters
+    pub fn metrics(&self) -> MemoryPoolMetrics {
+        let state = self.state.lock().unwrap();
+        MemoryPoolMetrics {
+            reserved: state.reserved,
+            peak_reserved: state.peak_reserved,
+            rejected_total: self.rejected_total.load(Ordering::Relaxed),
+        }
+    }
+
+    fn budget(&self, consumer: MemoryConsumer) -> Option<usize> {
+        match consumer {
+            MemoryConsumer::Query => self.config.max_query_bytes,
+            MemoryConsumer::IndexBuild => self.config.max_index_build_bytes,
+        }
+    }
+
+    fn try_grow(&self, consumer: MemoryConsumer, current: usize, bytes: usize) -> Result<()> {
+        let requested = current.saturating_add(bytes);
+        let mut state = self.state.lock().unwrap();
+        let message = match self.budget(consumer) {
+            Some(budget) if requested > budget => Some(format!(
+                "the {} needs at least {} bytes but may only use {}",
+                consumer, requested, budget
+            )),
+            _ if state.reserved.saturating_add(bytes) > self.config.max_bytes => Some(format!(
+                "the {} needs {} more bytes but only {} of the {} bytes of the pool are left",
+                consumer,
+                bytes,
+                self.config.max_bytes - state.reserved,
+                self.config.max_bytes
+            )),
+            _ => None,
+        };
+        if let Some(message) = message {
+            self.rejected_total.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::MemoryLimitExceeded { message });
+        }
+        state.reserved += bytes;
+        state.peak_reserved = state.peak_reserved.max(state.reserved);
+        Ok(())
+    }
+
+    fn release(&self, bytes: usize) {
+        self.state.lock().unwrap().reserved -= bytes;
+    }
+}
+
+/// The memory reserved by one operation
+///
+/// The memory is returned to the pool when this is dropped.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    pool: Arc<MemoryPool>,
+    consumer: MemoryConsumer,
+    size: usize,
+}
+
+impl MemoryReservation {
+    /// The number of bytes reserved
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Reserve `bytes` more bytes
+    ///
+    /// Returns [`Error::MemoryLimitExceeded`] if the operation or the pool
+    /// would use more than its limit, the reservation is unchanged then.
+    pub fn try_grow(&mut self, bytes: usize) -> Result<()> {
+        self.pool.try_grow(self.consumer, self.size, bytes)?;
+        self.size += bytes;
+        Ok(())
+    }
+
+    /// Return `bytes` bytes to the pool
+    pub fn shrink(&mut self, bytes: usize) {
+        let bytes = bytes.min(self.size);
+        self.pool.release(bytes);
+        self.size -= bytes;
+    }
+
+    /// Change the reservation to `bytes` bytes
+    pub fn try_resize(&mut self, bytes: usize) -> Result<()> {
+        if bytes > self.size {
+            self.try_grow(bytes - self.size)
+        } else {
+            self.shrink(self.size - bytes);
+            Ok(())
+        }
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.pool.release(self.size);
+    }
+}
+
+/// Start a reservation if there is a pool, otherwise do nothing
+pub(crate) fn maybe_reserve(
+    pool: &Option<Arc<MemoryPool>>,
+    consumer: MemoryConsumer,
+) -> Option<MemoryReservation> {
+    pool.as_ref().map(|pool| pool.reserve(consumer))
+}
+
+/// Reserve `bytes` more bytes if there is a reservation
+pub(crate) fn maybe_grow(reservation: &mut Option<MemoryReservation>, bytes: usize) -> Result<()> {
+    match reservation {
+        Some(reservation) => reservation.try_grow(bytes),
+        None => Ok(()),
+    }
+}
+
+/// Collect the batches of a stream, reserving their memory as they arrive
+pub(crate) async fn collect_reserved(
+    mut stream: SendableRecordBatchStream,
+    reservation: &mut Option<MemoryReservation>,
+) -> Result<Vec<RecordBatch>> {
+    let mut batches = Vec::new();
+    while let Some(batch) = stream.try_next().await? {
+        maybe_grow(reservation, batch.get_array_memory_size())?;
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_pool() {
+        let pool = Arc::new(MemoryPool::new(MemoryPoolConfig {
+            max_query_bytes: Some(60),
+            ..MemoryPoolConfig::new(100)
+        }));
+
+        let mut first = pool.reserve(MemoryConsumer::Query);
+        first.try_grow(50).unwrap();
+        // Over the budget of a query
+        assert!(matches!(
+            first.try_grow(20),
+            Err(Error::MemoryLimitExceeded { .. })
+        ));
+        assert_eq!(first.size(), 50);
+
+        // Over the size of the pool
+        let mut build = pool.reserve(MemoryConsumer::IndexBuild);
+        build.try_grow(40).unwrap();
+        assert!(build.try_grow(20).is_err());
+        first.try_resize(20).unwrap();
+        build.try_grow(20).unwrap();
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.reserved, 80);
+        assert_eq!(metrics.peak_reserved, 90);
+        assert_eq!(metrics.rejected_total, 2);
+
+        drop(first);
+        drop(build);
+        assert_eq!(pool.metrics().reserved, 0);
+    }
+}
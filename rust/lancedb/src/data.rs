@@ -15,4 +15,6 @@
 //! Data types, schema coercion, and data cleaning and etc.
 
 pub mod inspect;
+pub mod precision;
 pub mod sanitize;
+pub mod sort;
@@ -0,0 +1,341 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of incoming vectors to the precision of the table
+//!
+//! Embedding pipelines often produce `f64` vectors while the table stores
+//! `f32` (or `f16`) vectors.  When data is added to a table, vector columns
+//! with a different float type than the table are cast according to a
+//! [`VectorPrecisionPolicy`], and the precision that was lost is recorded in
+//! [`crate::table::write_stats::WriteStats::vector_casts`].
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{
+    cast::AsArray, types::Float64Type, Array, ArrayRef, FixedSizeListArray, RecordBatch,
+    RecordBatchReader,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use lance::arrow::DataTypeExt;
+
+use crate::error::{Error, Result};
+
+/// What to do when an incoming vector column has a different float type than the table
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum VectorPrecisionPolicy {
+    /// Cast the vectors to the type of the table (the default)
+    #[default]
+    Cast,
+    /// Cast the vectors, but fail the write if any value changes by more than
+    /// the given absolute error or overflows the type of the table
+    CastWithin(f64),
+    /// Fail the write if the vectors would lose precision
+    ///
+    /// Vectors with a narrower type than the table (e.g. `f32` vectors in an
+    /// `f64` table) are still cast, since that is lossless.
+    Error,
+}
+
+/// The precision lost when casting the vectors of a column
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VectorCastStats {
+    /// The number of values (not vectors) that were cast
+    pub values: u64,
+    /// The number of values that changed
+    pub lossy_values: u64,
+    /// The number of finite values that became infinite
+    pub overflowed_values: u64,
+    /// The largest absolute change of a finite value that did not overflow
+    pub max_abs_error: f64,
+}
+
+impl VectorCastStats {
+    /// Combine the stats of two casts of the same column
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.values += other.values;
+        self.lossy_values += other.lossy_values;
+        self.overflowed_values += other.overflowed_values;
+        self.max_abs_error = self.max_abs_error.max(other.max_abs_error);
+    }
+}
+
+fn float_item(data_type: &DataType) -> Option<(&DataType, i32)> {
+    match data_type {
+        DataType::FixedSizeList(item, dim) if item.data_type().is_floating() => {
+            Some((item.data_type(), *dim))
+        }
+        _ => None,
+    }
+}
+
+/// A vector column that needs to be cast
+struct VectorCast {
+    index: usize,
+    name: String,
+    target: Arc<Field>,
+}
+
+/// Casts the vector columns of the incoming data to the types of the table
+pub(crate) struct VectorCaster {
+    inner: Box<dyn RecordBatchReader + Send>,
+    schema: SchemaRef,
+    casts: Vec<VectorCast>,
+    policy: VectorPrecisionPolicy,
+    stats: Arc<Mutex<BTreeMap<String, VectorCastStats>>>,
+}
+
+impl VectorCaster {
+    /// Wrap the data if any of its vector columns need to be cast
+    ///
+    /// Returns the data and the stats of the casts, which are filled in as the
+    /// data is read.
+    pub(crate) fn try_new(
+        data: Box<dyn RecordBatchReader + Send>,
+        table_schema: &Schema,
+        policy: VectorPrecisionPolicy,
+    ) -> Result<(
+        Box<dyn RecordBatchReader + Send>,
+        Arc<Mutex<BTreeMap<String, VectorCastStats>>>,
+    )> {
+        let stats = Arc::new(Mutex::new(BTreeMap::new()));
+        let input_schema = data.schema();
+        let mut fields = input_schema.fields().iter().cloned().collect::<Vec<_>>();
+        let mut casts = Vec::new();
+        for (index, field) in input_schema.fields().iter().enumerate() {
+            let Ok(target) = table_schema.field_with_name(field.name()) else {
+                continue;
+            };
+            let (Some((from, dim)), Some((to, target_dim))) = (
+                float_item(field.data_type()),
+                float_item(target.data_type()),
+            ) else {
+                continue;
+            };
+            if from == to || dim != target_dim {
+                continue;
+            }
+            let narrowing = from.byte_width() > to.byte_width();
+            if narrowing && policy == VectorPrecisionPolicy::Error {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the vector column '{}' has type {} but the table stores {}, \
+                         casting would lose precision",
+                        field.name(),
+                        from,
+                        to
+                    ),
+                });
+            }
+            let target = Arc::new(Field::new(
+                field.name(),
+                target.data_type().clone(),
+                field.is_nullable(),
+            ));
+            fields[index] = target.clone();
+            casts.push(VectorCast {
+                index,
+                name: field.name().clone(),
+                target,
+            });
+        }
+        if casts.is_empty() {
+            return Ok((data, stats));
+        }
+        let schema = Arc::new(Schema::new_with_metadata(
+            fields,
+            input_schema.metadata().clone(),
+        ));
+        let caster = Self {
+            inner: data,
+            schema,
+            casts,
+            policy,
+            stats: stats.clone(),
+        };
+        Ok((Box::new(caster), stats))
+    }
+
+    fn cast_column(
+        &self,
+        cast: &VectorCast,
+        column: &ArrayRef,
+    ) -> std::result::Result<ArrayRef, ArrowError> {
+        // arrow can not cast between fixed size lists, so the values are cast
+        let list = column.as_fixed_size_list();
+        let DataType::FixedSizeList(item, dim) = cast.target.data_type() else {
+            unreachable!("only vector columns are cast")
+        };
+        let cast_values = arrow_cast::cast(list.values(), item.data_type())?;
+        let before = arrow_cast::cast(list.values(), &DataType::Float64)?;
+        let after = arrow_cast::cast(&cast_values, &DataType::Float64)?;
+        let cast_column: ArrayRef = Arc::new(FixedSizeListArray::try_new(
+            item.clone(),
+            *dim,
+            cast_values,
+            list.nulls().cloned(),
+        )?);
+        let mut stats = VectorCastStats::default();
+        let values = before
+            .as_primitive::<Float64Type>()
+            .iter()
+            .zip(after.as_primitive::<Float64Type>().iter());
+        for (before, after) in values {
+            let (Some(before), Some(after)) = (before, after) else {
+                continue;
+            };
+            stats.values += 1;
+            if before.is_finite() && after.is_infinite() {
+                stats.overflowed_values += 1;
+                stats.lossy_values += 1;
+            } else if before != after && !(before.is_nan() && after.is_nan()) {
+                stats.lossy_values += 1;
+                stats.max_abs_error = stats.max_abs_error.max((before - after).abs());
+            }
+        }
+        if let VectorPrecisionPolicy::CastWithin(tolerance) = self.policy {
+            if stats.overflowed_values > 0 || stats.max_abs_error > tolerance {
+                return Err(ArrowError::ComputeError(format!(
+                    "casting the vector column '{}' to {} changes values by more than {} \
+                     ({} values overflowed, the largest change was {})",
+                    cast.name,
+                    cast.target.data_type(),
+                    tolerance,
+                    stats.overflowed_values,
+                    stats.max_abs_error
+                )));
+            }
+        }
+        self.stats
+            .lock()
+            .unwrap()
+            .entry(cast.name.clone())
+            .or_default()
+            .merge(&stats);
+        Ok(cast_column)
+    }
+
+    fn cast_batch(&self, batch: RecordBatch) -> std::result::Result<RecordBatch, ArrowError> {
+        let mut columns = batch.columns().to_vec();
+        for cast in &self.casts {
+            columns[cast.index] = self.cast_column(cast, &columns[cast.index])?;
+        }
+        RecordBatch::try_new(self.schema.clone(), columns)
+    }
+}
+
+impl Iterator for VectorCaster {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|batch| batch.and_then(|batch| self.cast_batch(batch)))
+    }
+}
+
+impl RecordBatchReader for VectorCaster {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Float32Array, Float64Array, RecordBatchIterator};
+    use lance::arrow::FixedSizeListArrayExt;
+
+    use super::*;
+
+    fn vectors(values: Vec<f64>, dim: i32) -> FixedSizeListArray {
+        FixedSizeListArray::try_new_from_values(Float64Array::from(values), dim).unwrap()
+    }
+
+    fn table_schema() -> Schema {
+        Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float16, true)), 2),
+            true,
+        )])
+    }
+
+    fn data(values: Vec<f64>) -> Box<dyn RecordBatchReader + Send> {
+        let vectors = vectors(values, 2);
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            vectors.data_type().clone(),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema))
+    }
+
+    #[test]
+    fn test_vector_caster() {
+        let (reader, stats) = VectorCaster::try_new(
+            data(vec![1.0, 0.5, 0.1, 100000.0]),
+            &table_schema(),
+            VectorPrecisionPolicy::Cast,
+        )
+        .unwrap();
+        assert_eq!(reader.schema().as_ref(), &table_schema());
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches[0].schema().as_ref(), &table_schema());
+        let stats = stats.lock().unwrap()["vector"].clone();
+        assert_eq!(stats.values, 4);
+        assert_eq!(stats.lossy_values, 2);
+        assert_eq!(stats.overflowed_values, 1);
+        assert!(stats.max_abs_error > 0.0 && stats.max_abs_error < 1e-3);
+
+        let (reader, _) = VectorCaster::try_new(
+            data(vec![1.0, 0.5, 0.1, 100000.0]),
+            &table_schema(),
+            VectorPrecisionPolicy::CastWithin(0.01),
+        )
+        .unwrap();
+        assert!(reader.collect::<std::result::Result<Vec<_>, _>>().is_err());
+
+        assert!(VectorCaster::try_new(
+            data(vec![1.0, 0.5]),
+            &table_schema(),
+            VectorPrecisionPolicy::Error
+        )
+        .is_err());
+
+        // Widening is lossless so it is allowed by every policy
+        let wide = Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), 2),
+            true,
+        )]);
+        let narrow: ArrayRef = Arc::new(
+            FixedSizeListArray::try_new_from_values(Float32Array::from(vec![1.0, 0.5]), 2).unwrap(),
+        );
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            narrow.data_type().clone(),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![narrow]).unwrap();
+        let (reader, stats) = VectorCaster::try_new(
+            Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema)),
+            &wide,
+            VectorPrecisionPolicy::Error,
+        )
+        .unwrap();
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches[0].schema().as_ref(), &wide);
+        assert_eq!(stats.lock().unwrap()["vector"].lossy_values, 0);
+    }
+}
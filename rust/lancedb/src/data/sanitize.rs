@@ -18,7 +18,7 @@ use arrow_array::{
     cast::AsArray,
     types::{Float16Type, Float32Type, Float64Type, Int32Type, Int64Type},
     Array, ArrowNumericType, FixedSizeListArray, PrimitiveArray, RecordBatch, RecordBatchIterator,
-    RecordBatchReader,
+    RecordBatchReader, StructArray,
 };
 use arrow_cast::{can_cast_types, cast};
 use arrow_schema::{ArrowError, DataType, Field, Schema};
@@ -28,7 +28,7 @@ use log::warn;
 use num_traits::cast::AsPrimitive;
 
 use super::inspect::infer_dimension;
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 fn cast_array<I: ArrowNumericType, O: ArrowNumericType>(
     arr: &PrimitiveArray<I>,
@@ -68,6 +68,30 @@ fn coerce_array(
         return Ok(array.clone());
     }
     match (array.data_type(), field.data_type()) {
+        // Coerce struct children by name, so the children may be in a different order.
+        (DataType::Struct(_), DataType::Struct(exp_fields)) => {
+            let struct_array = array.as_struct();
+            let columns = exp_fields
+                .iter()
+                .map(|child| {
+                    struct_array
+                        .column_by_name(child.name())
+                        .ok_or_else(|| {
+                            ArrowError::SchemaError(format!(
+                                "Field {} not found in struct column {}",
+                                child.name(),
+                                field.name()
+                            ))
+                        })
+                        .and_then(|c| coerce_array(c, child))
+                })
+                .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+            Ok(Arc::new(StructArray::try_new(
+                exp_fields.clone(),
+                columns,
+                struct_array.nulls().cloned(),
+            )?) as Arc<dyn Array>)
+        }
         // Normal cast-able types.
         (adt, dt) if can_cast_types(adt, dt) => cast(&array, dt),
         // Casting between f16/f32/f64 can be lossy.
@@ -188,6 +212,35 @@ pub fn coerce_schema(
     Ok(Box::new(RecordBatchIterator::new(batches, schema)))
 }
 
+fn check_supported_type(path: &str, data_type: &DataType) -> Result<()> {
+    match data_type {
+        DataType::Map(_, _) => Err(Error::Schema {
+            message: format!(
+                "The column '{}' has type {} but map columns are not supported, \
+                    store the entries as a list of key/value structs instead",
+                path, data_type
+            ),
+        }),
+        DataType::Struct(fields) => fields.iter().try_for_each(|f| {
+            check_supported_type(&format!("{}.{}", path, f.name()), f.data_type())
+        }),
+        DataType::List(f) | DataType::LargeList(f) | DataType::FixedSizeList(f, _) => {
+            check_supported_type(path, f.data_type())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that every column (including nested fields) can be stored in a table
+///
+/// This gives a clear error up front instead of a failure part way through a write.
+pub(crate) fn check_supported_types(schema: &Schema) -> Result<()> {
+    schema
+        .fields()
+        .iter()
+        .try_for_each(|f| check_supported_type(f.name(), f.data_type()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -198,7 +251,7 @@ mod tests {
         FixedSizeListArray, Float16Array, Float32Array, Float64Array, Int32Array, Int8Array,
         RecordBatch, RecordBatchIterator, StringArray,
     };
-    use arrow_schema::Field;
+    use arrow_schema::{Field, Fields};
     use half::f16;
     use lance::arrow::FixedSizeListArrayExt;
 
@@ -280,4 +333,94 @@ mod tests {
         .unwrap();
         assert_eq!(batch, &expected);
     }
+
+    #[test]
+    fn test_coerce_struct() {
+        let actual_fields = Fields::from(vec![
+            Field::new("b", DataType::Float64, true),
+            Field::new("a", DataType::Int32, true),
+        ]);
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "s",
+            DataType::Struct(actual_fields.clone()),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StructArray::new(
+                actual_fields,
+                vec![
+                    Arc::new(Float64Array::from(vec![0.5, 1.5])),
+                    Arc::new(Int32Array::from(vec![1, 2])),
+                ],
+                None,
+            ))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+
+        let expected_fields = Fields::from(vec![
+            Field::new("a", DataType::Int8, true),
+            Field::new("b", DataType::Float32, true),
+        ]);
+        let expected_schema = Arc::new(Schema::new(vec![Field::new(
+            "s",
+            DataType::Struct(expected_fields.clone()),
+            true,
+        )]));
+        let batches = coerce_schema(reader, expected_schema.clone())
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let expected = RecordBatch::try_new(
+            expected_schema,
+            vec![Arc::new(StructArray::new(
+                expected_fields,
+                vec![
+                    Arc::new(Int8Array::from(vec![1, 2])),
+                    Arc::new(Float32Array::from(vec![0.5, 1.5])),
+                ],
+                None,
+            ))],
+        )
+        .unwrap();
+        assert_eq!(batches, vec![expected]);
+    }
+
+    #[test]
+    fn test_check_supported_types() {
+        let entries = Field::new(
+            "entries",
+            DataType::Struct(Fields::from(vec![
+                Field::new("keys", DataType::Utf8, false),
+                Field::new("values", DataType::Utf8, true),
+            ])),
+            false,
+        );
+        let nested_map = Schema::new(vec![Field::new(
+            "metadata",
+            DataType::Struct(Fields::from(vec![Field::new(
+                "attrs",
+                DataType::Map(Arc::new(entries), false),
+                true,
+            )])),
+            true,
+        )]);
+        let err = check_supported_types(&nested_map).unwrap_err().to_string();
+        assert!(err.contains("metadata.attrs"), "{}", err);
+
+        let supported = Schema::new(vec![
+            Field::new("blob", DataType::LargeBinary, true),
+            Field::new(
+                "nested",
+                DataType::List(Arc::new(Field::new(
+                    "item",
+                    DataType::List(Arc::new(Field::new("item", DataType::Int32, true))),
+                    true,
+                ))),
+                true,
+            ),
+        ]);
+        assert!(check_supported_types(&supported).is_ok());
+    }
 }
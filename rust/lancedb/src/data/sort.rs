@@ -0,0 +1,114 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sorting incoming data before it is written
+//!
+//! Lance keeps statistics about the values in each fragment.  When the data of
+//! a table is written in order of a column (e.g. a timestamp) each fragment
+//! covers a narrow range of that column and scans that filter on it can skip
+//! most of the fragments.
+
+use arrow::compute::{concat_batches, sort_to_indices, SortOptions};
+use arrow_array::{RecordBatchIterator, RecordBatchReader};
+
+use crate::arrow::take_record_batch;
+use crate::error::{Error, Result};
+
+/// Sort the data by a column, in ascending order with nulls last
+///
+/// All of the data is read into memory.  The sorted data is returned in
+/// batches the size of the largest incoming batch.
+pub(crate) fn sort_by_column(
+    data: Box<dyn RecordBatchReader + Send>,
+    column: &str,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = data.schema();
+    let index = schema.index_of(column).map_err(|_| Error::InvalidInput {
+        message: format!(
+            "cannot sort by '{}': there is no top level column with that name",
+            column
+        ),
+    })?;
+    let batches = data.collect::<std::result::Result<Vec<_>, _>>()?;
+    let batch_size = batches
+        .iter()
+        .map(|batch| batch.num_rows())
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let batch = concat_batches(&schema, &batches)?;
+    let options = SortOptions {
+        descending: false,
+        nulls_first: false,
+    };
+    let order = sort_to_indices(batch.column(index), Some(options), None)?;
+    let sorted = take_record_batch(&batch, &order)?;
+    let num_rows = sorted.num_rows();
+    let batches = (0..num_rows)
+        .step_by(batch_size)
+        .map(|offset| Ok(sorted.slice(offset, batch_size.min(num_rows - offset))))
+        .collect::<Vec<_>>();
+    Ok(Box::new(RecordBatchIterator::new(batches, schema)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+
+    #[test]
+    fn test_sort_by_column() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ts", DataType::Int32, true),
+            Field::new("id", DataType::Int32, false),
+        ]));
+        let batch = |ts: Vec<Option<i32>>, id: Vec<i32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(ts)),
+                    Arc::new(Int32Array::from(id)),
+                ],
+            )
+        };
+        let data = RecordBatchIterator::new(
+            vec![
+                batch(vec![Some(5), None, Some(1)], vec![0, 1, 2]),
+                batch(vec![Some(3), Some(2)], vec![3, 4]),
+            ],
+            schema.clone(),
+        );
+
+        let sorted = sort_by_column(Box::new(data), "ts")
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            sorted.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+        let ids = sorted
+            .iter()
+            .flat_map(|b| b.column(1).as_primitive::<Int32Type>().values().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 4, 3, 0, 1]);
+
+        let empty = RecordBatchIterator::new(vec![], schema.clone());
+        assert!(sort_by_column(Box::new(empty), "nope").is_err());
+    }
+}
@@ -17,6 +17,14 @@ use std::sync::PoisonError;
 use arrow_schema::ArrowError;
 use snafu::Snafu;
 
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The errors returned by LanceDb
+///
+/// Errors from Lance are mapped to the specific variants below (e.g.
+/// [`Error::CommitConflict`] or [`Error::NotFound`]) where possible, with the
+/// original error kept as the source.  Use [`Error::is_retryable`] to decide
+/// whether an operation can be retried.
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub(crate)))]
 pub enum Error {
@@ -37,6 +45,31 @@ pub enum Error {
     Schema { message: String },
     #[snafu(display("Runtime error: {message}"))]
     Runtime { message: String },
+    #[snafu(display("Overloaded: {message}"))]
+    Overloaded { message: String },
+    /// The operation needs more memory than its budget, see
+    /// [`crate::connection::memory`]
+    #[snafu(display("Memory limit exceeded: {message}"))]
+    MemoryLimitExceeded { message: String },
+    /// The table handle is not allowed to perform the operation
+    #[snafu(display("Permission denied: {message}"))]
+    PermissionDenied { message: String },
+    #[snafu(display("Invalid filter '{filter}': {message}"))]
+    InvalidFilter { filter: String, message: String },
+    /// There is no index with the given name, or on the given column
+    ///
+    /// Also returned for the index lookups made by Lance, e.g. for the
+    /// statistics of an index.
+    #[snafu(display("No index found for '{name}'"))]
+    IndexNotFound { name: String },
+    /// Another writer committed a conflicting change to the table
+    #[snafu(display("Commit conflict at version {version}: {source}"))]
+    CommitConflict { version: u64, source: lance::Error },
+    /// A file or dataset does not exist
+    #[snafu(display("Not found: {path}"))]
+    NotFound { path: String, source: BoxError },
+    #[snafu(display("IO error: {source}"))]
+    Io { source: BoxError },
 
     // 3rd party / external errors
     #[snafu(display("object_store error: {source}"))]
@@ -65,17 +98,107 @@ impl From<ArrowError> for Error {
     }
 }
 
+impl Error {
+    /// Whether the operation that failed may succeed if it is retried
+    ///
+    /// This is true for commit conflicts (after the conflicting commit the
+    /// operation can be attempted again on the new version), for overload and
+    /// for transient IO failures.  Errors caused by the request itself, such as
+    /// invalid input, a missing table or file, or a denied permission, are not
+    /// retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::CommitConflict { .. } | Self::Overloaded { .. } => true,
+            Self::Io { source } => !is_permanent_io_error(source.as_ref()),
+            Self::ObjectStore { source } => matches!(
+                source,
+                object_store::Error::Generic { .. } | object_store::Error::JoinError { .. }
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// The IO error kinds caused by the request rather than by a transient failure
+fn is_permanent_io_error_kind(kind: std::io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        std::io::ErrorKind::NotFound
+            | std::io::ErrorKind::PermissionDenied
+            | std::io::ErrorKind::InvalidInput
+    )
+}
+
+/// The messages of the permanent IO error kinds, see [`is_permanent_io_error`]
+const PERMANENT_IO_ERROR_MESSAGES: &[&str] = &[
+    "not found",
+    "no such file",
+    "permission denied",
+    "access denied",
+    "invalid input",
+];
+
+/// Whether an IO error is caused by the request rather than by a transient failure
+///
+/// A [`std::io::Error`] in the chain of sources is classified by its kind.
+/// Lance keeps only the message of the IO errors it wraps, so these are
+/// classified by their message.
+fn is_permanent_io_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(error) = error.downcast_ref::<std::io::Error>() {
+            return is_permanent_io_error_kind(error.kind());
+        }
+        current = error.source();
+    }
+    let message = error.to_string().to_lowercase();
+    PERMANENT_IO_ERROR_MESSAGES
+        .iter()
+        .any(|permanent| message.contains(permanent))
+}
+
 impl From<lance::Error> for Error {
     fn from(source: lance::Error) -> Self {
         // TODO: Once Lance is changed to preserve ObjectStore, DataFusion, and Arrow errors, we can
         // pass those variants through here as well.
-        Self::Lance { source }
+        match &source {
+            lance::Error::CommitConflict { version, .. } => Self::CommitConflict {
+                version: *version,
+                source,
+            },
+            lance::Error::DatasetNotFound { path, .. } => Self::NotFound {
+                path: path.clone(),
+                source: Box::new(source),
+            },
+            lance::Error::NotFound { uri, .. } => Self::NotFound {
+                path: uri.clone(),
+                source: Box::new(source),
+            },
+            lance::Error::IndexNotFound { identity, .. } => Self::IndexNotFound {
+                name: identity.clone(),
+            },
+            lance::Error::IO { .. } => Self::Io {
+                source: Box::new(source),
+            },
+            _ => Self::Lance { source },
+        }
     }
 }
 
 impl From<object_store::Error> for Error {
     fn from(source: object_store::Error) -> Self {
-        Self::ObjectStore { source }
+        match source {
+            object_store::Error::NotFound { path, source } => Self::NotFound { path, source },
+            source => Self::ObjectStore { source },
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io {
+            source: Box::new(source),
+        }
     }
 }
 
@@ -112,3 +235,83 @@ impl From<url::ParseError> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_store_errors() {
+        let err = Error::from(object_store::Error::NotFound {
+            path: "a/b.lance".to_string(),
+            source: "missing".into(),
+        });
+        assert!(matches!(err, Error::NotFound { ref path, .. } if path == "a/b.lance"));
+        assert!(!err.is_retryable());
+        // The original error is kept as the source
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "missing"
+        );
+
+        let err = Error::from(object_store::Error::Generic {
+            store: "S3",
+            source: "connection reset".into(),
+        });
+        assert!(matches!(err, Error::ObjectStore { .. }));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let err = Error::from(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "timed out",
+        ));
+        assert!(matches!(err, Error::Io { .. }));
+        assert!(err.is_retryable());
+        for kind in [
+            std::io::ErrorKind::NotFound,
+            std::io::ErrorKind::PermissionDenied,
+            std::io::ErrorKind::InvalidInput,
+        ] {
+            let err = Error::from(std::io::Error::new(kind, "failed"));
+            assert!(matches!(err, Error::Io { .. }));
+            assert!(!err.is_retryable());
+        }
+
+        // Lance keeps only the message of IO errors
+        let err = Error::from(lance::Error::IO {
+            message: std::io::Error::from(std::io::ErrorKind::NotFound).to_string(),
+            location: snafu::location!(),
+        });
+        assert!(matches!(err, Error::Io { .. }));
+        assert!(!err.is_retryable());
+        let err = Error::from(lance::Error::IO {
+            message: "connection reset by peer".to_string(),
+            location: snafu::location!(),
+        });
+        assert!(err.is_retryable());
+
+        let err = Error::from(lance::Error::IndexNotFound {
+            identity: "name=vector_idx".to_string(),
+            location: snafu::location!(),
+        });
+        assert!(matches!(err, Error::IndexNotFound { .. }));
+        assert!(!err.is_retryable());
+
+        assert!(Error::Overloaded {
+            message: "busy".to_string()
+        }
+        .is_retryable());
+        assert!(!Error::InvalidFilter {
+            filter: "x >".to_string(),
+            message: "unexpected end".to_string()
+        }
+        .is_retryable());
+        assert!(!Error::TableNotFound {
+            name: "t".to_string()
+        }
+        .is_retryable());
+    }
+}
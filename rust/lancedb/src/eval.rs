@@ -0,0 +1,462 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measuring the recall of vector searches
+//!
+//! An approximate (ANN) vector search trades recall, the fraction of the true
+//! nearest neighbors that are found, for speed.  [`crate::Table::evaluate_recall`]
+//! runs a set of queries with several search settings, compares the results
+//! with the true nearest neighbors and reports the recall and latency of each
+//! setting.  This helps to pick the smallest `nprobes` and `refine_factor`
+//! that give the recall an application needs.
+//!
+//! The true nearest neighbors are found with an exact search (see
+//! [`crate::query::VectorQuery::bypass_vector_index`]) unless they are given
+//! with [`EvaluateRecall::ground_truth`].  If no queries are given, the
+//! vectors of a random sample of rows of the table are used as queries.
+//!
+//! ```
+//! # use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchIterator};
+//! # use arrow_array::types::Float32Type;
+//! # use arrow_schema::{DataType, Field, Schema};
+//! # use std::sync::Arc;
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! # let tmpdir = tempfile::tempdir().unwrap();
+//! # let db = lancedb::connect(tmpdir.path().to_str().unwrap()).execute().await.unwrap();
+//! # let schema = Arc::new(Schema::new(vec![Field::new(
+//! #     "vector",
+//! #     DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+//! #     true,
+//! # )]));
+//! # let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+//! #     (0..100).map(|i| Some(vec![Some(i as f32), Some(1.0)])),
+//! #     2,
+//! # );
+//! # let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+//! # let batches = RecordBatchIterator::new(vec![Ok(batch)], schema);
+//! # let table = db.create_table("items", Box::new(batches)).execute().await.unwrap();
+//! let report = table
+//!     .evaluate_recall(vec![], 10)
+//!     .nprobes(&[5, 10, 20])
+//!     .execute()
+//!     .await
+//!     .unwrap();
+//! for result in &report.results {
+//!     println!(
+//!         "nprobes={} recall={:.3} latency={:?}",
+//!         result.settings.nprobes, result.recall, result.mean_latency
+//!     );
+//! }
+//! # });
+//! ```
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, UInt64Type};
+use arrow_array::Array;
+use arrow_schema::DataType;
+use futures::TryStreamExt;
+
+use crate::error::{Error, Result};
+use crate::query::late::ROW_ID_COLUMN;
+use crate::query::{ExecutableQuery, QueryBase, Select};
+use crate::table::Table;
+use crate::utils::default_vector_column;
+use crate::DistanceType;
+
+/// The number of rows sampled as queries if no queries are given
+const DEFAULT_SAMPLE_QUERIES: usize = 100;
+/// The values of `nprobes` evaluated by default
+const DEFAULT_NPROBES: [usize; 5] = [5, 10, 20, 50, 100];
+
+/// The settings of a vector search that affect its recall
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchSettings {
+    /// See [`crate::query::VectorQuery::nprobes`]
+    pub nprobes: usize,
+    /// See [`crate::query::VectorQuery::refine_factor`]
+    pub refine_factor: Option<u32>,
+}
+
+/// The recall and latency of one search setting
+#[derive(Debug, Clone)]
+pub struct RecallResult {
+    pub settings: SearchSettings,
+    /// The average fraction of the true nearest neighbors that were found
+    pub recall: f64,
+    /// The average time of a query
+    pub mean_latency: Duration,
+    /// The time within which 95% of the queries completed
+    pub p95_latency: Duration,
+}
+
+/// The results of [`EvaluateRecall::execute`]
+#[derive(Debug, Clone)]
+pub struct RecallReport {
+    /// The number of results of each query
+    pub k: usize,
+    /// The number of queries that were run for each setting
+    pub num_queries: usize,
+    /// The average time of an exact search, None if the ground truth was given
+    pub exact_latency: Option<Duration>,
+    /// The results of each setting, in the order they were evaluated
+    pub results: Vec<RecallResult>,
+}
+
+impl RecallReport {
+    /// The fastest setting that reaches the target recall, if any
+    pub fn fastest_with_recall(&self, target_recall: f64) -> Option<&RecallResult> {
+        self.results
+            .iter()
+            .filter(|r| r.recall >= target_recall)
+            .min_by_key(|r| r.mean_latency)
+    }
+}
+
+/// A builder for a recall evaluation, see [`crate::Table::evaluate_recall`]
+pub struct EvaluateRecall {
+    table: Table,
+    queries: Vec<Vec<f32>>,
+    k: usize,
+    column: Option<String>,
+    ground_truth: Option<Vec<Vec<u64>>>,
+    nprobes: Vec<usize>,
+    refine_factors: Vec<Option<u32>>,
+    distance_type: Option<DistanceType>,
+    sample_size: usize,
+    seed: Option<u64>,
+}
+
+impl EvaluateRecall {
+    pub(crate) fn new(table: Table, queries: Vec<Vec<f32>>, k: usize) -> Self {
+        Self {
+            table,
+            queries,
+            k,
+            column: None,
+            ground_truth: None,
+            nprobes: DEFAULT_NPROBES.to_vec(),
+            refine_factors: vec![None],
+            distance_type: None,
+            sample_size: DEFAULT_SAMPLE_QUERIES,
+            seed: None,
+        }
+    }
+
+    /// The vector column to search
+    ///
+    /// This is only needed if the table has more than one vector column.
+    pub fn column(mut self, column: &str) -> Self {
+        self.column = Some(column.to_string());
+        self
+    }
+
+    /// The row ids of the true nearest neighbors of each query
+    ///
+    /// There must be one list per query.  If this is not given, the nearest
+    /// neighbors are found with an exact search.
+    pub fn ground_truth(mut self, ground_truth: Vec<Vec<u64>>) -> Self {
+        self.ground_truth = Some(ground_truth);
+        self
+    }
+
+    /// The values of `nprobes` to evaluate
+    ///
+    /// By default, these are 5, 10, 20, 50 and 100.
+    pub fn nprobes(mut self, nprobes: &[usize]) -> Self {
+        self.nprobes = nprobes.to_vec();
+        self
+    }
+
+    /// The values of `refine_factor` to evaluate, None means no refinement
+    ///
+    /// Every value is evaluated with every value of `nprobes`.  By default,
+    /// only searches without refinement are evaluated.
+    pub fn refine_factors(mut self, refine_factors: &[Option<u32>]) -> Self {
+        self.refine_factors = refine_factors.to_vec();
+        self
+    }
+
+    /// The distance type of the searches, see [`crate::query::VectorQuery::distance_type`]
+    pub fn distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.distance_type = Some(distance_type);
+        self
+    }
+
+    /// The number of rows to sample as queries if no queries were given
+    ///
+    /// By default, this is 100.
+    pub fn sample_size(mut self, sample_size: usize) -> Self {
+        self.sample_size = sample_size;
+        self
+    }
+
+    /// The seed of the sample of queries, see [`crate::Table::sample`]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Run the queries and measure the recall of each setting
+    pub async fn execute(mut self) -> Result<RecallReport> {
+        if self.k == 0 {
+            return Err(Error::InvalidInput {
+                message: "k must be at least 1".to_string(),
+            });
+        }
+        if self.column.is_none() {
+            let dim = self.queries.first().map(|q| q.len() as i32);
+            let schema = self.table.schema().await?;
+            self.column = Some(default_vector_column(&schema, dim)?);
+        }
+        let queries = if self.queries.is_empty() {
+            self.sample_queries().await?
+        } else {
+            self.queries.clone()
+        };
+        if queries.is_empty() {
+            return Err(Error::InvalidInput {
+                message: "there are no queries to evaluate".to_string(),
+            });
+        }
+
+        let (ground_truth, exact_latency) = match &self.ground_truth {
+            Some(ground_truth) => {
+                if ground_truth.len() != queries.len() {
+                    return Err(Error::InvalidInput {
+                        message: format!(
+                            "there are {} queries but the ground truth has {} entries",
+                            queries.len(),
+                            ground_truth.len()
+                        ),
+                    });
+                }
+                (ground_truth.clone(), None)
+            }
+            None => {
+                let mut ground_truth = Vec::with_capacity(queries.len());
+                let mut latencies = Vec::with_capacity(queries.len());
+                for query in &queries {
+                    let (row_ids, latency) = self.search(query, None).await?;
+                    ground_truth.push(row_ids);
+                    latencies.push(latency);
+                }
+                (ground_truth, Some(mean(&latencies)))
+            }
+        };
+
+        let mut results = Vec::new();
+        for refine_factor in &self.refine_factors {
+            for nprobes in &self.nprobes {
+                let settings = SearchSettings {
+                    nprobes: *nprobes,
+                    refine_factor: *refine_factor,
+                };
+                let mut recall = 0.0;
+                let mut latencies = Vec::with_capacity(queries.len());
+                for (query, truth) in queries.iter().zip(&ground_truth) {
+                    let (row_ids, latency) = self.search(query, Some(&settings)).await?;
+                    recall += query_recall(&row_ids, truth, self.k);
+                    latencies.push(latency);
+                }
+                latencies.sort();
+                results.push(RecallResult {
+                    settings,
+                    recall: recall / queries.len() as f64,
+                    mean_latency: mean(&latencies),
+                    p95_latency: latencies[(latencies.len() * 95).div_ceil(100) - 1],
+                });
+            }
+        }
+        Ok(RecallReport {
+            k: self.k,
+            num_queries: queries.len(),
+            exact_latency,
+            results,
+        })
+    }
+
+    /// Use the vectors of a sample of rows as queries
+    async fn sample_queries(&self) -> Result<Vec<Vec<f32>>> {
+        // The column is resolved by `execute`
+        let column = self.column.clone().unwrap_or_default();
+        let sample = self.table.sample(self.sample_size, self.seed).await?;
+        let vectors = sample
+            .column_by_name(&column)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!("there is no column named '{}'", column),
+            })?;
+        let vectors = match vectors.data_type() {
+            DataType::FixedSizeList(_, _) => vectors.as_fixed_size_list(),
+            other => {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the column '{}' has type {} which is not a vector column",
+                        column, other
+                    ),
+                })
+            }
+        };
+        let mut queries = Vec::with_capacity(vectors.len());
+        for i in 0..vectors.len() {
+            if vectors.is_null(i) {
+                continue;
+            }
+            let values = arrow_cast::cast(&vectors.value(i), &DataType::Float32)?;
+            queries.push(values.as_primitive::<Float32Type>().values().to_vec());
+        }
+        Ok(queries)
+    }
+
+    /// Run a search, exact if there are no settings
+    async fn search(
+        &self,
+        query: &[f32],
+        settings: Option<&SearchSettings>,
+    ) -> Result<(Vec<u64>, Duration)> {
+        // Only the vector column is read, all settings pay the same cost for it
+        let column = self.column.clone().unwrap_or_default();
+        let mut search = self
+            .table
+            .query()
+            .limit(self.k)
+            .select(Select::columns(&[column.as_str()]))
+            .nearest_to(query)?
+            .column(&column);
+        if let Some(distance_type) = self.distance_type {
+            search = search.distance_type(distance_type);
+        }
+        search = match settings {
+            Some(settings) => {
+                search = search.nprobes(settings.nprobes);
+                match settings.refine_factor {
+                    Some(refine_factor) => search.refine_factor(refine_factor),
+                    None => search,
+                }
+            }
+            None => search.bypass_vector_index(),
+        };
+        search.base.with_row_id = true;
+
+        let start = Instant::now();
+        let batches = search.execute().await?.try_collect::<Vec<_>>().await?;
+        let latency = start.elapsed();
+        let row_ids = batches
+            .iter()
+            .filter_map(|batch| batch.column_by_name(ROW_ID_COLUMN))
+            .flat_map(|row_ids| row_ids.as_primitive::<UInt64Type>().values().to_vec())
+            .collect();
+        Ok((row_ids, latency))
+    }
+}
+
+/// The fraction of the true nearest neighbors in the results
+fn query_recall(results: &[u64], truth: &[u64], k: usize) -> f64 {
+    let truth = truth.iter().take(k).collect::<HashSet<_>>();
+    if truth.is_empty() {
+        return 1.0;
+    }
+    let found = results.iter().take(k).filter(|r| truth.contains(r)).count();
+    found as f64 / truth.len() as f64
+}
+
+fn mean(latencies: &[Duration]) -> Duration {
+    latencies.iter().sum::<Duration>() / latencies.len().max(1) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::types::Float32Type;
+    use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{Field, Schema};
+    use tempfile::tempdir;
+
+    use crate::connect;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_evaluate_recall() {
+        let tmp_dir = tempdir().unwrap();
+        let conn = connect(tmp_dir.path().to_str().unwrap())
+            .execute()
+            .await
+            .unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+            true,
+        )]));
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            (0..50).map(|i| Some(vec![Some(i as f32), Some(0.0)])),
+            2,
+        );
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let table = conn
+            .create_table(
+                "items",
+                RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        // Without an index every search is exact
+        let report = table
+            .evaluate_recall(vec![], 5)
+            .nprobes(&[1, 10])
+            .refine_factors(&[None, Some(2)])
+            .sample_size(4)
+            .seed(1)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(report.num_queries, 4);
+        assert!(report.exact_latency.is_some());
+        assert_eq!(report.results.len(), 4);
+        assert!(report.results.iter().all(|r| r.recall == 1.0));
+        assert!(report.fastest_with_recall(0.9).is_some());
+
+        // The nearest neighbors of [0, 0] are the first rows
+        let report = table
+            .evaluate_recall(vec![vec![0.0, 0.0]], 2)
+            .ground_truth(vec![vec![0, 5]])
+            .nprobes(&[1])
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(report.exact_latency, None);
+        assert_eq!(report.results[0].recall, 0.5);
+
+        assert!(table
+            .evaluate_recall(vec![vec![0.0, 0.0]], 2)
+            .ground_truth(vec![])
+            .execute()
+            .await
+            .is_err());
+        assert!(table.evaluate_recall(vec![], 0).execute().await.is_err());
+    }
+
+    #[test]
+    fn test_query_recall() {
+        assert_eq!(query_recall(&[1, 2, 3], &[3, 2, 1], 3), 1.0);
+        assert_eq!(query_recall(&[1, 2, 4, 5], &[1, 2, 3, 4], 2), 1.0);
+        assert_eq!(query_recall(&[1, 5], &[1, 2], 2), 0.5);
+        assert_eq!(query_recall(&[], &[], 10), 1.0);
+    }
+}
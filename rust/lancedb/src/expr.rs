@@ -0,0 +1,407 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A builder for filter expressions
+//!
+//! Filters are ultimately passed to LanceDb as SQL strings.  Building these
+//! strings by concatenating user input is error prone and can allow a user
+//! to inject arbitrary conditions into the filter.  The builder in this module
+//! quotes column names and escapes literal values so that the generated SQL
+//! always has the structure that the code describes.
+//!
+//! ```
+//! use lancedb::expr::{col, param};
+//! let user_input = "x' OR 1=1 OR 'y";
+//! let filter = col("id").gt(5).and(col("tag").eq(param(user_input)));
+//! assert_eq!(
+//!     filter.to_sql(),
+//!     "((`id` > 5) AND (`tag` = 'x'' OR 1=1 OR ''y'))"
+//! );
+//! ```
+//!
+//! The generated SQL can be used anywhere a filter string is accepted (e.g.
+//! [`crate::query::QueryBase::only_if`] or [`crate::Table::count_rows`]).
+
+use std::fmt::{Display, Formatter};
+
+/// A literal value in a filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "NULL"),
+            Self::Boolean(v) => write!(f, "{}", v),
+            Self::Int(v) => write!(f, "{}", v),
+            Self::UInt(v) => write!(f, "{}", v),
+            Self::Float(v) if v.is_finite() => write!(f, "{:?}", v),
+            Self::Float(v) => write!(f, "CAST('{}' AS DOUBLE)", v),
+            Self::String(v) => write!(f, "'{}'", v.replace('\'', "''")),
+        }
+    }
+}
+
+macro_rules! impl_from_value {
+    ($variant:ident, $target:ty, $($source:ty),+) => {
+        $(
+            impl From<$source> for Value {
+                fn from(value: $source) -> Self {
+                    Self::$variant(value as $target)
+                }
+            }
+        )+
+    };
+}
+
+impl_from_value!(Int, i64, i8, i16, i32, i64);
+impl_from_value!(UInt, u64, u8, u16, u32, u64);
+impl_from_value!(Float, f64, f32, f64);
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Boolean(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
+/// An operator that combines two expressions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Gt,
+    GtEq,
+    Lt,
+    LtEq,
+    And,
+    Or,
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Self::Eq => "=",
+            Self::NotEq => "!=",
+            Self::Gt => ">",
+            Self::GtEq => ">=",
+            Self::Lt => "<",
+            Self::LtEq => "<=",
+            Self::And => "AND",
+            Self::Or => "OR",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// A filter expression
+///
+/// Create expressions with [`col`] and [`param`] and combine them with the
+/// methods on this type.  Use [`Expr::to_sql`] to get the filter string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A (possibly nested) column reference
+    Column(Vec<String>),
+    /// A literal value
+    Literal(Value),
+    /// A binary operation
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    Not(Box<Expr>),
+    IsNull(Box<Expr>),
+    IsNotNull(Box<Expr>),
+    InList(Box<Expr>, Vec<Value>),
+    Like(Box<Expr>, Value),
+}
+
+/// A reference to a column
+///
+/// Use [`Expr::field`] to refer to a field of a struct column
+pub fn col(name: impl Into<String>) -> Expr {
+    Expr::Column(vec![name.into()])
+}
+
+/// A literal value, typically provided by a user
+///
+/// The value is always escaped and can never change the structure of the filter
+pub fn param(value: impl Into<Value>) -> Expr {
+    Expr::Literal(value.into())
+}
+
+impl<T: Into<Value>> From<T> for Expr {
+    fn from(value: T) -> Self {
+        Self::Literal(value.into())
+    }
+}
+
+impl From<Expr> for String {
+    fn from(expr: Expr) -> Self {
+        expr.to_sql()
+    }
+}
+
+impl Expr {
+    fn binary(self, op: BinaryOp, other: impl Into<Expr>) -> Self {
+        Self::Binary {
+            left: Box::new(self),
+            op,
+            right: Box::new(other.into()),
+        }
+    }
+
+    /// Refer to a field of a struct column
+    ///
+    /// For example, `col("metadata").field("user")` refers to `metadata.user`
+    ///
+    /// # Panics
+    ///
+    /// If this expression is not a column reference
+    pub fn field(self, name: impl Into<String>) -> Self {
+        match self {
+            Self::Column(mut path) => {
+                path.push(name.into());
+                Self::Column(path)
+            }
+            _ => panic!("field can only be called on a column reference"),
+        }
+    }
+
+    /// Matches if the values are equal
+    ///
+    /// Comparing with a null value is rendered as `IS NULL`, since `= NULL`
+    /// never matches in SQL.
+    pub fn eq(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::Eq, other)
+    }
+
+    /// Matches if the values are not equal
+    ///
+    /// Comparing with a null value is rendered as `IS NOT NULL`.
+    pub fn not_eq(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::NotEq, other)
+    }
+
+    pub fn gt(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::Gt, other)
+    }
+
+    pub fn gt_eq(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::GtEq, other)
+    }
+
+    pub fn lt(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::Lt, other)
+    }
+
+    pub fn lt_eq(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::LtEq, other)
+    }
+
+    pub fn and(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::And, other)
+    }
+
+    pub fn or(self, other: impl Into<Expr>) -> Self {
+        self.binary(BinaryOp::Or, other)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+
+    pub fn is_null(self) -> Self {
+        Self::IsNull(Box::new(self))
+    }
+
+    pub fn is_not_null(self) -> Self {
+        Self::IsNotNull(Box::new(self))
+    }
+
+    /// Matches if the value is equal to any of the given values
+    ///
+    /// An empty list never matches and is rendered as `false`.
+    pub fn is_in<T: Into<Value>>(self, values: impl IntoIterator<Item = T>) -> Self {
+        Self::InList(Box::new(self), values.into_iter().map(Into::into).collect())
+    }
+
+    /// Matches if the value matches the SQL `LIKE` pattern
+    ///
+    /// The pattern is escaped as a literal but `%` and `_` keep their
+    /// wildcard meaning.
+    pub fn like(self, pattern: impl Into<String>) -> Self {
+        Self::Like(Box::new(self), Value::String(pattern.into()))
+    }
+
+    /// Generate the SQL filter string for this expression
+    pub fn to_sql(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Column(path) => {
+                let quoted = path
+                    .iter()
+                    .map(|part| format!("`{}`", part.replace('`', "``")))
+                    .collect::<Vec<_>>();
+                write!(f, "{}", quoted.join("."))
+            }
+            Self::Literal(value) => write!(f, "{}", value),
+            Self::Binary { left, op, right } => {
+                let null = Self::Literal(Value::Null);
+                match op {
+                    BinaryOp::Eq | BinaryOp::NotEq if **left == null || **right == null => {
+                        let expr = if **right == null { left } else { right };
+                        let is = if *op == BinaryOp::Eq { "IS" } else { "IS NOT" };
+                        write!(f, "({} {} NULL)", expr, is)
+                    }
+                    _ => write!(f, "({} {} {})", left, op, right),
+                }
+            }
+            Self::Not(expr) => write!(f, "(NOT {})", expr),
+            Self::IsNull(expr) => write!(f, "({} IS NULL)", expr),
+            Self::IsNotNull(expr) => write!(f, "({} IS NOT NULL)", expr),
+            Self::InList(_, values) if values.is_empty() => write!(f, "false"),
+            Self::InList(expr, values) => {
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                write!(f, "({} IN ({}))", expr, values.join(", "))
+            }
+            Self::Like(expr, pattern) => write!(f, "({} LIKE {})", expr, pattern),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::connect;
+
+    #[test]
+    fn test_to_sql() {
+        assert_eq!(col("id").gt(5).to_sql(), "(`id` > 5)");
+        assert_eq!(
+            col("a").eq(1.5).or(col("b").is_null().not()).to_sql(),
+            "((`a` = 1.5) OR (NOT (`b` IS NULL)))"
+        );
+        assert_eq!(
+            col("metadata").field("user").field("id").eq(3).to_sql(),
+            "(`metadata`.`user`.`id` = 3)"
+        );
+        assert_eq!(
+            col("tag").is_in(["a", "b'c"]).to_sql(),
+            "(`tag` IN ('a', 'b''c'))"
+        );
+        assert_eq!(
+            col("weird`name").lt_eq(0u8).to_sql(),
+            "(`weird``name` <= 0)"
+        );
+        assert_eq!(
+            col("x").eq(param(Option::<i32>::None)).to_sql(),
+            "(`x` IS NULL)"
+        );
+        assert_eq!(
+            param(Value::Null).not_eq(col("x")).to_sql(),
+            "(`x` IS NOT NULL)"
+        );
+        assert_eq!(
+            col("x")
+                .is_in(Vec::<i32>::new())
+                .or(col("y").eq(1))
+                .to_sql(),
+            "(false OR (`y` = 1))"
+        );
+        assert_eq!(col("x").like("ab%").to_sql(), "(`x` LIKE 'ab%')");
+    }
+
+    #[tokio::test]
+    async fn test_filter_with_user_input() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("tag", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..4)),
+                Arc::new(StringArray::from(vec!["a", "b", "a", "it's"])),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table(
+                "my_table",
+                RecordBatchIterator::new(vec![Ok(batch)], schema),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        let filter = col("id").gt(0).and(col("tag").eq(param("a")));
+        assert_eq!(table.count_rows(Some(filter.into())).await.unwrap(), 1);
+
+        let filter = col("tag").eq(param("it's"));
+        assert_eq!(table.count_rows(Some(filter.into())).await.unwrap(), 1);
+
+        let filter = col("tag").eq(param(Option::<&str>::None));
+        assert_eq!(table.count_rows(Some(filter.into())).await.unwrap(), 0);
+        let filter = col("tag").not_eq(param(Option::<&str>::None));
+        assert_eq!(table.count_rows(Some(filter.into())).await.unwrap(), 4);
+        let filter = col("tag").is_in(Vec::<&str>::new());
+        assert_eq!(table.count_rows(Some(filter.into())).await.unwrap(), 0);
+
+        // An injection attempt only ever matches the literal string
+        let filter = col("tag").eq(param("a' OR '1'='1"));
+        assert_eq!(table.count_rows(Some(filter.into())).await.unwrap(), 0);
+    }
+}
@@ -12,15 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::{table::TableInternal, Result};
+use arrow_schema::{Schema, SchemaRef};
+use serde::{Deserialize, Serialize};
+
+use crate::table::estimate::{estimate_index, CostEstimate};
+use crate::{error::Error, table::TableInternal, Result};
 
 use self::{scalar::BTreeIndexBuilder, vector::IvfPqIndexBuilder};
 
+pub mod metadata;
 pub mod scalar;
 pub mod vector;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Index {
     Auto,
     BTree(BTreeIndexBuilder),
@@ -60,6 +67,14 @@ impl IndexBuilder {
     pub async fn execute(self) -> Result<()> {
         self.parent.clone().create_index(self).await
     }
+
+    /// Estimate the cost of building the index, without building it
+    ///
+    /// See [`crate::table::estimate`] for how the estimate is made.
+    pub async fn estimate(&self) -> Result<CostEstimate> {
+        let stats = self.parent.statistics().await?;
+        estimate_index(&stats, self)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -78,3 +93,48 @@ pub struct IndexConfig {
     /// be more columns to represent composite indices.
     pub columns: Vec<String>,
 }
+
+/// The schema metadata key used to record indices declared with
+/// [`crate::connection::CreateTableBuilder::declare_index`]
+pub(crate) const PENDING_INDICES_KEY: &str = "lancedb:pending_indices";
+
+/// The default number of rows a table must have before declared indices are built
+pub const DEFAULT_PENDING_INDEX_THRESHOLD: usize = 100_000;
+
+/// An index that has been declared but not yet built
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingIndex {
+    pub columns: Vec<String>,
+    pub index: Index,
+}
+
+/// The set of declared indices, stored in the table schema metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PendingIndices {
+    /// The number of rows at which the indices will be built
+    pub threshold: usize,
+    pub indices: Vec<PendingIndex>,
+}
+
+impl PendingIndices {
+    pub(crate) fn from_schema(schema: &Schema) -> Result<Option<Self>> {
+        schema
+            .metadata()
+            .get(PENDING_INDICES_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the declared indices: {}", e),
+                })
+            })
+            .transpose()
+    }
+
+    pub(crate) fn apply_to_schema(&self, schema: &Schema) -> Result<SchemaRef> {
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the declared indices: {}", e),
+        })?;
+        let mut metadata: HashMap<String, String> = schema.metadata().clone();
+        metadata.insert(PENDING_INDICES_KEY.to_string(), value);
+        Ok(Arc::new(schema.clone().with_metadata(metadata)))
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Index metadata that can be exported for debugging
+//!
+//! An [`IndexMetadata`] describes how an index was built and what it looks
+//! like, without containing any of the indexed data.  It is meant to be
+//! attached to bug reports (e.g. about poor recall) and can be used to build an
+//! equivalent index on a different (possibly synthetic) dataset with
+//! [`crate::Table::import_index`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::DistanceType;
+
+use super::scalar::BTreeIndexBuilder;
+use super::vector::IvfPqIndexBuilder;
+use super::Index;
+
+/// Information about an index build, recorded when the index is created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexBuildInfo {
+    /// The parameters used to build the index, with defaults resolved
+    pub parameters: Index,
+    /// The number of rows in the table when the index was built
+    pub num_rows: usize,
+    /// How long it took to build the index
+    pub duration_ms: u64,
+    /// When the build finished, as an RFC 3339 timestamp
+    pub built_at: String,
+}
+
+/// Summary statistics of the IVF partitions of a vector index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartitionStats {
+    pub num_partitions: usize,
+    pub min_size: usize,
+    pub max_size: usize,
+    pub mean_size: f64,
+    /// The number of partitions with no rows, a high number of empty
+    /// partitions usually means the index was trained on too little data
+    pub empty_partitions: usize,
+}
+
+impl PartitionStats {
+    fn from_statistics(statistics: &serde_json::Value) -> Option<Self> {
+        let sizes = statistics
+            .get("partitions")?
+            .as_array()?
+            .iter()
+            .map(|p| p.get("size").and_then(|s| s.as_u64()).map(|s| s as usize))
+            .collect::<Option<Vec<_>>>()?;
+        if sizes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            num_partitions: sizes.len(),
+            min_size: *sizes.iter().min().unwrap(),
+            max_size: *sizes.iter().max().unwrap(),
+            mean_size: sizes.iter().sum::<usize>() as f64 / sizes.len() as f64,
+            empty_partitions: sizes.iter().filter(|s| **s == 0).count(),
+        })
+    }
+}
+
+/// A description of an index, see the [module documentation](self)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexMetadata {
+    /// The version of LanceDb that exported the metadata
+    pub lancedb_version: String,
+    pub name: String,
+    pub uuid: String,
+    pub columns: Vec<String>,
+    /// Either `IVF_PQ` or `BTREE`
+    pub index_type: String,
+    /// The version of the table when the metadata was exported
+    pub table_version: u64,
+    /// The number of rows in the table when the metadata was exported
+    pub num_rows: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partitions: Option<PartitionStats>,
+    /// Only available if the index was built through the same table handle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<IndexBuildInfo>,
+    /// The raw statistics reported by the index
+    pub statistics: serde_json::Value,
+}
+
+impl IndexMetadata {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::Other {
+            message: format!("failed to serialize index metadata: {}", e),
+            source: None,
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| Error::InvalidInput {
+            message: format!("invalid index metadata: {}", e),
+        })
+    }
+
+    /// The parameters for an index equivalent to the one described
+    ///
+    /// If the build parameters were recorded they are used as-is.  Otherwise
+    /// the parameters are recovered from the index statistics, any parameter
+    /// that cannot be recovered keeps its default value.
+    pub fn equivalent_index(&self) -> Index {
+        if let Some(build) = &self.build {
+            return build.parameters.clone();
+        }
+        if self.index_type == "BTREE" {
+            return Index::BTree(BTreeIndexBuilder::default());
+        }
+        let mut builder = IvfPqIndexBuilder::default();
+        let stats = &self.statistics;
+        if let Some(num_partitions) = stats.get("num_partitions").and_then(|n| n.as_u64()) {
+            builder = builder.num_partitions(num_partitions as u32);
+        }
+        let sub_index = stats.get("sub_index");
+        if let Some(num_sub_vectors) = sub_index
+            .and_then(|s| s.get("num_sub_vectors"))
+            .and_then(|n| n.as_u64())
+        {
+            builder = builder.num_sub_vectors(num_sub_vectors as u32);
+        }
+        if let Some(num_bits) = sub_index
+            .and_then(|s| s.get("nbits"))
+            .and_then(|n| n.as_u64())
+        {
+            builder = builder.num_bits(num_bits as u32);
+        }
+        let metric = stats
+            .get("metric_type")
+            .or_else(|| sub_index.and_then(|s| s.get("metric_type")))
+            .and_then(|m| m.as_str());
+        if let Some(distance_type) = metric.and_then(parse_distance_type) {
+            builder = builder.distance_type(distance_type);
+        }
+        Index::IvfPq(builder)
+    }
+
+    /// Create the metadata, the table information and build info are left empty
+    pub(crate) fn new(
+        name: String,
+        uuid: String,
+        columns: Vec<String>,
+        is_vector: bool,
+        statistics: serde_json::Value,
+    ) -> Self {
+        Self {
+            lancedb_version: env!("CARGO_PKG_VERSION").to_string(),
+            name,
+            uuid,
+            columns,
+            index_type: if is_vector { "IVF_PQ" } else { "BTREE" }.to_string(),
+            table_version: 0,
+            num_rows: 0,
+            partitions: PartitionStats::from_statistics(&statistics),
+            build: None,
+            statistics,
+        }
+    }
+}
+
+fn parse_distance_type(metric: &str) -> Option<DistanceType> {
+    match metric.to_lowercase().as_str() {
+        "l2" | "euclidean" => Some(DistanceType::L2),
+        "cosine" => Some(DistanceType::Cosine),
+        "dot" => Some(DistanceType::Dot),
+        _ => None,
+    }
+}
+
+/// Records the index builds made through a table handle, keyed by column
+#[derive(Debug, Default)]
+pub(crate) struct IndexBuildTracker {
+    builds: Mutex<HashMap<String, IndexBuildInfo>>,
+}
+
+impl IndexBuildTracker {
+    pub fn record(&self, column: &str, parameters: Index, num_rows: usize, duration: Duration) {
+        self.builds.lock().unwrap().insert(
+            column.to_string(),
+            IndexBuildInfo {
+                parameters,
+                num_rows,
+                duration_ms: duration.as_millis() as u64,
+                built_at: chrono::Utc::now().to_rfc3339(),
+            },
+        );
+    }
+
+    pub fn get(&self, column: &str) -> Option<IndexBuildInfo> {
+        self.builds.lock().unwrap().get(column).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equivalent_index_from_statistics() {
+        let statistics = serde_json::json!({
+            "index_type": "IVF",
+            "metric_type": "cosine",
+            "num_partitions": 3,
+            "sub_index": {"index_type": "PQ", "num_sub_vectors": 4, "nbits": 4},
+            "partitions": [{"size": 10}, {"size": 0}, {"size": 5}],
+        });
+        let metadata = IndexMetadata::new(
+            "vector_idx".to_string(),
+            "uuid".to_string(),
+            vec!["vector".to_string()],
+            true,
+            statistics,
+        );
+        let metadata = IndexMetadata::from_json(&metadata.to_json().unwrap()).unwrap();
+        assert_eq!(
+            metadata.partitions,
+            Some(PartitionStats {
+                num_partitions: 3,
+                min_size: 0,
+                max_size: 10,
+                mean_size: 5.0,
+                empty_partitions: 1,
+            })
+        );
+        let Index::IvfPq(builder) = metadata.equivalent_index() else {
+            panic!("expected an IVF PQ index");
+        };
+        assert_eq!(builder.num_partitions, Some(3));
+        assert_eq!(builder.num_sub_vectors, Some(4));
+        assert_eq!(builder.num_bits, 4);
+        assert_eq!(builder.distance_type, DistanceType::Cosine);
+    }
+
+    #[test]
+    fn test_index_without_num_bits() {
+        // Indices declared by older versions do not store num_bits
+        let json = r#"{"IvfPq":{"distance_type":"L2","num_partitions":2,
+            "num_sub_vectors":null,"sample_rate":256,"max_iterations":50}}"#;
+        let Index::IvfPq(builder) = serde_json::from_str(json).unwrap() else {
+            panic!("expected an IVF PQ index");
+        };
+        assert_eq!(builder.num_bits, 8);
+        assert_eq!(builder.num_partitions, Some(2));
+    }
+}
@@ -7,6 +7,35 @@
 //! etc.  Scalar indices can also speed up prefiltering for vector searches.  A single
 //! vector search with prefiltering can use both a scalar index and a vector index.
 
+use serde::{Deserialize, Serialize};
+
+/// How string values are normalized before they are indexed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+    /// Index the lowercase values, for filters like `lower(name) = 'foo'`
+    Lowercase,
+    /// Index the uppercase values, for filters like `upper(name) = 'FOO'`
+    Uppercase,
+}
+
+impl Normalization {
+    /// The SQL function that applies the normalization
+    pub fn function(&self) -> &'static str {
+        match self {
+            Self::Lowercase => "lower",
+            Self::Uppercase => "upper",
+        }
+    }
+
+    /// Normalize a single value, the same way as [`Self::function`]
+    pub(crate) fn apply(&self, value: &str) -> String {
+        match self {
+            Self::Lowercase => value.to_lowercase(),
+            Self::Uppercase => value.to_uppercase(),
+        }
+    }
+}
+
 /// Builder for a btree index
 ///
 /// A btree index is an index on scalar columns.  The index stores a copy of the column
@@ -22,9 +51,28 @@
 /// This index is good for scalar columns with mostly distinct values and does best when
 /// the query is highly selective.
 ///
-/// The btree index does not currently have any parameters though parameters such as the
-/// block size may be added in the future.
-#[derive(Default, Debug, Clone)]
-pub struct BTreeIndexBuilder {}
+/// By default the index compares values exactly as they are stored.  See
+/// [`Self::normalization`] for indexing normalized strings.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct BTreeIndexBuilder {
+    #[serde(default)]
+    pub(crate) normalization: Option<Normalization>,
+}
 
-impl BTreeIndexBuilder {}
+impl BTreeIndexBuilder {
+    /// Index the normalized values of a string column
+    ///
+    /// The normalized values are stored in a hidden column, named after the
+    /// function and the column (e.g. `_lower_name`), and the index is created on
+    /// that column.  Filters that apply the function to the column, like
+    /// `lower(name) = 'foo'`, are rewritten to use the hidden column and so the
+    /// index.  Rows written by `add`, `merge_insert` and `update` keep the
+    /// hidden column up to date, files registered with `add_files` must
+    /// include it.
+    ///
+    /// Defaults to None, the values are indexed as they are stored.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+}
@@ -18,13 +18,18 @@
 //! Vector indices are only supported on fixed-size-list (tensor) columns of floating point
 //! values
 use std::cmp::max;
+use std::collections::HashMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use lance::table::format::{Index, Manifest};
 
+use crate::error::{Error, Result};
 use crate::DistanceType;
 
+/// The schema metadata key used to store the distance types of vector indices
+pub(crate) const INDEX_DISTANCE_TYPES_KEY: &str = "lancedb:index_distance_types";
+
 pub struct VectorIndex {
     pub columns: Vec<String>,
     pub index_name: String,
@@ -72,21 +77,29 @@ pub struct VectorIndexStatistics {
 ///
 /// Note that training an IVF PQ index on a large dataset is a slow operation and
 /// currently is also a memory intensive operation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IvfPqIndexBuilder {
     pub(crate) distance_type: DistanceType,
     pub(crate) num_partitions: Option<u32>,
     pub(crate) num_sub_vectors: Option<u32>,
+    // Indices declared before this option existed do not store it
+    #[serde(default = "default_num_bits")]
+    pub(crate) num_bits: u32,
     pub(crate) sample_rate: u32,
     pub(crate) max_iterations: u32,
 }
 
+fn default_num_bits() -> u32 {
+    8
+}
+
 impl Default for IvfPqIndexBuilder {
     fn default() -> Self {
         Self {
             distance_type: DistanceType::L2,
             num_partitions: None,
             num_sub_vectors: None,
+            num_bits: default_num_bits(),
             sample_rate: 256,
             max_iterations: 50,
         }
@@ -139,6 +152,17 @@ impl IvfPqIndexBuilder {
         self
     }
 
+    /// The number of bits used to encode each sub-vector.
+    ///
+    /// Each sub-vector is replaced by the id of the closest of `2^num_bits` centroids.
+    /// Fewer bits make the index smaller but less accurate.
+    ///
+    /// The value must be between 1 and 8.  The default value is 8.
+    pub fn num_bits(mut self, num_bits: u32) -> Self {
+        self.num_bits = num_bits;
+        self
+    }
+
     /// The rate used to calculate the number of training vectors for kmeans.
     ///
     /// When an IVF PQ index is trained, we need to calculate partitions.  These are groups
@@ -174,11 +198,75 @@ impl IvfPqIndexBuilder {
     }
 }
 
+/// The distance type a vector index was built with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexDistanceType {
+    pub distance_type: DistanceType,
+    /// The field id of the column, so the distance type of an index on a
+    /// dropped column does not apply to a new column with the same name
+    pub field_id: i32,
+}
+
+/// The distance types of the vector indices of a table, by column name
+///
+/// Vector searches use the distance type of the index unless they bypass the
+/// index.  Indices created before the distance type was recorded are not
+/// listed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexDistanceTypes(pub HashMap<String, IndexDistanceType>);
+
+impl IndexDistanceTypes {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(INDEX_DISTANCE_TYPES_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the index distance types: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the index distance types: {}", e),
+        })?;
+        metadata.insert(INDEX_DISTANCE_TYPES_KEY.to_string(), value);
+        Ok(())
+    }
+
+    /// The distance type of the index on a column, if it is recorded
+    pub(crate) fn get(&self, column: &str, field_id: i32) -> Option<DistanceType> {
+        self.0
+            .get(column)
+            .filter(|index| index.field_id == field_id)
+            .map(|index| index.distance_type)
+    }
+}
+
 pub(crate) fn suggested_num_partitions(rows: usize) -> u32 {
     let num_partitions = (rows as f64).sqrt() as u32;
     max(1, num_partitions)
 }
 
+/// An estimate of the memory needed to train an IVF_PQ index
+///
+/// Training loads a sample of the vectors: `sample_rate` vectors per IVF
+/// partition and per PQ centroid, at most every vector.  The sample is
+/// converted to 32 bit floats and copied while the centroids are computed.
+pub(crate) fn ivf_pq_training_bytes(
+    num_rows: usize,
+    dim: usize,
+    num_partitions: u32,
+    num_bits: u32,
+    sample_rate: u32,
+) -> usize {
+    let num_centroids = (num_partitions as usize).max(1 << num_bits);
+    let sample = num_rows.min(num_centroids.saturating_mul(sample_rate as usize));
+    sample.saturating_mul(dim).saturating_mul(4 * 2)
+}
+
 pub(crate) fn suggested_num_sub_vectors(dim: u32) -> u32 {
     if dim % 16 == 0 {
         // Should be more aggressive than this default.
@@ -1 +1,27 @@
+pub mod disk_cache;
+pub mod encryption;
+pub mod metrics;
 pub mod object_store;
+pub mod read_write;
+pub(crate) mod shallow_clone;
+pub mod throttle;
+pub mod tiering;
+
+use std::ops::Range;
+
+use ::object_store::GetRange;
+
+/// The bytes of an object of `size` bytes that a get request asks for
+///
+/// This follows the rules of the object stores: a bounded range may end after
+/// the end of the object and is then cut short.  Returns None if the range is
+/// empty or starts after the end of the object.
+pub(crate) fn resolve_get_range(range: Option<&GetRange>, size: usize) -> Option<Range<usize>> {
+    let range = match range {
+        None => return Some(0..size),
+        Some(GetRange::Bounded(range)) => range.start..range.end.min(size),
+        Some(GetRange::Offset(offset)) => *offset..size,
+        Some(GetRange::Suffix(suffix)) => size.saturating_sub(*suffix)..size,
+    };
+    (range.start < range.end).then_some(range)
+}
@@ -0,0 +1,593 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A read-through cache of table files on the local disk
+//!
+//! [`crate::connection::ConnectBuilder::disk_cache`] keeps the recently read
+//! parts of the data files, deletion files and index files of the tables in
+//! a local directory, so repeated queries of the same partitions do not go
+//! to S3 or GCS.  These files are never modified after they are written, so
+//! the cache never has to be invalidated.  Manifests and other files that
+//! can change are always read from the store.
+//!
+//! Files are cached in blocks of 1MiB.  A read fetches the blocks it is
+//! missing with one request per run of consecutive blocks and the least
+//! recently used blocks are removed once the cache is over its capacity.
+//! The cache survives restarts: the blocks already in the directory are
+//! reused by the next connection with the same directory.
+//!
+//! Errors of the local disk are not returned to the caller, the read falls
+//! back to the store.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Formatter;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use lance::io::WrappingObjectStore;
+use object_store::{
+    path::Path, GetOptions, GetResult, GetResultPayload, ListResult, MultipartId, ObjectMeta,
+    ObjectStore, PutOptions, PutResult, Result,
+};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWrite;
+
+use crate::error::Error;
+use crate::runtime;
+
+use super::resolve_get_range;
+
+const STORE: &str = "DiskCachedObjectStore";
+const BLOCK_SIZE: usize = 1024 * 1024;
+const TMP_MARKER: &str = ".tmp-";
+// The sizes of the files are kept in memory, forget them past this many files
+const MAX_TRACKED_OBJECTS: usize = 100_000;
+
+/// The settings of the disk cache of a connection
+///
+/// See [`crate::connection::ConnectBuilder::disk_cache`]
+#[derive(Debug, Clone)]
+pub struct DiskCacheConfig {
+    /// The directory of the cache, created if it does not exist
+    pub path: PathBuf,
+    /// The maximum number of bytes kept in the directory
+    pub capacity_bytes: u64,
+}
+
+impl DiskCacheConfig {
+    pub fn new(path: impl Into<PathBuf>, capacity_bytes: u64) -> Self {
+        Self {
+            path: path.into(),
+            capacity_bytes,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    last_used: u64,
+}
+
+/// Which blocks are on disk, and in which order they were used
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, Entry>,
+    // The keys by the time of their last use
+    lru: BTreeMap<u64, String>,
+    total_bytes: u64,
+    clock: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, key: &str) -> bool {
+        self.clock += 1;
+        let clock = self.clock;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.lru.remove(&entry.last_used);
+                entry.last_used = clock;
+                self.lru.insert(clock, key.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        self.clock += 1;
+        self.total_bytes += size;
+        self.lru.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                size,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.last_used);
+            self.total_bytes -= entry.size;
+        }
+    }
+
+    /// Remove the least recently used blocks until the cache fits
+    fn evict(&mut self, capacity: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_bytes > capacity {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.total_bytes -= entry.size;
+            }
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+/// The blocks on disk, shared by all the stores of a connection
+#[derive(Debug)]
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+    capacity: u64,
+    state: Mutex<CacheState>,
+    objects: Mutex<HashMap<String, ObjectMeta>>,
+}
+
+impl DiskCache {
+    /// Open the cache, reusing the blocks already in the directory
+    pub fn open(config: &DiskCacheConfig) -> crate::Result<Self> {
+        if config.capacity_bytes == 0 {
+            return Err(Error::InvalidInput {
+                message: "the capacity of the disk cache must be positive".to_string(),
+            });
+        }
+        std::fs::create_dir_all(&config.path).map_err(|e| Error::Runtime {
+            message: format!(
+                "failed to create the disk cache at {}: {}",
+                config.path.display(),
+                e
+            ),
+        })?;
+        let mut state = CacheState::default();
+        let entries = std::fs::read_dir(&config.path).map_err(|e| Error::Runtime {
+            message: format!(
+                "failed to read the disk cache at {}: {}",
+                config.path.display(),
+                e
+            ),
+        })?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.contains(TMP_MARKER) {
+                // Left over by a write that did not finish
+                let _ = std::fs::remove_file(entry.path());
+            } else if let Ok(metadata) = entry.metadata() {
+                if metadata.is_file() {
+                    state.insert(name, metadata.len());
+                }
+            }
+        }
+        let cache = Self {
+            dir: config.path.clone(),
+            capacity: config.capacity_bytes,
+            state: Mutex::new(state),
+            objects: Mutex::new(HashMap::new()),
+        };
+        // The capacity may be smaller than the last time
+        let evicted = cache.state.lock().unwrap().evict(cache.capacity);
+        for key in evicted {
+            let _ = std::fs::remove_file(cache.dir.join(key));
+        }
+        Ok(cache)
+    }
+
+    fn key(store: &str, location: &Path, block: usize) -> String {
+        let digest = Sha256::digest(format!("{}\n{}\n{}", store, location, block));
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn object(&self, store: &str, location: &Path) -> Option<ObjectMeta> {
+        let objects = self.objects.lock().unwrap();
+        objects.get(&format!("{}\n{}", store, location)).cloned()
+    }
+
+    fn set_object(&self, store: &str, meta: ObjectMeta) {
+        let mut objects = self.objects.lock().unwrap();
+        if objects.len() >= MAX_TRACKED_OBJECTS {
+            objects.clear();
+        }
+        objects.insert(format!("{}\n{}", store, meta.location), meta);
+    }
+
+    /// The block, if it is on disk with the expected length
+    async fn read_block(&self, key: &str, len: usize) -> Option<Bytes> {
+        if !self.state.lock().unwrap().touch(key) {
+            return None;
+        }
+        match runtime::fs::read(self.dir.join(key)).await {
+            Ok(bytes) if bytes.len() == len => Some(Bytes::from(bytes)),
+            _ => {
+                // Removed or truncated by someone else
+                self.state.lock().unwrap().remove(key);
+                None
+            }
+        }
+    }
+
+    async fn write_block(&self, key: String, bytes: Bytes) {
+        if bytes.len() as u64 > self.capacity {
+            return;
+        }
+        let tmp = self.dir.join(format!(
+            "{}{}{:016x}",
+            key,
+            TMP_MARKER,
+            rand::random::<u64>()
+        ));
+        if runtime::fs::write(&tmp, &bytes).await.is_err()
+            || runtime::fs::rename(&tmp, self.dir.join(&key))
+                .await
+                .is_err()
+        {
+            let _ = runtime::fs::remove_file(&tmp).await;
+            return;
+        }
+        let evicted = {
+            let mut state = self.state.lock().unwrap();
+            state.insert(key, bytes.len() as u64);
+            state.evict(self.capacity)
+        };
+        for key in evicted {
+            let _ = runtime::fs::remove_file(self.dir.join(key)).await;
+        }
+    }
+
+    #[cfg(test)]
+    fn total_bytes(&self) -> u64 {
+        self.state.lock().unwrap().total_bytes
+    }
+}
+
+/// Whether the file is never modified once written
+///
+/// These are the files in the `data`, `_indices` and `_deletions`
+/// directories of a table.
+fn is_immutable(location: &Path) -> bool {
+    let parts = location
+        .parts()
+        .map(|part| part.as_ref().to_string())
+        .collect::<Vec<_>>();
+    parts.windows(2).any(|pair| {
+        pair[0].ends_with(".lance")
+            && matches!(pair[1].as_str(), "data" | "_indices" | "_deletions")
+    })
+}
+
+/// An object store that serves the reads of immutable files from a [`DiskCache`]
+#[derive(Debug)]
+struct DiskCachedObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    // Tells apart the files of different buckets
+    store: String,
+    cache: Arc<DiskCache>,
+}
+
+impl std::fmt::Display for DiskCachedObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DiskCachedObjectStore({})", self.inner)
+    }
+}
+
+impl DiskCachedObjectStore {
+    async fn meta(&self, location: &Path) -> Result<ObjectMeta> {
+        if let Some(meta) = self.cache.object(&self.store, location) {
+            return Ok(meta);
+        }
+        let meta = self.inner.head(location).await?;
+        self.cache.set_object(&self.store, meta.clone());
+        Ok(meta)
+    }
+
+    async fn read_cached(
+        &self,
+        location: &Path,
+        range: Range<usize>,
+        size: usize,
+    ) -> Result<Bytes> {
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+        let block_range = |block: usize| block * BLOCK_SIZE..((block + 1) * BLOCK_SIZE).min(size);
+        let first = range.start / BLOCK_SIZE;
+        let last = (range.end - 1) / BLOCK_SIZE;
+
+        let mut blocks = Vec::with_capacity(last - first + 1);
+        for block in first..=last {
+            let key = DiskCache::key(&self.store, location, block);
+            blocks.push(self.cache.read_block(&key, block_range(block).len()).await);
+        }
+
+        // Fetch each run of missing blocks with one request
+        let mut block = first;
+        while block <= last {
+            if blocks[block - first].is_some() {
+                block += 1;
+                continue;
+            }
+            let mut end = block;
+            while end < last && blocks[end + 1 - first].is_none() {
+                end += 1;
+            }
+            let fetched = self
+                .inner
+                .get_range(location, block * BLOCK_SIZE..block_range(end).end)
+                .await?;
+            for missing in block..=end {
+                let offset = (missing - block) * BLOCK_SIZE;
+                let bytes = fetched.slice(offset..offset + block_range(missing).len());
+                let key = DiskCache::key(&self.store, location, missing);
+                self.cache.write_block(key, bytes.clone()).await;
+                blocks[missing - first] = Some(bytes);
+            }
+            block = end + 1;
+        }
+
+        let mut data = BytesMut::with_capacity(range.len());
+        for bytes in blocks.into_iter().flatten() {
+            data.extend_from_slice(&bytes);
+        }
+        let offset = range.start - first * BLOCK_SIZE;
+        Ok(data.freeze().slice(offset..offset + range.len()))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for DiskCachedObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        options: PutOptions,
+    ) -> Result<PutResult> {
+        self.inner.put_opts(location, bytes, options).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.inner.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        let conditional = options.if_match.is_some()
+            || options.if_none_match.is_some()
+            || options.if_modified_since.is_some()
+            || options.if_unmodified_since.is_some()
+            || options.version.is_some()
+            || options.head;
+        if !is_immutable(location) || conditional {
+            return self.inner.get_opts(location, options).await;
+        }
+        let meta = self.meta(location).await?;
+        let Some(range) = resolve_get_range(options.range.as_ref(), meta.size) else {
+            return Err(object_store::Error::Generic {
+                store: STORE,
+                source: format!(
+                    "the range {:?} is out of bounds for '{}' of size {}",
+                    options.range, location, meta.size
+                )
+                .into(),
+            });
+        };
+        let bytes = self.read_cached(location, range.clone(), meta.size).await?;
+        Ok(GetResult {
+            payload: GetResultPayload::Stream(futures::stream::once(async { Ok(bytes) }).boxed()),
+            meta,
+            range,
+        })
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        if !is_immutable(location) {
+            return self.inner.get_range(location, range).await;
+        }
+        let meta = self.meta(location).await?;
+        if range.start > range.end || range.end > meta.size {
+            return self.inner.get_range(location, range).await;
+        }
+        self.read_cached(location, range, meta.size).await
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        if is_immutable(location) {
+            self.meta(location).await
+        } else {
+            self.inner.head(location).await
+        }
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        // The blocks are left to be evicted, the file name is never reused
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+/// Caches the immutable files of all the tables of a connection on disk
+#[derive(Debug)]
+pub(crate) struct DiskCacheWrapper {
+    cache: Arc<DiskCache>,
+    inner: Option<Arc<dyn WrappingObjectStore>>,
+}
+
+impl DiskCacheWrapper {
+    pub fn open(config: &DiskCacheConfig) -> crate::Result<Self> {
+        Ok(Self {
+            cache: Arc::new(DiskCache::open(config)?),
+            inner: None,
+        })
+    }
+
+    /// Apply another wrapper beneath the cache
+    pub fn around(mut self, inner: Option<Arc<dyn WrappingObjectStore>>) -> Self {
+        self.inner = inner;
+        self
+    }
+}
+
+impl WrappingObjectStore for DiskCacheWrapper {
+    fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+        let store = original.to_string();
+        let inner = match &self.inner {
+            Some(wrapper) => wrapper.wrap(original),
+            None => original,
+        };
+        Arc::new(DiskCachedObjectStore {
+            inner,
+            store,
+            cache: self.cache.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn data(len: usize) -> Bytes {
+        (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>().into()
+    }
+
+    #[test]
+    fn test_is_immutable() {
+        assert!(is_immutable(&Path::from("db/t.lance/data/a.lance")));
+        assert!(is_immutable(&Path::from("t.lance/_indices/abc/index.idx")));
+        assert!(is_immutable(&Path::from("t.lance/_deletions/0-1-2.arrow")));
+        assert!(!is_immutable(&Path::from("t.lance/_versions/1.manifest")));
+        assert!(!is_immutable(&Path::from("data/t.lance/_latest.manifest")));
+    }
+
+    #[tokio::test]
+    async fn test_read_through() {
+        let tmp_dir = tempdir().unwrap();
+        let config = DiskCacheConfig::new(tmp_dir.path(), 64 * 1024 * 1024);
+        let original = Arc::new(InMemory::new());
+        let store = DiskCacheWrapper::open(&config)
+            .unwrap()
+            .wrap(original.clone());
+
+        let location = Path::from("t.lance/data/a.lance");
+        let expected = data(3 * BLOCK_SIZE + 100);
+        original.put(&location, expected.clone()).await.unwrap();
+
+        let range = BLOCK_SIZE - 10..2 * BLOCK_SIZE + 10;
+        assert_eq!(
+            store.get_range(&location, range.clone()).await.unwrap(),
+            expected.slice(range.clone())
+        );
+
+        // The cached blocks are served without the store
+        original.delete(&location).await.unwrap();
+        assert_eq!(
+            store.get_range(&location, range.clone()).await.unwrap(),
+            expected.slice(range)
+        );
+        assert!(store.get_range(&location, 0..10).await.is_err());
+
+        // Mutable files are never cached
+        let manifest = Path::from("t.lance/_versions/1.manifest");
+        original.put(&manifest, data(10)).await.unwrap();
+        store.get(&manifest).await.unwrap().bytes().await.unwrap();
+        original.delete(&manifest).await.unwrap();
+        assert!(store.get(&manifest).await.is_err());
+
+        // The blocks are reused by the next connection
+        let reopened = DiskCache::open(&config).unwrap();
+        assert_eq!(reopened.total_bytes(), 2 * BLOCK_SIZE as u64);
+    }
+
+    #[tokio::test]
+    async fn test_eviction() {
+        let tmp_dir = tempdir().unwrap();
+        let config = DiskCacheConfig::new(tmp_dir.path(), 2 * BLOCK_SIZE as u64);
+        let cache = Arc::new(DiskCache::open(&config).unwrap());
+        let original = Arc::new(InMemory::new());
+        let store = DiskCachedObjectStore {
+            inner: original.clone(),
+            store: original.to_string(),
+            cache: cache.clone(),
+        };
+
+        let location = Path::from("t.lance/_indices/abc/index.idx");
+        let expected = data(3 * BLOCK_SIZE);
+        original.put(&location, expected.clone()).await.unwrap();
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes, expected);
+
+        assert_eq!(cache.total_bytes(), 2 * BLOCK_SIZE as u64);
+        assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 2);
+
+        // The first block was the least recently used
+        original.delete(&location).await.unwrap();
+        assert!(store.get_range(&location, 0..10).await.is_err());
+        assert_eq!(
+            store
+                .get_range(&location, 2 * BLOCK_SIZE..2 * BLOCK_SIZE + 10)
+                .await
+                .unwrap(),
+            expected.slice(2 * BLOCK_SIZE..2 * BLOCK_SIZE + 10)
+        );
+
+        assert!(DiskCache::open(&DiskCacheConfig::new(tmp_dir.path(), 0)).is_err());
+    }
+}
@@ -0,0 +1,858 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encryption at rest with AES-256-GCM
+//!
+//! Every object written to a table is split into chunks of 64KiB and each
+//! chunk is encrypted with its own random nonce.  The path of the object
+//! (from the directory of the table on) and the index of the chunk are
+//! authenticated with every chunk, and the last chunk also authenticates the
+//! number of chunks.  Chunks can not be reordered or moved to another object,
+//! and an object can not be truncated or extended, without the read failing.
+//! An empty object is written as one empty chunk, so an object truncated to
+//! nothing is rejected as well.  Because the chunks have a fixed size a range
+//! read only fetches and decrypts the chunks that overlap the range (plus a
+//! `head` request to learn the size of the object).
+//!
+//! Since the path is authenticated, copying or renaming an object decrypts it
+//! and encrypts it again for its new path (and with the key of the table it
+//! is copied to).  The whole object is held in memory while it is copied.
+//!
+//! The key of each table is requested from a [`KeyProvider`] the first time
+//! the table is read or written and kept in memory afterwards.  Configure
+//! encryption with [`crate::connection::ConnectBuilder::encryption`].
+//!
+//! Only the contents of objects are encrypted.  The names of the tables and
+//! of the files within them (and the size of the files) are visible to anyone
+//! with access to the storage.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use lance::io::WrappingObjectStore;
+use object_store::{
+    path::Path, Error, GetOptions, GetRange, GetResult, GetResultPayload, ListResult, MultipartId,
+    ObjectMeta, ObjectStore, PutMode, PutOptions, PutResult, Result,
+};
+use tokio::io::AsyncWrite;
+
+use super::resolve_get_range;
+
+const STORE: &str = "EncryptingObjectStore";
+
+/// The size of the plaintext of each chunk, except the last
+const CHUNK_SIZE: usize = 64 * 1024;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+const ENCRYPTED_CHUNK_SIZE: usize = CHUNK_SIZE + NONCE_SIZE + TAG_SIZE;
+
+/// Provides the encryption key of each table
+///
+/// Return the same key for every table to use one key for the database, or
+/// look the key up in a key management service to use a key per table.  The
+/// key of a table must never change, objects written with another key can
+/// not be read.
+#[async_trait]
+pub trait KeyProvider: Send + Sync + Debug {
+    /// The 256 bit key of the table with the given name
+    async fn key(&self, table: &str) -> crate::Result<[u8; 32]>;
+}
+
+/// A [`KeyProvider`] with one key for every table
+#[derive(Clone)]
+pub struct StaticKeyProvider {
+    key: [u8; 32],
+}
+
+impl StaticKeyProvider {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key }
+    }
+}
+
+impl Debug for StaticKeyProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticKeyProvider").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl KeyProvider for StaticKeyProvider {
+    async fn key(&self, _table: &str) -> crate::Result<[u8; 32]> {
+        Ok(self.key)
+    }
+}
+
+fn generic_error(message: impl Into<String>) -> Error {
+    Error::Generic {
+        store: STORE,
+        source: message.into().into(),
+    }
+}
+
+/// The size of the plaintext of an encrypted object of the given size
+fn plaintext_size(size: usize) -> usize {
+    let full_chunks = size / ENCRYPTED_CHUNK_SIZE;
+    let rest = size % ENCRYPTED_CHUNK_SIZE;
+    full_chunks * CHUNK_SIZE + rest.saturating_sub(NONCE_SIZE + TAG_SIZE)
+}
+
+/// The number of chunks of an encrypted object of the given size
+fn chunk_count(size: usize) -> usize {
+    size.div_ceil(ENCRYPTED_CHUNK_SIZE)
+}
+
+/// The data authenticated with a chunk
+///
+/// `total` is the number of chunks of the object if this is the last chunk.
+/// A multipart upload only knows the number of chunks once it is complete, so
+/// the other chunks only authenticate that they are not the last one.
+fn associated_data(object: &str, index: u64, total: Option<u64>) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(24 + object.len());
+    aad.extend_from_slice(&(object.len() as u64).to_le_bytes());
+    aad.extend_from_slice(object.as_bytes());
+    aad.extend_from_slice(&index.to_le_bytes());
+    aad.extend_from_slice(&total.unwrap_or(0).to_le_bytes());
+    aad
+}
+
+fn encrypt_chunk(
+    cipher: &Aes256Gcm,
+    object: &str,
+    index: u64,
+    total: Option<u64>,
+    plaintext: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let aad = associated_data(object, index, total);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| generic_error("failed to encrypt a chunk"))?;
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(())
+}
+
+fn decrypt_chunk(
+    cipher: &Aes256Gcm,
+    object: &str,
+    index: u64,
+    total: Option<u64>,
+    chunk: &[u8],
+) -> Result<Vec<u8>> {
+    if chunk.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(generic_error("the encrypted object is truncated"));
+    }
+    let (nonce, ciphertext) = chunk.split_at(NONCE_SIZE);
+    let aad = associated_data(object, index, total);
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| {
+            generic_error(
+                "failed to decrypt the object, the key is wrong or the object was modified",
+            )
+        })
+}
+
+/// Encrypt a whole object
+///
+/// An empty object is encrypted as one empty chunk.
+fn encrypt(cipher: &Aes256Gcm, object: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let num_chunks = plaintext.len().div_ceil(CHUNK_SIZE).max(1);
+    let mut out = Vec::with_capacity(plaintext.len() + num_chunks * (NONCE_SIZE + TAG_SIZE));
+    let chunks = plaintext.chunks(CHUNK_SIZE).chain(
+        // chunks() yields nothing for an empty slice
+        plaintext.is_empty().then_some(plaintext),
+    );
+    for (index, chunk) in chunks.enumerate() {
+        let total = (index + 1 == num_chunks).then_some(num_chunks as u64);
+        encrypt_chunk(cipher, object, index as u64, total, chunk, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Decrypt consecutive chunks, starting with the chunk `first`
+///
+/// `total` is the number of chunks of the whole object.
+fn decrypt(
+    cipher: &Aes256Gcm,
+    object: &str,
+    first: usize,
+    ciphertext: &[u8],
+    total: usize,
+) -> Result<Vec<u8>> {
+    if total == 0 {
+        return Err(generic_error("the encrypted object is empty"));
+    }
+    let num_chunks = ciphertext.len().div_ceil(ENCRYPTED_CHUNK_SIZE);
+    let mut out = Vec::with_capacity(num_chunks * CHUNK_SIZE);
+    for (i, chunk) in ciphertext.chunks(ENCRYPTED_CHUNK_SIZE).enumerate() {
+        let index = first + i;
+        let last = (index + 1 == total).then_some(total as u64);
+        out.extend(decrypt_chunk(cipher, object, index as u64, last, chunk)?);
+    }
+    Ok(out)
+}
+
+/// The name of the table an object belongs to and the path of the object
+/// from the directory of the table on
+///
+/// The directory of the table is the last directory of the path with the
+/// `.lance` extension.  The database directory and the data files also have
+/// the extension.  The path is authenticated with the object, starting at the
+/// table so that the database can still be moved.
+fn table_object(location: &Path) -> Option<(String, String)> {
+    let parts = location
+        .parts()
+        .map(|part| part.as_ref().to_string())
+        .collect::<Vec<_>>();
+    let (position, table) = parts[..parts.len().saturating_sub(1)]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, part)| part.strip_suffix(".lance").map(|table| (i, table)))
+        .last()?;
+    Some((table.to_string(), parts[position..].join("/")))
+}
+
+/// Encrypts the chunks written through a multipart upload
+struct EncryptingWriter {
+    inner: Box<dyn AsyncWrite + Unpin + Send>,
+    cipher: Aes256Gcm,
+    /// The path of the object, see [`table_object`]
+    object: String,
+    /// The plaintext that has not been encrypted yet
+    ///
+    /// A full chunk is kept until more data arrives since it is only known
+    /// to be the last chunk when the writer is shut down.
+    plaintext: Vec<u8>,
+    /// The ciphertext that has not been written to `inner` yet
+    ciphertext: Vec<u8>,
+    written: usize,
+    next_chunk: u64,
+    finished: bool,
+}
+
+impl EncryptingWriter {
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.ciphertext.len() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.ciphertext[self.written..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => self.written += n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        self.ciphertext.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn encrypt_chunk(&mut self, len: usize, last: bool) -> io::Result<()> {
+        let chunk = self.plaintext.drain(..len).collect::<Vec<_>>();
+        encrypt_chunk(
+            &self.cipher,
+            &self.object,
+            self.next_chunk,
+            last.then_some(self.next_chunk + 1),
+            &chunk,
+            &mut self.ciphertext,
+        )
+        .map_err(io::Error::other)?;
+        self.next_chunk += 1;
+        Ok(())
+    }
+}
+
+impl AsyncWrite for EncryptingWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.as_mut().get_mut();
+        if let Poll::Ready(Err(e)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(e));
+        }
+        if !this.ciphertext.is_empty() {
+            return Poll::Pending;
+        }
+        this.plaintext.extend_from_slice(buf);
+        while this.plaintext.len() > CHUNK_SIZE {
+            this.encrypt_chunk(CHUNK_SIZE, false)?;
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.as_mut().get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.as_mut().get_mut();
+        if !this.finished {
+            // An empty object still gets its (empty) last chunk
+            if !this.plaintext.is_empty() || this.next_chunk == 0 {
+                this.encrypt_chunk(this.plaintext.len(), true)?;
+            }
+            this.finished = true;
+        }
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_shutdown(cx),
+            other => other,
+        }
+    }
+}
+
+/// An object store that encrypts the objects of the wrapped store
+struct EncryptingObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    provider: Arc<dyn KeyProvider>,
+    ciphers: Arc<Mutex<HashMap<String, Aes256Gcm>>>,
+}
+
+impl Debug for EncryptingObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptingObjectStore")
+            .field("inner", &self.inner)
+            .field("provider", &self.provider)
+            .finish()
+    }
+}
+
+impl std::fmt::Display for EncryptingObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EncryptingObjectStore({})", self.inner)
+    }
+}
+
+impl EncryptingObjectStore {
+    /// The cipher of the table of an object and the path that is
+    /// authenticated with the object
+    async fn cipher(&self, location: &Path) -> Result<(Aes256Gcm, String)> {
+        let (table, object) = table_object(location).ok_or_else(|| {
+            generic_error(format!(
+                "cannot encrypt '{}', it does not belong to a table",
+                location
+            ))
+        })?;
+        if let Some(cipher) = self.ciphers.lock().unwrap().get(&table) {
+            return Ok((cipher.clone(), object));
+        }
+        let key = self
+            .provider
+            .key(&table)
+            .await
+            .map_err(|e| Error::Generic {
+                store: STORE,
+                source: Box::new(e),
+            })?;
+        let cipher = Aes256Gcm::new(&key.into());
+        self.ciphers.lock().unwrap().insert(table, cipher.clone());
+        Ok((cipher, object))
+    }
+
+    /// Read and decrypt the bytes of an object in the given plaintext range
+    ///
+    /// `size` is the size of the encrypted object.  Only the chunks that
+    /// overlap the range are fetched.  Returns the plaintext and the metadata
+    /// of the encrypted object.
+    async fn read_range(
+        &self,
+        location: &Path,
+        mut options: GetOptions,
+        size: usize,
+        range: Range<usize>,
+    ) -> Result<(Bytes, ObjectMeta)> {
+        let (cipher, object) = self.cipher(location).await?;
+        let first = range.start / CHUNK_SIZE;
+        let last = (range.end - 1) / CHUNK_SIZE;
+        let encrypted = first * ENCRYPTED_CHUNK_SIZE..((last + 1) * ENCRYPTED_CHUNK_SIZE).min(size);
+        options.range = Some(GetRange::Bounded(encrypted));
+        let result = self.inner.get_opts(location, options).await?;
+        let meta = result.meta.clone();
+        if meta.size != size {
+            return Err(generic_error(format!(
+                "'{}' was modified while it was read",
+                location
+            )));
+        }
+        let ciphertext = result.bytes().await?;
+        let plaintext = Bytes::from(decrypt(
+            &cipher,
+            &object,
+            first,
+            &ciphertext,
+            chunk_count(size),
+        )?);
+        let offset = range.start - first * CHUNK_SIZE;
+        Ok((plaintext.slice(offset..offset + range.len()), meta))
+    }
+
+    /// Decrypt an object and encrypt it again for another path
+    async fn reencrypt(&self, from: &Path, to: &Path) -> Result<Bytes> {
+        let plaintext = self.get(from).await?.bytes().await?;
+        let (cipher, object) = self.cipher(to).await?;
+        Ok(encrypt(&cipher, &object, &plaintext)?.into())
+    }
+}
+
+#[async_trait]
+impl ObjectStore for EncryptingObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        options: PutOptions,
+    ) -> Result<PutResult> {
+        let (cipher, object) = self.cipher(location).await?;
+        let ciphertext = encrypt(&cipher, &object, &bytes)?;
+        self.inner
+            .put_opts(location, ciphertext.into(), options)
+            .await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        let (cipher, object) = self.cipher(location).await?;
+        let (id, inner) = self.inner.put_multipart(location).await?;
+        let writer = EncryptingWriter {
+            inner,
+            cipher,
+            object,
+            plaintext: Vec::with_capacity(CHUNK_SIZE),
+            ciphertext: Vec::new(),
+            written: 0,
+            next_chunk: 0,
+            finished: false,
+        };
+        Ok((id, Box::new(writer)))
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, mut options: GetOptions) -> Result<GetResult> {
+        let (plaintext, mut meta, range) = match options.range.take() {
+            None => {
+                let (cipher, object) = self.cipher(location).await?;
+                let result = self.inner.get_opts(location, options).await?;
+                let meta = result.meta.clone();
+                let ciphertext = result.bytes().await?;
+                let total = chunk_count(ciphertext.len());
+                let plaintext = Bytes::from(decrypt(&cipher, &object, 0, &ciphertext, total)?);
+                let range = 0..plaintext.len();
+                (plaintext, meta, range)
+            }
+            Some(range) => {
+                let size = self.inner.head(location).await?.size;
+                let plaintext_range = resolve_get_range(Some(&range), plaintext_size(size))
+                    .ok_or_else(|| {
+                        generic_error(format!(
+                            "the range {:?} is out of bounds for '{}' of size {}",
+                            range,
+                            location,
+                            plaintext_size(size)
+                        ))
+                    })?;
+                let (plaintext, meta) = self
+                    .read_range(location, options, size, plaintext_range.clone())
+                    .await?;
+                (plaintext, meta, plaintext_range)
+            }
+        };
+        meta.size = plaintext_size(meta.size);
+        Ok(GetResult {
+            payload: GetResultPayload::Stream(
+                futures::stream::once(async { Ok(plaintext) }).boxed(),
+            ),
+            meta,
+            range,
+        })
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        let size = self.inner.head(location).await?.size;
+        if range.start > range.end || range.end > plaintext_size(size) {
+            return Err(generic_error(format!(
+                "the range {:?} is out of bounds for '{}' of size {}",
+                range,
+                location,
+                plaintext_size(size)
+            )));
+        }
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+        let (plaintext, _) = self
+            .read_range(location, GetOptions::default(), size, range)
+            .await?;
+        Ok(plaintext)
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        let mut meta = self.inner.head(location).await?;
+        meta.size = plaintext_size(meta.size);
+        Ok(meta)
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+        self.inner
+            .list(prefix)
+            .map_ok(|mut meta| {
+                meta.size = plaintext_size(meta.size);
+                meta
+            })
+            .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        let mut result = self.inner.list_with_delimiter(prefix).await?;
+        for meta in &mut result.objects {
+            meta.size = plaintext_size(meta.size);
+        }
+        Ok(result)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        let ciphertext = self.reencrypt(from, to).await?;
+        self.inner.put(to, ciphertext).await?;
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.copy(from, to).await?;
+        self.inner.delete(from).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        let ciphertext = self.reencrypt(from, to).await?;
+        self.inner
+            .put_opts(to, ciphertext, PutMode::Create.into())
+            .await?;
+        Ok(())
+    }
+
+    async fn rename_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.copy_if_not_exists(from, to).await?;
+        self.inner.delete(from).await
+    }
+}
+
+/// Encrypts the objects of every table with AES-256-GCM
+///
+/// See the [module documentation](self) for the format.  Other wrappers of
+/// the connection (e.g. a cache) are applied beneath the encryption, so they
+/// only ever see encrypted objects.
+#[derive(Clone)]
+pub struct EncryptionWrapper {
+    provider: Arc<dyn KeyProvider>,
+    inner: Option<Arc<dyn WrappingObjectStore>>,
+    ciphers: Arc<Mutex<HashMap<String, Aes256Gcm>>>,
+}
+
+impl Debug for EncryptionWrapper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptionWrapper")
+            .field("provider", &self.provider)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl EncryptionWrapper {
+    pub fn new(provider: Arc<dyn KeyProvider>) -> Self {
+        Self {
+            provider,
+            inner: None,
+            ciphers: Arc::default(),
+        }
+    }
+
+    /// Apply another wrapper beneath the encryption
+    pub(crate) fn around(mut self, inner: Option<Arc<dyn WrappingObjectStore>>) -> Self {
+        self.inner = inner;
+        self
+    }
+}
+
+impl WrappingObjectStore for EncryptionWrapper {
+    fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+        let inner = match &self.inner {
+            Some(wrapper) => wrapper.wrap(original),
+            None => original,
+        };
+        Arc::new(EncryptingObjectStore {
+            inner,
+            provider: self.provider.clone(),
+            ciphers: self.ciphers.clone(),
+        })
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::TryStreamExt;
+    use object_store::memory::InMemory;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::connect;
+    use crate::query::{ExecutableQuery, QueryBase};
+
+    fn store() -> Arc<dyn ObjectStore> {
+        EncryptionWrapper::new(Arc::new(StaticKeyProvider::new([7; 32])))
+            .wrap(Arc::new(InMemory::new()))
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let store = store();
+        let plaintext = (0..3 * CHUNK_SIZE + 100)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let location = Path::from("db/t.lance/data/a.lance");
+        store
+            .put(&location, plaintext.clone().into())
+            .await
+            .unwrap();
+        assert_eq!(store.head(&location).await.unwrap().size, plaintext.len());
+        let all = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(all.as_ref(), plaintext.as_slice());
+        for range in [
+            0..10,
+            CHUNK_SIZE - 5..CHUNK_SIZE + 5,
+            3 * CHUNK_SIZE..3 * CHUNK_SIZE + 100,
+        ] {
+            let bytes = store.get_range(&location, range.clone()).await.unwrap();
+            assert_eq!(bytes.as_ref(), &plaintext[range]);
+        }
+        assert!(store
+            .get_range(&location, 0..plaintext.len() + 1)
+            .await
+            .is_err());
+        let suffix = store
+            .get_opts(
+                &location,
+                GetOptions {
+                    range: Some(GetRange::Suffix(CHUNK_SIZE + 10)),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(suffix.meta.size, plaintext.len());
+        assert_eq!(
+            suffix.bytes().await.unwrap().as_ref(),
+            &plaintext[plaintext.len() - CHUNK_SIZE - 10..]
+        );
+
+        // Empty objects have one (empty) chunk
+        let location = Path::from("db/t.lance/data/empty.lance");
+        store.put(&location, Bytes::new()).await.unwrap();
+        let all = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert!(all.is_empty());
+
+        // Multipart uploads produce the same format
+        let location = Path::from("db/t.lance/data/b.lance");
+        let (_, mut writer) = store.put_multipart(&location).await.unwrap();
+        for part in plaintext.chunks(1000) {
+            writer.write_all(part).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+        let all = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(all.as_ref(), plaintext.as_slice());
+
+        // Objects outside of a table can not be encrypted
+        assert!(store
+            .put(&Path::from("db/other"), Bytes::from_static(b"x"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tampering_is_detected() {
+        let memory = Arc::new(InMemory::new());
+        let wrapper = EncryptionWrapper::new(Arc::new(StaticKeyProvider::new([7; 32])));
+        let store = wrapper.wrap(memory.clone());
+        let location = Path::from("t.lance/x");
+        let plaintext = vec![1u8; 2 * CHUNK_SIZE];
+        store.put(&location, plaintext.into()).await.unwrap();
+
+        let ciphertext = memory.get(&location).await.unwrap().bytes().await.unwrap();
+        // Drop the last chunk
+        memory
+            .put(&location, ciphertext.slice(..ENCRYPTED_CHUNK_SIZE))
+            .await
+            .unwrap();
+        assert!(store.get(&location).await.is_err());
+
+        // Truncate everything
+        memory.put(&location, Bytes::new()).await.unwrap();
+        assert!(store.get(&location).await.is_err());
+
+        let other =
+            EncryptionWrapper::new(Arc::new(StaticKeyProvider::new([8; 32]))).wrap(memory.clone());
+        memory.put(&location, ciphertext.clone()).await.unwrap();
+        assert!(other.get(&location).await.is_err());
+        assert!(store.get(&location).await.is_ok());
+
+        // Swap an object, or a chunk, with another object of the table
+        let swapped = Path::from("t.lance/y");
+        memory.put(&swapped, ciphertext.clone()).await.unwrap();
+        assert!(store.get(&swapped).await.is_err());
+        store
+            .put(&swapped, vec![2u8; 2 * CHUNK_SIZE].into())
+            .await
+            .unwrap();
+        let mut mixed = memory
+            .get(&swapped)
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap()
+            .to_vec();
+        mixed[..ENCRYPTED_CHUNK_SIZE].copy_from_slice(&ciphertext[..ENCRYPTED_CHUNK_SIZE]);
+        memory.put(&swapped, mixed.into()).await.unwrap();
+        assert!(store.get(&swapped).await.is_err());
+        assert!(store.get_range(&swapped, 0..10).await.is_err());
+    }
+
+    #[derive(Debug)]
+    struct KeyPerTable;
+
+    #[async_trait]
+    impl KeyProvider for KeyPerTable {
+        async fn key(&self, table: &str) -> crate::Result<[u8; 32]> {
+            Ok([table.len() as u8; 32])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy_and_rename() {
+        let memory = Arc::new(InMemory::new());
+        let store = EncryptionWrapper::new(Arc::new(KeyPerTable)).wrap(memory.clone());
+        let plaintext = (0..CHUNK_SIZE + 100)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let location = Path::from("db/t.lance/data/a.lance");
+        store
+            .put(&location, plaintext.clone().into())
+            .await
+            .unwrap();
+
+        // Copied to another table, with another key
+        let copy = Path::from("db/copy.lance/data/a.lance");
+        store.copy(&location, &copy).await.unwrap();
+        let copied = store.get(&copy).await.unwrap().bytes().await.unwrap();
+        assert_eq!(copied.as_ref(), plaintext.as_slice());
+        assert!(store.copy_if_not_exists(&location, &copy).await.is_err());
+
+        let renamed = Path::from("db/t.lance/data/b.lance");
+        store.rename(&location, &renamed).await.unwrap();
+        assert!(store.head(&location).await.is_err());
+        let bytes = store.get(&renamed).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), plaintext.as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = dir.path().to_str().unwrap();
+        let db = connect(uri)
+            .encryption(Arc::new(StaticKeyProvider::new([7; 32])))
+            .execute()
+            .await
+            .unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("secret", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| format!("top secret {}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+        let table = db
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+        let batches = table
+            .query()
+            .only_if("id = 42")
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches[0]["secret"].len(), 1);
+
+        // Nothing readable is written to disk
+        for entry in walkdir::WalkDir::new(dir.path()) {
+            let entry = entry.unwrap();
+            if entry.file_type().is_file() {
+                let contents = std::fs::read(entry.path()).unwrap();
+                let text = String::from_utf8_lossy(&contents);
+                assert!(!text.contains("top secret"), "{:?}", entry.path());
+            }
+        }
+
+        // The table can not be read without the key
+        let plain = connect(uri).execute().await.unwrap();
+        assert!(plain.open_table("test").execute().await.is_err());
+    }
+}
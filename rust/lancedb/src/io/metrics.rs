@@ -0,0 +1,506 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics of the object store requests made by a table
+//!
+//! Every table handle wraps its object store with an [`IoMetricsWrapper`]
+//! that counts the requests and bytes read and written, measures the
+//! latency of each request and logs a warning for the requests slower than
+//! [`crate::connection::ConnectBuilder::slow_request_threshold`].  The
+//! totals are returned by [`crate::Table::io_stats`].
+//!
+//! The latency of a GET is the time until the response starts, the body is
+//! streamed afterwards.  The retries made inside the object store client
+//! are not visible to the wrapper and show up as latency.  A request for an
+//! object whose previous request failed is counted as a retry.
+
+use std::collections::HashSet;
+use std::fmt::Formatter;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use lance::io::{ObjectStoreParams, WrappingObjectStore};
+use object_store::{
+    path::Path, GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore,
+    PutOptions, PutResult, Result,
+};
+use tokio::io::AsyncWrite;
+
+/// The default of [`crate::connection::ConnectBuilder::slow_request_threshold`]
+pub const DEFAULT_SLOW_REQUEST_THRESHOLD: Duration = Duration::from_secs(2);
+
+/// The object store requests made through a table handle
+///
+/// See [`crate::Table::io_stats`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IoStats {
+    /// The number of GET requests, including range reads
+    pub get_requests: u64,
+    /// The number of bytes returned by GET requests
+    pub get_bytes: u64,
+    /// The number of PUT requests, a multipart upload counts as one
+    pub put_requests: u64,
+    /// The number of bytes written by PUT requests
+    pub put_bytes: u64,
+    /// The number of other requests: HEAD, LIST, DELETE, COPY and RENAME
+    pub other_requests: u64,
+    /// The number of requests that failed
+    pub failed_requests: u64,
+    /// The number of requests for an object whose previous request failed
+    pub retries: u64,
+    /// The number of requests slower than the slow request threshold
+    pub slow_requests: u64,
+    /// The sum of the latencies of all requests
+    pub total_latency: Duration,
+    /// The latency of the slowest request
+    pub max_latency: Duration,
+}
+
+impl IoStats {
+    /// The total number of requests
+    pub fn requests(&self) -> u64 {
+        self.get_requests + self.put_requests + self.other_requests
+    }
+
+    /// The mean latency of a request, None if no request was made
+    pub fn mean_latency(&self) -> Option<Duration> {
+        match self.requests() {
+            0 => None,
+            requests => Some(self.total_latency / requests as u32),
+        }
+    }
+}
+
+// Bounds the memory used to detect retries
+const MAX_TRACKED_FAILURES: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+enum RequestKind {
+    Get,
+    Put,
+    Other,
+}
+
+/// The counters shared by the stores of a table handle
+#[derive(Debug)]
+pub(crate) struct IoMetrics {
+    table: String,
+    get_requests: AtomicU64,
+    get_bytes: AtomicU64,
+    put_requests: AtomicU64,
+    put_bytes: AtomicU64,
+    other_requests: AtomicU64,
+    failed_requests: AtomicU64,
+    retries: AtomicU64,
+    slow_requests: AtomicU64,
+    total_latency_ns: AtomicU64,
+    max_latency_ns: AtomicU64,
+    slow_threshold_ns: AtomicU64,
+    // The objects whose last request failed
+    failed: Mutex<HashSet<Path>>,
+}
+
+impl IoMetrics {
+    pub fn new(table: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            get_requests: AtomicU64::new(0),
+            get_bytes: AtomicU64::new(0),
+            put_requests: AtomicU64::new(0),
+            put_bytes: AtomicU64::new(0),
+            other_requests: AtomicU64::new(0),
+            failed_requests: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            slow_requests: AtomicU64::new(0),
+            total_latency_ns: AtomicU64::new(0),
+            max_latency_ns: AtomicU64::new(0),
+            slow_threshold_ns: AtomicU64::new(DEFAULT_SLOW_REQUEST_THRESHOLD.as_nanos() as u64),
+            failed: Mutex::default(),
+        }
+    }
+
+    pub fn set_slow_threshold(&self, threshold: Duration) {
+        self.slow_threshold_ns
+            .store(threshold.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> IoStats {
+        IoStats {
+            get_requests: self.get_requests.load(Ordering::Relaxed),
+            get_bytes: self.get_bytes.load(Ordering::Relaxed),
+            put_requests: self.put_requests.load(Ordering::Relaxed),
+            put_bytes: self.put_bytes.load(Ordering::Relaxed),
+            other_requests: self.other_requests.load(Ordering::Relaxed),
+            failed_requests: self.failed_requests.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            slow_requests: self.slow_requests.load(Ordering::Relaxed),
+            total_latency: Duration::from_nanos(self.total_latency_ns.load(Ordering::Relaxed)),
+            max_latency: Duration::from_nanos(self.max_latency_ns.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn record<T>(
+        &self,
+        kind: RequestKind,
+        location: &Path,
+        start: Instant,
+        result: &Result<T>,
+        bytes: impl FnOnce(&T) -> u64,
+    ) {
+        let (requests, byte_counter) = match kind {
+            RequestKind::Get => (&self.get_requests, Some(&self.get_bytes)),
+            RequestKind::Put => (&self.put_requests, Some(&self.put_bytes)),
+            RequestKind::Other => (&self.other_requests, None),
+        };
+        requests.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut failed = self.failed.lock().unwrap();
+            if failed.remove(location) {
+                self.retries.fetch_add(1, Ordering::Relaxed);
+            }
+            // A missing object is an answer, not a failure worth retrying
+            let retryable =
+                matches!(result, Err(err) if !matches!(err, object_store::Error::NotFound { .. }));
+            if retryable && failed.len() < MAX_TRACKED_FAILURES {
+                failed.insert(location.clone());
+            }
+        }
+        match result {
+            Ok(value) => {
+                if let Some(counter) = byte_counter {
+                    counter.fetch_add(bytes(value), Ordering::Relaxed);
+                }
+            }
+            Err(_) => {
+                self.failed_requests.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let latency = start.elapsed();
+        let latency_ns = latency.as_nanos() as u64;
+        self.total_latency_ns
+            .fetch_add(latency_ns, Ordering::Relaxed);
+        self.max_latency_ns.fetch_max(latency_ns, Ordering::Relaxed);
+        if latency_ns > self.slow_threshold_ns.load(Ordering::Relaxed) {
+            self.slow_requests.fetch_add(1, Ordering::Relaxed);
+            log::warn!(
+                "slow {:?} request for {} of table '{}' took {:?}",
+                kind,
+                location,
+                self.table,
+                latency
+            );
+        }
+    }
+}
+
+/// An object store that records its requests in [`IoMetrics`]
+#[derive(Debug)]
+struct MetricsObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    metrics: Arc<IoMetrics>,
+}
+
+impl std::fmt::Display for MetricsObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MetricsObjectStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MetricsObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        options: PutOptions,
+    ) -> Result<PutResult> {
+        let start = Instant::now();
+        let len = bytes.len() as u64;
+        let result = self.inner.put_opts(location, bytes, options).await;
+        self.metrics
+            .record(RequestKind::Put, location, start, &result, |_| len);
+        result
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        let start = Instant::now();
+        let result = self.inner.put_multipart(location).await;
+        // The bytes are counted as they are written
+        self.metrics
+            .record(RequestKind::Put, location, start, &result, |_| 0);
+        let (id, writer) = result?;
+        let writer = CountingWriter {
+            inner: writer,
+            metrics: self.metrics.clone(),
+        };
+        Ok((id, Box::new(writer)))
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        let start = Instant::now();
+        let result = self.inner.get_opts(location, options).await;
+        self.metrics
+            .record(RequestKind::Get, location, start, &result, |result| {
+                result.range.len() as u64
+            });
+        result
+    }
+
+    // Forwarded explicitly so that the inner store can specialize range reads
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        let start = Instant::now();
+        let result = self.inner.get_range(location, range).await;
+        self.metrics
+            .record(RequestKind::Get, location, start, &result, |bytes| {
+                bytes.len() as u64
+            });
+        result
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+        let start = Instant::now();
+        let result = self.inner.get_ranges(location, ranges).await;
+        self.metrics
+            .record(RequestKind::Get, location, start, &result, |ranges| {
+                ranges.iter().map(|bytes| bytes.len() as u64).sum()
+            });
+        result
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        let start = Instant::now();
+        let result = self.inner.head(location).await;
+        self.metrics
+            .record(RequestKind::Other, location, start, &result, |_| 0);
+        result
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        let start = Instant::now();
+        let result = self.inner.delete(location).await;
+        self.metrics
+            .record(RequestKind::Other, location, start, &result, |_| 0);
+        result
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+        // The listing is lazy, only the request is counted
+        self.metrics.other_requests.fetch_add(1, Ordering::Relaxed);
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        let start = Instant::now();
+        let result = self.inner.list_with_delimiter(prefix).await;
+        let location = prefix.cloned().unwrap_or_default();
+        self.metrics
+            .record(RequestKind::Other, &location, start, &result, |_| 0);
+        result
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        let start = Instant::now();
+        let result = self.inner.copy(from, to).await;
+        self.metrics
+            .record(RequestKind::Other, to, start, &result, |_| 0);
+        result
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let start = Instant::now();
+        let result = self.inner.rename(from, to).await;
+        self.metrics
+            .record(RequestKind::Other, to, start, &result, |_| 0);
+        result
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        let start = Instant::now();
+        let result = self.inner.copy_if_not_exists(from, to).await;
+        self.metrics
+            .record(RequestKind::Other, to, start, &result, |_| 0);
+        result
+    }
+}
+
+/// Counts the bytes written to a multipart upload
+struct CountingWriter {
+    inner: Box<dyn AsyncWrite + Unpin + Send>,
+    metrics: Arc<IoMetrics>,
+}
+
+impl AsyncWrite for CountingWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = &poll {
+            self.metrics
+                .put_bytes
+                .fetch_add(*written as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Records the requests of a table in its [`IoMetrics`]
+///
+/// The metrics are taken above any other wrapper, so they describe the
+/// requests made by the table, not those made by a cache or a mirror.
+#[derive(Debug, Clone)]
+pub(crate) struct IoMetricsWrapper {
+    metrics: Arc<IoMetrics>,
+    inner: Option<Arc<dyn WrappingObjectStore>>,
+}
+
+impl IoMetricsWrapper {
+    pub fn new(metrics: Arc<IoMetrics>) -> Self {
+        Self {
+            metrics,
+            inner: None,
+        }
+    }
+
+    /// Apply another wrapper beneath the metrics
+    pub fn around(mut self, inner: Option<Arc<dyn WrappingObjectStore>>) -> Self {
+        self.inner = inner;
+        self
+    }
+
+    pub fn metrics(&self) -> &Arc<IoMetrics> {
+        &self.metrics
+    }
+
+    /// The wrapper beneath the metrics, e.g. the wrapper of the connection
+    pub fn inner(&self) -> Option<Arc<dyn WrappingObjectStore>> {
+        self.inner.clone()
+    }
+
+    /// Set the metrics as the wrapper of the params of an operation
+    ///
+    /// A wrapper already set in the params is kept beneath the metrics.  As
+    /// with [`crate::utils::PatchStoreParam`], it can not be combined with
+    /// the wrapper of the connection.
+    pub fn patch(&self, params: Option<ObjectStoreParams>) -> crate::Result<ObjectStoreParams> {
+        let mut params = params.unwrap_or_default();
+        let wrapper = match (params.object_store_wrapper.take(), &self.inner) {
+            (Some(_), Some(_)) => {
+                return Err(crate::Error::Other {
+                    message: "can not patch param because object store is already set".into(),
+                    source: None,
+                })
+            }
+            (Some(wrapper), None) => self.clone().around(Some(wrapper)),
+            (None, _) => self.clone(),
+        };
+        params.object_store_wrapper = Some(Arc::new(wrapper));
+        Ok(params)
+    }
+}
+
+impl WrappingObjectStore for IoMetricsWrapper {
+    fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+        let inner = match &self.inner {
+            Some(wrapper) => wrapper.wrap(original),
+            None => original,
+        };
+        Arc::new(MetricsObjectStore {
+            inner,
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_io_metrics() {
+        let metrics = Arc::new(IoMetrics::new("test"));
+        let store = IoMetricsWrapper::new(metrics.clone()).wrap(Arc::new(InMemory::new()));
+        let location = Path::from("t.lance/data/a.lance");
+
+        store.put(&location, vec![1u8; 100].into()).await.unwrap();
+        store.get_range(&location, 10..30).await.unwrap();
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.len(), 100);
+        store.head(&location).await.unwrap();
+
+        let (_, mut writer) = store
+            .put_multipart(&Path::from("t.lance/data/b.lance"))
+            .await
+            .unwrap();
+        writer.write_all(&[2u8; 50]).await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        // A missing object is a failed request, but not retried
+        assert!(store.head(&Path::from("t.lance/missing")).await.is_err());
+        // A failed request, then a retry of it
+        let create = PutOptions {
+            mode: object_store::PutMode::Create,
+            ..Default::default()
+        };
+        assert!(store
+            .put_opts(&location, vec![3u8; 10].into(), create)
+            .await
+            .is_err());
+        store.put(&location, vec![3u8; 10].into()).await.unwrap();
+
+        let stats = metrics.stats();
+        assert_eq!(stats.get_requests, 2);
+        assert_eq!(stats.get_bytes, 120);
+        assert_eq!(stats.put_requests, 4);
+        assert_eq!(stats.put_bytes, 160);
+        assert_eq!(stats.other_requests, 2);
+        assert_eq!(stats.failed_requests, 2);
+        assert_eq!(stats.retries, 1);
+        assert_eq!(stats.slow_requests, 0);
+        assert_eq!(stats.requests(), 8);
+        assert!(stats.max_latency <= stats.total_latency);
+        assert!(stats.mean_latency().is_some());
+
+        metrics.set_slow_threshold(Duration::ZERO);
+        store.head(&location).await.unwrap();
+        assert_eq!(metrics.stats().slow_requests, 1);
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Separate object store wrappers for the read and write paths
+
+use std::{fmt::Formatter, ops::Range, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use lance::io::WrappingObjectStore;
+use object_store::{
+    path::Path, GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore,
+    PutOptions, PutResult, Result,
+};
+use tokio::io::AsyncWrite;
+
+/// An object store that routes reads and writes to different stores
+///
+/// Both stores are wrappers around the same underlying store.  Reads (get,
+/// head, list) go to `reads`, everything else goes to `writes`.
+#[derive(Debug)]
+struct ReadWriteObjectStore {
+    reads: Arc<dyn ObjectStore>,
+    writes: Arc<dyn ObjectStore>,
+}
+
+impl std::fmt::Display for ReadWriteObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "ReadWriteObjectStore")?;
+        writeln!(f, "reads:")?;
+        self.reads.fmt(f)?;
+        writeln!(f, "writes:")?;
+        self.writes.fmt(f)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ObjectStore for ReadWriteObjectStore {
+    async fn put(&self, location: &Path, bytes: Bytes) -> Result<PutResult> {
+        self.writes.put(location, bytes).await
+    }
+
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        options: PutOptions,
+    ) -> Result<PutResult> {
+        self.writes.put_opts(location, bytes, options).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.writes.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.writes.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        self.reads.get_opts(location, options).await
+    }
+
+    // Forwarded explicitly so read wrappers can specialize range reads
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        self.reads.get_range(location, range).await
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+        self.reads.get_ranges(location, ranges).await
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        self.reads.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        self.writes.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+        self.reads.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.reads.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.writes.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.writes.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.writes.copy_if_not_exists(from, to).await
+    }
+}
+
+/// Applies one wrapper to the read path and another to the write path
+///
+/// Either wrapper may be missing, in which case that path uses the underlying
+/// store directly.  If the same wrapper is used for both paths the store is
+/// only wrapped once.
+#[derive(Debug, Clone, Default)]
+pub struct ReadWriteStoreWrapper {
+    read: Option<Arc<dyn WrappingObjectStore>>,
+    write: Option<Arc<dyn WrappingObjectStore>>,
+}
+
+impl ReadWriteStoreWrapper {
+    pub fn new(
+        read: Option<Arc<dyn WrappingObjectStore>>,
+        write: Option<Arc<dyn WrappingObjectStore>>,
+    ) -> Self {
+        Self { read, write }
+    }
+
+    /// The wrapper to pass to lance, None if there is nothing to wrap
+    pub fn into_wrapper(self) -> Option<Arc<dyn WrappingObjectStore>> {
+        match (&self.read, &self.write) {
+            (None, None) => None,
+            (Some(read), Some(write)) if Arc::ptr_eq(read, write) => Some(read.clone()),
+            _ => Some(Arc::new(self)),
+        }
+    }
+}
+
+impl WrappingObjectStore for ReadWriteStoreWrapper {
+    fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+        let wrap = |wrapper: &Option<Arc<dyn WrappingObjectStore>>| match wrapper {
+            Some(wrapper) => wrapper.wrap(original.clone()),
+            None => original.clone(),
+        };
+        Arc::new(ReadWriteObjectStore {
+            reads: wrap(&self.read),
+            writes: wrap(&self.write),
+        })
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::TryStreamExt;
+    use lance_testing::datagen::{BatchGenerator, IncrementingInt32};
+
+    use crate::{
+        connect,
+        index::{scalar::BTreeIndexBuilder, Index},
+        query::{ExecutableQuery, QueryBase},
+    };
+
+    /// Counts the reads or writes made through the wrapped store
+    #[derive(Debug)]
+    struct CountingStore {
+        inner: Arc<dyn ObjectStore>,
+        reads: Arc<AtomicUsize>,
+        writes: Arc<AtomicUsize>,
+    }
+
+    impl std::fmt::Display for CountingStore {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "CountingStore({})", self.inner)
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for CountingStore {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            bytes: Bytes,
+            options: PutOptions,
+        ) -> Result<PutResult> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.inner.put_opts(location, bytes, options).await
+        }
+
+        async fn put_multipart(
+            &self,
+            location: &Path,
+        ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.inner.put_multipart(location).await
+        }
+
+        async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+            self.inner.abort_multipart(location, multipart_id).await
+        }
+
+        async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            self.inner.get_opts(location, options).await
+        }
+
+        async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            self.inner.head(location).await
+        }
+
+        async fn delete(&self, location: &Path) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.inner.delete(location).await
+        }
+
+        fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.inner.copy(from, to).await
+        }
+
+        async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.inner.copy_if_not_exists(from, to).await
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingWrapper {
+        reads: Arc<AtomicUsize>,
+        writes: Arc<AtomicUsize>,
+    }
+
+    impl WrappingObjectStore for CountingWrapper {
+        fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+            Arc::new(CountingStore {
+                inner: original,
+                reads: self.reads.clone(),
+                writes: self.writes.clone(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_separate_read_and_write_wrappers() {
+        let dir = tempfile::tempdir().unwrap();
+        let read_wrapper = Arc::new(CountingWrapper::default());
+        let write_wrapper = Arc::new(CountingWrapper::default());
+
+        let db = connect(dir.path().to_str().unwrap())
+            .read_store_wrapper(read_wrapper.clone())
+            .write_store_wrapper(write_wrapper.clone())
+            .execute()
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let table = db
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+
+        assert!(write_wrapper.writes.load(Ordering::Relaxed) > 0);
+        assert_eq!(read_wrapper.writes.load(Ordering::Relaxed), 0);
+
+        table
+            .create_index(&["id"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+
+        // Reopen so that nothing is served from the table's caches
+        let table = db.open_table("test").execute().await.unwrap();
+        let reads_before = read_wrapper.reads.load(Ordering::Relaxed);
+        let write_reads_before = write_wrapper.reads.load(Ordering::Relaxed);
+        let batches = table
+            .query()
+            .only_if("id = 5")
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+
+        // Both the index and the data are read through the read wrapper
+        assert!(read_wrapper.reads.load(Ordering::Relaxed) > reads_before);
+        assert_eq!(
+            write_wrapper.reads.load(Ordering::Relaxed),
+            write_reads_before
+        );
+        assert_eq!(read_wrapper.writes.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shared_wrapper() {
+        let dir = tempfile::tempdir().unwrap();
+        let wrapper = Arc::new(CountingWrapper::default());
+
+        let db = connect(dir.path().to_str().unwrap())
+            .store_wrapper(wrapper.clone())
+            .execute()
+            .await
+            .unwrap();
+
+        let mut datagen = BatchGenerator::new().col(Box::<IncrementingInt32>::default());
+        let table = db
+            .create_table("test", Box::new(datagen.batch(100)))
+            .execute()
+            .await
+            .unwrap();
+        let table = db.open_table(table.name()).execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 100);
+
+        assert!(wrapper.writes.load(Ordering::Relaxed) > 0);
+        assert!(wrapper.reads.load(Ordering::Relaxed) > 0);
+    }
+}
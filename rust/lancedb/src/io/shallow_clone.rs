@@ -0,0 +1,277 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading the data files that a shallow clone shares with its sources
+//!
+//! The manifest of a shallow clone made by [`crate::Table::copy_to`] on an
+//! object store lists the data files of its source without copying them.
+//! Lance looks every data file up in the data directory of the table, so the
+//! clone is opened with [`ShallowCloneWrapper`]: a read of a data file that is
+//! not in the data directory of the clone is served from the data directory
+//! of its sources, in order.  Everything else, including every write, goes to
+//! the clone.
+
+use std::fmt::Formatter;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use lance::io::WrappingObjectStore;
+use object_store::{
+    path::Path, GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore,
+    PutOptions, PutResult, Result,
+};
+use tokio::io::AsyncWrite;
+
+/// The copy of a get request made to a source
+fn copy_options(options: &GetOptions) -> GetOptions {
+    GetOptions {
+        if_match: options.if_match.clone(),
+        if_none_match: options.if_none_match.clone(),
+        if_modified_since: options.if_modified_since,
+        if_unmodified_since: options.if_unmodified_since,
+        range: options.range.clone(),
+        version: options.version.clone(),
+        head: options.head,
+    }
+}
+
+/// An object store that reads the missing data files of a clone from its sources
+#[derive(Debug)]
+struct ShallowCloneObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    // The data directory of the clone
+    data_dir: Path,
+    // The data directories of the sources
+    sources: Arc<Vec<Path>>,
+}
+
+impl std::fmt::Display for ShallowCloneObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ShallowCloneObjectStore({})", self.inner)
+    }
+}
+
+impl ShallowCloneObjectStore {
+    /// The locations of a data file of the clone in the sources
+    fn source_locations(&self, location: &Path) -> Vec<Path> {
+        let Some(parts) = location.prefix_match(&self.data_dir) else {
+            return Vec::new();
+        };
+        let parts = parts.collect::<Vec<_>>();
+        if parts.is_empty() {
+            return Vec::new();
+        }
+        self.sources
+            .iter()
+            .map(|source| {
+                parts
+                    .iter()
+                    .fold(source.clone(), |path, part| path.child(part.clone()))
+            })
+            .collect()
+    }
+
+    /// Read from the clone, then from each source while the file is not found
+    async fn read<T, F, Fut>(&self, location: &Path, read: F) -> Result<T>
+    where
+        F: Fn(Path) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut result = read(location.clone()).await;
+        for source in self.source_locations(location) {
+            if !matches!(result, Err(object_store::Error::NotFound { .. })) {
+                break;
+            }
+            result = read(source).await;
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl ObjectStore for ShallowCloneObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        options: PutOptions,
+    ) -> Result<PutResult> {
+        self.inner.put_opts(location, bytes, options).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.inner.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        self.read(location, |path| {
+            let options = copy_options(&options);
+            async move { self.inner.get_opts(&path, options).await }
+        })
+        .await
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        self.read(location, |path| {
+            let range = range.clone();
+            async move { self.inner.get_range(&path, range).await }
+        })
+        .await
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+        self.read(location, |path| async move {
+            self.inner.get_ranges(&path, ranges).await
+        })
+        .await
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        self.read(location, |path| async move { self.inner.head(&path).await })
+            .await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+/// Reads the data files that a shallow clone shares with its sources
+///
+/// `table` and `sources` are the paths of the tables in the object store.
+#[derive(Debug)]
+pub(crate) struct ShallowCloneWrapper {
+    data_dir: Path,
+    sources: Arc<Vec<Path>>,
+    inner: Option<Arc<dyn WrappingObjectStore>>,
+}
+
+impl ShallowCloneWrapper {
+    pub fn new(table: &Path, sources: &[Path]) -> Self {
+        Self {
+            data_dir: table.child("data"),
+            sources: Arc::new(sources.iter().map(|source| source.child("data")).collect()),
+            inner: None,
+        }
+    }
+
+    /// Apply another wrapper beneath the redirection
+    ///
+    /// The wrappers beneath see the path of the table that has the file, so
+    /// e.g. a file is decrypted with the key of the table that wrote it.
+    pub fn around(mut self, inner: Option<Arc<dyn WrappingObjectStore>>) -> Self {
+        self.inner = inner;
+        self
+    }
+}
+
+impl WrappingObjectStore for ShallowCloneWrapper {
+    fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+        let inner = match &self.inner {
+            Some(wrapper) => wrapper.wrap(original),
+            None => original,
+        };
+        Arc::new(ShallowCloneObjectStore {
+            inner,
+            data_dir: self.data_dir.clone(),
+            sources: self.sources.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reads_fall_back_to_the_sources() {
+        let memory = Arc::new(InMemory::new());
+        let wrapper = ShallowCloneWrapper::new(
+            &Path::from("db/clone.lance"),
+            &[Path::from("db/parent.lance"), Path::from("db/root.lance")],
+        );
+        let store = wrapper.wrap(memory.clone());
+        for (path, contents) in [
+            ("db/root.lance/data/a.lance", "root"),
+            ("db/parent.lance/data/b.lance", "parent"),
+            ("db/clone.lance/data/c.lance", "clone"),
+            ("db/root.lance/_versions/1.manifest", "manifest"),
+        ] {
+            memory
+                .put(&Path::from(path), Bytes::from(contents))
+                .await
+                .unwrap();
+        }
+
+        for (file, contents) in [("a", "root"), ("b", "parent"), ("c", "clone")] {
+            let location = Path::from(format!("db/clone.lance/data/{}.lance", file));
+            let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+            assert_eq!(bytes.as_ref(), contents.as_bytes());
+            let range = store.get_range(&location, 0..2).await.unwrap();
+            assert_eq!(range.as_ref(), &contents.as_bytes()[..2]);
+            let meta = store.head(&location).await.unwrap();
+            assert_eq!(meta.size, contents.len());
+        }
+
+        // Only the data files are shared, and writes go to the clone
+        assert!(store
+            .head(&Path::from("db/clone.lance/_versions/1.manifest"))
+            .await
+            .is_err());
+        assert!(store
+            .head(&Path::from("db/clone.lance/data/missing.lance"))
+            .await
+            .is_err());
+        let location = Path::from("db/clone.lance/data/a.lance");
+        store.put(&location, Bytes::from("new")).await.unwrap();
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), b"new");
+        let root = Path::from("db/root.lance/data/a.lance");
+        let bytes = memory.get(&root).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes.as_ref(), b"root");
+    }
+}
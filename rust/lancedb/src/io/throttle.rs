@@ -0,0 +1,331 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Throttling of the object store requests of a connection
+//!
+//! [`crate::connection::ConnectBuilder::io_throttle`] caps the number of
+//! object store requests that the tables of a connection have in flight and
+//! the rate at which they start new ones.  The limits are shared by all of
+//! the tables and operations of the connection, so a background job such as
+//! [`crate::Table::optimize`] can not take more of the store than the limits
+//! allow, leaving room for other clients of the store.  Requests beyond the
+//! limits wait, they are never rejected.
+//!
+//! A GET holds its slot until the response starts, the body is streamed
+//! afterwards.  A multipart upload holds its slot while the upload is
+//! created, and a listing only counts against the rate.
+
+use std::fmt::Formatter;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use lance::io::WrappingObjectStore;
+use object_store::{
+    path::Path, GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore,
+    PutOptions, PutResult, Result,
+};
+use tokio::io::AsyncWrite;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::Error;
+use crate::runtime::{self, Instant};
+
+/// Limits on the object store requests of a connection
+///
+/// See [`crate::connection::ConnectBuilder::io_throttle`]
+#[derive(Debug, Clone, Default)]
+pub struct IoThrottleConfig {
+    /// The maximum number of requests in flight at the same time, if limited
+    pub max_concurrent_requests: Option<usize>,
+    /// The maximum number of requests started per second, if limited
+    pub max_requests_per_second: Option<f64>,
+}
+
+impl IoThrottleConfig {
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        if self.max_concurrent_requests == Some(0) {
+            return Err(Error::InvalidInput {
+                message: "the maximum number of concurrent requests must be at least 1".to_string(),
+            });
+        }
+        if let Some(rate) = self.max_requests_per_second {
+            if !rate.is_finite() || rate <= 0.0 {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the maximum number of requests per second must be positive, got {}",
+                        rate
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The state shared by the stores of a connection
+#[derive(Debug)]
+struct Throttle {
+    semaphore: Option<Arc<Semaphore>>,
+    // The time between the starts of two requests
+    interval: Option<Duration>,
+    // The earliest start of the next request
+    next_start: Mutex<Instant>,
+}
+
+impl Throttle {
+    fn new(config: &IoThrottleConfig) -> Self {
+        Self {
+            semaphore: config
+                .max_concurrent_requests
+                .map(|max| Arc::new(Semaphore::new(max))),
+            interval: config
+                .max_requests_per_second
+                .map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next_start: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until a request may start
+    ///
+    /// The request holds the returned permit until it completes.
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.semaphore {
+            // The semaphore is never closed
+            Some(semaphore) => Some(semaphore.clone().acquire_owned().await.unwrap()),
+            None => None,
+        };
+        if let Some(interval) = self.interval {
+            let start = {
+                let mut next_start = self.next_start.lock().unwrap();
+                let start = (*next_start).max(Instant::now());
+                *next_start = start + interval;
+                start
+            };
+            runtime::sleep_until(start).await;
+        }
+        permit
+    }
+}
+
+/// An object store that waits for the [`Throttle`] before each request
+#[derive(Debug)]
+struct ThrottledObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    throttle: Arc<Throttle>,
+}
+
+impl std::fmt::Display for ThrottledObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ThrottledObjectStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for ThrottledObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        options: PutOptions,
+    ) -> Result<PutResult> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.put_opts(location, bytes, options).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.get_opts(location, options).await
+    }
+
+    // Forwarded explicitly so that the inner store can specialize range reads
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.get_range(location, range).await
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.get_ranges(location, ranges).await
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+        let listing = self.inner.list(prefix);
+        let throttle = self.throttle.clone();
+        futures::stream::once(async move {
+            throttle.acquire().await;
+            listing
+        })
+        .flatten()
+        .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        let _permit = self.throttle.acquire().await;
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+/// Throttles the object store requests of all the tables of a connection
+#[derive(Debug)]
+pub(crate) struct ThrottleWrapper {
+    throttle: Arc<Throttle>,
+    inner: Option<Arc<dyn WrappingObjectStore>>,
+}
+
+impl ThrottleWrapper {
+    pub fn new(config: &IoThrottleConfig) -> Self {
+        Self {
+            throttle: Arc::new(Throttle::new(config)),
+            inner: None,
+        }
+    }
+
+    /// Apply another wrapper beneath the throttle
+    pub fn around(mut self, inner: Option<Arc<dyn WrappingObjectStore>>) -> Self {
+        self.inner = inner;
+        self
+    }
+}
+
+impl WrappingObjectStore for ThrottleWrapper {
+    fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+        let inner = match &self.inner {
+            Some(wrapper) => wrapper.wrap(original),
+            None => original,
+        };
+        Arc::new(ThrottledObjectStore {
+            inner,
+            throttle: self.throttle.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(IoThrottleConfig::default().validate().is_ok());
+        for config in [
+            IoThrottleConfig {
+                max_concurrent_requests: Some(0),
+                ..Default::default()
+            },
+            IoThrottleConfig {
+                max_requests_per_second: Some(0.0),
+                ..Default::default()
+            },
+            IoThrottleConfig {
+                max_requests_per_second: Some(f64::NAN),
+                ..Default::default()
+            },
+        ] {
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let config = IoThrottleConfig {
+            max_requests_per_second: Some(100.0),
+            ..Default::default()
+        };
+        let store = ThrottleWrapper::new(&config).wrap(Arc::new(InMemory::new()));
+        let location = Path::from("t.lance/a");
+        store.put(&location, vec![1u8; 10].into()).await.unwrap();
+
+        let start = Instant::now();
+        for _ in 0..10 {
+            store.head(&location).await.unwrap();
+        }
+        // The requests start 10ms apart
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let throttle = Arc::new(Throttle::new(&IoThrottleConfig {
+            max_concurrent_requests: Some(2),
+            ..Default::default()
+        }));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..8)
+            .map(|_| {
+                let throttle = throttle.clone();
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight.clone();
+                tokio::spawn(async move {
+                    let _permit = throttle.acquire().await;
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+}
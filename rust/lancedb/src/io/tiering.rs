@@ -0,0 +1,813 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tiered storage of the data files of tables
+//!
+//! With [`crate::connection::ConnectBuilder::tiering`] the data files of the
+//! tables are kept in two tiers: every file lives in the object store, and
+//! the hot files are also mirrored in a local directory.  A file is hot if it
+//! was written recently or if it was read often recently, as set by the
+//! [`TieringPolicy`].  Reads of a mirrored file are served from the local
+//! copy, the other files are read from the object store.
+//!
+//! A file is copied when a read finds it hot, by that read, and the copies
+//! of the files that are no longer hot are removed the next time a file is
+//! copied or a report is made.  When the mirror is over its size limit the
+//! copies of the least read files are removed first.  The mirror is kept
+//! between runs, but the read counts are not.
+//!
+//! Unlike the disk cache, which keeps the recently read blocks of any
+//! immutable file, the tiers hold whole data files and follow a policy.  Use
+//! [`crate::Table::tiering_report`] to see the tier of each fragment.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Formatter;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use lance::io::WrappingObjectStore;
+use object_store::{
+    path::Path, GetOptions, GetRange, GetResult, GetResultPayload, ListResult, MultipartId,
+    ObjectMeta, ObjectStore, PutOptions, PutResult, Result,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::Error;
+use crate::runtime;
+
+use super::resolve_get_range;
+
+const STORE: &str = "TieredObjectStore";
+const TMP_MARKER: &str = ".tmp-";
+
+/// When the data files of a table are kept in the local tier
+///
+/// See [`crate::connection::ConnectBuilder::tiering`]
+#[derive(Debug, Clone)]
+pub struct TieringPolicy {
+    /// The directory of the local tier, created if it does not exist
+    pub local_path: PathBuf,
+    /// Files written less than this long ago are hot
+    pub hot_after_write: Duration,
+    /// Files read at least this many times within the read window are hot
+    pub hot_read_count: usize,
+    /// The period over which the reads of a file are counted
+    pub read_window: Duration,
+    /// The maximum size of the local tier, if limited
+    pub max_local_bytes: Option<u64>,
+}
+
+impl TieringPolicy {
+    /// Mirror the files written in the last day or read 3 times in the last hour
+    pub fn new(local_path: impl Into<PathBuf>) -> Self {
+        Self {
+            local_path: local_path.into(),
+            hot_after_write: Duration::from_secs(24 * 60 * 60),
+            hot_read_count: 3,
+            read_window: Duration::from_secs(60 * 60),
+            max_local_bytes: None,
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.hot_read_count == 0 {
+            return Err(Error::InvalidInput {
+                message: "the read count of hot files must be at least 1".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Where the data of a fragment is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageTier {
+    /// All the files of the fragment are on the local disk
+    Local,
+    /// Some files of the fragment are only in the object store
+    Remote,
+}
+
+/// The tier of a fragment, see [`TieringReport`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FragmentTier {
+    pub fragment_id: u64,
+    pub tier: StorageTier,
+    /// The size of the local copies of the files of the fragment
+    pub local_bytes: u64,
+    /// The number of reads of the files of the fragment within the read window
+    pub recent_reads: usize,
+    /// When the files of the fragment were written, if known
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// The tiers of the fragments of a table
+///
+/// Returned by [`crate::Table::tiering_report`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TieringReport {
+    /// False if tiering is not enabled, then every fragment is in the tier
+    /// of the table's store
+    pub enabled: bool,
+    pub fragments: Vec<FragmentTier>,
+}
+
+impl TieringReport {
+    /// A report for a table that is not tiered
+    pub(crate) fn untiered(fragments: Vec<(u64, Vec<Path>)>, local: bool) -> Self {
+        let tier = if local {
+            StorageTier::Local
+        } else {
+            StorageTier::Remote
+        };
+        Self {
+            enabled: false,
+            fragments: fragments
+                .into_iter()
+                .map(|(fragment_id, _)| FragmentTier {
+                    fragment_id,
+                    tier,
+                    local_bytes: 0,
+                    recent_reads: 0,
+                    last_modified: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// The total size of the local copies of the fragments
+    pub fn local_bytes(&self) -> u64 {
+        self.fragments.iter().map(|f| f.local_bytes).sum()
+    }
+
+    /// The ids of the fragments in the local tier
+    pub fn local_fragments(&self) -> Vec<u64> {
+        self.fragments
+            .iter()
+            .filter(|f| f.tier == StorageTier::Local)
+            .map(|f| f.fragment_id)
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct FileState {
+    reads: VecDeque<Instant>,
+    // Unknown until the file is first read in this process
+    last_modified: Option<DateTime<Utc>>,
+    // The size of the local copy, if there is one
+    local_bytes: Option<u64>,
+    promoting: bool,
+}
+
+enum Decision {
+    Local(u64),
+    Promote,
+    Remote,
+}
+
+/// The state of the tiers, shared by all the stores of a connection
+#[derive(Debug)]
+pub(crate) struct Tiering {
+    policy: TieringPolicy,
+    files: Mutex<HashMap<Path, FileState>>,
+}
+
+impl Tiering {
+    /// Open the local tier, reusing the copies already in the directory
+    pub fn open(policy: &TieringPolicy) -> crate::Result<Self> {
+        policy.validate()?;
+        let io_error = |e: std::io::Error| Error::Runtime {
+            message: format!(
+                "failed to open the local tier at {}: {}",
+                policy.local_path.display(),
+                e
+            ),
+        };
+        std::fs::create_dir_all(&policy.local_path).map_err(io_error)?;
+        let mut files = HashMap::new();
+        let mut dirs = vec![policy.local_path.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir).map_err(io_error)?.flatten() {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    dirs.push(entry.path());
+                    continue;
+                }
+                if entry.file_name().to_string_lossy().contains(TMP_MARKER) {
+                    // Left over by a copy that did not finish
+                    let _ = std::fs::remove_file(entry.path());
+                    continue;
+                }
+                let Ok(relative) = entry.path().strip_prefix(&policy.local_path).map(|p| {
+                    p.components()
+                        .map(|c| c.as_os_str().to_string_lossy().to_string())
+                        .collect::<Vec<_>>()
+                        .join("/")
+                }) else {
+                    continue;
+                };
+                if let Ok(location) = Path::parse(relative) {
+                    let state = FileState {
+                        local_bytes: Some(metadata.len()),
+                        ..Default::default()
+                    };
+                    files.insert(location, state);
+                }
+            }
+        }
+        Ok(Self {
+            policy: policy.clone(),
+            files: Mutex::new(files),
+        })
+    }
+
+    fn local_path(&self, location: &Path) -> PathBuf {
+        self.policy.local_path.join(location.as_ref())
+    }
+
+    fn is_hot(&self, state: &FileState, now: DateTime<Utc>) -> bool {
+        let recently_written = state.last_modified.is_some_and(|last_modified| {
+            match (now - last_modified).to_std() {
+                Ok(age) => age < self.policy.hot_after_write,
+                // Written in the future, by a store with another clock
+                Err(_) => true,
+            }
+        });
+        recently_written || state.reads.len() >= self.policy.hot_read_count
+    }
+
+    /// Count a read of the file, returns true if its last modification is unknown
+    fn record_read(&self, location: &Path) -> bool {
+        let mut files = self.files.lock().unwrap();
+        let state = files.entry(location.clone()).or_default();
+        let now = Instant::now();
+        state.reads.push_back(now);
+        while let Some(oldest) = state.reads.front() {
+            if now.duration_since(*oldest) <= self.policy.read_window {
+                break;
+            }
+            state.reads.pop_front();
+        }
+        state.last_modified.is_none()
+    }
+
+    fn set_last_modified(&self, location: &Path, last_modified: DateTime<Utc>) {
+        let mut files = self.files.lock().unwrap();
+        files.entry(location.clone()).or_default().last_modified = Some(last_modified);
+    }
+
+    fn decide(&self, location: &Path) -> Decision {
+        let mut files = self.files.lock().unwrap();
+        let Some(state) = files.get_mut(location) else {
+            return Decision::Remote;
+        };
+        if let Some(local_bytes) = state.local_bytes {
+            Decision::Local(local_bytes)
+        } else if !state.promoting && self.is_hot(state, Utc::now()) {
+            state.promoting = true;
+            Decision::Promote
+        } else {
+            Decision::Remote
+        }
+    }
+
+    fn finish_promotion(&self, location: &Path, local_bytes: Option<u64>) {
+        let mut files = self.files.lock().unwrap();
+        let state = files.entry(location.clone()).or_default();
+        state.promoting = false;
+        state.local_bytes = local_bytes;
+    }
+
+    /// Forget the local copy of the file, e.g. because it could not be read
+    fn forget_local(&self, location: &Path) {
+        if let Some(state) = self.files.lock().unwrap().get_mut(location) {
+            state.local_bytes = None;
+        }
+    }
+
+    fn remove(&self, location: &Path) {
+        self.files.lock().unwrap().remove(location);
+    }
+
+    /// Decide which local copies to remove
+    ///
+    /// Copies of files that are no longer hot are removed, and then the copies
+    /// of the least read files until the local tier fits its size limit.
+    /// Files that were not read since the start of the process are kept until
+    /// they are, because when they were written is not known.
+    fn demote(&self) -> Vec<Path> {
+        let mut files = self.files.lock().unwrap();
+        let now = Utc::now();
+        let mut demoted = Vec::new();
+        for (location, state) in files.iter_mut() {
+            if state.local_bytes.is_some()
+                && state.last_modified.is_some()
+                && !self.is_hot(state, now)
+            {
+                state.local_bytes = None;
+                demoted.push(location.clone());
+            }
+        }
+        if let Some(max_local_bytes) = self.policy.max_local_bytes {
+            let mut local = files
+                .iter()
+                .filter_map(|(location, state)| {
+                    let size = state.local_bytes?;
+                    Some((
+                        state.reads.len(),
+                        state.last_modified,
+                        size,
+                        location.clone(),
+                    ))
+                })
+                .collect::<Vec<_>>();
+            let mut total = local.iter().map(|(_, _, size, _)| size).sum::<u64>();
+            // The least read, and then the oldest, first
+            local.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+            for (_, _, size, location) in local {
+                if total <= max_local_bytes {
+                    break;
+                }
+                total -= size;
+                if let Some(state) = files.get_mut(&location) {
+                    state.local_bytes = None;
+                }
+                demoted.push(location);
+            }
+        }
+        demoted
+    }
+
+    async fn remove_local_copies(&self, locations: Vec<Path>) {
+        for location in locations {
+            let _ = runtime::fs::remove_file(self.local_path(&location)).await;
+        }
+    }
+
+    /// The tiers of the given fragments and the paths of their data files
+    pub async fn report(&self, fragments: Vec<(u64, Vec<Path>)>) -> TieringReport {
+        self.remove_local_copies(self.demote()).await;
+        let files = self.files.lock().unwrap();
+        let fragments = fragments
+            .into_iter()
+            .map(|(fragment_id, paths)| {
+                let states = paths.iter().map(|path| files.get(path)).collect::<Vec<_>>();
+                let all_local = states
+                    .iter()
+                    .all(|state| state.is_some_and(|s| s.local_bytes.is_some()));
+                FragmentTier {
+                    fragment_id,
+                    tier: if all_local && !states.is_empty() {
+                        StorageTier::Local
+                    } else {
+                        StorageTier::Remote
+                    },
+                    local_bytes: states.iter().flatten().filter_map(|s| s.local_bytes).sum(),
+                    recent_reads: states
+                        .iter()
+                        .flatten()
+                        .map(|s| s.reads.len())
+                        .max()
+                        .unwrap_or(0),
+                    last_modified: states
+                        .iter()
+                        .flatten()
+                        .filter_map(|s| s.last_modified)
+                        .max(),
+                }
+            })
+            .collect();
+        TieringReport {
+            enabled: true,
+            fragments,
+        }
+    }
+}
+
+/// Whether the file is a data file of a table
+fn is_data_file(location: &Path) -> bool {
+    let parts = location
+        .parts()
+        .map(|part| part.as_ref().to_string())
+        .collect::<Vec<_>>();
+    parts
+        .windows(2)
+        .any(|pair| pair[0].ends_with(".lance") && pair[1] == "data")
+}
+
+fn generic_error(message: impl Into<String>) -> object_store::Error {
+    object_store::Error::Generic {
+        store: STORE,
+        source: message.into().into(),
+    }
+}
+
+/// An object store that serves the reads of hot data files from the local tier
+#[derive(Debug)]
+struct TieredObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    tiering: Arc<Tiering>,
+}
+
+impl std::fmt::Display for TieredObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TieredObjectStore({})", self.inner)
+    }
+}
+
+impl TieredObjectStore {
+    async fn read_local(&self, location: &Path, range: Range<usize>) -> std::io::Result<Bytes> {
+        let mut file = runtime::fs::File::open(self.tiering.local_path(location)).await?;
+        file.seek(SeekFrom::Start(range.start as u64)).await?;
+        let mut buffer = vec![0; range.len()];
+        file.read_exact(&mut buffer).await?;
+        Ok(Bytes::from(buffer))
+    }
+
+    /// Copy the file to the local tier, returns its size
+    ///
+    /// The file is streamed to the local tier in chunks, it is never held in
+    /// memory.  Returns None if the local copy could not be written.
+    async fn promote(&self, location: &Path) -> Result<Option<u64>> {
+        let mut stream = match self.inner.get(location).await {
+            Ok(result) => result.into_stream(),
+            Err(err) => {
+                self.tiering.finish_promotion(location, None);
+                return Err(err);
+            }
+        };
+        let path = self.tiering.local_path(location);
+        let tmp = path.with_file_name(format!(
+            "{}{}{:016x}",
+            location.filename().unwrap_or_default(),
+            TMP_MARKER,
+            rand::random::<u64>()
+        ));
+        let mut remote_error = None;
+        let copied = async {
+            if let Some(parent) = path.parent() {
+                runtime::fs::create_dir_all(parent).await?;
+            }
+            let mut file = runtime::fs::File::create(&tmp).await?;
+            let mut size = 0;
+            while let Some(chunk) = stream.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(err) => {
+                        remote_error = Some(err);
+                        return Err(std::io::ErrorKind::Interrupted.into());
+                    }
+                };
+                file.write_all(&chunk).await?;
+                size += chunk.len() as u64;
+            }
+            file.flush().await?;
+            drop(file);
+            runtime::fs::rename(&tmp, &path).await?;
+            Ok::<_, std::io::Error>(size)
+        }
+        .await;
+        match copied {
+            Ok(size) => {
+                self.tiering.finish_promotion(location, Some(size));
+                self.tiering
+                    .remove_local_copies(self.tiering.demote())
+                    .await;
+                Ok(Some(size))
+            }
+            Err(err) => {
+                let _ = runtime::fs::remove_file(&tmp).await;
+                self.tiering.finish_promotion(location, None);
+                if let Some(err) = remote_error {
+                    return Err(err);
+                }
+                log::warn!("failed to copy {} to the local tier: {}", location, err);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read the range of a data file from the tier it is in
+    ///
+    /// Returns the bytes, the metadata of the file and the range that was
+    /// read, or None if the file should be read from the object store.
+    async fn read_tiered(
+        &self,
+        location: &Path,
+        range: Option<GetRange>,
+    ) -> Result<Option<(Bytes, ObjectMeta, Range<usize>)>> {
+        if self.tiering.record_read(location) {
+            let meta = self.inner.head(location).await?;
+            self.tiering.set_last_modified(location, meta.last_modified);
+        }
+        let size = match self.tiering.decide(location) {
+            Decision::Remote => return Ok(None),
+            Decision::Local(size) => size as usize,
+            Decision::Promote => match self.promote(location).await? {
+                Some(size) => size as usize,
+                None => return Ok(None),
+            },
+        };
+        let Some(resolved) = resolve_get_range(range.as_ref(), size) else {
+            return Err(generic_error(format!(
+                "the range {:?} is out of bounds for '{}' of size {}",
+                range, location, size
+            )));
+        };
+        let bytes = match self.read_local(location, resolved.clone()).await {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                log::warn!("failed to read {} from the local tier: {}", location, err);
+                self.tiering.forget_local(location);
+                return Ok(None);
+            }
+        };
+        let last_modified = self
+            .tiering
+            .files
+            .lock()
+            .unwrap()
+            .get(location)
+            .and_then(|state| state.last_modified)
+            .unwrap_or_default();
+        let meta = ObjectMeta {
+            location: location.clone(),
+            last_modified,
+            size,
+            e_tag: None,
+            version: None,
+        };
+        Ok(Some((bytes, meta, resolved)))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for TieredObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        options: PutOptions,
+    ) -> Result<PutResult> {
+        self.inner.put_opts(location, bytes, options).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.inner.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> Result<()> {
+        self.inner.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        let conditional = options.if_match.is_some()
+            || options.if_none_match.is_some()
+            || options.if_modified_since.is_some()
+            || options.if_unmodified_since.is_some()
+            || options.version.is_some()
+            || options.head;
+        if !is_data_file(location) || conditional {
+            return self.inner.get_opts(location, options).await;
+        }
+        match self.read_tiered(location, options.range.clone()).await? {
+            Some((bytes, meta, range)) => Ok(GetResult {
+                payload: GetResultPayload::Stream(
+                    futures::stream::once(async { Ok(bytes) }).boxed(),
+                ),
+                meta,
+                range,
+            }),
+            None => self.inner.get_opts(location, options).await,
+        }
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
+        if !is_data_file(location) {
+            return self.inner.get_range(location, range).await;
+        }
+        match self
+            .read_tiered(location, Some(GetRange::Bounded(range.clone())))
+            .await?
+        {
+            Some((bytes, _, _)) => Ok(bytes),
+            None => self.inner.get_range(location, range).await,
+        }
+    }
+
+    async fn head(&self, location: &Path) -> Result<ObjectMeta> {
+        self.inner.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<()> {
+        self.inner.delete(location).await?;
+        if is_data_file(location) {
+            self.tiering.remove(location);
+            let _ = runtime::fs::remove_file(self.tiering.local_path(location)).await;
+        }
+        Ok(())
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+/// Keeps the hot data files of all the tables of a connection locally
+#[derive(Debug)]
+pub(crate) struct TieringWrapper {
+    tiering: Arc<Tiering>,
+    inner: Option<Arc<dyn WrappingObjectStore>>,
+}
+
+impl TieringWrapper {
+    pub fn new(tiering: Arc<Tiering>) -> Self {
+        Self {
+            tiering,
+            inner: None,
+        }
+    }
+
+    /// Apply another wrapper beneath the tiers
+    pub fn around(mut self, inner: Option<Arc<dyn WrappingObjectStore>>) -> Self {
+        self.inner = inner;
+        self
+    }
+}
+
+impl WrappingObjectStore for TieringWrapper {
+    fn wrap(&self, original: Arc<dyn ObjectStore>) -> Arc<dyn ObjectStore> {
+        let inner = match &self.inner {
+            Some(wrapper) => wrapper.wrap(original),
+            None => original,
+        };
+        Arc::new(TieredObjectStore {
+            inner,
+            tiering: self.tiering.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn policy(dir: &std::path::Path) -> TieringPolicy {
+        TieringPolicy {
+            hot_after_write: Duration::ZERO,
+            hot_read_count: 2,
+            ..TieringPolicy::new(dir)
+        }
+    }
+
+    fn store(tiering: &Arc<Tiering>) -> (Arc<InMemory>, Arc<dyn ObjectStore>) {
+        let original = Arc::new(InMemory::new());
+        let store = TieringWrapper::new(tiering.clone()).wrap(original.clone());
+        (original, store)
+    }
+
+    #[test]
+    fn test_is_data_file() {
+        assert!(is_data_file(&Path::from("db/t.lance/data/a.lance")));
+        assert!(!is_data_file(&Path::from("t.lance/_indices/abc/index.idx")));
+        assert!(!is_data_file(&Path::from("data/t.lance/_latest.manifest")));
+    }
+
+    #[tokio::test]
+    async fn test_frequently_read_files_are_local() {
+        let tmp_dir = tempdir().unwrap();
+        let tiering = Arc::new(Tiering::open(&policy(tmp_dir.path())).unwrap());
+        let (original, store) = store(&tiering);
+        let location = Path::from("t.lance/data/a.lance");
+        original
+            .put(&location, Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+        let fragments = vec![(0, vec![location.clone()])];
+
+        // The first read goes to the object store
+        assert_eq!(store.get_range(&location, 2..4).await.unwrap(), "23");
+        let report = tiering.report(fragments.clone()).await;
+        assert!(report.enabled);
+        assert_eq!(report.fragments[0].tier, StorageTier::Remote);
+        assert_eq!(report.fragments[0].recent_reads, 1);
+
+        // The second makes the file hot
+        assert_eq!(store.get_range(&location, 4..6).await.unwrap(), "45");
+        let report = tiering.report(fragments.clone()).await;
+        assert_eq!(report.local_fragments(), vec![0]);
+        assert_eq!(report.local_bytes(), 10);
+
+        original.delete(&location).await.unwrap();
+        assert_eq!(store.get_range(&location, 6..8).await.unwrap(), "67");
+        let bytes = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(bytes, "0123456789");
+
+        // The copy is found again by the next connection
+        let reopened = Tiering::open(&policy(tmp_dir.path())).unwrap();
+        assert_eq!(reopened.report(fragments).await.local_bytes(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_recently_written_files_are_local() {
+        let tmp_dir = tempdir().unwrap();
+        let policy = TieringPolicy {
+            hot_read_count: 100,
+            ..TieringPolicy::new(tmp_dir.path())
+        };
+        let tiering = Arc::new(Tiering::open(&policy).unwrap());
+        let (original, store) = store(&tiering);
+        let location = Path::from("t.lance/data/a.lance");
+        original
+            .put(&location, Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+
+        store.get_range(&location, 0..1).await.unwrap();
+        let report = tiering.report(vec![(0, vec![location])]).await;
+        assert_eq!(report.local_fragments(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_size_limit() {
+        let tmp_dir = tempdir().unwrap();
+        let policy = TieringPolicy {
+            hot_read_count: 1,
+            max_local_bytes: Some(15),
+            ..policy(tmp_dir.path())
+        };
+        let tiering = Arc::new(Tiering::open(&policy).unwrap());
+        let (original, store) = store(&tiering);
+        let a = Path::from("t.lance/data/a.lance");
+        let b = Path::from("t.lance/data/b.lance");
+        original
+            .put(&a, Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+        original
+            .put(&b, Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+
+        store.get_range(&a, 0..1).await.unwrap();
+        store.get_range(&a, 0..1).await.unwrap();
+        store.get_range(&b, 0..1).await.unwrap();
+
+        // The less read file is removed from the local tier
+        let report = tiering
+            .report(vec![(0, vec![a]), (1, vec![b.clone()])])
+            .await;
+        assert_eq!(report.local_fragments(), vec![0]);
+        assert!(!tmp_dir.path().join(b.as_ref()).exists());
+
+        let invalid = TieringPolicy {
+            hot_read_count: 0,
+            ..TieringPolicy::new(tmp_dir.path())
+        };
+        assert!(Tiering::open(&invalid).is_err());
+    }
+}
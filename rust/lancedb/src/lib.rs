@@ -182,16 +182,25 @@
 //! ```
 
 pub mod arrow;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod blocking;
+pub mod catalog;
 pub mod connection;
 pub mod data;
 pub mod error;
+pub mod eval;
+pub mod expr;
 pub mod index;
 pub mod io;
 pub mod ipc;
 pub mod query;
 #[cfg(feature = "remote")]
 pub(crate) mod remote;
+pub(crate) mod runtime;
+pub mod serve;
 pub mod table;
+pub mod telemetry;
+pub mod units;
 pub mod utils;
 
 use std::fmt::Display;
@@ -14,15 +14,42 @@
 
 use std::future::Future;
 use std::sync::Arc;
+use std::time::Instant;
 
 use arrow_array::{make_array, Array, Float16Array, Float32Array, Float64Array};
-use arrow_schema::DataType;
+use arrow_schema::{DataType, Schema};
+use chrono::{DateTime, Utc};
 use half::f16;
 
-use crate::arrow::SendableRecordBatchStream;
+use crate::arrow::{stream_into_reader, SendableRecordBatchStream};
 use crate::error::{Error, Result};
+use crate::table::estimate::{estimate_query, estimate_vector_query, CostEstimate};
+use crate::table::fragment_stats::FragmentPruning;
 use crate::table::TableInternal;
-use crate::DistanceType;
+use crate::telemetry::instrument_query_stream;
+use crate::{DistanceType, Table};
+
+use self::aggregate::GroupBy;
+use self::diagnostics::{diagnose_filter, FilterDiagnostic};
+use self::join::{Join, JoinType};
+use self::order::SortOrder;
+use self::resumable::{ResumableStream, ScanCheckpoint};
+use self::score::{ScoreNorm, ScoreTransform};
+
+pub mod aggregate;
+pub mod diagnostics;
+pub(crate) mod distinct;
+pub(crate) mod filter;
+pub mod filter_cache;
+pub mod join;
+pub(crate) mod late;
+pub mod order;
+pub mod pipeline;
+pub mod prepared;
+pub(crate) mod reference;
+pub(crate) mod rescore;
+pub mod resumable;
+pub mod score;
 
 pub(crate) const DEFAULT_TOP_K: usize = 10;
 
@@ -42,6 +69,10 @@ pub enum Select {
     ///
     /// See [`Query::select`] for more details and examples
     Dynamic(Vec<(String, String)>),
+    /// Select all columns except the provided ones
+    ///
+    /// This is commonly used to avoid returning large vector columns.
+    AllExcept(Vec<String>),
 }
 
 impl Select {
@@ -64,6 +95,105 @@ impl Select {
                 .collect(),
         )
     }
+
+    /// Create a dynamic selection from SQL expressions with optional aliases
+    ///
+    /// Each expression is written as in the SELECT clause of an SQL query,
+    /// for example `metadata.title AS title`, `lower(name) AS name` or `id`.
+    /// Fields of struct columns are referenced with dots.  An expression
+    /// without an alias is named after the expression itself.
+    pub fn expressions(expressions: &[impl AsRef<str>]) -> Self {
+        Self::Dynamic(
+            expressions
+                .iter()
+                .map(|expression| split_alias(expression.as_ref()))
+                .collect(),
+        )
+    }
+
+    /// Create a selection of all columns except the given ones
+    ///
+    /// This method is a convenience method for creating a [`Select::AllExcept`] variant
+    pub fn all_except(columns: &[impl AsRef<str>]) -> Self {
+        Self::AllExcept(columns.iter().map(|c| c.as_ref().to_string()).collect())
+    }
+
+    /// The names of the selected columns of the schema
+    ///
+    /// Returns None for a dynamic selection.  Fails if a column excluded by
+    /// [`Select::AllExcept`] does not exist.
+    pub(crate) fn column_names(&self, schema: &Schema) -> Result<Option<Vec<String>>> {
+        let all = || schema.fields().iter().map(|f| f.name().clone());
+        match self {
+            Self::All => Ok(Some(all().collect())),
+            Self::Columns(columns) => Ok(Some(columns.clone())),
+            Self::Dynamic(_) => Ok(None),
+            Self::AllExcept(excluded) => {
+                if let Some(missing) = excluded
+                    .iter()
+                    .find(|column| schema.field_with_name(column).is_err())
+                {
+                    return Err(Error::InvalidInput {
+                        message: format!(
+                            "cannot exclude the column '{}', it does not exist",
+                            missing
+                        ),
+                    });
+                }
+                Ok(Some(
+                    all().filter(|name| !excluded.contains(name)).collect(),
+                ))
+            }
+        }
+    }
+}
+
+/// Split `expression AS alias` into the alias and the expression
+///
+/// Only an `AS` outside of parentheses and quotes starts an alias, so
+/// `CAST(x AS INT)` is not split.
+fn split_alias(expression: &str) -> (String, String) {
+    let chars = expression.char_indices().collect::<Vec<_>>();
+    let mut quote = None;
+    let mut depth = 0;
+    let mut split = None;
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '\'' | '"' | '`' => quote = Some(c),
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ if depth == 0
+                    && c.is_whitespace()
+                    && chars.len() > i + 3
+                    && chars[i + 1].1.eq_ignore_ascii_case(&'a')
+                    && chars[i + 2].1.eq_ignore_ascii_case(&'s')
+                    && chars[i + 3].1.is_whitespace() =>
+                {
+                    split = Some((offset, chars[i + 3].0));
+                }
+                _ => {}
+            },
+        }
+    }
+    let trimmed = expression.trim();
+    match split {
+        Some((end, alias_start)) => {
+            let alias = expression[alias_start..].trim();
+            let unquoted = alias
+                .strip_prefix('`')
+                .and_then(|a| a.strip_suffix('`'))
+                .or_else(|| alias.strip_prefix('"').and_then(|a| a.strip_suffix('"')))
+                .unwrap_or(alias);
+            if unquoted.is_empty() {
+                return (trimmed.to_string(), trimmed.to_string());
+            }
+            (unquoted.to_string(), expression[..end].trim().to_string())
+        }
+        None => (trimmed.to_string(), trimmed.to_string()),
+    }
 }
 
 /// A trait for converting a type to a query vector
@@ -370,7 +500,160 @@ pub trait QueryBase {
     ///
     /// Columns will always be returned in the order given, even if that order is different than
     /// the order used when adding the data.
+    ///
+    /// [`Select::expressions`] creates dynamic columns from SQL expressions with aliases, such
+    /// as `metadata.title AS title`, and [`Select::AllExcept`] selects every column but a few.
     fn select(self, selection: Select) -> Self;
+
+    /// Return all columns except the specified ones.
+    ///
+    /// This is a shortcut for `select(Select::all_except(columns))`.  It is most often used to
+    /// avoid returning the vector column, which is usually the largest column of a table and
+    /// rarely needed in the results.
+    fn select_all_except(self, columns: &[impl AsRef<str>]) -> Self;
+
+    /// Leave the vector columns out of the results.
+    ///
+    /// Vector columns (all `FixedSizeList` columns) are usually the largest columns of a table
+    /// and most applications only need the ids and metadata of the results.  This only applies
+    /// when the query does not select its columns with [`Self::select`] (or selects all but
+    /// some with [`Self::select_all_except`]).  A vector search can still search a vector column
+    /// that is left out of its results.
+    ///
+    /// See [`crate::connection::ConnectBuilder::exclude_vectors`] to leave vector columns out
+    /// of the results by default.
+    fn without_vectors(self) -> Self;
+
+    /// Return the vector columns, even if they are left out of results by default.
+    ///
+    /// See [`Self::without_vectors`].
+    fn with_vectors(self) -> Self;
+
+    /// Include the soft deleted rows and the column marking them.
+    ///
+    /// This only matters for tables with soft deletes enabled (see
+    /// [`crate::Table::set_soft_delete`]).  The filter can refer to the
+    /// [`crate::table::soft_delete::DELETED_COLUMN`], e.g. to only return the
+    /// deleted rows.
+    fn with_deleted_rows(self) -> Self;
+
+    /// Only return the first row for each distinct value of the given columns.
+    ///
+    /// For a vector search the nearest row of each value is kept.  This is
+    /// useful when a document is split into several chunks and the search should
+    /// return one result per document:
+    ///
+    /// ```no_run
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("chunks").execute().await.unwrap();
+    /// let results = tbl
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .distinct_on(&["document_id"])
+    ///     .limit(5)
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    ///
+    /// The limit applies to the distinct rows.  A vector search fetches more
+    /// candidates than the limit, and repeats the search with more candidates
+    /// if there are not enough distinct values, so it is more expensive than a
+    /// regular search when many rows share a value.  Null is treated as a
+    /// regular value.
+    ///
+    /// The columns do not need to be selected, they are removed from the
+    /// results if they were not.
+    fn distinct_on(self, columns: &[impl AsRef<str>]) -> Self;
+
+    /// Only return the rows that were valid at the given time
+    ///
+    /// The table must have temporal validity columns, see
+    /// [`crate::Table::set_temporal_validity`].  The validity filter is combined
+    /// with the filter given to [`Self::only_if`], if any.  This is different from
+    /// [`crate::Table::checkout`] which reads an old version of the table.
+    fn as_of(self, time: DateTime<Utc>) -> Self;
+
+    /// Run the query against the version of the table that was current at the given time
+    ///
+    /// This reads the latest version that was committed at or before the time,
+    /// like [`crate::Table::checkout`] does for a version number, but only for
+    /// this query and without changing the table handle.  The query fails if the
+    /// table did not exist yet at that time, or if the version was removed (see
+    /// [`crate::table::OptimizeAction::Prune`]).
+    ///
+    /// This is different from [`Self::as_of`], which filters the rows of the
+    /// current version by their temporal validity columns.  The two can be
+    /// combined.
+    fn version_at(self, time: DateTime<Utc>) -> Self;
+
+    /// Read the given columns only for the rows that are returned
+    ///
+    /// By default every selected column is read while the results are being
+    /// selected.  The late columns are instead read with a second, random
+    /// access read of the result rows once the results are known.  This is
+    /// much cheaper for wide columns (e.g. images or long documents) when the
+    /// search considers many more rows than it returns, for example, a vector
+    /// search with a filter or a refine factor.  It is more expensive when
+    /// most of the rows are returned.
+    ///
+    /// Late columns that are not selected are ignored.  Columns used by
+    /// [`Self::distinct_on`] are always read early.  This cannot be combined
+    /// with [`Select::Dynamic`].
+    ///
+    /// ```no_run
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("images").execute().await.unwrap();
+    /// let results = tbl
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .only_if("label = 'cat'")
+    ///     .late_materialization(&["image"])
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    fn late_materialization(self, columns: &[impl AsRef<str>]) -> Self;
+
+    /// Do not read the values of the given binary columns
+    ///
+    /// The results do not contain the selected blob columns.  Instead they
+    /// contain a `_rowid` column, and [`crate::Table::blob_refs`] creates a
+    /// reference for each row that reads the value on demand.  This avoids
+    /// holding large values (images, audio, documents) in memory, and reading
+    /// the values that are never used.
+    ///
+    /// ```no_run
+    /// # use arrow_array::RecordBatch;
+    /// # use futures::TryStreamExt;
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("images").execute().await.unwrap();
+    /// let batches: Vec<RecordBatch> = tbl
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .lazy_blobs(&["image"])
+    ///     .execute()
+    ///     .await
+    ///     .unwrap()
+    ///     .try_collect()
+    ///     .await
+    ///     .unwrap();
+    /// let images = tbl.blob_refs(&batches[0], "image").await.unwrap();
+    /// let first_image = images[0].read().await.unwrap();
+    /// # });
+    /// ```
+    fn lazy_blobs(self, columns: &[impl AsRef<str>]) -> Self;
 }
 
 pub trait HasQuery {
@@ -392,9 +675,74 @@ impl<T: HasQuery> QueryBase for T {
         self.mut_query().select = select;
         self
     }
+
+    fn select_all_except(self, columns: &[impl AsRef<str>]) -> Self {
+        self.select(Select::all_except(columns))
+    }
+
+    fn without_vectors(mut self) -> Self {
+        self.mut_query().include_vectors = Some(false);
+        self
+    }
+
+    fn with_vectors(mut self) -> Self {
+        self.mut_query().include_vectors = Some(true);
+        self
+    }
+
+    fn with_deleted_rows(mut self) -> Self {
+        self.mut_query().include_deleted = true;
+        self
+    }
+
+    fn as_of(mut self, time: DateTime<Utc>) -> Self {
+        self.mut_query().as_of = Some(time);
+        self
+    }
+
+    fn version_at(mut self, time: DateTime<Utc>) -> Self {
+        self.mut_query().version_at = Some(time);
+        self
+    }
+
+    fn distinct_on(mut self, columns: &[impl AsRef<str>]) -> Self {
+        self.mut_query().distinct_on = Some(
+            columns
+                .iter()
+                .map(|column| column.as_ref().to_string())
+                .collect(),
+        );
+        self
+    }
+
+    fn lazy_blobs(mut self, columns: &[impl AsRef<str>]) -> Self {
+        self.mut_query().lazy_blobs = Some(
+            columns
+                .iter()
+                .map(|column| column.as_ref().to_string())
+                .collect(),
+        );
+        self
+    }
+
+    fn late_materialization(mut self, columns: &[impl AsRef<str>]) -> Self {
+        self.mut_query().late_materialization = Some(
+            columns
+                .iter()
+                .map(|column| column.as_ref().to_string())
+                .collect(),
+        );
+        self
+    }
 }
 
 /// Options for controlling the execution of a query
+///
+/// There is no option for the size of the IO buffer of a scan: the scanner
+/// of the Lance release used by LanceDB does not have one.  The memory used
+/// by a scan is limited with [`Self::fragment_readahead`] and
+/// [`Self::decode_parallelism`] instead.
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct QueryExecutionOptions {
     /// The maximum number of rows that will be contained in a single
@@ -410,12 +758,41 @@ pub struct QueryExecutionOptions {
     ///
     /// By default, this is 1024
     pub max_batch_length: u32,
+    /// The number of batches that are read and decoded concurrently
+    ///
+    /// Decoding is usually limited by memory bandwidth rather than by the
+    /// number of cores.  On machines with many cores a lower value can give
+    /// the same throughput with less memory traffic and less memory use.
+    ///
+    /// By default, this is the value of the table (see
+    /// [`crate::connection::ConnectBuilder::decode_parallelism`]), or the
+    /// default of Lance if that is not set.  This only affects LanceDB OSS.
+    pub decode_parallelism: Option<usize>,
+    /// The number of fragments that are read concurrently
+    ///
+    /// Each fragment being read keeps some of its batches in memory.  Raising
+    /// this helps to hide the latency of object stores when the fragments are
+    /// small, lowering it reduces the memory used by large scans.
+    ///
+    /// By default, this is the default of Lance.  This only affects LanceDB OSS.
+    pub fragment_readahead: Option<usize>,
+    /// Skip the fragments that cannot match the filter of a plain query
+    ///
+    /// This uses the statistics computed by [`crate::Table::compute_statistics`]
+    /// and has no effect on tables without statistics.  Use
+    /// [`Query::fragment_pruning`] to see how many fragments are skipped.
+    ///
+    /// By default, this is true.  This only affects LanceDB OSS.
+    pub use_fragment_statistics: bool,
 }
 
 impl Default for QueryExecutionOptions {
     fn default() -> Self {
         Self {
             max_batch_length: 1024,
+            decode_parallelism: None,
+            fragment_readahead: None,
+            use_fragment_statistics: true,
         }
     }
 }
@@ -453,6 +830,26 @@ pub trait ExecutableQuery {
         &self,
         options: QueryExecutionOptions,
     ) -> impl Future<Output = Result<SendableRecordBatchStream>> + Send;
+
+    /// Execute the query and return the results as a standard Arrow reader
+    ///
+    /// This is useful to pass the results to libraries that expect an
+    /// [`arrow_array::RecordBatchReader`], such as the parquet writer or the
+    /// Arrow C stream interface.  The query runs in the background and the
+    /// reader blocks until the next batch is available, so the reader must be
+    /// read from a thread that is not running async code (e.g. with
+    /// `tokio::task::spawn_blocking`).
+    ///
+    /// Errors that happen while the query runs are returned by the reader as
+    /// [`arrow_schema::ArrowError::ExternalError`].
+    fn execute_into_reader(
+        &self,
+    ) -> impl Future<Output = Result<Box<dyn arrow_array::RecordBatchReader + Send>>> + Send
+    where
+        Self: Sync,
+    {
+        async move { Ok(stream_into_reader(self.execute().await?)) }
+    }
 }
 
 /// A builder for LanceDB queries.
@@ -477,6 +874,31 @@ pub struct Query {
     pub(crate) filter: Option<String>,
     /// Select column projection.
     pub(crate) select: Select,
+    /// Only keep the first row for each value of these columns.
+    pub(crate) distinct_on: Option<Vec<String>>,
+    /// Only return the rows that were valid at this time.
+    pub(crate) as_of: Option<DateTime<Utc>>,
+    /// Read the version of the table that was current at this time.
+    pub(crate) version_at: Option<DateTime<Utc>>,
+    /// Read these columns after the results are selected.
+    pub(crate) late_materialization: Option<Vec<String>>,
+    /// Return references to the values of these columns instead of the values.
+    pub(crate) lazy_blobs: Option<Vec<String>>,
+    /// Include the `_rowid` column in the results.
+    pub(crate) with_row_id: bool,
+    /// Whether vector columns are returned when all columns are selected, None
+    /// for the default of the table.
+    pub(crate) include_vectors: Option<bool>,
+    /// Include the soft deleted rows.
+    pub(crate) include_deleted: bool,
+    /// Sort the results by these columns, in order of precedence.
+    pub(crate) order_by: Vec<(String, SortOrder)>,
+    /// Read this version of the table, set by resumable scans.
+    pub(crate) version: Option<u64>,
+    /// Only read these fragments, set by resumable scans.
+    pub(crate) fragment_ids: Option<Vec<u64>>,
+    /// Skip this many rows, set by resumable scans.
+    pub(crate) offset: Option<usize>,
 }
 
 impl Query {
@@ -486,9 +908,65 @@ impl Query {
             limit: None,
             filter: None,
             select: Select::All,
+            distinct_on: None,
+            as_of: None,
+            version_at: None,
+            late_materialization: None,
+            lazy_blobs: None,
+            with_row_id: false,
+            include_vectors: None,
+            include_deleted: false,
+            order_by: Vec::new(),
+            version: None,
+            fragment_ids: None,
+            offset: None,
         }
     }
 
+    /// Sort the results by a column.
+    ///
+    /// Calling this more than once sorts by each column in turn, the first
+    /// column takes precedence.  Null values come last in either order.  The
+    /// limit (see [`QueryBase::limit`]) is applied after sorting, so this can
+    /// be used to find, for example, the ten newest rows that match a filter.
+    ///
+    /// Only top level columns of primitive or string types can be sorted by,
+    /// and ordering can not be combined with a vector search or
+    /// [`QueryBase::distinct_on`].  See [`order`] for how the order is pushed
+    /// down to the scan.
+    pub fn order_by(mut self, column: impl Into<String>, order: SortOrder) -> Self {
+        self.order_by.push((column.into(), order));
+        self
+    }
+
+    /// Run the query as a scan that can be resumed after a failure
+    ///
+    /// Each batch of the returned stream comes with a [`ScanCheckpoint`].
+    /// Passing the checkpoint of the last batch that was handled, or the
+    /// token made from it with [`ScanCheckpoint::to_token`], continues the
+    /// scan with the next row, without reading the fragments that were
+    /// already completed.  Pass `None` to start a new scan.
+    ///
+    /// See [`resumable`] for how the scan is made and what it supports.
+    pub async fn execute_resumable(
+        &self,
+        checkpoint: Option<ScanCheckpoint>,
+    ) -> Result<ResumableStream> {
+        self.execute_resumable_with_options(checkpoint, QueryExecutionOptions::default())
+            .await
+    }
+
+    /// Run the query as a resumable scan with the given options
+    ///
+    /// See [`Query::execute_resumable`]
+    pub async fn execute_resumable_with_options(
+        &self,
+        checkpoint: Option<ScanCheckpoint>,
+        options: QueryExecutionOptions,
+    ) -> Result<ResumableStream> {
+        resumable::execute_resumable(self.parent.clone(), self, checkpoint, options).await
+    }
+
     /// Helper method to convert the query to a VectorQuery with a `query_vector`
     /// of None.  This retrofits to some existing inner paths that work with a
     /// single query object for both vector and plain queries.
@@ -496,6 +974,37 @@ impl Query {
         VectorQuery::new(self)
     }
 
+    /// Estimate the cost of running this query, without running it
+    ///
+    /// A filter is assumed to read every row of the columns it references.
+    /// See [`crate::table::estimate`] for how the estimate is made.
+    pub async fn estimate(&self) -> Result<CostEstimate> {
+        let stats = self.parent.statistics().await?;
+        Ok(estimate_query(&stats, self))
+    }
+
+    /// How many fragments this query skips because of the fragment statistics
+    ///
+    /// Fragments are only skipped if statistics were computed with
+    /// [`crate::Table::compute_statistics`].  See
+    /// [`crate::table::fragment_stats`] for which filters can skip fragments.
+    pub async fn fragment_pruning(&self) -> Result<FragmentPruning> {
+        self.parent.fragment_pruning(self).await
+    }
+
+    /// Check the filter of the query against the schema of the table
+    ///
+    /// Returns every problem that was found, without running the query.  The
+    /// result is empty if there is no filter or no problem was found.  See
+    /// [`diagnostics`] for what is checked.
+    pub async fn validate(&self) -> Result<Vec<FilterDiagnostic>> {
+        let Some(filter) = &self.filter else {
+            return Ok(Vec::new());
+        };
+        let schema = self.parent.schema().await?;
+        Ok(diagnose_filter(&schema, filter))
+    }
+
     /// Find the nearest vectors to the given query vector.
     ///
     /// This converts the query from a plain query to a vector query.
@@ -536,6 +1045,29 @@ impl Query {
         vector_query.query_vector = Some(query_vector);
         Ok(vector_query)
     }
+
+    /// Group the results by the given columns and aggregate each group
+    ///
+    /// See [`VectorQuery::group_by`]
+    pub fn group_by(self, columns: &[impl AsRef<str>]) -> GroupBy<Self> {
+        GroupBy::new(
+            self,
+            columns.iter().map(|c| c.as_ref().to_string()).collect(),
+        )
+    }
+
+    /// Attach the rows of another table with the same values in the given
+    /// columns
+    ///
+    /// See [`VectorQuery::join`]
+    pub fn join(self, right: &Table, on: &[impl AsRef<str>], join_type: JoinType) -> Join<Self> {
+        Join::new(
+            self,
+            right.clone(),
+            on.iter().map(|c| c.as_ref().to_string()).collect(),
+            join_type,
+        )
+    }
 }
 
 impl HasQuery for Query {
@@ -545,12 +1077,23 @@ impl HasQuery for Query {
 }
 
 impl ExecutableQuery for Query {
+    #[tracing::instrument(
+        name = "lancedb.query",
+        level = "debug",
+        skip_all,
+        fields(table = self.parent.name(), kind = "plain")
+    )]
     async fn execute_with_options(
         &self,
         options: QueryExecutionOptions,
     ) -> Result<SendableRecordBatchStream> {
-        Ok(SendableRecordBatchStream::from(
-            self.parent.clone().plain_query(self, options).await?,
+        let start = Instant::now();
+        let stream = self.parent.clone().plain_query(self, options).await?;
+        Ok(instrument_query_stream(
+            stream,
+            self.parent.name(),
+            "plain",
+            start,
         ))
     }
 }
@@ -574,11 +1117,21 @@ pub struct VectorQuery {
     pub(crate) query_vector: Option<Arc<dyn Array>>,
     pub(crate) nprobes: usize,
     pub(crate) refine_factor: Option<u32>,
+    /// Set if nprobes was given, which overrides the tuned default of the table
+    pub(crate) nprobes_set: bool,
+    /// Set if refine_factor was given, which overrides the tuned default of the table
+    pub(crate) refine_factor_set: bool,
     pub(crate) distance_type: Option<DistanceType>,
     /// Default is true. Set to false to enforce a brute force search.
     pub(crate) use_index: bool,
     /// Apply filter before ANN search/
     pub(crate) prefilter: bool,
+    /// Recompute the distances of the final results from the original vectors
+    pub(crate) rescore: bool,
+    /// Add a score column calculated from the distance
+    pub(crate) score_transform: Option<ScoreTransform>,
+    /// Add a column with the distance to each of these vectors
+    pub(crate) reference_vectors: Vec<(String, Arc<dyn Array>)>,
 }
 
 impl VectorQuery {
@@ -589,9 +1142,14 @@ impl VectorQuery {
             query_vector: None,
             nprobes: 20,
             refine_factor: None,
+            nprobes_set: false,
+            refine_factor_set: false,
             distance_type: None,
             use_index: true,
             prefilter: true,
+            rescore: false,
+            score_transform: None,
+            reference_vectors: Vec::new(),
         }
     }
 
@@ -626,9 +1184,11 @@ impl VectorQuery {
     ///
     /// For best results we recommend tuning this parameter with a benchmark against
     /// your actual data to find the smallest possible value that will still give
-    /// you the desired recall.
+    /// you the desired recall.  [`crate::Table::tune_search`] can run this
+    /// benchmark and store the result as the default of the table.
     pub fn nprobes(mut self, nprobes: usize) -> Self {
         self.nprobes = nprobes;
+        self.nprobes_set = true;
         self
     }
 
@@ -661,6 +1221,7 @@ impl VectorQuery {
     /// distance between the query vector and the actual uncompressed vector.
     pub fn refine_factor(mut self, refine_factor: u32) -> Self {
         self.refine_factor = Some(refine_factor);
+        self.refine_factor_set = true;
         self
     }
 
@@ -673,9 +1234,13 @@ impl VectorQuery {
     ///
     /// Note: if there is a vector index then the distance type used MUST match the distance
     /// type used to train the vector index.  If this is not done then the results will be
-    /// invalid.
+    /// invalid.  The distance type of indices created by LanceDB is recorded and a search
+    /// with a different distance type fails, unless it bypasses the index (see
+    /// [`Self::bypass_vector_index`]).
     ///
-    /// By default [`DistanceType::L2`] is used.
+    /// By default the distance type of the index on the column is used, or
+    /// [`DistanceType::L2`] if there is no index (or the index was created before its
+    /// distance type was recorded).
     pub fn distance_type(mut self, distance_type: DistanceType) -> Self {
         self.distance_type = Some(distance_type);
         self
@@ -704,6 +1269,203 @@ impl VectorQuery {
         self
     }
 
+    /// If this is called then the final results are re-scored using the original vectors
+    ///
+    /// This is different from [`Self::refine_factor`].  The refine step fetches extra
+    /// candidates and reorders them inside the search.  Re-scoring happens after the
+    /// search has completed and only touches the final `limit` results.  The full
+    /// precision vector of each result is compared to the query vector and the results
+    /// are reordered by this exact distance.
+    ///
+    /// The results will contain two distance columns.  The `_distance` column will
+    /// contain the exact distance and the `_approx_distance` column will contain the
+    /// distance that was reported by the search (which, with an IVF PQ index, is based
+    /// on the quantized vectors).  Comparing the two can help to decide whether a more
+    /// expensive refine step is worthwhile.
+    ///
+    /// This requires reading the vector column for every result, even if the vector
+    /// column is not part of the selected columns.  The vector column is only included
+    /// in the output if it was selected.
+    pub fn rescore_exact(mut self) -> Self {
+        self.rescore = true;
+        self
+    }
+
+    /// Estimate the cost of running this search, without running it
+    ///
+    /// The estimate takes the index on the vector column (if any), `nprobes`
+    /// and the refine factor into account.  See [`crate::table::estimate`] for
+    /// how the estimate is made.
+    pub async fn estimate(&self) -> Result<CostEstimate> {
+        let stats = self.base.parent.statistics().await?;
+        estimate_vector_query(&stats, self)
+    }
+
+    /// Check the filter of the search against the schema of the table
+    ///
+    /// See [`Query::validate`].
+    pub async fn validate(&self) -> Result<Vec<FilterDiagnostic>> {
+        self.base.validate().await
+    }
+
+    /// Add a column with the distance from each result to a reference vector
+    ///
+    /// The distance is calculated with the distance type of the search (see
+    /// [`Self::distance_type`]) from the full precision vectors.  The reference
+    /// vectors do not change which results are returned, or their order.  This
+    /// can be called more than once, for example, to calculate features for a
+    /// reranker or to measure the diversity of the results.
+    ///
+    /// The vector column is read for every result, even if it is not selected.
+    /// It is only returned if it was selected.
+    ///
+    /// ```no_run
+    /// # use lancedb::query::ExecutableQuery;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("tbl").execute().await.unwrap();
+    /// let results = tbl
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .distance_to("_distance_to_centroid", &[0.5, 0.5, 0.5])
+    ///     .unwrap()
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub fn distance_to(mut self, name: &str, vector: impl IntoQueryVector) -> Result<Self> {
+        let vector = vector.to_query_vector(&DataType::Float32, "default")?;
+        self.reference_vectors.push((name.to_string(), vector));
+        Ok(self)
+    }
+
+    /// Add a `_score` column calculated from the `_distance` of each result
+    ///
+    /// The transform is applied by the database so that every client uses the
+    /// same conventions, for example, to convert distances into similarities.
+    /// See [`ScoreTransform`] for the available steps.  If [`Self::rescore_exact`]
+    /// is set then the score is calculated from the exact distance.
+    ///
+    /// ```no_run
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # use lancedb::query::score::ScoreTransform;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("tbl").execute().await.unwrap();
+    /// let results = tbl
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .distance_type(lancedb::DistanceType::Cosine)
+    ///     .score_transform(ScoreTransform::new().similarity().clamp(0.0, 1.0))
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub fn score_transform(mut self, transform: ScoreTransform) -> Self {
+        self.score_transform = Some(transform);
+        self
+    }
+
+    /// Add a normalized `_score` column calculated from the `_distance` of each result
+    ///
+    /// The score is larger for better results, whatever the distance type, so
+    /// it can be used to fuse the results of several searches or to apply a
+    /// threshold.  This is a shorthand for [`Self::score_transform`] and
+    /// replaces any transform set earlier.
+    ///
+    /// ```no_run
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # use lancedb::query::score::ScoreNorm;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("tbl").execute().await.unwrap();
+    /// let results = tbl
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .score_normalization(ScoreNorm::MinMax)
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub fn score_normalization(self, norm: ScoreNorm) -> Self {
+        self.score_transform(norm.into())
+    }
+
+    /// Group the results by the given columns and aggregate each group
+    ///
+    /// The aggregation is applied by the database to the results of the search.
+    /// For example, if documents are split into chunks, this finds the documents
+    /// whose chunks are closest to the query vector:
+    ///
+    /// ```no_run
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # use lancedb::query::aggregate::{count, min};
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("chunks").execute().await.unwrap();
+    /// let documents = tbl
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .limit(100)
+    ///     .group_by(&["doc_id"])
+    ///     .agg([min("_distance").alias("score"), count("_distance")])
+    ///     .limit(10)
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    ///
+    /// The limit of the search controls how many rows are grouped and
+    /// [`GroupBy::limit`] controls how many groups are returned.  The groups
+    /// are ordered by their nearest row.
+    pub fn group_by(self, columns: &[impl AsRef<str>]) -> GroupBy<Self> {
+        GroupBy::new(
+            self,
+            columns.iter().map(|c| c.as_ref().to_string()).collect(),
+        )
+    }
+
+    /// Attach the rows of another table with the same values in the given
+    /// columns
+    ///
+    /// This is useful when metadata is stored in its own table, keyed by a
+    /// column of the searched table.
+    ///
+    /// ```
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # use lancedb::query::join::JoinType;
+    /// # async fn search(chunks: lancedb::Table, documents: lancedb::Table) {
+    /// let results = chunks
+    ///     .query()
+    ///     .nearest_to(&[1.0, 2.0])
+    ///     .unwrap()
+    ///     .join(&documents, &["doc_id"], JoinType::Left)
+    ///     .right_columns(&["title", "author"])
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// The search runs first and the results keep its order.  See
+    /// [`join`](crate::query::join) for how the other table is read.
+    pub fn join(self, right: &Table, on: &[impl AsRef<str>], join_type: JoinType) -> Join<Self> {
+        Join::new(
+            self,
+            right.clone(),
+            on.iter().map(|c| c.as_ref().to_string()).collect(),
+            join_type,
+        )
+    }
+
     /// If this is called then any vector index is skipped
     ///
     /// An exhaustive (flat) search will be performed.  The query vector will
@@ -718,12 +1480,23 @@ impl VectorQuery {
 }
 
 impl ExecutableQuery for VectorQuery {
+    #[tracing::instrument(
+        name = "lancedb.query",
+        level = "debug",
+        skip_all,
+        fields(table = self.base.parent.name(), kind = "vector")
+    )]
     async fn execute_with_options(
         &self,
         options: QueryExecutionOptions,
     ) -> Result<SendableRecordBatchStream> {
-        Ok(SendableRecordBatchStream::from(
-            self.base.parent.clone().vector_query(self, options).await?,
+        let start = Instant::now();
+        let stream = self.base.parent.clone().vector_query(self, options).await?;
+        Ok(instrument_query_stream(
+            stream,
+            self.base.parent.name(),
+            "vector",
+            start,
         ))
     }
 }
@@ -739,9 +1512,11 @@ mod tests {
     use std::sync::Arc;
 
     use super::*;
+    use arrow::compute::concat_batches;
     use arrow_array::{
-        cast::AsArray, Float32Array, Int32Array, RecordBatch, RecordBatchIterator,
-        RecordBatchReader,
+        cast::AsArray,
+        types::{Float32Type, Int32Type},
+        Float32Array, Int32Array, RecordBatch, RecordBatchIterator, RecordBatchReader, StringArray,
     };
     use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
     use futures::{StreamExt, TryStreamExt};
@@ -881,58 +1656,723 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_split_alias() {
+        let split = |expression: &str| {
+            let (name, expression) = split_alias(expression);
+            (name.to_string(), expression.to_string())
+        };
+        assert_eq!(
+            split("metadata.title AS title"),
+            ("title".to_string(), "metadata.title".to_string())
+        );
+        assert_eq!(
+            split(" lower(name)  as `lower name` "),
+            ("lower name".to_string(), "lower(name)".to_string())
+        );
+        assert_eq!(
+            split("CAST(id AS VARCHAR)"),
+            (
+                "CAST(id AS VARCHAR)".to_string(),
+                "CAST(id AS VARCHAR)".to_string()
+            )
+        );
+        assert_eq!(
+            split("name = 'x as y'"),
+            ("name = 'x as y'".to_string(), "name = 'x as y'".to_string())
+        );
+        assert_eq!(split("id"), ("id".to_string(), "id".to_string()));
+    }
+
     #[tokio::test]
-    async fn test_execute_no_vector() {
-        // TODO: Switch back to memory://foo after https://github.com/lancedb/lancedb/issues/1051
-        // is fixed
+    async fn test_select_expressions_and_exclusions() {
         let tmp_dir = tempdir().unwrap();
         let dataset_path = tmp_dir.path().join("test.lance");
         let uri = dataset_path.to_str().unwrap();
 
-        // test that it's ok to not specify a query vector (just filter / limit)
-        let batches = make_non_empty_batches();
         let conn = connect(uri).execute().await.unwrap();
         let table = conn
-            .create_table("my_table", Box::new(batches))
+            .create_table("my_table", Box::new(make_non_empty_batches()))
             .execute()
             .await
             .unwrap();
 
-        let query = table.query();
-        let result = query.only_if("id % 2 == 0").execute().await;
-        let mut stream = result.expect("should have result");
-        // should only have one batch
-        while let Some(batch) = stream.next().await {
-            let b = batch.expect("should be Ok");
-            // cast arr into Int32Array
-            let arr: &Int32Array = b["id"].as_primitive();
-            assert!(arr.iter().all(|x| x.unwrap() % 2 == 0));
-        }
+        let batches = table
+            .query()
+            .limit(10)
+            .select(Select::expressions(&[
+                "id * 2 AS doubled",
+                "abs(id) AS `abs id`",
+            ]))
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let schema = batches[0].schema();
+        let names = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["doubled", "abs id"]);
+
+        let batches = table
+            .query()
+            .limit(10)
+            .select_all_except(&["vector"])
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].num_columns(), 1);
+        assert!(batches[0].column_by_name("id").is_some());
 
-        // Reject bad filter
-        let result = table.query().only_if("id = 0 AND").execute().await;
-        assert!(result.is_err());
-    }
+        // A vector search can exclude the vector column it searches
+        let batches = table
+            .query()
+            .select_all_except(&["vector"])
+            .nearest_to(&[0.0; 4])
+            .unwrap()
+            .limit(3)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(batches[0].column_by_name("vector").is_none());
+        assert!(batches[0].column_by_name("id").is_some());
 
-    fn make_non_empty_batches() -> impl RecordBatchReader + Send + 'static {
-        let vec = Box::new(RandomVector::new().named("vector".to_string()));
-        let id = Box::new(IncrementingInt32::new().named("id".to_string()));
-        BatchGenerator::new().col(vec).col(id).batch(512)
+        let result = table.query().select_all_except(&["nope"]).execute().await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
     }
 
-    fn make_test_batches() -> impl RecordBatchReader + Send + 'static {
-        let dim: usize = 128;
-        let schema = Arc::new(ArrowSchema::new(vec![
-            ArrowField::new("key", DataType::Int32, false),
-            ArrowField::new(
-                "vector",
-                DataType::FixedSizeList(
-                    Arc::new(ArrowField::new("item", DataType::Float32, true)),
-                    dim as i32,
-                ),
-                true,
-            ),
-            ArrowField::new("uri", DataType::Utf8, true),
+    #[tokio::test]
+    async fn test_exclude_vectors() {
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+
+        let column_names = |query: Query| async move {
+            let batches = query
+                .execute()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            batches[0]
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().clone())
+                .collect::<Vec<_>>()
+        };
+
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", Box::new(make_non_empty_batches()))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(column_names(table.query()).await, vec!["vector", "id"]);
+        assert_eq!(
+            column_names(table.query().without_vectors()).await,
+            vec!["id"]
+        );
+        // An explicit selection is kept
+        assert_eq!(
+            column_names(
+                table
+                    .query()
+                    .select(Select::columns(&["vector"]))
+                    .without_vectors()
+            )
+            .await,
+            vec!["vector"]
+        );
+
+        let conn = connect(uri).exclude_vectors(true).execute().await.unwrap();
+        let table = conn.open_table("my_table").execute().await.unwrap();
+        assert_eq!(column_names(table.query()).await, vec!["id"]);
+        assert_eq!(
+            column_names(table.query().with_vectors()).await,
+            vec!["vector", "id"]
+        );
+        let batches = table
+            .query()
+            .nearest_to(&[0.0; 4])
+            .unwrap()
+            .limit(3)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(batches[0].column_by_name("vector").is_none());
+        assert_eq!(batches[0].num_rows(), 3);
+
+        let table = conn
+            .open_table("my_table")
+            .exclude_vectors(false)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(column_names(table.query()).await, vec!["vector", "id"]);
+    }
+
+    #[tokio::test]
+    async fn test_rescore_exact() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+
+        let batches = table
+            .query()
+            .limit(10)
+            .select(Select::columns(&["id"]))
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .rescore_exact()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 10);
+        // The vector column is fetched for rescoring but not returned
+        assert!(batch.column_by_name("vector").is_none());
+        assert!(batch.column_by_name("_approx_distance").is_some());
+
+        let exact = batch["_distance"].as_primitive::<Float32Type>().values();
+        assert!(exact.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_distinct_on() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        // 8 documents with 8 chunks each, the chunks of a document are neighbors
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("doc", DataType::Int32, false),
+            ArrowField::new(
+                "vector",
+                DataType::FixedSizeList(
+                    Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                    4,
+                ),
+                true,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..64)),
+                Arc::new(Int32Array::from_iter_values((0..64).map(|i| i / 8))),
+                Arc::new(arrow_array::FixedSizeListArray::from_iter_primitive::<
+                    Float32Type,
+                    _,
+                    _,
+                >(
+                    (0..64).map(|i| Some(vec![Some(i as f32); 4])), 4
+                )),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table(
+                "chunks",
+                RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        let batches = table
+            .query()
+            .nearest_to(&[0.0; 4])
+            .unwrap()
+            .distinct_on(&["doc"])
+            .select(Select::columns(&["id"]))
+            .limit(3)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        // The distinct column was not selected so it is not returned
+        assert!(batch.column_by_name("doc").is_none());
+        let ids = batch["id"].as_primitive::<arrow_array::types::Int32Type>();
+        assert_eq!(ids.values().to_vec(), vec![0, 8, 16]);
+
+        let batches = table
+            .query()
+            .distinct_on(&["doc"])
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_group_by() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+
+        let batches = table
+            .query()
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .limit(50)
+            .select(Select::columns(&["vector"]))
+            .group_by(&["id"])
+            .agg([
+                aggregate::min("_distance").alias("score"),
+                aggregate::count("_distance"),
+            ])
+            .limit(5)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 5);
+        assert_eq!(batch.num_columns(), 3);
+        // The ids are unique so each group has one row
+        let counts = batch["count(_distance)"].as_primitive::<arrow_array::types::Int64Type>();
+        assert!(counts.values().iter().all(|c| *c == 1));
+        let scores = batch["score"].as_primitive::<arrow_array::types::Float64Type>();
+        assert!(scores.values().windows(2).all(|w| w[0] <= w[1]));
+
+        let plain = table
+            .query()
+            .group_by(&["id"])
+            .agg([aggregate::count("id")])
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(plain[0].num_rows(), 512);
+    }
+
+    #[tokio::test]
+    async fn test_join() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+
+        // Only the even ids have a document
+        let ids = (0..512).step_by(2).collect::<Vec<i32>>();
+        let titles = ids
+            .iter()
+            .map(|id| format!("doc {}", id))
+            .collect::<Vec<_>>();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("title", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(StringArray::from(titles)),
+            ],
+        )
+        .unwrap();
+        let conn = connect(tmp_dir.path().join("test.lance").to_str().unwrap())
+            .execute()
+            .await
+            .unwrap();
+        let documents = conn
+            .create_table(
+                "documents",
+                Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema)),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        let batches = table
+            .query()
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .limit(10)
+            .select(Select::columns(&["vector"]))
+            .join(&documents, &["id"], JoinType::Left)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 10);
+        // The key is only selected to join
+        assert!(batch.column_by_name("id").is_none());
+        let distances = batch["_distance"].as_primitive::<Float32Type>();
+        assert!(distances.values().windows(2).all(|w| w[0] <= w[1]));
+
+        let batches = table
+            .query()
+            .join(&documents, &["id"], JoinType::Inner)
+            .right_columns(&["title"])
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 256);
+        let ids = batch["id"].as_primitive::<Int32Type>();
+        let titles = batch["title"].as_string::<i32>();
+        for (id, title) in ids.values().iter().zip(titles.iter()) {
+            assert_eq!(title.unwrap(), format!("doc {}", id));
+        }
+
+        let result = table
+            .query()
+            .join(&documents, &["nope"], JoinType::Inner)
+            .execute()
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_score_transform() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+
+        let batches = table
+            .query()
+            .limit(10)
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .score_transform(ScoreTransform::new().similarity().temperature(1.0))
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 10);
+
+        let distances = batch["_distance"].as_primitive::<Float32Type>().values();
+        let scores = batch["_score"].as_primitive::<Float32Type>().values();
+        // Closer results have higher scores and the softmax sums to one
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+        assert!((scores.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(distances.windows(2).all(|w| w[0] <= w[1]));
+
+        let batches = table
+            .query()
+            .limit(10)
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .score_normalization(ScoreNorm::MinMax)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        let scores = batch["_score"].as_primitive::<Float32Type>().values();
+        assert_eq!(scores[0], 1.0);
+        assert_eq!(scores[scores.len() - 1], 0.0);
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+
+        let invalid = table
+            .query()
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .score_transform(ScoreTransform::new().clamp(1.0, 0.0))
+            .execute()
+            .await;
+        assert!(matches!(invalid, Err(Error::InvalidInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_distance_to() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+
+        let batches = table
+            .query()
+            .limit(10)
+            .select(Select::columns(&["id"]))
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .distance_to("_to_query", &[0.1; 4])
+            .unwrap()
+            .distance_to("_to_origin", &[0.0; 4])
+            .unwrap()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 10);
+        assert!(batch.column_by_name("vector").is_none());
+        assert!(batch.column_by_name("_to_origin").is_some());
+
+        // The reference vectors do not change the ranking
+        let distances = batch["_distance"].as_primitive::<Float32Type>().values();
+        let to_query = batch["_to_query"].as_primitive::<Float32Type>().values();
+        assert!(distances.windows(2).all(|w| w[0] <= w[1]));
+        for (distance, to_query) in distances.iter().zip(to_query.iter()) {
+            assert!((distance - to_query).abs() < 1e-4);
+        }
+
+        let invalid = table
+            .query()
+            .nearest_to(&[0.1; 4])
+            .unwrap()
+            .distance_to("_to_other", &[0.1; 3])
+            .unwrap()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(matches!(invalid, Err(Error::InvalidInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_filter_cache() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let conn = connect(dataset_path.to_str().unwrap())
+            .filter_cache(filter_cache::FilterCacheConfig {
+                max_rows: 100,
+                ..Default::default()
+            })
+            .execute()
+            .await
+            .unwrap();
+        let cached = conn.open_table("my_table").execute().await.unwrap();
+        assert_eq!(table.filter_cache_metrics().unwrap(), None);
+
+        let ids = |table: &Table, filter: &'static str| {
+            let search = table.query().only_if(filter).nearest_to(&[0.1; 4]).unwrap();
+            async move {
+                let batches = search
+                    .execute()
+                    .await
+                    .unwrap()
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap();
+                batches
+                    .iter()
+                    .flat_map(|b| b["id"].as_primitive::<Int32Type>().values().to_vec())
+                    .collect::<Vec<_>>()
+            }
+        };
+        let expected = ids(&table, "id < 50").await;
+        assert_eq!(expected.len(), DEFAULT_TOP_K);
+        assert_eq!(ids(&cached, "id < 50").await, expected);
+        assert_eq!(ids(&cached, "id < 50").await, expected);
+        let metrics = cached.filter_cache_metrics().unwrap().unwrap();
+        assert_eq!((metrics.hits, metrics.misses), (1, 1));
+
+        // Too many rows for the cache, searched with the index as usual
+        assert_eq!(ids(&cached, "id >= 0").await, ids(&table, "id >= 0").await);
+        assert_eq!(cached.filter_cache_metrics().unwrap().unwrap().entries, 2);
+
+        // A new version invalidates the cached rows
+        cached
+            .add(make_non_empty_batches())
+            .execute()
+            .await
+            .unwrap();
+        ids(&cached, "id < 50").await;
+        let metrics = cached.filter_cache_metrics().unwrap().unwrap();
+        assert_eq!(metrics.invalidations, 2);
+        assert_eq!(metrics.entries, 1);
+    }
+
+    #[tokio::test]
+    async fn test_late_materialization() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+
+        let search = || {
+            table
+                .query()
+                .limit(10)
+                .only_if("id % 2 = 0")
+                .select(Select::columns(&["vector", "id"]))
+                .nearest_to(&[0.1; 4])
+                .unwrap()
+        };
+        let collect = |stream: SendableRecordBatchStream| async move {
+            let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+            concat_batches(&batches[0].schema(), &batches).unwrap()
+        };
+        let expected = collect(search().execute().await.unwrap()).await;
+        let late = collect(
+            search()
+                .late_materialization(&["vector"])
+                .execute()
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(late, expected);
+        assert_eq!(
+            late.schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().as_str())
+                .collect::<Vec<_>>(),
+            vec!["vector", "id", "_distance"]
+        );
+
+        let plain = collect(
+            table
+                .query()
+                .limit(5)
+                .late_materialization(&["vector"])
+                .execute()
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(plain.num_rows(), 5);
+        assert!(plain.column_by_name("_rowid").is_none());
+        assert_eq!(
+            plain.schema().fields(),
+            table.schema().await.unwrap().fields()
+        );
+
+        let invalid = table
+            .query()
+            .late_materialization(&["nope"])
+            .execute()
+            .await;
+        assert!(matches!(invalid, Err(Error::InvalidInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_execute_no_vector() {
+        // TODO: Switch back to memory://foo after https://github.com/lancedb/lancedb/issues/1051
+        // is fixed
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+
+        // test that it's ok to not specify a query vector (just filter / limit)
+        let batches = make_non_empty_batches();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", Box::new(batches))
+            .execute()
+            .await
+            .unwrap();
+
+        let query = table.query();
+        let result = query.only_if("id % 2 == 0").execute().await;
+        let mut stream = result.expect("should have result");
+        // should only have one batch
+        while let Some(batch) = stream.next().await {
+            let b = batch.expect("should be Ok");
+            // cast arr into Int32Array
+            let arr: &Int32Array = b["id"].as_primitive();
+            assert!(arr.iter().all(|x| x.unwrap() % 2 == 0));
+        }
+
+        // Reject bad filter
+        let result = table.query().only_if("id = 0 AND").execute().await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", Box::new(make_non_empty_batches()))
+            .execute()
+            .await
+            .unwrap();
+
+        assert!(table.query().validate().await.unwrap().is_empty());
+        assert!(table
+            .query()
+            .only_if("id % 2 == 0")
+            .validate()
+            .await
+            .unwrap()
+            .is_empty());
+
+        let diagnostics = table
+            .query()
+            .only_if("idd > 5 AND id = 'five'")
+            .nearest_to(&[0.0; 4])
+            .unwrap()
+            .validate()
+            .await
+            .unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![
+                FilterDiagnostic::UnknownColumn {
+                    column: "idd".to_string(),
+                    suggestions: vec!["id".to_string()],
+                },
+                FilterDiagnostic::TypeMismatch {
+                    column: "id".to_string(),
+                    data_type: DataType::Int32,
+                    expression: "the string 'five'".to_string(),
+                },
+            ]
+        );
+    }
+
+    fn make_non_empty_batches() -> impl RecordBatchReader + Send + 'static {
+        let vec = Box::new(RandomVector::new().named("vector".to_string()));
+        let id = Box::new(IncrementingInt32::new().named("id".to_string()));
+        BatchGenerator::new().col(vec).col(id).batch(512)
+    }
+
+    fn make_test_batches() -> impl RecordBatchReader + Send + 'static {
+        let dim: usize = 128;
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("key", DataType::Int32, false),
+            ArrowField::new(
+                "vector",
+                DataType::FixedSizeList(
+                    Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                    dim as i32,
+                ),
+                true,
+            ),
+            ArrowField::new("uri", DataType::Utf8, true),
         ]));
         RecordBatchIterator::new(
             vec![RecordBatch::new_empty(schema.clone())]
@@ -963,6 +2403,7 @@ mod tests {
             .query()
             .execute_with_options(QueryExecutionOptions {
                 max_batch_length: 10,
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -972,6 +2413,117 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_execute_into_reader() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+        let expected = table.count_rows(None).await.unwrap();
+
+        let reader = table.query().execute_into_reader().await.unwrap();
+        let schema = reader.schema();
+        let batches = tokio::task::spawn_blocking(move || {
+            reader.collect::<std::result::Result<Vec<_>, _>>().unwrap()
+        })
+        .await
+        .unwrap();
+        assert!(batches.iter().all(|b| b.schema() == schema));
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).sum::<usize>(),
+            expected
+        );
+
+        // Dropping the reader early stops the query
+        let mut reader = table.query().limit(1).execute_into_reader().await.unwrap();
+        let batch = tokio::task::spawn_blocking(move || reader.next())
+            .await
+            .unwrap();
+        assert_eq!(batch.unwrap().unwrap().num_rows(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_decode_parallelism() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+        let expected = table.count_rows(None).await.unwrap();
+
+        let results = table
+            .query()
+            .execute_with_options(QueryExecutionOptions {
+                decode_parallelism: Some(1),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            results.iter().map(|b| b.num_rows()).sum::<usize>(),
+            expected
+        );
+
+        let invalid = table
+            .query()
+            .execute_with_options(QueryExecutionOptions {
+                decode_parallelism: Some(0),
+                ..Default::default()
+            })
+            .await;
+        assert!(invalid.is_err());
+
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+        assert!(connect(uri).decode_parallelism(0).execute().await.is_err());
+        let conn = connect(uri).decode_parallelism(2).execute().await.unwrap();
+        let table = conn.open_table("my_table").execute().await.unwrap();
+        let results = table
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            results.iter().map(|b| b.num_rows()).sum::<usize>(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_tuning() {
+        let tmp_dir = tempdir().unwrap();
+        let table = make_test_table(&tmp_dir).await;
+        let expected = table.count_rows(None).await.unwrap();
+
+        let results = table
+            .query()
+            .execute_with_options(QueryExecutionOptions {
+                max_batch_length: 7,
+                fragment_readahead: Some(1),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(results.iter().all(|b| b.num_rows() <= 7));
+        assert_eq!(
+            results.iter().map(|b| b.num_rows()).sum::<usize>(),
+            expected
+        );
+
+        let invalid = table
+            .query()
+            .execute_with_options(QueryExecutionOptions {
+                fragment_readahead: Some(0),
+                ..Default::default()
+            })
+            .await;
+        assert!(invalid.is_err());
+    }
+
     #[tokio::test]
     async fn query_base_methods_on_vector_query() {
         // Make sure VectorQuery can be used as a QueryBase
@@ -0,0 +1,375 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Aggregation of query results, see [`super::VectorQuery::group_by`]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::compute::{concat_batches, take};
+use arrow::row::{RowConverter, SortField};
+use arrow_array::{
+    cast::AsArray, types::Float64Type, Array, ArrayRef, Float64Array, Int64Array, RecordBatch,
+    UInt32Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use futures::TryStreamExt;
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+
+use super::distinct::select_distinct_columns;
+use super::{ExecutableQuery, HasQuery, QueryExecutionOptions};
+
+/// An aggregate function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    /// The smallest value
+    Min,
+    /// The largest value
+    Max,
+    /// The sum of the values
+    Sum,
+    /// The average of the values
+    Mean,
+    /// The number of non-null values
+    Count,
+}
+
+impl AggregateFunction {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Sum => "sum",
+            Self::Mean => "mean",
+            Self::Count => "count",
+        }
+    }
+}
+
+/// An aggregate of a column, computed for each group
+///
+/// Created with [`min`], [`max`], [`sum`], [`mean`] or [`count`].  The result
+/// column is named after the function and the column, e.g. `min(_distance)`,
+/// unless an [`Self::alias`] is given.  Nulls are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregate {
+    function: AggregateFunction,
+    column: String,
+    alias: Option<String>,
+}
+
+impl Aggregate {
+    /// Create an aggregate of the given column
+    pub fn new(function: AggregateFunction, column: &str) -> Self {
+        Self {
+            function,
+            column: column.to_string(),
+            alias: None,
+        }
+    }
+
+    /// Set the name of the result column
+    pub fn alias(mut self, name: &str) -> Self {
+        self.alias = Some(name.to_string());
+        self
+    }
+
+    fn output_name(&self) -> String {
+        self.alias
+            .clone()
+            .unwrap_or_else(|| format!("{}({})", self.function.name(), self.column))
+    }
+
+    fn output_field(&self) -> Field {
+        let data_type = match self.function {
+            AggregateFunction::Count => DataType::Int64,
+            _ => DataType::Float64,
+        };
+        Field::new(self.output_name(), data_type, true)
+    }
+
+    fn compute(&self, batch: &RecordBatch, groups: &[u32], num_groups: usize) -> Result<ArrayRef> {
+        let column = batch
+            .column_by_name(&self.column)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!(
+                    "the aggregated column '{}' is not in the query results",
+                    self.column
+                ),
+            })?;
+        if self.function == AggregateFunction::Count {
+            let mut counts = vec![0_i64; num_groups];
+            for (row, group) in groups.iter().enumerate() {
+                if column.is_valid(row) {
+                    counts[*group as usize] += 1;
+                }
+            }
+            return Ok(Arc::new(Int64Array::from(counts)));
+        }
+
+        if !column.data_type().is_numeric() {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "cannot compute {} of the column '{}' with type {}",
+                    self.function.name(),
+                    self.column,
+                    column.data_type()
+                ),
+            });
+        }
+        let values = arrow_cast::cast(column, &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+        let mut results: Vec<Option<f64>> = vec![None; num_groups];
+        let mut counts = vec![0_usize; num_groups];
+        for (value, group) in values.iter().zip(groups) {
+            let Some(value) = value else { continue };
+            let group = *group as usize;
+            counts[group] += 1;
+            results[group] = Some(match (self.function, results[group]) {
+                (_, None) => value,
+                (AggregateFunction::Min, Some(current)) => current.min(value),
+                (AggregateFunction::Max, Some(current)) => current.max(value),
+                (_, Some(current)) => current + value,
+            });
+        }
+        if self.function == AggregateFunction::Mean {
+            for (result, count) in results.iter_mut().zip(counts) {
+                if let Some(result) = result {
+                    *result /= count as f64;
+                }
+            }
+        }
+        Ok(Arc::new(Float64Array::from(results)))
+    }
+}
+
+/// The smallest value of the column in each group
+pub fn min(column: &str) -> Aggregate {
+    Aggregate::new(AggregateFunction::Min, column)
+}
+
+/// The largest value of the column in each group
+pub fn max(column: &str) -> Aggregate {
+    Aggregate::new(AggregateFunction::Max, column)
+}
+
+/// The sum of the column in each group
+pub fn sum(column: &str) -> Aggregate {
+    Aggregate::new(AggregateFunction::Sum, column)
+}
+
+/// The average of the column in each group
+pub fn mean(column: &str) -> Aggregate {
+    Aggregate::new(AggregateFunction::Mean, column)
+}
+
+/// The number of non-null values of the column in each group
+pub fn count(column: &str) -> Aggregate {
+    Aggregate::new(AggregateFunction::Count, column)
+}
+
+/// Group the results of a query and aggregate each group
+///
+/// Created with [`super::Query::group_by`] or [`super::VectorQuery::group_by`].
+/// The groups are returned in the order of their first row.  For a vector
+/// search this is the order of the nearest row of each group.
+#[derive(Debug, Clone)]
+pub struct GroupBy<Q> {
+    query: Q,
+    columns: Vec<String>,
+    aggregates: Vec<Aggregate>,
+    limit: Option<usize>,
+}
+
+impl<Q: HasQuery> GroupBy<Q> {
+    pub(crate) fn new(query: Q, columns: Vec<String>) -> Self {
+        Self {
+            query,
+            columns,
+            aggregates: Vec::new(),
+            limit: None,
+        }
+    }
+
+    /// Add aggregates, computed for each group
+    pub fn agg(mut self, aggregates: impl IntoIterator<Item = Aggregate>) -> Self {
+        self.aggregates.extend(aggregates);
+        self
+    }
+
+    /// Set the maximum number of groups to return
+    ///
+    /// The limit of the query itself (e.g. the number of nearest rows) controls
+    /// how many rows are grouped.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+fn output_schema(schema: &Schema, columns: &[String], aggregates: &[Aggregate]) -> Result<Schema> {
+    let mut fields = Vec::with_capacity(columns.len() + aggregates.len());
+    for column in columns {
+        fields.push(schema.field_with_name(column)?.clone());
+    }
+    fields.extend(aggregates.iter().map(|agg| agg.output_field()));
+    Ok(Schema::new(fields))
+}
+
+/// Group the rows of the batch by the columns and compute the aggregates
+///
+/// The groups are in the order of their first row.
+fn aggregate_batch(
+    batch: &RecordBatch,
+    columns: &[String],
+    aggregates: &[Aggregate],
+    limit: Option<usize>,
+) -> Result<RecordBatch> {
+    let keys = columns
+        .iter()
+        .map(|column| {
+            batch
+                .column_by_name(column)
+                .cloned()
+                .ok_or_else(|| Error::InvalidInput {
+                    message: format!("the group column '{}' is not in the query results", column),
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let schema = Arc::new(output_schema(&batch.schema(), columns, aggregates)?);
+    let converter = RowConverter::new(
+        keys.iter()
+            .map(|key| SortField::new(key.data_type().clone()))
+            .collect(),
+    )?;
+    let rows = converter.convert_columns(&keys)?;
+    let mut group_ids = HashMap::new();
+    let mut first_rows = Vec::new();
+    let groups = rows
+        .iter()
+        .enumerate()
+        .map(|(row_idx, row)| {
+            *group_ids.entry(row.owned()).or_insert_with(|| {
+                first_rows.push(row_idx as u32);
+                (first_rows.len() - 1) as u32
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let num_groups = first_rows.len();
+    let first_rows = UInt32Array::from(first_rows);
+    let mut arrays = keys
+        .iter()
+        .map(|key| Ok(take(key.as_ref(), &first_rows, None)?))
+        .collect::<Result<Vec<_>>>()?;
+    for aggregate in aggregates {
+        arrays.push(aggregate.compute(batch, &groups, num_groups)?);
+    }
+    let result = RecordBatch::try_new(schema, arrays)?;
+    Ok(match limit {
+        Some(limit) => result.slice(0, limit.min(result.num_rows())),
+        None => result,
+    })
+}
+
+impl<Q: ExecutableQuery + HasQuery + Clone + Send + Sync> ExecutableQuery for GroupBy<Q> {
+    async fn execute_with_options(
+        &self,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        let mut query = self.query.clone();
+        let mut needed = self.columns.clone();
+        needed.extend(self.aggregates.iter().map(|agg| agg.column.clone()));
+        // The extra columns are not part of the output so they do not need to be removed
+        select_distinct_columns(&mut query.mut_query().select, &needed);
+
+        let stream = query.execute_with_options(options).await?;
+        let schema = stream.schema();
+        let batches = stream.try_collect::<Vec<_>>().await?;
+        let batch = aggregate_batch(
+            &concat_batches(&schema, &batches)?,
+            &self.columns,
+            &self.aggregates,
+            self.limit,
+        )?;
+        Ok(Box::pin(SimpleRecordBatchStream {
+            schema: batch.schema(),
+            stream: futures::stream::once(async move { Ok(batch) }),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{types::Int64Type, Float32Array, Int32Array, StringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_aggregate() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("doc", DataType::Utf8, false),
+            Field::new("_distance", DataType::Float32, true),
+            Field::new("page", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["b", "a", "b", "a", "c"])),
+                Arc::new(Float32Array::from(vec![
+                    Some(0.5),
+                    Some(1.0),
+                    Some(1.5),
+                    Some(3.0),
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])),
+            ],
+        )
+        .unwrap();
+
+        let columns = vec!["doc".to_string()];
+        let aggregates = vec![
+            min("_distance"),
+            mean("_distance").alias("avg"),
+            count("_distance"),
+            sum("page"),
+        ];
+        let result = aggregate_batch(&batch, &columns, &aggregates, None).unwrap();
+        let docs = result["doc"].as_string::<i32>();
+        assert_eq!(
+            docs.iter().flatten().collect::<Vec<_>>(),
+            vec!["b", "a", "c"]
+        );
+        let mins = result["min(_distance)"].as_primitive::<Float64Type>();
+        assert_eq!(mins.value(0), 0.5);
+        assert_eq!(mins.value(1), 1.0);
+        assert!(mins.is_null(2));
+        let means = result["avg"].as_primitive::<Float64Type>();
+        assert_eq!(means.value(1), 2.0);
+        let counts = result["count(_distance)"].as_primitive::<Int64Type>();
+        assert_eq!(counts.values().to_vec(), vec![2, 2, 0]);
+        let sums = result["sum(page)"].as_primitive::<Float64Type>();
+        assert_eq!(sums.value(0), 4.0);
+
+        let limited = aggregate_batch(&batch, &columns, &aggregates, Some(1)).unwrap();
+        assert_eq!(limited.num_rows(), 1);
+
+        assert!(aggregate_batch(&batch, &columns, &[sum("doc")], None).is_err());
+        let missing = vec!["nope".to_string()];
+        assert!(aggregate_batch(&batch, &missing, &[], None).is_err());
+    }
+}
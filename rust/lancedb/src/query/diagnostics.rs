@@ -0,0 +1,703 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Diagnostics for the filter of a query
+//!
+//! [`crate::query::Query::validate`] checks a filter against the schema of the
+//! table without running the query and reports every problem it finds:
+//!
+//! * references to columns (or fields of struct columns) that do not exist,
+//!   with the most similar names as suggestions
+//! * comparisons of a column with a literal of an incompatible type, such as
+//!   `age = 'abc'` or `name LIKE 5`, and list functions applied to columns
+//!   that are not lists
+//! * calls of functions that are not supported
+//!
+//! The checks are syntactic, a filter without diagnostics can still fail
+//! when it is planned (for example because of unbalanced parentheses).
+
+use std::fmt;
+
+use arrow_schema::{DataType, Fields, Schema};
+
+use crate::query::filter::{
+    find_field, is_ident_part, is_ident_start, FUNCTION_ALIASES, KEYWORDS, LIST_FUNCTIONS,
+};
+
+/// The maximum number of suggestions of a diagnostic
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Functions that can be used in a filter, in addition to the list functions
+const FUNCTIONS: &[&str] = &[
+    "abs",
+    "acos",
+    "asin",
+    "atan",
+    "atan2",
+    "ceil",
+    "cos",
+    "exp",
+    "floor",
+    "ln",
+    "log",
+    "log10",
+    "log2",
+    "pow",
+    "power",
+    "round",
+    "signum",
+    "sin",
+    "sqrt",
+    "tan",
+    "trunc",
+    "isnan",
+    "iszero",
+    "random",
+    "coalesce",
+    "nullif",
+    "nvl",
+    "lower",
+    "upper",
+    "length",
+    "char_length",
+    "character_length",
+    "octet_length",
+    "trim",
+    "ltrim",
+    "rtrim",
+    "btrim",
+    "substr",
+    "substring",
+    "concat",
+    "concat_ws",
+    "replace",
+    "reverse",
+    "left",
+    "right",
+    "lpad",
+    "rpad",
+    "starts_with",
+    "strpos",
+    "split_part",
+    "regexp_match",
+    "regexp_replace",
+    "md5",
+    "sha256",
+    "now",
+    "current_date",
+    "current_time",
+    "date_trunc",
+    "date_part",
+    "datepart",
+    "date_bin",
+    "extract",
+    "from_unixtime",
+    "to_timestamp",
+    "to_timestamp_seconds",
+    "to_timestamp_millis",
+    "to_timestamp_micros",
+    "array_element",
+    "array_position",
+    "cardinality",
+    "arrow_typeof",
+];
+
+/// A problem found in a filter
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterDiagnostic {
+    /// The filter references a column, or a field of a struct column, that
+    /// does not exist.  Nested fields are named with dots.
+    UnknownColumn {
+        column: String,
+        suggestions: Vec<String>,
+    },
+    /// A column is used with a value, operator or function that does not
+    /// match its type
+    TypeMismatch {
+        column: String,
+        data_type: DataType,
+        /// The literal, operator or function the column is used with
+        expression: String,
+    },
+    /// The filter calls a function that is not supported
+    UnsupportedFunction {
+        function: String,
+        suggestions: Vec<String>,
+    },
+    /// The filter could not be parsed, or uses a column in a way that is not
+    /// supported
+    Invalid { message: String },
+}
+
+impl fmt::Display for FilterDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let did_you_mean = |f: &mut fmt::Formatter<'_>, suggestions: &[String]| {
+            if suggestions.is_empty() {
+                Ok(())
+            } else {
+                write!(f, ", did you mean {}?", suggestions.join(" or "))
+            }
+        };
+        match self {
+            Self::UnknownColumn {
+                column,
+                suggestions,
+            } => {
+                write!(f, "column '{}' does not exist", column)?;
+                did_you_mean(f, suggestions)
+            }
+            Self::TypeMismatch {
+                column,
+                data_type,
+                expression,
+            } => write!(
+                f,
+                "column '{}' has type {} which can not be used with {}",
+                column, data_type, expression
+            ),
+            Self::UnsupportedFunction {
+                function,
+                suggestions,
+            } => {
+                write!(f, "function '{}' is not supported", function)?;
+                did_you_mean(f, suggestions)
+            }
+            Self::Invalid { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Number(String),
+    Ident { path: Vec<String>, quoted: bool },
+    Op(String),
+    Punct(char),
+}
+
+impl Token {
+    fn keyword(&self) -> Option<String> {
+        match self {
+            Self::Ident {
+                path,
+                quoted: false,
+            } if path.len() == 1 && KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(&path[0])) => {
+                Some(path[0].to_uppercase())
+            }
+            _ => None,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.keyword().as_deref() == Some(keyword)
+    }
+
+    /// The token as a literal value, if it is one
+    fn literal(&self) -> Option<Literal> {
+        match self {
+            Self::Str(value) => Some(Literal::Str(value.clone())),
+            Self::Number(value) => Some(Literal::Number(value.clone())),
+            _ if self.is_keyword("TRUE") || self.is_keyword("FALSE") => Some(Literal::Bool),
+            _ => None,
+        }
+    }
+}
+
+enum Literal {
+    Str(String),
+    Number(String),
+    Bool,
+}
+
+impl Literal {
+    fn describe(&self) -> String {
+        match self {
+            Self::Str(value) => format!("the string '{}'", value),
+            Self::Number(value) => format!("the number {}", value),
+            Self::Bool => "a boolean".to_string(),
+        }
+    }
+
+    fn matches(&self, data_type: &DataType) -> bool {
+        match data_type {
+            DataType::Utf8 | DataType::LargeUtf8 => matches!(self, Self::Str(_)),
+            DataType::Boolean => match self {
+                Self::Bool => true,
+                Self::Str(value) => ["true", "false"]
+                    .iter()
+                    .any(|b| b.eq_ignore_ascii_case(value.trim())),
+                Self::Number(_) => false,
+            },
+            DataType::List(_)
+            | DataType::LargeList(_)
+            | DataType::FixedSizeList(_, _)
+            | DataType::Struct(_)
+            | DataType::Map(_, _) => false,
+            data_type if data_type.is_numeric() => match self {
+                Self::Number(_) => true,
+                Self::Str(value) => value.trim().parse::<f64>().is_ok(),
+                Self::Bool => false,
+            },
+            data_type if data_type.is_temporal() => !matches!(self, Self::Bool),
+            _ => true,
+        }
+    }
+}
+
+fn tokenize(filter: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars = filter.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c == '\'' {
+            let mut value = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None => return Err("unterminated string literal".to_string()),
+                    Some('\'') if chars.get(pos + 1) == Some(&'\'') => {
+                        value.push('\'');
+                        pos += 2;
+                    }
+                    Some('\'') => {
+                        pos += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        value.push(other);
+                        pos += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Str(value));
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && (is_ident_part(chars[pos]) || chars[pos] == '.') {
+                pos += 1;
+            }
+            tokens.push(Token::Number(chars[start..pos].iter().collect()));
+        } else if is_ident_start(c) || c == '`' || c == '"' {
+            let mut path = Vec::new();
+            let mut quoted = false;
+            loop {
+                match chars.get(pos) {
+                    Some(&quote) if quote == '`' || quote == '"' => {
+                        quoted = true;
+                        let mut part = String::new();
+                        pos += 1;
+                        loop {
+                            match chars.get(pos) {
+                                None => return Err("unterminated quoted identifier".to_string()),
+                                Some(&q) if q == quote && chars.get(pos + 1) == Some(&quote) => {
+                                    part.push(quote);
+                                    pos += 2;
+                                }
+                                Some(&q) if q == quote => {
+                                    pos += 1;
+                                    break;
+                                }
+                                Some(&other) => {
+                                    part.push(other);
+                                    pos += 1;
+                                }
+                            }
+                        }
+                        path.push(part);
+                    }
+                    Some(&first) if is_ident_start(first) => {
+                        let start = pos;
+                        while pos < chars.len() && is_ident_part(chars[pos]) {
+                            pos += 1;
+                        }
+                        path.push(chars[start..pos].iter().collect());
+                    }
+                    _ => return Err("expected an identifier after '.'".to_string()),
+                }
+                if chars.get(pos) == Some(&'.') {
+                    pos += 1;
+                } else {
+                    break;
+                }
+            }
+            // The prefix of a typed literal such as X'0102' is not an identifier
+            if quoted || path.len() > 1 || chars.get(pos) != Some(&'\'') {
+                tokens.push(Token::Ident { path, quoted });
+            }
+        } else if matches!(c, '=' | '<' | '>' | '!') {
+            let start = pos;
+            while pos < chars.len() && matches!(chars[pos], '=' | '<' | '>' | '!') {
+                pos += 1;
+            }
+            tokens.push(Token::Op(chars[start..pos].iter().collect()));
+        } else {
+            tokens.push(Token::Punct(c));
+            pos += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// The edit distance between two names, ignoring case
+///
+/// Swapping two adjacent characters counts as a single edit, since it is a
+/// common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<_>>();
+    let b = b.to_lowercase().chars().collect::<Vec<_>>();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        distances[0][j] = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// The candidates that are most similar to `name`
+fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let lower = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut scored = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = edit_distance(name, candidate);
+            let candidate_lower = candidate.to_lowercase();
+            let related = candidate_lower.contains(&lower) || lower.contains(&candidate_lower);
+            (distance <= max_distance || related).then_some((distance, candidate))
+        })
+        .collect::<Vec<_>>();
+    scored.sort();
+    scored.dedup();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+fn field_name_iter(fields: &Fields) -> impl Iterator<Item = &str> {
+    fields.iter().map(|f| f.name().as_str())
+}
+
+/// Resolve a column reference, or explain why it can not be resolved
+fn resolve<'a>(
+    schema: &'a Schema,
+    path: &[String],
+) -> std::result::Result<&'a DataType, FilterDiagnostic> {
+    let Some(field) = find_field(schema.fields(), &path[0]) else {
+        return Err(FilterDiagnostic::UnknownColumn {
+            column: path.join("."),
+            suggestions: suggest(&path[0], field_name_iter(schema.fields())),
+        });
+    };
+    let mut data_type = field.data_type();
+    for (depth, part) in path.iter().enumerate().skip(1) {
+        let parent = path[..depth].join(".");
+        data_type = match data_type {
+            DataType::Struct(fields) => match find_field(fields, part) {
+                Some(field) => field.data_type(),
+                None => {
+                    return Err(FilterDiagnostic::UnknownColumn {
+                        column: path[..=depth].join("."),
+                        suggestions: suggest(part, field_name_iter(fields))
+                            .into_iter()
+                            .map(|name| format!("{}.{}", parent, name))
+                            .collect(),
+                    })
+                }
+            },
+            DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(_, _) => {
+                return Err(FilterDiagnostic::Invalid {
+                    message: format!(
+                        "'{}' is a list column, use array_has({}, <value>) to filter on its elements",
+                        parent, parent
+                    ),
+                })
+            }
+            other => {
+                return Err(FilterDiagnostic::TypeMismatch {
+                    column: parent,
+                    data_type: other.clone(),
+                    expression: format!("the field access '.{}'", part),
+                })
+            }
+        };
+    }
+    Ok(data_type)
+}
+
+fn is_comparison(token: Option<&Token>) -> bool {
+    matches!(token, Some(Token::Op(op)) if ["=", "==", "!=", "<>", "<", "<=", ">", ">="].contains(&op.as_str()))
+}
+
+/// Check a filter against a schema, returning every problem that was found
+///
+/// An empty result means that no problem was found.
+pub fn diagnose_filter(schema: &Schema, filter: &str) -> Vec<FilterDiagnostic> {
+    let tokens = match tokenize(filter) {
+        Ok(tokens) => tokens,
+        Err(message) => return vec![FilterDiagnostic::Invalid { message }],
+    };
+    let mut diagnostics = Vec::new();
+    let mut skip_next_ident = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Ident { path, quoted } = token else {
+            continue;
+        };
+        if std::mem::take(&mut skip_next_ident) {
+            // The type name of `CAST(x AS type)`
+            continue;
+        }
+        if let Some(keyword) = token.keyword() {
+            skip_next_ident = keyword == "AS";
+            continue;
+        }
+        if !quoted && path.len() == 1 && tokens.get(i + 1) == Some(&Token::Punct('(')) {
+            let name = path[0].to_lowercase();
+            let known = FUNCTIONS
+                .iter()
+                .chain(LIST_FUNCTIONS)
+                .chain(FUNCTION_ALIASES.iter().map(|(alias, _)| alias));
+            if !known.clone().any(|f| *f == name) {
+                diagnostics.push(FilterDiagnostic::UnsupportedFunction {
+                    function: path[0].clone(),
+                    suggestions: suggest(&path[0], known.copied()),
+                });
+            } else if LIST_FUNCTIONS.contains(&name.as_str()) {
+                // The first argument must be a list column
+                if let Some(Token::Ident { path: arg, .. }) = tokens.get(i + 2) {
+                    if let Ok(data_type) = resolve(schema, arg) {
+                        if !matches!(
+                            data_type,
+                            DataType::List(_)
+                                | DataType::LargeList(_)
+                                | DataType::FixedSizeList(_, _)
+                        ) {
+                            diagnostics.push(FilterDiagnostic::TypeMismatch {
+                                column: arg.join("."),
+                                data_type: data_type.clone(),
+                                expression: format!("{}, which requires a list", name),
+                            });
+                        }
+                    }
+                }
+            }
+            continue;
+        }
+
+        let data_type = match resolve(schema, path) {
+            Ok(data_type) => data_type,
+            Err(diagnostic) => {
+                if !diagnostics.contains(&diagnostic) {
+                    diagnostics.push(diagnostic);
+                }
+                continue;
+            }
+        };
+        let mismatch = |expression: String| FilterDiagnostic::TypeMismatch {
+            column: path.join("."),
+            data_type: data_type.clone(),
+            expression,
+        };
+        // `column <op> literal`, allowing a sign before a number
+        let mut right = i + 2;
+        if matches!(tokens.get(right), Some(Token::Punct('-' | '+'))) {
+            right += 1;
+        }
+        let mut literal = None;
+        if is_comparison(tokens.get(i + 1)) {
+            literal = tokens.get(right).and_then(Token::literal);
+        }
+        // or `literal <op> column`
+        if literal.is_none() && i >= 2 && is_comparison(tokens.get(i - 1)) {
+            literal = tokens[i - 2].literal();
+        }
+        if let Some(literal) = literal {
+            if !literal.matches(data_type) {
+                diagnostics.push(mismatch(literal.describe()));
+            }
+        }
+        let mut next = i + 1;
+        if tokens.get(next).map_or(false, |t| t.is_keyword("NOT")) {
+            next += 1;
+        }
+        if let Some(keyword) = tokens
+            .get(next)
+            .and_then(Token::keyword)
+            .filter(|k| k == "LIKE" || k == "ILIKE")
+        {
+            if !matches!(data_type, DataType::Utf8 | DataType::LargeUtf8) {
+                diagnostics.push(mismatch(keyword));
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::Field;
+
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("age", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("active", DataType::Boolean, true),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true,
+            ),
+            Field::new(
+                "metadata",
+                DataType::Struct(
+                    vec![
+                        Field::new("user_id", DataType::Int64, true),
+                        Field::new("source", DataType::Utf8, true),
+                    ]
+                    .into(),
+                ),
+                true,
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_valid_filters() {
+        let schema = schema();
+        for filter in [
+            "age > 5 AND name = 'x'",
+            "age = '5' OR -3 < age",
+            "active = true AND name LIKE 'a%'",
+            "array_has(tags, 'x') AND metadata.user_id IS NOT NULL",
+            "CAST(age AS VARCHAR) = '3' AND lower(name) = 'it''s'",
+            "`age` IN (1, 2, 3) AND X'01' IS NOT NULL",
+        ] {
+            assert_eq!(diagnose_filter(&schema, filter), vec![], "{}", filter);
+        }
+    }
+
+    #[test]
+    fn test_unknown_columns() {
+        let schema = schema();
+        assert_eq!(
+            diagnose_filter(&schema, "agee > 5 AND naem = 'x' AND agee < 10"),
+            vec![
+                FilterDiagnostic::UnknownColumn {
+                    column: "agee".to_string(),
+                    suggestions: vec!["age".to_string()],
+                },
+                FilterDiagnostic::UnknownColumn {
+                    column: "naem".to_string(),
+                    suggestions: vec!["name".to_string()],
+                },
+            ]
+        );
+        let diagnostics = diagnose_filter(&schema, "metadata.user = 3 OR foo = 1");
+        assert_eq!(
+            diagnostics[0],
+            FilterDiagnostic::UnknownColumn {
+                column: "metadata.user".to_string(),
+                suggestions: vec!["metadata.user_id".to_string()],
+            }
+        );
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "column 'foo' does not exist".to_string()
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "column 'metadata.user' does not exist, did you mean metadata.user_id?"
+        );
+    }
+
+    #[test]
+    fn test_type_mismatches() {
+        let schema = schema();
+        let expressions = |filter: &str| {
+            diagnose_filter(&schema, filter)
+                .into_iter()
+                .map(|d| match d {
+                    FilterDiagnostic::TypeMismatch { expression, .. } => expression,
+                    other => panic!("unexpected diagnostic {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(expressions("age = 'abc'"), vec!["the string 'abc'"]);
+        assert_eq!(expressions("5 < name"), vec!["the number 5"]);
+        assert_eq!(expressions("active = 1"), vec!["the number 1"]);
+        assert_eq!(expressions("age NOT LIKE 'a%'"), vec!["LIKE"]);
+        assert_eq!(
+            expressions("array_has(name, 'x')"),
+            vec!["array_has, which requires a list"]
+        );
+        assert_eq!(expressions("age.x = 1"), vec!["the field access '.x'"]);
+    }
+
+    #[test]
+    fn test_functions_and_syntax() {
+        let schema = schema();
+        assert_eq!(
+            diagnose_filter(&schema, "lowr(name) = 'x' AND array_hass(tags, 'x')"),
+            vec![
+                FilterDiagnostic::UnsupportedFunction {
+                    function: "lowr".to_string(),
+                    suggestions: vec!["lower".to_string()],
+                },
+                FilterDiagnostic::UnsupportedFunction {
+                    function: "array_hass".to_string(),
+                    suggestions: vec!["array_has".to_string()],
+                },
+            ]
+        );
+        assert!(matches!(
+            diagnose_filter(&schema, "name = 'x").as_slice(),
+            [FilterDiagnostic::Invalid { .. }]
+        ));
+        assert!(matches!(
+            diagnose_filter(&schema, "tags.item = 'x'").as_slice(),
+            [FilterDiagnostic::Invalid { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("age", "age"), 0);
+        assert_eq!(edit_distance("Age", "agee"), 1);
+        assert_eq!(edit_distance("naem", "name"), 1);
+        assert_eq!(edit_distance("lowr", "log"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
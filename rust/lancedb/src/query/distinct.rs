@@ -0,0 +1,284 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! De-duplication of query results, see [`super::QueryBase::distinct_on`]
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::compute::{filter_record_batch, sort_to_indices};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{BooleanArray, RecordBatch};
+use arrow_schema::{Schema, SchemaRef};
+use futures::TryStreamExt;
+
+use crate::arrow::{take_record_batch, SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+
+use super::rescore::DISTANCE_COLUMN;
+use super::Select;
+
+/// A vector search with `distinct_on` first fetches this many candidates per
+/// result, doubling the number until enough distinct results are found.
+pub(crate) const DISTINCT_OVERSAMPLE: usize = 4;
+
+/// Keeps the first row seen for each distinct key
+pub(crate) struct Deduplicator {
+    columns: Vec<String>,
+    converter: Option<RowConverter>,
+    seen: HashSet<OwnedRow>,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(columns: Vec<String>) -> Self {
+        Self {
+            columns,
+            converter: None,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Remove the rows whose key was already seen, in this or a previous batch
+    ///
+    /// Null is treated as a regular key value.
+    pub(crate) fn filter(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let keys = self
+            .columns
+            .iter()
+            .map(|column| {
+                batch
+                    .column_by_name(column)
+                    .cloned()
+                    .ok_or_else(|| Error::InvalidInput {
+                        message: format!(
+                            "the distinct column '{}' is not in the query results",
+                            column
+                        ),
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if self.converter.is_none() {
+            self.converter = Some(RowConverter::new(
+                keys.iter()
+                    .map(|key| SortField::new(key.data_type().clone()))
+                    .collect(),
+            )?);
+        }
+        let rows = self.converter.as_ref().unwrap().convert_columns(&keys)?;
+        let first = rows
+            .iter()
+            .map(|row| self.seen.insert(row.owned()))
+            .collect::<Vec<_>>();
+        Ok(filter_record_batch(batch, &BooleanArray::from(first))?)
+    }
+}
+
+/// Add the distinct columns to the projection if they are not selected
+///
+/// Returns the columns that were added, which should be removed from the
+/// results with [`drop_columns`].
+pub(crate) fn select_distinct_columns(select: &mut Select, columns: &[String]) -> Vec<String> {
+    let mut added = Vec::new();
+    match select {
+        Select::All => {}
+        Select::Columns(selected) => {
+            for column in columns {
+                if !selected.contains(column) {
+                    selected.push(column.clone());
+                    added.push(column.clone());
+                }
+            }
+        }
+        Select::Dynamic(selected) => {
+            for column in columns {
+                if !selected.iter().any(|(name, _)| name == column) {
+                    selected.push((column.clone(), column.clone()));
+                    added.push(column.clone());
+                }
+            }
+        }
+        Select::AllExcept(excluded) => {
+            for column in columns {
+                if let Some(position) = excluded.iter().position(|c| c == column) {
+                    excluded.remove(position);
+                    added.push(column.clone());
+                }
+            }
+        }
+    }
+    added
+}
+
+fn kept_columns(schema: &Schema, dropped: &[String]) -> Vec<usize> {
+    (0..schema.fields().len())
+        .filter(|idx| !dropped.contains(schema.field(*idx).name()))
+        .collect()
+}
+
+/// Remove the columns that were only selected to find the distinct rows
+pub(crate) fn drop_columns(batch: &RecordBatch, dropped: &[String]) -> Result<RecordBatch> {
+    Ok(batch.project(&kept_columns(&batch.schema(), dropped))?)
+}
+
+/// Sort the results of a vector search so that the nearest row of each key is kept
+pub(crate) fn nearest_first(batch: RecordBatch) -> Result<RecordBatch> {
+    match batch.column_by_name(DISTANCE_COLUMN) {
+        Some(distances) => {
+            let order = sort_to_indices(distances, None, None)?;
+            Ok(take_record_batch(&batch, &order)?)
+        }
+        None => Ok(batch),
+    }
+}
+
+/// Remove duplicate rows from a stream, keeping the first row of each key
+pub(crate) fn distinct_stream(
+    stream: SendableRecordBatchStream,
+    columns: Vec<String>,
+    limit: Option<usize>,
+    dropped: Vec<String>,
+) -> Result<SendableRecordBatchStream> {
+    let schema: SchemaRef = Arc::new(
+        stream
+            .schema()
+            .project(&kept_columns(&stream.schema(), &dropped))?,
+    );
+    let state = (stream, Deduplicator::new(columns), limit, dropped);
+    let stream = futures::stream::try_unfold(
+        state,
+        |(mut stream, mut dedup, mut remaining, dropped)| async move {
+            if remaining == Some(0) {
+                return Ok(None);
+            }
+            while let Some(batch) = stream.try_next().await? {
+                let mut batch = dedup.filter(&batch)?;
+                if let Some(remaining) = remaining.as_mut() {
+                    let num_rows = batch.num_rows().min(*remaining);
+                    batch = batch.slice(0, num_rows);
+                    *remaining -= num_rows;
+                }
+                if batch.num_rows() > 0 {
+                    let batch = drop_columns(&batch, &dropped)?;
+                    return Ok(Some((batch, (stream, dedup, remaining, dropped))));
+                }
+            }
+            Ok(None)
+        },
+    );
+    Ok(Box::pin(SimpleRecordBatchStream { schema, stream }))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Int32Type, Float32Array, Int32Array};
+    use arrow_schema::{DataType, Field};
+    use futures::stream;
+
+    use super::*;
+
+    fn batch(docs: Vec<Option<i32>>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("doc", DataType::Int32, true),
+            Field::new("chunk", DataType::Int32, false),
+        ]));
+        let chunks = Int32Array::from_iter_values(0..docs.len() as i32);
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int32Array::from(docs)), Arc::new(chunks)],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_deduplicator() {
+        let mut dedup = Deduplicator::new(vec!["doc".to_string()]);
+        let first = dedup
+            .filter(&batch(vec![Some(1), Some(2), Some(1), None, None]))
+            .unwrap();
+        assert_eq!(
+            first["chunk"].as_primitive::<Int32Type>().values().to_vec(),
+            vec![0, 1, 3]
+        );
+        // Keys are remembered across batches
+        let second = dedup.filter(&batch(vec![Some(2), Some(3)])).unwrap();
+        assert_eq!(
+            second["chunk"]
+                .as_primitive::<Int32Type>()
+                .values()
+                .to_vec(),
+            vec![1]
+        );
+
+        let mut missing = Deduplicator::new(vec!["nope".to_string()]);
+        assert!(missing.filter(&batch(vec![Some(1)])).is_err());
+    }
+
+    #[test]
+    fn test_nearest_first() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("doc", DataType::Int32, false),
+            Field::new(DISTANCE_COLUMN, DataType::Float32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 1])),
+                Arc::new(Float32Array::from(vec![0.5, 0.2, 0.1])),
+            ],
+        )
+        .unwrap();
+        let mut dedup = Deduplicator::new(vec!["doc".to_string()]);
+        let distinct = dedup.filter(&nearest_first(batch).unwrap()).unwrap();
+        assert_eq!(
+            distinct["doc"]
+                .as_primitive::<Int32Type>()
+                .values()
+                .to_vec(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            distinct[DISTANCE_COLUMN]
+                .as_primitive::<arrow_array::types::Float32Type>()
+                .values()
+                .to_vec(),
+            vec![0.1, 0.2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_distinct_stream() {
+        let input = vec![
+            Ok(batch(vec![Some(1), Some(1), Some(2)])),
+            Ok(batch(vec![Some(2), Some(3), Some(4)])),
+        ];
+        let inner: SendableRecordBatchStream = Box::pin(SimpleRecordBatchStream {
+            schema: input[0].as_ref().unwrap().schema(),
+            stream: stream::iter(input),
+        });
+        let distinct = distinct_stream(
+            inner,
+            vec!["doc".to_string()],
+            Some(3),
+            vec!["chunk".to_string()],
+        )
+        .unwrap();
+        assert!(distinct.schema().field_with_name("chunk").is_err());
+        let batches = distinct.try_collect::<Vec<_>>().await.unwrap();
+        let docs = batches
+            .iter()
+            .flat_map(|b| b["doc"].as_primitive::<Int32Type>().values().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(docs, vec![1, 2, 3]);
+    }
+}
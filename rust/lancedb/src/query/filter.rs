@@ -0,0 +1,539 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation of filter strings against a table schema
+//!
+//! Lance reports errors in a filter in terms of the logical plan which can be
+//! hard to relate back to the filter, especially when the filter refers to
+//! fields of nested columns.  Before a filter is handed to lance we check every
+//! column reference against the schema so that we can report a clear error.
+//!
+//! Nested struct fields are referenced with dots (e.g. `metadata.user.id = 3`)
+//! and list columns are filtered with the array functions (e.g.
+//! `array_has(tags, 'x')`).  `array_contains` and `list_contains` are accepted
+//! as aliases of `array_has`.
+
+use arrow_schema::{DataType, Field, Fields, Schema};
+
+use crate::error::{Error, Result};
+
+/// Keywords that can appear unquoted in a filter and are not column references
+pub(super) const KEYWORDS: &[&str] = &[
+    "AND",
+    "OR",
+    "NOT",
+    "IS",
+    "NULL",
+    "IN",
+    "LIKE",
+    "ILIKE",
+    "TRUE",
+    "FALSE",
+    "BETWEEN",
+    "CAST",
+    "TRY_CAST",
+    "AS",
+    "CASE",
+    "WHEN",
+    "THEN",
+    "ELSE",
+    "END",
+    "DATE",
+    "TIMESTAMP",
+    "INTERVAL",
+    "ESCAPE",
+    "DISTINCT",
+    "FROM",
+];
+
+/// Functions whose first argument must be a list column
+pub(super) const LIST_FUNCTIONS: &[&str] = &[
+    "array_has",
+    "array_has_all",
+    "array_has_any",
+    "array_length",
+    "array_contains",
+    "list_has",
+    "list_contains",
+];
+
+/// Functions that are rewritten to the name understood by lance
+pub(super) const FUNCTION_ALIASES: &[(&str, &str)] = &[
+    ("array_contains", "array_has"),
+    ("list_contains", "array_has"),
+];
+
+fn invalid(filter: &str, reason: impl AsRef<str>) -> Error {
+    Error::InvalidFilter {
+        filter: filter.to_string(),
+        message: reason.as_ref().to_string(),
+    }
+}
+
+/// Convert an error raised by lance while planning a filter
+pub(crate) fn invalid_filter(filter: &str, err: impl std::fmt::Display) -> Error {
+    invalid(filter, err.to_string())
+}
+
+pub(crate) fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+pub(crate) fn is_ident_part(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn field_names(fields: &Fields) -> String {
+    fields
+        .iter()
+        .map(|f| f.name().as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(super) fn find_field<'a>(fields: &'a Fields, name: &str) -> Option<&'a Field> {
+    fields
+        .iter()
+        .find(|f| f.name() == name)
+        .or_else(|| fields.iter().find(|f| f.name().eq_ignore_ascii_case(name)))
+        .map(|f| f.as_ref())
+}
+
+/// Resolve a (possibly nested) column reference to its data type
+fn resolve_column<'a>(schema: &'a Schema, filter: &str, path: &[String]) -> Result<&'a DataType> {
+    let field = find_field(schema.fields(), &path[0]).ok_or_else(|| {
+        invalid(
+            filter,
+            format!(
+                "column '{}' does not exist, the available columns are: {}",
+                path[0],
+                field_names(schema.fields())
+            ),
+        )
+    })?;
+    let mut data_type = field.data_type();
+    for (depth, part) in path.iter().enumerate().skip(1) {
+        let parent = path[..depth].join(".");
+        data_type = match data_type {
+            DataType::Struct(fields) => find_field(fields, part)
+                .ok_or_else(|| {
+                    invalid(
+                        filter,
+                        format!(
+                            "the struct column '{}' has no field '{}', the available fields are: {}",
+                            parent,
+                            part,
+                            field_names(fields)
+                        ),
+                    )
+                })?
+                .data_type(),
+            DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(_, _) => {
+                return Err(invalid(
+                    filter,
+                    format!(
+                        "'{}' is a list column, use array_has({}, <value>) to filter on its elements",
+                        parent, parent
+                    ),
+                ))
+            }
+            DataType::Map(_, _) => {
+                return Err(invalid(
+                    filter,
+                    format!(
+                        "filtering on the entries of the map column '{}' is not supported",
+                        parent
+                    ),
+                ))
+            }
+            other => {
+                return Err(invalid(
+                    filter,
+                    format!(
+                        "'{}' has type {} and has no field '{}'",
+                        parent, other, part
+                    ),
+                ))
+            }
+        };
+    }
+    Ok(data_type)
+}
+
+/// Check the column references of a filter against the schema
+///
+/// Returns the filter, with function aliases rewritten, if it is valid.
+pub(crate) fn normalize_filter(schema: &Schema, filter: &str) -> Result<String> {
+    parse_filter(schema, filter, None).map(|(filter, _)| filter)
+}
+
+/// Check a filter like [`normalize_filter`] and prefix its column references
+///
+/// The conditions of a merge insert refer to the columns of the table as
+/// `target.<column>`.
+pub(crate) fn qualify_filter(schema: &Schema, filter: &str, qualifier: &str) -> Result<String> {
+    parse_filter(schema, filter, Some(qualifier)).map(|(filter, _)| filter)
+}
+
+/// The names of the top level columns referenced by a filter or expression
+pub(crate) fn filter_columns(schema: &Schema, filter: &str) -> Result<Vec<String>> {
+    parse_filter(schema, filter, None).map(|(_, columns)| columns)
+}
+
+/// Returns the normalized filter and the top level columns it references
+fn parse_filter(
+    schema: &Schema,
+    filter: &str,
+    qualifier: Option<&str>,
+) -> Result<(String, Vec<String>)> {
+    let mut columns: Vec<String> = Vec::new();
+    let chars = filter.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(filter.len());
+    let mut pos = 0;
+    // Set after a function that requires a list as its first argument
+    let mut list_function: Option<String> = None;
+    // The type name in `CAST(x AS type)` is not a column
+    let mut after_as = false;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c == '\'' {
+            // String literal, '' is an escaped quote
+            let start = pos;
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None => return Err(invalid(filter, "unterminated string literal")),
+                    Some('\'') if chars.get(pos + 1) == Some(&'\'') => pos += 2,
+                    Some('\'') => {
+                        pos += 1;
+                        break;
+                    }
+                    Some(_) => pos += 1,
+                }
+            }
+            out.extend(&chars[start..pos]);
+            list_function = None;
+            after_as = false;
+        } else if c.is_ascii_digit() {
+            // Numeric literal (including exponents like 1e5)
+            let start = pos;
+            while pos < chars.len() && (is_ident_part(chars[pos]) || chars[pos] == '.') {
+                pos += 1;
+            }
+            out.extend(&chars[start..pos]);
+            list_function = None;
+            after_as = false;
+        } else if is_ident_start(c) || c == '`' || c == '"' {
+            let start = pos;
+            let mut path = Vec::new();
+            let mut quoted = false;
+            loop {
+                match chars.get(pos) {
+                    Some(&quote) if quote == '`' || quote == '"' => {
+                        quoted = true;
+                        let mut part = String::new();
+                        pos += 1;
+                        loop {
+                            match chars.get(pos) {
+                                None => {
+                                    return Err(invalid(filter, "unterminated quoted identifier"))
+                                }
+                                Some(&q) if q == quote && chars.get(pos + 1) == Some(&quote) => {
+                                    part.push(quote);
+                                    pos += 2;
+                                }
+                                Some(&q) if q == quote => {
+                                    pos += 1;
+                                    break;
+                                }
+                                Some(&other) => {
+                                    part.push(other);
+                                    pos += 1;
+                                }
+                            }
+                        }
+                        path.push(part);
+                    }
+                    Some(&first) if is_ident_start(first) => {
+                        let part_start = pos;
+                        while pos < chars.len() && is_ident_part(chars[pos]) {
+                            pos += 1;
+                        }
+                        path.push(chars[part_start..pos].iter().collect::<String>());
+                    }
+                    _ => return Err(invalid(filter, "expected an identifier after '.'")),
+                }
+                if chars.get(pos) == Some(&'.') {
+                    pos += 1;
+                } else {
+                    break;
+                }
+            }
+            let raw = chars[start..pos].iter().collect::<String>();
+            if !quoted && path.len() == 1 && chars.get(pos) == Some(&'\'') {
+                // The prefix of a typed literal such as X'0102'
+                out.push_str(&raw);
+                continue;
+            }
+
+            let mut lookahead = pos;
+            while lookahead < chars.len() && chars[lookahead].is_whitespace() {
+                lookahead += 1;
+            }
+            let is_function = !quoted && path.len() == 1 && chars.get(lookahead) == Some(&'(');
+            let is_keyword = !quoted
+                && path.len() == 1
+                && KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(&path[0]));
+
+            if is_function {
+                let name = path[0].to_lowercase();
+                let alias = FUNCTION_ALIASES.iter().find(|(alias, _)| *alias == name);
+                match alias {
+                    Some((_, target)) => out.push_str(target),
+                    None => out.push_str(&raw),
+                }
+                list_function = LIST_FUNCTIONS.contains(&name.as_str()).then_some(name);
+                after_as = false;
+                continue;
+            }
+            if let Some(qualifier) = qualifier.filter(|_| !after_as && !is_keyword) {
+                out.push_str(qualifier);
+                out.push('.');
+            }
+            out.push_str(&raw);
+            if after_as {
+                after_as = false;
+            } else if is_keyword {
+                after_as = path[0].eq_ignore_ascii_case("AS");
+            } else {
+                let data_type = resolve_column(schema, filter, &path)?;
+                // resolve_column has checked that the column exists
+                let column = find_field(schema.fields(), &path[0]).unwrap().name();
+                if !columns.contains(column) {
+                    columns.push(column.clone());
+                }
+                if let Some(function) = list_function.take() {
+                    if !matches!(
+                        data_type,
+                        DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(_, _)
+                    ) {
+                        return Err(invalid(
+                            filter,
+                            format!(
+                                "{} requires a list column but '{}' has type {}",
+                                function,
+                                path.join("."),
+                                data_type
+                            ),
+                        ));
+                    }
+                }
+            }
+            list_function = None;
+        } else {
+            out.push(c);
+            if !c.is_whitespace() && c != '(' {
+                list_function = None;
+            }
+            pos += 1;
+        }
+    }
+    Ok((out, columns))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        builder::{ListBuilder, StringBuilder},
+        Array, Int32Array, RecordBatch, RecordBatchIterator, StringArray, StructArray,
+    };
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::connect;
+    use crate::query::{ExecutableQuery, QueryBase};
+    use futures::TryStreamExt;
+
+    fn user_fields() -> Fields {
+        Fields::from(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+        ])
+    }
+
+    fn nested_schema() -> Schema {
+        Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "metadata",
+                DataType::Struct(Fields::from(vec![Field::new(
+                    "user",
+                    DataType::Struct(user_fields()),
+                    true,
+                )])),
+                true,
+            ),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true,
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_normalize_filter() {
+        let schema = nested_schema();
+        let ok = |filter: &str| normalize_filter(&schema, filter).unwrap();
+
+        assert_eq!(ok("metadata.user.id = 3"), "metadata.user.id = 3");
+        assert_eq!(
+            ok("`metadata`.`user`.`name` = 'it''s' AND id > 1e3"),
+            "`metadata`.`user`.`name` = 'it''s' AND id > 1e3"
+        );
+        assert_eq!(
+            ok("array_contains(tags, 'x') OR id IS NULL"),
+            "array_has(tags, 'x') OR id IS NULL"
+        );
+        assert_eq!(
+            ok("CAST(id AS BIGINT) > 2 AND tags IS NOT NULL"),
+            "CAST(id AS BIGINT) > 2 AND tags IS NOT NULL"
+        );
+        assert_eq!(ok("id = X'0102'"), "id = X'0102'");
+        // Words inside string literals are not columns
+        assert_eq!(
+            ok("metadata.user.name = 'nope.x'"),
+            "metadata.user.name = 'nope.x'"
+        );
+
+        let err = |filter: &str| normalize_filter(&schema, filter).unwrap_err().to_string();
+        assert!(err("nope = 1").contains("column 'nope' does not exist"));
+        assert!(err("metadata.user.age > 3").contains("has no field 'age'"));
+        assert!(err("metadata.user.id.x > 3").contains("has type Int32"));
+        assert!(err("tags.item = 'x'").contains("use array_has"));
+        assert!(err("array_has(id, 3)").contains("requires a list column"));
+        assert!(err("id = 'abc").contains("unterminated"));
+    }
+
+    #[test]
+    fn test_filter_columns() {
+        let schema = nested_schema();
+        assert_eq!(
+            filter_columns(&schema, "metadata.user.id = 3 AND ID > 1 OR id < 0").unwrap(),
+            vec!["metadata", "id"]
+        );
+        assert_eq!(
+            filter_columns(&schema, "array_has(tags, 'id')").unwrap(),
+            vec!["tags"]
+        );
+        assert!(filter_columns(&schema, "nope = 1").is_err());
+    }
+
+    #[test]
+    fn test_qualify_filter() {
+        let schema = nested_schema();
+        assert_eq!(
+            qualify_filter(
+                &schema,
+                "metadata.user.name = 'id' AND CAST(`id` AS BIGINT) > 1e3",
+                "target"
+            )
+            .unwrap(),
+            "target.metadata.user.name = 'id' AND CAST(target.`id` AS BIGINT) > 1e3"
+        );
+        assert_eq!(
+            qualify_filter(&schema, "array_contains(tags, 'x')", "t").unwrap(),
+            "array_has(t.tags, 'x')"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nested_filters() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let schema = Arc::new(nested_schema());
+        let users = StructArray::new(
+            user_fields(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+            None,
+        );
+        let metadata = StructArray::new(
+            Fields::from(vec![Field::new("user", users.data_type().clone(), true)]),
+            vec![Arc::new(users)],
+            None,
+        );
+        let mut tags = ListBuilder::new(StringBuilder::new());
+        for row in [vec!["x", "y"], vec!["y"], vec!["x"]] {
+            for tag in row {
+                tags.values().append_value(tag);
+            }
+            tags.append(true);
+        }
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 1, 2])),
+                Arc::new(metadata),
+                Arc::new(tags.finish()),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table("nested", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            table
+                .count_rows(Some("metadata.user.id = 3".to_string()))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            table
+                .count_rows(Some("array_contains(tags, 'x')".to_string()))
+                .await
+                .unwrap(),
+            2
+        );
+
+        let batches = table
+            .query()
+            .only_if("metadata.user.id >= 2 AND array_has(tags, 'x')")
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+
+        let err = table
+            .count_rows(Some("metadata.user.age = 3".to_string()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidFilter { .. }));
+        assert!(!err.is_retryable());
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Caching of the rows selected by the filters of vector searches
+//!
+//! Multi-tenant applications run many vector searches with the same
+//! prefilter (e.g. `tenant_id = 7`).  With the cache enabled (see
+//! [`crate::connection::ConnectBuilder::filter_cache`]) the row ids selected
+//! by the filter of a prefiltered vector search are cached, keyed by the
+//! filter and the table version.  If the filter selects at most
+//! [`FilterCacheConfig::max_rows`] rows, later searches with the same filter
+//! skip the filter and compare the query vector to the vectors of the cached
+//! rows directly.  This gives exact results, so it only pays off for
+//! selective filters.  Filters that select more rows are remembered as such
+//! and searched with the index as usual.
+//!
+//! The entries of older versions are dropped when the table changes.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use arrow::compute::{filter_record_batch, is_not_null};
+use arrow_array::{cast::AsArray, types::UInt64Type, Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+
+use crate::error::Result;
+use crate::DistanceType;
+
+use super::filter::{filter_columns, invalid_filter};
+use super::late::ROW_ID_COLUMN;
+use super::rescore::{rescore_batches, vector_column_of};
+
+/// Configuration of the filter cache of a table
+#[derive(Debug, Clone)]
+pub struct FilterCacheConfig {
+    /// The maximum number of filters cached per table
+    ///
+    /// By default, this is 128
+    pub max_entries: usize,
+    /// The maximum number of rows a filter may select to be searched from the cache
+    ///
+    /// By default, this is 10,000
+    pub max_rows: usize,
+}
+
+impl Default for FilterCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 128,
+            max_rows: 10_000,
+        }
+    }
+}
+
+/// Counters of the filter cache of a table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterCacheMetrics {
+    /// The number of searches that found their filter in the cache
+    pub hits: u64,
+    /// The number of searches that had to evaluate their filter
+    pub misses: u64,
+    /// The number of entries dropped because the table changed
+    pub invalidations: u64,
+    /// The number of entries dropped to make room for new ones
+    pub evictions: u64,
+    /// The number of entries in the cache
+    pub entries: usize,
+}
+
+/// The rows selected by a filter
+#[derive(Debug, Clone)]
+pub(crate) enum CachedRows {
+    /// The row ids, in ascending order
+    Rows(Arc<Vec<u64>>),
+    /// The filter selects more than [`FilterCacheConfig::max_rows`] rows
+    TooMany,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<(String, u64), (CachedRows, u64)>,
+    // incremented on every access, used to find the least recently used entry
+    clock: u64,
+    metrics: FilterCacheMetrics,
+}
+
+/// The cached filter results of a table
+#[derive(Debug)]
+pub(crate) struct FilterCache {
+    config: FilterCacheConfig,
+    state: Mutex<CacheState>,
+}
+
+impl FilterCache {
+    pub(crate) fn new(config: FilterCacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn max_rows(&self) -> usize {
+        self.config.max_rows
+    }
+
+    /// The cached rows of the filter at the version, counting a hit or a miss
+    ///
+    /// Entries of other versions are dropped.
+    pub(crate) fn get(&self, filter: &str, version: u64) -> Option<CachedRows> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.entries.len();
+        state.entries.retain(|(_, v), _| *v == version);
+        state.metrics.invalidations += (before - state.entries.len()) as u64;
+        state.clock += 1;
+        let clock = state.clock;
+        let found =
+            state
+                .entries
+                .get_mut(&(filter.to_string(), version))
+                .map(|(rows, last_used)| {
+                    *last_used = clock;
+                    rows.clone()
+                });
+        if found.is_some() {
+            state.metrics.hits += 1;
+        } else {
+            state.metrics.misses += 1;
+        }
+        found
+    }
+
+    /// Cache the rows selected by the filter at the version
+    pub(crate) fn insert(&self, filter: &str, version: u64, rows: CachedRows) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        state
+            .entries
+            .insert((filter.to_string(), version), (rows, clock));
+        while state.entries.len() > self.config.max_entries {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+                state.metrics.evictions += 1;
+            }
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> FilterCacheMetrics {
+        let state = self.state.lock().unwrap();
+        FilterCacheMetrics {
+            entries: state.entries.len(),
+            ..state.metrics.clone()
+        }
+    }
+}
+
+/// Find the rows selected by the filter, up to `max_rows` rows
+pub(crate) async fn evaluate_filter(
+    dataset: &Dataset,
+    filter: &str,
+    max_rows: usize,
+) -> Result<CachedRows> {
+    let schema = Schema::from(dataset.schema());
+    let mut scanner = dataset.scan();
+    scanner.project(&filter_columns(&schema, filter)?)?;
+    scanner.with_row_id();
+    scanner
+        .filter(filter)
+        .map_err(|e| invalid_filter(filter, e))?;
+    let mut stream = scanner.try_into_stream().await?;
+    let mut rows = Vec::new();
+    while let Some(batch) = stream.try_next().await? {
+        if let Some(row_ids) = batch.column_by_name(ROW_ID_COLUMN) {
+            rows.extend(row_ids.as_primitive::<UInt64Type>().values());
+        }
+        if rows.len() > max_rows {
+            return Ok(CachedRows::TooMany);
+        }
+    }
+    rows.sort_unstable();
+    Ok(CachedRows::Rows(Arc::new(rows)))
+}
+
+/// A vector search over the given rows
+pub(crate) struct RowSearch<'a> {
+    pub(crate) vector_column: &'a str,
+    pub(crate) query_vector: &'a dyn Array,
+    pub(crate) distance_type: DistanceType,
+    /// The selected columns, None for all columns
+    pub(crate) columns: Option<&'a [String]>,
+    pub(crate) limit: usize,
+    pub(crate) with_row_id: bool,
+}
+
+impl RowSearch<'_> {
+    /// Compare the query vector to the vector of every row and keep the nearest
+    ///
+    /// The results have the same layout as the results of a vector search.
+    /// Rows with a null vector are skipped.
+    pub(crate) async fn execute(&self, dataset: &Dataset, row_ids: &[u64]) -> Result<RecordBatch> {
+        let mut columns = match self.columns {
+            Some(columns) => columns.to_vec(),
+            None => dataset
+                .schema()
+                .fields
+                .iter()
+                .map(|f| f.name.clone())
+                .collect(),
+        };
+        let drop_vector_column = !columns.iter().any(|c| c == self.vector_column);
+        if drop_vector_column {
+            columns.push(self.vector_column.to_string());
+        }
+        let projection = dataset.schema().project(&columns)?;
+        let batch = dataset.take_rows(row_ids, &projection).await?;
+
+        // Keep the row ids with the rows while they are reordered
+        let mut fields = batch.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new(ROW_ID_COLUMN, DataType::UInt64, true)));
+        let mut arrays = batch.columns().to_vec();
+        arrays.push(Arc::new(UInt64Array::from(row_ids.to_vec())));
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new_with_metadata(
+                fields,
+                batch.schema().metadata().clone(),
+            )),
+            arrays,
+        )?;
+        let has_vector = is_not_null(vector_column_of(&batch, self.vector_column)?)?;
+        let batch = filter_record_batch(&batch, &has_vector)?;
+
+        let results = rescore_batches(
+            batch.schema(),
+            &[batch],
+            self.vector_column,
+            self.query_vector,
+            self.distance_type,
+            drop_vector_column,
+        )?;
+        let mut results = results.slice(0, self.limit.min(results.num_rows()));
+        if !self.with_row_id {
+            let idx = results.schema().index_of(ROW_ID_COLUMN)?;
+            results.remove_column(idx);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_cache() {
+        let cache = FilterCache::new(FilterCacheConfig {
+            max_entries: 2,
+            max_rows: 10,
+        });
+        assert!(cache.get("a = 1", 1).is_none());
+        cache.insert("a = 1", 1, CachedRows::Rows(Arc::new(vec![1, 2])));
+        cache.insert("a = 2", 1, CachedRows::TooMany);
+        assert!(matches!(cache.get("a = 1", 1), Some(CachedRows::Rows(_))));
+        assert!(matches!(cache.get("a = 2", 1), Some(CachedRows::TooMany)));
+
+        // "a = 1" is the least recently used
+        cache.insert("a = 3", 1, CachedRows::TooMany);
+        assert!(cache.get("a = 1", 1).is_none());
+        assert_eq!(cache.metrics().evictions, 1);
+
+        // A new version drops the entries of the old one
+        assert!(cache.get("a = 2", 2).is_none());
+        let metrics = cache.metrics();
+        assert_eq!(metrics.invalidations, 2);
+        assert_eq!(metrics.entries, 0);
+        assert_eq!(metrics.hits, 2);
+        assert_eq!(metrics.misses, 3);
+    }
+}
@@ -0,0 +1,380 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Joining the results of a query with the rows of another table
+//!
+//! [`super::Query::join`] and [`super::VectorQuery::join`] attach the rows
+//! of a second table that have the same key as each result, for example the
+//! metadata of a document that is stored in its own table.  The query runs
+//! first.  The second table is then only read for the keys of the results
+//! when there is a single integer or string key column, otherwise it is
+//! scanned.  Results keep the order of the query, e.g. the nearest rows
+//! first.
+//!
+//! A result matching several rows of the second table is returned once for
+//! each of them.  Null keys match nothing.  The key columns of the second
+//! table are not repeated in the output, and its other columns must not have
+//! the name of a column of the results (see [`Join::right_columns`]).
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow::compute::{concat_batches, filter, is_not_null, take};
+use arrow::row::{RowConverter, SortField};
+use arrow_array::{ArrayRef, RecordBatch, UInt32Array};
+use arrow_schema::{DataType, Schema, SchemaRef};
+use futures::TryStreamExt;
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+use crate::table::primary_key::{key_values, lookup_filter, KEY_LOOKUP_BATCH_SIZE};
+use crate::Table;
+
+use super::distinct::{drop_columns, select_distinct_columns};
+use super::{ExecutableQuery, HasQuery, QueryBase, QueryExecutionOptions, Select};
+
+/// Which results are returned by a join
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    /// Only the results that match a row of the other table
+    Inner,
+    /// Every result, with nulls for the columns of the other table if there
+    /// is no match
+    Left,
+}
+
+/// The results of a query joined with the rows of another table
+///
+/// Created with [`super::Query::join`] or [`super::VectorQuery::join`], see
+/// [`self`] for more details.
+#[derive(Clone)]
+pub struct Join<Q> {
+    query: Q,
+    right: Table,
+    on: Vec<String>,
+    join_type: JoinType,
+    right_columns: Option<Vec<String>>,
+}
+
+impl<Q: HasQuery> Join<Q> {
+    pub(crate) fn new(query: Q, right: Table, on: Vec<String>, join_type: JoinType) -> Self {
+        Self {
+            query,
+            right,
+            on,
+            join_type,
+            right_columns: None,
+        }
+    }
+
+    /// Only attach these columns of the other table
+    ///
+    /// By default every column of the other table is attached.
+    pub fn right_columns(mut self, columns: &[impl AsRef<str>]) -> Self {
+        self.right_columns = Some(columns.iter().map(|c| c.as_ref().to_string()).collect());
+        self
+    }
+}
+
+fn key_columns(batch: &RecordBatch, on: &[String], side: &str) -> Result<Vec<ArrayRef>> {
+    on.iter()
+        .map(|column| {
+            batch
+                .column_by_name(column)
+                .cloned()
+                .ok_or_else(|| Error::InvalidInput {
+                    message: format!("the join column '{}' is not in the {}", column, side),
+                })
+        })
+        .collect()
+}
+
+/// The filter selecting the rows of the other table with the keys of the
+/// results, in chunks, or None if the other table must be scanned
+fn key_filters(
+    keys: &[ArrayRef],
+    on: &[String],
+    right_schema: &Schema,
+) -> Result<Option<Vec<String>>> {
+    let [key] = keys else {
+        return Ok(None);
+    };
+    let data_type = key.data_type();
+    if !(data_type.is_integer() || matches!(data_type, DataType::Utf8 | DataType::LargeUtf8)) {
+        return Ok(None);
+    }
+    let key = filter(key, &is_not_null(key)?)?;
+    let mut seen = HashSet::new();
+    let values = key_values(&key, &on[0])?
+        .into_iter()
+        .filter(|value| seen.insert(value.clone()))
+        .collect::<Vec<_>>();
+    let filters = values
+        .chunks(KEY_LOOKUP_BATCH_SIZE)
+        .map(|chunk| lookup_filter(right_schema, &on[0], chunk))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(filters))
+}
+
+fn output_schema(
+    left: &Schema,
+    right: &Schema,
+    on: &[String],
+    join_type: JoinType,
+) -> Result<SchemaRef> {
+    let mut fields = left.fields().iter().cloned().collect::<Vec<_>>();
+    for field in right.fields() {
+        if on.contains(field.name()) {
+            continue;
+        }
+        if left.field_with_name(field.name()).is_ok() {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the column '{}' is in both tables, select the columns to attach with \
+                     right_columns",
+                    field.name()
+                ),
+            });
+        }
+        let field = match join_type {
+            JoinType::Inner => field.as_ref().clone(),
+            JoinType::Left => field.as_ref().clone().with_nullable(true),
+        };
+        fields.push(Arc::new(field));
+    }
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// Join the rows of `left` with the rows of `right` that have the same keys
+///
+/// The rows are in the order of `left`.
+fn join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    on: &[String],
+    join_type: JoinType,
+) -> Result<RecordBatch> {
+    let schema = output_schema(&left.schema(), &right.schema(), on, join_type)?;
+    let left_keys = key_columns(left, on, "query results")?;
+    let right_keys = key_columns(right, on, "joined table")?;
+    for ((column, left_key), right_key) in on.iter().zip(&left_keys).zip(&right_keys) {
+        if left_key.data_type() != right_key.data_type() {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "cannot join on '{}', it is {} in the query results but {} in the joined table",
+                    column,
+                    left_key.data_type(),
+                    right_key.data_type()
+                ),
+            });
+        }
+    }
+    let converter = RowConverter::new(
+        left_keys
+            .iter()
+            .map(|key| SortField::new(key.data_type().clone()))
+            .collect(),
+    )?;
+    let has_null = |keys: &[ArrayRef], row: usize| keys.iter().any(|key| key.is_null(row));
+
+    let right_rows = converter.convert_columns(&right_keys)?;
+    let mut matches: HashMap<_, Vec<u32>> = HashMap::new();
+    for (row_idx, row) in right_rows.iter().enumerate() {
+        if !has_null(&right_keys, row_idx) {
+            matches.entry(row).or_default().push(row_idx as u32);
+        }
+    }
+
+    let left_rows = converter.convert_columns(&left_keys)?;
+    let mut left_indices = Vec::with_capacity(left.num_rows());
+    let mut right_indices = Vec::with_capacity(left.num_rows());
+    for (row_idx, row) in left_rows.iter().enumerate() {
+        let matched = if has_null(&left_keys, row_idx) {
+            None
+        } else {
+            matches.get(&row)
+        };
+        match (matched, join_type) {
+            (Some(matched), _) => {
+                for right_idx in matched {
+                    left_indices.push(row_idx as u32);
+                    right_indices.push(Some(*right_idx));
+                }
+            }
+            (None, JoinType::Left) => {
+                left_indices.push(row_idx as u32);
+                right_indices.push(None);
+            }
+            (None, JoinType::Inner) => {}
+        }
+    }
+
+    let left_indices = UInt32Array::from(left_indices);
+    let right_indices = UInt32Array::from(right_indices);
+    let mut columns = left
+        .columns()
+        .iter()
+        .map(|column| Ok(take(column.as_ref(), &left_indices, None)?))
+        .collect::<Result<Vec<_>>>()?;
+    for (field, column) in right.schema().fields().iter().zip(right.columns()) {
+        if !on.contains(field.name()) {
+            columns.push(take(column.as_ref(), &right_indices, None)?);
+        }
+    }
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+impl<Q: ExecutableQuery + HasQuery + Clone + Send + Sync> ExecutableQuery for Join<Q> {
+    async fn execute_with_options(
+        &self,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        let mut query = self.query.clone();
+        let added = select_distinct_columns(&mut query.mut_query().select, &self.on);
+        let stream = query.execute_with_options(options.clone()).await?;
+        let schema = stream.schema();
+        let batches = stream.try_collect::<Vec<_>>().await?;
+        let left = concat_batches(&schema, &batches)?;
+
+        let right_schema = self.right.schema().await?;
+        let right_select = match &self.right_columns {
+            Some(columns) => {
+                let mut select = Select::Columns(columns.clone());
+                select_distinct_columns(&mut select, &self.on);
+                select
+            }
+            None => Select::All,
+        };
+        let left_keys = key_columns(&left, &self.on, "query results")?;
+        let filters = match key_filters(&left_keys, &self.on, &right_schema)? {
+            Some(filters) => filters.into_iter().map(Some).collect(),
+            None => vec![None],
+        };
+        let mut right_batches = Vec::new();
+        let mut right_batch_schema = None;
+        for filter in filters {
+            let mut right_query = self.right.query().select(right_select.clone());
+            if let Some(filter) = filter {
+                right_query = right_query.only_if(filter);
+            }
+            let stream = right_query.execute_with_options(options.clone()).await?;
+            right_batch_schema = Some(stream.schema());
+            right_batches.extend(stream.try_collect::<Vec<_>>().await?);
+        }
+        let right = match right_batch_schema {
+            Some(schema) => concat_batches(&schema, &right_batches)?,
+            // There were no keys to look up
+            None => RecordBatch::new_empty(Arc::new(Schema::new(
+                right_schema
+                    .fields()
+                    .iter()
+                    .filter(|field| match &right_select {
+                        Select::Columns(columns) => columns.contains(field.name()),
+                        _ => true,
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            ))),
+        };
+
+        let batch = join_batches(&left, &right, &self.on, self.join_type)?;
+        let batch = drop_columns(&batch, &added)?;
+        Ok(Box::pin(SimpleRecordBatchStream {
+            schema: batch.schema(),
+            stream: futures::stream::once(async move { Ok(batch) }),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Int32Type, Array, Int32Array, StringArray};
+    use arrow_schema::Field;
+
+    use super::*;
+
+    #[test]
+    fn test_join_batches() {
+        let left = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("doc_id", DataType::Int32, true),
+                Field::new("_distance", DataType::Int32, false),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(3), Some(1), None, Some(2)])),
+                Arc::new(Int32Array::from(vec![10, 20, 30, 40])),
+            ],
+        )
+        .unwrap();
+        let right = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("doc_id", DataType::Int32, true),
+                Field::new("title", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), Some(3), Some(1), None])),
+                Arc::new(StringArray::from(vec!["one", "three", "uno", "null"])),
+            ],
+        )
+        .unwrap();
+        let on = vec!["doc_id".to_string()];
+
+        let inner = join_batches(&left, &right, &on, JoinType::Inner).unwrap();
+        assert_eq!(inner.num_columns(), 3);
+        assert_eq!(
+            inner["doc_id"]
+                .as_primitive::<Int32Type>()
+                .values()
+                .to_vec(),
+            vec![3, 1, 1]
+        );
+        assert_eq!(
+            inner["title"]
+                .as_string::<i32>()
+                .iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            vec!["three", "one", "uno"]
+        );
+
+        let joined = join_batches(&left, &right, &on, JoinType::Left).unwrap();
+        assert_eq!(joined.num_rows(), 5);
+        assert!(joined
+            .schema()
+            .field_with_name("title")
+            .unwrap()
+            .is_nullable());
+        let titles = joined["title"].as_string::<i32>();
+        assert!(titles.is_null(3));
+        assert!(titles.is_null(4));
+
+        // Columns in both tables are ambiguous
+        let on = vec!["title".to_string()];
+        let conflicting = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("title", DataType::Utf8, false),
+                Field::new("_distance", DataType::Int32, false),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec!["one"])),
+                Arc::new(Int32Array::from(vec![1])),
+            ],
+        )
+        .unwrap();
+        assert!(matches!(
+            join_batches(&conflicting, &left, &on, JoinType::Inner),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
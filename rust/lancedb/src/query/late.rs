@@ -0,0 +1,189 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Late materialization of columns, see [`super::QueryBase::late_materialization`]
+
+use std::sync::Arc;
+
+use arrow_array::{cast::AsArray, types::UInt64Type, ArrayRef, RecordBatch};
+use arrow_schema::{Schema, SchemaRef};
+use futures::StreamExt;
+use lance::dataset::Dataset;
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+
+use super::{Select, VectorQuery};
+
+/// The row id column added by the scanner
+pub(crate) const ROW_ID_COLUMN: &str = "_rowid";
+
+/// Where an output column comes from
+enum Source {
+    /// The column at this index of the query results
+    Early(usize),
+    /// The column at this index of the late columns
+    Late(usize),
+}
+
+/// Fetches some of the selected columns after the results are known
+///
+/// The query is run with only the early columns and the row ids.  The late
+/// columns are then read with a take of the result rows.
+pub(crate) struct LateMaterialization {
+    dataset: Arc<Dataset>,
+    /// The late columns that are selected
+    late: Vec<String>,
+    /// The selected columns, in the order they should be returned
+    output: Vec<String>,
+    /// Keep the row id column, it was requested by an earlier step
+    keep_row_id: bool,
+}
+
+impl LateMaterialization {
+    /// Remove the late columns from the projection of the query
+    ///
+    /// Returns `None` (and leaves the query untouched) if none of the late
+    /// columns are selected.  Columns used by `distinct_on` are always read
+    /// early since they are needed to pick the results.
+    pub(crate) fn plan(
+        dataset: Arc<Dataset>,
+        query: &mut VectorQuery,
+        late_columns: &[String],
+    ) -> Result<Option<Self>> {
+        let schema = Schema::from(dataset.schema());
+        for column in late_columns {
+            if schema.field_with_name(column).is_err() {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the late materialization column '{}' does not exist",
+                        column
+                    ),
+                });
+            }
+        }
+        let Some(selected) = query.base.select.column_names(&schema)? else {
+            return Err(Error::InvalidInput {
+                message: "late materialization cannot be combined with a dynamic projection"
+                    .to_string(),
+            });
+        };
+        let needed = query.base.distinct_on.clone().unwrap_or_default();
+        let is_late = |column: &String| late_columns.contains(column) && !needed.contains(column);
+        let late = selected
+            .iter()
+            .filter(|column| is_late(column))
+            .cloned()
+            .collect::<Vec<_>>();
+        if late.is_empty() {
+            return Ok(None);
+        }
+        query.base.select = Select::Columns(
+            selected
+                .iter()
+                .filter(|column| !is_late(column))
+                .cloned()
+                .collect(),
+        );
+        let keep_row_id = query.base.with_row_id;
+        query.base.with_row_id = true;
+        Ok(Some(Self {
+            dataset,
+            late,
+            output: selected,
+            keep_row_id,
+        }))
+    }
+
+    /// The source of each output column
+    ///
+    /// Columns added by the search (e.g. `_distance`) come after the selected
+    /// columns and the row id is removed, unless it was requested.
+    fn layout(&self, early: &Schema) -> Vec<Source> {
+        let mut layout = Vec::with_capacity(early.fields().len() + self.late.len());
+        for column in &self.output {
+            if let Some(idx) = self.late.iter().position(|late| late == column) {
+                layout.push(Source::Late(idx));
+            } else if let Ok(idx) = early.index_of(column) {
+                layout.push(Source::Early(idx));
+            }
+        }
+        for (idx, field) in early.fields().iter().enumerate() {
+            let is_row_id = field.name() == ROW_ID_COLUMN && !self.keep_row_id;
+            if !is_row_id && !self.output.contains(field.name()) {
+                layout.push(Source::Early(idx));
+            }
+        }
+        layout
+    }
+
+    fn output_schema(&self, early: &Schema) -> Result<SchemaRef> {
+        let late = Schema::from(&self.dataset.schema().project(&self.late)?);
+        let fields = self
+            .layout(early)
+            .into_iter()
+            .map(|source| match source {
+                Source::Early(idx) => early.field(idx).clone(),
+                Source::Late(idx) => late.field(idx).clone(),
+            })
+            .collect::<Vec<_>>();
+        Ok(Arc::new(Schema::new_with_metadata(
+            fields,
+            early.metadata().clone(),
+        )))
+    }
+
+    async fn materialize(&self, schema: SchemaRef, batch: RecordBatch) -> Result<RecordBatch> {
+        let row_ids = batch
+            .column_by_name(ROW_ID_COLUMN)
+            .ok_or_else(|| Error::Schema {
+                message: format!(
+                    "the column '{}' is missing from the search results",
+                    ROW_ID_COLUMN
+                ),
+            })?
+            .as_primitive::<UInt64Type>()
+            .values()
+            .to_vec();
+        let projection = self.dataset.schema().project(&self.late)?;
+        let late = self.dataset.take_rows(&row_ids, &projection).await?;
+        let columns = self
+            .layout(&batch.schema())
+            .into_iter()
+            .map(|source| match source {
+                Source::Early(idx) => batch.column(idx).clone(),
+                Source::Late(idx) => late.column(idx).clone(),
+            })
+            .collect::<Vec<ArrayRef>>();
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+
+    /// Read the late columns for the results of the query
+    pub(crate) fn apply(
+        self,
+        stream: SendableRecordBatchStream,
+    ) -> Result<SendableRecordBatchStream> {
+        let schema = self.output_schema(&stream.schema())?;
+        let batch_schema = schema.clone();
+        let this = Arc::new(self);
+        Ok(Box::pin(SimpleRecordBatchStream {
+            schema,
+            stream: stream.then(move |batch| {
+                let this = this.clone();
+                let schema = batch_schema.clone();
+                async move { this.materialize(schema, batch?).await }
+            }),
+        }))
+    }
+}
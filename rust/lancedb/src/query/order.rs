@@ -0,0 +1,339 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ordering the results of queries without a vector
+//!
+//! [`crate::query::Query::order_by`] sorts the results by one or more
+//! columns, with nulls last.  With a limit only the best rows seen so far are
+//! kept in memory, without a limit all results are sorted in memory.
+//!
+//! Lance has no sorted scan, so the order is pushed down through the
+//! [fragment statistics](crate::table::fragment_stats) instead.  If
+//! statistics were computed for the first order column, and the data was
+//! written in (roughly) that order (see
+//! [`crate::table::AddDataBuilder::sort_by`]), the fragments that can
+//! not contain any of the first rows are skipped: a bound such as
+//! `created_at >= <value>` is added to the filter.  If fewer rows than the
+//! limit match the bound (because of the filter or deletions) the query is
+//! run again without it.
+
+use arrow::compute::{concat_batches, lexsort_to_indices, SortColumn};
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, SchemaRef, SortOptions};
+use lance::dataset::Dataset;
+
+use crate::arrow::take_record_batch;
+use crate::connection::memory::{maybe_grow, MemoryReservation};
+use crate::error::{Error, Result};
+use crate::table::fragment_stats::{FragmentStatistics, StatisticValue};
+
+/// The direction of an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Smallest values first
+    Asc,
+    /// Largest values first
+    Desc,
+}
+
+impl SortOrder {
+    fn options(self) -> SortOptions {
+        SortOptions {
+            descending: self == Self::Desc,
+            nulls_first: false,
+        }
+    }
+}
+
+/// Check that the rows of a table can be ordered by the columns
+pub(crate) fn check_order_columns(
+    schema: &arrow_schema::Schema,
+    order_by: &[(String, SortOrder)],
+) -> Result<()> {
+    for (column, _) in order_by {
+        let field = schema
+            .field_with_name(column)
+            .map_err(|_| Error::InvalidInput {
+                message: format!(
+                    "cannot order by '{}': there is no top level column with that name",
+                    column
+                ),
+            })?;
+        if matches!(
+            field.data_type(),
+            DataType::List(_)
+                | DataType::LargeList(_)
+                | DataType::FixedSizeList(_, _)
+                | DataType::Struct(_)
+                | DataType::Map(_, _)
+        ) {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "cannot order by '{}': columns of type {} can not be ordered",
+                    column,
+                    field.data_type()
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Sort a batch, keeping at most `limit` rows
+fn sort_batch(
+    batch: &RecordBatch,
+    order_by: &[(String, SortOrder)],
+    limit: Option<usize>,
+) -> Result<RecordBatch> {
+    let columns = order_by
+        .iter()
+        .map(|(column, order)| {
+            let values = batch
+                .column_by_name(column)
+                .ok_or_else(|| Error::InvalidInput {
+                    message: format!("the order column '{}' is not in the results", column),
+                })?
+                .clone();
+            Ok(SortColumn {
+                values,
+                options: Some(order.options()),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let indices = lexsort_to_indices(&columns, limit)?;
+    Ok(take_record_batch(batch, &indices)?)
+}
+
+/// Collects the first rows of a stream in order
+pub(crate) struct OrderedRows {
+    schema: SchemaRef,
+    order_by: Vec<(String, SortOrder)>,
+    limit: Option<usize>,
+    batches: Vec<RecordBatch>,
+    num_rows: usize,
+    /// The number of rows added, including the ones that were dropped
+    seen: usize,
+    /// The memory of the batches, if it is limited
+    reservation: Option<MemoryReservation>,
+}
+
+impl OrderedRows {
+    pub(crate) fn new(
+        schema: SchemaRef,
+        order_by: Vec<(String, SortOrder)>,
+        limit: Option<usize>,
+    ) -> Self {
+        Self {
+            schema,
+            order_by,
+            limit,
+            batches: Vec::new(),
+            num_rows: 0,
+            seen: 0,
+            reservation: None,
+        }
+    }
+
+    /// Reserve the memory of the rows, failing once it exceeds the budget
+    pub(crate) fn with_reservation(mut self, reservation: Option<MemoryReservation>) -> Self {
+        self.reservation = reservation;
+        self
+    }
+
+    pub(crate) fn push(&mut self, batch: RecordBatch) -> Result<()> {
+        maybe_grow(&mut self.reservation, batch.get_array_memory_size())?;
+        self.seen += batch.num_rows();
+        self.num_rows += batch.num_rows();
+        self.batches.push(batch);
+        // Keep the memory bounded by the limit, sorting only once enough
+        // batches have piled up
+        if let Some(limit) = self.limit {
+            if self.num_rows > limit.max(1024) * 2 {
+                let sorted = self.sorted()?;
+                if let Some(reservation) = &mut self.reservation {
+                    reservation.try_resize(sorted.get_array_memory_size())?;
+                }
+                self.num_rows = sorted.num_rows();
+                self.batches = vec![sorted];
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of rows that were added
+    pub(crate) fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// The first rows, in order
+    pub(crate) fn sorted(&self) -> Result<RecordBatch> {
+        let batch = concat_batches(&self.schema, &self.batches)?;
+        sort_batch(&batch, &self.order_by, self.limit)
+    }
+}
+
+/// A filter that only keeps the rows that may be among the first `limit` rows
+///
+/// The bound is chosen from the fragment statistics of the first order
+/// column so that the fragments covering the first `limit` values match it.
+/// Every row in those fragments matches the bound, so if the (unfiltered)
+/// table is unchanged at least `limit` rows match.  Returns None if the
+/// bound would not skip any fragment.
+pub(crate) fn order_bound(
+    dataset: &Dataset,
+    column: &str,
+    order: SortOrder,
+    limit: usize,
+) -> Result<Option<String>> {
+    let stats = FragmentStatistics::from_metadata(&dataset.schema().metadata)?;
+    let Some(field) = dataset.schema().field(column) else {
+        return Ok(None);
+    };
+    if stats.field_ids.get(column) != Some(&field.id) {
+        return Ok(None);
+    }
+    // The statistics of the current fragments, by their first value in the order
+    let mut fragments = dataset
+        .get_fragments()
+        .iter()
+        .filter_map(|f| stats.fragments.get(&(f.id() as u64))?.get(column))
+        .filter_map(|s| Some((s.min.clone()?, s.max.clone()?, s.num_rows - s.null_count)))
+        .collect::<Vec<_>>();
+    let first = |(min, max, _): &(StatisticValue, StatisticValue, u64)| match order {
+        SortOrder::Asc => min.clone(),
+        SortOrder::Desc => max.clone(),
+    };
+    let last = |(min, max, _): &(StatisticValue, StatisticValue, u64)| match order {
+        SortOrder::Asc => max.clone(),
+        SortOrder::Desc => min.clone(),
+    };
+    let before = |a: &StatisticValue, b: &StatisticValue| match order {
+        SortOrder::Asc => a < b,
+        SortOrder::Desc => a > b,
+    };
+    fragments.sort_by(|a, b| {
+        let (a, b) = (first(a), first(b));
+        if before(&a, &b) {
+            std::cmp::Ordering::Less
+        } else if before(&b, &a) {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+
+    let mut bound: Option<StatisticValue> = None;
+    let mut covered = 0;
+    let mut taken = 0;
+    for fragment in &fragments {
+        if covered >= limit as u64 {
+            break;
+        }
+        let value = last(fragment);
+        if bound.as_ref().map_or(true, |b| before(b, &value)) {
+            bound = Some(value);
+        }
+        covered += fragment.2;
+        taken += 1;
+    }
+    let Some(bound) = bound.filter(|_| covered >= limit as u64) else {
+        return Ok(None);
+    };
+    // Only worth it if a fragment can be skipped
+    if !fragments[taken..]
+        .iter()
+        .any(|fragment| before(&bound, &first(fragment)))
+    {
+        return Ok(None);
+    }
+    let Some(literal) = bound.to_literal() else {
+        return Ok(None);
+    };
+    let comparison = match order {
+        SortOrder::Asc => "<=",
+        SortOrder::Desc => ">=",
+    };
+    Ok(Some(format!("`{}` {} {}", column, comparison, literal)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, StringArray};
+    use arrow_schema::{Field, Schema};
+
+    use super::*;
+
+    #[test]
+    fn test_ordered_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ts", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = |ts: Vec<Option<i32>>, names: Vec<&str>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(ts)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .unwrap()
+        };
+        let order_by = vec![
+            ("ts".to_string(), SortOrder::Desc),
+            ("name".to_string(), SortOrder::Asc),
+        ];
+        let mut rows = OrderedRows::new(schema.clone(), order_by.clone(), Some(3));
+        rows.push(batch(vec![Some(1), None, Some(3)], vec!["a", "b", "c"]))
+            .unwrap();
+        rows.push(batch(vec![Some(3), Some(2)], vec!["b", "d"]))
+            .unwrap();
+        assert_eq!(rows.seen(), 5);
+        let sorted = rows.sorted().unwrap();
+        let names = sorted.column(1).as_string::<i32>();
+        assert_eq!(
+            names.iter().flatten().collect::<Vec<_>>(),
+            vec!["b", "c", "d"]
+        );
+
+        // Nulls are last, in both directions
+        let mut rows = OrderedRows::new(
+            schema.clone(),
+            vec![("ts".to_string(), SortOrder::Asc)],
+            None,
+        );
+        rows.push(batch(vec![None, Some(2), Some(1)], vec!["a", "b", "c"]))
+            .unwrap();
+        let sorted = rows.sorted().unwrap();
+        let ts = sorted.column(0).as_primitive::<Int32Type>();
+        assert_eq!(ts.iter().collect::<Vec<_>>(), vec![Some(1), Some(2), None]);
+    }
+
+    #[test]
+    fn test_check_order_columns() {
+        let schema = Schema::new(vec![
+            Field::new("ts", DataType::Int32, true),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]);
+        assert!(check_order_columns(&schema, &[("ts".to_string(), SortOrder::Asc)]).is_ok());
+        assert!(check_order_columns(&schema, &[("nope".to_string(), SortOrder::Asc)]).is_err());
+        assert!(check_order_columns(&schema, &[("vector".to_string(), SortOrder::Asc)]).is_err());
+    }
+}
@@ -0,0 +1,584 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multi-stage search pipelines
+//!
+//! A [`Pipeline`] describes a search as a set of stages:
+//!
+//! prefilter → ANN search → rerank → post-filter → limit → payload join
+//!
+//! The stages are declared, not executed in the order the builder methods are
+//! called.  The pipeline decides where each stage runs:
+//!
+//! * Filters are evaluated by the search itself.  If there is both a prefilter
+//!   and a post-filter the post-filter is pushed into the prefilter, which can
+//!   only return more results.
+//! * Without a reranker the limit is pushed into the search.  With a reranker
+//!   the search returns [`Pipeline::candidates`] rows which are reranked and
+//!   then limited.
+//! * The exact distance reranker is fused into the search
+//!   (see [`super::VectorQuery::rescore_exact`]).
+//! * The payload columns are only fetched for the final results.  The search
+//!   and the reranker only read the columns they need.
+//!
+//! [`PipelineResults::stats`] reports what each stage did.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arrow::compute::{concat_batches, take};
+use arrow::row::{RowConverter, SortField};
+use arrow_array::{cast::AsArray, Array, ArrayRef, RecordBatch, UInt32Array};
+use arrow_schema::{DataType, Schema, SchemaRef};
+use futures::TryStreamExt;
+
+use crate::error::{Error, Result};
+use crate::table::TableInternal;
+use crate::DistanceType;
+
+use super::{ExecutableQuery, IntoQueryVector, Query, QueryBase, Select, VectorQuery};
+
+/// With a reranker, and no explicit number of candidates, the search returns
+/// this many candidates per result
+pub const DEFAULT_CANDIDATES_PER_RESULT: usize = 4;
+
+/// Reorders the candidates returned by the search
+pub trait Reranker: Debug + Send + Sync {
+    /// The columns the reranker reads, in addition to `_distance`
+    fn required_columns(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Reorder the candidates, best first
+    ///
+    /// The reranker may add columns (e.g. a relevance score) and may remove rows.
+    fn rerank(&self, candidates: RecordBatch) -> Result<RecordBatch>;
+}
+
+/// The rerank stage of a [`Pipeline`]
+#[derive(Debug, Clone)]
+pub enum Rerank {
+    /// Reorder by the exact distance to the original vectors
+    Exact,
+    /// Reorder with a custom reranker
+    Custom(Arc<dyn Reranker>),
+}
+
+/// What a stage of a [`Pipeline`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageStats {
+    /// The name of the stage, e.g. "search" or "rerank"
+    pub stage: String,
+    /// The number of rows the stage received
+    pub rows_in: usize,
+    /// The number of rows the stage produced
+    pub rows_out: usize,
+    /// How long the stage took
+    pub elapsed: Duration,
+    /// How the stage was placed, e.g. "post-filter pushed into the search"
+    pub notes: Vec<String>,
+}
+
+impl StageStats {
+    fn new(stage: &str, rows_in: usize, rows_out: usize, start: Instant) -> Self {
+        Self {
+            stage: stage.to_string(),
+            rows_in,
+            rows_out,
+            elapsed: start.elapsed(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// The results of a [`Pipeline`]
+#[derive(Debug, Clone)]
+pub struct PipelineResults {
+    /// The schema of the results
+    pub schema: SchemaRef,
+    /// The results, best first
+    pub batches: Vec<RecordBatch>,
+    /// One entry for each stage that ran, in the order they ran
+    pub stats: Vec<StageStats>,
+}
+
+/// A builder for multi-stage searches, see the [module docs](self)
+///
+/// Created with [`crate::Table::pipeline`]
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    parent: Arc<dyn TableInternal>,
+    prefilter: Option<String>,
+    query_vector: Option<Arc<dyn Array>>,
+    column: Option<String>,
+    nprobes: Option<usize>,
+    distance_type: Option<DistanceType>,
+    candidates: Option<usize>,
+    rerank: Option<Rerank>,
+    postfilter: Option<String>,
+    limit: usize,
+    select: Option<Vec<String>>,
+    payload: Option<(String, Vec<String>)>,
+}
+
+impl Pipeline {
+    pub(crate) fn new(parent: Arc<dyn TableInternal>) -> Self {
+        Self {
+            parent,
+            prefilter: None,
+            query_vector: None,
+            column: None,
+            nprobes: None,
+            distance_type: None,
+            candidates: None,
+            rerank: None,
+            postfilter: None,
+            limit: super::DEFAULT_TOP_K,
+            select: None,
+            payload: None,
+        }
+    }
+
+    /// Only search the rows that match the filter
+    pub fn prefilter(mut self, filter: impl AsRef<str>) -> Self {
+        self.prefilter = Some(filter.as_ref().to_string());
+        self
+    }
+
+    /// The ANN search stage, this stage is required
+    ///
+    /// See [`Query::nearest_to`]
+    pub fn nearest_to(mut self, vector: impl IntoQueryVector) -> Result<Self> {
+        self.query_vector = Some(vector.to_query_vector(&DataType::Float32, "default")?);
+        Ok(self)
+    }
+
+    /// The vector column to search, see [`VectorQuery::column`]
+    pub fn column(mut self, column: &str) -> Self {
+        self.column = Some(column.to_string());
+        self
+    }
+
+    /// See [`VectorQuery::nprobes`]
+    pub fn nprobes(mut self, nprobes: usize) -> Self {
+        self.nprobes = Some(nprobes);
+        self
+    }
+
+    /// See [`VectorQuery::distance_type`]
+    pub fn distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.distance_type = Some(distance_type);
+        self
+    }
+
+    /// The number of candidates the search passes to the reranker
+    ///
+    /// Defaults to [`DEFAULT_CANDIDATES_PER_RESULT`] times the limit.  This is
+    /// ignored if there is no reranker.
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.candidates = Some(candidates);
+        self
+    }
+
+    /// Reorder the candidates before the limit is applied
+    pub fn rerank(mut self, rerank: Rerank) -> Self {
+        self.rerank = Some(rerank);
+        self
+    }
+
+    /// Only keep the results that match the filter
+    pub fn postfilter(mut self, filter: impl AsRef<str>) -> Self {
+        self.postfilter = Some(filter.as_ref().to_string());
+        self
+    }
+
+    /// The number of results, defaults to 10
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// The columns returned by the search
+    ///
+    /// By default all columns are returned, or, if there is a payload join,
+    /// only the key column.
+    pub fn select(mut self, columns: &[impl AsRef<str>]) -> Self {
+        self.select = Some(columns.iter().map(|c| c.as_ref().to_string()).collect());
+        self
+    }
+
+    /// Fetch the given columns for the final results only
+    ///
+    /// Large columns (e.g. the document text) are then not read for candidates
+    /// that are discarded by the reranker or the limit.  The results are joined
+    /// on `key`, which should be unique and must be an integer or string column.
+    pub fn join_payload(mut self, key: &str, columns: &[impl AsRef<str>]) -> Self {
+        self.payload = Some((
+            key.to_string(),
+            columns.iter().map(|c| c.as_ref().to_string()).collect(),
+        ));
+        self
+    }
+
+    /// The columns read by the search
+    fn search_columns(&self) -> Option<Vec<String>> {
+        let mut columns = match (&self.select, &self.payload) {
+            (Some(select), _) => select.clone(),
+            (None, Some((key, _))) => vec![key.clone()],
+            (None, None) => return None,
+        };
+        let mut required = Vec::new();
+        if let Some((key, _)) = &self.payload {
+            required.push(key.clone());
+        }
+        if let Some(Rerank::Custom(reranker)) = &self.rerank {
+            required.extend(reranker.required_columns());
+        }
+        for column in required {
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+        Some(columns)
+    }
+
+    async fn search(&self, stats: &mut Vec<StageStats>) -> Result<RecordBatch> {
+        let query_vector = self
+            .query_vector
+            .clone()
+            .ok_or_else(|| Error::InvalidInput {
+                message: "a pipeline requires a nearest_to stage".to_string(),
+            })?;
+        let mut notes = Vec::new();
+        let mut query = VectorQuery::new(Query::new(self.parent.clone()));
+        query.query_vector = Some(query_vector);
+        query.column = self.column.clone();
+        query.distance_type = self.distance_type;
+        if let Some(nprobes) = self.nprobes {
+            query = query.nprobes(nprobes);
+        }
+        let filter = match (&self.prefilter, &self.postfilter) {
+            (Some(pre), Some(post)) => {
+                notes.push("post-filter pushed into the prefilter".to_string());
+                Some(format!("({}) AND ({})", pre, post))
+            }
+            (Some(pre), None) => Some(pre.clone()),
+            (None, Some(post)) => {
+                query.prefilter = false;
+                Some(post.clone())
+            }
+            (None, None) => None,
+        };
+        if let Some(filter) = filter {
+            query = query.only_if(filter);
+        }
+        let limit = match &self.rerank {
+            None => {
+                notes.push("limit pushed into the search".to_string());
+                self.limit
+            }
+            Some(rerank) => {
+                if let Rerank::Exact = rerank {
+                    notes.push("exact rerank fused into the search".to_string());
+                    query.rescore = true;
+                }
+                self.candidates
+                    .unwrap_or(self.limit * DEFAULT_CANDIDATES_PER_RESULT)
+                    .max(self.limit)
+            }
+        };
+        query = query.limit(limit);
+        if let Some(columns) = self.search_columns() {
+            query = query.select(Select::Columns(columns));
+        }
+        if self.payload.is_some() {
+            notes.push("payload join deferred until after the limit".to_string());
+        }
+
+        let start = Instant::now();
+        let stream = query.execute().await?;
+        let schema = stream.schema();
+        let batches = stream.try_collect::<Vec<_>>().await?;
+        let batch = concat_batches(&schema, &batches)?;
+        let mut stage = StageStats::new("search", 0, batch.num_rows(), start);
+        stage.notes = notes;
+        stats.push(stage);
+        Ok(batch)
+    }
+
+    async fn join(&self, key: &str, columns: &[String], batch: RecordBatch) -> Result<RecordBatch> {
+        let keys = batch
+            .column_by_name(key)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!("the payload key '{}' is not in the search results", key),
+            })?;
+        let mut payload_columns = vec![key.to_string()];
+        payload_columns.extend(
+            columns
+                .iter()
+                .filter(|c| batch.column_by_name(c).is_none())
+                .cloned(),
+        );
+        if payload_columns.len() == 1 || batch.num_rows() == 0 {
+            return Ok(batch);
+        }
+
+        let filter = format!("{} IN ({})", key, sql_literals(keys)?.join(", "));
+        let stream = Query::new(self.parent.clone())
+            .only_if(filter)
+            .select(Select::Columns(payload_columns.clone()))
+            .execute()
+            .await?;
+        let schema = stream.schema();
+        let payload = concat_batches(&schema, &stream.try_collect::<Vec<_>>().await?)?;
+
+        // Find the payload row of each result
+        let converter = RowConverter::new(vec![SortField::new(keys.data_type().clone())])?;
+        let payload_keys = converter.convert_columns(&[payload.column(0).clone()])?;
+        let positions = payload_keys
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| (row.owned(), idx as u32))
+            .collect::<HashMap<_, _>>();
+        let result_keys = converter.convert_columns(&[keys.clone()])?;
+        let indices = UInt32Array::from(
+            result_keys
+                .iter()
+                .map(|row| positions.get(&row.owned()).copied())
+                .collect::<Vec<_>>(),
+        );
+
+        let mut fields = batch.schema().fields().iter().cloned().collect::<Vec<_>>();
+        let mut arrays = batch.columns().to_vec();
+        for (field, column) in payload
+            .schema()
+            .fields()
+            .iter()
+            .zip(payload.columns())
+            .skip(1)
+        {
+            // Rows deleted since the search have no payload
+            fields.push(Arc::new(field.as_ref().clone().with_nullable(true)));
+            arrays.push(take(column.as_ref(), &indices, None)?);
+        }
+        let schema = Arc::new(Schema::new_with_metadata(
+            fields,
+            batch.schema().metadata().clone(),
+        ));
+        Ok(RecordBatch::try_new(schema, arrays)?)
+    }
+
+    /// Run the pipeline
+    pub async fn execute(&self) -> Result<PipelineResults> {
+        let mut stats = Vec::new();
+        let mut batch = self.search(&mut stats).await?;
+
+        if let Some(Rerank::Custom(reranker)) = &self.rerank {
+            let start = Instant::now();
+            let rows_in = batch.num_rows();
+            batch = reranker.rerank(batch)?;
+            stats.push(StageStats::new("rerank", rows_in, batch.num_rows(), start));
+        }
+
+        if self.rerank.is_some() {
+            let start = Instant::now();
+            let rows_in = batch.num_rows();
+            batch = batch.slice(0, rows_in.min(self.limit));
+            stats.push(StageStats::new("limit", rows_in, batch.num_rows(), start));
+        }
+
+        if let Some((key, columns)) = &self.payload {
+            let start = Instant::now();
+            let rows_in = batch.num_rows();
+            batch = self.join(key, columns, batch).await?;
+            stats.push(StageStats::new(
+                "join_payload",
+                rows_in,
+                batch.num_rows(),
+                start,
+            ));
+        }
+
+        Ok(PipelineResults {
+            schema: batch.schema(),
+            batches: vec![batch],
+            stats,
+        })
+    }
+}
+
+/// Format the values of a key column as SQL literals
+fn sql_literals(keys: &ArrayRef) -> Result<Vec<String>> {
+    let literals = match keys.data_type() {
+        DataType::Utf8 => keys
+            .as_string::<i32>()
+            .iter()
+            .flatten()
+            .map(|v| format!("'{}'", v.replace('\'', "''")))
+            .collect(),
+        DataType::LargeUtf8 => keys
+            .as_string::<i64>()
+            .iter()
+            .flatten()
+            .map(|v| format!("'{}'", v.replace('\'', "''")))
+            .collect(),
+        data_type if data_type.is_integer() => {
+            let formatter = arrow_cast::display::ArrayFormatter::try_new(
+                keys.as_ref(),
+                &arrow_cast::display::FormatOptions::default(),
+            )?;
+            (0..keys.len())
+                .filter(|idx| keys.is_valid(*idx))
+                .map(|idx| formatter.value(idx).to_string())
+                .collect()
+        }
+        data_type => {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "a payload key must be an integer or string column, not {}",
+                    data_type
+                ),
+            })
+        }
+    };
+    Ok(literals)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::compute::sort_to_indices;
+    use arrow_array::{
+        types::{Float32Type, Int32Type},
+        FixedSizeListArray, Int32Array, RecordBatchIterator, StringArray,
+    };
+    use arrow_schema::Field;
+    use tempfile::tempdir;
+
+    use crate::arrow::take_record_batch;
+    use crate::connect;
+
+    use super::*;
+
+    /// Prefers odd ids
+    #[derive(Debug)]
+    struct OddFirst;
+
+    impl Reranker for OddFirst {
+        fn required_columns(&self) -> Vec<String> {
+            vec!["id".to_string()]
+        }
+
+        fn rerank(&self, candidates: RecordBatch) -> Result<RecordBatch> {
+            // Odd ids first, then by the original order
+            let keys = Int32Array::from_iter_values(
+                candidates["id"]
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, id)| (id + 1) % 2 * 1000 + idx as i32),
+            );
+            let order = sort_to_indices(&keys, None, None)?;
+            Ok(take_record_batch(&candidates, &order)?)
+        }
+    }
+
+    #[test]
+    fn test_sql_literals() {
+        let ints: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(-3)]));
+        assert_eq!(sql_literals(&ints).unwrap(), vec!["1", "-3"]);
+        let strings: ArrayRef = Arc::new(StringArray::from(vec!["a", "it's"]));
+        assert_eq!(sql_literals(&strings).unwrap(), vec!["'a'", "'it''s'"]);
+        let floats: ArrayRef = Arc::new(arrow_array::Float32Array::from(vec![1.0]));
+        assert!(sql_literals(&floats).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("text", DataType::Utf8, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| format!("doc {}", i)),
+                )),
+                Arc::new(
+                    FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                        (0..100).map(|i| Some(vec![Some(i as f32), Some(0.0)])),
+                        2,
+                    ),
+                ),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table("docs", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+
+        let results = table
+            .pipeline()
+            .prefilter("id >= 10")
+            .nearest_to(&[0.0, 0.0])
+            .unwrap()
+            .candidates(8)
+            .rerank(Rerank::Custom(Arc::new(OddFirst)))
+            .postfilter("id < 50")
+            .limit(3)
+            .join_payload("id", &["text"])
+            .execute()
+            .await
+            .unwrap();
+
+        let batch = &results.batches[0];
+        assert!(batch.column_by_name("vector").is_none());
+        let ids = batch["id"].as_primitive::<Int32Type>().values().to_vec();
+        assert_eq!(ids, vec![11, 13, 15]);
+        let text = batch["text"].as_string::<i32>();
+        assert_eq!(text.value(0), "doc 11");
+
+        let stages = results
+            .stats
+            .iter()
+            .map(|s| s.stage.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(stages, vec!["search", "rerank", "limit", "join_payload"]);
+        assert_eq!(results.stats[0].rows_out, 8);
+        assert_eq!(results.stats[2].rows_out, 3);
+        assert!(results.stats[0]
+            .notes
+            .contains(&"post-filter pushed into the prefilter".to_string()));
+
+        assert!(matches!(
+            table.pipeline().execute().await,
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
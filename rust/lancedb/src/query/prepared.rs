@@ -0,0 +1,208 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow_schema::{DataType, SchemaRef};
+
+use crate::error::{Error, Result};
+use crate::query::filter::normalize_filter;
+use crate::utils::default_vector_column;
+
+use super::{IntoQueryVector, VectorQuery};
+
+/// A vector query that has been validated against the table schema and
+/// can be executed many times with different parameters.
+///
+/// Created with [`crate::Table::prepare`].  Only the query vector and the
+/// filter change between executions.  The vector column, its dimension, the
+/// tuned search defaults of the column (see [`crate::Table::tune_search`]) and
+/// all other search parameters are resolved once when the query is prepared.
+/// Filters are checked against the schema when the query is prepared and when
+/// a new filter is bound, so that an invalid filter is reported before the
+/// query is executed.
+///
+/// The scan itself is planned by each execution: the plan depends on the
+/// version of the table that is read and Lance plans the scan with the query
+/// vector.
+#[derive(Debug, Clone)]
+pub struct PreparedQuery {
+    template: VectorQuery,
+    schema: SchemaRef,
+    column: String,
+    dim: usize,
+}
+
+impl PreparedQuery {
+    pub(crate) async fn try_new(mut template: VectorQuery) -> Result<Self> {
+        let schema = template.base.parent.schema().await?;
+        let column = match template.column.as_ref() {
+            Some(column) => column.clone(),
+            None => default_vector_column(
+                &schema,
+                template.query_vector.as_ref().map(|v| v.len() as i32),
+            )?,
+        };
+        let field = schema.field_with_name(&column)?;
+        let dim = match field.data_type() {
+            DataType::FixedSizeList(item, dim) if item.data_type().is_floating() => *dim as usize,
+            _ => {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "cannot prepare a vector query on the column '{}' which has data type {}",
+                        column,
+                        field.data_type()
+                    ),
+                })
+            }
+        };
+        template.column = Some(column.clone());
+        if let Some(filter) = &template.base.filter {
+            template.base.filter = Some(normalize_filter(&schema, filter)?);
+        }
+
+        // Resolve the search defaults now, instead of reading them from the
+        // table metadata on each execution
+        let defaults = match template.base.parent.search_defaults().await {
+            Ok(mut defaults) => defaults.remove(&column),
+            Err(Error::NotSupported { .. }) => None,
+            Err(e) => return Err(e),
+        };
+        if let Some(defaults) = defaults {
+            if !template.nprobes_set {
+                template.nprobes = defaults.nprobes;
+            }
+            if !template.refine_factor_set {
+                template.refine_factor = defaults.refine_factor;
+            }
+        }
+        template.nprobes_set = true;
+        template.refine_factor_set = true;
+
+        Ok(Self {
+            template,
+            schema,
+            column,
+            dim,
+        })
+    }
+
+    /// The vector column that will be searched
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// The dimension of the query vectors this query accepts
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Create an executable query from the prepared template
+    ///
+    /// # Arguments
+    ///
+    /// * `vector` - The query vector.  It must have the same dimension as the
+    ///   vector column.
+    /// * `filter` - If present, this replaces the filter (if any) of the template.
+    pub fn bind(&self, vector: impl IntoQueryVector, filter: Option<&str>) -> Result<VectorQuery> {
+        let query_vector = vector.to_query_vector(&DataType::Float32, "default")?;
+        if query_vector.len() != self.dim {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "The dimension of the query vector does not match with the dimension of the vector column '{}': query dim={}, expected vector dim={}",
+                    self.column,
+                    query_vector.len(),
+                    self.dim
+                ),
+            });
+        }
+        let mut query = self.template.clone();
+        query.query_vector = Some(query_vector);
+        if let Some(filter) = filter {
+            query.base.filter = Some(normalize_filter(&self.schema, filter)?);
+        }
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Int32Type, RecordBatchReader};
+    use futures::TryStreamExt;
+    use lance_testing::datagen::{BatchGenerator, IncrementingInt32, RandomVector};
+    use tempfile::tempdir;
+
+    use crate::connect;
+    use crate::query::{ExecutableQuery, QueryBase};
+
+    use super::*;
+
+    fn make_batches() -> impl RecordBatchReader + Send + 'static {
+        let vec = Box::new(RandomVector::new().named("vector".to_string()));
+        let id = Box::new(IncrementingInt32::new().named("id".to_string()));
+        BatchGenerator::new().col(vec).col(id).batch(512)
+    }
+
+    #[tokio::test]
+    async fn test_prepared_query() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", make_batches())
+            .execute()
+            .await
+            .unwrap();
+
+        let template = table.query().limit(5).nearest_to(&[0.0; 4]).unwrap();
+        let prepared = table.prepare(template).await.unwrap();
+        assert_eq!(prepared.column(), "vector");
+        assert_eq!(prepared.dim(), 4);
+
+        for vector in [[0.1; 4], [0.5; 4]] {
+            let batches = prepared
+                .bind(&vector, Some("id % 2 = 0"))
+                .unwrap()
+                .execute()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            let ids = batches
+                .iter()
+                .flat_map(|b| b["id"].as_primitive::<Int32Type>().values().to_vec())
+                .collect::<Vec<_>>();
+            assert_eq!(ids.len(), 5);
+            assert!(ids.iter().all(|id| id % 2 == 0));
+        }
+
+        assert!(matches!(
+            prepared.bind(&[0.1; 3], None),
+            Err(Error::InvalidInput { .. })
+        ));
+        // Filters are checked when they are bound, and when the query is prepared
+        assert!(matches!(
+            prepared.bind(&[0.1; 4], Some("missing > 1")),
+            Err(Error::InvalidFilter { .. })
+        ));
+        let template = table
+            .query()
+            .only_if("missing > 1")
+            .nearest_to(&[0.0; 4])
+            .unwrap();
+        assert!(matches!(
+            table.prepare(template).await,
+            Err(Error::InvalidFilter { .. })
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Distances to reference vectors, see [`super::VectorQuery::distance_to`]
+
+use std::sync::Arc;
+
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use futures::StreamExt;
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::Result;
+use crate::DistanceType;
+
+use super::rescore::{distances_to, vector_column_of};
+
+/// Adds a column with the distance to each reference vector
+pub(crate) struct ReferenceDistances {
+    pub(crate) vector_column: String,
+    pub(crate) references: Vec<(String, Arc<dyn Array>)>,
+    pub(crate) distance_type: DistanceType,
+    /// Remove the vector column after the distances are computed
+    pub(crate) drop_vector_column: bool,
+}
+
+impl ReferenceDistances {
+    fn output_schema(&self, schema: &Schema) -> SchemaRef {
+        let mut fields = schema
+            .fields()
+            .iter()
+            .filter(|f| !(self.drop_vector_column && f.name() == &self.vector_column))
+            .cloned()
+            .collect::<Vec<_>>();
+        for (name, _) in &self.references {
+            fields.push(Arc::new(Field::new(name, DataType::Float32, true)));
+        }
+        Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+    }
+
+    fn add_distances(&self, schema: &SchemaRef, batch: RecordBatch) -> Result<RecordBatch> {
+        let vectors = vector_column_of(&batch, &self.vector_column)?;
+        let mut distances: Vec<ArrayRef> = Vec::with_capacity(self.references.len());
+        for (_, reference) in &self.references {
+            distances.push(Arc::new(distances_to(
+                vectors,
+                reference.as_ref(),
+                self.distance_type,
+            )?));
+        }
+        let mut columns = batch
+            .schema()
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .filter(|(f, _)| !(self.drop_vector_column && f.name() == &self.vector_column))
+            .map(|(_, c)| c.clone())
+            .collect::<Vec<_>>();
+        columns.extend(distances);
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+
+    /// Add the distance columns to the results of a search
+    pub(crate) fn apply(self, stream: SendableRecordBatchStream) -> SendableRecordBatchStream {
+        let schema = self.output_schema(&stream.schema());
+        let batch_schema = schema.clone();
+        Box::pin(SimpleRecordBatchStream {
+            schema,
+            stream: stream.map(move |batch| self.add_distances(&batch_schema, batch?)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{
+        cast::AsArray, types::Float32Type, FixedSizeListArray, Float32Array, Int32Array,
+    };
+    use futures::{stream, TryStreamExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reference_distances() {
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![Some(vec![Some(1.0), Some(0.0)]), None],
+            2,
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("vector", vectors.data_type().clone(), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2])), Arc::new(vectors)],
+        )
+        .unwrap();
+        let inner: SendableRecordBatchStream = Box::pin(SimpleRecordBatchStream {
+            schema,
+            stream: stream::iter(vec![Ok(batch)]),
+        });
+
+        let references = ReferenceDistances {
+            vector_column: "vector".to_string(),
+            references: vec![
+                (
+                    "to_origin".to_string(),
+                    Arc::new(Float32Array::from(vec![0.0, 0.0])),
+                ),
+                (
+                    "to_y".to_string(),
+                    Arc::new(Float32Array::from(vec![0.0, 1.0])),
+                ),
+            ],
+            distance_type: DistanceType::L2,
+            drop_vector_column: true,
+        };
+        let batches = references
+            .apply(inner)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert!(batch.column_by_name("vector").is_none());
+        let to_origin = batch["to_origin"].as_primitive::<Float32Type>();
+        assert_eq!(to_origin.value(0), 1.0);
+        assert!(to_origin.is_null(1));
+        assert_eq!(batch["to_y"].as_primitive::<Float32Type>().value(0), 2.0);
+    }
+}
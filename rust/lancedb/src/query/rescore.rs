@@ -0,0 +1,205 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Exact re-scoring of vector search results, see [`super::VectorQuery::rescore_exact`]
+
+use std::sync::Arc;
+
+use arrow::compute::{concat_batches, sort_to_indices};
+use arrow_array::{
+    cast::AsArray, types::Float32Type, Array, FixedSizeListArray, Float32Array, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+
+use crate::arrow::take_record_batch;
+use crate::error::{Error, Result};
+use crate::DistanceType;
+
+pub(crate) const DISTANCE_COLUMN: &str = "_distance";
+pub(crate) const APPROX_DISTANCE_COLUMN: &str = "_approx_distance";
+
+/// Calculate the distance between two vectors, consistent with the distances
+/// reported by the vector index
+pub(crate) fn distance(distance_type: DistanceType, a: &[f32], b: &[f32]) -> f32 {
+    match distance_type {
+        DistanceType::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+        DistanceType::Cosine => {
+            let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+            let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+            1.0 - dot / (norm_a * norm_b)
+        }
+        DistanceType::Dot => 1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>(),
+    }
+}
+
+/// Find the vector column in the search results
+pub(crate) fn vector_column_of<'a>(
+    batch: &'a RecordBatch,
+    vector_column: &str,
+) -> Result<&'a FixedSizeListArray> {
+    batch
+        .column_by_name(vector_column)
+        .ok_or_else(|| Error::Schema {
+            message: format!(
+                "the vector column '{}' is missing from the search results",
+                vector_column
+            ),
+        })?
+        .as_fixed_size_list_opt()
+        .ok_or_else(|| Error::InvalidInput {
+            message: format!("the column '{}' is not a vector column", vector_column),
+        })
+}
+
+/// Calculate the distance from each vector to the query vector
+///
+/// Null vectors have a null distance.
+pub(crate) fn distances_to(
+    vectors: &FixedSizeListArray,
+    query_vector: &dyn Array,
+    distance_type: DistanceType,
+) -> Result<Float32Array> {
+    let query_vector = arrow_cast::cast(query_vector, &DataType::Float32)?;
+    let query_vector = query_vector.as_primitive::<Float32Type>().values();
+    let dim = vectors.value_length() as usize;
+    if query_vector.len() != dim {
+        return Err(Error::InvalidInput {
+            message: format!(
+                "cannot compare a vector of dimension {} to vectors of dimension {}",
+                query_vector.len(),
+                dim
+            ),
+        });
+    }
+    let values = arrow_cast::cast(vectors.values(), &DataType::Float32)?;
+    let values = values.as_primitive::<Float32Type>().values();
+    Ok(Float32Array::from_iter((0..vectors.len()).map(|i| {
+        if vectors.is_null(i) {
+            None
+        } else {
+            let offset = (vectors.offset() + i) * dim;
+            Some(distance(
+                distance_type,
+                query_vector,
+                &values[offset..offset + dim],
+            ))
+        }
+    })))
+}
+
+/// Recompute the distances of the search results using the original vectors
+///
+/// The distance reported by the index is moved to the `_approx_distance` column
+/// and the `_distance` column is replaced with the exact distance.  The results
+/// are reordered by the exact distance.
+///
+/// If `drop_vector_column` is true then the vector column is removed from the
+/// output (it was only fetched for the purpose of rescoring).
+pub(crate) fn rescore_batches(
+    schema: SchemaRef,
+    batches: &[RecordBatch],
+    vector_column: &str,
+    query_vector: &dyn Array,
+    distance_type: DistanceType,
+    drop_vector_column: bool,
+) -> Result<RecordBatch> {
+    let batch = concat_batches(&schema, batches)?;
+    let vectors = vector_column_of(&batch, vector_column)?;
+    let exact = distances_to(vectors, query_vector, distance_type)?;
+
+    let mut fields = Vec::with_capacity(batch.num_columns() + 1);
+    let mut columns = Vec::with_capacity(batch.num_columns() + 1);
+    for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+        if drop_vector_column && field.name() == vector_column {
+            continue;
+        }
+        if field.name() == DISTANCE_COLUMN {
+            fields.push(Arc::new(Field::new(
+                APPROX_DISTANCE_COLUMN,
+                DataType::Float32,
+                true,
+            )));
+            columns.push(column.clone());
+        } else {
+            fields.push(field.clone());
+            columns.push(column.clone());
+        }
+    }
+    fields.push(Arc::new(Field::new(
+        DISTANCE_COLUMN,
+        DataType::Float32,
+        true,
+    )));
+    columns.push(Arc::new(exact.clone()));
+
+    let schema = Arc::new(Schema::new_with_metadata(
+        fields,
+        batch.schema().metadata().clone(),
+    ));
+    let rescored = RecordBatch::try_new(schema, columns)?;
+    let order = sort_to_indices(&exact, None, None)?;
+    Ok(take_record_batch(&rescored, &order)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{types::Int32Type, Int32Array};
+
+    use super::*;
+
+    #[test]
+    fn test_rescore_batches() {
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some(vec![Some(3.0), Some(0.0)]),
+                Some(vec![Some(1.0), Some(0.0)]),
+                Some(vec![Some(2.0), Some(0.0)]),
+            ],
+            2,
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("vector", vectors.data_type().clone(), true),
+            Field::new(DISTANCE_COLUMN, DataType::Float32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 1, 2])),
+                Arc::new(vectors),
+                Arc::new(Float32Array::from(vec![0.5, 0.6, 0.7])),
+            ],
+        )
+        .unwrap();
+
+        let query = Float32Array::from(vec![0.0, 0.0]);
+        let rescored =
+            rescore_batches(schema, &[batch], "vector", &query, DistanceType::L2, true).unwrap();
+
+        assert!(rescored.column_by_name("vector").is_none());
+        let ids = rescored["id"].as_primitive::<Int32Type>().values().to_vec();
+        assert_eq!(ids, vec![1, 2, 0]);
+        let exact = rescored[DISTANCE_COLUMN]
+            .as_primitive::<Float32Type>()
+            .values()
+            .to_vec();
+        assert_eq!(exact, vec![1.0, 4.0, 9.0]);
+        let approx = rescored[APPROX_DISTANCE_COLUMN]
+            .as_primitive::<Float32Type>()
+            .values()
+            .to_vec();
+        assert_eq!(approx, vec![0.6, 0.7, 0.5]);
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scans that can be resumed after a failure
+//!
+//! A resumable scan reads the version of the table that was current when it
+//! started, one fragment at a time in the order of the fragment ids, and
+//! returns the rows of each fragment in the order they are stored.  Because
+//! that order never changes for a version, the position of the scan is fully
+//! described by the fragment being read and the number of its rows that were
+//! returned, which is what a [`ScanCheckpoint`] records.
+//!
+//! Resuming reads the same version again, skips the fragments that were
+//! completed and the rows of the current fragment that were returned, so
+//! an export job that fails after hours only reads what is left.  The query
+//! must be the same when the scan is resumed, and the version must not be
+//! removed by a cleanup (see [`crate::Table::optimize`]) before the scan has
+//! completed.
+//!
+//! Queries that order their results ([`super::Query::order_by`] and
+//! [`super::QueryBase::distinct_on`]) can not be resumed, nor can a query
+//! that reads an older version with [`super::QueryBase::version_at`].  The
+//! limit of the query applies to the whole scan.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::arrow::SendableRecordBatchStream;
+use crate::error::{Error, Result};
+use crate::table::TableInternal;
+
+use super::{Query, QueryExecutionOptions};
+
+/// The position of a resumable scan
+///
+/// Every row up to and including the batch that came with the checkpoint
+/// was returned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanCheckpoint {
+    /// The version of the table that is scanned
+    pub version: u64,
+    /// The fragment being read, all the fragments with a lower id were
+    /// completed
+    pub fragment_id: u64,
+    /// The number of rows of the fragment that were returned
+    pub fragment_rows: usize,
+    /// The number of rows returned by the whole scan
+    pub rows: usize,
+}
+
+impl ScanCheckpoint {
+    /// Serialize the checkpoint, for example to store it with the job
+    pub fn to_token(&self) -> String {
+        // Serializing a struct of integers can not fail
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Parse a checkpoint made with [`ScanCheckpoint::to_token`]
+    pub fn from_token(token: &str) -> Result<Self> {
+        serde_json::from_str(token).map_err(|e| Error::InvalidInput {
+            message: format!("invalid scan checkpoint '{}': {}", token, e),
+        })
+    }
+}
+
+/// A batch of a resumable scan with the position of the scan after it
+#[derive(Debug, Clone)]
+pub struct CheckpointedBatch {
+    pub batch: RecordBatch,
+    pub checkpoint: ScanCheckpoint,
+}
+
+/// The stream returned by [`super::Query::execute_resumable`]
+pub type ResumableStream = BoxStream<'static, Result<CheckpointedBatch>>;
+
+struct ScanState {
+    parent: Arc<dyn TableInternal>,
+    query: Query,
+    options: QueryExecutionOptions,
+    fragments: VecDeque<u64>,
+    current: Option<SendableRecordBatchStream>,
+    checkpoint: ScanCheckpoint,
+}
+
+impl ScanState {
+    /// Start reading the next fragment, returns false if there is none left
+    async fn next_fragment(&mut self) -> Result<bool> {
+        let Some(fragment_id) = self.fragments.pop_front() else {
+            return Ok(false);
+        };
+        if self
+            .query
+            .limit
+            .is_some_and(|limit| self.checkpoint.rows >= limit)
+        {
+            return Ok(false);
+        }
+        if fragment_id != self.checkpoint.fragment_id {
+            self.checkpoint.fragment_id = fragment_id;
+            self.checkpoint.fragment_rows = 0;
+        }
+        let mut query = self.query.clone();
+        query.version = Some(self.checkpoint.version);
+        query.fragment_ids = Some(vec![fragment_id]);
+        query.offset = Some(self.checkpoint.fragment_rows).filter(|rows| *rows > 0);
+        query.limit = self.query.limit.map(|limit| limit - self.checkpoint.rows);
+        self.current = Some(
+            self.parent
+                .clone()
+                .plain_query(&query, self.options.clone())
+                .await?,
+        );
+        Ok(true)
+    }
+}
+
+pub(crate) async fn execute_resumable(
+    parent: Arc<dyn TableInternal>,
+    query: &Query,
+    checkpoint: Option<ScanCheckpoint>,
+    options: QueryExecutionOptions,
+) -> Result<ResumableStream> {
+    if !query.order_by.is_empty() || query.distinct_on.is_some() {
+        return Err(Error::InvalidInput {
+            message: "a scan that orders its results, with order_by or distinct_on, can not be \
+                      resumed"
+                .to_string(),
+        });
+    }
+    if query.version_at.is_some() {
+        return Err(Error::InvalidInput {
+            message: "a resumable scan always reads the version of the table that is current \
+                      when it starts, it can not be combined with version_at"
+                .to_string(),
+        });
+    }
+    let (version, fragments) = parent
+        .scan_fragments(checkpoint.as_ref().map(|c| c.version))
+        .await?;
+    let checkpoint = checkpoint.unwrap_or(ScanCheckpoint {
+        version,
+        fragment_id: fragments.first().copied().unwrap_or_default(),
+        fragment_rows: 0,
+        rows: 0,
+    });
+    let fragments = fragments
+        .into_iter()
+        .filter(|id| *id >= checkpoint.fragment_id)
+        .collect();
+    let state = ScanState {
+        parent,
+        query: query.clone(),
+        options,
+        fragments,
+        current: None,
+        checkpoint,
+    };
+    Ok(futures::stream::try_unfold(state, |mut state| async move {
+        loop {
+            if let Some(stream) = &mut state.current {
+                match stream.try_next().await? {
+                    Some(batch) if batch.num_rows() == 0 => continue,
+                    Some(batch) => {
+                        state.checkpoint.fragment_rows += batch.num_rows();
+                        state.checkpoint.rows += batch.num_rows();
+                        let item = CheckpointedBatch {
+                            batch,
+                            checkpoint: state.checkpoint.clone(),
+                        };
+                        return Ok(Some((item, state)));
+                    }
+                    None => state.current = None,
+                }
+            }
+            if !state.next_fragment().await? {
+                return Ok(None);
+            }
+        }
+    })
+    .boxed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_token() {
+        let checkpoint = ScanCheckpoint {
+            version: 3,
+            fragment_id: 7,
+            fragment_rows: 1024,
+            rows: 10_000,
+        };
+        let token = checkpoint.to_token();
+        assert_eq!(ScanCheckpoint::from_token(&token).unwrap(), checkpoint);
+        assert!(matches!(
+            ScanCheckpoint::from_token("not a checkpoint"),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scoring of vector search results, see [`super::VectorQuery::score_transform`]
+
+use std::sync::Arc;
+
+use arrow::compute::concat_batches;
+use arrow_array::{cast::AsArray, types::Float32Type, Float32Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use futures::{StreamExt, TryStreamExt};
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+use crate::DistanceType;
+
+use super::rescore::DISTANCE_COLUMN;
+
+pub(crate) const SCORE_COLUMN: &str = "_score";
+
+/// Describes how the `_distance` of each vector search result is turned into
+/// a `_score`
+///
+/// The steps are applied in this order:
+///
+/// 1. If [`Self::similarity`] is set the distance is converted to a similarity,
+///    where larger is better.
+/// 2. The value is multiplied by [`Self::scale`].
+/// 3. If [`Self::temperature`] is set a softmax is applied across the results
+///    so that the scores sum to one.  If [`Self::min_max`] is set the scores
+///    are instead rescaled so that the largest is one and the smallest zero.
+/// 4. If [`Self::clamp`] is set the score is clamped to the range.
+///
+/// The `_distance` column is left untouched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreTransform {
+    pub(crate) similarity: bool,
+    pub(crate) scale: f32,
+    pub(crate) temperature: Option<f32>,
+    pub(crate) min_max: bool,
+    pub(crate) clamp: Option<(f32, f32)>,
+}
+
+/// A normalization of the scores of vector search results
+///
+/// A shorthand for the common [`ScoreTransform`]s, see
+/// [`super::VectorQuery::score_normalization`].  Every option first converts
+/// the distance into a similarity so that a larger score is always better,
+/// which makes the scores comparable across searches with different distance
+/// types.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScoreNorm {
+    /// The similarity, without normalization
+    #[default]
+    None,
+    /// The similarity rescaled so the best result scores one and the worst zero
+    MinMax,
+    /// A softmax of the similarity, the scores sum to one
+    Softmax,
+}
+
+impl From<ScoreNorm> for ScoreTransform {
+    fn from(norm: ScoreNorm) -> Self {
+        let transform = Self::new().similarity();
+        match norm {
+            ScoreNorm::None => transform,
+            ScoreNorm::MinMax => transform.min_max(),
+            ScoreNorm::Softmax => transform.temperature(1.0),
+        }
+    }
+}
+
+impl Default for ScoreTransform {
+    fn default() -> Self {
+        Self {
+            similarity: false,
+            scale: 1.0,
+            temperature: None,
+            min_max: false,
+            clamp: None,
+        }
+    }
+}
+
+impl ScoreTransform {
+    /// Create a transform that copies the distance into the score
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Convert the distance into a similarity
+    ///
+    /// For the cosine and dot distances the similarity is `1 - distance`,
+    /// which is the cosine similarity or the dot product.  For the l2 distance
+    /// (which is squared) the similarity is `1 / (1 + distance)`, which is in
+    /// the range (0, 1].
+    pub fn similarity(mut self) -> Self {
+        self.similarity = true;
+        self
+    }
+
+    /// Multiply the score by a constant factor
+    pub fn scale(mut self, factor: f32) -> Self {
+        self.scale = factor;
+        self
+    }
+
+    /// Apply a softmax with the given temperature across the results
+    ///
+    /// The scores of the results sum to one.  A lower temperature gives more
+    /// weight to the best results.  The softmax favors larger scores so this
+    /// is normally combined with [`Self::similarity`].
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Rescale the scores linearly across the results to the range `[0, 1]`
+    ///
+    /// The largest score becomes one and the smallest zero.  If every result
+    /// has the same score then every score is one.  This is normally combined
+    /// with [`Self::similarity`] so that the best result scores one.
+    pub fn min_max(mut self) -> Self {
+        self.min_max = true;
+        self
+    }
+
+    /// Clamp the score to the range `[min, max]`
+    pub fn clamp(mut self, min: f32, max: f32) -> Self {
+        self.clamp = Some((min, max));
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.scale.is_finite() {
+            return Err(Error::InvalidInput {
+                message: format!("the score scale must be finite, got {}", self.scale),
+            });
+        }
+        if let Some(temperature) = self.temperature {
+            if temperature <= 0.0 || !temperature.is_finite() {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the score temperature must be positive, got {}",
+                        temperature
+                    ),
+                });
+            }
+        }
+        if self.min_max && self.temperature.is_some() {
+            return Err(Error::InvalidInput {
+                message: "a score cannot use both min_max and a temperature".to_string(),
+            });
+        }
+        if let Some((min, max)) = self.clamp {
+            if min.is_nan() || max.is_nan() || min > max {
+                return Err(Error::InvalidInput {
+                    message: format!("invalid score clamp range [{}, {}]", min, max),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn to_similarity(distance_type: DistanceType, distance: f32) -> f32 {
+        match distance_type {
+            DistanceType::L2 => 1.0 / (1.0 + distance),
+            DistanceType::Cosine | DistanceType::Dot => 1.0 - distance,
+        }
+    }
+
+    /// Calculate the scores for a set of distances
+    ///
+    /// The softmax and min-max normalization need every result so this should
+    /// be called once with all of the distances if either is set.
+    fn scores(&self, distances: &Float32Array, distance_type: DistanceType) -> Float32Array {
+        let mut scores = distances
+            .iter()
+            .map(|distance| {
+                distance.map(|distance| {
+                    let score = if self.similarity {
+                        Self::to_similarity(distance_type, distance)
+                    } else {
+                        distance
+                    };
+                    score * self.scale
+                })
+            })
+            .collect::<Vec<_>>();
+        if let Some(temperature) = self.temperature {
+            let max = scores
+                .iter()
+                .flatten()
+                .copied()
+                .fold(f32::NEG_INFINITY, f32::max);
+            for score in scores.iter_mut().flatten() {
+                *score = ((*score - max) / temperature).exp();
+            }
+            let total = scores.iter().flatten().sum::<f32>();
+            for score in scores.iter_mut().flatten() {
+                *score /= total;
+            }
+        }
+        if self.min_max {
+            let (min, max) = scores
+                .iter()
+                .flatten()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), score| {
+                    (min.min(*score), max.max(*score))
+                });
+            for score in scores.iter_mut().flatten() {
+                *score = if max > min {
+                    (*score - min) / (max - min)
+                } else {
+                    1.0
+                };
+            }
+        }
+        if let Some((min, max)) = self.clamp {
+            for score in scores.iter_mut().flatten() {
+                *score = score.clamp(min, max);
+            }
+        }
+        Float32Array::from(scores)
+    }
+
+    fn score_batch(
+        &self,
+        schema: &SchemaRef,
+        batch: RecordBatch,
+        distance_type: DistanceType,
+    ) -> Result<RecordBatch> {
+        let distances = batch
+            .column_by_name(DISTANCE_COLUMN)
+            .ok_or_else(|| Error::Schema {
+                message: format!(
+                    "the column '{}' is missing from the search results",
+                    DISTANCE_COLUMN
+                ),
+            })?;
+        let distances = arrow_cast::cast(distances, &DataType::Float32)?;
+        let scores = self.scores(distances.as_primitive::<Float32Type>(), distance_type);
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(scores));
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+
+    /// Add a `_score` column to the results of a vector search
+    pub(crate) async fn apply(
+        self,
+        stream: SendableRecordBatchStream,
+        distance_type: DistanceType,
+    ) -> Result<SendableRecordBatchStream> {
+        self.validate()?;
+        let input_schema = stream.schema();
+        let mut fields = input_schema.fields().iter().cloned().collect::<Vec<_>>();
+        fields.push(Arc::new(Field::new(SCORE_COLUMN, DataType::Float32, true)));
+        let schema = Arc::new(Schema::new_with_metadata(
+            fields,
+            input_schema.metadata().clone(),
+        ));
+
+        if self.temperature.is_some() || self.min_max {
+            let batches = stream.try_collect::<Vec<_>>().await?;
+            let batch = concat_batches(&input_schema, &batches)?;
+            let batch = self.score_batch(&schema, batch, distance_type)?;
+            return Ok(Box::pin(SimpleRecordBatchStream {
+                schema,
+                stream: futures::stream::once(async move { Ok(batch) }),
+            }));
+        }
+
+        let batch_schema = schema.clone();
+        Ok(Box::pin(SimpleRecordBatchStream {
+            schema,
+            stream: stream.map(move |batch| self.score_batch(&batch_schema, batch?, distance_type)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+
+    use super::*;
+
+    #[test]
+    fn test_scores() {
+        let distances = Float32Array::from(vec![Some(0.0), Some(1.0), None, Some(3.0)]);
+
+        let identity = ScoreTransform::new().scores(&distances, DistanceType::L2);
+        assert_eq!(identity, distances);
+
+        let similarity = ScoreTransform::new()
+            .similarity()
+            .scores(&distances, DistanceType::L2);
+        assert_eq!(
+            similarity,
+            Float32Array::from(vec![Some(1.0), Some(0.5), None, Some(0.25)])
+        );
+
+        let cosine = ScoreTransform::new()
+            .similarity()
+            .scale(2.0)
+            .clamp(0.0, 1.0)
+            .scores(&distances, DistanceType::Cosine);
+        assert_eq!(
+            cosine,
+            Float32Array::from(vec![Some(1.0), Some(0.0), None, Some(0.0)])
+        );
+
+        let softmax = ScoreTransform::new()
+            .similarity()
+            .temperature(0.5)
+            .scores(&distances, DistanceType::Cosine);
+        assert!(softmax.is_null(2));
+        let total = softmax.iter().flatten().sum::<f32>();
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!(softmax.value(0) > softmax.value(1));
+        assert!(softmax.value(1) > softmax.value(3));
+
+        let min_max = ScoreTransform::from(ScoreNorm::MinMax).scores(&distances, DistanceType::Dot);
+        assert_eq!(
+            min_max,
+            Float32Array::from(vec![Some(1.0), Some(2.0 / 3.0), None, Some(0.0)])
+        );
+        let single = ScoreTransform::from(ScoreNorm::MinMax)
+            .scores(&Float32Array::from(vec![2.0]), DistanceType::L2);
+        assert_eq!(single, Float32Array::from(vec![1.0]));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ScoreTransform::new().validate().is_ok());
+        assert!(ScoreTransform::new().temperature(0.0).validate().is_err());
+        assert!(ScoreTransform::new().clamp(1.0, 0.0).validate().is_err());
+        assert!(ScoreTransform::new().scale(f32::NAN).validate().is_err());
+        assert!(ScoreTransform::new()
+            .min_max()
+            .temperature(1.0)
+            .validate()
+            .is_err());
+    }
+}
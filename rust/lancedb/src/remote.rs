@@ -19,5 +19,6 @@
 
 pub mod client;
 pub mod db;
+pub mod protocol;
 pub mod table;
 pub mod util;
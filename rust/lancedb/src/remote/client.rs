@@ -12,19 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use log::debug;
 use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    RequestBuilder, Response,
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    Request, RequestBuilder, Response, StatusCode,
 };
+use tracing::Instrument;
 
+use crate::connection::auth::{AuthProvider, AuthRequest};
+use crate::connection::client_config::{ClientConfig, RetryConfig};
 use crate::error::{Error, Result};
+use crate::runtime;
+use crate::telemetry::{record_remote_request, RequestContext};
 
 #[derive(Clone, Debug)]
 pub struct RestfulLanceDbClient {
     client: reqwest::Client,
     host: String,
+    config: ClientConfig,
+    auth: Arc<dyn AuthProvider>,
 }
 
 impl RestfulLanceDbClient {
@@ -32,19 +41,8 @@ impl RestfulLanceDbClient {
         &self.host
     }
 
-    fn default_headers(
-        api_key: &str,
-        region: &str,
-        db_name: &str,
-        has_host_override: bool,
-    ) -> Result<HeaderMap> {
+    fn default_headers(region: &str, db_name: &str, has_host_override: bool) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
-        headers.insert(
-            "x-api-key",
-            HeaderValue::from_str(api_key).map_err(|_| Error::Http {
-                message: "non-ascii api key provided".to_string(),
-            })?,
-        );
         if region == "local" {
             let host = format!("{}.local.api.lancedb.com", db_name);
             headers.insert(
@@ -68,9 +66,10 @@ impl RestfulLanceDbClient {
 
     pub fn try_new(
         db_url: &str,
-        api_key: &str,
+        auth: Arc<dyn AuthProvider>,
         region: &str,
         host_override: Option<String>,
+        config: ClientConfig,
     ) -> Result<Self> {
         let parsed_url = url::Url::parse(db_url)?;
         debug_assert_eq!(parsed_url.scheme(), "db");
@@ -80,10 +79,14 @@ impl RestfulLanceDbClient {
             });
         }
         let db_name = parsed_url.host_str().unwrap();
+        config.retry.validate()?;
         let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(30))
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle)
+            .tcp_keepalive(config.tcp_keepalive)
             .default_headers(Self::default_headers(
-                api_key,
                 region,
                 db_name,
                 host_override.is_some(),
@@ -93,7 +96,12 @@ impl RestfulLanceDbClient {
             Some(host_override) => host_override,
             None => format!("https://{}.{}.api.lancedb.com", db_name, region),
         };
-        Ok(Self { client, host })
+        Ok(Self {
+            client,
+            host,
+            config,
+            auth,
+        })
     }
 
     pub fn get(&self, uri: &str) -> RequestBuilder {
@@ -106,6 +114,139 @@ impl RestfulLanceDbClient {
         self.client.post(full_uri)
     }
 
+    /// Send a request, retrying according to the [`RetryConfig`]
+    ///
+    /// The final response is returned even if it has a retryable status, use
+    /// [`Self::check_response`] to turn it into an error.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let retry = &self.config.retry;
+        let mut request = request;
+        let mut attempt = 0;
+        let mut reauthenticated = false;
+        loop {
+            attempt += 1;
+            // Requests with a streaming body cannot be cloned and are not retried
+            let next = request.try_clone();
+            let (client, current) = request.build_split();
+            let mut current = current?;
+            self.authenticate(&mut current).await?;
+            Self::add_request_headers(&mut current)?;
+            let method = current.method().to_string();
+            let url = current.url().to_string();
+            if let Some(hook) = &self.config.hook {
+                hook.on_request(&method, &url, attempt);
+            }
+            let span = tracing::debug_span!(
+                "lancedb.remote.request",
+                method = %method,
+                url = %url,
+                attempt,
+                status = tracing::field::Empty,
+            );
+            let start = Instant::now();
+            let result = client.execute(current).instrument(span.clone()).await;
+            let status = result.as_ref().ok().map(|r| r.status().as_u16());
+            if let Some(status) = status {
+                span.record("status", status);
+            }
+            span.in_scope(|| record_remote_request(&method, status, start.elapsed()));
+            if let Some(hook) = &self.config.hook {
+                hook.on_response(&method, &url, attempt, status, start.elapsed());
+            }
+
+            let unauthorized =
+                matches!(&result, Ok(response) if response.status() == StatusCode::UNAUTHORIZED);
+            if unauthorized && !reauthenticated && next.is_some() {
+                reauthenticated = true;
+                if self.auth.on_unauthorized().await? {
+                    debug!("Retrying {} {} with refreshed credentials", method, url);
+                    // Re-authentication does not count against the retry budget
+                    attempt -= 1;
+                    request = next.unwrap();
+                    continue;
+                }
+            }
+
+            let retry_after = match &result {
+                Ok(response) if RetryConfig::is_retryable_status(response.status().as_u16()) => {
+                    Some(Self::retry_after(response))
+                }
+                Ok(_) => None,
+                Err(err) if err.is_connect() || err.is_timeout() => Some(None),
+                Err(_) => None,
+            };
+            let (Some(retry_after), Some(next)) = (retry_after, next) else {
+                return Ok(result?);
+            };
+            if attempt > retry.max_retries {
+                return Ok(result?);
+            }
+            let delay = retry_after.unwrap_or_else(|| retry.backoff(attempt));
+            debug!(
+                "Retrying {} {} in {:?} (attempt {} of {})",
+                method,
+                url,
+                delay,
+                attempt,
+                retry.max_retries + 1
+            );
+            runtime::sleep(delay).await;
+            request = next;
+        }
+    }
+
+    async fn authenticate(&self, request: &mut Request) -> Result<()> {
+        let headers = {
+            let auth_request = AuthRequest {
+                method: request.method().as_str(),
+                url: request.url(),
+                body: request.body().and_then(|b| b.as_bytes()),
+            };
+            self.auth.headers(&auth_request).await?
+        };
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| Error::Http {
+                message: format!("invalid auth header name '{}'", name),
+            })?;
+            let value = HeaderValue::from_str(&value).map_err(|_| Error::Http {
+                message: format!("invalid value for auth header '{}'", name),
+            })?;
+            request.headers_mut().insert(name, value);
+        }
+        Ok(())
+    }
+
+    /// Tag the request with the [`RequestContext`] of the operation, if any
+    fn add_request_headers(request: &mut Request) -> Result<()> {
+        let Some(context) = RequestContext::current() else {
+            return Ok(());
+        };
+        let invalid = |name: &str| Error::Http {
+            message: format!("invalid value for header '{}'", name),
+        };
+        request.headers_mut().insert(
+            "x-request-id",
+            HeaderValue::from_str(context.request_id()).map_err(|_| invalid("x-request-id"))?,
+        );
+        if !context.tags().is_empty() {
+            request.headers_mut().insert(
+                "x-lancedb-tags",
+                HeaderValue::from_str(&context.tags_string())
+                    .map_err(|_| invalid("x-lancedb-tags"))?,
+            );
+        }
+        Ok(())
+    }
+
+    fn retry_after(response: &Response) -> Option<Duration> {
+        response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(Duration::from_secs)
+    }
+
     async fn rsp_to_str(response: Response) -> String {
         let status = response.status();
         response.text().await.unwrap_or_else(|_| status.to_string())
@@ -17,21 +17,25 @@ use std::sync::Arc;
 use arrow_array::RecordBatchReader;
 use async_trait::async_trait;
 use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
 use serde::Deserialize;
-use tokio::task::spawn_blocking;
 
+use crate::connection::auth::AuthProvider;
+use crate::connection::client_config::ClientConfig;
 use crate::connection::{
-    ConnectionInternal, CreateTableBuilder, NoData, OpenTableBuilder, TableNamesBuilder,
+    ConnectionInternal, CreateTableBuilder, CreateTableMode, NoData, OpenTableBuilder,
+    TableNamesBuilder,
 };
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::runtime;
+use crate::table::view::StoredView;
 use crate::Table;
 
 use super::client::RestfulLanceDbClient;
+use super::protocol::{ARROW_STREAM_CONTENT_TYPE, IDEMPOTENCY_KEY_HEADER};
 use super::table::RemoteTable;
 use super::util::batches_to_ipc_bytes;
 
-const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
-
 #[derive(Deserialize)]
 struct ListTablesResponse {
     tables: Vec<String>,
@@ -45,11 +49,13 @@ pub struct RemoteDatabase {
 impl RemoteDatabase {
     pub fn try_new(
         uri: &str,
-        api_key: &str,
+        auth: Arc<dyn AuthProvider>,
         region: &str,
         host_override: Option<String>,
+        client_config: ClientConfig,
     ) -> Result<Self> {
-        let client = RestfulLanceDbClient::try_new(uri, api_key, region, host_override)?;
+        let client =
+            RestfulLanceDbClient::try_new(uri, auth, region, host_override, client_config)?;
         Ok(Self { client })
     }
 }
@@ -70,11 +76,17 @@ impl ConnectionInternal for RemoteDatabase {
         if let Some(start_after) = options.start_after {
             req = req.query(&[("page_token", start_after)]);
         }
-        let rsp = req.send().await?;
+        let rsp = self.client.send(req).await?;
         let rsp = self.client.check_response(rsp).await?;
         Ok(rsp.json::<ListTablesResponse>().await?.tables)
     }
 
+    async fn register_table(&self, _name: &str, _location: &str) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "catalogs are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+
     async fn do_create_table(
         &self,
         options: CreateTableBuilder<false, NoData>,
@@ -83,18 +95,32 @@ impl ConnectionInternal for RemoteDatabase {
         // TODO: https://github.com/lancedb/lancedb/issues/1026
         // We should accept data from an async source.  In the meantime, spawn this as blocking
         // to make sure we don't block the tokio runtime if the source is slow.
-        let data_buffer = spawn_blocking(move || batches_to_ipc_bytes(data))
-            .await
-            .unwrap()?;
-
-        self.client
+        let data_buffer = runtime::spawn_blocking(move || batches_to_ipc_bytes(data)).await?;
+
+        // The request is retried if it times out, the token lets the server
+        // recognize a retry of a create that succeeded
+        let token = options
+            .idempotency_token
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let req = self
+            .client
             .post(&format!("/v1/table/{}/create", options.name))
             .body(data_buffer)
             .header(CONTENT_TYPE, ARROW_STREAM_CONTENT_TYPE)
+            .header(IDEMPOTENCY_KEY_HEADER, token)
             // This is currently expected by LanceDb cloud but will be removed soon.
-            .header("x-request-id", "na")
-            .send()
-            .await?;
+            .header("x-request-id", "na");
+        let rsp = self.client.send(req).await?;
+        if rsp.status() == StatusCode::CONFLICT {
+            // There are no options for opening a remote table so the exist_ok
+            // callback is not needed
+            if !matches!(options.mode, CreateTableMode::ExistOk(_)) {
+                return Err(Error::TableAlreadyExists { name: options.name });
+            }
+        } else {
+            self.client.check_response(rsp).await?;
+        }
 
         Ok(Table::new(Arc::new(RemoteTable::new(
             self.client.clone(),
@@ -113,4 +139,16 @@ impl ConnectionInternal for RemoteDatabase {
     async fn drop_db(&self) -> Result<()> {
         todo!()
     }
+
+    async fn load_view(&self, _name: &str) -> Result<Option<StoredView>> {
+        Err(Error::NotSupported {
+            message: "views are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+
+    async fn save_view(&self, _name: &str, _view: Option<&StoredView>) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "views are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
 }
@@ -0,0 +1,174 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Request and response bodies of the REST protocol
+//!
+//! These are shared by the remote client and the HTTP server in `serve::http`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::table::prune::PrunePreview;
+use crate::DistanceType;
+
+pub const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// The header carrying the idempotency token of a `create` request
+pub const IDEMPOTENCY_KEY_HEADER: &str = "x-lancedb-idempotency-key";
+
+/// The body of the `create_index` and `create_scalar_index` endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateIndexRequest {
+    pub column: String,
+    /// Either `vector` or `scalar`
+    pub index_type: String,
+    #[serde(default = "default_true")]
+    pub replace: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_partitions: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_sub_vectors: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_bits: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<u32>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// The query parameters of the `merge_insert` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeInsertParams {
+    /// The key columns, separated by commas
+    pub on: String,
+    #[serde(default)]
+    pub when_matched_update_all: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when_matched_update_all_filt: Option<String>,
+    #[serde(default)]
+    pub when_not_matched_insert_all: bool,
+    #[serde(default)]
+    pub when_not_matched_by_source_delete: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when_not_matched_by_source_delete_filt: Option<String>,
+}
+
+/// The body of the `optimize` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum OptimizeRequest {
+    All,
+    Compact {
+        target_rows_per_fragment: usize,
+        max_rows_per_group: usize,
+        materialize_deletions: bool,
+        materialize_deletions_threshold: f32,
+    },
+    Prune {
+        older_than_seconds: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        delete_unverified: Option<bool>,
+        // Always sent, a server that does not know it must reject the
+        // request instead of pruning
+        #[serde(default)]
+        dry_run: bool,
+    },
+    Index {
+        num_indices_to_merge: usize,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompactionResponse {
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    pub files_removed: usize,
+    pub files_added: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PruneResponse {
+    pub bytes_removed: u64,
+    pub old_versions: u64,
+}
+
+/// The response of the `optimize` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OptimizeResponse {
+    #[serde(default)]
+    pub compaction: Option<CompactionResponse>,
+    #[serde(default)]
+    pub prune: Option<PruneResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prune_preview: Option<PrunePreview>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewColumn {
+    pub name: String,
+    /// A SQL expression calculating the value of the column
+    pub expression: String,
+}
+
+/// The body of the `add_columns` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddColumnsRequest {
+    pub new_columns: Vec<NewColumn>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnAlterationRequest {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nullable: Option<bool>,
+}
+
+/// The body of the `alter_columns` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlterColumnsRequest {
+    pub alterations: Vec<ColumnAlterationRequest>,
+}
+
+/// The body of the `drop_columns` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DropColumnsRequest {
+    pub columns: Vec<String>,
+}
+
+/// The name of a distance type in the protocol
+pub fn metric_type_name(distance_type: DistanceType) -> &'static str {
+    match distance_type {
+        DistanceType::L2 => "L2",
+        DistanceType::Cosine => "cosine",
+        DistanceType::Dot => "dot",
+    }
+}
+
+/// Parse a distance type name, case insensitive
+#[cfg_attr(not(feature = "http-server"), allow(dead_code))]
+pub fn parse_metric_type(name: &str) -> Option<DistanceType> {
+    match name.to_lowercase().as_str() {
+        "l2" => Some(DistanceType::L2),
+        "cosine" => Some(DistanceType::Cosine),
+        "dot" => Some(DistanceType::Dot),
+        _ => None,
+    }
+}
@@ -1,24 +1,58 @@
-use arrow_array::RecordBatchReader;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{RecordBatch, RecordBatchReader};
 use arrow_schema::SchemaRef;
 use async_trait::async_trait;
-use lance::dataset::{scanner::DatasetRecordBatchStream, ColumnAlteration, NewColumnTransform};
+use chrono::Duration;
+use lance::dataset::cleanup::RemovalStats;
+use lance::dataset::optimize::CompactionMetrics;
+use lance::dataset::{ColumnAlteration, NewColumnTransform};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
 
 use crate::{
+    arrow::SendableRecordBatchStream,
     connection::NoData,
-    error::Result,
-    index::{IndexBuilder, IndexConfig},
-    query::{Query, QueryExecutionOptions, VectorQuery},
+    error::{Error, Result},
+    index::{metadata::IndexMetadata, Index, IndexBuilder, IndexConfig},
+    io::{metrics::IoStats, tiering::TieringReport},
+    query::{filter_cache::FilterCacheMetrics, Query, QueryExecutionOptions, VectorQuery},
+    runtime,
     table::{
-        merge::MergeInsertBuilder, AddDataBuilder, NativeTable, OptimizeAction, OptimizeStats,
-        TableInternal, UpdateBuilder,
+        audit::AuditRecord,
+        batch_alter::BatchAlterBuilder,
+        blob::BlobRef,
+        bulk::BulkLoadStats,
+        constraints::Constraint,
+        estimate::TableStatistics,
+        fragment_stats::FragmentPruning,
+        masking::MaskingPolicy,
+        merge::MergeInsertBuilder,
+        merge_columns::MergeColumnsBuilder,
+        primary_key::KeyValue,
+        prune::PrunePreview,
+        reembed::{ReembedBuilder, ReembedReport},
+        repair::{RepairOptions, RepairReport},
+        temporal::TemporalValidity,
+        tuning::SearchDefaults,
+        verify::IntegrityReport,
+        write_stats::WriteStats,
+        AddDataBuilder, NativeTable, OptimizeAction, OptimizeStats, TableInternal, UpdateBuilder,
+        Version,
     },
 };
 
 use super::client::RestfulLanceDbClient;
+use super::protocol::{
+    metric_type_name, AddColumnsRequest, AlterColumnsRequest, ColumnAlterationRequest,
+    CreateIndexRequest, DropColumnsRequest, MergeInsertParams, NewColumn, OptimizeRequest,
+    OptimizeResponse, ARROW_STREAM_CONTENT_TYPE,
+};
+use super::util::batches_to_ipc_bytes;
 
 #[derive(Debug)]
 pub struct RemoteTable {
-    #[allow(dead_code)]
     client: RestfulLanceDbClient,
     name: String,
 }
@@ -27,6 +61,15 @@ impl RemoteTable {
     pub fn new(client: RestfulLanceDbClient, name: String) -> Self {
         Self { client, name }
     }
+
+    async fn post_json(&self, endpoint: &str, body: &impl Serialize) -> Result<reqwest::Response> {
+        let req = self
+            .client
+            .post(&format!("/v1/table/{}/{}/", self.name, endpoint))
+            .json(body);
+        let rsp = self.client.send(req).await?;
+        self.client.check_response(rsp).await
+    }
 }
 
 impl std::fmt::Display for RemoteTable {
@@ -49,6 +92,11 @@ impl TableInternal for RemoteTable {
     async fn version(&self) -> Result<u64> {
         todo!()
     }
+    async fn list_versions(&self) -> Result<Vec<Version>> {
+        Err(Error::NotSupported {
+            message: "listing versions is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
     async fn checkout(&self, _version: u64) -> Result<()> {
         todo!()
     }
@@ -58,6 +106,125 @@ impl TableInternal for RemoteTable {
     async fn restore(&self) -> Result<()> {
         todo!()
     }
+    async fn copy_to(&self, _name: &str) -> Result<Arc<dyn TableInternal>> {
+        Err(Error::NotSupported {
+            message: "copying tables is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn snapshot(&self) -> Result<Arc<dyn TableInternal>> {
+        Err(Error::NotSupported {
+            message: "snapshots are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    fn write_stats(&self) -> Result<WriteStats> {
+        Err(Error::NotSupported {
+            message: "write statistics are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    fn io_stats(&self) -> Result<IoStats> {
+        Err(Error::NotSupported {
+            message: "I/O statistics are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn tiering_report(&self) -> Result<TieringReport> {
+        Err(Error::NotSupported {
+            message: "tiered storage is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn ids_of_rows(&self, _row_ids: &[u64]) -> Result<Vec<KeyValue>> {
+        Err(Error::NotSupported {
+            message: "mapping rows to ids is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn scan_fragments(&self, _version: Option<u64>) -> Result<(u64, Vec<u64>)> {
+        Err(Error::NotSupported {
+            message: "resumable scans are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    fn filter_cache_metrics(&self) -> Result<Option<FilterCacheMetrics>> {
+        Err(Error::NotSupported {
+            message: "the filter cache is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn statistics(&self) -> Result<TableStatistics> {
+        Err(Error::NotSupported {
+            message: "cost estimates are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn verify(&self) -> Result<IntegrityReport> {
+        Err(Error::NotSupported {
+            message: "verify is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn repair(&self, _options: RepairOptions) -> Result<RepairReport> {
+        Err(Error::NotSupported {
+            message: "repair is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn reembed(&self, _reembed: ReembedBuilder) -> Result<ReembedReport> {
+        Err(Error::NotSupported {
+            message: "re-embedding is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn index_metadata(&self, _column: &str) -> Result<IndexMetadata> {
+        Err(Error::NotSupported {
+            message: "index metadata is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn compute_statistics(&self, _columns: &[&str]) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "fragment statistics are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn fragment_pruning(&self, _query: &Query) -> Result<FragmentPruning> {
+        Err(Error::NotSupported {
+            message: "fragment statistics are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn sample(&self, _n: usize, _seed: Option<u64>) -> Result<RecordBatch> {
+        Err(Error::NotSupported {
+            message: "sampling is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn estimate_count(&self, _filter: Option<String>) -> Result<usize> {
+        Err(Error::NotSupported {
+            message: "approximate counts are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn estimate_distinct(&self, _column: &str) -> Result<usize> {
+        Err(Error::NotSupported {
+            message: "approximate counts are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn search_defaults(&self) -> Result<HashMap<String, SearchDefaults>> {
+        Err(Error::NotSupported {
+            message: "search defaults are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn set_search_defaults(
+        &self,
+        _column: &str,
+        _defaults: Option<SearchDefaults>,
+    ) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "search defaults are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn metadata(&self, _column: Option<&str>) -> Result<HashMap<String, String>> {
+        Err(Error::NotSupported {
+            message: "table metadata is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn set_metadata(
+        &self,
+        _column: Option<&str>,
+        _key: &str,
+        _value: Option<&str>,
+    ) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "table metadata is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
     async fn schema(&self) -> Result<SchemaRef> {
         todo!()
     }
@@ -71,18 +238,33 @@ impl TableInternal for RemoteTable {
     ) -> Result<()> {
         todo!()
     }
+    async fn finish_bulk_load(&self) -> Result<BulkLoadStats> {
+        Err(Error::NotSupported {
+            message: "bulk loads are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn discard_bulk_load(&self) -> Result<usize> {
+        Err(Error::NotSupported {
+            message: "bulk loads are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn history(&self) -> Result<Vec<AuditRecord>> {
+        Err(Error::NotSupported {
+            message: "the audit log is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
     async fn plain_query(
         &self,
         _query: &Query,
         _options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream> {
+    ) -> Result<SendableRecordBatchStream> {
         todo!()
     }
     async fn vector_query(
         &self,
         _query: &VectorQuery,
         _options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream> {
+    ) -> Result<SendableRecordBatchStream> {
         todo!()
     }
     async fn update(&self, _update: UpdateBuilder) -> Result<()> {
@@ -91,33 +273,282 @@ impl TableInternal for RemoteTable {
     async fn delete(&self, _predicate: &str) -> Result<()> {
         todo!()
     }
-    async fn create_index(&self, _index: IndexBuilder) -> Result<()> {
-        todo!()
+    async fn delete_by_values(&self, _column: &str, _values: &[KeyValue]) -> Result<usize> {
+        Err(Error::NotSupported {
+            message: "deleting by a list of values is not yet supported on LanceDB Cloud"
+                .to_string(),
+        })
+    }
+    async fn create_index(&self, index: IndexBuilder) -> Result<()> {
+        if index.columns.is_empty() {
+            return Err(Error::InvalidInput {
+                message: "an index must be created on at least one column".to_string(),
+            });
+        }
+        if index.columns.len() > 1 {
+            return Err(Error::NotSupported {
+                message: "creating one btree index per column is not yet supported on LanceDB \
+                          Cloud"
+                    .to_string(),
+            });
+        }
+        let column = &index.columns[0];
+        let (endpoint, request) = match &index.index {
+            Index::Auto => {
+                return Err(Error::NotSupported {
+                    message: "remote tables require an explicit index type".to_string(),
+                })
+            }
+            Index::BTree(btree) if btree.normalization.is_some() => {
+                return Err(Error::NotSupported {
+                    message: "btree index options are not yet supported on LanceDB Cloud"
+                        .to_string(),
+                })
+            }
+            Index::BTree(_) => (
+                "create_scalar_index",
+                CreateIndexRequest {
+                    column: column.clone(),
+                    index_type: "scalar".to_string(),
+                    replace: index.replace,
+                    metric_type: None,
+                    num_partitions: None,
+                    num_sub_vectors: None,
+                    num_bits: None,
+                    sample_rate: None,
+                    max_iterations: None,
+                },
+            ),
+            Index::IvfPq(ivf_pq) => (
+                "create_index",
+                CreateIndexRequest {
+                    column: column.clone(),
+                    index_type: "vector".to_string(),
+                    replace: index.replace,
+                    metric_type: Some(metric_type_name(ivf_pq.distance_type).to_string()),
+                    num_partitions: ivf_pq.num_partitions,
+                    num_sub_vectors: ivf_pq.num_sub_vectors,
+                    num_bits: Some(ivf_pq.num_bits),
+                    sample_rate: Some(ivf_pq.sample_rate),
+                    max_iterations: Some(ivf_pq.max_iterations),
+                },
+            ),
+        };
+        self.post_json(endpoint, &request).await?;
+        Ok(())
     }
     async fn merge_insert(
         &self,
-        _params: MergeInsertBuilder,
+        params: MergeInsertBuilder,
+        new_data: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<()> {
+        let query = MergeInsertParams {
+            on: params.on.join(","),
+            when_matched_update_all: params.when_matched_update_all,
+            when_matched_update_all_filt: params.when_matched_update_all_filt,
+            when_not_matched_insert_all: params.when_not_matched_insert_all,
+            when_not_matched_by_source_delete: params.when_not_matched_by_source_delete,
+            when_not_matched_by_source_delete_filt: params.when_not_matched_by_source_delete_filt,
+        };
+        // See the comment in RemoteDatabase::do_create_table
+        let data_buffer = runtime::spawn_blocking(move || batches_to_ipc_bytes(new_data)).await?;
+        let req = self
+            .client
+            .post(&format!("/v1/table/{}/merge_insert/", self.name))
+            .query(&query)
+            .body(data_buffer)
+            .header(CONTENT_TYPE, ARROW_STREAM_CONTENT_TYPE);
+        let rsp = self.client.send(req).await?;
+        self.client.check_response(rsp).await?;
+        Ok(())
+    }
+    async fn merge_columns(
+        &self,
+        _params: MergeColumnsBuilder,
         _new_data: Box<dyn RecordBatchReader + Send>,
     ) -> Result<()> {
-        todo!()
+        Err(Error::NotSupported {
+            message: "merge is not yet supported on LanceDB Cloud".to_string(),
+        })
     }
-    async fn optimize(&self, _action: OptimizeAction) -> Result<OptimizeStats> {
-        todo!()
+    async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats> {
+        let request = match action {
+            OptimizeAction::All => OptimizeRequest::All,
+            OptimizeAction::Compact {
+                options,
+                remap_options,
+            } => {
+                if remap_options.is_some() {
+                    return Err(Error::NotSupported {
+                        message: "remap options are not supported for remote tables".to_string(),
+                    });
+                }
+                OptimizeRequest::Compact {
+                    target_rows_per_fragment: options.target_rows_per_fragment,
+                    max_rows_per_group: options.max_rows_per_group,
+                    materialize_deletions: options.materialize_deletions,
+                    materialize_deletions_threshold: options.materialize_deletions_threshold,
+                }
+            }
+            OptimizeAction::Prune {
+                older_than,
+                delete_unverified,
+            } => OptimizeRequest::Prune {
+                older_than_seconds: older_than.num_seconds(),
+                delete_unverified,
+                dry_run: false,
+            },
+            OptimizeAction::Index(options) => OptimizeRequest::Index {
+                num_indices_to_merge: options.num_indices_to_merge,
+            },
+        };
+        let rsp = self.post_json("optimize", &request).await?;
+        let rsp = rsp.json::<OptimizeResponse>().await?;
+        Ok(OptimizeStats {
+            compaction: rsp.compaction.map(|c| CompactionMetrics {
+                fragments_removed: c.fragments_removed,
+                fragments_added: c.fragments_added,
+                files_removed: c.files_removed,
+                files_added: c.files_added,
+            }),
+            prune: rsp.prune.map(|p| RemovalStats {
+                bytes_removed: p.bytes_removed,
+                old_versions: p.old_versions,
+            }),
+        })
+    }
+    async fn preview_prune(
+        &self,
+        older_than: Duration,
+        delete_unverified: Option<bool>,
+    ) -> Result<PrunePreview> {
+        let request = OptimizeRequest::Prune {
+            older_than_seconds: older_than.num_seconds(),
+            delete_unverified,
+            dry_run: true,
+        };
+        let rsp = self.post_json("optimize", &request).await?;
+        let rsp = rsp.json::<OptimizeResponse>().await?;
+        rsp.prune_preview.ok_or_else(|| Error::Http {
+            message: "the server did not return a prune preview".to_string(),
+        })
     }
     async fn add_columns(
         &self,
-        _transforms: NewColumnTransform,
-        _read_columns: Option<Vec<String>>,
+        transforms: NewColumnTransform,
+        read_columns: Option<Vec<String>>,
     ) -> Result<()> {
-        todo!()
+        let NewColumnTransform::SqlExpressions(expressions) = transforms else {
+            return Err(Error::NotSupported {
+                message: "only SQL expressions can be used to add columns to remote tables"
+                    .to_string(),
+            });
+        };
+        if read_columns.is_some() {
+            return Err(Error::NotSupported {
+                message: "read_columns is not supported for remote tables".to_string(),
+            });
+        }
+        let request = AddColumnsRequest {
+            new_columns: expressions
+                .into_iter()
+                .map(|(name, expression)| NewColumn { name, expression })
+                .collect(),
+        };
+        self.post_json("add_columns", &request).await?;
+        Ok(())
     }
-    async fn alter_columns(&self, _alterations: &[ColumnAlteration]) -> Result<()> {
-        todo!()
+    async fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()> {
+        let request = AlterColumnsRequest {
+            alterations: alterations
+                .iter()
+                .map(|alteration| ColumnAlterationRequest {
+                    path: alteration.path.clone(),
+                    rename: alteration.rename.clone(),
+                    nullable: alteration.nullable,
+                })
+                .collect(),
+        };
+        self.post_json("alter_columns", &request).await?;
+        Ok(())
     }
-    async fn drop_columns(&self, _columns: &[&str]) -> Result<()> {
-        todo!()
+    async fn drop_columns(&self, columns: &[&str]) -> Result<()> {
+        let request = DropColumnsRequest {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+        };
+        self.post_json("drop_columns", &request).await?;
+        Ok(())
+    }
+    async fn batch_alter(&self, _alter: BatchAlterBuilder) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "batch alter is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn masking_policies(&self) -> Result<HashMap<String, MaskingPolicy>> {
+        Err(Error::NotSupported {
+            message: "masking policies are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn set_masking_policy(
+        &self,
+        _column: &str,
+        _policy: Option<MaskingPolicy>,
+    ) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "masking policies are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn temporal_validity(&self) -> Result<Option<TemporalValidity>> {
+        Err(Error::NotSupported {
+            message: "temporal validity is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn set_temporal_validity(&self, _validity: Option<TemporalValidity>) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "temporal validity is not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn soft_delete(&self) -> Result<bool> {
+        Err(Error::NotSupported {
+            message: "soft deletes are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn set_soft_delete(&self, _enabled: bool) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "soft deletes are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn undelete(&self, _predicate: &str) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "soft deletes are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn constraints(&self) -> Result<HashMap<String, Vec<Constraint>>> {
+        Err(Error::NotSupported {
+            message: "constraints are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn set_constraints(&self, _column: &str, _constraints: Vec<Constraint>) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "constraints are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn primary_key(&self) -> Result<Option<String>> {
+        Err(Error::NotSupported {
+            message: "primary keys are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
+    async fn set_primary_key(&self, _column: Option<&str>) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "primary keys are not yet supported on LanceDB Cloud".to_string(),
+        })
     }
     async fn list_indices(&self) -> Result<Vec<IndexConfig>> {
         todo!()
     }
+    async fn blob_refs(&self, _batch: &RecordBatch, _column: &str) -> Result<Vec<BlobRef>> {
+        Err(Error::NotSupported {
+            message: "lazy blobs are not yet supported on LanceDB Cloud".to_string(),
+        })
+    }
 }
@@ -0,0 +1,252 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The async runtime services used by LanceDb
+//!
+//! Everything that needs an executor (spawning tasks, timers, file IO and
+//! running blocking code) goes through this module so that the rest of the
+//! crate does not depend on tokio's runtime.  On native targets these are
+//! backed by tokio.  On `wasm32` they are backed by the browser's event loop,
+//! where there are no threads and blocking code runs inline, and there is no
+//! file system.
+//!
+//! The tokio synchronization primitives (`tokio::sync`) and IO traits
+//! (`tokio::io`) do not need a runtime and are used directly.
+//!
+//! Note: this only covers LanceDb's own use of the runtime.  The crate does
+//! not build for `wasm32` yet because lance itself depends on tokio's runtime
+//! and on local file IO.  The [`crate::blocking`] API and the mirroring object
+//! store are native only.
+
+use std::future::Future;
+use std::time::Duration;
+
+/// Run a future in the background, without waiting for its result
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    tokio::spawn(future);
+}
+
+/// Run a future in the background, without waiting for its result
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Wait for the given duration
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+/// Wait for the given duration
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await
+}
+
+/// A point in time, for [`sleep_until`]
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::Instant;
+
+/// A point in time, for [`sleep_until`]
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+/// Wait until the given point in time
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep_until(deadline: Instant) {
+    tokio::time::sleep_until(deadline).await
+}
+
+/// Wait until the given point in time
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep_until(deadline: Instant) {
+    sleep(deadline.saturating_duration_since(Instant::now())).await
+}
+
+/// The error of a future that did not complete within its [`timeout`]
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+/// Wait for a future for at most the given duration
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn timeout<T>(
+    duration: Duration,
+    future: impl Future<Output = T>,
+) -> Result<T, Elapsed> {
+    tokio::time::timeout(duration, future)
+        .await
+        .map_err(|_| Elapsed)
+}
+
+/// Wait for a future for at most the given duration
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout<T>(
+    duration: Duration,
+    future: impl Future<Output = T>,
+) -> Result<T, Elapsed> {
+    let future = std::pin::pin!(future);
+    let timer = std::pin::pin!(sleep(duration));
+    match futures::future::select(future, timer).await {
+        futures::future::Either::Left((value, _)) => Ok(value),
+        futures::future::Either::Right(_) => Err(Elapsed),
+    }
+}
+
+/// Asynchronous file system access
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod fs {
+    pub(crate) use tokio::fs::{create_dir_all, read, remove_file, rename, write, File};
+}
+
+/// Asynchronous file system access
+///
+/// There is no file system, every operation fails with
+/// [`std::io::ErrorKind::Unsupported`].
+#[cfg(target_arch = "wasm32")]
+pub(crate) mod fs {
+    use std::io::{Error, ErrorKind, Result, SeekFrom};
+    use std::path::Path;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+    fn unsupported() -> Error {
+        Error::new(ErrorKind::Unsupported, "there is no file system on wasm32")
+    }
+
+    pub(crate) async fn read(_path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn write(_path: impl AsRef<Path>, _contents: impl AsRef<[u8]>) -> Result<()> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn rename(_from: impl AsRef<Path>, _to: impl AsRef<Path>) -> Result<()> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn remove_file(_path: impl AsRef<Path>) -> Result<()> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn create_dir_all(_path: impl AsRef<Path>) -> Result<()> {
+        Err(unsupported())
+    }
+
+    /// A file, none can be opened
+    pub(crate) enum File {}
+
+    impl File {
+        pub(crate) async fn open(_path: impl AsRef<Path>) -> Result<Self> {
+            Err(unsupported())
+        }
+
+        pub(crate) async fn create(_path: impl AsRef<Path>) -> Result<Self> {
+            Err(unsupported())
+        }
+    }
+
+    impl AsyncRead for File {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut ReadBuf<'_>,
+        ) -> Poll<Result<()>> {
+            match *self {}
+        }
+    }
+
+    impl AsyncSeek for File {
+        fn start_seek(self: Pin<&mut Self>, _position: SeekFrom) -> Result<()> {
+            match *self {}
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<u64>> {
+            match *self {}
+        }
+    }
+
+    impl AsyncWrite for File {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<Result<usize>> {
+            match *self {}
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+            match *self {}
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+            match *self {}
+        }
+    }
+}
+
+/// Run blocking code (e.g. reading from a synchronous data source) without
+/// blocking the executor
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn spawn_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f).await.unwrap()
+}
+
+/// Run blocking code
+///
+/// There are no threads to move the work to, so it is run inline.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn spawn_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_runtime() {
+        assert_eq!(spawn_blocking(|| 1 + 1).await, 2);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let task_done = done.clone();
+        spawn(async move {
+            task_done.store(true, Ordering::SeqCst);
+        });
+        for _ in 0..100 {
+            if done.load(Ordering::SeqCst) {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(done.load(Ordering::SeqCst));
+
+        assert_eq!(
+            timeout(Duration::from_secs(10), async { 1 }).await.unwrap(),
+            1
+        );
+        let pending = std::future::pending::<()>();
+        assert!(timeout(Duration::from_millis(10), pending).await.is_err());
+        let start = Instant::now();
+        sleep_until(start + Duration::from_millis(10)).await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,501 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serve the tables of a [`Connection`] over Arrow Flight
+//!
+//! This allows any Arrow Flight client (Python, Java, Go, BI tools with a
+//! Flight connector, ...) to query and ingest data without going through the
+//! Rust API.
+//!
+//! * `ListFlights` lists one flight per table.
+//! * `GetFlightInfo` / `GetSchema` accept either a path descriptor (`[table]`),
+//!   which describes a full scan of the table, or a command descriptor whose
+//!   command is a JSON encoded [`FlightQuery`].
+//! * `DoGet` executes the query in the ticket (a JSON encoded [`FlightQuery`]).
+//! * `DoPut` appends the uploaded batches to the table named by the path of the
+//!   descriptor.  The table is created if it does not exist.
+//!
+//! LanceDb does not have a SQL frontend and so queries are described with
+//! [`FlightQuery`] rather than with SQL text.
+//!
+//! ```no_run
+//! # use lancedb::connect;
+//! # use lancedb::serve::flight::FlightServer;
+//! # async fn doctest_helper() {
+//! let conn = connect("data/sample-lancedb").execute().await.unwrap();
+//! FlightServer::new(conn)
+//!     .serve("0.0.0.0:50051".parse().unwrap())
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use arrow_array::RecordBatchIterator;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_descriptor::DescriptorType;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use arrow_ipc::writer::IpcWriteOptions;
+use arrow_schema::Schema;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::arrow::SendableRecordBatchStream;
+use crate::error::{Error, Result};
+use crate::query::{ExecutableQuery, QueryBase, Select};
+use crate::Connection;
+
+type FlightStream<T> = Pin<Box<dyn Stream<Item = std::result::Result<T, Status>> + Send + 'static>>;
+
+/// A query that can be sent to the [`FlightServer`]
+///
+/// Queries are JSON encoded when used as a ticket or as the command of a
+/// flight descriptor.  For example:
+///
+/// ```json
+/// {"table": "my_table", "vector": [0.1, 0.2], "filter": "id > 5", "limit": 10}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlightQuery {
+    /// The table to query
+    pub table: String,
+    /// An SQL filter, see [`crate::query::QueryBase::only_if`]
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// The columns to return, all columns are returned if not specified
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+    /// The maximum number of rows to return
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// If set, a vector search is performed with this query vector
+    #[serde(default)]
+    pub vector: Option<Vec<f32>>,
+    /// The vector column to search
+    #[serde(default)]
+    pub column: Option<String>,
+    /// See [`crate::query::VectorQuery::nprobes`]
+    #[serde(default)]
+    pub nprobes: Option<usize>,
+    /// See [`crate::query::VectorQuery::refine_factor`]
+    #[serde(default)]
+    pub refine_factor: Option<u32>,
+}
+
+impl FlightQuery {
+    /// A query that scans the entire table
+    pub fn scan(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            ..Default::default()
+        }
+    }
+
+    fn from_json(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).map_err(|e| Error::InvalidInput {
+            message: format!("invalid flight query: {}", e),
+        })
+    }
+
+    fn from_descriptor(descriptor: &FlightDescriptor) -> Result<Self> {
+        match descriptor.r#type() {
+            DescriptorType::Path => match descriptor.path.as_slice() {
+                [table] => Ok(Self::scan(table)),
+                _ => Err(Error::InvalidInput {
+                    message: format!(
+                        "expected the descriptor path to be a single table name but got {:?}",
+                        descriptor.path
+                    ),
+                }),
+            },
+            DescriptorType::Cmd => Self::from_json(&descriptor.cmd),
+            DescriptorType::Unknown => Err(Error::InvalidInput {
+                message: "the flight descriptor has an unknown type".to_string(),
+            }),
+        }
+    }
+
+    fn to_ticket(&self) -> Result<Ticket> {
+        let bytes = serde_json::to_vec(self).map_err(|e| Error::Runtime {
+            message: format!("failed to encode flight query: {}", e),
+        })?;
+        Ok(Ticket::new(bytes))
+    }
+
+    async fn execute(&self, conn: &Connection) -> Result<SendableRecordBatchStream> {
+        let table = conn.open_table(&self.table).execute().await?;
+        let mut query = table.query();
+        if let Some(filter) = &self.filter {
+            query = query.only_if(filter.clone());
+        }
+        if let Some(columns) = &self.columns {
+            query = query.select(Select::columns(columns.as_slice()));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        match &self.vector {
+            None => query.execute().await,
+            Some(vector) => {
+                let mut query = query.nearest_to(vector.as_slice())?;
+                if let Some(column) = &self.column {
+                    query = query.column(column);
+                }
+                if let Some(nprobes) = self.nprobes {
+                    query = query.nprobes(nprobes);
+                }
+                if let Some(refine_factor) = self.refine_factor {
+                    query = query.refine_factor(refine_factor);
+                }
+                query.execute().await
+            }
+        }
+    }
+}
+
+fn to_status(err: Error) -> Status {
+    match err {
+        Error::TableNotFound { .. } => Status::not_found(err.to_string()),
+        Error::TableAlreadyExists { .. } => Status::already_exists(err.to_string()),
+        Error::InvalidTableName { .. } | Error::InvalidInput { .. } | Error::Schema { .. } => {
+            Status::invalid_argument(err.to_string())
+        }
+        Error::Overloaded { .. } | Error::MemoryLimitExceeded { .. } => {
+            Status::resource_exhausted(err.to_string())
+        }
+        Error::NotSupported { .. } => Status::unimplemented(err.to_string()),
+        _ => Status::internal(err.to_string()),
+    }
+}
+
+fn flight_info(
+    schema: &Schema,
+    descriptor: FlightDescriptor,
+    query: &FlightQuery,
+) -> Result<FlightInfo> {
+    Ok(FlightInfo::new()
+        .try_with_schema(schema)?
+        .with_descriptor(descriptor)
+        .with_endpoint(FlightEndpoint::new().with_ticket(query.to_ticket()?)))
+}
+
+/// An Arrow Flight service backed by a [`Connection`]
+///
+/// See the [module documentation](self) for the supported operations.
+#[derive(Clone)]
+pub struct FlightServer {
+    connection: Connection,
+}
+
+impl FlightServer {
+    pub fn new(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    /// Create a tonic service, for use with a custom tonic server
+    pub fn into_service(self) -> FlightServiceServer<Self> {
+        FlightServiceServer::new(self)
+    }
+
+    /// Serve the connection on the given address until the server fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await
+            .map_err(|e| Error::Runtime {
+                message: format!("flight server failed: {}", e),
+            })
+    }
+
+    async fn query_info(&self, descriptor: FlightDescriptor) -> Result<FlightInfo> {
+        let query = FlightQuery::from_descriptor(&descriptor)?;
+        let schema = query.execute(&self.connection).await?.schema();
+        flight_info(&schema, descriptor, &query)
+    }
+
+    async fn table_infos(&self) -> Result<Vec<FlightInfo>> {
+        let mut infos = Vec::new();
+        for name in self.connection.table_names().execute().await? {
+            let table = self.connection.open_table(&name).execute().await?;
+            let schema = table.schema().await?;
+            infos.push(flight_info(
+                &schema,
+                FlightDescriptor::new_path(vec![name.clone()]),
+                &FlightQuery::scan(name),
+            )?);
+        }
+        Ok(infos)
+    }
+
+    async fn ingest(
+        &self,
+        data: impl Stream<Item = std::result::Result<FlightData, FlightError>> + Send + Unpin + 'static,
+    ) -> Result<u64> {
+        let mut data = data.peekable();
+        let descriptor = match Pin::new(&mut data).peek().await {
+            Some(Ok(first)) => first.flight_descriptor.clone(),
+            _ => None,
+        };
+        let table_name = match descriptor.as_ref().map(|d| d.path.as_slice()) {
+            Some([table]) => table.clone(),
+            _ => {
+                return Err(Error::InvalidInput {
+                    message: "DoPut requires a descriptor whose path is a single table name"
+                        .to_string(),
+                })
+            }
+        };
+
+        let mut batches = FlightRecordBatchStream::new_from_flight_data(data);
+        let mut collected = Vec::new();
+        while let Some(batch) = batches.next().await {
+            collected.push(batch.map_err(|e| Error::InvalidInput {
+                message: format!("failed to decode uploaded data: {}", e),
+            })?);
+        }
+        let Some(schema) = batches.schema().cloned() else {
+            return Err(Error::InvalidInput {
+                message: "DoPut did not receive a schema".to_string(),
+            });
+        };
+        let num_rows = collected.iter().map(|b| b.num_rows() as u64).sum();
+        let reader = RecordBatchIterator::new(collected.into_iter().map(Ok), schema);
+
+        match self.connection.open_table(&table_name).execute().await {
+            Ok(table) => table.add(reader).execute().await?,
+            Err(Error::TableNotFound { .. }) => {
+                self.connection
+                    .create_table(&table_name, reader)
+                    .execute()
+                    .await?;
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(num_rows)
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for FlightServer {
+    type HandshakeStream = FlightStream<HandshakeResponse>;
+    type ListFlightsStream = FlightStream<FlightInfo>;
+    type DoGetStream = FlightStream<FlightData>;
+    type DoPutStream = FlightStream<PutResult>;
+    type DoExchangeStream = FlightStream<FlightData>;
+    type DoActionStream = FlightStream<arrow_flight::Result>;
+    type ListActionsStream = FlightStream<ActionType>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> std::result::Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("handshake is not supported"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> std::result::Result<Response<Self::ListFlightsStream>, Status> {
+        let infos = self.table_infos().await.map_err(to_status)?;
+        Ok(Response::new(Box::pin(stream::iter(
+            infos.into_iter().map(Ok),
+        ))))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let info = self
+            .query_info(request.into_inner())
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(info))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info is not supported"))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<SchemaResult>, Status> {
+        let query = FlightQuery::from_descriptor(&request.into_inner()).map_err(to_status)?;
+        let schema = query
+            .execute(&self.connection)
+            .await
+            .map_err(to_status)?
+            .schema();
+        let result = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|e: arrow_schema::ArrowError| Status::internal(e.to_string()))?;
+        Ok(Response::new(result))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> std::result::Result<Response<Self::DoGetStream>, Status> {
+        let query = FlightQuery::from_json(&request.into_inner().ticket).map_err(to_status)?;
+        let batches = query
+            .execute(&self.connection)
+            .await
+            .map_err(to_status)?
+            .map_err(|e| FlightError::ExternalError(Box::new(e)));
+        let stream = FlightDataEncoderBuilder::new()
+            .build(batches)
+            .map_err(Status::from);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn do_put(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoPutStream>, Status> {
+        let num_rows = self
+            .ingest(request.into_inner().map_err(FlightError::from))
+            .await
+            .map_err(to_status)?;
+        let result = PutResult {
+            app_metadata: format!("{{\"num_rows\": {}}}", num_rows)
+                .into_bytes()
+                .into(),
+        };
+        Ok(Response::new(Box::pin(stream::once(
+            async move { Ok(result) },
+        ))))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange is not supported"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> std::result::Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action is not supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> std::result::Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(Box::pin(stream::empty())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch};
+    use arrow_flight::utils::batches_to_flight_data;
+    use arrow_schema::{DataType, Field};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::connect;
+
+    #[test]
+    fn test_flight_query_json() {
+        let query: FlightQuery =
+            serde_json::from_str(r#"{"table": "t", "vector": [1.0, 2.0], "limit": 3}"#).unwrap();
+        assert_eq!(query.table, "t");
+        assert_eq!(query.vector, Some(vec![1.0, 2.0]));
+        assert_eq!(query.limit, Some(3));
+        assert_eq!(query.filter, None);
+        assert!(FlightQuery::from_json(b"{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_and_put() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let server = FlightServer::new(conn.clone());
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+
+        let mut flight_data = batches_to_flight_data(&schema, vec![batch]).unwrap();
+        flight_data[0].flight_descriptor = Some(FlightDescriptor::new_path(vec!["t".into()]));
+        let upload = || stream::iter(flight_data.clone().into_iter().map(Ok));
+        // The first upload creates the table, the second appends to it
+        assert_eq!(server.ingest(upload()).await.unwrap(), 10);
+        assert_eq!(server.ingest(upload()).await.unwrap(), 10);
+        let table = conn.open_table("t").execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 20);
+
+        // An upload must name the table
+        let mut unnamed = flight_data.clone();
+        unnamed[0].flight_descriptor = None;
+        assert!(server
+            .ingest(stream::iter(unnamed.into_iter().map(Ok)))
+            .await
+            .is_err());
+
+        let info = server
+            .query_info(FlightDescriptor::new_path(vec!["t".into()]))
+            .await
+            .unwrap();
+        let ticket = info.endpoint[0].ticket.clone().unwrap();
+        let query = FlightQuery::from_json(&ticket.ticket).unwrap();
+        assert_eq!(query, FlightQuery::scan("t"));
+
+        let query = FlightQuery {
+            filter: Some("id >= 5".to_string()),
+            ..query
+        };
+        let rows = query
+            .execute(&conn)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .iter()
+            .map(|b| b.num_rows())
+            .sum::<usize>();
+        assert_eq!(rows, 10);
+
+        assert_eq!(server.table_infos().await.unwrap().len(), 1);
+        let missing = server
+            .query_info(FlightDescriptor::new_path(vec!["missing".into()]))
+            .await
+            .unwrap_err();
+        assert_eq!(to_status(missing).code(), tonic::Code::NotFound);
+    }
+}
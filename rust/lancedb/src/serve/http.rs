@@ -0,0 +1,1009 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A self-hosted HTTP server that speaks the LanceDb REST protocol
+//!
+//! This is the server half of the remote client.  A [`crate::Connection`]
+//! opened with a `db://` URI and a `host_override` pointing at this server can
+//! be used to access the tables of the connection the server was created with.
+//!
+//! The following endpoints are supported.  Data is exchanged in the Arrow IPC
+//! format and everything else is JSON.
+//!
+//! | Method | Path                              | Body                        |
+//! |--------|-----------------------------------|-----------------------------|
+//! | GET    | `/v1/table/`                      |                             |
+//! | POST   | `/v1/table/{name}/create/`        | Arrow IPC                   |
+//! | POST   | `/v1/table/{name}/insert/`        | Arrow IPC (`?mode=`)        |
+//! | POST   | `/v1/table/{name}/describe/`      |                             |
+//! | POST   | `/v1/table/{name}/count_rows/`    | `{"predicate": ...}`        |
+//! | POST   | `/v1/table/{name}/query/`         | [`QueryRequest`]            |
+//! | POST   | `/v1/table/{name}/delete/`        | `{"predicate": ...}`        |
+//! | POST   | `/v1/table/{name}/drop/`          |                             |
+//! | POST   | `/v1/table/{name}/create_index/`  | JSON index parameters       |
+//! | POST   | `/v1/table/{name}/create_scalar_index/` | JSON index parameters |
+//! | POST   | `/v1/table/{name}/merge_insert/`  | Arrow IPC (`?on=...`)       |
+//! | POST   | `/v1/table/{name}/optimize/`      | `{"action": ...}`           |
+//! | POST   | `/v1/table/{name}/add_columns/`   | `{"new_columns": [...]}`    |
+//! | POST   | `/v1/table/{name}/alter_columns/` | `{"alterations": [...]}`    |
+//! | POST   | `/v1/table/{name}/drop_columns/`  | `{"columns": [...]}`        |
+//!
+//! Every path is also accepted without the trailing slash.
+//!
+//! Request bodies are limited to [`DEFAULT_MAX_BODY_SIZE`] bytes, see
+//! [`HttpServer::max_body_size`].
+//!
+//! A `create` request with an `x-lancedb-idempotency-key` header succeeds if
+//! the table was already created by a request with the same key, see
+//! [`crate::connection::CreateTableBuilder::idempotency_token`].
+//!
+//! ```no_run
+//! # use lancedb::connect;
+//! # use lancedb::serve::http::HttpServer;
+//! # async fn doctest_helper() {
+//! let conn = connect("data/sample-lancedb").execute().await.unwrap();
+//! HttpServer::new(conn)
+//!     .api_key("my-secret-key")
+//!     .serve("0.0.0.0:10024".parse().unwrap())
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use arrow_array::{RecordBatch, RecordBatchIterator};
+use arrow_ipc::reader::{FileReader, StreamReader};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::SchemaRef;
+use axum::body::{Bytes, StreamBody};
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, MethodRouter};
+use axum::{Json, Router};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use lance::dataset::optimize::CompactionOptions;
+use lance_index::optimize::OptimizeOptions;
+
+use crate::arrow::SendableRecordBatchStream;
+use crate::error::{Error, Result};
+use crate::index::scalar::BTreeIndexBuilder;
+use crate::index::vector::IvfPqIndexBuilder;
+use crate::index::Index;
+use crate::query::{ExecutableQuery, QueryBase, Select};
+use crate::remote::protocol::{
+    parse_metric_type, AddColumnsRequest, AlterColumnsRequest, CompactionResponse,
+    CreateIndexRequest, DropColumnsRequest, MergeInsertParams, OptimizeRequest, OptimizeResponse,
+    PruneResponse, IDEMPOTENCY_KEY_HEADER,
+};
+use crate::table::{AddDataMode, ColumnAlteration, NewColumnTransform, OptimizeAction};
+use crate::{Connection, DistanceType};
+
+const ARROW_FILE_CONTENT_TYPE: &str = "application/vnd.apache.arrow.file";
+
+/// The default limit of the size of a request body, 256 MiB
+pub const DEFAULT_MAX_BODY_SIZE: usize = 256 * 1024 * 1024;
+
+/// An error that is returned to the client with an appropriate status code
+struct ServerError(Error);
+
+impl From<Error> for ServerError {
+    fn from(err: Error) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            Error::TableNotFound { .. } => StatusCode::NOT_FOUND,
+            Error::TableAlreadyExists { .. } => StatusCode::CONFLICT,
+            Error::InvalidTableName { .. } | Error::InvalidInput { .. } | Error::Schema { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+            Error::Overloaded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Error::MemoryLimitExceeded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Error::NotSupported { .. } => StatusCode::NOT_IMPLEMENTED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.0.to_string()).into_response()
+    }
+}
+
+type ServerResult<T> = std::result::Result<T, ServerError>;
+
+struct ServerState {
+    connection: Connection,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ListTablesParams {
+    limit: Option<u32>,
+    page_token: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ListTablesResponse {
+    tables: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct InsertParams {
+    #[serde(default)]
+    mode: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct PredicateRequest {
+    #[serde(default)]
+    predicate: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DescribeResponse {
+    version: u64,
+    num_rows: usize,
+}
+
+/// The body of a `query` request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryRequest {
+    /// The query vector, if empty a plain (non-vector) query is performed
+    #[serde(default)]
+    pub vector: Vec<f32>,
+    /// The vector column to search
+    #[serde(default)]
+    pub vector_column: Option<String>,
+    /// The number of results to return
+    #[serde(default)]
+    pub k: Option<usize>,
+    #[serde(default)]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+    #[serde(default)]
+    pub nprobes: Option<usize>,
+    #[serde(default)]
+    pub refine_factor: Option<u32>,
+    #[serde(default)]
+    pub metric: Option<DistanceType>,
+    /// Defaults to true
+    #[serde(default)]
+    pub prefilter: Option<bool>,
+}
+
+/// Decode a request body, which may be in either the IPC file or stream format
+fn decode_ipc(body: Bytes) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    if let Ok(reader) = FileReader::try_new(Cursor::new(body.clone()), None) {
+        let schema = reader.schema();
+        return Ok((schema, reader.collect::<std::result::Result<Vec<_>, _>>()?));
+    }
+    let reader =
+        StreamReader::try_new(Cursor::new(body), None).map_err(|e| Error::InvalidInput {
+            message: format!("the request body is not valid Arrow IPC data: {}", e),
+        })?;
+    let schema = reader.schema();
+    Ok((schema, reader.collect::<std::result::Result<Vec<_>, _>>()?))
+}
+
+async fn list_tables(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<ListTablesParams>,
+) -> ServerResult<Json<ListTablesResponse>> {
+    let mut builder = state.connection.table_names();
+    if let Some(limit) = params.limit {
+        builder = builder.limit(limit);
+    }
+    if let Some(page_token) = params.page_token {
+        builder = builder.start_after(page_token);
+    }
+    Ok(Json(ListTablesResponse {
+        tables: builder.execute().await?,
+    }))
+}
+
+async fn create_table(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ServerResult<StatusCode> {
+    let (schema, batches) = decode_ipc(body)?;
+    let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+    let mut builder = state.connection.create_table(name, reader);
+    if let Some(token) = headers.get(IDEMPOTENCY_KEY_HEADER) {
+        let token = token.to_str().map_err(|_| Error::InvalidInput {
+            message: format!("the {} header is not valid", IDEMPOTENCY_KEY_HEADER),
+        })?;
+        builder = builder.idempotency_token(token);
+    }
+    builder.execute().await?;
+    Ok(StatusCode::OK)
+}
+
+async fn insert(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Query(params): Query<InsertParams>,
+    body: Bytes,
+) -> ServerResult<StatusCode> {
+    let mode = match params.mode.as_deref() {
+        None | Some("append") => AddDataMode::Append,
+        Some("overwrite") => AddDataMode::Overwrite,
+        Some(other) => {
+            return Err(Error::InvalidInput {
+                message: format!("unknown insert mode '{}'", other),
+            }
+            .into())
+        }
+    };
+    let (schema, batches) = decode_ipc(body)?;
+    let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+    let table = state.connection.open_table(name).execute().await?;
+    table.add(reader).mode(mode).execute().await?;
+    Ok(StatusCode::OK)
+}
+
+async fn describe(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+) -> ServerResult<Json<DescribeResponse>> {
+    let table = state.connection.open_table(name).execute().await?;
+    Ok(Json(DescribeResponse {
+        version: table.version().await?,
+        num_rows: table.count_rows(None).await?,
+    }))
+}
+
+async fn count_rows(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> ServerResult<Json<usize>> {
+    // An empty body counts all rows
+    let request = if body.is_empty() {
+        PredicateRequest::default()
+    } else {
+        parse_json::<PredicateRequest>(&body)?
+    };
+    let table = state.connection.open_table(name).execute().await?;
+    Ok(Json(table.count_rows(request.predicate).await?))
+}
+
+async fn query(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<QueryRequest>,
+) -> ServerResult<Response> {
+    let table = state.connection.open_table(name).execute().await?;
+    let mut query = table.query();
+    if let Some(filter) = request.filter {
+        query = query.only_if(filter);
+    }
+    if let Some(columns) = &request.columns {
+        query = query.select(Select::columns(columns.as_slice()));
+    }
+    if let Some(k) = request.k {
+        query = query.limit(k);
+    }
+    let stream = if request.vector.is_empty() {
+        query.execute().await?
+    } else {
+        let mut query = query.nearest_to(request.vector)?;
+        if let Some(column) = &request.vector_column {
+            query = query.column(column);
+        }
+        if let Some(nprobes) = request.nprobes {
+            query = query.nprobes(nprobes);
+        }
+        if let Some(refine_factor) = request.refine_factor {
+            query = query.refine_factor(refine_factor);
+        }
+        if let Some(metric) = request.metric {
+            query = query.distance_type(metric);
+        }
+        if request.prefilter == Some(false) {
+            query = query.postfilter();
+        }
+        query.execute().await?
+    };
+    Ok((
+        [(header::CONTENT_TYPE, ARROW_FILE_CONTENT_TYPE)],
+        StreamBody::new(ipc_file_body(stream)?),
+    )
+        .into_response())
+}
+
+/// Encode the results of a query as an Arrow IPC file while they are read
+///
+/// Each batch is sent as soon as it is encoded and the footer follows the
+/// last batch, so only one batch is held in memory.  The status is sent
+/// before the results are read, an error while reading them ends the
+/// response early and the client fails to decode the truncated file.
+fn ipc_file_body(stream: SendableRecordBatchStream) -> Result<BoxStream<'static, Result<Bytes>>> {
+    let writer = FileWriter::try_new(Vec::new(), &stream.schema())?;
+    let body =
+        futures::stream::try_unfold((stream, Some(writer)), |(mut stream, writer)| async move {
+            let Some(mut writer) = writer else {
+                return Ok(None);
+            };
+            match stream.try_next().await? {
+                Some(batch) => {
+                    writer.write(&batch)?;
+                    // The bytes that are still buffered by the writer are sent later
+                    let bytes = Bytes::from(std::mem::take(writer.get_mut()));
+                    Ok(Some((bytes, (stream, Some(writer)))))
+                }
+                None => {
+                    let bytes = Bytes::from(writer.into_inner()?);
+                    Ok(Some((bytes, (stream, None))))
+                }
+            }
+        });
+    Ok(body.boxed())
+}
+
+async fn delete(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<PredicateRequest>,
+) -> ServerResult<StatusCode> {
+    let Some(predicate) = request.predicate else {
+        return Err(Error::InvalidInput {
+            message: "a predicate is required to delete rows".to_string(),
+        }
+        .into());
+    };
+    let table = state.connection.open_table(name).execute().await?;
+    table.delete(&predicate).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn drop_table(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+) -> ServerResult<StatusCode> {
+    state.connection.drop_table(name).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn create_index(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<CreateIndexRequest>,
+) -> ServerResult<StatusCode> {
+    let index = match request.index_type.as_str() {
+        "scalar" => Index::BTree(BTreeIndexBuilder::default()),
+        "vector" => {
+            let mut builder = IvfPqIndexBuilder::default();
+            if let Some(metric_type) = &request.metric_type {
+                let distance_type =
+                    parse_metric_type(metric_type).ok_or_else(|| Error::InvalidInput {
+                        message: format!("unknown metric type '{}'", metric_type),
+                    })?;
+                builder = builder.distance_type(distance_type);
+            }
+            if let Some(num_partitions) = request.num_partitions {
+                builder = builder.num_partitions(num_partitions);
+            }
+            if let Some(num_sub_vectors) = request.num_sub_vectors {
+                builder = builder.num_sub_vectors(num_sub_vectors);
+            }
+            if let Some(num_bits) = request.num_bits {
+                builder = builder.num_bits(num_bits);
+            }
+            if let Some(sample_rate) = request.sample_rate {
+                builder = builder.sample_rate(sample_rate);
+            }
+            if let Some(max_iterations) = request.max_iterations {
+                builder = builder.max_iterations(max_iterations);
+            }
+            Index::IvfPq(builder)
+        }
+        other => {
+            return Err(Error::InvalidInput {
+                message: format!("unknown index type '{}'", other),
+            }
+            .into())
+        }
+    };
+    let table = state.connection.open_table(name).execute().await?;
+    table
+        .create_index(&[request.column], index)
+        .replace(request.replace)
+        .execute()
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn merge_insert(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Query(params): Query<MergeInsertParams>,
+    body: Bytes,
+) -> ServerResult<StatusCode> {
+    let (schema, batches) = decode_ipc(body)?;
+    let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+    let table = state.connection.open_table(name).execute().await?;
+    let on = params.on.split(',').map(str::trim).collect::<Vec<_>>();
+    let mut builder = table.merge_insert(&on);
+    if params.when_matched_update_all {
+        builder.when_matched_update_all(params.when_matched_update_all_filt);
+    }
+    if params.when_not_matched_insert_all {
+        builder.when_not_matched_insert_all();
+    }
+    if params.when_not_matched_by_source_delete {
+        builder.when_not_matched_by_source_delete(params.when_not_matched_by_source_delete_filt);
+    }
+    builder.execute(Box::new(reader)).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn optimize(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<OptimizeRequest>,
+) -> ServerResult<Json<OptimizeResponse>> {
+    let table = state.connection.open_table(name).execute().await?;
+    if let OptimizeRequest::Prune {
+        older_than_seconds,
+        delete_unverified,
+        dry_run: true,
+    } = request
+    {
+        let preview = table
+            .preview_prune(prune_age(older_than_seconds)?, delete_unverified)
+            .await?;
+        return Ok(Json(OptimizeResponse {
+            compaction: None,
+            prune: None,
+            prune_preview: Some(preview),
+        }));
+    }
+    let action = match request {
+        OptimizeRequest::All => OptimizeAction::All,
+        OptimizeRequest::Compact {
+            target_rows_per_fragment,
+            max_rows_per_group,
+            materialize_deletions,
+            materialize_deletions_threshold,
+        } => OptimizeAction::Compact {
+            options: CompactionOptions {
+                target_rows_per_fragment,
+                max_rows_per_group,
+                materialize_deletions,
+                materialize_deletions_threshold,
+                ..Default::default()
+            },
+            remap_options: None,
+        },
+        OptimizeRequest::Prune {
+            older_than_seconds,
+            delete_unverified,
+            ..
+        } => OptimizeAction::Prune {
+            older_than: prune_age(older_than_seconds)?,
+            delete_unverified,
+        },
+        OptimizeRequest::Index {
+            num_indices_to_merge,
+        } => OptimizeAction::Index(OptimizeOptions {
+            num_indices_to_merge,
+        }),
+    };
+    let stats = table.optimize(action).await?;
+    Ok(Json(OptimizeResponse {
+        compaction: stats.compaction.map(|c| CompactionResponse {
+            fragments_removed: c.fragments_removed,
+            fragments_added: c.fragments_added,
+            files_removed: c.files_removed,
+            files_added: c.files_added,
+        }),
+        prune: stats.prune.map(|p| PruneResponse {
+            bytes_removed: p.bytes_removed,
+            old_versions: p.old_versions,
+        }),
+        prune_preview: None,
+    }))
+}
+
+async fn add_columns(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<AddColumnsRequest>,
+) -> ServerResult<StatusCode> {
+    let expressions = request
+        .new_columns
+        .into_iter()
+        .map(|c| (c.name, c.expression))
+        .collect();
+    let table = state.connection.open_table(name).execute().await?;
+    table
+        .add_columns(NewColumnTransform::SqlExpressions(expressions), None)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn alter_columns(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<AlterColumnsRequest>,
+) -> ServerResult<StatusCode> {
+    let alterations = request
+        .alterations
+        .into_iter()
+        .map(|a| {
+            let mut alteration = ColumnAlteration::new(a.path);
+            if let Some(rename) = a.rename {
+                alteration = alteration.rename(rename);
+            }
+            if let Some(nullable) = a.nullable {
+                alteration = alteration.set_nullable(nullable);
+            }
+            alteration
+        })
+        .collect::<Vec<_>>();
+    let table = state.connection.open_table(name).execute().await?;
+    table.alter_columns(&alterations).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn drop_columns(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    Json(request): Json<DropColumnsRequest>,
+) -> ServerResult<StatusCode> {
+    let columns = request
+        .columns
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let table = state.connection.open_table(name).execute().await?;
+    table.drop_columns(&columns).await?;
+    Ok(StatusCode::OK)
+}
+
+/// The age of the versions to prune, which the client may send out of range
+fn prune_age(older_than_seconds: i64) -> Result<chrono::Duration> {
+    chrono::Duration::try_seconds(older_than_seconds).ok_or_else(|| Error::InvalidInput {
+        message: format!("older_than_seconds is out of range: {}", older_than_seconds),
+    })
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|e| Error::InvalidInput {
+        message: format!("invalid request body: {}", e),
+    })
+}
+
+async fn check_api_key<B>(
+    State(state): State<Arc<ServerState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if let Some(expected) = &state.api_key {
+        let provided = request.headers().get("x-api-key").map(|v| v.as_bytes());
+        if !provided.is_some_and(|provided| api_key_matches(provided, expected.as_bytes())) {
+            return (StatusCode::UNAUTHORIZED, "invalid api key").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Compare API keys in constant time
+///
+/// The time a failed check takes does not tell how much of the key was
+/// right.  The digests of the keys are compared, which also hides the length
+/// of the expected key.
+fn api_key_matches(provided: &[u8], expected: &[u8]) -> bool {
+    let provided = Sha256::digest(provided);
+    let expected = Sha256::digest(expected);
+    provided
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// An HTTP server backed by a [`Connection`]
+///
+/// See the [module documentation](self) for the supported endpoints.
+pub struct HttpServer {
+    connection: Connection,
+    api_key: Option<String>,
+    max_body_size: usize,
+}
+
+impl HttpServer {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            api_key: None,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Require clients to send this key in the `x-api-key` header
+    ///
+    /// By default any client is accepted.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// The largest request body, in bytes, that the server accepts
+    ///
+    /// Larger requests, e.g. an insert of too much data at once, are rejected
+    /// with 413 Payload Too Large.  Defaults to [`DEFAULT_MAX_BODY_SIZE`].
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Create the router, for use with a custom server
+    pub fn router(self) -> Router {
+        let state = Arc::new(ServerState {
+            connection: self.connection,
+            api_key: self.api_key,
+        });
+        let routes: Vec<(&str, MethodRouter<Arc<ServerState>>)> = vec![
+            ("/v1/table", get(list_tables)),
+            ("/v1/table/:name/create", post(create_table)),
+            ("/v1/table/:name/insert", post(insert)),
+            ("/v1/table/:name/describe", post(describe)),
+            ("/v1/table/:name/count_rows", post(count_rows)),
+            ("/v1/table/:name/query", post(query)),
+            ("/v1/table/:name/delete", post(delete)),
+            ("/v1/table/:name/drop", post(drop_table)),
+            ("/v1/table/:name/create_index", post(create_index)),
+            ("/v1/table/:name/create_scalar_index", post(create_index)),
+            ("/v1/table/:name/merge_insert", post(merge_insert)),
+            ("/v1/table/:name/optimize", post(optimize)),
+            ("/v1/table/:name/add_columns", post(add_columns)),
+            ("/v1/table/:name/alter_columns", post(alter_columns)),
+            ("/v1/table/:name/drop_columns", post(drop_columns)),
+        ];
+        let mut router = Router::new();
+        for (path, handler) in routes {
+            router = router
+                .route(path, handler.clone())
+                .route(&format!("{}/", path), handler);
+        }
+        router
+            .layer(DefaultBodyLimit::max(self.max_body_size))
+            .layer(middleware::from_fn_with_state(state.clone(), check_api_key))
+            .with_state(state)
+    }
+
+    /// Serve the connection on the given address until the server fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        axum::Server::bind(&addr)
+            .serve(self.router().into_make_service())
+            .await
+            .map_err(|e| Error::Runtime {
+                message: format!("http server failed: {}", e),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Int32Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::connect;
+
+    async fn start_server(conn: Connection) -> String {
+        start_router(HttpServer::new(conn).api_key("secret").router()).await
+    }
+
+    async fn start_router(router: Router) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        crate::runtime::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(router.into_make_service())
+                .await
+                .unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    fn make_batches() -> impl arrow_array::RecordBatchReader + Send + 'static {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        RecordBatchIterator::new(vec![Ok(batch)], schema)
+    }
+
+    #[tokio::test]
+    async fn test_remote_client_against_server() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let local = connect(uri).execute().await.unwrap();
+        let host = start_server(local.clone()).await;
+
+        let remote = connect("db://my-db")
+            .api_key("secret")
+            .region("us-east-1")
+            .host_override(&host)
+            .execute()
+            .await
+            .unwrap();
+        remote
+            .create_table("from_remote", make_batches())
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(
+            remote.table_names().execute().await.unwrap(),
+            vec!["from_remote".to_string()]
+        );
+        let table = local.open_table("from_remote").execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        // The protocol endpoints that the client does not use yet
+        let client = reqwest::Client::new();
+        let count = client
+            .post(format!("{}/v1/table/from_remote/count_rows/", host))
+            .header("x-api-key", "secret")
+            .json(&serde_json::json!({"predicate": "i >= 5"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(count.status(), 200);
+        assert_eq!(count.json::<usize>().await.unwrap(), 5);
+
+        let results = client
+            .post(format!("{}/v1/table/from_remote/query/", host))
+            .header("x-api-key", "secret")
+            .json(&serde_json::json!({"filter": "i < 3"}))
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        let (_, batches) = decode_ipc(results).unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+
+        let missing = client
+            .post(format!("{}/v1/table/missing/describe/", host))
+            .header("x-api-key", "secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), 404);
+
+        let unauthorized = client
+            .get(format!("{}/v1/table/", host))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status(), 401);
+        let wrong_key = client
+            .get(format!("{}/v1/table/", host))
+            .header("x-api-key", "secres")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(wrong_key.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_request_limits() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let local = connect(uri).execute().await.unwrap();
+        local
+            .create_table("test", make_batches())
+            .execute()
+            .await
+            .unwrap();
+        let host = start_router(HttpServer::new(local).max_body_size(1024).router()).await;
+
+        let client = reqwest::Client::new();
+        let too_large = client
+            .post(format!("{}/v1/table/test/insert/", host))
+            .header(header::CONTENT_TYPE, ARROW_FILE_CONTENT_TYPE)
+            .body(vec![0u8; 2048])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(too_large.status(), 413);
+
+        for dry_run in [false, true] {
+            let out_of_range = client
+                .post(format!("{}/v1/table/test/optimize/", host))
+                .json(&serde_json::json!({
+                    "action": "prune",
+                    "older_than_seconds": i64::MAX,
+                    "dry_run": dry_run,
+                }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(out_of_range.status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_api_key_matches() {
+        assert!(api_key_matches(b"secret", b"secret"));
+        assert!(!api_key_matches(b"secres", b"secret"));
+        assert!(!api_key_matches(b"secret2", b"secret"));
+        assert!(!api_key_matches(b"", b"secret"));
+    }
+
+    #[tokio::test]
+    async fn test_ipc_file_body() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batches = (0..3)
+            .map(|n| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(n * 10..(n + 1) * 10))],
+                )
+                .map_err(Error::from)
+            })
+            .collect::<Vec<_>>();
+        let stream: SendableRecordBatchStream = Box::pin(crate::arrow::SimpleRecordBatchStream {
+            schema,
+            stream: futures::stream::iter(batches),
+        });
+        let chunks = ipc_file_body(stream)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        // The batches are sent as they are encoded
+        assert!(chunks.len() > 1);
+        let (_, batches) = decode_ipc(Bytes::from(chunks.concat())).unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_remote_create_table_is_idempotent() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let local = connect(uri).execute().await.unwrap();
+        let host = start_server(local.clone()).await;
+        let remote = connect("db://my-db")
+            .api_key("secret")
+            .region("us-east-1")
+            .host_override(&host)
+            .execute()
+            .await
+            .unwrap();
+
+        remote
+            .create_table("test", make_batches())
+            .idempotency_token("create-test")
+            .execute()
+            .await
+            .unwrap();
+        // Simulates a retry after a create whose response was lost
+        remote
+            .create_table("test", make_batches())
+            .idempotency_token("create-test")
+            .execute()
+            .await
+            .unwrap();
+        let table = local.open_table("test").execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        let err = remote
+            .create_table("test", make_batches())
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::TableAlreadyExists { .. }));
+        remote
+            .create_table("test", make_batches())
+            .mode(crate::connection::CreateTableMode::exist_ok(|b| b))
+            .execute()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remote_table_operations() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        // The server makes changes through its own table handles
+        let local = connect(uri)
+            .read_consistency_interval(std::time::Duration::from_secs(0))
+            .execute()
+            .await
+            .unwrap();
+        let host = start_server(local.clone()).await;
+        let remote = connect("db://my-db")
+            .api_key("secret")
+            .region("us-east-1")
+            .host_override(&host)
+            .execute()
+            .await
+            .unwrap();
+        let table = remote
+            .create_table("ops", make_batches())
+            .execute()
+            .await
+            .unwrap();
+        let local_table = local.open_table("ops").execute().await.unwrap();
+
+        table
+            .create_index(&["i"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(local_table.list_indices().await.unwrap().len(), 1);
+
+        // Rows 10..15 are new, 5..10 already exist
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(5..15))],
+        )
+        .unwrap();
+        let mut merge = table.merge_insert(&["i"]);
+        merge.when_not_matched_insert_all();
+        merge
+            .execute(Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema)))
+            .await
+            .unwrap();
+        assert_eq!(local_table.count_rows(None).await.unwrap(), 15);
+
+        table
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("j".to_string(), "i * 2".to_string())]),
+                None,
+            )
+            .await
+            .unwrap();
+        table
+            .alter_columns(&[ColumnAlteration::new("j".to_string()).rename("k".to_string())])
+            .await
+            .unwrap();
+        let schema = local_table.schema().await.unwrap();
+        assert_eq!(schema.field(1).name(), "k");
+        table.drop_columns(&["k"]).await.unwrap();
+        assert_eq!(local_table.schema().await.unwrap().fields().len(), 1);
+
+        let stats = table
+            .optimize(OptimizeAction::Compact {
+                options: CompactionOptions::default(),
+                remap_options: None,
+            })
+            .await
+            .unwrap();
+        assert!(stats.compaction.unwrap().fragments_removed > 0);
+        assert_eq!(local_table.count_rows(None).await.unwrap(), 15);
+    }
+}
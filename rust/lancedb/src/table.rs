@@ -14,53 +14,172 @@
 
 //! LanceDB Table APIs
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 
 use arrow::array::AsArray;
-use arrow::datatypes::Float32Type;
-use arrow_array::{RecordBatchIterator, RecordBatchReader};
+use arrow::datatypes::{Float32Type, UInt64Type};
+use arrow_array::{RecordBatch, RecordBatchIterator, RecordBatchReader};
 use arrow_schema::{DataType, Field, Schema, SchemaRef};
 use async_trait::async_trait;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
 use lance::dataset::builder::DatasetBuilder;
 use lance::dataset::cleanup::RemovalStats;
+use lance::dataset::fragment::FileFragment;
 use lance::dataset::optimize::{
     compact_files, CompactionMetrics, CompactionOptions, IndexRemapperOptions,
 };
 use lance::dataset::scanner::{DatasetRecordBatchStream, Scanner};
+use lance::dataset::transaction::Operation;
 pub use lance::dataset::ColumnAlteration;
 pub use lance::dataset::NewColumnTransform;
 pub use lance::dataset::ReadParams;
+pub use lance::dataset::Version;
 use lance::dataset::{
     Dataset, UpdateBuilder as LanceUpdateBuilder, WhenMatched, WriteMode, WriteParams,
 };
 use lance::dataset::{MergeInsertBuilder as LanceMergeInsertBuilder, WhenNotMatchedBySource};
-use lance::io::WrappingObjectStore;
+use lance::io::{ObjectStore, ObjectStoreParams, WrappingObjectStore};
 use lance_index::IndexType;
 use lance_index::{optimize::OptimizeOptions, DatasetIndexExt};
-use log::info;
+use log::{info, warn};
 use snafu::whatever;
 
-use crate::arrow::IntoArrow;
+use crate::arrow::{
+    stream_into_reader, IntoArrow, SendableRecordBatchStream, SimpleRecordBatchStream,
+};
+use crate::connection::admission::{
+    hold_while_streaming, maybe_acquire, AdmissionController, OperationKind,
+};
+use crate::connection::memory::{
+    collect_reserved, maybe_grow, maybe_reserve, MemoryConsumer, MemoryPool,
+};
 use crate::connection::NoData;
+use crate::data::precision::{VectorCaster, VectorPrecisionPolicy};
+use crate::data::sanitize::check_supported_types;
+use crate::data::sort::sort_by_column;
 use crate::error::{Error, Result};
-use crate::index::vector::{IvfPqIndexBuilder, VectorIndex, VectorIndexStatistics};
-use crate::index::IndexConfig;
+use crate::eval::EvaluateRecall;
+use crate::index::metadata::{IndexBuildTracker, IndexMetadata};
+use crate::index::scalar::{BTreeIndexBuilder, Normalization};
+use crate::index::vector::{
+    IndexDistanceType, IndexDistanceTypes, IvfPqIndexBuilder, VectorIndex, VectorIndexStatistics,
+};
 use crate::index::{
-    vector::{suggested_num_partitions, suggested_num_sub_vectors},
+    vector::{ivf_pq_training_bytes, suggested_num_partitions, suggested_num_sub_vectors},
     Index, IndexBuilder,
 };
+use crate::index::{IndexConfig, PendingIndices};
+use crate::io::metrics::{IoMetrics, IoMetricsWrapper, IoStats};
+use crate::io::shallow_clone::ShallowCloneWrapper;
+use crate::io::tiering::{Tiering, TieringReport};
+use crate::query::distinct::{
+    distinct_stream, drop_columns, nearest_first, select_distinct_columns, Deduplicator,
+    DISTINCT_OVERSAMPLE,
+};
+use crate::query::filter::{filter_columns, invalid_filter, normalize_filter, qualify_filter};
+use crate::query::filter_cache::{
+    evaluate_filter, CachedRows, FilterCache, FilterCacheConfig, FilterCacheMetrics, RowSearch,
+};
+use crate::query::late::{LateMaterialization, ROW_ID_COLUMN};
+use crate::query::order::{check_order_columns, order_bound, OrderedRows, SortOrder};
+use crate::query::pipeline::Pipeline;
+use crate::query::prepared::PreparedQuery;
+use crate::query::reference::ReferenceDistances;
+use crate::query::rescore::rescore_batches;
 use crate::query::{
-    IntoQueryVector, Query, QueryExecutionOptions, Select, VectorQuery, DEFAULT_TOP_K,
+    ExecutableQuery, IntoQueryVector, Query, QueryBase, QueryExecutionOptions, Select, VectorQuery,
+    DEFAULT_TOP_K,
+};
+use crate::runtime;
+use crate::telemetry::{record_optimize, record_version, record_write};
+use crate::utils::{default_vector_column, validate_table_name, PatchStoreParam};
+use crate::DistanceType;
+
+use self::audit::{read_history, AuditRecord};
+use self::batch_alter::BatchAlterBuilder;
+use self::blob::{blob_refs, lazy_blobs_version, mark_lazy_blobs, plan_lazy_blobs, BlobRef};
+use self::buffered::{BufferedWriter, BufferedWriterConfig};
+use self::bulk::{bulk_load_params, BulkLoadStats, BulkLoadTracker};
+use self::cardinality::{estimate_count, estimate_distinct};
+use self::commit_metadata::{read_commit_metadata, write_commit_metadata};
+use self::constraints::{
+    scan_violations, violation_report, write_result, Constraint, ConstraintChecker, Constraints,
+    ViolationSlot,
 };
-use crate::utils::{default_vector_column, PatchReadParam, PatchWriteParam};
-
 use self::dataset::DatasetConsistencyWrapper;
+use self::estimate::{
+    estimate_optimize, CostEstimate, IndexStatistics, TableStatistics, SAMPLE_ROWS,
+};
+use self::evolve::Evolve;
+use self::fragment_stats::{
+    prune_fragments, supports_statistics, FragmentPruning, FragmentStatistics,
+};
+use self::hooks::{CommitHook, CommitInfo};
+use self::ingest::{AddFilesBuilder, FileFormat};
+use self::interceptor::{and_filters, InterceptedOperation, QueryInterceptor, Restrictions};
+use self::masking::{MaskingPolicies, MaskingPolicy};
 use self::merge::MergeInsertBuilder;
-
+use self::merge_columns::{
+    validate_merge_keys, with_matched_column, MergeColumnsBuilder, MergeJoinType, MATCHED_COLUMN,
+};
+use self::normalized::{
+    normalized_column_name, with_normalized_columns, NormalizedColumn, NormalizedColumns,
+};
+use self::pins::{pin_while_streaming, VersionPin, VersionPins};
+use self::primary_key::{DuplicateKeys, KeyValue, UniqueKeys, KEY_LOOKUP_BATCH_SIZE};
+use self::prune::{preview_prune, PrunePreview};
+use self::reembed::{reembed_fragments, EmbeddingFunction, ReembedBuilder, ReembedReport};
+use self::repair::{find_orphaned_files, is_data_problem, RepairOptions, RepairReport};
+use self::sample::sample_dataset;
+use self::schema_diff::SchemaDiff;
+use self::shallow_clone::{ClonedFrom, Clones};
+use self::soft_delete::{with_deleted_column, DELETED_COLUMN, LIVE_FILTER};
+use self::spec::TableSpec;
+use self::temporal::TemporalValidity;
+use self::tuning::{SearchDefaults, SearchDefaultsMap, TUNED_NPROBES, TUNED_REFINE_FACTORS};
+use self::user_metadata::{set_user_metadata, user_metadata, with_table_settings};
+use self::verify::{verify_dataset, IntegrityReport};
+use self::write_stats::{CountingReader, WriteStats, WriteStatsTracker};
+
+pub mod audit;
+pub mod batch_alter;
+pub mod blob;
+pub mod buffered;
+pub mod bulk;
+pub mod cardinality;
+pub mod commit_metadata;
+pub mod constraints;
 pub(crate) mod dataset;
+pub mod estimate;
+pub mod evolve;
+pub mod fragment_stats;
+pub mod hooks;
+pub mod ingest;
+pub mod interceptor;
+pub mod masking;
 pub mod merge;
+pub mod merge_columns;
+mod normalized;
+pub(crate) mod pins;
+pub mod primary_key;
+pub mod prune;
+pub mod reembed;
+pub mod repair;
+pub mod sample;
+pub mod schema_diff;
+mod shallow_clone;
+pub mod soft_delete;
+pub mod spec;
+pub mod temporal;
+pub mod tuning;
+pub mod user_metadata;
+pub mod verify;
+pub mod view;
+pub mod write_stats;
 
 /// Optimize the dataset.
 ///
@@ -72,6 +191,11 @@ pub enum OptimizeAction {
     /// Run optimization on every, with default options.
     All,
     /// Compact files in the dataset
+    ///
+    /// Compaction rewrites fragments, so the `_rowid` of every row that is
+    /// moved changes.  Row ids should not be stored outside of the table.
+    /// Stable row ids need Lance 0.11 or newer and are not available in this
+    /// build, which uses Lance 0.10.
     Compact {
         options: CompactionOptions,
         remap_options: Option<Arc<dyn IndexRemapperOptions>>,
@@ -94,6 +218,16 @@ impl Default for OptimizeAction {
     }
 }
 
+impl OptimizeAction {
+    /// Estimate the cost of running this optimization on the table
+    ///
+    /// See [`estimate`] for how the estimate is made.
+    pub async fn estimate(&self, table: &Table) -> Result<CostEstimate> {
+        let stats = table.inner.statistics().await?;
+        Ok(estimate_optimize(&stats, self))
+    }
+}
+
 /// Statistics about the optimization.
 pub struct OptimizeStats {
     /// Stats of the file compaction.
@@ -113,6 +247,62 @@ pub struct WriteOptions {
     ///
     /// If set, these will take precedence over any overlapping `OpenTableBuilder` options
     pub lance_write_params: Option<WriteParams>,
+    /// Stage the data for [`Table::finish_bulk_load`] instead of committing it
+    ///
+    /// See [`Self::bulk_load`]
+    pub bulk_load: bool,
+    /// Key/value metadata recorded with the commit of the write
+    ///
+    /// See [`Self::commit_metadata`]
+    pub commit_metadata: BTreeMap<String, String>,
+    /// What an append does with columns that are not in the table
+    ///
+    /// See [`Self::schema_evolution`]
+    pub schema_evolution: Evolve,
+}
+
+impl WriteOptions {
+    /// Maximize the write throughput of an initial load of a large table
+    ///
+    /// Each add writes its data into a single large fragment and stages it
+    /// on the table handle instead of committing it.  Declared indices are
+    /// not built and existing indices are not updated.  Nothing is visible
+    /// to readers until [`Table::finish_bulk_load`] commits all of the
+    /// staged data as a single new version and updates the indices.  Staged
+    /// data is lost if the process exits first, see [`bulk`] for details.
+    ///
+    /// Only appends can be staged, and only by [`Table::add`].
+    pub fn bulk_load(mut self, bulk_load: bool) -> Self {
+        self.bulk_load = bulk_load;
+        self
+    }
+
+    /// Record key/value metadata, e.g. the author, a job id or the reason of
+    /// the change, with the commit of the write
+    ///
+    /// The metadata is returned with the new version by
+    /// [`Table::list_versions`], see [`commit_metadata`] for details.  It can
+    /// not be combined with [`Self::bulk_load`], the staged data is committed
+    /// later by [`Table::finish_bulk_load`].
+    pub fn commit_metadata(
+        mut self,
+        metadata: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.commit_metadata
+            .extend(metadata.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Add the columns of the data that are not in the table before appending
+    ///
+    /// With [`Evolve::AddNewColumns`] new nullable columns are added to the
+    /// table, with nulls for the existing rows, instead of failing the write.
+    /// The default is [`Evolve::Strict`].  See [`evolve`] for details.  It can
+    /// not be combined with [`Self::bulk_load`].
+    pub fn schema_evolution(mut self, evolve: Evolve) -> Self {
+        self.schema_evolution = evolve;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -121,6 +311,10 @@ pub enum AddDataMode {
     #[default]
     Append,
     /// The existing table will be overwritten with the new data
+    ///
+    /// The settings of the table, such as its masking policies, constraints
+    /// and primary key, are kept.  The new data must have every column of a
+    /// table with settings.
     Overwrite,
 }
 
@@ -131,6 +325,9 @@ pub struct AddDataBuilder<T: IntoArrow> {
     pub(crate) data: T,
     pub(crate) mode: AddDataMode,
     pub(crate) write_options: WriteOptions,
+    pub(crate) vector_precision: VectorPrecisionPolicy,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    pub(crate) sort_by: Option<String>,
 }
 
 impl<T: IntoArrow> std::fmt::Debug for AddDataBuilder<T> {
@@ -139,6 +336,9 @@ impl<T: IntoArrow> std::fmt::Debug for AddDataBuilder<T> {
             .field("parent", &self.parent)
             .field("mode", &self.mode)
             .field("write_options", &self.write_options)
+            .field("vector_precision", &self.vector_precision)
+            .field("duplicate_keys", &self.duplicate_keys)
+            .field("sort_by", &self.sort_by)
             .finish()
     }
 }
@@ -154,14 +354,51 @@ impl<T: IntoArrow> AddDataBuilder<T> {
         self
     }
 
+    /// What to do if a vector column has a different float type than the table
+    ///
+    /// By default the vectors are cast to the type of the table.  The precision
+    /// that is lost is reported by [`Table::write_stats`].  This only applies
+    /// when appending, an overwrite replaces the schema of the table.
+    pub fn vector_precision(mut self, policy: VectorPrecisionPolicy) -> Self {
+        self.vector_precision = policy;
+        self
+    }
+
+    /// What to do with rows whose primary key is already taken
+    ///
+    /// By default duplicate keys are allowed.  Any other policy requires a
+    /// primary key (see [`Table::set_primary_key`]) and reads all of the data
+    /// into memory before it is written.  When overwriting only the keys in
+    /// the new data are checked.
+    pub fn on_duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Sort the new data by a column (ascending, nulls last) before writing it
+    ///
+    /// Writing data in order of a column that is often filtered on, such as a
+    /// timestamp, lets scans skip the fragments that cannot match the filter.
+    /// Only the new data is sorted, and all of it is read into memory first.
+    pub fn sort_by(mut self, column: impl Into<String>) -> Self {
+        self.sort_by = Some(column.into());
+        self
+    }
+
     pub async fn execute(self) -> Result<()> {
         let parent = self.parent.clone();
-        let data = self.data.into_arrow()?;
+        let mut data = self.data.into_arrow()?;
+        if let Some(column) = &self.sort_by {
+            data = sort_by_column(data, column)?;
+        }
         let without_data = AddDataBuilder::<NoData> {
             data: NoData {},
             mode: self.mode,
             parent: self.parent,
             write_options: self.write_options,
+            vector_precision: self.vector_precision,
+            duplicate_keys: self.duplicate_keys,
+            sort_by: self.sort_by,
         };
         parent.add(without_data, data).await
     }
@@ -247,19 +484,23 @@ pub(crate) trait TableInternal: std::fmt::Display + std::fmt::Debug + Send + Syn
         &self,
         query: &Query,
         options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream>;
+    ) -> Result<SendableRecordBatchStream>;
     async fn vector_query(
         &self,
         query: &VectorQuery,
         options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream>;
+    ) -> Result<SendableRecordBatchStream>;
     async fn add(
         &self,
         add: AddDataBuilder<NoData>,
         data: Box<dyn arrow_array::RecordBatchReader + Send>,
     ) -> Result<()>;
     async fn delete(&self, predicate: &str) -> Result<()>;
+    async fn delete_by_values(&self, column: &str, values: &[KeyValue]) -> Result<usize>;
     async fn update(&self, update: UpdateBuilder) -> Result<()>;
+    async fn finish_bulk_load(&self) -> Result<BulkLoadStats>;
+    async fn discard_bulk_load(&self) -> Result<usize>;
+    async fn history(&self) -> Result<Vec<AuditRecord>>;
     async fn create_index(&self, index: IndexBuilder) -> Result<()>;
     async fn list_indices(&self) -> Result<Vec<IndexConfig>>;
     async fn merge_insert(
@@ -267,7 +508,17 @@ pub(crate) trait TableInternal: std::fmt::Display + std::fmt::Debug + Send + Syn
         params: MergeInsertBuilder,
         new_data: Box<dyn RecordBatchReader + Send>,
     ) -> Result<()>;
+    async fn merge_columns(
+        &self,
+        params: MergeColumnsBuilder,
+        new_data: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<()>;
     async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats>;
+    async fn preview_prune(
+        &self,
+        older_than: Duration,
+        delete_unverified: Option<bool>,
+    ) -> Result<PrunePreview>;
     async fn add_columns(
         &self,
         transforms: NewColumnTransform,
@@ -275,10 +526,58 @@ pub(crate) trait TableInternal: std::fmt::Display + std::fmt::Debug + Send + Syn
     ) -> Result<()>;
     async fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()>;
     async fn drop_columns(&self, columns: &[&str]) -> Result<()>;
+    async fn batch_alter(&self, alter: BatchAlterBuilder) -> Result<()>;
+    async fn masking_policies(&self) -> Result<HashMap<String, MaskingPolicy>>;
+    async fn set_masking_policy(&self, column: &str, policy: Option<MaskingPolicy>) -> Result<()>;
+    async fn temporal_validity(&self) -> Result<Option<TemporalValidity>>;
+    async fn set_temporal_validity(&self, validity: Option<TemporalValidity>) -> Result<()>;
+    async fn soft_delete(&self) -> Result<bool>;
+    async fn set_soft_delete(&self, enabled: bool) -> Result<()>;
+    async fn undelete(&self, predicate: &str) -> Result<()>;
+    async fn primary_key(&self) -> Result<Option<String>>;
+    async fn set_primary_key(&self, column: Option<&str>) -> Result<()>;
+    async fn constraints(&self) -> Result<HashMap<String, Vec<Constraint>>>;
+    async fn set_constraints(&self, column: &str, constraints: Vec<Constraint>) -> Result<()>;
     async fn version(&self) -> Result<u64>;
+    async fn list_versions(&self) -> Result<Vec<Version>>;
     async fn checkout(&self, version: u64) -> Result<()>;
     async fn checkout_latest(&self) -> Result<()>;
     async fn restore(&self) -> Result<()>;
+    async fn snapshot(&self) -> Result<Arc<dyn TableInternal>>;
+    async fn copy_to(&self, name: &str) -> Result<Arc<dyn TableInternal>>;
+    fn write_stats(&self) -> Result<WriteStats>;
+    fn io_stats(&self) -> Result<IoStats>;
+    async fn tiering_report(&self) -> Result<TieringReport>;
+    async fn ids_of_rows(&self, row_ids: &[u64]) -> Result<Vec<KeyValue>>;
+    /// The version a scan reads, the current one if not given, and the ids
+    /// of its fragments in ascending order
+    async fn scan_fragments(&self, version: Option<u64>) -> Result<(u64, Vec<u64>)>;
+    fn filter_cache_metrics(&self) -> Result<Option<FilterCacheMetrics>>;
+    async fn statistics(&self) -> Result<TableStatistics>;
+    async fn verify(&self) -> Result<IntegrityReport>;
+    async fn repair(&self, options: RepairOptions) -> Result<RepairReport>;
+    async fn reembed(&self, reembed: ReembedBuilder) -> Result<ReembedReport>;
+    async fn index_metadata(&self, column: &str) -> Result<IndexMetadata>;
+    async fn compute_statistics(&self, columns: &[&str]) -> Result<()>;
+    async fn fragment_pruning(&self, query: &Query) -> Result<FragmentPruning>;
+    async fn sample(&self, n: usize, seed: Option<u64>) -> Result<RecordBatch>;
+    async fn estimate_count(&self, filter: Option<String>) -> Result<usize>;
+    async fn estimate_distinct(&self, column: &str) -> Result<usize>;
+    async fn search_defaults(&self) -> Result<HashMap<String, SearchDefaults>>;
+    async fn set_search_defaults(
+        &self,
+        column: &str,
+        defaults: Option<SearchDefaults>,
+    ) -> Result<()>;
+    /// The user metadata of the table, or of `column` if set
+    async fn metadata(&self, column: Option<&str>) -> Result<HashMap<String, String>>;
+    async fn set_metadata(
+        &self,
+        column: Option<&str>,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<()>;
+    async fn blob_refs(&self, batch: &RecordBatch, column: &str) -> Result<Vec<BlobRef>>;
 }
 
 /// A Table is a collection of strong typed Rows.
@@ -286,7 +585,7 @@ pub(crate) trait TableInternal: std::fmt::Display + std::fmt::Debug + Send + Syn
 /// The type of the each row is defined in Apache Arrow [Schema].
 #[derive(Clone)]
 pub struct Table {
-    inner: Arc<dyn TableInternal>,
+    pub(crate) inner: Arc<dyn TableInternal>,
 }
 
 impl std::fmt::Display for Table {
@@ -327,6 +626,30 @@ impl Table {
         self.inner.count_rows(filter).await
     }
 
+    /// Estimate the number of rows in this dataset, without reading all of it
+    ///
+    /// This reads a sample of the fragments that may match the filter, which
+    /// is much faster than [`Self::count_rows`] on large tables.  Fragments
+    /// are skipped with the statistics of [`Self::compute_statistics`].  The
+    /// count is exact if there is no filter.  See [`cardinality`] for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` if present, only count rows matching the filter
+    pub async fn estimate_count(&self, filter: Option<String>) -> Result<usize> {
+        self.inner.estimate_count(filter).await
+    }
+
+    /// Estimate the number of distinct non-null values in a column
+    ///
+    /// The estimate is usually within a few percent of the exact count.  It
+    /// is fast if [`Self::compute_statistics`] was called for the column,
+    /// otherwise the column (but no other column) is read completely.  See
+    /// [`cardinality`] for details.
+    pub async fn estimate_distinct(&self, column: &str) -> Result<usize> {
+        self.inner.estimate_distinct(column).await
+    }
+
     /// Insert new records into this Table
     ///
     /// # Arguments
@@ -339,9 +662,79 @@ impl Table {
             data: batches,
             mode: AddDataMode::Append,
             write_options: WriteOptions::default(),
+            vector_precision: VectorPrecisionPolicy::default(),
+            duplicate_keys: DuplicateKeys::default(),
+            sort_by: None,
         }
     }
 
+    /// Commit the data staged by adds with [`WriteOptions::bulk_load`]
+    ///
+    /// All of the staged data is committed as a single new version.  Then the
+    /// declared indices are built and the new rows are added to the existing
+    /// indices.  If the commit fails the data stays staged and this can be
+    /// called again.
+    ///
+    /// Data is staged on a table handle and its clones, this must be called
+    /// on the handle that staged it.
+    pub async fn finish_bulk_load(&self) -> Result<BulkLoadStats> {
+        self.inner.finish_bulk_load().await
+    }
+
+    /// The operations recorded in the audit log of the table, oldest first
+    ///
+    /// Operations are only recorded by table handles with the
+    /// [`audit::AuditLog`] commit hook, see [`audit`] for details.  Returns an
+    /// empty list if nothing was recorded.
+    pub async fn history(&self) -> Result<Vec<AuditRecord>> {
+        self.inner.history().await
+    }
+
+    /// Drop the data staged by adds with [`WriteOptions::bulk_load`]
+    ///
+    /// The data files that were written are deleted.  If a file can not be
+    /// deleted the error is returned, the files that are left are not
+    /// referenced by any version and [`Self::repair`] removes them.  Returns
+    /// the number of rows dropped.
+    pub async fn discard_bulk_load(&self) -> Result<usize> {
+        self.inner.discard_bulk_load().await
+    }
+
+    /// Add the contents of many Parquet, CSV or JSON lines files
+    ///
+    /// The files are read concurrently, see [`ingest`] for how many are held
+    /// in memory, and staged with a bulk load (see
+    /// [`WriteOptions::bulk_load`]).  By default all of the files are
+    /// committed as a single new version at the end, use
+    /// [`AddFilesBuilder::files_per_commit`] to commit in batches.  Fails if
+    /// data is already staged through this handle by a bulk load.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lancedb::Table;
+    /// # use lancedb::table::ingest::FileFormat;
+    /// # async fn doctest_helper(tbl: Table) {
+    /// let stats = tbl
+    ///     .add_files(["s3://bucket/part-0.parquet", "s3://bucket/part-1.parquet"], FileFormat::Parquet)
+    ///     .parallelism(16)
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn add_files(
+        &self,
+        paths: impl IntoIterator<Item = impl Into<String>>,
+        format: FileFormat,
+    ) -> AddFilesBuilder {
+        AddFilesBuilder::new(
+            self.clone(),
+            paths.into_iter().map(Into::into).collect(),
+            format,
+        )
+    }
+
     /// Update existing records in the Table
     ///
     /// An update operation can be used to adjust existing values.  Use the
@@ -362,6 +755,9 @@ impl Table {
 
     /// Delete the rows from table that match the predicate.
     ///
+    /// If soft deletes are enabled (see [`Self::set_soft_delete`]) the rows
+    /// are only marked as deleted and can be restored with [`Self::undelete`].
+    ///
     /// # Arguments
     /// - `predicate` - The SQL predicate string to filter the rows to be deleted.
     ///
@@ -413,6 +809,33 @@ impl Table {
         self.inner.delete(predicate).await
     }
 
+    /// Delete the rows whose value in `column` is any of the given values
+    ///
+    /// This is meant for long lists of values, which would make a predicate
+    /// for [`Self::delete`] too large to parse.  The rows are found with
+    /// lookups of [`primary_key::KEY_LOOKUP_BATCH_SIZE`] values, which use a
+    /// btree index on the column if there is one, and only the fragments that
+    /// hold them are rewritten.  The column must be an integer or string column.
+    ///
+    /// All the rows are deleted in a single commit, unless the table uses soft
+    /// deletes (see [`Self::set_soft_delete`]) in which case each batch of
+    /// values is committed separately.  Returns the number of rows deleted.
+    ///
+    /// ```no_run
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("events").execute().await.unwrap();
+    /// let ids = (0..100_000).map(Into::into).collect::<Vec<_>>();
+    /// let deleted = tbl.delete_by_values("id", &ids).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn delete_by_values(&self, column: &str, values: &[KeyValue]) -> Result<usize> {
+        if values.is_empty() {
+            return Ok(0);
+        }
+        self.inner.delete_by_values(column, values).await
+    }
+
     /// Create an index on the provided column(s).
     ///
     /// Indices are used to speed up searches and are often needed when the size of the table
@@ -438,8 +861,12 @@ impl Table {
     /// index without rerunning the full index creation process.  For more details see
     /// [Table::optimize].
     ///
-    /// Note: Multi-column (composite) indices are not currently supported.  However, they will
-    /// be supported in the future and the API is designed to be compatible with them.
+    /// Passing several columns with [`Index::BTree`] creates one btree index per column,
+    /// which accelerates filters that combine the columns (e.g. `tenant_id = 7 AND
+    /// created_at > 100`).  This is not an index on the composite key: the indices are
+    /// listed separately by [`Table::list_indices`] and can be dropped or replaced
+    /// separately.  Either all of the indices are created or none is.  Other index types
+    /// only support a single column.
     ///
     /// # Examples
     ///
@@ -555,6 +982,85 @@ impl Table {
         )
     }
 
+    /// Insert the rows of `data`, replacing the existing rows with the same key
+    ///
+    /// This is a shortcut for a [`Self::merge_insert`] on the `on` columns
+    /// that updates the matched rows and inserts the others.  The merge insert
+    /// of this Lance version does not report how many rows it inserted or
+    /// updated, so no counts are returned.
+    ///
+    /// ```no_run
+    /// # use arrow_array::RecordBatchReader;
+    /// # async fn doctest_helper(tbl: lancedb::Table, data: Box<dyn RecordBatchReader + Send>) {
+    /// tbl.upsert(&["id"], data).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn upsert(&self, on: &[&str], data: impl IntoArrow) -> Result<()> {
+        let mut merge_insert = self.merge_insert(on);
+        merge_insert
+            .when_matched_update_all(None)
+            .when_not_matched_insert_all();
+        merge_insert.execute(data.into_arrow()?).await
+    }
+
+    /// Add new columns to the table by joining it with new data
+    ///
+    /// Each row of the table is matched with the row of the new data where
+    /// the value of `right_on` equals the value of `left_on`.  All columns of
+    /// the new data except `right_on` are added to the table.  Unlike
+    /// [`Self::merge_insert`] no rows are inserted or updated.
+    ///
+    /// By default rows without a match get nulls in the new columns, see
+    /// [`MergeColumnsBuilder::join_type`] to remove them instead.  Keys in the
+    /// new data that do not match any row are ignored unless
+    /// [`MergeColumnsBuilder::validate_keys`] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `left_on` - The key column of the table
+    /// * `right_on` - The key column of the new data
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    /// # use arrow_schema::{DataType, Field, Schema};
+    /// # use lancedb::table::merge_columns::MergeJoinType;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let db = lancedb::connect("data/sample-lancedb").execute().await.unwrap();
+    /// let tbl = db.open_table("my_table").execute().await.unwrap();
+    /// let schema = Arc::new(Schema::new(vec![
+    ///     Field::new("id", DataType::Int32, false),
+    ///     Field::new("label", DataType::Utf8, false),
+    /// ]));
+    /// let new_data = RecordBatchIterator::new(
+    ///     vec![RecordBatch::try_new(
+    ///         schema.clone(),
+    ///         vec![
+    ///             Arc::new(Int32Array::from(vec![1, 2])),
+    ///             Arc::new(StringArray::from(vec!["cat", "dog"])),
+    ///         ],
+    ///     )
+    ///     .unwrap()]
+    ///     .into_iter()
+    ///     .map(Ok),
+    ///     schema.clone(),
+    /// );
+    /// // Add a "label" column, keeping only the rows that have a label
+    /// let mut merge = tbl.merge("id", "id");
+    /// merge.join_type(MergeJoinType::Inner).validate_keys();
+    /// merge.execute(Box::new(new_data)).await.unwrap();
+    /// # });
+    /// ```
+    pub fn merge(&self, left_on: &str, right_on: &str) -> MergeColumnsBuilder {
+        MergeColumnsBuilder::new(
+            self.inner.clone(),
+            left_on.to_string(),
+            right_on.to_string(),
+        )
+    }
+
     /// Create a [`Query`] Builder.
     ///
     /// Queries allow you to search your existing data.  By default the query will
@@ -563,8 +1069,8 @@ impl Table {
     /// vector similarity, sorting, and more.
     ///
     /// Note: By default, all columns are returned.  For best performance, you should
-    /// only fetch the columns you need.  See [`Query::select_with_projection`] for
-    /// more details.
+    /// only fetch the columns you need.  See [`QueryBase::select`] and
+    /// [`QueryBase::select_all_except`] for more details.
     ///
     /// When appropriate, various indices and statistics will be used to accelerate
     /// the query.
@@ -655,16 +1161,99 @@ impl Table {
         self.query().nearest_to(query)
     }
 
+    /// Create a multi-stage search [`Pipeline`]
+    ///
+    /// A pipeline declares a prefilter, an ANN search, a reranker, a post-filter,
+    /// a limit and a payload join.  The stages are placed to read as little data
+    /// as possible and statistics are reported for each stage.  See
+    /// [`crate::query::pipeline`] for more details.
+    ///
+    /// ```no_run
+    /// # use lancedb::query::pipeline::Rerank;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("docs").execute().await.unwrap();
+    /// let results = tbl
+    ///     .pipeline()
+    ///     .prefilter("lang = 'en'")
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .candidates(100)
+    ///     .rerank(Rerank::Exact)
+    ///     .limit(10)
+    ///     .join_payload("id", &["text"])
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// for stage in &results.stats {
+    ///     println!("{}: {} rows in {:?}", stage.stage, stage.rows_out, stage.elapsed);
+    /// }
+    /// # });
+    /// ```
+    pub fn pipeline(&self) -> Pipeline {
+        Pipeline::new(self.inner.clone())
+    }
+
+    /// Prepare a vector query so that it can be executed many times
+    ///
+    /// The vector column and the search defaults are resolved, and the filter is
+    /// validated against the table schema, once.  Each execution only needs to
+    /// supply a new query vector and, optionally, a new filter (see
+    /// [`PreparedQuery::bind`]).  This cuts the per-query overhead for
+    /// applications that issue the same kind of search at a high rate.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use arrow_array::RecordBatch;
+    /// # use futures::TryStreamExt;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("tbl").execute().await.unwrap();
+    /// use crate::lancedb::query::{ExecutableQuery, QueryBase};
+    /// let template = tbl.query().limit(5).nearest_to(&[0.0; 3]).unwrap();
+    /// let prepared = tbl.prepare(template).await.unwrap();
+    /// let stream = prepared
+    ///     .bind(&[1.0, 2.0, 3.0], Some("id > 5"))
+    ///     .unwrap()
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// let batches: Vec<RecordBatch> = stream.try_collect().await.unwrap();
+    /// # });
+    /// ```
+    pub async fn prepare(&self, template: VectorQuery) -> Result<PreparedQuery> {
+        PreparedQuery::try_new(template).await
+    }
+
     /// Optimize the on-disk data and indices for better performance.
     ///
     /// <section class="warning">Experimental API</section>
     ///
     /// Modeled after ``VACUUM`` in PostgreSQL.
     /// Not all implementations support explicit optimization.
+    ///
+    /// It is safe to optimize while queries are running.  Unless disabled with
+    /// [`crate::connection::ConnectBuilder::pin_query_versions`], the versions
+    /// read by live query streams in this process are not pruned.
     pub async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats> {
         self.inner.optimize(action).await
     }
 
+    /// List what [`OptimizeAction::Prune`] with the same options would
+    /// remove, without removing anything
+    ///
+    /// See [`prune`] for how the preview is computed.
+    pub async fn preview_prune(
+        &self,
+        older_than: Duration,
+        delete_unverified: Option<bool>,
+    ) -> Result<PrunePreview> {
+        self.inner
+            .preview_prune(older_than, delete_unverified)
+            .await
+    }
+
     /// Add new columns to the table, providing values to fill in.
     pub async fn add_columns(
         &self,
@@ -684,1680 +1273,8598 @@ impl Table {
         self.inner.drop_columns(columns).await
     }
 
-    /// Retrieve the version of the table
+    /// Add, alter and drop columns in a single atomic change
     ///
-    /// LanceDb supports versioning.  Every operation that modifies the table increases
-    /// version.  As long as a version hasn't been deleted you can `[Self::checkout]` that
-    /// version to view the data at that point.  In addition, you can `[Self::restore]` the
-    /// version to replace the current table with a previous version.
-    pub async fn version(&self) -> Result<u64> {
-        self.inner.version().await
+    /// Calling [`Self::add_columns`], [`Self::alter_columns`] and
+    /// [`Self::drop_columns`] one after the other creates a new version for each
+    /// call and readers may see the intermediate schemas.  The changes in a
+    /// batch alter are committed as one new version instead.  New columns are
+    /// added first, then the alterations are applied and finally columns are
+    /// dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lancedb::Table;
+    /// # use lancedb::table::{ColumnAlteration, NewColumnTransform};
+    /// # async fn doctest_helper(tbl: Table) {
+    /// tbl.batch_alter()
+    ///     .add_columns(
+    ///         NewColumnTransform::SqlExpressions(vec![("price_usd".into(), "price / 100".into())]),
+    ///         None,
+    ///     )
+    ///     .alter_columns([ColumnAlteration::new("name".into()).rename("title".into())])
+    ///     .drop_columns(["price"])
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn batch_alter(&self) -> BatchAlterBuilder {
+        BatchAlterBuilder::new(self.inner.clone())
     }
 
-    /// Checks out a specific version of the Table
+    /// Get the masking policies of the table, by column name
     ///
-    /// Any read operation on the table will now access the data at the checked out version.
-    /// As a consequence, calling this method will disable any read consistency interval
-    /// that was previously set.
+    /// See [`masking`] for more details.
+    pub async fn masking_policies(&self) -> Result<HashMap<String, MaskingPolicy>> {
+        self.inner.masking_policies().await
+    }
+
+    /// Set (or, if `policy` is None, remove) the masking policy of a column
     ///
-    /// This is a read-only operation that turns the table into a sort of "view"
-    /// or "detached head".  Other table instances will not be affected.  To make the change
-    /// permanent you can use the `[Self::restore]` method.
+    /// The policy is stored in the table properties and is enforced on every
+    /// handle that opens the table without unmasked access (see
+    /// [`crate::connection::OpenTableBuilder::unmasked`]).  Policies can only
+    /// be changed through a handle with unmasked access, and only top level
+    /// columns can be masked.
+    pub async fn set_masking_policy(
+        &self,
+        column: &str,
+        policy: Option<MaskingPolicy>,
+    ) -> Result<()> {
+        self.inner.set_masking_policy(column, policy).await
+    }
+
+    /// Get the temporal validity columns of the table, if they were declared
     ///
-    /// Any operation that modifies the table will fail while the table is in a checked
-    /// out state.
+    /// See [`temporal`] for more details.
+    pub async fn temporal_validity(&self) -> Result<Option<TemporalValidity>> {
+        self.inner.temporal_validity().await
+    }
+
+    /// Declare (or, if `validity` is None, remove) the temporal validity columns
     ///
-    /// To return the table to a normal state use `[Self::checkout_latest]`
-    pub async fn checkout(&self, version: u64) -> Result<()> {
-        self.inner.checkout(version).await
+    /// The columns are stored in the table properties and are used by queries
+    /// with [`crate::query::QueryBase::as_of`].  Both columns must be timestamps.
+    ///
+    /// ```no_run
+    /// # use lancedb::query::{ExecutableQuery, QueryBase};
+    /// # use lancedb::table::temporal::TemporalValidity;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("products").execute().await.unwrap();
+    /// tbl.set_temporal_validity(Some(TemporalValidity::new("valid_from", "valid_to")))
+    ///     .await
+    ///     .unwrap();
+    /// let as_of = chrono::Utc::now() - chrono::Duration::days(30);
+    /// let results = tbl
+    ///     .query()
+    ///     .as_of(as_of)
+    ///     .nearest_to(&[1.0, 2.0, 3.0])
+    ///     .unwrap()
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub async fn set_temporal_validity(&self, validity: Option<TemporalValidity>) -> Result<()> {
+        self.inner.set_temporal_validity(validity).await
     }
 
-    /// Ensures the table is pointing at the latest version
+    /// Whether [`Self::delete`] marks rows as deleted instead of removing them
     ///
-    /// This can be used to manually update a table when the read_consistency_interval is None
-    /// It can also be used to undo a `[Self::checkout]` operation
-    pub async fn checkout_latest(&self) -> Result<()> {
-        self.inner.checkout_latest().await
+    /// See [`soft_delete`] for more details.
+    pub async fn soft_delete(&self) -> Result<bool> {
+        self.inner.soft_delete().await
     }
 
-    /// Restore the table to the currently checked out version
+    /// Enable or disable soft deletes
     ///
-    /// This operation will fail if checkout has not been called previously
+    /// Enabling soft deletes adds the hidden [`DELETED_COLUMN`] to the table,
+    /// if it does not have it yet.  From then on [`Self::delete`] only marks
+    /// rows as deleted, and [`Self::undelete`] restores them.
     ///
-    /// This operation will overwrite the latest version of the table with a
-    /// previous version.  Any changes made since the checked out version will
-    /// no longer be visible.
+    /// Disabling soft deletes removes the rows that are marked as deleted for
+    /// good and drops the column.
+    pub async fn set_soft_delete(&self, enabled: bool) -> Result<()> {
+        self.inner.set_soft_delete(enabled).await
+    }
+
+    /// Restore the soft deleted rows that match the predicate
     ///
-    /// Once the operation concludes the table will no longer be in a checked
-    /// out state and the read_consistency_interval, if any, will apply.
-    pub async fn restore(&self) -> Result<()> {
-        self.inner.restore().await
+    /// Soft deletes must be enabled, see [`Self::set_soft_delete`].  The
+    /// predicate uses the same syntax as [`Self::delete`].
+    pub async fn undelete(&self, predicate: &str) -> Result<()> {
+        self.inner.undelete(predicate).await
     }
 
-    /// List all indices that have been created with [`Self::create_index`]
-    pub async fn list_indices(&self) -> Result<Vec<IndexConfig>> {
-        self.inner.list_indices().await
+    /// Get the primary key column of the table, if one was declared
+    ///
+    /// See [`primary_key`] for more details.
+    pub async fn primary_key(&self) -> Result<Option<String>> {
+        self.inner.primary_key().await
     }
-}
 
-impl From<NativeTable> for Table {
-    fn from(table: NativeTable) -> Self {
-        Self {
-            inner: Arc::new(table),
-        }
+    /// Declare (or, if `column` is None, remove) the primary key column
+    ///
+    /// The column must be a top level integer or string column.  A btree index
+    /// is created on the column if it does not have one yet.  The index is not
+    /// dropped when the primary key is removed.
+    pub async fn set_primary_key(&self, column: Option<&str>) -> Result<()> {
+        self.inner.set_primary_key(column).await
     }
-}
-/// A table in a LanceDB database.
-#[derive(Debug, Clone)]
-pub struct NativeTable {
-    name: String,
-    uri: String,
-    pub(crate) dataset: dataset::DatasetConsistencyWrapper,
 
-    // the object store wrapper to use on write path
-    store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
+    /// Compare a schema with the schema of the table
+    ///
+    /// Returns every difference: the columns of the table that are missing
+    /// from the schema, the columns that are not in the table, and the
+    /// columns with a different type or nullability.  Data with the schema
+    /// can be added if [`SchemaDiff::is_compatible`] is true.  See
+    /// [`schema_diff`] for how the schemas are compared.
+    pub async fn check_schema_compatible(&self, schema: &Schema) -> Result<SchemaDiff> {
+        let table_schema = self.schema().await?;
+        let mut diff = SchemaDiff::between(schema, &table_schema);
+        // The soft delete column is added by the write
+        if self.soft_delete().await? {
+            diff.missing_columns
+                .retain(|column| column != DELETED_COLUMN);
+        }
+        // As are the normalized columns of indices
+        let normalized = NormalizedColumns::from_metadata(table_schema.metadata())?;
+        diff.missing_columns
+            .retain(|column| !normalized.is_hidden(column));
+        Ok(diff)
+    }
 
-    // This comes from the connection options. We store here so we can pass down
-    // to the dataset when we recreate it (for example, in checkout_latest).
-    read_consistency_interval: Option<std::time::Duration>,
-}
+    /// Get the constraints of the table, by column name
+    ///
+    /// See [`constraints`] for more details.
+    pub async fn constraints(&self) -> Result<HashMap<String, Vec<Constraint>>> {
+        self.inner.constraints().await
+    }
 
-impl std::fmt::Display for NativeTable {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "NativeTable({}, uri={}, read_consistency_interval={})",
-            self.name,
-            self.uri,
-            match self.read_consistency_interval {
-                None => {
-                    "None".to_string()
-                }
-                Some(duration) => {
-                    format!("{}s", duration.as_secs_f64())
-                }
-            }
-        )
+    /// Set the constraints of a column, replacing its previous constraints
+    ///
+    /// An empty list removes the constraints of the column.  The constraints
+    /// are stored in the table properties and are checked by `add`,
+    /// `merge_insert` and `update`.  The existing rows of the table must
+    /// already satisfy them.
+    ///
+    /// ```no_run
+    /// # use lancedb::table::constraints::Constraint;
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("users").execute().await.unwrap();
+    /// tbl.set_constraints(
+    ///     "age",
+    ///     vec![
+    ///         Constraint::NotNull,
+    ///         Constraint::Range { min: Some(0.0), max: Some(150.0) },
+    ///     ],
+    /// )
+    /// .await
+    /// .unwrap();
+    /// # });
+    /// ```
+    pub async fn set_constraints(&self, column: &str, constraints: Vec<Constraint>) -> Result<()> {
+        self.inner.set_constraints(column, constraints).await
     }
-}
 
-impl NativeTable {
-    /// Opens an existing Table
+    /// Get the row with the given primary key, None if there is no such row
     ///
-    /// # Arguments
+    /// This is a shortcut for a query that filters on the primary key (see
+    /// [`Self::set_primary_key`]) and is answered with the btree index.
     ///
-    /// * `uri` - The uri to a [NativeTable]
-    /// * `name` - The table name
+    /// ```no_run
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("products").execute().await.unwrap();
+    /// tbl.set_primary_key(Some("id")).await.unwrap();
+    /// let row = tbl.get(42).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn get(&self, key: impl Into<KeyValue>) -> Result<Option<RecordBatch>> {
+        let batch = self.get_many(&[key.into()]).await?;
+        Ok((batch.num_rows() > 0).then(|| batch.slice(0, 1)))
+    }
+
+    /// Get the rows with any of the given primary keys
     ///
-    /// # Returns
+    /// The rows are returned in no particular order and keys without a row
+    /// are skipped.  Primary keys are not checked for uniqueness, so a key
+    /// with several rows returns all of them.  See [`Self::get`].
+    pub async fn get_many(&self, keys: &[KeyValue]) -> Result<RecordBatch> {
+        let column = self.required_primary_key().await?;
+        let schema = self.schema().await?;
+        if keys.is_empty() {
+            return Ok(RecordBatch::new_empty(schema));
+        }
+        let filter = primary_key::lookup_filter(&schema, &column, keys)?;
+        let stream = self.query().only_if(filter).execute().await?;
+        let schema = stream.schema();
+        let batches = stream.try_collect::<Vec<_>>().await?;
+        Ok(arrow::compute::concat_batches(&schema, &batches)?)
+    }
+
+    /// Get the rows with the given external ids, in the order of the ids
     ///
-    /// * A [NativeTable] object.
-    pub async fn open(uri: &str) -> Result<Self> {
-        let name = Self::get_table_name(uri)?;
-        Self::open_with_params(uri, &name, None, None, None).await
+    /// The external ids are the values of the primary key column (see
+    /// [`Self::set_primary_key`]).  Ids without a row are skipped, and an id
+    /// that is given more than once is returned once.  The ids are looked up
+    /// with the btree index in batches of [`primary_key::KEY_LOOKUP_BATCH_SIZE`].
+    pub async fn get_by_ids(&self, ids: &[KeyValue]) -> Result<RecordBatch> {
+        let column = self.required_primary_key().await?;
+        let mut batches = Vec::new();
+        for ids in ids.chunks(KEY_LOOKUP_BATCH_SIZE) {
+            batches.push(self.get_many(ids).await?);
+        }
+        let batch = match batches.first() {
+            Some(first) => arrow::compute::concat_batches(&first.schema(), &batches)?,
+            None => RecordBatch::new_empty(self.schema().await?),
+        };
+        primary_key::take_in_key_order(&batch, &column, ids)
     }
 
-    /// Opens an existing Table
+    /// Update the rows with the given external ids
     ///
-    /// # Arguments
+    /// Returns an [`UpdateBuilder`] that only updates those rows, the columns
+    /// to update are set on the builder.  See [`Self::get_by_ids`] for what
+    /// the ids are.
     ///
-    /// * `base_path` - The base path where the table is located
-    /// * `name` The Table name
-    /// * `params` The [ReadParams] to use when opening the table
+    /// ```no_run
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// # let conn = lancedb::connect("/tmp").execute().await.unwrap();
+    /// # let tbl = conn.open_table("products").execute().await.unwrap();
+    /// tbl.update_by_ids(&["sku-1".into(), "sku-7".into()])
+    ///     .await
+    ///     .unwrap()
+    ///     .column("in_stock", "false")
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub async fn update_by_ids(&self, ids: &[KeyValue]) -> Result<UpdateBuilder> {
+        Ok(self.update().only_if(self.ids_filter(ids).await?))
+    }
+
+    /// Delete the rows with the given external ids
     ///
-    /// # Returns
+    /// Returns the number of rows that were deleted.  See
+    /// [`Self::delete_by_values`] for how the rows are deleted and
+    /// [`Self::get_by_ids`] for what the ids are.
+    pub async fn delete_by_ids(&self, ids: &[KeyValue]) -> Result<usize> {
+        let column = self.required_primary_key().await?;
+        self.delete_by_values(&column, ids).await
+    }
+
+    /// Map external ids to the addresses of their rows
     ///
-    /// * A [NativeTable] object.
-    pub async fn open_with_params(
-        uri: &str,
-        name: &str,
-        write_store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
-        params: Option<ReadParams>,
-        read_consistency_interval: Option<std::time::Duration>,
-    ) -> Result<Self> {
-        let params = params.unwrap_or_default();
-        // patch the params if we have a write store wrapper
-        let params = match write_store_wrapper.clone() {
-            Some(wrapper) => params.patch_with_store_wrapper(wrapper)?,
-            None => params,
-        };
+    /// Ids without a row are left out of the map.  If an id has more than
+    /// one row the map holds one of them.  Row addresses change when the
+    /// table is compacted, they should not be stored.  See
+    /// [`Self::ids_of_rows`] for the opposite direction.
+    pub async fn row_ids_of(&self, ids: &[KeyValue]) -> Result<HashMap<KeyValue, u64>> {
+        let column = self.required_primary_key().await?;
+        let schema = self.schema().await?;
+        let mut row_ids = HashMap::with_capacity(ids.len());
+        for ids in ids.chunks(KEY_LOOKUP_BATCH_SIZE) {
+            let mut query = self
+                .query()
+                .only_if(primary_key::lookup_filter(&schema, &column, ids)?)
+                .select(Select::columns(&[column.as_str()]));
+            query.with_row_id = true;
+            let batches = query.execute().await?.try_collect::<Vec<_>>().await?;
+            for batch in batches {
+                let keys = primary_key::key_values(&batch[column.as_str()], &column)?;
+                let addresses = batch[ROW_ID_COLUMN].as_primitive::<UInt64Type>();
+                row_ids.extend(keys.into_iter().zip(addresses.values().iter().copied()));
+            }
+        }
+        Ok(row_ids)
+    }
 
-        let dataset = DatasetBuilder::from_uri(uri)
-            .with_read_params(params)
-            .load()
-            .await
-            .map_err(|e| match e {
-                lance::Error::DatasetNotFound { .. } => Error::TableNotFound {
-                    name: name.to_string(),
-                },
-                source => Error::Lance { source },
-            })?;
+    /// Map row addresses, for example from a query, to the external ids of the rows
+    ///
+    /// Returns the id of each row, in the order of the addresses.  See
+    /// [`Self::row_ids_of`].
+    pub async fn ids_of_rows(&self, row_ids: &[u64]) -> Result<Vec<KeyValue>> {
+        self.inner.ids_of_rows(row_ids).await
+    }
 
-        let dataset = DatasetConsistencyWrapper::new_latest(dataset, read_consistency_interval);
+    async fn required_primary_key(&self) -> Result<String> {
+        self.primary_key()
+            .await?
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!(
+                    "the table '{}' has no primary key, see Table::set_primary_key",
+                    self.name()
+                ),
+            })
+    }
 
-        Ok(Self {
-            name: name.to_string(),
-            uri: uri.to_string(),
-            dataset,
-            store_wrapper: write_store_wrapper,
-            read_consistency_interval,
-        })
+    /// The filter selecting the rows with any of the external ids
+    async fn ids_filter(&self, ids: &[KeyValue]) -> Result<String> {
+        let column = self.required_primary_key().await?;
+        if ids.is_empty() {
+            return Ok("false".to_string());
+        }
+        let schema = self.schema().await?;
+        primary_key::lookup_filter(&schema, &column, ids)
     }
 
-    fn get_table_name(uri: &str) -> Result<String> {
-        let path = Path::new(uri);
-        let name = path
-            .file_stem()
-            .ok_or(Error::TableNotFound {
-                name: uri.to_string(),
-            })?
-            .to_str()
-            .ok_or(Error::InvalidTableName {
-                name: uri.to_string(),
-                reason: "Table name is not valid URL".to_string(),
-            })?;
-        Ok(name.to_string())
+    /// Create a reference to the value of a lazy blob column for every row of the batch
+    ///
+    /// The batch must come from a query with [`crate::query::QueryBase::lazy_blobs`]
+    /// that included the column.  The references read the version of the table
+    /// that the query read.  See [`blob`] for more details.
+    pub async fn blob_refs(&self, batch: &RecordBatch, column: &str) -> Result<Vec<BlobRef>> {
+        self.inner.blob_refs(batch, column).await
     }
 
-    fn supported_btree_data_type(dtype: &DataType) -> bool {
-        dtype.is_integer()
-            || dtype.is_floating()
-            || matches!(
-                dtype,
-                DataType::Boolean
-                    | DataType::Utf8
-                    | DataType::Time32(_)
-                    | DataType::Time64(_)
-                    | DataType::Date32
-                    | DataType::Date64
-                    | DataType::Timestamp(_, _)
-            )
+    /// Retrieve the version of the table
+    ///
+    /// LanceDb supports versioning.  Every operation that modifies the table increases
+    /// version.  As long as a version hasn't been deleted you can `[Self::checkout]` that
+    /// version to view the data at that point.  In addition, you can `[Self::restore]` the
+    /// version to replace the current table with a previous version.
+    pub async fn version(&self) -> Result<u64> {
+        self.inner.version().await
     }
 
-    fn supported_vector_data_type(dtype: &DataType) -> bool {
-        match dtype {
-            DataType::FixedSizeList(inner, _) => DataType::is_floating(inner.data_type()),
-            _ => false,
-        }
+    /// List the versions of the table, oldest first
+    ///
+    /// The metadata of each version includes the metadata recorded with
+    /// [`WriteOptions::commit_metadata`] by the write that created it.
+    pub async fn list_versions(&self) -> Result<Vec<Version>> {
+        self.inner.list_versions().await
     }
 
-    /// Creates a new Table
+    /// Checks out a specific version of the Table
     ///
-    /// # Arguments
+    /// Any read operation on the table will now access the data at the checked out version.
+    /// As a consequence, calling this method will disable any read consistency interval
+    /// that was previously set.
     ///
-    /// * `uri` - The URI to the table.
-    /// * `name` The Table name
-    /// * `batches` RecordBatch to be saved in the database.
-    /// * `params` - Write parameters.
+    /// This is a read-only operation that turns the table into a sort of "view"
+    /// or "detached head".  Other table instances will not be affected.  To make the change
+    /// permanent you can use the `[Self::restore]` method.
     ///
-    /// # Returns
+    /// Any operation that modifies the table will fail while the table is in a checked
+    /// out state.
     ///
-    /// * A [TableImpl] object.
-    pub(crate) async fn create(
-        uri: &str,
-        name: &str,
-        batches: impl RecordBatchReader + Send + 'static,
-        write_store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
-        params: Option<WriteParams>,
-        read_consistency_interval: Option<std::time::Duration>,
-    ) -> Result<Self> {
-        let params = params.unwrap_or_default();
-        // patch the params if we have a write store wrapper
-        let params = match write_store_wrapper.clone() {
-            Some(wrapper) => params.patch_with_store_wrapper(wrapper)?,
-            None => params,
-        };
+    /// To return the table to a normal state use `[Self::checkout_latest]`
+    pub async fn checkout(&self, version: u64) -> Result<()> {
+        self.inner.checkout(version).await
+    }
 
-        let dataset = Dataset::write(batches, uri, Some(params))
-            .await
-            .map_err(|e| match e {
-                lance::Error::DatasetAlreadyExists { .. } => Error::TableAlreadyExists {
-                    name: name.to_string(),
-                },
-                source => Error::Lance { source },
-            })?;
-        Ok(Self {
-            name: name.to_string(),
-            uri: uri.to_string(),
-            dataset: DatasetConsistencyWrapper::new_latest(dataset, read_consistency_interval),
-            store_wrapper: write_store_wrapper,
-            read_consistency_interval,
-        })
+    /// Ensures the table is pointing at the latest version
+    ///
+    /// This can be used to manually update a table when the read_consistency_interval is None
+    /// It can also be used to undo a `[Self::checkout]` operation
+    pub async fn checkout_latest(&self) -> Result<()> {
+        self.inner.checkout_latest().await
     }
 
-    pub async fn create_empty(
-        uri: &str,
-        name: &str,
-        schema: SchemaRef,
-        write_store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
-        params: Option<WriteParams>,
-        read_consistency_interval: Option<std::time::Duration>,
-    ) -> Result<Self> {
-        let batches = RecordBatchIterator::new(vec![], schema);
-        Self::create(
-            uri,
-            name,
-            batches,
-            write_store_wrapper,
-            params,
-            read_consistency_interval,
-        )
-        .await
+    /// Restore the table to the currently checked out version
+    ///
+    /// This operation will fail if checkout has not been called previously
+    ///
+    /// This operation will overwrite the latest version of the table with a
+    /// previous version.  Any changes made since the checked out version will
+    /// no longer be visible.
+    ///
+    /// Once the operation concludes the table will no longer be in a checked
+    /// out state and the read_consistency_interval, if any, will apply.
+    pub async fn restore(&self) -> Result<()> {
+        self.inner.restore().await
     }
 
-    async fn optimize_indices(&self, options: &OptimizeOptions) -> Result<()> {
-        info!("LanceDB: optimizing indices: {:?}", options);
-        self.dataset
-            .get_mut()
-            .await?
-            .optimize_indices(options)
-            .await?;
-        Ok(())
+    /// List all indices that have been created with [`Self::create_index`]
+    pub async fn list_indices(&self) -> Result<Vec<IndexConfig>> {
+        self.inner.list_indices().await
     }
 
-    /// Merge new data into this table.
-    pub async fn merge(
-        &mut self,
-        batches: impl RecordBatchReader + Send + 'static,
-        left_on: &str,
-        right_on: &str,
-    ) -> Result<()> {
-        self.dataset
-            .get_mut()
+    /// Export the definition of this table as a portable [`TableSpec`]
+    ///
+    /// The spec contains the schema, indices, properties and embedding function
+    /// configuration but no data.  Use [`TableSpec::to_json`] to write it out and
+    /// [`crate::Connection::create_table_from_spec`] to recreate the table.
+    pub async fn to_spec(&self) -> Result<TableSpec> {
+        let schema = self.schema().await?;
+        let indices = self
+            .list_indices()
             .await?
-            .merge(batches, left_on, right_on)
-            .await?;
-        Ok(())
+            .into_iter()
+            .map(|index| (index.index_type, index.columns))
+            .collect();
+        TableSpec::new(self.name(), &schema, indices)
     }
 
-    /// Remove old versions of the dataset from disk.
+    /// Export the metadata of the index on the given column
     ///
-    /// # Arguments
-    /// * `older_than` - The duration of time to keep versions of the dataset.
-    /// * `delete_unverified` - Because they may be part of an in-progress
-    ///   transaction, files newer than 7 days old are not deleted by default.
-    ///   If you are sure that there are no in-progress transactions, then you
-    ///   can set this to True to delete all files older than `older_than`.
+    /// The metadata contains the index parameters and statistics but no data and
+    /// can be attached to bug reports.  See [`IndexMetadata`] for details.
+    pub async fn index_metadata(&self, column: &str) -> Result<IndexMetadata> {
+        self.inner.index_metadata(column).await
+    }
+
+    /// Create an index equivalent to the one described by the given metadata
     ///
-    /// This calls into [lance::dataset::Dataset::cleanup_old_versions] and
-    /// returns the result.
-    async fn cleanup_old_versions(
-        &self,
-        older_than: Duration,
-        delete_unverified: Option<bool>,
-    ) -> Result<RemovalStats> {
-        Ok(self
-            .dataset
-            .get_mut()
-            .await?
-            .cleanup_old_versions(older_than, delete_unverified)
-            .await?)
+    /// The index is created on the same columns as the original index.  This is
+    /// useful to reproduce index issues on synthetic data with the same schema.
+    pub fn import_index(&self, metadata: &IndexMetadata) -> IndexBuilder {
+        self.create_index(&metadata.columns, metadata.equivalent_index())
     }
 
-    /// Compact files in the dataset.
+    /// Compute the minimum, maximum and null count of columns in every fragment
     ///
-    /// This can be run after making several small appends to optimize the table
-    /// for faster reads.
+    /// Plain queries use these statistics to skip the fragments that cannot
+    /// match their filter, which helps filters on columns without an index.
+    /// If `columns` is empty the statistics of all top level boolean, numeric
+    /// and string columns are computed.
     ///
-    /// This calls into [lance::dataset::optimize::compact_files].
-    async fn compact_files(
-        &self,
-        options: CompactionOptions,
-        remap_options: Option<Arc<dyn IndexRemapperOptions>>,
-    ) -> Result<CompactionMetrics> {
-        let mut dataset_mut = self.dataset.get_mut().await?;
-        let metrics = compact_files(&mut dataset_mut, options, remap_options).await?;
-        Ok(metrics)
+    /// The statistics are not updated by writes, call this again after adding
+    /// data.  See [`fragment_stats`] for details and
+    /// [`crate::query::Query::fragment_pruning`] to check how many fragments a
+    /// query skips.
+    pub async fn compute_statistics(&self, columns: &[&str]) -> Result<()> {
+        self.inner.compute_statistics(columns).await
     }
 
-    // TODO: why are these individual methods and not some single "get_stats" method?
-    pub async fn count_fragments(&self) -> Result<usize> {
-        Ok(self.dataset.get().await?.count_fragments())
+    /// Read a uniform random sample of `n` rows of the table
+    ///
+    /// Only the sampled rows are read, which makes this much cheaper than a
+    /// full scan on large tables (e.g. to build a training set).  If the table
+    /// has fewer than `n` rows then all of them are returned.  The rows are
+    /// returned in the order of the table.
+    ///
+    /// The same `seed` returns the same sample as long as the table does not
+    /// change.  See [`sample`] for details.
+    pub async fn sample(&self, n: usize, seed: Option<u64>) -> Result<RecordBatch> {
+        self.inner.sample(n, seed).await
     }
 
-    pub async fn count_deleted_rows(&self) -> Result<usize> {
-        Ok(self.dataset.get().await?.count_deleted_rows().await?)
+    /// Measure the recall and latency of vector searches with different settings
+    ///
+    /// The results of the `queries` with each setting are compared with the
+    /// `k` true nearest neighbors.  If `queries` is empty, the vectors of a
+    /// sample of rows are used.  See [`crate::eval`] for details.
+    pub fn evaluate_recall(&self, queries: Vec<Vec<f32>>, k: usize) -> EvaluateRecall {
+        EvaluateRecall::new(self.clone(), queries, k)
     }
 
-    pub async fn num_small_files(&self, max_rows_per_group: usize) -> Result<usize> {
-        Ok(self
-            .dataset
-            .get()
-            .await?
-            .num_small_files(max_rows_per_group)
-            .await)
+    /// Find and store the fastest search settings that reach a target recall
+    ///
+    /// The recall of searches on the vector column is measured with `sample`
+    /// rows as queries and several values of `nprobes` and `refine_factor`.
+    /// The fastest setting with a recall of at least `target_recall` (or the
+    /// setting with the best recall, if none reaches it) is stored in the table
+    /// properties and used by vector searches that do not set these values.
+    ///
+    /// The table must have a single vector column with an index.  See
+    /// [`tuning`] for details.
+    pub async fn tune_search(&self, target_recall: f64, sample: usize) -> Result<SearchDefaults> {
+        if !(0.0..=1.0).contains(&target_recall) {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the target recall must be between 0 and 1, not {}",
+                    target_recall
+                ),
+            });
+        }
+        let column = default_vector_column(&*self.schema().await?, None)?;
+        let has_index = self.list_indices().await?.iter().any(|index| {
+            index.index_type == crate::index::IndexType::IvfPq && index.columns == [column.clone()]
+        });
+        if !has_index {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the column '{}' has no vector index, there is nothing to tune",
+                    column
+                ),
+            });
+        }
+        let report = self
+            .evaluate_recall(vec![], DEFAULT_TOP_K)
+            .column(&column)
+            .sample_size(sample)
+            .nprobes(&TUNED_NPROBES)
+            .refine_factors(&TUNED_REFINE_FACTORS)
+            .execute()
+            .await?;
+        let best = match report.fastest_with_recall(target_recall) {
+            Some(best) => best,
+            None => {
+                let best = report
+                    .results
+                    .iter()
+                    .max_by(|a, b| a.recall.total_cmp(&b.recall))
+                    .ok_or_else(|| Error::InvalidInput {
+                        message: "no search settings were evaluated".to_string(),
+                    })?;
+                log::warn!(
+                    "No search settings of the table '{}' reach a recall of {}, using the best \
+                     recall of {}",
+                    self.name(),
+                    target_recall,
+                    best.recall
+                );
+                best
+            }
+        };
+        let defaults = SearchDefaults {
+            nprobes: best.settings.nprobes,
+            refine_factor: best.settings.refine_factor,
+            recall: best.recall,
+            k: report.k,
+        };
+        self.inner
+            .set_search_defaults(&column, Some(defaults.clone()))
+            .await?;
+        Ok(defaults)
     }
 
-    pub async fn count_indexed_rows(&self, index_uuid: &str) -> Result<Option<usize>> {
-        match self.load_index_stats(index_uuid).await? {
-            Some(stats) => Ok(Some(stats.num_indexed_rows)),
-            None => Ok(None),
-        }
+    /// Get the tuned search defaults of the table, by vector column name
+    ///
+    /// See [`Self::tune_search`].
+    pub async fn search_defaults(&self) -> Result<HashMap<String, SearchDefaults>> {
+        self.inner.search_defaults().await
     }
 
-    pub async fn count_unindexed_rows(&self, index_uuid: &str) -> Result<Option<usize>> {
-        match self.load_index_stats(index_uuid).await? {
-            Some(stats) => Ok(Some(stats.num_unindexed_rows)),
-            None => Ok(None),
-        }
+    /// Set (or, if `defaults` is None, remove) the search defaults of a vector column
+    ///
+    /// This stores settings without measuring their recall, see
+    /// [`Self::tune_search`] to find them with a benchmark.
+    pub async fn set_search_defaults(
+        &self,
+        column: &str,
+        defaults: Option<SearchDefaults>,
+    ) -> Result<()> {
+        self.inner.set_search_defaults(column, defaults).await
     }
 
-    pub async fn load_indices(&self) -> Result<Vec<VectorIndex>> {
-        let dataset = self.dataset.get().await?;
-        let (indices, mf) = futures::try_join!(dataset.load_indices(), dataset.latest_manifest())?;
-        Ok(indices
-            .iter()
-            .map(|i| VectorIndex::new_from_format(&mf, i))
-            .collect())
+    /// The descriptive metadata of the table, e.g. its description and owner
+    ///
+    /// See [`user_metadata`] for the common keys.  The settings LanceDB
+    /// stores in the schema metadata are not included.
+    pub async fn metadata(&self) -> Result<HashMap<String, String>> {
+        self.inner.metadata(None).await
     }
 
-    async fn load_index_stats(&self, index_uuid: &str) -> Result<Option<VectorIndexStatistics>> {
-        let index = self
-            .load_indices()
-            .await?
-            .into_iter()
-            .find(|i| i.index_uuid == index_uuid);
-        if index.is_none() {
-            return Ok(None);
-        }
-        let dataset = self.dataset.get().await?;
-        let index_stats = dataset.index_statistics(&index.unwrap().index_name).await?;
-        let index_stats: VectorIndexStatistics = whatever!(
-            serde_json::from_str(&index_stats),
-            "error deserializing index statistics {index_stats}",
-        );
+    /// Set (or, if `value` is None, remove) an entry of the table metadata
+    ///
+    /// Each call commits a new version of the table.
+    pub async fn set_metadata(&self, key: &str, value: Option<&str>) -> Result<()> {
+        self.inner.set_metadata(None, key, value).await
+    }
 
-        Ok(Some(index_stats))
+    /// The descriptive metadata of a column, e.g. the model of its embeddings
+    ///
+    /// Nested columns are referred to by their path, e.g. `"meta.source"`.
+    pub async fn column_metadata(&self, column: &str) -> Result<HashMap<String, String>> {
+        self.inner.metadata(Some(column)).await
     }
 
-    async fn create_ivf_pq_index(
+    /// Set (or, if `value` is None, remove) an entry of the metadata of a column
+    ///
+    /// Each call commits a new version of the table.
+    pub async fn set_column_metadata(
         &self,
-        index: IvfPqIndexBuilder,
-        field: &Field,
-        replace: bool,
+        column: &str,
+        key: &str,
+        value: Option<&str>,
     ) -> Result<()> {
-        if !Self::supported_vector_data_type(field.data_type()) {
-            return Err(Error::InvalidInput {
-                message: format!(
-                    "An IVF PQ index cannot be created on the column `{}` which has data type {}",
-                    field.name(),
-                    field.data_type()
-                ),
-            });
-        }
+        self.inner.set_metadata(Some(column), key, value).await
+    }
 
-        let num_partitions = if let Some(n) = index.num_partitions {
-            n
-        } else {
-            suggested_num_partitions(self.count_rows(None).await?)
-        };
-        let num_sub_vectors: u32 = if let Some(n) = index.num_sub_vectors {
-            n
-        } else {
-            match field.data_type() {
-                arrow_schema::DataType::FixedSizeList(_, n) => {
-                    Ok::<u32, Error>(suggested_num_sub_vectors(*n as u32))
-                }
-                _ => Err(Error::Schema {
-                    message: format!("Column '{}' is not a FixedSizeList", field.name()),
-                }),
-            }?
-        };
-        let mut dataset = self.dataset.get_mut().await?;
-        let lance_idx_params = lance::index::vector::VectorIndexParams::ivf_pq(
-            num_partitions as usize,
-            /*num_bits=*/ 8,
-            num_sub_vectors as usize,
-            false,
-            index.distance_type.into(),
-            index.max_iterations as usize,
-        );
-        dataset
-            .create_index(
-                &[field.name()],
-                IndexType::Vector,
-                None,
-                &lance_idx_params,
-                replace,
-            )
-            .await?;
-        Ok(())
+    /// Statistics about the size of the writes made through this handle
+    ///
+    /// Use [`WriteStats::small_write_warning`] to check if the table is receiving
+    /// too many small commits.  A warning is also logged when this happens.
+    pub fn write_stats(&self) -> Result<WriteStats> {
+        self.inner.write_stats()
     }
 
-    async fn create_auto_index(&self, field: &Field, opts: IndexBuilder) -> Result<()> {
-        if Self::supported_vector_data_type(field.data_type()) {
-            self.create_ivf_pq_index(IvfPqIndexBuilder::default(), field, opts.replace)
-                .await
-        } else if Self::supported_btree_data_type(field.data_type()) {
-            self.create_btree_index(field, opts).await
-        } else {
-            Err(Error::InvalidInput {
-                message: format!(
-                    "there are no indices supported for the field `{}` with the data type {}",
-                    field.name(),
-                    field.data_type()
-                ),
-            })
-        }
+    /// Statistics about the object store requests made through this handle
+    ///
+    /// The requests, bytes and latencies of reads and writes, see
+    /// [`crate::io::metrics`] for details.  Use them to find where the
+    /// storage cost and latency of a workload come from.
+    pub fn io_stats(&self) -> Result<IoStats> {
+        self.inner.io_stats()
     }
 
-    async fn create_btree_index(&self, field: &Field, opts: IndexBuilder) -> Result<()> {
-        if !Self::supported_btree_data_type(field.data_type()) {
-            return Err(Error::Schema {
-                message: format!(
-                    "A BTree index cannot be created on the field `{}` which has data type {}",
-                    field.name(),
-                    field.data_type()
-                ),
-            });
-        }
+    /// Which fragments of the table are in the local tier
+    ///
+    /// Lists every fragment of the current version with its tier, the size
+    /// of its local copy and how often it was read recently.  See
+    /// [`crate::io::tiering`] for how the tiers are decided.  If tiering is
+    /// not enabled for the connection the report says so and every fragment
+    /// is in the tier of the table's store.
+    pub async fn tiering_report(&self) -> Result<TieringReport> {
+        self.inner.tiering_report().await
+    }
 
-        let mut dataset = self.dataset.get_mut().await?;
-        let lance_idx_params = lance::index::scalar::ScalarIndexParams {};
-        dataset
-            .create_index(
-                &[field.name()],
-                IndexType::Scalar,
-                None,
-                &lance_idx_params,
-                opts.replace,
-            )
-            .await?;
-        Ok(())
+    /// Check the integrity of the current version of the table
+    ///
+    /// Every data file referenced by the manifest must exist and every row is
+    /// read and decoded.  The manifest is checked for duplicate fragments and
+    /// each index is opened and compared with the table.  See [`verify`] for
+    /// the limits of these checks.
+    ///
+    /// Problems with the data are returned in the report, an error is only
+    /// returned if the manifest itself can not be read.  This reads the whole
+    /// table and should be run in the background, e.g. as a periodic scrub.
+    pub async fn verify(&self) -> Result<IntegrityReport> {
+        self.inner.verify().await
     }
 
-    async fn generic_query(
+    /// Repair the table after a writer crashed
+    ///
+    /// Depending on the [`RepairOptions`] this deletes orphaned data files,
+    /// rolls the table back to the latest version without missing or corrupt
+    /// data and rebuilds the indices that can not be opened.  See [`repair`]
+    /// for more details.
+    ///
+    /// Like [`Self::verify`] this reads the whole table.  Rolling back discards
+    /// the changes made after the restored version, check the returned
+    /// [`RepairReport::integrity`] or run [`Self::verify`] first to see what
+    /// is wrong.
+    pub async fn repair(&self, options: RepairOptions) -> Result<RepairReport> {
+        self.inner.repair(options).await
+    }
+
+    /// Recompute the embeddings of a vector column with a new model
+    ///
+    /// The embeddings of every row are computed from the source column and
+    /// committed as a single new version, replacing the column or, with
+    /// [`ReembedBuilder::output_column`], as a new column.  The index of the
+    /// column is then rebuilt.  An interrupted re-embedding resumes from the
+    /// fragments it completed when it is run again.  See [`reembed`] for
+    /// more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use lancedb::Table;
+    /// # use lancedb::table::reembed::EmbeddingFunction;
+    /// # async fn doctest_helper(tbl: Table, model: Arc<dyn EmbeddingFunction>) {
+    /// let report = tbl
+    ///     .reembed("vector", model)
+    ///     .source_column("text")
+    ///     .on_progress(|p| println!("{}/{} fragments", p.fragments_done, p.fragments_total))
+    ///     .execute()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn reembed(
         &self,
-        query: &VectorQuery,
-        options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream> {
-        let ds_ref = self.dataset.get().await?;
-        let mut scanner: Scanner = ds_ref.scan();
+        column: impl Into<String>,
+        function: Arc<dyn EmbeddingFunction>,
+    ) -> ReembedBuilder {
+        ReembedBuilder::new(self.inner.clone(), column.into(), function)
+    }
 
-        if let Some(query_vector) = query.query_vector.as_ref() {
-            // If there is a vector query, default to limit=10 if unspecified
-            let column = if let Some(col) = query.column.as_ref() {
-                col.clone()
-            } else {
-                // Infer a vector column with the same dimension of the query vector.
-                let arrow_schema = Schema::from(ds_ref.schema());
-                default_vector_column(&arrow_schema, Some(query_vector.len() as i32))?
-            };
-            let field = ds_ref.schema().field(&column).ok_or(Error::Schema {
-                message: format!("Column {} not found in dataset schema", column),
-            })?;
-            if let arrow_schema::DataType::FixedSizeList(f, dim) = field.data_type() {
-                if !f.data_type().is_floating() {
-                    return Err(Error::InvalidInput {
-                        message: format!(
-                            "The data type of the vector column '{}' is not a floating point type",
-                            column
-                        ),
-                    });
-                }
-                if dim != query_vector.len() as i32 {
-                    return Err(Error::InvalidInput {
-                        message: format!(
-                            "The dimension of the query vector does not match with the dimension of the vector column '{}':
-                                query dim={}, expected vector dim={}",
-                            column,
-                            query_vector.len(),
-                            dim,
-                        ),
-                    });
-                }
-            }
-            let query_vector = query_vector.as_primitive::<Float32Type>();
-            scanner.nearest(
-                &column,
-                query_vector,
-                query.base.limit.unwrap_or(DEFAULT_TOP_K),
-            )?;
-        } else {
-            // If there is no vector query, it's ok to not have a limit
-            scanner.limit(query.base.limit.map(|limit| limit as i64), None)?;
-        }
-        scanner.nprobs(query.nprobes);
-        scanner.use_index(query.use_index);
-        scanner.prefilter(query.prefilter);
-        scanner.batch_size(options.max_batch_length as usize);
-
-        match &query.base.select {
-            Select::Columns(select) => {
-                scanner.project(select.as_slice())?;
-            }
-            Select::Dynamic(select_with_transform) => {
-                scanner.project_with_transform(select_with_transform.as_slice())?;
-            }
-            Select::All => { /* Do nothing */ }
-        }
+    /// Get the counters of the filter cache of this handle
+    ///
+    /// Returns None if the filter cache is not enabled, see
+    /// [`crate::connection::ConnectBuilder::filter_cache`].
+    pub fn filter_cache_metrics(&self) -> Result<Option<FilterCacheMetrics>> {
+        self.inner.filter_cache_metrics()
+    }
 
-        if let Some(filter) = &query.base.filter {
-            scanner.filter(filter)?;
-        }
+    /// Create a writer that coalesces many small writes into fewer commits
+    ///
+    /// See [`BufferedWriter`] for details.  This must be called from within a
+    /// tokio runtime since the writer spawns a task to flush data in the background.
+    pub fn buffered_writer(&self, config: BufferedWriterConfig) -> BufferedWriter {
+        BufferedWriter::new(self.clone(), config)
+    }
 
-        if let Some(refine_factor) = query.refine_factor {
-            scanner.refine(refine_factor);
-        }
+    /// Create a read-only handle pinned to the current version of the table
+    ///
+    /// Every operation executed through the snapshot (queries, vector searches,
+    /// row counts, ...) sees the same version of the data, even if the table is
+    /// modified concurrently.  This is useful when a single logical request is
+    /// made up of several steps (e.g. a vector search followed by a query for the
+    /// payloads of the results and then some counts) that need to agree with
+    /// each other.
+    ///
+    /// The snapshot is independent of this table.  Writes to this table are not
+    /// visible through the snapshot and any attempt to modify the table through
+    /// the snapshot will fail.
+    ///
+    /// The version of the snapshot is pinned (see
+    /// [`crate::connection::ConnectBuilder::pin_query_versions`]) until the
+    /// snapshot and all of its clones are dropped, so pruning old versions
+    /// through this handle keeps it.  Pins are held in memory: other handles of
+    /// the table, opened separately or in other processes, do not see them
+    /// and can still prune the version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lancedb::Table;
+    /// # async fn doctest_helper(tbl: Table) {
+    /// let snapshot = tbl.snapshot().await.unwrap();
+    /// // Both calls see the same version, regardless of concurrent writes
+    /// let total = snapshot.count_rows(None).await.unwrap();
+    /// let matching = snapshot.count_rows(Some("id > 10".to_string())).await.unwrap();
+    /// assert!(matching <= total);
+    /// # }
+    /// ```
+    pub async fn snapshot(&self) -> Result<Self> {
+        Ok(Self::new(self.inner.snapshot().await?))
+    }
 
-        if let Some(distance_type) = query.distance_type {
-            scanner.distance_metric(distance_type.into());
-        }
-        Ok(scanner.try_into_stream().await?)
+    /// Create a new table, next to this one, with the data of the current version
+    ///
+    /// Unlike [`Self::snapshot`] the new table is writable: writes to either
+    /// table are not visible in the other.
+    ///
+    /// This is a shallow clone, no data is copied:
+    ///
+    /// * On a local file system the data files of the new table are hard links
+    ///   to the files of this table.
+    /// * On an object store the new table reads the data files of this table
+    ///   in place.  This table keeps the cloned version, and so the files,
+    ///   when it is pruned, for as long as the new table exists.  This table
+    ///   must not be dropped or overwritten before the new table is.
+    ///
+    /// The new table has the schema and the table properties of this table but
+    /// no indices and no history.  Use [`Self::list_indices`] and
+    /// [`Self::create_index`] to recreate the indices.
+    pub async fn copy_to(&self, name: &str) -> Result<Self> {
+        Ok(Self::new(self.inner.copy_to(name).await?))
     }
 }
 
-#[async_trait::async_trait]
-impl TableInternal for NativeTable {
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
+impl From<NativeTable> for Table {
+    fn from(table: NativeTable) -> Self {
+        Self {
+            inner: Arc::new(table),
+        }
     }
+}
+/// A table in a LanceDB database.
+#[derive(Debug, Clone)]
+pub struct NativeTable {
+    name: String,
+    uri: String,
+    pub(crate) dataset: dataset::DatasetConsistencyWrapper,
 
-    fn as_native(&self) -> Option<&NativeTable> {
-        Some(self)
-    }
+    // wraps the object store on both the read and the write path, always
+    // includes the metrics
+    store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
+    // records the object store requests of the table
+    io_metrics: IoMetricsWrapper,
+    // the parameters of the object store, with the wrapper and the
+    // credentials of the connection
+    store_params: ObjectStoreParams,
 
-    fn name(&self) -> &str {
-        self.name.as_str()
-    }
+    // This comes from the connection options. We store here so we can pass down
+    // to the dataset when we recreate it (for example, in checkout_latest).
+    read_consistency_interval: Option<std::time::Duration>,
 
-    async fn version(&self) -> Result<u64> {
-        Ok(self.dataset.get().await?.version().version)
-    }
+    // Limits concurrent operations, shared with the other tables of the connection
+    admission: Option<Arc<AdmissionController>>,
 
-    async fn checkout(&self, version: u64) -> Result<()> {
-        self.dataset.as_time_travel(version).await
-    }
+    // Limits the memory of queries and index builds, shared with the other tables of the connection
+    memory_pool: Option<Arc<MemoryPool>>,
 
-    async fn checkout_latest(&self) -> Result<()> {
-        self.dataset
-            .as_latest(self.read_consistency_interval)
-            .await?;
-        self.dataset.reload().await
-    }
+    // Sizes of the commits made through this handle
+    write_stats: Arc<WriteStatsTracker>,
 
-    async fn restore(&self) -> Result<()> {
-        let version =
-            self.dataset
-                .time_travel_version()
-                .await
-                .ok_or_else(|| Error::InvalidInput {
-                    message: "you must run checkout before running restore".to_string(),
-                })?;
-        {
-            // Use get_mut_unchecked as restore is the only "write" operation that is allowed
-            // when the table is in time travel mode.
-            // Also, drop the guard after .restore because as_latest will need it
-            let mut dataset = self.dataset.get_mut_unchecked().await?;
-            debug_assert_eq!(dataset.version().version, version);
-            dataset.restore().await?;
-        }
-        self.dataset
-            .as_latest(self.read_consistency_interval)
-            .await?;
-        Ok(())
-    }
+    // Parameters and timings of the indices built through this handle
+    index_builds: Arc<IndexBuildTracker>,
 
-    async fn schema(&self) -> Result<SchemaRef> {
-        let lance_schema = self.dataset.get().await?.schema().clone();
-        Ok(Arc::new(Schema::from(&lance_schema)))
-    }
+    // Fragments written by bulk loads through this handle but not committed yet
+    bulk_load: Arc<BulkLoadTracker>,
 
-    async fn count_rows(&self, filter: Option<String>) -> Result<usize> {
-        let dataset = self.dataset.get().await?;
-        if let Some(filter) = filter {
-            let mut scanner = dataset.scan();
-            scanner.filter(&filter)?;
-            Ok(scanner.count_rows().await? as usize)
-        } else {
-            Ok(dataset.count_rows().await?)
-        }
-    }
+    // If true, the masking policies of the table are not applied to reads
+    unmasked: bool,
 
-    async fn add(
-        &self,
-        add: AddDataBuilder<NoData>,
-        data: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<()> {
-        let lance_params = add.write_options.lance_write_params.unwrap_or(WriteParams {
-            mode: match add.mode {
-                AddDataMode::Append => WriteMode::Append,
-                AddDataMode::Overwrite => WriteMode::Overwrite,
-            },
-            ..Default::default()
-        });
+    // The versions read by live query streams, None if pinning is disabled
+    version_pins: Option<Arc<VersionPins>>,
 
-        // patch the params if we have a write store wrapper
-        let lance_params = match self.store_wrapper.clone() {
-            Some(wrapper) => lance_params.patch_with_store_wrapper(wrapper)?,
-            None => lance_params,
-        };
+    // Pins the version of a snapshot while any handle of the snapshot is alive
+    snapshot_pin: Option<Arc<VersionPin>>,
 
-        self.dataset.ensure_mutable().await?;
+    // Called after each commit made through this handle
+    commit_hooks: Vec<Arc<dyn CommitHook>>,
 
-        let dataset = Dataset::write(data, &self.uri, Some(lance_params)).await?;
-        self.dataset.set_latest(dataset).await;
-        Ok(())
-    }
+    // Passed to the commit hooks to tell who made a commit
+    audit_context: Option<String>,
 
-    async fn create_index(&self, opts: IndexBuilder) -> Result<()> {
-        if opts.columns.len() != 1 {
-            return Err(Error::Schema {
-                message: "Multi-column (composite) indices are not yet supported".to_string(),
-            });
-        }
-        let schema = self.schema().await?;
+    // The tiers of the data files, shared with the other tables of the connection
+    tiering: Option<Arc<Tiering>>,
 
-        let field = schema.field_with_name(&opts.columns[0])?;
+    // The default number of batches decoded concurrently by a query
+    decode_parallelism: Option<usize>,
 
-        match opts.index {
-            Index::Auto => self.create_auto_index(field, opts).await,
-            Index::BTree(_) => self.create_btree_index(field, opts).await,
-            Index::IvfPq(ivf_pq) => self.create_ivf_pq_index(ivf_pq, field, opts.replace).await,
-        }
-    }
+    // The rows selected by the filters of recent vector searches, if enabled
+    filter_cache: Option<Arc<FilterCache>>,
 
-    async fn update(&self, update: UpdateBuilder) -> Result<()> {
-        let dataset = self.dataset.get().await?.clone();
-        let mut builder = LanceUpdateBuilder::new(Arc::new(dataset));
-        if let Some(predicate) = update.filter {
-            builder = builder.update_where(&predicate)?;
-        }
+    // Adds mandatory filters and masks to queries, updates and deletes
+    query_interceptor: Option<Arc<dyn QueryInterceptor>>,
 
-        for (column, value) in update.columns {
-            builder = builder.set(column, &value)?;
-        }
+    // If true, queries that do not select columns leave out the vector columns
+    exclude_vectors: bool,
+}
 
-        let operation = builder.build()?;
-        let ds = operation.execute().await?;
-        self.dataset.set_latest(ds.as_ref().clone()).await;
-        Ok(())
+impl std::fmt::Display for NativeTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "NativeTable({}, uri={}, read_consistency_interval={})",
+            self.name,
+            self.uri,
+            match self.read_consistency_interval {
+                None => {
+                    "None".to_string()
+                }
+                Some(duration) => {
+                    format!("{}s", duration.as_secs_f64())
+                }
+            }
+        )
     }
+}
 
-    async fn plain_query(
-        &self,
-        query: &Query,
-        options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream> {
-        self.generic_query(&query.clone().into_vector(), options)
-            .await
+impl NativeTable {
+    /// Opens an existing Table
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - The uri to a [NativeTable]
+    /// * `name` - The table name
+    ///
+    /// # Returns
+    ///
+    /// * A [NativeTable] object.
+    pub async fn open(uri: &str) -> Result<Self> {
+        let name = Self::get_table_name(uri)?;
+        Self::open_with_params(uri, &name, None, None, None).await
     }
 
-    async fn vector_query(
-        &self,
-        query: &VectorQuery,
-        options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream> {
-        self.generic_query(query, options).await
-    }
+    /// Opens an existing Table
+    ///
+    /// # Arguments
+    ///
+    /// * `base_path` - The base path where the table is located
+    /// * `name` The Table name
+    /// * `params` The [ReadParams] to use when opening the table
+    ///
+    /// # Returns
+    ///
+    /// * A [NativeTable] object.
+    pub async fn open_with_params(
+        uri: &str,
+        name: &str,
+        store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
+        params: Option<ReadParams>,
+        read_consistency_interval: Option<std::time::Duration>,
+    ) -> Result<Self> {
+        let metrics = Arc::new(IoMetrics::new(name));
+        let io_metrics = IoMetricsWrapper::new(metrics.clone()).around(store_wrapper.clone());
+        let params = params.unwrap_or_default();
+        let (mut dataset, mut store_params) =
+            Self::load_dataset(uri, name, &io_metrics, params.clone()).await?;
+
+        // A shallow clone on an object store reads the data files it shares
+        // from its sources, the store has to be wrapped before it is opened
+        let cloned_from = ClonedFrom::from_metadata(&dataset.schema().metadata)?;
+        let io_metrics = if cloned_from.0.is_empty() {
+            io_metrics
+        } else {
+            let (_, path) = ObjectStore::from_uri_and_params(uri, &store_params).await?;
+            let shallow_clone =
+                ShallowCloneWrapper::new(&path, &cloned_from.paths()?).around(store_wrapper);
+            let io_metrics = IoMetricsWrapper::new(metrics).around(Some(Arc::new(shallow_clone)));
+            (dataset, store_params) = Self::load_dataset(uri, name, &io_metrics, params).await?;
+            io_metrics
+        };
 
-    async fn merge_insert(
-        &self,
-        params: MergeInsertBuilder,
-        new_data: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<()> {
-        let dataset = Arc::new(self.dataset.get().await?.clone());
-        let mut builder = LanceMergeInsertBuilder::try_new(dataset.clone(), params.on)?;
-        match (
-            params.when_matched_update_all,
-            params.when_matched_update_all_filt,
-        ) {
-            (false, _) => builder.when_matched(WhenMatched::DoNothing),
-            (true, None) => builder.when_matched(WhenMatched::UpdateAll),
-            (true, Some(filt)) => builder.when_matched(WhenMatched::update_if(&dataset, &filt)?),
-        };
-        if params.when_not_matched_insert_all {
-            builder.when_not_matched(lance::dataset::WhenNotMatched::InsertAll);
-        } else {
-            builder.when_not_matched(lance::dataset::WhenNotMatched::DoNothing);
-        }
-        if params.when_not_matched_by_source_delete {
-            let behavior = if let Some(filter) = params.when_not_matched_by_source_delete_filt {
-                WhenNotMatchedBySource::delete_if(dataset.as_ref(), &filter)?
-            } else {
-                WhenNotMatchedBySource::Delete
-            };
-            builder.when_not_matched_by_source(behavior);
-        } else {
-            builder.when_not_matched_by_source(WhenNotMatchedBySource::Keep);
-        }
-        let job = builder.try_build()?;
-        let new_dataset = job.execute_reader(new_data).await?;
-        self.dataset.set_latest(new_dataset.as_ref().clone()).await;
-        Ok(())
+        let dataset = DatasetConsistencyWrapper::new_latest(dataset, read_consistency_interval);
+
+        Ok(Self {
+            name: name.to_string(),
+            uri: uri.to_string(),
+            dataset,
+            store_wrapper: Some(Arc::new(io_metrics.clone())),
+            io_metrics,
+            store_params,
+            read_consistency_interval,
+            admission: None,
+            memory_pool: None,
+            write_stats: Arc::default(),
+            index_builds: Arc::default(),
+            bulk_load: Arc::default(),
+            unmasked: false,
+            version_pins: Some(Arc::default()),
+            snapshot_pin: None,
+            commit_hooks: Vec::new(),
+            audit_context: None,
+            tiering: None,
+            decode_parallelism: None,
+            filter_cache: None,
+            query_interceptor: None,
+            exclude_vectors: false,
+        })
     }
 
-    /// Delete rows from the table
-    async fn delete(&self, predicate: &str) -> Result<()> {
-        self.dataset.get_mut().await?.delete(predicate).await?;
-        Ok(())
+    /// Load the dataset with the wrappers of `io_metrics`, returns the store params used
+    async fn load_dataset(
+        uri: &str,
+        name: &str,
+        io_metrics: &IoMetricsWrapper,
+        mut params: ReadParams,
+    ) -> Result<(Dataset, ObjectStoreParams)> {
+        let store_params = io_metrics.patch(params.store_options.take())?;
+        params.store_options = Some(store_params.clone());
+        let dataset = DatasetBuilder::from_uri(uri)
+            .with_read_params(params)
+            .load()
+            .await
+            .map_err(|e| match e {
+                lance::Error::DatasetNotFound { .. } => Error::TableNotFound {
+                    name: name.to_string(),
+                },
+                source => Error::Lance { source },
+            })?;
+        Ok((dataset, store_params))
     }
 
-    async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats> {
-        let mut stats = OptimizeStats {
-            compaction: None,
-            prune: None,
-        };
-        match action {
-            OptimizeAction::All => {
-                stats.compaction = self
-                    .optimize(OptimizeAction::Compact {
-                        options: CompactionOptions::default(),
-                        remap_options: None,
-                    })
-                    .await?
-                    .compaction;
-                stats.prune = self
-                    .optimize(OptimizeAction::Prune {
-                        older_than: Duration::try_days(7).unwrap(),
-                        delete_unverified: None,
-                    })
-                    .await?
-                    .prune;
-                self.optimize(OptimizeAction::Index(OptimizeOptions::default()))
-                    .await?;
-            }
-            OptimizeAction::Compact {
-                options,
-                remap_options,
-            } => {
-                stats.compaction = Some(self.compact_files(options, remap_options).await?);
-            }
-            OptimizeAction::Prune {
-                older_than,
-                delete_unverified,
-            } => {
-                stats.prune = Some(
-                    self.cleanup_old_versions(older_than, delete_unverified)
-                        .await?,
-                );
-            }
-            OptimizeAction::Index(options) => {
-                self.optimize_indices(&options).await?;
-            }
+    /// Open the object store of the table, with the credentials and the
+    /// wrappers of the connection
+    ///
+    /// Returns the store and the path of the table in it.
+    pub(crate) async fn object_store(&self) -> Result<(ObjectStore, object_store::path::Path)> {
+        Ok(ObjectStore::from_uri_and_params(&self.uri, &self.store_params).await?)
+    }
+
+    /// Read the original values of masked columns through this handle
+    ///
+    /// See [`crate::connection::OpenTableBuilder::unmasked`]
+    pub fn with_unmasked_access(mut self, unmasked: bool) -> Self {
+        self.unmasked = unmasked;
+        self
+    }
+
+    /// Pin the versions read by queries so that pruning does not remove them
+    ///
+    /// Enabled by default.  See [`crate::connection::ConnectBuilder::pin_query_versions`]
+    pub fn with_version_pinning(mut self, enabled: bool) -> Self {
+        self.version_pins = enabled.then(Arc::default);
+        self
+    }
+
+    /// The versions that are being read by live query streams
+    ///
+    /// Always empty if version pinning is disabled.
+    pub fn pinned_versions(&self) -> Vec<u64> {
+        self.version_pins
+            .as_ref()
+            .map(|pins| pins.versions())
+            .unwrap_or_default()
+    }
+
+    /// The number of batches decoded concurrently by queries that do not override it
+    ///
+    /// See [`crate::connection::ConnectBuilder::decode_parallelism`]
+    pub fn with_decode_parallelism(mut self, decode_parallelism: Option<usize>) -> Self {
+        self.decode_parallelism = decode_parallelism;
+        self
+    }
+
+    /// Log the object store requests slower than `threshold`, if set
+    ///
+    /// See [`crate::connection::ConnectBuilder::slow_request_threshold`]
+    pub fn with_slow_request_threshold(self, threshold: Option<std::time::Duration>) -> Self {
+        if let Some(threshold) = threshold {
+            self.io_metrics.metrics().set_slow_threshold(threshold);
         }
-        Ok(stats)
+        self
     }
 
-    async fn add_columns(
+    /// The context passed to the commit hooks, e.g. for the audit log
+    ///
+    /// See [`crate::connection::OpenTableBuilder::audit_context`]
+    pub fn with_audit_context(mut self, context: Option<String>) -> Self {
+        self.audit_context = context;
+        self
+    }
+
+    /// Report the tiers of the data files kept by `tiering`
+    ///
+    /// See [`crate::connection::ConnectBuilder::tiering`]
+    pub(crate) fn with_tiering(mut self, tiering: Option<Arc<Tiering>>) -> Self {
+        self.tiering = tiering;
+        self
+    }
+
+    /// Leave the vector columns out of the results of queries that do not select columns
+    ///
+    /// See [`crate::connection::ConnectBuilder::exclude_vectors`]
+    pub fn with_vectors_excluded(mut self, exclude_vectors: bool) -> Self {
+        self.exclude_vectors = exclude_vectors;
+        self
+    }
+
+    /// Cache the rows selected by the filters of vector searches
+    ///
+    /// See [`crate::connection::ConnectBuilder::filter_cache`]
+    pub fn with_filter_cache(mut self, config: Option<FilterCacheConfig>) -> Self {
+        self.filter_cache = config.map(|config| Arc::new(FilterCache::new(config)));
+        self
+    }
+
+    /// Restrict the queries, updates and deletes made through this handle
+    ///
+    /// See [`crate::connection::ConnectBuilder::query_interceptor`]
+    pub fn with_query_interceptor(
+        mut self,
+        interceptor: Option<Arc<dyn QueryInterceptor>>,
+    ) -> Self {
+        self.query_interceptor = interceptor;
+        self
+    }
+
+    /// Call the hooks after each commit made through this handle
+    ///
+    /// See [`crate::connection::ConnectBuilder::commit_hook`]
+    pub fn with_commit_hooks(mut self, hooks: Vec<Arc<dyn CommitHook>>) -> Self {
+        self.commit_hooks = hooks;
+        self
+    }
+
+    /// Call the commit hooks, in order, for a version committed through this handle
+    ///
+    /// Must be called after the dataset lock is released, hooks may read the table.
+    pub(crate) async fn run_commit_hooks(
         &self,
-        transforms: NewColumnTransform,
-        read_columns: Option<Vec<String>>,
+        operation: &'static str,
+        version: u64,
+        rows: Option<usize>,
     ) -> Result<()> {
-        self.dataset
-            .get_mut()
-            .await?
-            .add_columns(transforms, read_columns)
-            .await?;
-        Ok(())
+        self.run_commit_hooks_with_details(operation, version, rows, None)
+            .await
     }
 
-    async fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()> {
-        self.dataset
-            .get_mut()
-            .await?
-            .alter_columns(alterations)
-            .await?;
+    /// Call the commit hooks with the parameters of the operation
+    async fn run_commit_hooks_with_details(
+        &self,
+        operation: &'static str,
+        version: u64,
+        rows: Option<usize>,
+        details: Option<String>,
+    ) -> Result<()> {
+        if self.commit_hooks.is_empty() {
+            return Ok(());
+        }
+        let commit = CommitInfo {
+            table: self.name.clone(),
+            uri: self.uri.clone(),
+            store_params: self.store_params.clone(),
+            version,
+            operation: operation.to_string(),
+            rows,
+            details,
+            context: self.audit_context.clone(),
+        };
+        for hook in &self.commit_hooks {
+            hook.after_commit(&commit)
+                .await
+                .map_err(|e| Error::Runtime {
+                    message: format!(
+                        "the commit of version {} to table '{}' succeeded but a commit hook failed: {}",
+                        version, self.name, e
+                    ),
+                })?;
+        }
         Ok(())
     }
 
-    async fn drop_columns(&self, columns: &[&str]) -> Result<()> {
-        self.dataset.get_mut().await?.drop_columns(columns).await?;
-        Ok(())
+    /// The primary key column, required by any policy other than [`DuplicateKeys::Allow`]
+    async fn unique_key_column(&self, policy: DuplicateKeys) -> Result<Option<String>> {
+        if policy == DuplicateKeys::Allow {
+            return Ok(None);
+        }
+        let dataset = self.dataset.get().await?;
+        let column = primary_key::primary_key_from_metadata(&dataset.schema().metadata)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!(
+                    "cannot check the uniqueness of keys, the table '{}' has no primary key",
+                    self.name
+                ),
+            })?;
+        Ok(Some(column))
     }
 
-    async fn list_indices(&self) -> Result<Vec<IndexConfig>> {
+    /// The keys that are already in the table
+    async fn existing_keys(&self, column: &str, keys: &[KeyValue]) -> Result<HashSet<KeyValue>> {
         let dataset = self.dataset.get().await?;
-        let indices = dataset.load_indices().await?;
-        indices.iter().map(|idx| {
-            let mut is_vector = false;
-            let mut columns = Vec::with_capacity(idx.fields.len());
-            for field_id in &idx.fields {
-                let field = dataset.schema().field_by_id(*field_id).ok_or_else(|| Error::Runtime { message: format!("The index with name {} and uuid {} referenced a field with id {} which does not exist in the schema", idx.name, idx.uuid, field_id) })?;
-                if field.data_type().is_nested() {
-                    // Temporary hack to determine if an index is scalar or vector
-                    // Should be removed in https://github.com/lancedb/lance/issues/2039
-                    is_vector = true;
-                }
-                columns.push(field.name.clone());
+        let schema = Schema::from(dataset.schema());
+        let mut existing = HashSet::new();
+        for keys in keys.chunks(KEY_LOOKUP_BATCH_SIZE) {
+            let mut scanner = dataset.scan();
+            scanner.project(&[column])?;
+            scanner.filter(&primary_key::lookup_filter(&schema, column, keys)?)?;
+            let batches = scanner
+                .try_into_stream()
+                .await?
+                .try_collect::<Vec<_>>()
+                .await?;
+            for batch in batches {
+                existing.extend(primary_key::key_values(batch.column(0), column)?);
             }
-            Ok(IndexConfig { index_type: if is_vector { crate::index::IndexType::IvfPq } else { crate::index::IndexType::BTree }, columns })
-        }).collect::<Result<Vec<_>>>()
+        }
+        Ok(existing)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::iter;
-    use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::Arc;
-    use std::time::Duration;
+    /// Apply the duplicate key policy of a write to its data
+    ///
+    /// The data is checked batch by batch as it is written, keeping only the
+    /// keys seen so far.  If `check_table` is true the keys of each batch
+    /// that are not in an earlier batch are looked up in the table,
+    /// otherwise only the keys within the data are checked.  A violation is
+    /// reported in `violations`, see [`write_result`].
+    async fn with_unique_keys(
+        &self,
+        data: Box<dyn RecordBatchReader + Send>,
+        policy: DuplicateKeys,
+        check_table: bool,
+        violations: &ViolationSlot,
+    ) -> Result<Box<dyn RecordBatchReader + Send>> {
+        let Some(column) = self.unique_key_column(policy).await? else {
+            return Ok(data);
+        };
+        let keys = UniqueKeys::new(column.clone(), policy, violations.clone());
+        if !check_table {
+            return Ok(keys.check_reader(data));
+        }
+        let schema = data.schema();
+        let table = self.clone();
+        let batches =
+            futures::stream::try_unfold((data, keys, table), move |(mut data, mut keys, table)| {
+                let column = column.clone();
+                async move {
+                    // The data may block while it is read
+                    let (batch, data) = runtime::spawn_blocking(move || (data.next(), data)).await;
+                    let Some(batch) = batch.transpose()? else {
+                        return Ok(None);
+                    };
+                    let existing = table
+                        .existing_keys(&column, &keys.unseen_keys(&batch)?)
+                        .await?;
+                    let batch = keys.check(batch, &existing)?;
+                    Ok(Some((batch, (data, keys, table))))
+                }
+            });
+        Ok(stream_into_reader(Box::pin(SimpleRecordBatchStream {
+            schema,
+            stream: batches,
+        })))
+    }
 
-    use arrow_array::{
-        Array, BooleanArray, Date32Array, FixedSizeListArray, Float32Array, Float64Array,
-        Int32Array, Int64Array, LargeStringArray, RecordBatch, RecordBatchIterator,
-        RecordBatchReader, StringArray, TimestampMillisecondArray, TimestampNanosecondArray,
-        UInt32Array,
-    };
-    use arrow_data::ArrayDataBuilder;
-    use arrow_schema::{DataType, Field, Schema, TimeUnit};
-    use futures::TryStreamExt;
-    use lance::dataset::{Dataset, WriteMode};
-    use lance::io::{ObjectStoreParams, WrappingObjectStore};
-    use rand::Rng;
-    use tempfile::tempdir;
+    /// Check the data of a write against the constraints of the table as it is read
+    ///
+    /// A violation is reported in `violations`, see [`write_result`].
+    async fn with_constraints(
+        &self,
+        data: Box<dyn RecordBatchReader + Send>,
+        violations: &ViolationSlot,
+    ) -> Result<Box<dyn RecordBatchReader + Send>> {
+        let constraints = Constraints::from_metadata(&self.dataset.get().await?.schema().metadata)?;
+        if constraints.is_empty() {
+            return Ok(data);
+        }
+        Ok(ConstraintChecker::try_new(&constraints)?.check_reader(data, violations.clone()))
+    }
 
-    use crate::connect;
-    use crate::connection::ConnectBuilder;
-    use crate::index::scalar::BTreeIndexBuilder;
-    use crate::query::{ExecutableQuery, QueryBase};
+    /// Check the new values of an update against the constraints of the table
+    async fn check_update_constraints(
+        &self,
+        dataset: &Dataset,
+        schema: &Schema,
+        update: &UpdateBuilder,
+    ) -> Result<()> {
+        let constraints = Constraints::from_metadata(&dataset.schema().metadata)?;
+        let updated = update
+            .columns
+            .iter()
+            .map(|(column, _)| column.clone())
+            .collect::<Vec<_>>();
+        let constraints = constraints.only(&updated);
+        if constraints.is_empty() {
+            return Ok(());
+        }
+        let mut columns = Vec::new();
+        for (column, value) in &update.columns {
+            if constraints.0.contains_key(column) {
+                columns.push((column.clone(), normalize_filter(schema, value)?));
+            }
+        }
+        let filter = update
+            .filter
+            .as_ref()
+            .map(|filter| normalize_filter(schema, filter))
+            .transpose()?;
+        let checker = ConstraintChecker::try_new(&constraints)?;
+        let violations = scan_violations(dataset, &checker, &columns, filter.as_deref()).await?;
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput {
+                message: violation_report(&violations),
+            })
+        }
+    }
 
-    use super::*;
+    /// Call the commit hooks if an operation committed a version after `read_version`
+    ///
+    /// For operations that do not always commit, e.g. compaction with nothing to compact.
+    async fn run_commit_hooks_since(
+        &self,
+        operation: &'static str,
+        read_version: u64,
+    ) -> Result<()> {
+        let version = self.dataset.get().await?.version().version;
+        if version == read_version {
+            return Ok(());
+        }
+        self.run_commit_hooks(operation, version, None).await
+    }
 
-    #[tokio::test]
-    async fn test_open() {
-        let tmp_dir = tempdir().unwrap();
-        let dataset_path = tmp_dir.path().join("test.lance");
+    /// Pin the current version for the duration of a query
+    async fn pin_current_version(&self) -> Result<Option<VersionPin>> {
+        match &self.version_pins {
+            Some(pins) => Ok(Some(pins.pin(self.dataset.get().await?.version().version))),
+            None => Ok(None),
+        }
+    }
 
-        let batches = make_test_batches();
-        Dataset::write(batches, dataset_path.to_str().unwrap(), None)
+    /// Commit a change to the schema metadata (the table properties)
+    async fn commit_schema(
+        &self,
+        operation: &'static str,
+        read_version: u64,
+        schema: lance::datatypes::Schema,
+    ) -> Result<()> {
+        let store_params = match self.store_wrapper.clone() {
+            Some(wrapper) => None::<ObjectStoreParams>.patch_with_store_wrapper(wrapper)?,
+            None => None,
+        };
+        let dataset = Dataset::commit(
+            &self.uri,
+            Operation::Project { schema },
+            Some(read_version),
+            store_params,
+            None,
+        )
+        .await?;
+        let version = dataset.version().version;
+        self.dataset.set_latest(dataset).await;
+        self.run_commit_hooks(operation, version, None).await
+    }
+
+    /// The restrictions of the query interceptor for an operation through this handle
+    async fn restrictions(&self, operation: InterceptedOperation) -> Result<Restrictions> {
+        match &self.query_interceptor {
+            Some(interceptor) => interceptor.intercept(&self.name, operation).await,
+            None => Ok(Restrictions::default()),
+        }
+    }
+
+    /// The masking policies that apply to reads through this handle
+    ///
+    /// These are the policies of the table, unless this handle has unmasked
+    /// access, and the masks of the query interceptor.  Returns None if there
+    /// are none.
+    async fn read_masking_policies(
+        &self,
+        restrictions: &Restrictions,
+    ) -> Result<Option<MaskingPolicies>> {
+        let dataset = self.dataset.get().await?;
+        let mut policies = if self.unmasked {
+            MaskingPolicies::default()
+        } else {
+            MaskingPolicies::from_metadata(&dataset.schema().metadata)?
+        };
+        if !restrictions.masks.is_empty() {
+            let schema = Schema::from(dataset.schema());
+            for (column, policy) in &restrictions.masks {
+                let field = schema
+                    .field_with_name(column)
+                    .map_err(|_| Error::InvalidInput {
+                        message: format!(
+                            "the query interceptor masks '{}' but there is no top level column with that name",
+                            column
+                        ),
+                    })?;
+                policy.validate(field)?;
+                policies.0.insert(column.clone(), policy.clone());
+            }
+        }
+        Ok((!policies.is_empty()).then_some(policies))
+    }
+
+    /// Fail if a query refers to a masked column in its filter or projection
+    async fn check_masked_references(
+        &self,
+        policies: &MaskingPolicies,
+        query: &VectorQuery,
+    ) -> Result<()> {
+        let schema = self.schema().await?;
+        if let Some(filter) = &query.base.filter {
+            policies.check_not_referenced(&filter_columns(&schema, filter)?, "filter")?;
+        }
+        if let Select::Dynamic(columns) = &query.base.select {
+            for (_, expression) in columns {
+                policies
+                    .check_not_referenced(&filter_columns(&schema, expression)?, "projection")?;
+            }
+        }
+        if let Some(columns) = &query.base.distinct_on {
+            policies.check_not_referenced(columns, "distinct")?;
+        }
+        Ok(())
+    }
+
+    /// Limit concurrent queries and writes on this table with the given controller
+    ///
+    /// See [`crate::connection::ConnectBuilder::admission_control`]
+    pub fn with_admission_controller(
+        mut self,
+        admission: Option<Arc<AdmissionController>>,
+    ) -> Self {
+        self.admission = admission;
+        self
+    }
+
+    /// Reserve the memory of queries and index builds from the given pool
+    ///
+    /// See [`crate::connection::ConnectBuilder::memory_pool`]
+    pub fn with_memory_pool(mut self, memory_pool: Option<Arc<MemoryPool>>) -> Self {
+        self.memory_pool = memory_pool;
+        self
+    }
+
+    fn get_table_name(uri: &str) -> Result<String> {
+        let path = Path::new(uri);
+        let name = path
+            .file_stem()
+            .ok_or(Error::TableNotFound {
+                name: uri.to_string(),
+            })?
+            .to_str()
+            .ok_or(Error::InvalidTableName {
+                name: uri.to_string(),
+                reason: "Table name is not valid URL".to_string(),
+            })?;
+        Ok(name.to_string())
+    }
+
+    fn supported_btree_data_type(dtype: &DataType) -> bool {
+        dtype.is_integer()
+            || dtype.is_floating()
+            || matches!(
+                dtype,
+                DataType::Boolean
+                    | DataType::Utf8
+                    | DataType::Time32(_)
+                    | DataType::Time64(_)
+                    | DataType::Date32
+                    | DataType::Date64
+                    | DataType::Timestamp(_, _)
+            )
+    }
+
+    fn supported_vector_data_type(dtype: &DataType) -> bool {
+        match dtype {
+            DataType::FixedSizeList(inner, _) => DataType::is_floating(inner.data_type()),
+            _ => false,
+        }
+    }
+
+    /// Creates a new Table
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - The URI to the table.
+    /// * `name` The Table name
+    /// * `batches` RecordBatch to be saved in the database.
+    /// * `params` - Write parameters.
+    ///
+    /// # Returns
+    ///
+    /// * A [TableImpl] object.
+    pub(crate) async fn create(
+        uri: &str,
+        name: &str,
+        batches: impl RecordBatchReader + Send + 'static,
+        store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
+        params: Option<WriteParams>,
+        read_consistency_interval: Option<std::time::Duration>,
+    ) -> Result<Self> {
+        check_supported_types(&batches.schema())?;
+        let io_metrics =
+            IoMetricsWrapper::new(Arc::new(IoMetrics::new(name))).around(store_wrapper);
+        let mut params = params.unwrap_or_default();
+        let store_params = io_metrics.patch(params.store_params.take())?;
+        params.store_params = Some(store_params.clone());
+
+        let dataset = Dataset::write(batches, uri, Some(params))
+            .await
+            .map_err(|e| match e {
+                lance::Error::DatasetAlreadyExists { .. } => Error::TableAlreadyExists {
+                    name: name.to_string(),
+                },
+                source => Error::Lance { source },
+            })?;
+        Ok(Self {
+            name: name.to_string(),
+            uri: uri.to_string(),
+            dataset: DatasetConsistencyWrapper::new_latest(dataset, read_consistency_interval),
+            store_wrapper: Some(Arc::new(io_metrics.clone())),
+            io_metrics,
+            store_params,
+            read_consistency_interval,
+            admission: None,
+            memory_pool: None,
+            write_stats: Arc::default(),
+            index_builds: Arc::default(),
+            bulk_load: Arc::default(),
+            unmasked: false,
+            version_pins: Some(Arc::default()),
+            snapshot_pin: None,
+            commit_hooks: Vec::new(),
+            audit_context: None,
+            tiering: None,
+            decode_parallelism: None,
+            filter_cache: None,
+            query_interceptor: None,
+            exclude_vectors: false,
+        })
+    }
+
+    pub async fn create_empty(
+        uri: &str,
+        name: &str,
+        schema: SchemaRef,
+        store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
+        params: Option<WriteParams>,
+        read_consistency_interval: Option<std::time::Duration>,
+    ) -> Result<Self> {
+        let batches = RecordBatchIterator::new(vec![], schema);
+        Self::create(
+            uri,
+            name,
+            batches,
+            store_wrapper,
+            params,
+            read_consistency_interval,
+        )
+        .await
+    }
+
+    /// Build any indices declared at creation time, if the table is large enough
+    ///
+    /// See [`crate::connection::CreateTableBuilder::declare_index`].  This
+    /// runs after the data of a write is committed, so it is best-effort: a
+    /// failure is logged, the write still succeeds and the next write tries
+    /// again.
+    async fn build_pending_indices(&self) {
+        if let Err(err) = self.try_build_pending_indices().await {
+            warn!(
+                "LanceDB: failed to build the declared indices of table {}: {}",
+                self.name, err
+            );
+        }
+    }
+
+    async fn try_build_pending_indices(&self) -> Result<()> {
+        let schema = self.schema().await?;
+        let Some(pending) = PendingIndices::from_schema(&schema)? else {
+            return Ok(());
+        };
+        // Once the indices are built this is all a write pays, the indices
+        // are listed from the manifest that is already loaded
+        let existing = self.list_indices().await?;
+        let missing = pending
+            .indices
+            .into_iter()
+            .filter(|declared| !existing.iter().any(|idx| idx.columns == declared.columns))
+            .collect::<Vec<_>>();
+        if missing.is_empty() || self.count_rows(None).await? < pending.threshold {
+            return Ok(());
+        }
+        for declared in missing {
+            info!(
+                "LanceDB: building declared index on {:?} of table {}",
+                declared.columns, self.name
+            );
+            let builder =
+                IndexBuilder::new(Arc::new(self.clone()), declared.columns, declared.index);
+            self.create_index(builder).await?;
+        }
+        Ok(())
+    }
+
+    async fn optimize_indices(&self, options: &OptimizeOptions) -> Result<()> {
+        info!("LanceDB: optimizing indices: {:?}", options);
+        self.dataset
+            .get_mut()
+            .await?
+            .optimize_indices(options)
+            .await?;
+        Ok(())
+    }
+
+    /// Merge new data into this table.
+    pub async fn merge(
+        &mut self,
+        batches: impl RecordBatchReader + Send + 'static,
+        left_on: &str,
+        right_on: &str,
+    ) -> Result<()> {
+        self.dataset
+            .get_mut()
+            .await?
+            .merge(batches, left_on, right_on)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove old versions of the dataset from disk.
+    ///
+    /// # Arguments
+    /// * `older_than` - The duration of time to keep versions of the dataset.
+    /// * `delete_unverified` - Because they may be part of an in-progress
+    ///   transaction, files newer than 7 days old are not deleted by default.
+    ///   If you are sure that there are no in-progress transactions, then you
+    ///   can set this to True to delete all files older than `older_than`.
+    ///
+    /// Versions that are pinned by live query streams, and every version after
+    /// them, are kept regardless of `older_than`.
+    ///
+    /// This calls into [lance::dataset::Dataset::cleanup_old_versions] and
+    /// returns the result.
+    async fn cleanup_old_versions(
+        &self,
+        older_than: Duration,
+        delete_unverified: Option<bool>,
+    ) -> Result<RemovalStats> {
+        let dataset = self.dataset.get_mut().await?;
+        let older_than = self.unpinned_age(&dataset, older_than).await?;
+        Ok(dataset
+            .cleanup_old_versions(older_than, delete_unverified)
+            .await?)
+    }
+
+    /// List what [`Self::cleanup_old_versions`] would remove
+    async fn preview_cleanup(
+        &self,
+        older_than: Duration,
+        delete_unverified: Option<bool>,
+    ) -> Result<PrunePreview> {
+        let dataset = self.dataset.get().await?.clone();
+        let older_than = self.unpinned_age(&dataset, older_than).await?;
+        let (store, base) = self.object_store().await?;
+        preview_prune(
+            &dataset,
+            &store,
+            &base,
+            older_than,
+            delete_unverified.unwrap_or(false),
+        )
+        .await
+    }
+
+    /// Raise `older_than` so that the versions pinned by live query streams,
+    /// and the versions read by shallow clones, are not pruned
+    async fn unpinned_age(&self, dataset: &Dataset, older_than: Duration) -> Result<Duration> {
+        let oldest_pinned = self.version_pins.as_ref().and_then(|pins| pins.oldest());
+        let clones = Clones::from_metadata(&dataset.schema().metadata)?;
+        let oldest_cloned = if clones.0.is_empty() {
+            None
+        } else {
+            let (store, _) = self.object_store().await?;
+            clones.oldest_version(store.inner.as_ref()).await?
+        };
+        let oldest_pinned = match (oldest_pinned, oldest_cloned) {
+            (Some(pinned), Some(cloned)) => Some(pinned.min(cloned)),
+            (pinned, cloned) => pinned.or(cloned),
+        };
+        if let Some(oldest_pinned) = oldest_pinned {
+            let versions = dataset.versions().await?;
+            if let Some(pinned) = versions.iter().find(|v| v.version == oldest_pinned) {
+                // Keep a margin since the cutoff is computed again by lance
+                let age =
+                    chrono::Utc::now() - pinned.timestamp + Duration::try_seconds(60).unwrap();
+                return Ok(older_than.max(age));
+            }
+        }
+        Ok(older_than)
+    }
+
+    /// Compact files in the dataset.
+    ///
+    /// This can be run after making several small appends to optimize the table
+    /// for faster reads.
+    ///
+    /// This calls into [lance::dataset::optimize::compact_files].
+    async fn compact_files(
+        &self,
+        options: CompactionOptions,
+        remap_options: Option<Arc<dyn IndexRemapperOptions>>,
+    ) -> Result<CompactionMetrics> {
+        let mut dataset_mut = self.dataset.get_mut().await?;
+        let metrics = compact_files(&mut dataset_mut, options, remap_options).await?;
+        Ok(metrics)
+    }
+
+    // TODO: why are these individual methods and not some single "get_stats" method?
+    pub async fn count_fragments(&self) -> Result<usize> {
+        Ok(self.dataset.get().await?.count_fragments())
+    }
+
+    pub async fn count_deleted_rows(&self) -> Result<usize> {
+        Ok(self.dataset.get().await?.count_deleted_rows().await?)
+    }
+
+    pub async fn num_small_files(&self, max_rows_per_group: usize) -> Result<usize> {
+        Ok(self
+            .dataset
+            .get()
+            .await?
+            .num_small_files(max_rows_per_group)
+            .await)
+    }
+
+    pub async fn count_indexed_rows(&self, index_uuid: &str) -> Result<Option<usize>> {
+        match self.load_index_stats(index_uuid).await? {
+            Some(stats) => Ok(Some(stats.num_indexed_rows)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn count_unindexed_rows(&self, index_uuid: &str) -> Result<Option<usize>> {
+        match self.load_index_stats(index_uuid).await? {
+            Some(stats) => Ok(Some(stats.num_unindexed_rows)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn load_indices(&self) -> Result<Vec<VectorIndex>> {
+        let dataset = self.dataset.get().await?;
+        let (indices, mf) = futures::try_join!(dataset.load_indices(), dataset.latest_manifest())?;
+        Ok(indices
+            .iter()
+            .map(|i| VectorIndex::new_from_format(&mf, i))
+            .collect())
+    }
+
+    async fn load_index_stats(&self, index_uuid: &str) -> Result<Option<VectorIndexStatistics>> {
+        let index = self
+            .load_indices()
+            .await?
+            .into_iter()
+            .find(|i| i.index_uuid == index_uuid);
+        if index.is_none() {
+            return Ok(None);
+        }
+        let dataset = self.dataset.get().await?;
+        let index_stats = dataset.index_statistics(&index.unwrap().index_name).await?;
+        let index_stats: VectorIndexStatistics = whatever!(
+            serde_json::from_str(&index_stats),
+            "error deserializing index statistics {index_stats}",
+        );
+
+        Ok(Some(index_stats))
+    }
+
+    async fn create_ivf_pq_index(
+        &self,
+        index: IvfPqIndexBuilder,
+        field: &Field,
+        replace: bool,
+    ) -> Result<()> {
+        if !(1..=8).contains(&index.num_bits) {
+            return Err(Error::InvalidInput {
+                message: format!("num_bits must be between 1 and 8, got {}", index.num_bits),
+            });
+        }
+        if !Self::supported_vector_data_type(field.data_type()) {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "An IVF PQ index cannot be created on the column `{}` which has data type {}",
+                    field.name(),
+                    field.data_type()
+                ),
+            });
+        }
+
+        let start = Instant::now();
+        let num_rows = self.count_rows(None).await?;
+        let num_partitions = if let Some(n) = index.num_partitions {
+            n
+        } else {
+            suggested_num_partitions(num_rows)
+        };
+        let num_sub_vectors: u32 = if let Some(n) = index.num_sub_vectors {
+            n
+        } else {
+            match field.data_type() {
+                arrow_schema::DataType::FixedSizeList(_, n) => {
+                    Ok::<u32, Error>(suggested_num_sub_vectors(*n as u32))
+                }
+                _ => Err(Error::Schema {
+                    message: format!("Column '{}' is not a FixedSizeList", field.name()),
+                }),
+            }?
+        };
+        // Fail before training if the sample of the vectors does not fit
+        let dim = match field.data_type() {
+            arrow_schema::DataType::FixedSizeList(_, n) => *n as usize,
+            _ => 0,
+        };
+        let mut reservation = maybe_reserve(&self.memory_pool, MemoryConsumer::IndexBuild);
+        maybe_grow(
+            &mut reservation,
+            ivf_pq_training_bytes(
+                num_rows,
+                dim,
+                num_partitions,
+                index.num_bits,
+                index.sample_rate,
+            ),
+        )?;
+        let mut dataset = self.dataset.get_mut().await?;
+        let lance_idx_params = lance::index::vector::VectorIndexParams::ivf_pq(
+            num_partitions as usize,
+            index.num_bits as u8,
+            num_sub_vectors as usize,
+            false,
+            index.distance_type.into(),
+            index.max_iterations as usize,
+        );
+        dataset
+            .create_index(
+                &[field.name()],
+                IndexType::Vector,
+                None,
+                &lance_idx_params,
+                replace,
+            )
+            .await?;
+        let resolved = index
+            .num_partitions(num_partitions)
+            .num_sub_vectors(num_sub_vectors);
+        self.index_builds.record(
+            field.name(),
+            Index::IvfPq(resolved),
+            num_rows,
+            start.elapsed(),
+        );
+        Ok(())
+    }
+
+    async fn create_auto_index(&self, field: &Field, opts: IndexBuilder) -> Result<()> {
+        if Self::supported_vector_data_type(field.data_type()) {
+            self.create_ivf_pq_index(IvfPqIndexBuilder::default(), field, opts.replace)
+                .await
+        } else if Self::supported_btree_data_type(field.data_type()) {
+            self.create_btree_index(field, &BTreeIndexBuilder::default(), opts.replace)
+                .await
+        } else {
+            Err(Error::InvalidInput {
+                message: format!(
+                    "there are no indices supported for the field `{}` with the data type {}",
+                    field.name(),
+                    field.data_type()
+                ),
+            })
+        }
+    }
+
+    async fn create_btree_index(
+        &self,
+        field: &Field,
+        btree: &BTreeIndexBuilder,
+        replace: bool,
+    ) -> Result<()> {
+        if !Self::supported_btree_data_type(field.data_type()) {
+            return Err(Error::Schema {
+                message: format!(
+                    "A BTree index cannot be created on the field `{}` which has data type {}",
+                    field.name(),
+                    field.data_type()
+                ),
+            });
+        }
+
+        let column = match btree.normalization {
+            Some(normalization) => self.add_normalized_column(field, normalization).await?,
+            None => field.name().clone(),
+        };
+
+        let start = Instant::now();
+        let num_rows = self.count_rows(None).await?;
+        let mut dataset = self.dataset.get_mut().await?;
+        let lance_idx_params = lance::index::scalar::ScalarIndexParams {};
+        dataset
+            .create_index(
+                &[column.as_str()],
+                IndexType::Scalar,
+                None,
+                &lance_idx_params,
+                replace,
+            )
+            .await?;
+        self.index_builds.record(
+            &column,
+            Index::BTree(btree.clone()),
+            num_rows,
+            start.elapsed(),
+        );
+        Ok(())
+    }
+
+    /// Add the hidden column with the normalized values of a string column
+    ///
+    /// Returns the name of the hidden column, see [`normalized`].  An existing
+    /// hidden column is reused.
+    async fn add_normalized_column(
+        &self,
+        field: &Field,
+        normalization: Normalization,
+    ) -> Result<String> {
+        if !matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8) {
+            return Err(Error::Schema {
+                message: format!(
+                    "only string columns can be normalized, the field `{}` has data type {}",
+                    field.name(),
+                    field.data_type()
+                ),
+            });
+        }
+        let hidden = normalized_column_name(field.name(), normalization);
+        let mut dataset = self.dataset.get_mut().await?;
+        let mut normalized = NormalizedColumns::from_metadata(&dataset.schema().metadata)?;
+        if normalized.is_hidden(&hidden) {
+            return Ok(hidden);
+        }
+        if dataset.schema().field(&hidden).is_some() {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "cannot normalize the column '{}', the column '{}' already exists",
+                    field.name(),
+                    hidden
+                ),
+            });
+        }
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![(
+                    hidden.clone(),
+                    format!("{}(`{}`)", normalization.function(), field.name()),
+                )]),
+                None,
+            )
+            .await?;
+        let mut schema = dataset.schema().clone();
+        normalized.0.insert(
+            hidden.clone(),
+            NormalizedColumn {
+                column: field.name().clone(),
+                normalization,
+            },
+        );
+        normalized.apply_to_metadata(&mut schema.metadata)?;
+        let version = dataset.version().version;
+        drop(dataset);
+        self.commit_schema("create_index", version, schema).await?;
+        Ok(hidden)
+    }
+
+    /// Create one btree index per column
+    ///
+    /// Lance indices cover a single column, so this is not an index on the
+    /// composite key.  A filter that combines the columns with `AND` (e.g.
+    /// `tenant_id = 7 AND created_at > 100`) searches each index and
+    /// intersects the matching rows.
+    ///
+    /// Each index is committed as it is built.  If one of them fails, the
+    /// indices already built are removed again, so that either all of the
+    /// columns are indexed or the indices are as they were.
+    async fn create_btree_index_per_column(&self, opts: IndexBuilder) -> Result<()> {
+        let Index::BTree(btree) = &opts.index else {
+            return Err(Error::InvalidInput {
+                message: "only btree indices can be created on several columns, one index per \
+                          column"
+                    .to_string(),
+            });
+        };
+        let schema = self.schema().await?;
+        let mut fields = Vec::with_capacity(opts.columns.len());
+        for column in &opts.columns {
+            let field = schema.field_with_name(column)?;
+            if fields.iter().any(|f: &&Field| f.name() == column) {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the column '{}' appears more than once in the index",
+                        column
+                    ),
+                });
+            }
+            if !Self::supported_btree_data_type(field.data_type()) {
+                return Err(Error::Schema {
+                    message: format!(
+                        "A BTree index cannot be created on the field `{}` which has data type {}",
+                        field.name(),
+                        field.data_type()
+                    ),
+                });
+            }
+            fields.push(field);
+        }
+        let read_version = self.dataset.get().await?.version().version;
+        for field in fields {
+            if let Err(e) = self.create_btree_index(field, btree, opts.replace).await {
+                if let Err(rollback) = self.remove_indices_since(read_version).await {
+                    warn!(
+                        "failed to remove the indices created on the table '{}': {}",
+                        self.name, rollback
+                    );
+                }
+                return Err(e);
+            }
+        }
+        self.run_commit_hooks_since("create_index", read_version)
+            .await
+    }
+
+    /// Remove the indices created since `read_version`, and restore those they replaced
+    async fn remove_indices_since(&self, read_version: u64) -> Result<()> {
+        let dataset = self.dataset.get().await?.clone();
+        let before = dataset.checkout_version(read_version).await?;
+        let (before, now) = futures::try_join!(before.load_indices(), dataset.load_indices())?;
+        let removed_indices = now
+            .iter()
+            .filter(|index| !before.iter().any(|b| b.uuid == index.uuid))
+            .cloned()
+            .collect::<Vec<_>>();
+        if removed_indices.is_empty() {
+            return Ok(());
+        }
+        let new_indices = before
+            .iter()
+            .filter(|b| {
+                !now.iter().any(|index| index.uuid == b.uuid)
+                    && removed_indices
+                        .iter()
+                        .any(|removed| removed.fields == b.fields)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let dataset = Dataset::commit(
+            &self.uri,
+            Operation::CreateIndex {
+                new_indices,
+                removed_indices,
+            },
+            Some(dataset.version().version),
+            Some(self.store_params.clone()),
+            None,
+        )
+        .await?;
+        self.dataset.set_latest(dataset).await;
+        Ok(())
+    }
+
+    /// The normalized filter of a query, including the filter of `as_of`
+    fn query_filter(&self, dataset: &Dataset, query: &VectorQuery) -> Result<Option<String>> {
+        let mut filter = query.base.filter.clone();
+        if let Some(as_of) = &query.base.as_of {
+            let validity = TemporalValidity::from_metadata(&dataset.schema().metadata)?
+                .ok_or_else(|| Error::InvalidInput {
+                    message: format!(
+                        "cannot query the table '{}' as of a time, it has no temporal validity \
+                         columns",
+                        self.name
+                    ),
+                })?;
+            let validity_filter = validity.filter(as_of);
+            filter = Some(match filter {
+                Some(filter) => format!("({}) AND ({})", filter, validity_filter),
+                None => validity_filter,
+            });
+        }
+        // Read the normalized columns of the indices, see [`normalized`]
+        let normalized = NormalizedColumns::from_metadata(&dataset.schema().metadata)?;
+        filter
+            .map(|filter| {
+                normalize_filter(
+                    &Schema::from(dataset.schema()),
+                    &normalized.rewrite_filter(&filter),
+                )
+            })
+            .transpose()
+    }
+
+    async fn generic_query(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<DatasetRecordBatchStream> {
+        let ds_ref = self.dataset.get().await?;
+        let mut scanner: Scanner = ds_ref.scan();
+        let mut nprobes = query.nprobes;
+        let mut refine_factor = query.refine_factor;
+
+        if let Some(query_vector) = query.query_vector.as_ref() {
+            // If there is a vector query, default to limit=10 if unspecified
+            let column = if let Some(col) = query.column.as_ref() {
+                col.clone()
+            } else {
+                // Infer a vector column with the same dimension of the query vector.
+                let arrow_schema = Schema::from(ds_ref.schema());
+                default_vector_column(&arrow_schema, Some(query_vector.len() as i32))?
+            };
+            let field = ds_ref.schema().field(&column).ok_or(Error::Schema {
+                message: format!("Column {} not found in dataset schema", column),
+            })?;
+            if let arrow_schema::DataType::FixedSizeList(f, dim) = field.data_type() {
+                if !f.data_type().is_floating() {
+                    return Err(Error::InvalidInput {
+                        message: format!(
+                            "The data type of the vector column '{}' is not a floating point type",
+                            column
+                        ),
+                    });
+                }
+                if dim != query_vector.len() as i32 {
+                    return Err(Error::InvalidInput {
+                        message: format!(
+                            "The dimension of the query vector does not match with the dimension of the vector column '{}':
+                                query dim={}, expected vector dim={}",
+                            column,
+                            query_vector.len(),
+                            dim,
+                        ),
+                    });
+                }
+            }
+            // A prepared query has resolved the defaults already
+            let defaults = if query.nprobes_set && query.refine_factor_set {
+                None
+            } else {
+                SearchDefaultsMap::from_metadata(&ds_ref.schema().metadata)?
+                    .0
+                    .remove(&column)
+            };
+            if let Some(defaults) = defaults {
+                if !query.nprobes_set {
+                    nprobes = defaults.nprobes;
+                }
+                if !query.refine_factor_set {
+                    refine_factor = defaults.refine_factor;
+                }
+            }
+            let query_vector = query_vector.as_primitive::<Float32Type>();
+            scanner.nearest(
+                &column,
+                query_vector,
+                query.base.limit.unwrap_or(DEFAULT_TOP_K),
+            )?;
+        } else {
+            // If there is no vector query, it's ok to not have a limit
+            scanner.limit(
+                query.base.limit.map(|limit| limit as i64),
+                query.base.offset.map(|offset| offset as i64),
+            )?;
+        }
+        scanner.nprobs(nprobes);
+        scanner.use_index(query.use_index);
+        scanner.prefilter(query.prefilter);
+        scanner.batch_size(options.max_batch_length as usize);
+        match options.decode_parallelism.or(self.decode_parallelism) {
+            Some(0) => {
+                return Err(Error::InvalidInput {
+                    message: "the decode parallelism must be at least 1".to_string(),
+                })
+            }
+            Some(decode_parallelism) => {
+                scanner.batch_readahead(decode_parallelism);
+            }
+            None => {}
+        }
+        match options.fragment_readahead {
+            Some(0) => {
+                return Err(Error::InvalidInput {
+                    message: "the fragment readahead must be at least 1".to_string(),
+                })
+            }
+            Some(fragment_readahead) => {
+                scanner.fragment_readahead(fragment_readahead);
+            }
+            None => {}
+        }
+
+        match &query.base.select {
+            Select::Columns(select) => {
+                scanner.project(select.as_slice())?;
+            }
+            Select::Dynamic(select_with_transform) => {
+                // Check the column references and rewrite function aliases, as for filters
+                let schema = Schema::from(ds_ref.schema());
+                let select_with_transform = select_with_transform
+                    .iter()
+                    .map(|(name, expression)| {
+                        Ok((name.clone(), normalize_filter(&schema, expression)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                scanner.project_with_transform(select_with_transform.as_slice())?;
+            }
+            Select::AllExcept(_) => {
+                let schema = Schema::from(ds_ref.schema());
+                // column_names only returns None for a dynamic selection
+                let columns = query.base.select.column_names(&schema)?.unwrap();
+                scanner.project(columns.as_slice())?;
+            }
+            Select::All => { /* Do nothing */ }
+        }
+        if query.base.with_row_id {
+            scanner.with_row_id();
+        }
+
+        let mut fragments = query.base.fragment_ids.as_ref().map(|ids| {
+            ds_ref
+                .get_fragments()
+                .iter()
+                .filter(|fragment| ids.contains(&(fragment.id() as u64)))
+                .map(|fragment| fragment.metadata().clone())
+                .collect::<Vec<_>>()
+        });
+        if let Some(filter) = self.query_filter(&ds_ref, query)? {
+            if query.query_vector.is_none() && options.use_fragment_statistics {
+                if let Some(pruned) = prune_fragments(&ds_ref, &filter)? {
+                    fragments = Some(match fragments {
+                        Some(fragments) => pruned
+                            .into_iter()
+                            .filter(|p| fragments.iter().any(|f| f.id == p.id))
+                            .collect(),
+                        None => pruned,
+                    });
+                }
+            }
+            scanner
+                .filter(&filter)
+                .map_err(|e| invalid_filter(&filter, e))?;
+        }
+        if let Some(fragments) = fragments {
+            scanner.with_fragments(fragments);
+        }
+
+        if let Some(refine_factor) = refine_factor {
+            scanner.refine(refine_factor);
+        }
+
+        if let Some(distance_type) = query.distance_type {
+            scanner.distance_metric(distance_type.into());
+        }
+        Ok(scanner.try_into_stream().await?)
+    }
+
+    /// Use the distance type the index of the searched column was built with
+    ///
+    /// Returns None if the query does not change.  Fails if the query asks
+    /// for a different distance type and would use the index, since the
+    /// results would be computed with the wrong distance.
+    async fn with_index_distance_type(&self, query: &VectorQuery) -> Result<Option<VectorQuery>> {
+        let Some(query_vector) = &query.query_vector else {
+            return Ok(None);
+        };
+        let dataset = self.dataset.get().await?;
+        let distance_types = IndexDistanceTypes::from_metadata(&dataset.schema().metadata)?;
+        if distance_types.0.is_empty() {
+            return Ok(None);
+        }
+        let column = match &query.column {
+            Some(column) => column.clone(),
+            None => default_vector_column(
+                &Schema::from(dataset.schema()),
+                Some(query_vector.len() as i32),
+            )?,
+        };
+        let Some(field) = dataset.schema().field(&column) else {
+            return Ok(None);
+        };
+        let Some(index_distance_type) = distance_types.get(&column, field.id) else {
+            return Ok(None);
+        };
+        match query.distance_type {
+            None => {
+                let mut query = query.clone();
+                query.distance_type = Some(index_distance_type);
+                Ok(Some(query))
+            }
+            Some(distance_type) if distance_type == index_distance_type || !query.use_index => {
+                Ok(None)
+            }
+            Some(distance_type) => Err(Error::InvalidInput {
+                message: format!(
+                    "the index on the column '{}' was built with the {:?} distance but the \
+                     query uses the {:?} distance, use bypass_vector_index to search with a \
+                     different distance",
+                    column, index_distance_type, distance_type
+                ),
+            }),
+        }
+    }
+
+    /// A handle reading the version of the table that was current at the given time
+    ///
+    /// The handle shares the settings of this one.  See [`QueryBase::version_at`].
+    async fn at_time(&self, time: DateTime<Utc>) -> Result<Self> {
+        let dataset = self.dataset.get().await?.clone();
+        let version = dataset
+            .versions()
+            .await?
+            .into_iter()
+            .filter(|v| v.timestamp <= time)
+            .max_by_key(|v| v.version)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!(
+                    "the table '{}' has no version that was committed at or before {}",
+                    self.name, time
+                ),
+            })?;
+        self.at_version(version.version).await
+    }
+
+    /// A handle reading the given version of the table
+    ///
+    /// The handle shares the settings of this one.
+    async fn at_version(&self, version: u64) -> Result<Self> {
+        let dataset = self.dataset.get().await?.clone();
+        let dataset = dataset.checkout_version(version).await?;
+        let mut table = self.clone();
+        table.dataset = DatasetConsistencyWrapper::new_time_travel(dataset);
+        Ok(table)
+    }
+
+    /// The projection of a query without the columns that are left out by default
+    ///
+    /// These are the vector columns, if they should be left out, the soft
+    /// delete column and the normalized columns of indices.  They are only left out of queries that select all
+    /// columns (or all but some), an explicit selection is always returned as
+    /// is.
+    async fn default_projection(&self, query: &Query) -> Result<Option<Select>> {
+        let exclude_vectors = query
+            .include_vectors
+            .map_or(self.exclude_vectors, |include| !include);
+        let mut excluded = match &query.select {
+            Select::All => Vec::new(),
+            Select::AllExcept(excluded) => excluded.clone(),
+            Select::Columns(_) | Select::Dynamic(_) => return Ok(None),
+        };
+        let schema = self.schema().await?;
+        let hide_deleted = self.soft_delete_filter(query).await?.is_some();
+        let normalized = NormalizedColumns::from_metadata(schema.metadata())?;
+        let hidden = schema
+            .fields()
+            .iter()
+            .filter(|f| {
+                (exclude_vectors && matches!(f.data_type(), DataType::FixedSizeList(_, _)))
+                    || (hide_deleted && f.name() == DELETED_COLUMN)
+                    || normalized.is_hidden(f.name())
+            })
+            .map(|f| f.name())
+            .filter(|name| !excluded.contains(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if hidden.is_empty() {
+            return Ok(None);
+        }
+        excluded.extend(hidden);
+        Ok(Some(Select::AllExcept(excluded)))
+    }
+
+    /// The filter skipping soft deleted rows, unless the query includes them
+    async fn soft_delete_filter(&self, query: &Query) -> Result<Option<&'static str>> {
+        if query.include_deleted || !self.soft_delete().await? {
+            return Ok(None);
+        }
+        Ok(Some(LIVE_FILTER))
+    }
+
+    /// Add nullable columns for a write with schema evolution, see [`evolve`]
+    async fn add_null_columns(&self, fields: Vec<Field>) -> Result<()> {
+        let store_params = match self.store_wrapper.clone() {
+            Some(wrapper) => None::<ObjectStoreParams>.patch_with_store_wrapper(wrapper)?,
+            None => None,
+        };
+        let columns = fields
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        let dataset = self.dataset.get().await?.clone();
+        let dataset = evolve::add_null_columns(&dataset, &self.uri, fields, store_params).await?;
+        let version = dataset.version().version;
+        record_version(version);
+        self.dataset.set_latest(dataset).await;
+        self.run_commit_hooks_with_details("add_columns", version, None, Some(columns.join(", ")))
+            .await
+    }
+
+    /// Record the metadata of a commit, see [`WriteOptions::commit_metadata`]
+    ///
+    /// The commit has already succeeded, so a failure is only logged.
+    pub(crate) async fn record_commit_metadata(
+        &self,
+        version: u64,
+        metadata: &BTreeMap<String, String>,
+    ) {
+        if metadata.is_empty() {
+            return;
+        }
+        let result = match self.object_store().await {
+            Ok((store, base)) => write_commit_metadata(&store, &base, version, metadata).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            warn!(
+                "failed to record the commit metadata of version {} of the table '{}': {}",
+                version, self.name, err
+            );
+        }
+    }
+
+    /// Drop the columns added by [`Self::add_null_columns`] for a write that failed
+    ///
+    /// The write fails with its own error, a failure to drop the columns is
+    /// only logged.
+    async fn drop_added_columns(&self, columns: &[String]) {
+        if columns.is_empty() {
+            return;
+        }
+        let columns = columns.iter().map(String::as_str).collect::<Vec<_>>();
+        if let Err(err) = self.drop_columns(&columns).await {
+            warn!(
+                "failed to drop the columns {:?} added to the table '{}' by a failed write: {}",
+                columns, self.name, err
+            );
+        }
+    }
+
+    /// Mark the rows matching a predicate as deleted or not deleted
+    ///
+    /// Only the rows whose mark changes are updated.  Returns the new version.
+    async fn set_deleted(&self, predicate: &str, deleted: bool) -> Result<u64> {
+        let dataset = self.dataset.get().await?.clone();
+        let schema = Schema::from(dataset.schema());
+        let current = if deleted {
+            LIVE_FILTER.to_string()
+        } else {
+            format!("`{}` = true", DELETED_COLUMN)
+        };
+        let filter = normalize_filter(&schema, &format!("({}) AND {}", predicate, current))?;
+        let updated = LanceUpdateBuilder::new(Arc::new(dataset))
+            .update_where(&filter)?
+            .set(DELETED_COLUMN, if deleted { "true" } else { "false" })?
+            .build()?
+            .execute()
+            .await?;
+        let version = updated.version().version;
+        record_version(version);
+        self.dataset.set_latest(updated.as_ref().clone()).await;
+        Ok(version)
+    }
+
+    /// Add the vector column to the projection of a query with reference vectors
+    ///
+    /// Returns the modified query and the step that adds the distance columns.
+    async fn with_reference_vectors(
+        &self,
+        query: &VectorQuery,
+    ) -> Result<(VectorQuery, ReferenceDistances)> {
+        let dim = query.query_vector.as_ref().map(|v| v.len() as i32);
+        let column = match query.column.as_ref() {
+            Some(column) => column.clone(),
+            None => default_vector_column(&*self.schema().await?, dim)?,
+        };
+        let mut query = query.clone();
+        query.column = Some(column.clone());
+        let added = select_distinct_columns(&mut query.base.select, &[column.clone()]);
+        let references = ReferenceDistances {
+            vector_column: column,
+            references: std::mem::take(&mut query.reference_vectors),
+            distance_type: query.distance_type.unwrap_or(DistanceType::L2),
+            drop_vector_column: !added.is_empty(),
+        };
+        Ok((query, references))
+    }
+
+    /// Remove the lazy blob columns from the projection of a query
+    ///
+    /// Returns the modified query, the lazy columns and the version that is
+    /// read, if any of the lazy columns are selected.
+    async fn with_lazy_blobs(
+        &self,
+        query: &VectorQuery,
+        masking: Option<&MaskingPolicies>,
+    ) -> Result<Option<(VectorQuery, Vec<String>, u64)>> {
+        let Some(columns) = &query.base.lazy_blobs else {
+            return Ok(None);
+        };
+        if let Some(policies) = masking {
+            // The references would bypass the masking
+            policies.check_not_referenced(columns, "lazy_blobs")?;
+        }
+        let dataset = self.dataset.get().await?;
+        let mut query = query.clone();
+        let lazy = plan_lazy_blobs(&Schema::from(dataset.schema()), &mut query, columns)?;
+        if lazy.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((query, lazy, dataset.version().version)))
+    }
+
+    /// Remove the late materialized columns from the projection of a query
+    ///
+    /// Returns the modified query and the step that reads the late columns, if
+    /// any of the late columns are selected.
+    async fn with_late_materialization(
+        &self,
+        query: &VectorQuery,
+    ) -> Result<Option<(VectorQuery, LateMaterialization)>> {
+        let Some(columns) = &query.base.late_materialization else {
+            return Ok(None);
+        };
+        let dataset = Arc::new(self.dataset.get().await?.clone());
+        let mut query = query.clone();
+        Ok(LateMaterialization::plan(dataset, &mut query, columns)?.map(|late| (query, late)))
+    }
+
+    /// Whether the query is a prefiltered vector search that can use the filter cache
+    fn uses_filter_cache(&self, query: &VectorQuery) -> bool {
+        self.filter_cache.is_some()
+            && query.query_vector.is_some()
+            && query.prefilter
+            && query.base.filter.is_some()
+            && query.base.as_of.is_none()
+            && !matches!(query.base.select, Select::Dynamic(_))
+    }
+
+    /// Run a prefiltered vector search over the cached rows of its filter
+    ///
+    /// Returns None if the filter selects too many rows to be searched this
+    /// way, the query should then be run as usual.
+    async fn filter_cached_query(
+        &self,
+        query: &VectorQuery,
+    ) -> Result<Option<SendableRecordBatchStream>> {
+        // Checked by the caller
+        let cache = self.filter_cache.as_ref().unwrap();
+        let query_vector = query.query_vector.as_ref().unwrap();
+        let dataset = self.dataset.get().await?.clone();
+        let version = dataset.version().version;
+        let schema = Schema::from(dataset.schema());
+        let filter = normalize_filter(&schema, query.base.filter.as_ref().unwrap())?;
+        let rows = match cache.get(&filter, version) {
+            Some(rows) => rows,
+            None => {
+                let rows = evaluate_filter(&dataset, &filter, cache.max_rows()).await?;
+                cache.insert(&filter, version, rows.clone());
+                rows
+            }
+        };
+        let CachedRows::Rows(row_ids) = rows else {
+            return Ok(None);
+        };
+        let column = match query.column.as_ref() {
+            Some(column) => column.clone(),
+            None => default_vector_column(&schema, Some(query_vector.len() as i32))?,
+        };
+        let selected = match &query.base.select {
+            Select::All => None,
+            select => select.column_names(&schema)?,
+        };
+        let search = RowSearch {
+            vector_column: &column,
+            query_vector: query_vector.as_ref(),
+            distance_type: query.distance_type.unwrap_or(DistanceType::L2),
+            columns: selected.as_deref(),
+            limit: query.base.limit.unwrap_or(DEFAULT_TOP_K),
+            with_row_id: query.base.with_row_id,
+        };
+        let batch = search.execute(&dataset, &row_ids).await?;
+        Ok(Some(Box::pin(SimpleRecordBatchStream {
+            schema: batch.schema(),
+            stream: futures::stream::once(async move { Ok(batch) }),
+        })))
+    }
+
+    /// Run a vector query and re-score the results with the original vectors
+    ///
+    /// The vector column is added to the projection (if it is not already selected)
+    /// so that the exact distances can be computed.
+    async fn rescored_query(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        // Checked by the caller
+        let query_vector = query.query_vector.as_ref().unwrap();
+        let column = match query.column.as_ref() {
+            Some(column) => column.clone(),
+            None => default_vector_column(&*self.schema().await?, Some(query_vector.len() as i32))?,
+        };
+
+        let mut query = query.clone();
+        query.column = Some(column.clone());
+        let drop_vector_column = match &mut query.base.select {
+            Select::All => false,
+            Select::AllExcept(excluded) => {
+                let position = excluded.iter().position(|c| c == &column);
+                if let Some(position) = position {
+                    excluded.remove(position);
+                }
+                position.is_some()
+            }
+            Select::Columns(columns) => {
+                let missing = !columns.contains(&column);
+                if missing {
+                    columns.push(column.clone());
+                }
+                missing
+            }
+            Select::Dynamic(columns) => {
+                let missing = !columns.iter().any(|(name, _)| name == &column);
+                if missing {
+                    columns.push((column.clone(), column.clone()));
+                }
+                missing
+            }
+        };
+
+        let stream: SendableRecordBatchStream = self.generic_query(&query, options).await?.into();
+        let schema = stream.schema();
+        let mut reservation = maybe_reserve(&self.memory_pool, MemoryConsumer::Query);
+        let batches = collect_reserved(stream, &mut reservation).await?;
+        let batch = rescore_batches(
+            schema,
+            &batches,
+            &column,
+            query_vector.as_ref(),
+            query.distance_type.unwrap_or(DistanceType::L2),
+            drop_vector_column,
+        )?;
+        Ok(Box::pin(SimpleRecordBatchStream {
+            schema: batch.schema(),
+            stream: futures::stream::once(async move { Ok(batch) }),
+        }))
+    }
+
+    /// Run a plain query, keeping only the first row of each distinct key
+    async fn distinct_plain_query(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        // Checked by the caller
+        let columns = query.base.distinct_on.clone().unwrap();
+        let mut query = query.clone();
+        let limit = query.base.limit.take();
+        let dropped = select_distinct_columns(&mut query.base.select, &columns);
+        let stream = self.generic_query(&query, options).await?.into();
+        distinct_stream(stream, columns, limit, dropped)
+    }
+
+    /// Run a query without a vector, sorting the results
+    ///
+    /// See [`crate::query::order`] for how the first rows are found.
+    async fn ordered_query(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        if query.base.distinct_on.is_some() {
+            return Err(Error::InvalidInput {
+                message: "order_by can not be combined with distinct_on".to_string(),
+            });
+        }
+        let order_by = query.base.order_by.clone();
+        check_order_columns(&*self.schema().await?, &order_by)?;
+        let mut query = query.clone();
+        let limit = query.base.limit.take();
+        let columns = order_by
+            .iter()
+            .map(|(column, _)| column.clone())
+            .collect::<Vec<_>>();
+        let dropped = select_distinct_columns(&mut query.base.select, &columns);
+
+        let bound = match limit {
+            Some(limit) if options.use_fragment_statistics => {
+                let dataset = self.dataset.get().await?;
+                let (column, order) = &order_by[0];
+                order_bound(&dataset, column, *order, limit)?
+            }
+            _ => None,
+        };
+        let mut sorted = None;
+        if let Some(bound) = bound {
+            let mut bounded = query.clone();
+            bounded.base.filter = and_filters(bounded.base.filter.as_deref(), Some(&bound));
+            let rows = self
+                .collect_ordered(&bounded, options.clone(), &order_by, limit)
+                .await?;
+            // Otherwise rows outside of the bound may be among the first rows
+            if limit.map_or(false, |limit| rows.seen() >= limit) {
+                sorted = Some(rows.sorted()?);
+            }
+        }
+        let batch = match sorted {
+            Some(batch) => batch,
+            None => self
+                .collect_ordered(&query, options, &order_by, limit)
+                .await?
+                .sorted()?,
+        };
+        let batch = drop_columns(&batch, &dropped)?;
+        Ok(Box::pin(SimpleRecordBatchStream {
+            schema: batch.schema(),
+            stream: futures::stream::once(async move { Ok(batch) }),
+        }))
+    }
+
+    async fn collect_ordered(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+        order_by: &[(String, SortOrder)],
+        limit: Option<usize>,
+    ) -> Result<OrderedRows> {
+        let mut stream: SendableRecordBatchStream =
+            self.generic_query(query, options).await?.into();
+        let mut rows = OrderedRows::new(stream.schema(), order_by.to_vec(), limit)
+            .with_reservation(maybe_reserve(&self.memory_pool, MemoryConsumer::Query));
+        while let Some(batch) = stream.try_next().await? {
+            rows.push(batch)?;
+        }
+        Ok(rows)
+    }
+
+    /// Run a vector query, keeping only the nearest row of each distinct key
+    ///
+    /// The search is run with more candidates than the limit.  If there are not
+    /// enough distinct keys among the candidates the search is repeated with
+    /// twice as many candidates.
+    async fn distinct_vector_query(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        // Checked by the caller
+        let columns = query.base.distinct_on.clone().unwrap();
+        let limit = query.base.limit.unwrap_or(DEFAULT_TOP_K);
+        let mut query = query.clone();
+        let dropped = select_distinct_columns(&mut query.base.select, &columns);
+        let mut num_candidates = limit.max(1) * DISTINCT_OVERSAMPLE;
+        loop {
+            query.base.limit = Some(num_candidates);
+            let stream = if query.rescore {
+                self.rescored_query(&query, options.clone()).await?
+            } else {
+                self.generic_query(&query, options.clone()).await?.into()
+            };
+            let schema = stream.schema();
+            let mut reservation = maybe_reserve(&self.memory_pool, MemoryConsumer::Query);
+            let batches = collect_reserved(stream, &mut reservation).await?;
+            let batch = nearest_first(arrow::compute::concat_batches(&schema, &batches)?)?;
+            let distinct = Deduplicator::new(columns.clone()).filter(&batch)?;
+            // Fewer results than candidates means every row was considered
+            if distinct.num_rows() >= limit || batch.num_rows() < num_candidates {
+                let distinct =
+                    drop_columns(&distinct.slice(0, distinct.num_rows().min(limit)), &dropped)?;
+                return Ok(Box::pin(SimpleRecordBatchStream {
+                    schema: distinct.schema(),
+                    stream: futures::stream::once(async move { Ok(distinct) }),
+                }));
+            }
+            num_candidates *= 2;
+        }
+    }
+}
+
+/// Hard link local files, copying the files that can not be linked
+///
+/// e.g. when the target is on another file system.
+fn link_files(files: &[(object_store::path::Path, object_store::path::Path)]) -> Result<()> {
+    let fs = object_store::local::LocalFileSystem::new();
+    for (from, to) in files {
+        let from = fs.path_to_filesystem(from)?;
+        let to = fs.path_to_filesystem(to)?;
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if std::fs::hard_link(&from, &to).is_err() {
+            std::fs::copy(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+fn optimize_action_name(action: &OptimizeAction) -> &'static str {
+    match action {
+        OptimizeAction::All => "all",
+        OptimizeAction::Compact { .. } => "compact",
+        OptimizeAction::Prune { .. } => "prune",
+        OptimizeAction::Index(_) => "index",
+    }
+}
+
+#[async_trait::async_trait]
+impl TableInternal for NativeTable {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_native(&self) -> Option<&NativeTable> {
+        Some(self)
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    async fn version(&self) -> Result<u64> {
+        Ok(self.dataset.get().await?.version().version)
+    }
+
+    async fn list_versions(&self) -> Result<Vec<Version>> {
+        let mut versions = self.dataset.get().await?.versions().await?;
+        let (store, base) = self.object_store().await?;
+        let mut commit_metadata = read_commit_metadata(&store, &base).await?;
+        for version in versions.iter_mut() {
+            if let Some(metadata) = commit_metadata.remove(&version.version) {
+                version.metadata.extend(metadata);
+            }
+        }
+        Ok(versions)
+    }
+
+    async fn checkout(&self, version: u64) -> Result<()> {
+        self.dataset.as_time_travel(version).await
+    }
+
+    async fn checkout_latest(&self) -> Result<()> {
+        self.dataset
+            .as_latest(self.read_consistency_interval)
+            .await?;
+        self.dataset.reload().await
+    }
+
+    fn write_stats(&self) -> Result<WriteStats> {
+        Ok(self.write_stats.stats())
+    }
+
+    fn io_stats(&self) -> Result<IoStats> {
+        Ok(self.io_metrics.metrics().stats())
+    }
+
+    async fn tiering_report(&self) -> Result<TieringReport> {
+        let (store, base) = self.object_store().await?;
+        let data_dir = base.child("data");
+        let dataset = self.dataset.get().await?;
+        let fragments = dataset
+            .get_fragments()
+            .iter()
+            .map(|fragment| {
+                let files = fragment
+                    .metadata()
+                    .files
+                    .iter()
+                    .map(|file| data_dir.child(file.path.as_str()))
+                    .collect();
+                (fragment.id() as u64, files)
+            })
+            .collect();
+        match &self.tiering {
+            Some(tiering) => Ok(tiering.report(fragments).await),
+            None => Ok(TieringReport::untiered(fragments, store.is_local())),
+        }
+    }
+
+    async fn ids_of_rows(&self, row_ids: &[u64]) -> Result<Vec<KeyValue>> {
+        let dataset = self.dataset.get().await?;
+        let column = primary_key::primary_key_from_metadata(&dataset.schema().metadata)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!(
+                    "the table '{}' has no primary key, see Table::set_primary_key",
+                    self.name
+                ),
+            })?;
+        let projection = dataset.schema().project(&[column.as_str()])?;
+        let batch = dataset.take_rows(row_ids, &projection).await?;
+        primary_key::key_values(batch.column(0), &column)
+    }
+
+    async fn scan_fragments(&self, version: Option<u64>) -> Result<(u64, Vec<u64>)> {
+        let dataset = match version {
+            Some(version) => self.at_version(version).await?.dataset.get().await?.clone(),
+            None => self.dataset.get().await?.clone(),
+        };
+        let mut fragments = dataset
+            .get_fragments()
+            .iter()
+            .map(|fragment| fragment.id() as u64)
+            .collect::<Vec<_>>();
+        fragments.sort_unstable();
+        Ok((dataset.version().version, fragments))
+    }
+
+    fn filter_cache_metrics(&self) -> Result<Option<FilterCacheMetrics>> {
+        Ok(self.filter_cache.as_ref().map(|cache| cache.metrics()))
+    }
+
+    async fn statistics(&self) -> Result<TableStatistics> {
+        let dataset = self.dataset.get().await?;
+        let schema = Arc::new(Schema::from(dataset.schema()));
+        let num_rows = dataset.count_rows().await?;
+        let fragments = dataset.get_fragments();
+        let fragment_rows =
+            futures::future::try_join_all(fragments.iter().map(|f| f.count_rows())).await?;
+
+        let mut scanner = dataset.scan();
+        scanner.limit(Some(SAMPLE_ROWS as i64), None)?;
+        let sample = scanner
+            .try_into_stream()
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let sampled_rows = sample.iter().map(|b| b.num_rows()).sum::<usize>();
+        let column_bytes = schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let bytes = if sampled_rows == 0 {
+                    field.data_type().primitive_width().unwrap_or_default() as f64
+                } else {
+                    sample
+                        .iter()
+                        .map(|b| b.column(i).get_array_memory_size())
+                        .sum::<usize>() as f64
+                        / sampled_rows as f64
+                };
+                (field.name().clone(), bytes)
+            })
+            .collect();
+        drop(dataset);
+
+        let mut indices = Vec::new();
+        for index in self.load_indices().await? {
+            let [column] = index.columns.as_slice() else {
+                continue;
+            };
+            let metadata = self.index_metadata(column).await?;
+            let unindexed_rows = self
+                .count_unindexed_rows(&index.index_uuid)
+                .await?
+                .unwrap_or_default();
+            indices.push(IndexStatistics {
+                columns: index.columns.clone(),
+                index_type: if metadata.index_type == "IVF_PQ" {
+                    crate::index::IndexType::IvfPq
+                } else {
+                    crate::index::IndexType::BTree
+                },
+                num_partitions: metadata.partitions.map(|p| p.num_partitions),
+                unindexed_rows,
+            });
+        }
+        Ok(TableStatistics {
+            schema,
+            num_rows,
+            fragment_rows,
+            column_bytes,
+            indices,
+        })
+    }
+
+    async fn verify(&self) -> Result<IntegrityReport> {
+        let _permit = maybe_acquire(&self.admission, OperationKind::Query).await?;
+        let (store, base) = self.object_store().await?;
+        let dataset = self.dataset.get().await?;
+        verify_dataset(&dataset, &store, &base).await
+    }
+
+    async fn repair(&self, options: RepairOptions) -> Result<RepairReport> {
+        self.dataset.ensure_mutable().await?;
+        let mut report = RepairReport {
+            integrity: self.verify().await?,
+            ..Default::default()
+        };
+        let (store, base) = self.object_store().await?;
+        let permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+
+        let broken = report
+            .integrity
+            .problems
+            .iter()
+            .any(|problem| is_data_problem(problem.kind));
+        if options.rollback && broken {
+            let dataset = self.dataset.get().await?.clone();
+            let current = dataset.version().version;
+            let versions = dataset.versions().await?;
+            for version in versions.iter().rev().filter(|v| v.version < current) {
+                // A version with an unreadable manifest is not a candidate either
+                let Ok(mut candidate) = dataset.checkout_version(version.version).await else {
+                    continue;
+                };
+                let integrity = verify_dataset(&candidate, &store, &base).await?;
+                if !integrity.problems.iter().any(|p| is_data_problem(p.kind)) {
+                    candidate.restore().await?;
+                    report.restored_version = Some(version.version);
+                    break;
+                }
+            }
+            if report.restored_version.is_none() {
+                return Err(Error::Runtime {
+                    message: "no version of the table is free of missing or corrupt data"
+                        .to_string(),
+                });
+            }
+            self.dataset.reload().await?;
+        }
+
+        if options.remove_orphaned_files {
+            let dataset = self.dataset.get().await?.clone();
+            let orphaned =
+                find_orphaned_files(&dataset, &store, &base, options.orphan_grace_period).await?;
+            for file in orphaned {
+                store.inner.delete(&file.location).await?;
+                report.bytes_removed += file.size as u64;
+                report.removed_files.push(file.location.to_string());
+            }
+        }
+        drop(permit);
+        if report.restored_version.is_some() {
+            let version = self.dataset.get().await?.version().version;
+            self.run_commit_hooks("restore", version, None).await?;
+        }
+
+        if options.rebuild_indices {
+            for index in self.load_indices().await? {
+                let unreadable = {
+                    let dataset = self.dataset.get().await?;
+                    dataset.index_statistics(&index.index_name).await.is_err()
+                };
+                if !unreadable {
+                    continue;
+                }
+                let parameters = index
+                    .columns
+                    .first()
+                    .and_then(|column| self.index_builds.get(column))
+                    .map(|build| build.parameters)
+                    .unwrap_or(Index::Auto);
+                let builder =
+                    IndexBuilder::new(Arc::new(self.clone()), index.columns.clone(), parameters);
+                self.create_index(builder).await?;
+                report.rebuilt_indices.push(index.index_name);
+            }
+        }
+        Ok(report)
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.reembed",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn reembed(&self, reembed: ReembedBuilder) -> Result<ReembedReport> {
+        let start = Instant::now();
+        let permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let previous_version = dataset.version().version;
+
+        // Remember how the index of the column was built to build it again
+        // for the new embeddings
+        let index = self
+            .load_indices()
+            .await?
+            .into_iter()
+            .find(|index| index.columns == [reembed.column.clone()])
+            .map(|index| {
+                let parameters = self
+                    .index_builds
+                    .get(&reembed.column)
+                    .map(|build| build.parameters)
+                    .unwrap_or(Index::Auto);
+                (index, parameters)
+            });
+
+        let (reembedded, fragments_resumed, rows) =
+            reembed_fragments(&dataset, &self.uri, &reembed, self.store_params.clone()).await?;
+        let version = reembedded.version().version;
+        record_version(version);
+        self.dataset.set_latest(reembedded).await;
+        drop(permit);
+
+        let target = reembed.target_column().to_string();
+        let mut rebuilt_indices = Vec::new();
+        if let Some((index, parameters)) = index {
+            let builder =
+                IndexBuilder::new(Arc::new(self.clone()), vec![target.clone()], parameters);
+            if let Err(err) = self.create_index(builder).await {
+                // Roll back rather than leave the new embeddings without an index
+                let mut previous = dataset.checkout_version(previous_version).await?;
+                previous.restore().await?;
+                self.dataset.reload().await?;
+                let version = self.dataset.get().await?.version().version;
+                self.run_commit_hooks("restore", version, None).await?;
+                return Err(Error::Runtime {
+                    message: format!(
+                        "the re-embedding was rolled back, index '{}' could not be rebuilt: {}",
+                        index.index_name, err
+                    ),
+                });
+            }
+            rebuilt_indices.extend(
+                self.load_indices()
+                    .await?
+                    .into_iter()
+                    .filter(|index| index.columns == [target.clone()])
+                    .map(|index| index.index_name),
+            );
+        }
+        let version = self.dataset.get().await?.version().version;
+        record_write(&self.name, "reembed", Some(rows), start.elapsed());
+        self.run_commit_hooks_with_details("reembed", version, Some(rows), Some(target))
+            .await?;
+        Ok(ReembedReport {
+            previous_version,
+            version,
+            fragments_resumed,
+            rows,
+            rebuilt_indices,
+        })
+    }
+
+    async fn index_metadata(&self, column: &str) -> Result<IndexMetadata> {
+        let index = self
+            .load_indices()
+            .await?
+            .into_iter()
+            .find(|i| i.columns.iter().any(|c| c == column))
+            .ok_or_else(|| Error::IndexNotFound {
+                name: column.to_string(),
+            })?;
+        let dataset = self.dataset.get().await?;
+        let statistics = dataset.index_statistics(&index.index_name).await?;
+        let statistics: serde_json::Value = whatever!(
+            serde_json::from_str(&statistics),
+            "error deserializing index statistics {statistics}",
+        );
+        let is_vector = self
+            .schema()
+            .await?
+            .field_with_name(column)?
+            .data_type()
+            .is_nested();
+        let mut metadata = IndexMetadata::new(
+            index.index_name,
+            index.index_uuid,
+            index.columns,
+            is_vector,
+            statistics,
+        );
+        metadata.table_version = dataset.version().version;
+        metadata.num_rows = dataset.count_rows().await?;
+        metadata.build = self.index_builds.get(column);
+        Ok(metadata)
+    }
+
+    async fn copy_to(&self, name: &str) -> Result<Arc<dyn TableInternal>> {
+        validate_table_name(name)?;
+        let _permit = maybe_acquire(&self.admission, OperationKind::Query).await?;
+        let (path, query) = match self.uri.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (self.uri.as_str(), None),
+        };
+        let path = path.trim_end_matches(['/', '\\']);
+        let parent = &path[..path.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0)];
+        let mut uri = format!("{}{}.lance", parent, name);
+        if let Some(query) = query {
+            uri.push('?');
+            uri.push_str(query);
+        }
+
+        let dataset = self.dataset.get().await?.clone();
+        let (store, source) = self.object_store().await?;
+        let (_, target) = ObjectStore::from_uri_and_params(&uri, &self.store_params).await?;
+        if store.inner.list(Some(&target)).try_next().await?.is_some() {
+            return Err(Error::TableAlreadyExists {
+                name: name.to_string(),
+            });
+        }
+
+        let fragments = dataset
+            .get_fragments()
+            .iter()
+            .map(|fragment| fragment.metadata().clone())
+            .collect::<Vec<_>>();
+        let mut schema = dataset.schema().clone();
+        Clones::remove_from_metadata(&mut schema.metadata);
+        if store.is_local() {
+            // Lance reads local files directly, not through the wrappers of
+            // the store, so the clone has its own links to the files
+            let files = fragments
+                .iter()
+                .flat_map(|fragment| &fragment.files)
+                .map(|file| {
+                    (
+                        source.child("data").child(file.path.as_str()),
+                        target.child("data").child(file.path.as_str()),
+                    )
+                })
+                .collect::<Vec<_>>();
+            runtime::spawn_blocking(move || link_files(&files)).await?;
+        } else {
+            // The clone reads the data files from the sources, the source keeps
+            // the cloned version for as long as the clone exists
+            ClonedFrom::from_metadata(&schema.metadata)?
+                .clone_of(&source)
+                .apply_to_metadata(&mut schema.metadata)?;
+            let mut source_schema = dataset.schema().clone();
+            let mut clones = Clones::from_metadata(&source_schema.metadata)?;
+            clones
+                .0
+                .insert(target.to_string(), dataset.version().version);
+            clones.apply_to_metadata(&mut source_schema.metadata)?;
+            self.commit_schema("clone", dataset.version().version, source_schema)
+                .await?;
+        }
+        // The deletion files of every version are copied, they are small
+        let deletions = store
+            .inner
+            .list(Some(&source.child("_deletions")))
+            .try_collect::<Vec<_>>()
+            .await?;
+        for deletion in deletions {
+            let Some(name) = deletion.location.filename() else {
+                continue;
+            };
+            store
+                .inner
+                .copy(&deletion.location, &target.child("_deletions").child(name))
+                .await?;
+        }
+
+        Dataset::commit(
+            &uri,
+            Operation::Overwrite { fragments, schema },
+            None,
+            Some(self.store_params.clone()),
+            None,
+        )
+        .await?;
+        let cloned = Self::open_with_params(
+            &uri,
+            name,
+            self.io_metrics.inner(),
+            None,
+            self.read_consistency_interval,
+        )
+        .await?;
+        let table = Self {
+            name: cloned.name,
+            uri: cloned.uri,
+            dataset: cloned.dataset,
+            store_wrapper: cloned.store_wrapper,
+            io_metrics: cloned.io_metrics,
+            store_params: cloned.store_params,
+            write_stats: Arc::default(),
+            index_builds: Arc::default(),
+            bulk_load: Arc::default(),
+            version_pins: self.version_pins.as_ref().map(|_| Arc::default()),
+            snapshot_pin: None,
+            ..self.clone()
+        };
+        let version = table.dataset.get().await?.version().version;
+        table.run_commit_hooks("create", version, None).await?;
+        Ok(Arc::new(table))
+    }
+
+    async fn snapshot(&self) -> Result<Arc<dyn TableInternal>> {
+        let dataset = self.dataset.get().await?.clone();
+        let snapshot_pin = self
+            .version_pins
+            .as_ref()
+            .map(|pins| Arc::new(pins.pin(dataset.version().version)));
+        Ok(Arc::new(Self {
+            dataset: DatasetConsistencyWrapper::new_time_travel(dataset),
+            snapshot_pin,
+            ..self.clone()
+        }))
+    }
+
+    async fn restore(&self) -> Result<()> {
+        let version =
+            self.dataset
+                .time_travel_version()
+                .await
+                .ok_or_else(|| Error::InvalidInput {
+                    message: "you must run checkout before running restore".to_string(),
+                })?;
+        {
+            // Use get_mut_unchecked as restore is the only "write" operation that is allowed
+            // when the table is in time travel mode.
+            // Also, drop the guard after .restore because as_latest will need it
+            let mut dataset = self.dataset.get_mut_unchecked().await?;
+            debug_assert_eq!(dataset.version().version, version);
+            dataset.restore().await?;
+        }
+        self.dataset
+            .as_latest(self.read_consistency_interval)
+            .await?;
+        let version = self.dataset.get().await?.version().version;
+        self.run_commit_hooks("restore", version, None).await
+    }
+
+    async fn schema(&self) -> Result<SchemaRef> {
+        let lance_schema = self.dataset.get().await?.schema().clone();
+        Ok(Arc::new(Schema::from(&lance_schema)))
+    }
+
+    async fn count_rows(&self, filter: Option<String>) -> Result<usize> {
+        let restrictions = self.restrictions(InterceptedOperation::Query).await?;
+        let masking = self.read_masking_policies(&restrictions).await?;
+        if let (Some(filter), Some(policies)) = (&filter, masking) {
+            let schema = self.schema().await?;
+            policies.check_not_referenced(&filter_columns(&schema, filter)?, "filter")?;
+        }
+        let filter = and_filters(filter.as_deref(), restrictions.filter.as_deref());
+        let dataset = self.dataset.get().await?;
+        let filter = if soft_delete::is_enabled(&dataset.schema().metadata) {
+            and_filters(filter.as_deref(), Some(LIVE_FILTER))
+        } else {
+            filter
+        };
+        if let Some(filter) = filter {
+            let filter = normalize_filter(&Schema::from(dataset.schema()), &filter)?;
+            let mut scanner = dataset.scan();
+            scanner
+                .filter(&filter)
+                .map_err(|e| invalid_filter(&filter, e))?;
+            Ok(scanner.count_rows().await? as usize)
+        } else {
+            Ok(dataset.count_rows().await?)
+        }
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.add",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty, rows = tracing::field::Empty)
+    )]
+    async fn add(
+        &self,
+        add: AddDataBuilder<NoData>,
+        data: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<()> {
+        let start = Instant::now();
+        check_supported_types(&data.schema())?;
+        let bulk_load = add.write_options.bulk_load;
+        if bulk_load && matches!(add.mode, AddDataMode::Overwrite) {
+            return Err(Error::InvalidInput {
+                message: "a bulk load can only append to a table".to_string(),
+            });
+        }
+        let schema_evolution = add.write_options.schema_evolution;
+        if bulk_load && schema_evolution != Evolve::Strict {
+            return Err(Error::InvalidInput {
+                message: "schema evolution can not be combined with a bulk load".to_string(),
+            });
+        }
+        let commit_metadata = add.write_options.commit_metadata;
+        if bulk_load && !commit_metadata.is_empty() {
+            return Err(Error::InvalidInput {
+                message: "commit metadata can not be recorded by a bulk load, the data is \
+                          committed by finish_bulk_load"
+                    .to_string(),
+            });
+        }
+        let lance_params = add.write_options.lance_write_params.unwrap_or(WriteParams {
+            mode: match add.mode {
+                AddDataMode::Append => WriteMode::Append,
+                AddDataMode::Overwrite => WriteMode::Overwrite,
+            },
+            ..Default::default()
+        });
+
+        // patch the params with the store wrapper of the table
+        let mut lance_params = lance_params;
+        lance_params.store_params = Some(self.io_metrics.patch(lance_params.store_params)?);
+
+        self.dataset.ensure_mutable().await?;
+
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        let violations = ViolationSlot::default();
+        let data = self
+            .with_unique_keys(
+                data,
+                add.duplicate_keys,
+                matches!(lance_params.mode, WriteMode::Append),
+                &violations,
+            )
+            .await?;
+        let data = self.with_constraints(data, &violations).await?;
+        let data = if self.soft_delete().await? {
+            with_deleted_column(data)?
+        } else {
+            data
+        };
+        let data = {
+            let dataset = self.dataset.get().await?;
+            with_normalized_columns(
+                data,
+                &NormalizedColumns::from_metadata(&dataset.schema().metadata)?,
+            )?
+        };
+        // An overwrite replaces the schema of the table, including its settings
+        let data = if matches!(lance_params.mode, WriteMode::Overwrite) {
+            let dataset = self.dataset.get().await?;
+            with_table_settings(data, &Schema::from(dataset.schema()))?
+        } else {
+            data
+        };
+        // The columns added by schema evolution, dropped again if the write fails
+        let mut added_columns = Vec::new();
+        let (data, vector_casts) = if matches!(lance_params.mode, WriteMode::Append) {
+            let schema = Schema::from(self.dataset.get().await?.schema());
+            let (data, vector_casts) = VectorCaster::try_new(data, &schema, add.vector_precision)?;
+            let mut diff = SchemaDiff::between(&data.schema(), &schema);
+            if schema_evolution == Evolve::AddNewColumns && !diff.extra_columns.is_empty() {
+                let fields = evolve::new_columns(&data.schema(), &diff)?;
+                added_columns = fields.iter().map(|field| field.name().clone()).collect();
+                self.add_null_columns(fields).await?;
+                let schema = Schema::from(self.dataset.get().await?.schema());
+                diff = SchemaDiff::between(&data.schema(), &schema);
+            }
+            if !diff.is_compatible() {
+                self.drop_added_columns(&added_columns).await;
+                return Err(Error::Schema {
+                    message: format!("cannot add the data to the table '{}': {}", self.name, diff),
+                });
+            }
+            (data, vector_casts)
+        } else {
+            (data, Arc::default())
+        };
+        let (data, rows) = CountingReader::new(data);
+        if bulk_load {
+            // The fragment id is assigned when the staged fragments are committed
+            let fragment = write_result(
+                FileFragment::create(&self.uri, 0, data, Some(bulk_load_params(lance_params)))
+                    .await,
+                Some(&violations),
+            )?;
+            self.write_stats
+                .record_vector_casts(&vector_casts.lock().unwrap());
+            let rows = rows.load(std::sync::atomic::Ordering::Relaxed);
+            tracing::Span::current().record("rows", rows);
+            self.bulk_load.stage(fragment, rows);
+            return Ok(());
+        }
+        let dataset = match write_result(
+            Dataset::write(data, &self.uri, Some(lance_params)).await,
+            Some(&violations),
+        ) {
+            Ok(dataset) => dataset,
+            Err(err) => {
+                self.drop_added_columns(&added_columns).await;
+                return Err(err);
+            }
+        };
+        let version = dataset.version().version;
+        record_version(version);
+        self.dataset.set_latest(dataset).await;
+        self.record_commit_metadata(version, &commit_metadata).await;
+        self.write_stats
+            .record_vector_casts(&vector_casts.lock().unwrap());
+        let rows = rows.load(std::sync::atomic::Ordering::Relaxed);
+        tracing::Span::current().record("rows", rows);
+        self.write_stats.record(&self.name, rows);
+        record_write(&self.name, "add", Some(rows), start.elapsed());
+        self.run_commit_hooks("add", version, Some(rows)).await?;
+        self.build_pending_indices().await;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.finish_bulk_load",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty, rows = tracing::field::Empty)
+    )]
+    async fn finish_bulk_load(&self) -> Result<BulkLoadStats> {
+        let start = Instant::now();
+        self.dataset.ensure_mutable().await?;
+        let permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        let (fragments, rows) = self.bulk_load.take();
+        if fragments.is_empty() {
+            return Ok(BulkLoadStats::default());
+        }
+        let num_fragments = fragments.len();
+        let read_version = self.dataset.get().await?.version().version;
+        let committed = Dataset::commit(
+            &self.uri,
+            Operation::Append {
+                fragments: fragments.clone(),
+            },
+            Some(read_version),
+            Some(self.store_params.clone()),
+            None,
+        )
+        .await;
+        let dataset = match committed {
+            Ok(dataset) => dataset,
+            Err(err) => {
+                self.bulk_load.restore(fragments, rows);
+                return Err(err.into());
+            }
+        };
+        let version = dataset.version().version;
+        record_version(version);
+        self.dataset.set_latest(dataset).await;
+        tracing::Span::current().record("rows", rows);
+        self.write_stats.record(&self.name, rows);
+        record_write(&self.name, "add", Some(rows), start.elapsed());
+        drop(permit);
+        self.run_commit_hooks("add", version, Some(rows)).await?;
+
+        // The index work skipped by the staged adds
+        let has_indices = !self.list_indices().await?.is_empty();
+        self.build_pending_indices().await;
+        if has_indices {
+            self.optimize_indices(&OptimizeOptions::default()).await?;
+        }
+        Ok(BulkLoadStats {
+            fragments: num_fragments,
+            rows,
+            version: Some(version),
+        })
+    }
+
+    async fn discard_bulk_load(&self) -> Result<usize> {
+        let (fragments, rows) = self.bulk_load.take();
+        let (store, base) = self.object_store().await?;
+        for file in fragments.iter().flat_map(|fragment| &fragment.files) {
+            let path = base.child("data").child(file.path.as_str());
+            match store.inner.delete(&path).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(rows)
+    }
+
+    async fn history(&self) -> Result<Vec<AuditRecord>> {
+        let (store, base) = self.object_store().await?;
+        read_history(&store, &base).await
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.create_index",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, columns = ?opts.columns)
+    )]
+    async fn create_index(&self, opts: IndexBuilder) -> Result<()> {
+        if opts.columns.is_empty() {
+            return Err(Error::InvalidInput {
+                message: "an index must be created on at least one column".to_string(),
+            });
+        }
+        if opts.columns.len() > 1 {
+            return self.create_btree_index_per_column(opts).await;
+        }
+        let schema = self.schema().await?;
+
+        let field = schema.field_with_name(&opts.columns[0])?;
+        let read_version = self.dataset.get().await?.version().version;
+
+        let distance_type = match &opts.index {
+            Index::IvfPq(ivf_pq) => Some(ivf_pq.distance_type),
+            Index::Auto if Self::supported_vector_data_type(field.data_type()) => {
+                Some(IvfPqIndexBuilder::default().distance_type)
+            }
+            _ => None,
+        };
+        match opts.index {
+            Index::Auto => self.create_auto_index(field, opts).await,
+            Index::BTree(btree) => self.create_btree_index(field, &btree, opts.replace).await,
+            Index::IvfPq(ivf_pq) => self.create_ivf_pq_index(ivf_pq, field, opts.replace).await,
+        }?;
+        let Some(distance_type) = distance_type else {
+            return self
+                .run_commit_hooks_since("create_index", read_version)
+                .await;
+        };
+
+        // Record the distance type for searches and drop the search defaults
+        // tuned for the previous index
+        let dataset = self.dataset.get().await?.clone();
+        let mut schema = dataset.schema().clone();
+        let field_id = schema
+            .field(field.name())
+            .map(|f| f.id)
+            .ok_or_else(|| Error::Schema {
+                message: format!("Column {} not found in dataset schema", field.name()),
+            })?;
+        let mut distance_types = IndexDistanceTypes::from_metadata(&schema.metadata)?;
+        distance_types.0.insert(
+            field.name().clone(),
+            IndexDistanceType {
+                distance_type,
+                field_id,
+            },
+        );
+        distance_types.apply_to_metadata(&mut schema.metadata)?;
+        let mut search_defaults = SearchDefaultsMap::from_metadata(&schema.metadata)?;
+        search_defaults.0.remove(field.name());
+        search_defaults.apply_to_metadata(&mut schema.metadata)?;
+        // This runs the commit hooks for both commits
+        self.commit_schema("create_index", dataset.version().version, schema)
+            .await
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.update",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn update(&self, mut update: UpdateBuilder) -> Result<()> {
+        let start = Instant::now();
+        let restrictions = self.restrictions(InterceptedOperation::Update).await?;
+        update.filter = and_filters(update.filter.as_deref(), restrictions.filter.as_deref());
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        let dataset = self.dataset.get().await?.clone();
+        let schema = Schema::from(dataset.schema());
+        self.check_update_constraints(&dataset, &schema, &update)
+            .await?;
+        let mut details = update
+            .columns
+            .iter()
+            .map(|(column, value)| format!("{} = {}", column, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Some(filter) = &update.filter {
+            details = format!("{} WHERE {}", details, filter);
+        }
+        // Keep the normalized columns of indices in sync
+        let normalized = NormalizedColumns::from_metadata(schema.metadata())?;
+        let normalized_updates = normalized.update_columns(&update.columns);
+        update.columns.extend(normalized_updates);
+        let mut builder = LanceUpdateBuilder::new(Arc::new(dataset));
+        if let Some(predicate) = update.filter {
+            builder = builder.update_where(&normalize_filter(&schema, &predicate)?)?;
+        }
+
+        for (column, value) in update.columns {
+            // Only whole columns can be replaced.  A struct column is updated by
+            // providing an expression for the entire struct.
+            if schema.field_with_name(&column).is_err() {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "cannot update '{}': updates must target a top-level column, the available columns are: {}",
+                        column,
+                        schema
+                            .fields()
+                            .iter()
+                            .map(|f| f.name().as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                });
+            }
+            builder = builder.set(column, &normalize_filter(&schema, &value)?)?;
+        }
+
+        let operation = builder.build()?;
+        let ds = operation.execute().await?;
+        let version = ds.version().version;
+        record_version(version);
+        self.dataset.set_latest(ds.as_ref().clone()).await;
+        record_write(&self.name, "update", None, start.elapsed());
+        self.run_commit_hooks_with_details("update", version, None, Some(details))
+            .await?;
+        Ok(())
+    }
+
+    async fn plain_query(
+        &self,
+        query: &Query,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        if let Some(time) = query.version_at {
+            let mut query = query.clone();
+            query.version_at = None;
+            return self.at_time(time).await?.plain_query(&query, options).await;
+        }
+        if let Some(version) = query.version {
+            let mut query = query.clone();
+            query.version = None;
+            return self
+                .at_version(version)
+                .await?
+                .plain_query(&query, options)
+                .await;
+        }
+        let mut query = query.clone().into_vector();
+        if let Some(select) = self.default_projection(&query.base).await? {
+            query.base.select = select;
+        }
+        let restrictions = self.restrictions(InterceptedOperation::Query).await?;
+        let masking = self.read_masking_policies(&restrictions).await?;
+        if let Some(policies) = &masking {
+            self.check_masked_references(policies, &query).await?;
+        }
+        query.base.filter =
+            and_filters(query.base.filter.as_deref(), restrictions.filter.as_deref());
+        query.base.filter = and_filters(
+            query.base.filter.as_deref(),
+            self.soft_delete_filter(&query.base).await?,
+        );
+        let lazy = match self.with_lazy_blobs(&query, masking.as_ref()).await? {
+            Some((lazy_query, columns, version)) => {
+                query = lazy_query;
+                Some((columns, version))
+            }
+            None => None,
+        };
+        let late = match self.with_late_materialization(&query).await? {
+            Some((late_query, late)) => {
+                query = late_query;
+                Some(late)
+            }
+            None => None,
+        };
+        let permit = maybe_acquire(&self.admission, OperationKind::Query).await?;
+        let pin = self.pin_current_version().await?;
+        let mut stream: SendableRecordBatchStream = if !query.base.order_by.is_empty() {
+            self.ordered_query(&query, options).await?
+        } else if query.base.distinct_on.is_some() {
+            self.distinct_plain_query(&query, options).await?
+        } else {
+            self.generic_query(&query, options).await?.into()
+        };
+        if let Some(late) = late {
+            stream = late.apply(stream)?;
+        }
+        if let Some((columns, version)) = lazy {
+            stream = mark_lazy_blobs(stream, &columns, version)?;
+        }
+        if let Some(policies) = masking {
+            stream = policies.mask_stream(stream);
+        }
+        Ok(hold_while_streaming(
+            pin_while_streaming(stream, pin),
+            permit,
+        ))
+    }
+
+    async fn vector_query(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        if let Some(time) = query.base.version_at {
+            let mut query = query.clone();
+            query.base.version_at = None;
+            return self
+                .at_time(time)
+                .await?
+                .vector_query(&query, options)
+                .await;
+        }
+        if !query.base.order_by.is_empty() && query.query_vector.is_some() {
+            return Err(Error::InvalidInput {
+                message: "the results of a vector search are ordered by distance, order_by \
+                          can only be used without a query vector"
+                    .to_string(),
+            });
+        }
+        let restrictions = self.restrictions(InterceptedOperation::Query).await?;
+        let masking = self.read_masking_policies(&restrictions).await?;
+        if let Some(policies) = &masking {
+            self.check_masked_references(policies, query).await?;
+        }
+        let with_distance_type = self.with_index_distance_type(query).await?;
+        let query = with_distance_type.as_ref().unwrap_or(query);
+        let default_projection = self.default_projection(&query.base).await?.map(|select| {
+            let mut query = query.clone();
+            query.base.select = select;
+            query
+        });
+        let query = default_projection.as_ref().unwrap_or(query);
+        let mandatory = and_filters(
+            restrictions.filter.as_deref(),
+            self.soft_delete_filter(&query.base).await?,
+        );
+        let restricted = mandatory.as_deref().map(|mandatory| {
+            let mut query = query.clone();
+            query.base.filter = and_filters(query.base.filter.as_deref(), Some(mandatory));
+            query
+        });
+        let query = restricted.as_ref().unwrap_or(query);
+        let (with_references, references) = if query.reference_vectors.is_empty() {
+            (None, None)
+        } else {
+            let (query, references) = self.with_reference_vectors(query).await?;
+            if let Some(policies) = &masking {
+                policies.check_not_referenced(
+                    std::slice::from_ref(&references.vector_column),
+                    "distance_to",
+                )?;
+            }
+            (Some(query), Some(references))
+        };
+        let query = with_references.as_ref().unwrap_or(query);
+        let (with_lazy, lazy) = match self.with_lazy_blobs(query, masking.as_ref()).await? {
+            Some((query, columns, version)) => (Some(query), Some((columns, version))),
+            None => (None, None),
+        };
+        let query = with_lazy.as_ref().unwrap_or(query);
+        let (with_late, late) = match self.with_late_materialization(query).await? {
+            Some((query, late)) => (Some(query), Some(late)),
+            None => (None, None),
+        };
+        let query = with_late.as_ref().unwrap_or(query);
+        let kind = if query.query_vector.is_some() {
+            OperationKind::Search
+        } else {
+            OperationKind::Query
+        };
+        let permit = maybe_acquire(&self.admission, kind).await?;
+        let pin = self.pin_current_version().await?;
+        let mut stream = if query.base.distinct_on.is_some() && query.query_vector.is_some() {
+            self.distinct_vector_query(query, options).await?
+        } else if query.base.distinct_on.is_some() {
+            self.distinct_plain_query(query, options).await?
+        } else if query.rescore && query.query_vector.is_some() {
+            self.rescored_query(query, options).await?
+        } else if self.uses_filter_cache(query) {
+            match self.filter_cached_query(query).await? {
+                Some(stream) => stream,
+                None => self.generic_query(query, options).await?.into(),
+            }
+        } else {
+            self.generic_query(query, options).await?.into()
+        };
+        if let Some(late) = late {
+            stream = late.apply(stream)?;
+        }
+        if let Some((columns, version)) = lazy {
+            stream = mark_lazy_blobs(stream, &columns, version)?;
+        }
+        if let Some(references) = references {
+            stream = references.apply(stream);
+        }
+        if let Some(transform) = &query.score_transform {
+            let distance_type = query.distance_type.unwrap_or(DistanceType::L2);
+            stream = transform.clone().apply(stream, distance_type).await?;
+        }
+        if let Some(policies) = masking {
+            stream = policies.mask_stream(stream);
+        }
+        Ok(hold_while_streaming(
+            pin_while_streaming(stream, pin),
+            permit,
+        ))
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.merge_insert",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn merge_insert(
+        &self,
+        params: MergeInsertBuilder,
+        new_data: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let restrictions = self.restrictions(InterceptedOperation::MergeInsert).await?;
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        // Rows that match on the primary key are updated, so only the new data
+        // can contain duplicate keys
+        if let Some(column) = self.unique_key_column(params.duplicate_keys).await? {
+            if params.on != [column.as_str()] {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "a merge insert can only check the uniqueness of keys if it matches on \
+                         the primary key column '{}'",
+                        column
+                    ),
+                });
+            }
+        }
+        let violations = ViolationSlot::default();
+        let new_data = self
+            .with_unique_keys(new_data, params.duplicate_keys, false, &violations)
+            .await?;
+        let new_data = self.with_constraints(new_data, &violations).await?;
+        // Inserted and updated rows are not deleted
+        let new_data = if self.soft_delete().await? {
+            with_deleted_column(new_data)?
+        } else {
+            new_data
+        };
+        let dataset = Arc::new(self.dataset.get().await?.clone());
+        let new_data = with_normalized_columns(
+            new_data,
+            &NormalizedColumns::from_metadata(&dataset.schema().metadata)?,
+        )?;
+        // The condition of the matched rows refers to the columns of the table
+        // as `target.<column>`
+        let restriction = restrictions.filter.as_deref();
+        let target_restriction = restriction
+            .map(|filter| qualify_filter(&Schema::from(dataset.schema()), filter, "target"))
+            .transpose()?;
+        let details = format!("on {}", params.on.join(", "));
+        let mut builder = LanceMergeInsertBuilder::try_new(dataset.clone(), params.on)?;
+        match (
+            params.when_matched_update_all,
+            and_filters(
+                params.when_matched_update_all_filt.as_deref(),
+                target_restriction.as_deref(),
+            ),
+        ) {
+            (false, _) => builder.when_matched(WhenMatched::DoNothing),
+            (true, None) => builder.when_matched(WhenMatched::UpdateAll),
+            (true, Some(filt)) => builder.when_matched(WhenMatched::update_if(&dataset, &filt)?),
+        };
+        if params.when_not_matched_insert_all {
+            builder.when_not_matched(lance::dataset::WhenNotMatched::InsertAll);
+        } else {
+            builder.when_not_matched(lance::dataset::WhenNotMatched::DoNothing);
+        }
+        if params.when_not_matched_by_source_delete {
+            let behavior = if let Some(filter) = and_filters(
+                params.when_not_matched_by_source_delete_filt.as_deref(),
+                restriction,
+            ) {
+                WhenNotMatchedBySource::delete_if(dataset.as_ref(), &filter)?
+            } else {
+                WhenNotMatchedBySource::Delete
+            };
+            builder.when_not_matched_by_source(behavior);
+        } else {
+            builder.when_not_matched_by_source(WhenNotMatchedBySource::Keep);
+        }
+        let job = builder.try_build()?;
+        let new_dataset = write_result(job.execute_reader(new_data).await, Some(&violations))?;
+        let version = new_dataset.version().version;
+        record_version(version);
+        self.dataset.set_latest(new_dataset.as_ref().clone()).await;
+        record_write(&self.name, "merge_insert", None, start.elapsed());
+        self.run_commit_hooks_with_details("merge_insert", version, None, Some(details))
+            .await?;
+        Ok(())
+    }
+
+    /// Delete rows from the table
+    #[tracing::instrument(
+        name = "lancedb.delete",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn delete(&self, predicate: &str) -> Result<()> {
+        let start = Instant::now();
+        let restrictions = self.restrictions(InterceptedOperation::Delete).await?;
+        let predicate = and_filters(Some(predicate), restrictions.filter.as_deref()).unwrap();
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        let mut dataset = self.dataset.get_mut().await?;
+        let version = if soft_delete::is_enabled(&dataset.schema().metadata) {
+            drop(dataset);
+            self.set_deleted(&predicate, true).await?
+        } else {
+            dataset.delete(&predicate).await?;
+            let version = dataset.version().version;
+            record_version(version);
+            version
+        };
+        record_write(&self.name, "delete", None, start.elapsed());
+        self.run_commit_hooks_with_details("delete", version, None, Some(predicate))
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.delete",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn delete_by_values(&self, column: &str, values: &[KeyValue]) -> Result<usize> {
+        let start = Instant::now();
+        let restrictions = self.restrictions(InterceptedOperation::Delete).await?;
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let schema = Schema::from(dataset.schema());
+        let soft_delete = soft_delete::is_enabled(&dataset.schema().metadata);
+        let predicate = |values: &[KeyValue]| -> Result<String> {
+            let filter = primary_key::lookup_filter(&schema, column, values)?;
+            Ok(and_filters(Some(&filter), restrictions.filter.as_deref()).unwrap())
+        };
+
+        // Find the fragment of each matching row with small lookups
+        let mut by_fragment: BTreeMap<u64, HashSet<KeyValue>> = BTreeMap::new();
+        let mut rows = 0;
+        for values in values.chunks(KEY_LOOKUP_BATCH_SIZE) {
+            let mut filter = predicate(values)?;
+            if soft_delete {
+                filter = and_filters(Some(&filter), Some(LIVE_FILTER)).unwrap();
+            }
+            let mut scanner = dataset.scan();
+            scanner.project(&[column])?;
+            scanner.with_row_id();
+            scanner.filter(&filter)?;
+            let batches = scanner
+                .try_into_stream()
+                .await?
+                .try_collect::<Vec<_>>()
+                .await?;
+            for batch in batches {
+                let keys = primary_key::key_values(batch.column(0), column)?;
+                let row_ids = batch[ROW_ID_COLUMN].as_primitive::<UInt64Type>();
+                rows += keys.len();
+                for (key, row_id) in keys.into_iter().zip(row_ids.values()) {
+                    by_fragment.entry(row_id >> 32).or_default().insert(key);
+                }
+            }
+        }
+        if rows == 0 {
+            return Ok(0);
+        }
+
+        let version = if soft_delete {
+            let matched = by_fragment
+                .into_values()
+                .flatten()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            let mut version = dataset.version().version;
+            for values in matched.chunks(KEY_LOOKUP_BATCH_SIZE) {
+                version = self.set_deleted(&predicate(values)?, true).await?;
+            }
+            version
+        } else {
+            let mut updated_fragments = Vec::new();
+            let mut deleted_fragment_ids = Vec::new();
+            for fragment in dataset.get_fragments() {
+                let id = fragment.id() as u64;
+                let Some(values) = by_fragment.get(&id) else {
+                    continue;
+                };
+                let values = values.iter().cloned().collect::<Vec<_>>();
+                let mut remaining = Some(fragment);
+                for values in values.chunks(KEY_LOOKUP_BATCH_SIZE) {
+                    let Some(fragment) = remaining else {
+                        break;
+                    };
+                    remaining = fragment.delete(&predicate(values)?).await?;
+                }
+                match remaining {
+                    Some(fragment) => updated_fragments.push(fragment.metadata().clone()),
+                    None => deleted_fragment_ids.push(id),
+                }
+            }
+            let store_params = match self.store_wrapper.clone() {
+                Some(wrapper) => None::<ObjectStoreParams>.patch_with_store_wrapper(wrapper)?,
+                None => None,
+            };
+            let committed = Dataset::commit(
+                &self.uri,
+                Operation::Delete {
+                    updated_fragments,
+                    deleted_fragment_ids,
+                    predicate: format!("`{}` IN ({} values)", column, values.len()),
+                },
+                Some(dataset.version().version),
+                store_params,
+                None,
+            )
+            .await?;
+            let version = committed.version().version;
+            record_version(version);
+            self.dataset.set_latest(committed).await;
+            version
+        };
+        tracing::Span::current().record("version", version);
+        record_write(&self.name, "delete", Some(rows), start.elapsed());
+        self.run_commit_hooks_with_details(
+            "delete",
+            version,
+            Some(rows),
+            Some(format!("{} values of `{}`", values.len(), column)),
+        )
+        .await?;
+        Ok(rows)
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.optimize",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, action = optimize_action_name(&action))
+    )]
+    async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats> {
+        let start = Instant::now();
+        let action_name = optimize_action_name(&action);
+        let mut stats = OptimizeStats {
+            compaction: None,
+            prune: None,
+        };
+        match action {
+            OptimizeAction::All => {
+                stats.compaction = self
+                    .optimize(OptimizeAction::Compact {
+                        options: CompactionOptions::default(),
+                        remap_options: None,
+                    })
+                    .await?
+                    .compaction;
+                stats.prune = self
+                    .optimize(OptimizeAction::Prune {
+                        older_than: Duration::try_days(7).unwrap(),
+                        delete_unverified: None,
+                    })
+                    .await?
+                    .prune;
+                self.optimize(OptimizeAction::Index(OptimizeOptions::default()))
+                    .await?;
+            }
+            OptimizeAction::Compact {
+                options,
+                remap_options,
+            } => {
+                let read_version = self.dataset.get().await?.version().version;
+                stats.compaction = Some(self.compact_files(options, remap_options).await?);
+                self.run_commit_hooks_since("compact", read_version).await?;
+            }
+            OptimizeAction::Prune {
+                older_than,
+                delete_unverified,
+            } => {
+                stats.prune = Some(
+                    self.cleanup_old_versions(older_than, delete_unverified)
+                        .await?,
+                );
+            }
+            OptimizeAction::Index(options) => {
+                let read_version = self.dataset.get().await?.version().version;
+                self.optimize_indices(&options).await?;
+                self.run_commit_hooks_since("optimize_indices", read_version)
+                    .await?;
+            }
+        }
+        record_optimize(&self.name, action_name, start.elapsed());
+        Ok(stats)
+    }
+
+    async fn preview_prune(
+        &self,
+        older_than: Duration,
+        delete_unverified: Option<bool>,
+    ) -> Result<PrunePreview> {
+        self.preview_cleanup(older_than, delete_unverified).await
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.add_columns",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn add_columns(
+        &self,
+        transforms: NewColumnTransform,
+        read_columns: Option<Vec<String>>,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let mut dataset = self.dataset.get_mut().await?;
+        dataset.add_columns(transforms, read_columns).await?;
+        let version = dataset.version().version;
+        record_version(version);
+        record_write(&self.name, "add_columns", None, start.elapsed());
+        drop(dataset);
+        self.run_commit_hooks("add_columns", version, None).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.alter_columns",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()> {
+        let start = Instant::now();
+        let mut dataset = self.dataset.get_mut().await?;
+        dataset.alter_columns(alterations).await?;
+        let version = dataset.version().version;
+        record_version(version);
+        record_write(&self.name, "alter_columns", None, start.elapsed());
+        drop(dataset);
+        self.run_commit_hooks("alter_columns", version, None)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.batch_alter",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn batch_alter(&self, alter: BatchAlterBuilder) -> Result<()> {
+        let start = Instant::now();
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let store_params = match self.store_wrapper.clone() {
+            Some(wrapper) => None::<ObjectStoreParams>.patch_with_store_wrapper(wrapper)?,
+            None => None,
+        };
+        let dataset = self.dataset.get().await?.clone();
+        let dataset =
+            batch_alter::apply_batch_alter(&dataset, &self.uri, alter, store_params).await?;
+        let version = dataset.version().version;
+        record_version(version);
+        self.dataset.set_latest(dataset).await;
+        record_write(&self.name, "batch_alter", None, start.elapsed());
+        self.run_commit_hooks("batch_alter", version, None).await?;
+        Ok(())
+    }
+
+    async fn masking_policies(&self) -> Result<HashMap<String, MaskingPolicy>> {
+        let dataset = self.dataset.get().await?;
+        let policies = MaskingPolicies::from_metadata(&dataset.schema().metadata)?;
+        Ok(policies.0.into_iter().collect())
+    }
+
+    async fn set_masking_policy(&self, column: &str, policy: Option<MaskingPolicy>) -> Result<()> {
+        if !self.unmasked {
+            return Err(Error::PermissionDenied {
+                message: "masking policies can only be changed through a table opened with \
+                          unmasked access"
+                    .to_string(),
+            });
+        }
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let mut schema = dataset.schema().clone();
+        let mut policies = MaskingPolicies::from_metadata(&schema.metadata)?;
+        match policy {
+            Some(policy) => {
+                let field = schema
+                    .fields
+                    .iter()
+                    .find(|f| f.name == column)
+                    .ok_or_else(|| Error::InvalidInput {
+                        message: format!(
+                            "cannot mask '{}': there is no top level column with that name",
+                            column
+                        ),
+                    })?;
+                policy.validate(&Field::from(field))?;
+                policies.0.insert(column.to_string(), policy);
+            }
+            None => {
+                policies.0.remove(column);
+            }
+        }
+        policies.apply_to_metadata(&mut schema.metadata)?;
+        self.commit_schema("set_masking_policy", dataset.version().version, schema)
+            .await
+    }
+
+    async fn blob_refs(&self, batch: &RecordBatch, column: &str) -> Result<Vec<BlobRef>> {
+        let version = lazy_blobs_version(batch.schema().metadata(), column)?;
+        let dataset = self.dataset.get().await?;
+        let dataset = if dataset.version().version == version {
+            Arc::new(dataset.clone())
+        } else {
+            Arc::new(dataset.checkout_version(version).await?)
+        };
+        blob_refs(dataset, batch, column)
+    }
+
+    async fn temporal_validity(&self) -> Result<Option<TemporalValidity>> {
+        let dataset = self.dataset.get().await?;
+        TemporalValidity::from_metadata(&dataset.schema().metadata)
+    }
+
+    async fn compute_statistics(&self, columns: &[&str]) -> Result<()> {
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let arrow_schema = Schema::from(dataset.schema());
+        let columns = if columns.is_empty() {
+            arrow_schema
+                .fields()
+                .iter()
+                .filter(|f| supports_statistics(f.data_type()))
+                .map(|f| f.name().clone())
+                .collect::<Vec<_>>()
+        } else {
+            columns
+                .iter()
+                .map(|column| {
+                    let field =
+                        arrow_schema
+                            .field_with_name(column)
+                            .map_err(|_| Error::InvalidInput {
+                                message: format!("there is no top level column named '{}'", column),
+                            })?;
+                    if !supports_statistics(field.data_type()) {
+                        return Err(Error::InvalidInput {
+                            message: format!(
+                                "statistics can only be computed for boolean, numeric and \
+                                 string columns, '{}' has type {}",
+                                column,
+                                field.data_type()
+                            ),
+                        });
+                    }
+                    Ok(column.to_string())
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        let mut schema = dataset.schema().clone();
+        let mut stats = FragmentStatistics::from_metadata(&schema.metadata)?;
+        stats.compute(&dataset, &columns).await?;
+        stats.apply_to_metadata(&mut schema.metadata)?;
+        self.commit_schema("compute_statistics", dataset.version().version, schema)
+            .await
+    }
+
+    async fn fragment_pruning(&self, query: &Query) -> Result<FragmentPruning> {
+        let mut query = query.clone().into_vector();
+        let restrictions = self.restrictions(InterceptedOperation::Query).await?;
+        query.base.filter =
+            and_filters(query.base.filter.as_deref(), restrictions.filter.as_deref());
+        let dataset = self.dataset.get().await?;
+        let total_fragments = dataset.get_fragments().len();
+        let kept_fragments = match self.query_filter(&dataset, &query)? {
+            Some(filter) => prune_fragments(&dataset, &filter)?.map(|f| f.len()),
+            None => None,
+        };
+        Ok(FragmentPruning {
+            total_fragments,
+            pruned_fragments: kept_fragments.map_or(0, |kept| total_fragments - kept),
+        })
+    }
+
+    async fn sample(&self, n: usize, seed: Option<u64>) -> Result<RecordBatch> {
+        let restrictions = self.restrictions(InterceptedOperation::Query).await?;
+        let masking = self.read_masking_policies(&restrictions).await?;
+        let _permit = maybe_acquire(&self.admission, OperationKind::Query).await?;
+        let dataset = self.dataset.get().await?;
+        let filter = restrictions
+            .filter
+            .map(|filter| normalize_filter(&Schema::from(dataset.schema()), &filter))
+            .transpose()?;
+        let batch = sample_dataset(&dataset, n, seed, filter.as_deref()).await?;
+        match masking {
+            Some(policies) => policies.mask_record_batch(batch),
+            None => Ok(batch),
+        }
+    }
+
+    async fn estimate_count(&self, filter: Option<String>) -> Result<usize> {
+        let restrictions = self.restrictions(InterceptedOperation::Query).await?;
+        let masking = self.read_masking_policies(&restrictions).await?;
+        if let (Some(filter), Some(policies)) = (&filter, masking) {
+            let schema = self.schema().await?;
+            policies.check_not_referenced(&filter_columns(&schema, filter)?, "filter")?;
+        }
+        let filter = and_filters(filter.as_deref(), restrictions.filter.as_deref());
+        let dataset = self.dataset.get().await?;
+        match filter {
+            Some(filter) => {
+                let filter = normalize_filter(&Schema::from(dataset.schema()), &filter)?;
+                estimate_count(&dataset, &filter).await
+            }
+            None => Ok(dataset.count_rows().await?),
+        }
+    }
+
+    async fn estimate_distinct(&self, column: &str) -> Result<usize> {
+        let restrictions = self.restrictions(InterceptedOperation::Query).await?;
+        if let Some(policies) = self.read_masking_policies(&restrictions).await? {
+            policies.check_not_referenced(&[column.to_string()], "distinct estimate")?;
+        }
+        let dataset = self.dataset.get().await?;
+        let filter = restrictions
+            .filter
+            .map(|filter| normalize_filter(&Schema::from(dataset.schema()), &filter))
+            .transpose()?;
+        estimate_distinct(&dataset, column, filter.as_deref()).await
+    }
+
+    async fn search_defaults(&self) -> Result<HashMap<String, SearchDefaults>> {
+        let dataset = self.dataset.get().await?;
+        Ok(SearchDefaultsMap::from_metadata(&dataset.schema().metadata)?.0)
+    }
+
+    async fn set_search_defaults(
+        &self,
+        column: &str,
+        defaults: Option<SearchDefaults>,
+    ) -> Result<()> {
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let mut schema = dataset.schema().clone();
+        let mut map = SearchDefaultsMap::from_metadata(&schema.metadata)?;
+        match defaults {
+            Some(defaults) => {
+                let field = Schema::from(&schema)
+                    .field_with_name(column)
+                    .map_err(|_| Error::InvalidInput {
+                        message: format!("there is no top level column named '{}'", column),
+                    })?
+                    .clone();
+                if !matches!(field.data_type(), DataType::FixedSizeList(f, _) if f.data_type().is_floating())
+                {
+                    return Err(Error::InvalidInput {
+                        message: format!("the column '{}' is not a vector column", column),
+                    });
+                }
+                if defaults.nprobes == 0 {
+                    return Err(Error::InvalidInput {
+                        message: "nprobes must be at least 1".to_string(),
+                    });
+                }
+                map.0.insert(column.to_string(), defaults);
+            }
+            None => {
+                map.0.remove(column);
+            }
+        }
+        map.apply_to_metadata(&mut schema.metadata)?;
+        self.commit_schema("set_search_defaults", dataset.version().version, schema)
+            .await
+    }
+
+    async fn metadata(&self, column: Option<&str>) -> Result<HashMap<String, String>> {
+        let dataset = self.dataset.get().await?;
+        let schema = dataset.schema();
+        match column {
+            None => Ok(user_metadata(&schema.metadata)),
+            Some(column) => {
+                let field = schema.field(column).ok_or_else(|| Error::InvalidInput {
+                    message: format!("the column '{}' does not exist", column),
+                })?;
+                Ok(user_metadata(&field.metadata))
+            }
+        }
+    }
+
+    async fn set_metadata(
+        &self,
+        column: Option<&str>,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<()> {
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let mut schema = dataset.schema().clone();
+        match column {
+            None => set_user_metadata(&mut schema.metadata, key, value)?,
+            Some(column) => {
+                let id = schema
+                    .field(column)
+                    .ok_or_else(|| Error::InvalidInput {
+                        message: format!("the column '{}' does not exist", column),
+                    })?
+                    .id;
+                let field = schema.mut_field_by_id(id).unwrap();
+                set_user_metadata(&mut field.metadata, key, value)?;
+            }
+        }
+        self.commit_schema("set_metadata", dataset.version().version, schema)
+            .await
+    }
+
+    async fn set_temporal_validity(&self, validity: Option<TemporalValidity>) -> Result<()> {
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let mut schema = dataset.schema().clone();
+        if let Some(validity) = &validity {
+            validity.validate(&Schema::from(&schema))?;
+        }
+        TemporalValidity::apply_to_metadata(validity.as_ref(), &mut schema.metadata)?;
+        self.commit_schema("set_temporal_validity", dataset.version().version, schema)
+            .await
+    }
+
+    async fn soft_delete(&self) -> Result<bool> {
+        let dataset = self.dataset.get().await?;
+        Ok(soft_delete::is_enabled(&dataset.schema().metadata))
+    }
+
+    async fn set_soft_delete(&self, enabled: bool) -> Result<()> {
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let mut dataset = self.dataset.get_mut().await?;
+        if soft_delete::is_enabled(&dataset.schema().metadata) == enabled {
+            return Ok(());
+        }
+        if enabled {
+            if !soft_delete::check_column(&Schema::from(dataset.schema()))? {
+                dataset
+                    .add_columns(
+                        NewColumnTransform::SqlExpressions(vec![(
+                            DELETED_COLUMN.to_string(),
+                            "false".to_string(),
+                        )]),
+                        None,
+                    )
+                    .await?;
+            }
+        } else {
+            dataset
+                .delete(&format!("`{}` = true", DELETED_COLUMN))
+                .await?;
+            dataset.drop_columns(&[DELETED_COLUMN]).await?;
+        }
+        let mut schema = dataset.schema().clone();
+        if enabled {
+            schema
+                .metadata
+                .insert(soft_delete::SOFT_DELETE_KEY.to_string(), "true".to_string());
+        } else {
+            schema.metadata.remove(soft_delete::SOFT_DELETE_KEY);
+        }
+        let version = dataset.version().version;
+        drop(dataset);
+        self.commit_schema("set_soft_delete", version, schema).await
+    }
+
+    async fn undelete(&self, predicate: &str) -> Result<()> {
+        let start = Instant::now();
+        let restrictions = self.restrictions(InterceptedOperation::Delete).await?;
+        let predicate = and_filters(Some(predicate), restrictions.filter.as_deref()).unwrap();
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        if !self.soft_delete().await? {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "cannot undelete rows of the table '{}', it does not have soft deletes enabled",
+                    self.name
+                ),
+            });
+        }
+        let version = self.set_deleted(&predicate, false).await?;
+        record_write(&self.name, "undelete", None, start.elapsed());
+        self.run_commit_hooks_with_details("undelete", version, None, Some(predicate))
+            .await
+    }
+
+    async fn constraints(&self) -> Result<HashMap<String, Vec<Constraint>>> {
+        let dataset = self.dataset.get().await?;
+        Ok(Constraints::from_metadata(&dataset.schema().metadata)?
+            .0
+            .into_iter()
+            .collect())
+    }
+
+    async fn set_constraints(&self, column: &str, constraints: Vec<Constraint>) -> Result<()> {
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let mut schema = dataset.schema().clone();
+        let mut all = Constraints::from_metadata(&schema.metadata)?;
+        if constraints.is_empty() {
+            all.0.remove(column);
+        } else {
+            let arrow_schema = Schema::from(&schema);
+            let field = arrow_schema
+                .field_with_name(column)
+                .map_err(|_| Error::InvalidInput {
+                    message: format!(
+                        "cannot constrain '{}': there is no top level column with that name",
+                        column
+                    ),
+                })?;
+            for constraint in &constraints {
+                constraint.validate(field)?;
+            }
+            let new = Constraints([(column.to_string(), constraints.clone())].into());
+            let checker = ConstraintChecker::try_new(&new)?;
+            let projection = [(column.to_string(), format!("`{}`", column))];
+            let violations = scan_violations(&dataset, &checker, &projection, None).await?;
+            if !violations.is_empty() {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the existing rows do not satisfy the constraints, {}",
+                        violation_report(&violations)
+                    ),
+                });
+            }
+            all.0.insert(column.to_string(), constraints);
+        }
+        all.apply_to_metadata(&mut schema.metadata)?;
+        self.commit_schema("set_constraints", dataset.version().version, schema)
+            .await
+    }
+
+    async fn primary_key(&self) -> Result<Option<String>> {
+        let dataset = self.dataset.get().await?;
+        Ok(primary_key::primary_key_from_metadata(
+            &dataset.schema().metadata,
+        ))
+    }
+
+    async fn set_primary_key(&self, column: Option<&str>) -> Result<()> {
+        self.dataset.ensure_mutable().await?;
+        if let Some(column) = column {
+            let schema = self.schema().await?;
+            primary_key::validate(&schema, column)?;
+            let indexed = self
+                .list_indices()
+                .await?
+                .iter()
+                .any(|index| index.columns == [column]);
+            if !indexed {
+                let builder = IndexBuilder::new(
+                    Arc::new(self.clone()),
+                    vec![column.to_string()],
+                    Index::BTree(BTreeIndexBuilder::default()),
+                );
+                self.create_index(builder).await?;
+            }
+        }
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        let dataset = self.dataset.get().await?.clone();
+        let mut schema = dataset.schema().clone();
+        primary_key::apply_to_metadata(column, &mut schema.metadata);
+        self.commit_schema("set_primary_key", dataset.version().version, schema)
+            .await
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.merge",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn merge_columns(
+        &self,
+        params: MergeColumnsBuilder,
+        new_data: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<()> {
+        let start = Instant::now();
+        // The new columns are added to every row, so they cannot be restricted
+        if self
+            .restrictions(InterceptedOperation::Update)
+            .await?
+            .filter
+            .is_some()
+        {
+            return Err(Error::InvalidInput {
+                message: "cannot merge columns into a table with a mandatory filter".to_string(),
+            });
+        }
+        let _permit = maybe_acquire(&self.admission, OperationKind::Write).await?;
+        self.dataset.ensure_mutable().await?;
+        let mut dataset = self.dataset.get_mut().await?;
+        let new_data = if params.validate_keys {
+            validate_merge_keys(&dataset, &params.left_on, &params.right_on, new_data).await?
+        } else {
+            new_data
+        };
+        match params.join_type {
+            MergeJoinType::Left => {
+                dataset
+                    .merge(new_data, &params.left_on, &params.right_on)
+                    .await?;
+            }
+            MergeJoinType::Inner => {
+                // Lance only supports left merges.  The marker column is null
+                // for the rows that had no match, which are then deleted.
+                dataset
+                    .merge(
+                        with_matched_column(new_data)?,
+                        &params.left_on,
+                        &params.right_on,
+                    )
+                    .await?;
+                dataset
+                    .delete(&format!("{} IS NULL", MATCHED_COLUMN))
+                    .await?;
+                dataset.drop_columns(&[MATCHED_COLUMN]).await?;
+            }
+        }
+        let version = dataset.version().version;
+        record_version(version);
+        record_write(&self.name, "merge", None, start.elapsed());
+        drop(dataset);
+        self.run_commit_hooks("merge", version, None).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "lancedb.drop_columns",
+        level = "debug",
+        skip_all,
+        fields(table = %self.name, version = tracing::field::Empty)
+    )]
+    async fn drop_columns(&self, columns: &[&str]) -> Result<()> {
+        let start = Instant::now();
+        let mut dataset = self.dataset.get_mut().await?;
+        dataset.drop_columns(columns).await?;
+        let version = dataset.version().version;
+        record_version(version);
+        record_write(&self.name, "drop_columns", None, start.elapsed());
+        drop(dataset);
+        self.run_commit_hooks_with_details("drop_columns", version, None, Some(columns.join(", ")))
+            .await?;
+        Ok(())
+    }
+
+    async fn list_indices(&self) -> Result<Vec<IndexConfig>> {
+        let dataset = self.dataset.get().await?;
+        let indices = dataset.load_indices().await?;
+        indices.iter().map(|idx| {
+            let mut is_vector = false;
+            let mut columns = Vec::with_capacity(idx.fields.len());
+            for field_id in &idx.fields {
+                let field = dataset.schema().field_by_id(*field_id).ok_or_else(|| Error::Runtime { message: format!("The index with name {} and uuid {} referenced a field with id {} which does not exist in the schema", idx.name, idx.uuid, field_id) })?;
+                if field.data_type().is_nested() {
+                    // Temporary hack to determine if an index is scalar or vector
+                    // Should be removed in https://github.com/lancedb/lance/issues/2039
+                    is_vector = true;
+                }
+                columns.push(field.name.clone());
+            }
+            Ok(IndexConfig { index_type: if is_vector { crate::index::IndexType::IvfPq } else { crate::index::IndexType::BTree }, columns })
+        }).collect::<Result<Vec<_>>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow_array::builder::{Int32Builder, ListBuilder};
+    use arrow_array::{
+        types::Int32Type, Array, ArrayRef, BooleanArray, Date32Array, FixedSizeListArray,
+        Float32Array, Float64Array, Int32Array, Int64Array, LargeBinaryArray, LargeStringArray,
+        RecordBatch, RecordBatchIterator, RecordBatchReader, StringArray, StructArray,
+        TimestampMillisecondArray, TimestampNanosecondArray, UInt32Array,
+    };
+    use arrow_data::ArrayDataBuilder;
+    use arrow_schema::{ArrowError, DataType, Field, Fields, Schema, TimeUnit};
+    use futures::TryStreamExt;
+    use lance::dataset::{Dataset, WriteMode};
+    use lance::io::{ObjectStoreParams, WrappingObjectStore};
+    use rand::Rng;
+    use tempfile::tempdir;
+
+    use crate::connect;
+    use crate::connection::memory::MemoryPoolConfig;
+    use crate::connection::ConnectBuilder;
+    use crate::index::scalar::BTreeIndexBuilder;
+    use crate::query::resumable::ScanCheckpoint;
+    use crate::query::{ExecutableQuery, QueryBase};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_open() {
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+
+        let batches = make_test_batches();
+        Dataset::write(batches, dataset_path.to_str().unwrap(), None)
+            .await
+            .unwrap();
+
+        let table = NativeTable::open(dataset_path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(table.name, "test")
+    }
+
+    #[tokio::test]
+    async fn test_open_not_found() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let table = NativeTable::open(uri).await;
+        assert!(matches!(table.unwrap_err(), Error::TableNotFound { .. }));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_object_store_path() {
+        use std::path::Path as StdPath;
+        let p = StdPath::new("s3://bucket/path/to/file");
+        let c = p.join("subfile");
+        assert_eq!(c.to_str().unwrap(), "s3://bucket/path/to/file/subfile");
+    }
+
+    #[tokio::test]
+    async fn test_count_rows() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let batches = make_test_batches();
+        let table = NativeTable::create(uri, "test", batches, None, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        assert_eq!(
+            table.count_rows(Some("i >= 5".to_string())).await.unwrap(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let batches = make_test_batches();
+        let schema = batches.schema().clone();
+        let table = conn.create_table("test", batches).execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        let new_batches = RecordBatchIterator::new(
+            vec![RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(100..110))],
+            )
+            .unwrap()]
+            .into_iter()
+            .map(Ok),
+            schema.clone(),
+        );
+
+        table.add(new_batches).execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 20);
+        assert_eq!(table.name(), "test");
+    }
+
+    #[tokio::test]
+    async fn test_add_schema_evolution() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+
+        let data = |tag_nullable: bool| {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("i", DataType::Int32, false),
+                Field::new("age", DataType::Int32, false),
+                Field::new("tag", DataType::Utf8, tag_nullable),
+            ]));
+            RecordBatchIterator::new(
+                vec![Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(10..15)),
+                        Arc::new(Int32Array::from_iter_values(iter::repeat(1).take(5))),
+                        Arc::new(StringArray::from_iter_values(iter::repeat("new").take(5))),
+                    ],
+                )
+                .unwrap())],
+                schema,
+            )
+        };
+
+        // Strict by default
+        assert!(matches!(
+            table.add(data(true)).execute().await,
+            Err(Error::Schema { .. })
+        ));
+        let evolve = WriteOptions::default().schema_evolution(Evolve::AddNewColumns);
+        assert!(matches!(
+            table
+                .add(data(false))
+                .write_options(evolve.clone())
+                .execute()
+                .await,
+            Err(Error::Schema { .. })
+        ));
+        assert_eq!(table.schema().await.unwrap().fields().len(), 2);
+
+        // The new columns are dropped again if the data can not be written
+        let failing = data(true);
+        let schema = failing.schema();
+        let failing = RecordBatchIterator::new(
+            failing.chain(iter::once(Err(ArrowError::ComputeError(
+                "the data source failed".to_string(),
+            )))),
+            schema,
+        );
+        assert!(table
+            .add(failing)
+            .write_options(evolve.clone())
+            .execute()
+            .await
+            .is_err());
+        assert_eq!(table.schema().await.unwrap().fields().len(), 2);
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        table
+            .add(data(true))
+            .write_options(evolve)
+            .execute()
+            .await
+            .unwrap();
+        let schema = table.schema().await.unwrap();
+        assert_eq!(
+            schema.field_with_name("tag").unwrap().data_type(),
+            &DataType::Utf8
+        );
+        assert_eq!(table.count_rows(None).await.unwrap(), 15);
+        assert_eq!(
+            table
+                .count_rows(Some("tag IS NULL".to_string()))
+                .await
+                .unwrap(),
+            10
+        );
+        assert_eq!(
+            table
+                .count_rows(Some("tag = 'new'".to_string()))
+                .await
+                .unwrap(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_schema_mismatch() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let diff = table.check_schema_compatible(&schema).await.unwrap();
+        assert!(!diff.is_compatible());
+        assert_eq!(diff.missing_columns, vec!["age"]);
+        assert_eq!(diff.extra_columns, vec!["name"]);
+        assert_eq!(diff.type_mismatches[0].column, "i");
+
+        let data = RecordBatchIterator::new(
+            vec![Ok(RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(vec![1])),
+                    Arc::new(StringArray::from(vec!["a"])),
+                ],
+            )
+            .unwrap())],
+            schema,
+        );
+        match table.add(data).execute().await.unwrap_err() {
+            Error::Schema { message } => {
+                assert!(message.contains("'age' is missing"), "{}", message);
+                assert!(
+                    message.contains("'name' is not in the table"),
+                    "{}",
+                    message
+                );
+                assert!(message.contains("'i' is Int64"), "{}", message);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        let same = merge_insert_test_batches(0, 0).schema();
+        assert!(table
+            .check_schema_compatible(&same)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        // Create a dataset with i=0..10
+        let batches = merge_insert_test_batches(0, 0);
+        let table = conn
+            .create_table("my_table", batches)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        // Create new data with i=5..15
+        let new_batches = Box::new(merge_insert_test_batches(5, 1));
+
+        // Perform a "insert if not exists"
+        let mut merge_insert_builder = table.merge_insert(&["i"]);
+        merge_insert_builder.when_not_matched_insert_all();
+        merge_insert_builder.execute(new_batches).await.unwrap();
+        // Only 5 rows should actually be inserted
+        assert_eq!(table.count_rows(None).await.unwrap(), 15);
+
+        // Create new data with i=15..25 (no id matches)
+        let new_batches = Box::new(merge_insert_test_batches(15, 2));
+        // Perform a "bulk update" (should not affect anything)
+        let mut merge_insert_builder = table.merge_insert(&["i"]);
+        merge_insert_builder.when_matched_update_all(None);
+        merge_insert_builder.execute(new_batches).await.unwrap();
+        // No new rows should have been inserted
+        assert_eq!(table.count_rows(None).await.unwrap(), 15);
+        assert_eq!(
+            table.count_rows(Some("age = 2".to_string())).await.unwrap(),
+            0
+        );
+
+        // Conditional update that only replaces the age=0 data
+        let new_batches = Box::new(merge_insert_test_batches(5, 3));
+        let mut merge_insert_builder = table.merge_insert(&["i"]);
+        merge_insert_builder.when_matched_update_all(Some("target.age = 0".to_string()));
+        merge_insert_builder.execute(new_batches).await.unwrap();
+        assert_eq!(
+            table.count_rows(Some("age = 3".to_string())).await.unwrap(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upsert() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+
+        // i=5..15 updates five rows and inserts five
+        table
+            .upsert(&["i"], merge_insert_test_batches(5, 1))
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 15);
+        assert_eq!(
+            table.count_rows(Some("age = 1".to_string())).await.unwrap(),
+            10
+        );
+
+        table
+            .upsert(&["i"], merge_insert_test_batches(0, 2))
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 15);
+        assert_eq!(
+            table.count_rows(Some("age = 2".to_string())).await.unwrap(),
+            10
+        );
+    }
+
+    fn merge_test_batches(keys: Vec<Option<i32>>) -> Box<dyn RecordBatchReader + Send> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("score", DataType::Int32, false),
+        ]));
+        let scores = Int32Array::from_iter_values(0..keys.len() as i32);
+        Box::new(RecordBatchIterator::new(
+            vec![RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from(keys)), Arc::new(scores)],
+            )],
+            schema,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_merge_columns() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        // A left merge keeps every row
+        let table = conn
+            .create_table("left", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        table
+            .merge("i", "id")
+            .execute(merge_test_batches((5..15).map(Some).collect()))
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        assert_eq!(
+            table
+                .count_rows(Some("score IS NULL".to_string()))
+                .await
+                .unwrap(),
+            5
+        );
+        assert!(table.schema().await.unwrap().field_with_name("id").is_err());
+
+        // An inner merge removes the rows without a match
+        let table = conn
+            .create_table("inner", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        let mut merge = table.merge("i", "id");
+        merge.join_type(MergeJoinType::Inner);
+        merge
+            .execute(merge_test_batches((5..15).map(Some).collect()))
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 5);
+        assert_eq!(
+            table
+                .count_rows(Some("score IS NULL".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(table
+            .schema()
+            .await
+            .unwrap()
+            .field_with_name(MATCHED_COLUMN)
+            .is_err());
+
+        // Validation rejects null, duplicate and unmatched keys
+        let table = conn
+            .create_table("validated", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        for keys in [
+            vec![Some(1), None],
+            vec![Some(1), Some(1)],
+            vec![Some(1), Some(100)],
+        ] {
+            let mut merge = table.merge("i", "id");
+            merge.validate_keys();
+            let err = merge.execute(merge_test_batches(keys)).await.unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        }
+        let mut merge = table.merge("i", "id");
+        merge.validate_keys();
+        merge
+            .execute(merge_test_batches(vec![Some(1), Some(2)]))
+            .await
+            .unwrap();
+        assert_eq!(
+            table
+                .count_rows(Some("score IS NOT NULL".to_string()))
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_overwrite() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let batches = make_test_batches();
+        let schema = batches.schema().clone();
+        let table = conn.create_table("test", batches).execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        let batches = vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(100..110))],
+        )
+        .unwrap()]
+        .into_iter()
+        .map(Ok);
+
+        let new_batches = RecordBatchIterator::new(batches.clone(), schema.clone());
+
+        // Can overwrite using AddDataOptions::mode
+        table
+            .add(new_batches)
+            .mode(AddDataMode::Overwrite)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        assert_eq!(table.name(), "test");
+
+        // Can overwrite using underlying WriteParams (which
+        // take precedence over AddDataOptions::mode)
+
+        let param: WriteParams = WriteParams {
+            mode: WriteMode::Overwrite,
+            ..Default::default()
+        };
+
+        let new_batches = RecordBatchIterator::new(batches.clone(), schema.clone());
+        table
+            .add(new_batches)
+            .write_options(WriteOptions {
+                lance_write_params: Some(param),
+                ..Default::default()
+            })
+            .mode(AddDataMode::Append)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        assert_eq!(table.name(), "test");
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_keeps_settings() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        conn.create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        let admin = conn.open_table("test").unmasked().execute().await.unwrap();
+        admin.set_soft_delete(true).await.unwrap();
+        admin.set_primary_key(Some("i")).await.unwrap();
+        admin
+            .set_masking_policy("age", Some(MaskingPolicy::Null))
+            .await
+            .unwrap();
+
+        admin
+            .add(merge_insert_test_batches(10, 1))
+            .mode(AddDataMode::Overwrite)
+            .execute()
+            .await
+            .unwrap();
+        assert!(admin.soft_delete().await.unwrap());
+        assert_eq!(admin.primary_key().await.unwrap(), Some("i".to_string()));
+        assert_eq!(
+            admin.masking_policies().await.unwrap(),
+            HashMap::from([("age".to_string(), MaskingPolicy::Null)])
+        );
+
+        // The settings still apply to the new rows
+        let reader = conn.open_table("test").execute().await.unwrap();
+        let batches = reader
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+        assert!(batches
+            .iter()
+            .all(|b| b["age"].null_count() == b.num_rows()));
+        reader.delete("i < 15").await.unwrap();
+        assert_eq!(admin.count_rows(None).await.unwrap(), 5);
+        let deleted = admin
+            .query()
+            .with_deleted_rows()
+            .only_if("_deleted = true")
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(deleted.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+
+        // The settings refer to the columns of the table
+        assert!(admin
+            .add(make_test_batches())
+            .mode(AddDataMode::Overwrite)
+            .execute()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_with_predicate() {
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+        let conn = connect(uri)
+            .read_consistency_interval(Duration::from_secs(0))
+            .execute()
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+
+        let record_batch_iter = RecordBatchIterator::new(
+            vec![RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(0..10)),
+                    Arc::new(StringArray::from_iter_values(vec![
+                        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j",
+                    ])),
+                ],
+            )
+            .unwrap()]
+            .into_iter()
+            .map(Ok),
+            schema.clone(),
+        );
+
+        let table = conn
+            .create_table("my_table", record_batch_iter)
+            .execute()
+            .await
+            .unwrap();
+
+        table
+            .update()
+            .only_if("id > 5")
+            .column("name", "'foo'")
+            .execute()
+            .await
+            .unwrap();
+
+        let mut batches = table
+            .query()
+            .select(Select::columns(&["id", "name"]))
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        while let Some(batch) = batches.pop() {
+            let ids = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>();
+            let names = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>();
+            for (i, name) in names.iter().enumerate() {
+                let id = ids[i].unwrap();
+                let name = name.unwrap();
+                if id > 5 {
+                    assert_eq!(name, "foo");
+                } else {
+                    assert_eq!(name, &format!("{}", (b'a' + id as u8) as char));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_all_types() {
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+        let conn = connect(uri)
+            .read_consistency_interval(Duration::from_secs(0))
+            .execute()
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("int32", DataType::Int32, false),
+            Field::new("int64", DataType::Int64, false),
+            Field::new("uint32", DataType::UInt32, false),
+            Field::new("string", DataType::Utf8, false),
+            Field::new("large_string", DataType::LargeUtf8, false),
+            Field::new("float32", DataType::Float32, false),
+            Field::new("float64", DataType::Float64, false),
+            Field::new("bool", DataType::Boolean, false),
+            Field::new("date32", DataType::Date32, false),
+            Field::new(
+                "timestamp_ns",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new(
+                "timestamp_ms",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new(
+                "vec_f32",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                false,
+            ),
+            Field::new(
+                "vec_f64",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), 2),
+                false,
+            ),
+        ]));
+
+        let record_batch_iter = RecordBatchIterator::new(
+            vec![RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(0..10)),
+                    Arc::new(Int64Array::from_iter_values(0..10)),
+                    Arc::new(UInt32Array::from_iter_values(0..10)),
+                    Arc::new(StringArray::from_iter_values(vec![
+                        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j",
+                    ])),
+                    Arc::new(LargeStringArray::from_iter_values(vec![
+                        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j",
+                    ])),
+                    Arc::new(Float32Array::from_iter_values((0..10).map(|i| i as f32))),
+                    Arc::new(Float64Array::from_iter_values((0..10).map(|i| i as f64))),
+                    Arc::new(Into::<BooleanArray>::into(vec![
+                        true, false, true, false, true, false, true, false, true, false,
+                    ])),
+                    Arc::new(Date32Array::from_iter_values(0..10)),
+                    Arc::new(TimestampNanosecondArray::from_iter_values(0..10)),
+                    Arc::new(TimestampMillisecondArray::from_iter_values(0..10)),
+                    Arc::new(
+                        create_fixed_size_list(
+                            Float32Array::from_iter_values((0..20).map(|i| i as f32)),
+                            2,
+                        )
+                        .unwrap(),
+                    ),
+                    Arc::new(
+                        create_fixed_size_list(
+                            Float64Array::from_iter_values((0..20).map(|i| i as f64)),
+                            2,
+                        )
+                        .unwrap(),
+                    ),
+                ],
+            )
+            .unwrap()]
+            .into_iter()
+            .map(Ok),
+            schema.clone(),
+        );
+
+        let table = conn
+            .create_table("my_table", record_batch_iter)
+            .execute()
+            .await
+            .unwrap();
+
+        // check it can do update for each type
+        let updates: Vec<(&str, &str)> = vec![
+            ("string", "'foo'"),
+            ("large_string", "'large_foo'"),
+            ("int32", "1"),
+            ("int64", "1"),
+            ("uint32", "1"),
+            ("float32", "1.0"),
+            ("float64", "1.0"),
+            ("bool", "true"),
+            ("date32", "1"),
+            ("timestamp_ns", "1"),
+            ("timestamp_ms", "1"),
+            ("vec_f32", "[1.0, 1.0]"),
+            ("vec_f64", "[1.0, 1.0]"),
+        ];
+
+        let mut update_op = table.update();
+        for (column, value) in updates {
+            update_op = update_op.column(column, value);
+        }
+        update_op.execute().await.unwrap();
+
+        let mut batches = table
+            .query()
+            .select(Select::columns(&[
+                "string",
+                "large_string",
+                "int32",
+                "int64",
+                "uint32",
+                "float32",
+                "float64",
+                "bool",
+                "date32",
+                "timestamp_ns",
+                "timestamp_ms",
+                "vec_f32",
+                "vec_f64",
+            ]))
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = batches.pop().unwrap();
+
+        macro_rules! assert_column {
+            ($column:expr, $array_type:ty, $expected:expr) => {
+                let array = $column
+                    .as_any()
+                    .downcast_ref::<$array_type>()
+                    .unwrap()
+                    .iter()
+                    .collect::<Vec<_>>();
+                for v in array {
+                    assert_eq!(v, Some($expected));
+                }
+            };
+        }
+
+        assert_column!(batch.column(0), StringArray, "foo");
+        assert_column!(batch.column(1), LargeStringArray, "large_foo");
+        assert_column!(batch.column(2), Int32Array, 1);
+        assert_column!(batch.column(3), Int64Array, 1);
+        assert_column!(batch.column(4), UInt32Array, 1);
+        assert_column!(batch.column(5), Float32Array, 1.0);
+        assert_column!(batch.column(6), Float64Array, 1.0);
+        assert_column!(batch.column(7), BooleanArray, true);
+        assert_column!(batch.column(8), Date32Array, 1);
+        assert_column!(batch.column(9), TimestampNanosecondArray, 1);
+        assert_column!(batch.column(10), TimestampMillisecondArray, 1);
+
+        let array = batch
+            .column(11)
+            .as_any()
+            .downcast_ref::<FixedSizeListArray>()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>();
+        for v in array {
+            let v = v.unwrap();
+            let f32array = v.as_any().downcast_ref::<Float32Array>().unwrap();
+            for v in f32array {
+                assert_eq!(v, Some(1.0));
+            }
+        }
+
+        let array = batch
+            .column(12)
+            .as_any()
+            .downcast_ref::<FixedSizeListArray>()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>();
+        for v in array {
+            let v = v.unwrap();
+            let f64array = v.as_any().downcast_ref::<Float64Array>().unwrap();
+            for v in f64array {
+                assert_eq!(v, Some(1.0));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_via_expr() {
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+        let conn = connect(uri)
+            .read_consistency_interval(Duration::from_secs(0))
+            .execute()
+            .await
+            .unwrap();
+        let tbl = conn
+            .create_table("my_table", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(1, tbl.count_rows(Some("i == 0".to_string())).await.unwrap());
+        tbl.update().column("i", "i+1").execute().await.unwrap();
+        assert_eq!(0, tbl.count_rows(Some("i == 0".to_string())).await.unwrap());
+    }
+
+    fn make_nested_batch(ids: std::ops::Range<i32>) -> RecordBatch {
+        let metadata_fields = Fields::from(vec![
+            Field::new("user", DataType::Utf8, true),
+            Field::new("score", DataType::Float64, true),
+        ]);
+        let mut nested = ListBuilder::new(ListBuilder::new(Int32Builder::new()));
+        for id in ids.clone() {
+            for _ in 0..2 {
+                nested.values().values().append_slice(&[id, id + 1]);
+                nested.values().append(true);
+            }
+            nested.append(true);
+        }
+        let nested = nested.finish();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+            Field::new("metadata", DataType::Struct(metadata_fields.clone()), true),
+            Field::new("blob", DataType::LargeBinary, true),
+            Field::new("nested", nested.data_type().clone(), true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(ids.clone())),
+                Arc::new(
+                    create_fixed_size_list(
+                        Float32Array::from_iter_values(ids.clone().flat_map(|i| [i as f32; 2])),
+                        2,
+                    )
+                    .unwrap(),
+                ),
+                Arc::new(StructArray::new(
+                    metadata_fields,
+                    vec![
+                        Arc::new(StringArray::from_iter_values(
+                            ids.clone().map(|i| format!("user{}", i)),
+                        )),
+                        Arc::new(Float64Array::from_iter_values(
+                            ids.clone().map(|i| i as f64),
+                        )),
+                    ],
+                    None,
+                )),
+                Arc::new(LargeBinaryArray::from_iter_values(
+                    ids.clone().map(|i| i.to_le_bytes()),
+                )),
+                Arc::new(nested),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_nested_types_round_trip() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let first = make_nested_batch(0..5);
+        let schema = first.schema();
+        let table = conn
+            .create_table(
+                "nested",
+                RecordBatchIterator::new(vec![Ok(first.clone())], schema.clone()),
+            )
+            .execute()
+            .await
+            .unwrap();
+        let second = make_nested_batch(5..10);
+        table
+            .add(RecordBatchIterator::new(
+                vec![Ok(second.clone())],
+                schema.clone(),
+            ))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.schema().await.unwrap(), schema);
+
+        let batches = table
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let actual = arrow::compute::concat_batches(&schema, &batches).unwrap();
+        let expected = arrow::compute::concat_batches(&schema, &[first, second]).unwrap();
+        assert_eq!(actual, expected);
+
+        // Update with a filter on a nested field
+        table
+            .update()
+            .only_if("metadata.user = 'user3'")
+            .column("id", "id + 100")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(
+            table
+                .count_rows(Some("id = 103".to_string()))
+                .await
+                .unwrap(),
+            1
+        );
+
+        // Only whole columns can be updated
+        let err = table
+            .update()
+            .column("metadata.score", "1.0")
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+
+        // Nested columns are returned by vector searches
+        let batches = table
+            .query()
+            .limit(1)
+            .nearest_to(&[7.0, 7.0])
+            .unwrap()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let blob = batches[0]["blob"].as_binary::<i64>().value(0);
+        assert_eq!(blob, 7_i32.to_le_bytes());
+        let user = batches[0]["metadata"].as_struct()["user"]
+            .as_string::<i32>()
+            .value(0)
+            .to_string();
+        assert_eq!(user, "user7");
+    }
+
+    #[derive(Default, Debug)]
+    struct NoOpCacheWrapper {
+        called: AtomicBool,
+    }
+
+    impl NoOpCacheWrapper {
+        fn called(&self) -> bool {
+            self.called.load(Ordering::Relaxed)
+        }
+    }
+
+    impl WrappingObjectStore for NoOpCacheWrapper {
+        fn wrap(
+            &self,
+            original: Arc<dyn object_store::ObjectStore>,
+        ) -> Arc<dyn object_store::ObjectStore> {
+            self.called.store(true, Ordering::Relaxed);
+            original
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_table_options() {
+        let tmp_dir = tempdir().unwrap();
+        let dataset_path = tmp_dir.path().join("test.lance");
+        let uri = dataset_path.to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let batches = make_test_batches();
+
+        conn.create_table("my_table", batches)
+            .execute()
+            .await
+            .unwrap();
+
+        let wrapper = Arc::new(NoOpCacheWrapper::default());
+
+        let object_store_params = ObjectStoreParams {
+            object_store_wrapper: Some(wrapper.clone()),
+            ..Default::default()
+        };
+        let param = ReadParams {
+            store_options: Some(object_store_params),
+            ..Default::default()
+        };
+        assert!(!wrapper.called());
+        conn.open_table("my_table")
+            .lance_read_params(param)
+            .execute()
+            .await
+            .unwrap();
+        assert!(wrapper.called());
+    }
+
+    fn merge_insert_test_batches(
+        offset: i32,
+        age: i32,
+    ) -> impl RecordBatchReader + Send + Sync + 'static {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("age", DataType::Int32, false),
+        ]));
+        RecordBatchIterator::new(
+            vec![RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(offset..(offset + 10))),
+                    Arc::new(Int32Array::from_iter_values(iter::repeat(age).take(10))),
+                ],
+            )],
+            schema,
+        )
+    }
+
+    fn make_test_batches() -> impl RecordBatchReader + Send + Sync + 'static {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        RecordBatchIterator::new(
+            vec![RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(0..10))],
+            )],
+            schema,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_create_index() {
+        use arrow_array::RecordBatch;
+        use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+        use rand;
+        use std::iter::repeat_with;
+
+        use arrow_array::Float32Array;
+
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let dimension = 16;
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "embeddings",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                dimension,
+            ),
+            false,
+        )]));
+
+        let mut rng = rand::thread_rng();
+        let float_arr = Float32Array::from(
+            repeat_with(|| rng.gen::<f32>())
+                .take(512 * dimension as usize)
+                .collect::<Vec<f32>>(),
+        );
+
+        let vectors = Arc::new(create_fixed_size_list(float_arr, dimension).unwrap());
+        let batches = RecordBatchIterator::new(
+            vec![RecordBatch::try_new(schema.clone(), vec![vectors.clone()]).unwrap()]
+                .into_iter()
+                .map(Ok),
+            schema,
+        );
+
+        let table = conn.create_table("test", batches).execute().await.unwrap();
+
+        assert_eq!(
+            table
+                .as_native()
+                .unwrap()
+                .count_indexed_rows("my_index")
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            table
+                .as_native()
+                .unwrap()
+                .count_unindexed_rows("my_index")
+                .await
+                .unwrap(),
+            None
+        );
+
+        table
+            .create_index(&["embeddings"], Index::Auto)
+            .execute()
+            .await
+            .unwrap();
+
+        let index_configs = table.list_indices().await.unwrap();
+        assert_eq!(index_configs.len(), 1);
+        let index = index_configs.into_iter().next().unwrap();
+        assert_eq!(index.index_type, crate::index::IndexType::IvfPq);
+        assert_eq!(index.columns, vec!["embeddings".to_string()]);
+        assert_eq!(table.count_rows(None).await.unwrap(), 512);
+        assert_eq!(table.name(), "test");
+
+        let indices = table.as_native().unwrap().load_indices().await.unwrap();
+        let index_uuid = &indices[0].index_uuid;
+        assert_eq!(
+            table
+                .as_native()
+                .unwrap()
+                .count_indexed_rows(index_uuid)
+                .await
+                .unwrap(),
+            Some(512)
+        );
+        assert_eq!(
+            table
+                .as_native()
+                .unwrap()
+                .count_unindexed_rows(index_uuid)
+                .await
+                .unwrap(),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tune_search() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let dimension = 16;
+        let mut rng = rand::thread_rng();
+        let values = Float32Array::from_iter_values(
+            iter::repeat_with(|| rng.gen::<f32>()).take(512 * dimension as usize),
+        );
+        let vectors = Arc::new(create_fixed_size_list(values, dimension).unwrap());
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "embeddings",
+            vectors.data_type().clone(),
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![vectors]).unwrap();
+        let table = conn
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+
+        // There is nothing to tune without an index
+        assert!(table.tune_search(0.9, 10).await.is_err());
+        let ivf_pq = || {
+            Index::IvfPq(
+                IvfPqIndexBuilder::default()
+                    .num_partitions(4)
+                    .num_sub_vectors(4),
+            )
+        };
+        table
+            .create_index(&["embeddings"], ivf_pq())
+            .execute()
+            .await
+            .unwrap();
+        assert!(table.tune_search(1.5, 10).await.is_err());
+
+        let defaults = table.tune_search(0.5, 10).await.unwrap();
+        assert!(TUNED_NPROBES.contains(&defaults.nprobes));
+        assert!(TUNED_REFINE_FACTORS.contains(&defaults.refine_factor));
+        assert_eq!(
+            table.search_defaults().await.unwrap().get("embeddings"),
+            Some(&defaults)
+        );
+        // Searches still work with the stored defaults
+        let results = table
+            .query()
+            .nearest_to(&[0.5; 16])
+            .unwrap()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(results.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+
+        // Defaults can only be set for vector columns
+        let mut invalid = defaults.clone();
+        assert!(table
+            .set_search_defaults("nope", Some(invalid.clone()))
+            .await
+            .is_err());
+        invalid.nprobes = 0;
+        assert!(table
+            .set_search_defaults("embeddings", Some(invalid))
+            .await
+            .is_err());
+
+        // A new index removes the defaults tuned for the old one
+        table
+            .create_index(&["embeddings"], ivf_pq())
+            .execute()
+            .await
+            .unwrap();
+        assert!(table.search_defaults().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_distance_type() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let dimension = 16;
+        let mut rng = rand::thread_rng();
+        let values = Float32Array::from_iter_values(
+            iter::repeat_with(|| rng.gen::<f32>()).take(512 * dimension as usize),
+        );
+        let vectors = Arc::new(create_fixed_size_list(values, dimension).unwrap());
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "embeddings",
+            vectors.data_type().clone(),
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![vectors]).unwrap();
+        let table = conn
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .create_index(
+                &["embeddings"],
+                Index::IvfPq(
+                    IvfPqIndexBuilder::default()
+                        .num_partitions(2)
+                        .distance_type(DistanceType::Cosine),
+                ),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        let count_results = |query: VectorQuery| async move {
+            let batches = query.execute().await?.try_collect::<Vec<_>>().await?;
+            Result::Ok(batches.iter().map(|b| b.num_rows()).sum::<usize>())
+        };
+        let query = table.query().limit(5).nearest_to(&[0.5; 16]).unwrap();
+
+        // The distance type of the index is used by default
+        assert_eq!(count_results(query.clone()).await.unwrap(), 5);
+        assert_eq!(
+            count_results(query.clone().distance_type(DistanceType::Cosine))
+                .await
+                .unwrap(),
+            5
+        );
+        // A different distance type can not use the index
+        assert!(matches!(
+            count_results(query.clone().distance_type(DistanceType::L2)).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert_eq!(
+            count_results(query.distance_type(DistanceType::L2).bypass_vector_index())
+                .await
+                .unwrap(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index_metadata_export_import() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let make_table = |name: &'static str| {
+            let conn = conn.clone();
+            async move {
+                let dimension = 16;
+                let values = Float32Array::from_iter_values((0..512 * dimension).map(|i| i as f32));
+                let vectors = Arc::new(create_fixed_size_list(values, dimension).unwrap());
+                let schema = Arc::new(Schema::new(vec![Field::new(
+                    "embeddings",
+                    vectors.data_type().clone(),
+                    false,
+                )]));
+                let batch = RecordBatch::try_new(schema.clone(), vec![vectors]).unwrap();
+                conn.create_table(name, RecordBatchIterator::new(vec![Ok(batch)], schema))
+                    .execute()
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let table = make_table("source").await;
+        table
+            .create_index(
+                &["embeddings"],
+                Index::IvfPq(
+                    IvfPqIndexBuilder::default()
+                        .num_partitions(2)
+                        .distance_type(DistanceType::Cosine),
+                ),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        let json = table
+            .index_metadata("embeddings")
+            .await
+            .unwrap()
+            .to_json()
+            .unwrap();
+        let metadata = IndexMetadata::from_json(&json).unwrap();
+        assert_eq!(metadata.index_type, "IVF_PQ");
+        assert_eq!(metadata.num_rows, 512);
+        let build = metadata.build.as_ref().unwrap();
+        assert_eq!(build.num_rows, 512);
+        let Index::IvfPq(params) = &build.parameters else {
+            panic!("expected IVF PQ parameters");
+        };
+        assert_eq!(params.num_partitions, Some(2));
+        // The default number of sub vectors was resolved and recorded
+        assert_eq!(params.num_sub_vectors, Some(1));
+        assert_eq!(params.distance_type, DistanceType::Cosine);
+
+        let synthetic = make_table("synthetic").await;
+        synthetic.import_index(&metadata).execute().await.unwrap();
+        let indices = synthetic.list_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].columns, vec!["embeddings".to_string()]);
+
+        assert!(matches!(
+            make_table("unindexed")
+                .await
+                .index_metadata("embeddings")
+                .await,
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+
+    fn create_fixed_size_list<T: Array>(values: T, list_size: i32) -> Result<FixedSizeListArray> {
+        let list_type = DataType::FixedSizeList(
+            Arc::new(Field::new("item", values.data_type().clone(), true)),
+            list_size,
+        );
+        let data = ArrayDataBuilder::new(list_type)
+            .len(values.len() / list_size as usize)
+            .add_child_data(values.into_data())
+            .build()
+            .unwrap();
+
+        Ok(FixedSizeListArray::from(data))
+    }
+
+    fn some_sample_data() -> Box<dyn RecordBatchReader + Send> {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        let schema = batch.schema().clone();
+        let batch = Ok(batch);
+
+        Box::new(RecordBatchIterator::new(vec![batch], schema))
+    }
+
+    #[tokio::test]
+    async fn test_create_scalar_index() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        let conn = ConnectBuilder::new(uri).execute().await.unwrap();
+        let table = conn
+            .create_table(
+                "my_table",
+                RecordBatchIterator::new(vec![Ok(batch.clone())], batch.schema()),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        // Can create an index on a scalar column (will default to btree)
+        table
+            .create_index(&["i"], Index::Auto)
+            .execute()
+            .await
+            .unwrap();
+
+        let index_configs = table.list_indices().await.unwrap();
+        assert_eq!(index_configs.len(), 1);
+        let index = index_configs.into_iter().next().unwrap();
+        assert_eq!(index.index_type, crate::index::IndexType::BTree);
+        assert_eq!(index.columns, vec!["i".to_string()]);
+
+        // Can also specify btree
+        table
+            .create_index(&["i"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+
+        let index_configs = table.list_indices().await.unwrap();
+        assert_eq!(index_configs.len(), 1);
+        let index = index_configs.into_iter().next().unwrap();
+        assert_eq!(index.index_type, crate::index::IndexType::BTree);
+        assert_eq!(index.columns, vec!["i".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_create_btree_index_per_column() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 1))
+            .execute()
+            .await
+            .unwrap();
+
+        // Either every column is indexed or none is
+        table
+            .create_index(&["age"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+        assert!(table
+            .create_index(&["i", "age"], Index::BTree(BTreeIndexBuilder::default()))
+            .replace(false)
+            .execute()
+            .await
+            .is_err());
+        let indices = table.list_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].columns, vec!["age"]);
+
+        table
+            .create_index(&["i", "age"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+        let mut columns = table
+            .list_indices()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|index| {
+                assert_eq!(index.index_type, crate::index::IndexType::BTree);
+                index.columns
+            })
+            .collect::<Vec<_>>();
+        columns.sort();
+        assert_eq!(columns, vec![vec!["age"], vec!["i"]]);
+        assert_eq!(
+            table
+                .count_rows(Some("i >= 5 AND age = 1".to_string()))
+                .await
+                .unwrap(),
+            5
+        );
+
+        // Only btree indices can cover several columns
+        assert!(table
+            .create_index(&["i", "age"], Index::Auto)
+            .execute()
+            .await
+            .is_err());
+        // Each column can only appear once
+        assert!(table
+            .create_index(&["i", "i"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .is_err());
+        let empty: &[&str] = &[];
+        assert!(matches!(
+            table
+                .create_index(empty, Index::BTree(BTreeIndexBuilder::default()))
+                .execute()
+                .await,
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_normalized_scalar_index() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let names = |offset: i32, names: Vec<Option<&str>>| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(
+                        offset..offset + names.len() as i32,
+                    )),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone())
+        };
+        let table = conn
+            .create_table("my_table", names(0, vec![Some("Foo"), Some("bar"), None]))
+            .execute()
+            .await
+            .unwrap();
+
+        let btree = BTreeIndexBuilder::default().normalization(Normalization::Lowercase);
+        table
+            .create_index(&["name"], Index::BTree(btree))
+            .execute()
+            .await
+            .unwrap();
+        let indices = table.list_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].columns, vec!["_lower_name".to_string()]);
+
+        // New and updated rows are normalized as well
+        table
+            .add(names(3, vec![Some("FOO")]))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .update()
+            .only_if("i = 1")
+            .column("name", "'fOo'")
+            .execute()
+            .await
+            .unwrap();
+
+        let batches = table
+            .query()
+            .only_if("lower(name) = 'foo'")
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let mut ids = batches
+            .iter()
+            .flat_map(|b| {
+                b.column_by_name("i")
+                    .unwrap()
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 3]);
+        // The hidden column is not returned and not expected from new data
+        assert!(batches[0].column_by_name("_lower_name").is_none());
+        assert!(table
+            .check_schema_compatible(&schema)
+            .await
+            .unwrap()
+            .is_compatible());
+
+        // Only strings can be normalized
+        let btree = BTreeIndexBuilder::default().normalization(Normalization::Uppercase);
+        assert!(table
+            .create_index(&["i"], Index::BTree(btree))
+            .execute()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fragment_statistics() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 1))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(10, 2))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(20, 3))
+            .execute()
+            .await
+            .unwrap();
+
+        let pruning = |filter: &'static str| {
+            let query = table.query().only_if(filter);
+            async move { query.fragment_pruning().await.unwrap() }
+        };
+        // Nothing is skipped without statistics
+        assert_eq!(pruning("i >= 25").await.pruned_fragments, 0);
+
+        table.compute_statistics(&[]).await.unwrap();
+        let pruned = pruning("i >= 25").await;
+        assert_eq!(pruned.total_fragments, 3);
+        assert_eq!(pruned.pruned_fragments, 2);
+        assert_eq!(pruning("age = 2 AND i < 15").await.pruned_fragments, 2);
+        assert_eq!(
+            pruning("(age > 1) AND i IS NOT NULL")
+                .await
+                .pruned_fragments,
+            1
+        );
+        assert_eq!(pruning("i >= 25 OR age = 1").await.pruned_fragments, 0);
+        assert_eq!(pruning("i IS NULL").await.pruned_fragments, 3);
+
+        // Skipping fragments does not change the results
+        for use_fragment_statistics in [true, false] {
+            let batches = table
+                .query()
+                .only_if("i >= 25 AND age = 3")
+                .execute_with_options(QueryExecutionOptions {
+                    use_fragment_statistics,
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+        }
+
+        // Fragments added after the statistics were computed are never skipped
+        table
+            .add(merge_insert_test_batches(30, 4))
+            .execute()
+            .await
+            .unwrap();
+        let pruned = pruning("i >= 25").await;
+        assert_eq!(pruned.total_fragments, 4);
+        assert_eq!(pruned.pruned_fragments, 2);
+        assert_eq!(
+            table.count_rows(Some("i >= 25".to_string())).await.unwrap(),
+            15
+        );
+
+        assert!(table.compute_statistics(&["nope"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_version_at() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let before_create = Utc::now() - chrono::Duration::try_seconds(60).unwrap();
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 1))
+            .execute()
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let after_create = Utc::now();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        table
+            .add(merge_insert_test_batches(10, 2))
+            .execute()
+            .await
+            .unwrap();
+
+        let num_rows = |query: Query| async move {
+            let batches = query.execute().await?.try_collect::<Vec<_>>().await?;
+            Result::Ok(batches.iter().map(|b| b.num_rows()).sum::<usize>())
+        };
+        assert_eq!(
+            num_rows(table.query().version_at(after_create))
+                .await
+                .unwrap(),
+            10
+        );
+        assert_eq!(
+            num_rows(table.query().version_at(Utc::now()))
+                .await
+                .unwrap(),
+            20
+        );
+        // The handle itself still reads the latest version
+        assert_eq!(num_rows(table.query()).await.unwrap(), 20);
+        assert_eq!(table.version().await.unwrap(), 2);
+        assert!(matches!(
+            num_rows(table.query().version_at(before_create)).await,
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_memory_pool() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri)
+            .memory_pool(MemoryPoolConfig {
+                max_query_bytes: Some(1024),
+                max_index_build_bytes: Some(1024),
+                ..MemoryPoolConfig::new(1 << 30)
+            })
+            .execute()
+            .await
+            .unwrap();
+
+        let dimension = 16;
+        let mut rng = rand::thread_rng();
+        let values = Float32Array::from_iter_values(
+            iter::repeat_with(|| rng.gen::<f32>()).take(512 * dimension as usize),
+        );
+        let vectors = Arc::new(create_fixed_size_list(values, dimension).unwrap());
+        let ids = Arc::new(Int32Array::from_iter_values(0..512));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("embeddings", vectors.data_type().clone(), false),
+        ]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![ids, vectors]).unwrap();
+        let table = conn
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+
+        // Streaming queries are not limited
+        let batches = table
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 512);
+
+        // Ordered queries collect their results first
+        let ordered = table
+            .query()
+            .order_by("id", SortOrder::Desc)
+            .execute()
+            .await;
+        assert!(matches!(ordered, Err(Error::MemoryLimitExceeded { .. })));
+
+        let index = table
+            .create_index(
+                &["embeddings"],
+                Index::IvfPq(IvfPqIndexBuilder::default().num_partitions(2)),
+            )
+            .execute()
+            .await;
+        assert!(matches!(index, Err(Error::MemoryLimitExceeded { .. })));
+
+        let metrics = conn.memory_metrics().unwrap();
+        assert_eq!(metrics.reserved, 0);
+        assert_eq!(metrics.rejected_total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_order_by() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 1))
+            .execute()
+            .await
+            .unwrap();
+        for offset in 1..5 {
+            table
+                .add(merge_insert_test_batches(offset * 10, offset + 1))
+                .execute()
+                .await
+                .unwrap();
+        }
+        table.compute_statistics(&["i"]).await.unwrap();
+
+        let column = |query: Query, name: &'static str| async move {
+            let batches = query
+                .execute()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            batches
+                .iter()
+                .flat_map(|b| b[name].as_primitive::<Int32Type>().values().to_vec())
+                .collect::<Vec<_>>()
+        };
+
+        // The newest fragment holds the first rows, the others are skipped
+        let dataset = table.as_native().unwrap().dataset.get().await.unwrap();
+        assert_eq!(
+            order_bound(&dataset, "i", SortOrder::Desc, 3).unwrap(),
+            Some("`i` >= 40".to_string())
+        );
+        assert_eq!(
+            column(table.query().order_by("i", SortOrder::Desc).limit(3), "i").await,
+            vec![49, 48, 47]
+        );
+        // Not enough rows match the bound, so it is not used
+        assert_eq!(
+            column(
+                table
+                    .query()
+                    .only_if("i % 10 = 5")
+                    .order_by("i", SortOrder::Desc)
+                    .limit(3),
+                "i"
+            )
+            .await,
+            vec![45, 35, 25]
+        );
+        // Sorting by several columns, without selecting them
+        let query = table
+            .query()
+            .select(Select::columns(&["age"]))
+            .order_by("age", SortOrder::Asc)
+            .order_by("i", SortOrder::Desc)
+            .only_if("i % 10 < 2");
+        let batches = query
+            .clone()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].num_columns(), 1);
+        assert_eq!(
+            column(query.select(Select::All), "i").await,
+            vec![1, 0, 11, 10, 21, 20, 31, 30, 41, 40]
+        );
+
+        let invalid = table
+            .query()
+            .order_by("i", SortOrder::Asc)
+            .distinct_on(&["age"])
+            .execute()
+            .await;
+        assert!(matches!(invalid, Err(Error::InvalidInput { .. })));
+        let invalid = table
+            .query()
+            .order_by("nope", SortOrder::Asc)
+            .execute()
+            .await;
+        assert!(matches!(invalid, Err(Error::InvalidInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_sample() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 1))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(10, 2))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(20, 3))
+            .execute()
+            .await
+            .unwrap();
+
+        let ids = |batch: RecordBatch| batch["i"].as_primitive::<Int32Type>().values().to_vec();
+        let sample = ids(table.sample(5, Some(7)).await.unwrap());
+        assert_eq!(sample.len(), 5);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sample, ids(table.sample(5, Some(7)).await.unwrap()));
+        assert_eq!(
+            ids(table.sample(100, None).await.unwrap()),
+            (0..30).collect::<Vec<_>>()
+        );
+
+        // Deleted rows are never sampled
+        table.delete("i < 15").await.unwrap();
+        let sample = ids(table.sample(10, Some(7)).await.unwrap());
+        assert_eq!(sample.len(), 10);
+        assert!(sample.iter().all(|i| *i >= 15));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_count_and_distinct() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 1))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(10, 2))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(20, 3))
+            .execute()
+            .await
+            .unwrap();
+
+        // Small tables are counted exactly
+        assert_eq!(table.estimate_count(None).await.unwrap(), 30);
+        assert_eq!(
+            table
+                .estimate_count(Some("i >= 25".to_string()))
+                .await
+                .unwrap(),
+            5
+        );
+        assert!(table
+            .estimate_count(Some("nope > 1".to_string()))
+            .await
+            .is_err());
+        assert_eq!(table.estimate_distinct("age").await.unwrap(), 3);
+
+        table.compute_statistics(&[]).await.unwrap();
+        assert_eq!(
+            table
+                .estimate_count(Some("i >= 25 AND age = 3".to_string()))
+                .await
+                .unwrap(),
+            5
+        );
+        // The stored sketch is combined with the fragments added since
+        table
+            .add(merge_insert_test_batches(30, 4))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.estimate_distinct("age").await.unwrap(), 4);
+        let distinct = table.estimate_distinct("i").await.unwrap();
+        assert!((38..=40).contains(&distinct), "{}", distinct);
+        assert!(table.estimate_distinct("nope").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_declared_index_built_at_threshold() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let schema = make_test_batches().schema();
+        let table = conn
+            .create_empty_table("my_table", schema)
+            .declare_index(&["i"], Index::BTree(BTreeIndexBuilder::default()))
+            .pending_index_threshold(15)
+            .execute()
+            .await
+            .unwrap();
+
+        table.add(make_test_batches()).execute().await.unwrap();
+        assert!(table.list_indices().await.unwrap().is_empty());
+
+        table.add(make_test_batches()).execute().await.unwrap();
+        let indices = table.list_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].index_type, crate::index::IndexType::BTree);
+        assert_eq!(indices[0].columns, vec!["i".to_string()]);
+
+        // The declaration is satisfied, further adds do not rebuild the index
+        let version = table.version().await.unwrap();
+        table.add(make_test_batches()).execute().await.unwrap();
+        assert_eq!(table.version().await.unwrap(), version + 1);
+    }
+
+    #[tokio::test]
+    async fn test_declared_index_failure_does_not_fail_add() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        // An IVF PQ index can not be built on an integer column
+        let schema = make_test_batches().schema();
+        let table = conn
+            .create_empty_table("my_table", schema)
+            .declare_index(&["i"], Index::IvfPq(IvfPqIndexBuilder::default()))
+            .pending_index_threshold(1)
+            .execute()
+            .await
+            .unwrap();
+
+        // The data is committed once, so the add must not report an error
+        table.add(make_test_batches()).execute().await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        assert!(table.list_indices().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_table_spec_round_trip() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let table = conn
+            .create_table("source", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        table
+            .create_index(&["i"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+
+        let json = table.to_spec().await.unwrap().to_json().unwrap();
+        let mut spec = spec::TableSpec::from_json(&json).unwrap();
+        assert_eq!(spec.indices.len(), 1);
+        assert!(spec.indices[0].built);
+
+        spec.name = "copy".to_string();
+        spec.pending_index_threshold = Some(5);
+        let copy = conn
+            .create_table_from_spec(&spec)
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(copy.count_rows(None).await.unwrap(), 0);
+        assert_eq!(
+            copy.schema().await.unwrap().fields(),
+            table.schema().await.unwrap().fields()
+        );
+
+        // The index is declared on the new table and built once there is data
+        copy.add(make_test_batches()).execute().await.unwrap();
+        let indices = copy.list_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].columns, vec!["i".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_read_consistency_interval() {
+        let intervals = vec![
+            None,
+            Some(0),
+            Some(100), // 100 ms
+        ];
+
+        for interval in intervals {
+            let data = some_sample_data();
+
+            let tmp_dir = tempdir().unwrap();
+            let uri = tmp_dir.path().to_str().unwrap();
+
+            let conn1 = ConnectBuilder::new(uri).execute().await.unwrap();
+            let table1 = conn1
+                .create_empty_table("my_table", data.schema())
+                .execute()
+                .await
+                .unwrap();
+
+            let mut conn2 = ConnectBuilder::new(uri);
+            if let Some(interval) = interval {
+                conn2 = conn2.read_consistency_interval(std::time::Duration::from_millis(interval));
+            }
+            let conn2 = conn2.execute().await.unwrap();
+            let table2 = conn2.open_table("my_table").execute().await.unwrap();
+
+            assert_eq!(table1.count_rows(None).await.unwrap(), 0);
+            assert_eq!(table2.count_rows(None).await.unwrap(), 0);
+
+            table1.add(data).execute().await.unwrap();
+            assert_eq!(table1.count_rows(None).await.unwrap(), 1);
+
+            match interval {
+                None => {
+                    assert_eq!(table2.count_rows(None).await.unwrap(), 0);
+                    table2.checkout_latest().await.unwrap();
+                    assert_eq!(table2.count_rows(None).await.unwrap(), 1);
+                }
+                Some(0) => {
+                    assert_eq!(table2.count_rows(None).await.unwrap(), 1);
+                }
+                Some(100) => {
+                    assert_eq!(table2.count_rows(None).await.unwrap(), 0);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    assert_eq!(table2.count_rows(None).await.unwrap(), 1);
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri)
+            .read_consistency_interval(Duration::from_secs(0))
+            .execute()
+            .await
+            .unwrap();
+        let table = conn
+            .create_table("my_table", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        let snapshot = table.snapshot().await.unwrap();
+        let version = snapshot.version().await.unwrap();
+
+        table.add(make_test_batches()).execute().await.unwrap();
+        table.delete("i < 5").await.unwrap();
+
+        assert_eq!(snapshot.version().await.unwrap(), version);
+        assert_eq!(snapshot.count_rows(None).await.unwrap(), 10);
+        assert_eq!(
+            snapshot
+                .count_rows(Some("i < 5".to_string()))
+                .await
+                .unwrap(),
+            5
+        );
+        let rows = snapshot
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .iter()
+            .map(|b| b.num_rows())
+            .sum::<usize>();
+        assert_eq!(rows, 10);
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        // The snapshot is read-only
+        assert!(snapshot.add(make_test_batches()).execute().await.is_err());
+
+        // Pruning through the table keeps the version of the snapshot
+        let native = table.as_native().unwrap();
+        assert_eq!(native.pinned_versions(), vec![version]);
+        table
+            .optimize(OptimizeAction::Prune {
+                older_than: chrono::Duration::try_seconds(0).unwrap(),
+                delete_unverified: Some(true),
+            })
+            .await
+            .unwrap();
+        assert_eq!(snapshot.count_rows(None).await.unwrap(), 10);
+        drop(snapshot);
+        assert!(native.pinned_versions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_time_travel_write() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let conn = ConnectBuilder::new(uri)
+            .read_consistency_interval(Duration::from_secs(0))
+            .execute()
+            .await
+            .unwrap();
+        let table = conn
+            .create_table("my_table", some_sample_data())
+            .execute()
+            .await
+            .unwrap();
+        let version = table.version().await.unwrap();
+        table.add(some_sample_data()).execute().await.unwrap();
+        table.checkout(version).await.unwrap();
+        assert!(table.add(some_sample_data()).execute().await.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_batch_alter() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..10).map(|i| i.to_string()),
+                )),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+        let version = table.version().await.unwrap();
+
+        // Invalid changes are rejected before anything is written
+        let data_files = || {
+            std::fs::read_dir(tmp_dir.path().join("test.lance").join("data"))
+                .unwrap()
+                .count()
+        };
+        let files = data_files();
+        assert!(table
+            .batch_alter()
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("j".to_string(), "i * 2".to_string())]),
+                None,
+            )
+            .alter_columns([ColumnAlteration::new("i".to_string()).rename("id".to_string())])
+            .drop_columns(["missing"])
+            .execute()
+            .await
+            .is_err());
+        assert_eq!(table.version().await.unwrap(), version);
+        assert_eq!(data_files(), files);
+
+        table
+            .batch_alter()
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("j".to_string(), "i * 2".to_string())]),
+                None,
+            )
+            .alter_columns([ColumnAlteration::new("i".to_string()).rename("id".to_string())])
+            .drop_columns(["s"])
+            .execute()
+            .await
+            .unwrap();
+
+        assert_eq!(table.version().await.unwrap(), version + 1);
+        let schema = table.schema().await.unwrap();
+        let names = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["id", "j"]);
+        assert_eq!(
+            table
+                .count_rows(Some("j = id * 2".to_string()))
+                .await
+                .unwrap(),
+            10
+        );
+
+        // The id of the dropped column, still in the data files, is not reused
+        table
+            .batch_alter()
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("k".to_string(), "id + 1".to_string())]),
+                None,
+            )
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(
+            table
+                .count_rows(Some("k = id + 1".to_string()))
+                .await
+                .unwrap(),
+            10
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lazy_blobs() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let make_batches = |prefix: &str| {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("image", DataType::Binary, true),
+            ]));
+            let images = (0..4)
+                .map(|i| (i != 3).then(|| format!("{}{}", prefix, i).into_bytes()))
+                .collect::<Vec<_>>();
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(0..4)),
+                    Arc::new(arrow_array::BinaryArray::from_iter(images)),
+                ],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema)
+        };
+        let table = conn
+            .create_table("test", make_batches("image"))
+            .execute()
+            .await
+            .unwrap();
+
+        let batches = table
+            .query()
+            .only_if("id >= 1")
+            .lazy_blobs(&["image"])
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert!(batch.column_by_name("image").is_none());
+        assert!(batch.column_by_name("_rowid").is_some());
+
+        // The references read the version that was queried
+        table
+            .add(make_batches("new"))
+            .mode(AddDataMode::Overwrite)
+            .execute()
+            .await
+            .unwrap();
+        let refs = table.blob_refs(batch, "image").await.unwrap();
+        assert_eq!(refs.len(), 3);
+        assert_eq!(
+            refs[0].read().await.unwrap().unwrap().as_ref(),
+            b"image1".as_slice()
+        );
+        assert!(refs[2].read().await.unwrap().is_none());
+
+        assert!(table.blob_refs(batch, "id").await.is_err());
+        let invalid = table.query().lazy_blobs(&["id"]).execute().await;
+        assert!(matches!(invalid, Err(Error::InvalidInput { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_optimize_with_live_query() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        table.add(make_test_batches()).execute().await.unwrap();
+        let native = table.as_native().unwrap();
+
+        let stream = table.query().execute().await.unwrap();
+        assert_eq!(native.pinned_versions(), vec![2]);
+
+        // Rewrite the files read by the query and prune everything that is old
+        table.add(make_test_batches()).execute().await.unwrap();
+        let prune = || OptimizeAction::Prune {
+            older_than: chrono::Duration::try_seconds(0).unwrap(),
+            delete_unverified: Some(true),
+        };
+        table
+            .optimize(OptimizeAction::Compact {
+                options: CompactionOptions::default(),
+                remap_options: None,
+            })
+            .await
+            .unwrap();
+        table.optimize(prune()).await.unwrap();
+
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        let num_rows = batches.iter().map(|b| b.num_rows()).sum::<usize>();
+        assert_eq!(num_rows, 20);
+        assert!(native.pinned_versions().is_empty());
+
+        let stats = table.optimize(prune()).await.unwrap();
+        assert!(stats.prune.unwrap().old_versions > 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_by_primary_key() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        assert!(table.get(3).await.is_err());
+
+        table.set_primary_key(Some("i")).await.unwrap();
+        assert_eq!(table.primary_key().await.unwrap(), Some("i".to_string()));
+        let indices = table.list_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].columns, vec!["i".to_string()]);
+
+        let row = table.get(3).await.unwrap().unwrap();
+        assert_eq!(row.num_rows(), 1);
+        assert_eq!(row["i"].as_primitive::<Int32Type>().value(0), 3);
+        assert!(table.get(42).await.unwrap().is_none());
+        assert!(table.get("3").await.is_err());
+
+        let rows = table
+            .get_many(&[1.into(), 5.into(), 42.into()])
+            .await
+            .unwrap();
+        let mut keys = rows["i"].as_primitive::<Int32Type>().values().to_vec();
+        keys.sort();
+        assert_eq!(keys, vec![1, 5]);
+        assert_eq!(table.get_many(&[]).await.unwrap().num_rows(), 0);
+
+        // Keys are not unique, every row of a key is returned
+        table.add(make_test_batches()).execute().await.unwrap();
+        let rows = table.get_many(&[1.into(), 5.into()]).await.unwrap();
+        assert_eq!(rows.num_rows(), 4);
+
+        table.set_primary_key(None).await.unwrap();
+        assert_eq!(table.primary_key().await.unwrap(), None);
+        assert!(table.set_primary_key(Some("nope")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_by_values() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        for offset in [10, 20] {
+            table
+                .add(merge_insert_test_batches(offset, 1))
+                .execute()
+                .await
+                .unwrap();
+        }
+        let version = table.version().await.unwrap();
+
+        // More values than fit in one lookup, and all of the second fragment
+        let values = [3, 25]
+            .into_iter()
+            .chain(10..20)
+            .chain(1000..3000)
+            .map(KeyValue::from)
+            .collect::<Vec<_>>();
+        assert_eq!(table.delete_by_values("i", &values).await.unwrap(), 12);
+        assert_eq!(table.version().await.unwrap(), version + 1);
+        assert_eq!(table.count_rows(None).await.unwrap(), 18);
+        assert_eq!(
+            table
+                .count_rows(Some("i IN (3, 25) OR (i >= 10 AND i < 20)".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(table.delete_by_values("i", &[3.into()]).await.unwrap(), 0);
+        assert_eq!(table.delete_by_values("i", &[]).await.unwrap(), 0);
+        assert!(table.delete_by_values("i", &["3".into()]).await.is_err());
+        assert!(table.delete_by_values("nope", &[3.into()]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_external_ids() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("value", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    (0..10).map(|i| format!("doc-{}", i)),
+                )),
+                Arc::new(Int32Array::from_iter_values(0..10)),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+        assert!(table.get_by_ids(&["doc-1".into()]).await.is_err());
+        table.set_primary_key(Some("id")).await.unwrap();
+
+        let values =
+            |batch: &RecordBatch| batch["value"].as_primitive::<Int32Type>().values().to_vec();
+        let rows = table
+            .get_by_ids(&["doc-7".into(), "nope".into(), "doc-2".into()])
+            .await
+            .unwrap();
+        assert_eq!(values(&rows), vec![7, 2]);
+
+        // Ids map to row addresses and back
+        let ids = vec![KeyValue::from("doc-3"), KeyValue::from("doc-5")];
+        let row_ids = table.row_ids_of(&ids).await.unwrap();
+        assert_eq!(row_ids.len(), 2);
+        let addresses = ids.iter().map(|id| row_ids[id]).collect::<Vec<_>>();
+        assert_eq!(table.ids_of_rows(&addresses).await.unwrap(), ids);
+
+        table
+            .update_by_ids(&["doc-1".into(), "doc-2".into()])
+            .await
+            .unwrap()
+            .column("value", "value + 100")
+            .execute()
             .await
             .unwrap();
-
-        let table = NativeTable::open(dataset_path.to_str().unwrap())
+        let rows = table
+            .get_by_ids(&["doc-1".into(), "doc-2".into(), "doc-3".into()])
             .await
             .unwrap();
+        assert_eq!(values(&rows), vec![101, 102, 3]);
 
-        assert_eq!(table.name, "test")
+        let deleted = table
+            .delete_by_ids(&["doc-1".into(), "doc-9".into(), "nope".into()])
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+        assert_eq!(table.count_rows(None).await.unwrap(), 8);
+        assert_eq!(table.delete_by_ids(&[]).await.unwrap(), 0);
+        assert_eq!(table.delete_by_ids(&["doc-1".into()]).await.unwrap(), 0);
     }
 
     #[tokio::test]
-    async fn test_open_not_found() {
+    async fn test_unique_keys() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
-        let table = NativeTable::open(uri).await;
-        assert!(matches!(table.unwrap_err(), Error::TableNotFound { .. }));
-    }
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        // Checking keys requires a primary key
+        assert!(table
+            .add(merge_insert_test_batches(5, 1))
+            .on_duplicate_keys(DuplicateKeys::Reject)
+            .execute()
+            .await
+            .is_err());
+        table.set_primary_key(Some("i")).await.unwrap();
 
-    #[test]
-    #[cfg(not(windows))]
-    fn test_object_store_path() {
-        use std::path::Path as StdPath;
-        let p = StdPath::new("s3://bucket/path/to/file");
-        let c = p.join("subfile");
-        assert_eq!(c.to_str().unwrap(), "s3://bucket/path/to/file/subfile");
+        let err = table
+            .add(merge_insert_test_batches(5, 1))
+            .on_duplicate_keys(DuplicateKeys::Reject)
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidInput { message } if message.contains("is not unique")),
+            "{}",
+            err
+        );
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        table
+            .add(merge_insert_test_batches(5, 1))
+            .on_duplicate_keys(DuplicateKeys::Dedupe)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 15);
+        assert_eq!(
+            table.count_rows(Some("age = 1".to_string())).await.unwrap(),
+            5
+        );
+
+        // Duplicates within the new data of a merge insert
+        let duplicated = || {
+            let batches = merge_insert_test_batches(20, 2)
+                .chain(merge_insert_test_batches(20, 3))
+                .collect::<Vec<_>>();
+            let schema = batches[0].as_ref().unwrap().schema();
+            Box::new(RecordBatchIterator::new(batches, schema))
+        };
+        let mut merge_insert = table.merge_insert(&["i"]);
+        merge_insert
+            .when_not_matched_insert_all()
+            .on_duplicate_keys(DuplicateKeys::Reject);
+        assert!(merge_insert.execute(duplicated()).await.is_err());
+
+        let mut merge_insert = table.merge_insert(&["i"]);
+        merge_insert
+            .when_not_matched_insert_all()
+            .on_duplicate_keys(DuplicateKeys::Dedupe);
+        merge_insert.execute(duplicated()).await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 25);
+        assert_eq!(
+            table.count_rows(Some("age = 3".to_string())).await.unwrap(),
+            0
+        );
+
+        let mut merge_insert = table.merge_insert(&["age"]);
+        merge_insert
+            .when_not_matched_insert_all()
+            .on_duplicate_keys(DuplicateKeys::Dedupe);
+        assert!(merge_insert.execute(duplicated()).await.is_err());
     }
 
     #[tokio::test]
-    async fn test_count_rows() {
+    async fn test_cost_estimates() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
-
-        let batches = make_test_batches();
-        let table = NativeTable::create(uri, "test", batches, None, None, None)
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
             .await
             .unwrap();
 
-        assert_eq!(table.count_rows(None).await.unwrap(), 10);
-        assert_eq!(
-            table.count_rows(Some("i >= 5".to_string())).await.unwrap(),
-            5
-        );
+        let estimate = table.query().limit(5).estimate().await.unwrap();
+        assert_eq!(estimate.rows_scanned, 5);
+        assert!(estimate.io_bytes > 0);
+        let estimate = table.query().only_if("age > 3").estimate().await.unwrap();
+        assert_eq!(estimate.rows_scanned, 10);
+
+        let builder = table.create_index(&["i"], Index::BTree(Default::default()));
+        assert!(builder.estimate().await.unwrap().io_bytes > 0);
+        let builder = table.create_index(&["nope"], Index::BTree(Default::default()));
+        assert!(builder.estimate().await.is_err());
+
+        // A single fragment has nothing to compact
+        let estimate = OptimizeAction::All.estimate(&table).await.unwrap();
+        assert_eq!(estimate.rows_scanned, 0);
+        table
+            .add(merge_insert_test_batches(10, 1))
+            .execute()
+            .await
+            .unwrap();
+        let estimate = OptimizeAction::All.estimate(&table).await.unwrap();
+        assert_eq!(estimate.rows_scanned, 20);
     }
 
     #[tokio::test]
-    async fn test_add() {
+    async fn test_verify() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
         let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(10, 1))
+            .execute()
+            .await
+            .unwrap();
 
-        let batches = make_test_batches();
-        let schema = batches.schema().clone();
-        let table = conn.create_table("test", batches).execute().await.unwrap();
-        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        let report = table.verify().await.unwrap();
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.fragments_checked, 2);
+        assert_eq!(report.rows_checked, 20);
 
-        let new_batches = RecordBatchIterator::new(
-            vec![RecordBatch::try_new(
-                schema.clone(),
-                vec![Arc::new(Int32Array::from_iter_values(100..110))],
-            )
-            .unwrap()]
+        let data_file = walkdir::WalkDir::new(tmp_dir.path().join("test.lance/data"))
             .into_iter()
-            .map(Ok),
-            schema.clone(),
-        );
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.file_type().is_file())
+            .unwrap();
+        std::fs::remove_file(data_file.path()).unwrap();
 
-        table.add(new_batches).execute().await.unwrap();
-        assert_eq!(table.count_rows(None).await.unwrap(), 20);
-        assert_eq!(table.name(), "test");
+        let report = table.verify().await.unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, verify::ProblemKind::MissingFile);
+        assert_eq!(report.rows_checked, 10);
     }
 
     #[tokio::test]
-    async fn test_merge_insert() {
+    async fn test_repair() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
         let conn = connect(uri).execute().await.unwrap();
-
-        // Create a dataset with i=0..10
-        let batches = merge_insert_test_batches(0, 0);
         let table = conn
-            .create_table("my_table", batches)
+            .create_table("test", merge_insert_test_batches(0, 0))
             .execute()
             .await
             .unwrap();
+        let data_dir = tmp_dir.path().join("test.lance/data");
+        let data_files = || {
+            std::fs::read_dir(&data_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect::<HashSet<_>>()
+        };
+        let original = data_files();
+        table
+            .add(merge_insert_test_batches(10, 1))
+            .execute()
+            .await
+            .unwrap();
+        let added = data_files().difference(&original).next().unwrap().clone();
+        std::fs::remove_file(added).unwrap();
+        std::fs::write(data_dir.join("orphan.lance"), b"leftover").unwrap();
+
+        let mut options = RepairOptions::default();
+        options.orphan_grace_period = chrono::Duration::zero();
+        let report = table.repair(options).await.unwrap();
+        assert!(!report.integrity.is_ok());
+        assert_eq!(report.restored_version, Some(1));
+        assert_eq!(report.removed_files.len(), 1);
+        assert_eq!(report.bytes_removed, 8);
+        assert!(report.rebuilt_indices.is_empty());
         assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        assert!(table.verify().await.unwrap().is_ok());
+    }
 
-        // Create new data with i=5..15
-        let new_batches = Box::new(merge_insert_test_batches(5, 1));
-
-        // Perform a "insert if not exists"
-        let mut merge_insert_builder = table.merge_insert(&["i"]);
-        merge_insert_builder.when_not_matched_insert_all();
-        merge_insert_builder.execute(new_batches).await.unwrap();
-        // Only 5 rows should actually be inserted
+    #[tokio::test]
+    async fn test_soft_delete() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        assert!(!table.soft_delete().await.unwrap());
+        assert!(table.undelete("i < 5").await.is_err());
+
+        table.set_soft_delete(true).await.unwrap();
+        assert!(table.soft_delete().await.unwrap());
+        table.delete("i < 5").await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 5);
+        // Appended rows are not deleted
+        table
+            .add(merge_insert_test_batches(10, 1))
+            .execute()
+            .await
+            .unwrap();
         assert_eq!(table.count_rows(None).await.unwrap(), 15);
 
-        // Create new data with i=15..25 (no id matches)
-        let new_batches = Box::new(merge_insert_test_batches(15, 2));
-        // Perform a "bulk update" (should not affect anything)
-        let mut merge_insert_builder = table.merge_insert(&["i"]);
-        merge_insert_builder.when_matched_update_all(None);
-        merge_insert_builder.execute(new_batches).await.unwrap();
-        // No new rows should have been inserted
+        let batches = table
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 15);
+        assert!(batches[0].column_by_name(DELETED_COLUMN).is_none());
+
+        let deleted = table
+            .query()
+            .with_deleted_rows()
+            .only_if("_deleted = true")
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(deleted.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+        assert!(deleted[0].column_by_name(DELETED_COLUMN).is_some());
+
+        table.undelete("i < 2").await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 17);
+
+        // Disabling removes the deleted rows for good
+        table.set_soft_delete(false).await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 17);
+        let schema = table.schema().await.unwrap();
+        assert!(schema.field_with_name(DELETED_COLUMN).is_err());
+        table.delete("i < 2").await.unwrap();
         assert_eq!(table.count_rows(None).await.unwrap(), 15);
-        assert_eq!(
-            table.count_rows(Some("age = 2".to_string())).await.unwrap(),
-            0
-        );
+    }
 
-        // Conditional update that only replaces the age=0 data
-        let new_batches = Box::new(merge_insert_test_batches(5, 3));
-        let mut merge_insert_builder = table.merge_insert(&["i"]);
-        merge_insert_builder.when_matched_update_all(Some("target.age = 0".to_string()));
-        merge_insert_builder.execute(new_batches).await.unwrap();
-        assert_eq!(
-            table.count_rows(Some("age = 3".to_string())).await.unwrap(),
-            5
-        );
+    #[tokio::test]
+    async fn test_history() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri)
+            .commit_hook(Arc::new(audit::AuditLog::default()))
+            .execute()
+            .await
+            .unwrap();
+        conn.create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        let table = conn
+            .open_table("test")
+            .audit_context("job-42")
+            .execute()
+            .await
+            .unwrap();
+        table.delete("i < 5").await.unwrap();
+        table
+            .add(merge_insert_test_batches(10, 1))
+            .execute()
+            .await
+            .unwrap();
+
+        let history = table.history().await.unwrap();
+        let operations = history
+            .iter()
+            .map(|record| record.operation.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(operations, ["create", "delete", "add"]);
+        // The context is only set on the handle that was opened with it
+        assert_eq!(history[0].context, None);
+        assert_eq!(history[1].details.as_deref(), Some("i < 5"));
+        assert_eq!(history[1].context.as_deref(), Some("job-42"));
+        assert_eq!(history[2].rows, Some(10));
+        assert_eq!(history[2].version, table.version().await.unwrap());
     }
 
     #[tokio::test]
-    async fn test_add_overwrite() {
+    async fn test_commit_metadata() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
         let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .write_options(WriteOptions::default().commit_metadata([("job_id", "load-1")]))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(10, 1))
+            .write_options(
+                WriteOptions::default()
+                    .commit_metadata([("author", "etl"), ("reason", "daily refresh")]),
+            )
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(20, 2))
+            .execute()
+            .await
+            .unwrap();
 
-        let batches = make_test_batches();
-        let schema = batches.schema().clone();
-        let table = conn.create_table("test", batches).execute().await.unwrap();
-        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        let versions = table.list_versions().await.unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0].metadata.get("job_id").unwrap(), "load-1");
+        assert_eq!(versions[1].metadata.get("author").unwrap(), "etl");
+        assert_eq!(versions[1].metadata.get("reason").unwrap(), "daily refresh");
+        assert!(versions[2].metadata.get("author").is_none());
+
+        let err = table
+            .add(merge_insert_test_batches(30, 3))
+            .write_options(
+                WriteOptions::default()
+                    .bulk_load(true)
+                    .commit_metadata([("author", "etl")]),
+            )
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
 
-        let batches = vec![RecordBatch::try_new(
-            schema.clone(),
-            vec![Arc::new(Int32Array::from_iter_values(100..110))],
-        )
-        .unwrap()]
-        .into_iter()
-        .map(Ok);
+    #[tokio::test]
+    async fn test_table_metadata() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        table.set_soft_delete(true).await.unwrap();
+        assert!(table.metadata().await.unwrap().is_empty());
 
-        let new_batches = RecordBatchIterator::new(batches.clone(), schema.clone());
+        table
+            .set_metadata(user_metadata::DESCRIPTION_KEY, Some("test rows"))
+            .await
+            .unwrap();
+        table
+            .set_column_metadata("age", user_metadata::DESCRIPTION_KEY, Some("in years"))
+            .await
+            .unwrap();
+        let metadata = table.metadata().await.unwrap();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[user_metadata::DESCRIPTION_KEY], "test rows");
+        let column = table.column_metadata("age").await.unwrap();
+        assert_eq!(column[user_metadata::DESCRIPTION_KEY], "in years");
+        assert!(table.column_metadata("i").await.unwrap().is_empty());
+        // The settings of the table are kept
+        assert!(table.soft_delete().await.unwrap());
+
+        let schema = table.schema().await.unwrap();
+        assert_eq!(
+            schema.field_with_name("age").unwrap().metadata()[user_metadata::DESCRIPTION_KEY],
+            "in years"
+        );
 
-        // Can overwrite using AddDataOptions::mode
         table
-            .add(new_batches)
-            .mode(AddDataMode::Overwrite)
-            .execute()
+            .set_metadata(user_metadata::DESCRIPTION_KEY, None)
             .await
             .unwrap();
-        assert_eq!(table.count_rows(None).await.unwrap(), 10);
-        assert_eq!(table.name(), "test");
+        assert!(table.metadata().await.unwrap().is_empty());
+        assert!(table.column_metadata("missing").await.is_err());
+        assert!(table
+            .set_metadata("lancedb:soft_delete", Some("false"))
+            .await
+            .is_err());
+    }
 
-        // Can overwrite using underlying WriteParams (which
-        // take precedence over AddDataOptions::mode)
+    #[tokio::test]
+    async fn test_io_stats() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        let created = table.io_stats().unwrap();
+        assert!(created.put_requests > 0);
+        assert!(created.put_bytes > 0);
 
-        let param: WriteParams = WriteParams {
-            mode: WriteMode::Overwrite,
-            ..Default::default()
-        };
+        // Each handle has its own statistics
+        let table = conn.open_table("test").execute().await.unwrap();
+        let opened = table.io_stats().unwrap();
+        assert_eq!(opened.put_requests, 0);
+        assert!(opened.get_requests > 0);
 
-        let new_batches = RecordBatchIterator::new(batches.clone(), schema.clone());
         table
-            .add(new_batches)
-            .write_options(WriteOptions {
-                lance_write_params: Some(param),
-            })
-            .mode(AddDataMode::Append)
+            .query()
             .execute()
             .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
             .unwrap();
-        assert_eq!(table.count_rows(None).await.unwrap(), 10);
-        assert_eq!(table.name(), "test");
+        let queried = table.io_stats().unwrap();
+        assert!(queried.get_bytes > opened.get_bytes);
+        assert_eq!(queried.failed_requests, opened.failed_requests);
+        assert!(queried.total_latency >= queried.max_latency);
     }
 
     #[tokio::test]
-    async fn test_update_with_predicate() {
+    async fn test_tiering_report() {
         let tmp_dir = tempdir().unwrap();
-        let dataset_path = tmp_dir.path().join("test.lance");
-        let uri = dataset_path.to_str().unwrap();
-        let conn = connect(uri)
-            .read_consistency_interval(Duration::from_secs(0))
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add(merge_insert_test_batches(10, 1))
             .execute()
             .await
             .unwrap();
 
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("id", DataType::Int32, false),
-            Field::new("name", DataType::Utf8, false),
-        ]));
-
-        let record_batch_iter = RecordBatchIterator::new(
-            vec![RecordBatch::try_new(
-                schema.clone(),
-                vec![
-                    Arc::new(Int32Array::from_iter_values(0..10)),
-                    Arc::new(StringArray::from_iter_values(vec![
-                        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j",
-                    ])),
-                ],
-            )
-            .unwrap()]
-            .into_iter()
-            .map(Ok),
-            schema.clone(),
-        );
+        // Tiering is not used for local tables, which are already local
+        let report = table.tiering_report().await.unwrap();
+        assert!(!report.enabled);
+        assert_eq!(report.local_fragments(), vec![0, 1]);
+        assert_eq!(report.local_bytes(), 0);
+    }
 
+    #[tokio::test]
+    async fn test_resumable_scan() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
         let table = conn
-            .create_table("my_table", record_batch_iter)
+            .create_table("test", merge_insert_test_batches(0, 0))
             .execute()
             .await
             .unwrap();
+        for offset in [10, 20] {
+            table
+                .add(merge_insert_test_batches(offset, 1))
+                .execute()
+                .await
+                .unwrap();
+        }
+        let options = QueryExecutionOptions {
+            max_batch_length: 4,
+            ..Default::default()
+        };
+        let ids = |batch: &RecordBatch| batch["i"].as_primitive::<Int32Type>().values().to_vec();
+
+        // Stop in the middle of the first fragment
+        let query = table.query();
+        let mut stream = query
+            .execute_resumable_with_options(None, options.clone())
+            .await
+            .unwrap();
+        let mut rows = Vec::new();
+        let mut token = String::new();
+        for _ in 0..2 {
+            let item = stream.try_next().await.unwrap().unwrap();
+            rows.extend(ids(&item.batch));
+            token = item.checkpoint.to_token();
+        }
+        drop(stream);
+        let checkpoint = ScanCheckpoint::from_token(&token).unwrap();
+        assert_eq!(checkpoint.fragment_id, 0);
+        assert_eq!(checkpoint.fragment_rows, 8);
 
+        // Rows written after the scan started are not read when resuming
         table
-            .update()
-            .only_if("id > 5")
-            .column("name", "'foo'")
+            .add(merge_insert_test_batches(30, 1))
             .execute()
             .await
             .unwrap();
+        let batches = query
+            .execute_resumable_with_options(Some(checkpoint), options.clone())
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        for item in &batches {
+            rows.extend(ids(&item.batch));
+        }
+        assert_eq!(rows, (0..30).collect::<Vec<_>>());
+        let last = batches.last().unwrap().checkpoint.clone();
+        assert_eq!(last.fragment_id, 2);
+        assert_eq!(last.rows, 30);
+
+        // Resuming a completed scan returns nothing
+        let rest = query
+            .execute_resumable(Some(last))
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(rest.is_empty());
 
-        let mut batches = table
+        // The limit applies to the whole scan
+        let limited = table
             .query()
-            .select(Select::columns(&["id", "name"]))
-            .execute()
+            .only_if("age = 1")
+            .limit(15)
+            .execute_resumable(None)
             .await
             .unwrap()
             .try_collect::<Vec<_>>()
             .await
             .unwrap();
+        assert_eq!(limited.last().unwrap().checkpoint.rows, 15);
 
-        while let Some(batch) = batches.pop() {
-            let ids = batch
-                .column(0)
-                .as_any()
-                .downcast_ref::<Int32Array>()
-                .unwrap()
-                .iter()
-                .collect::<Vec<_>>();
-            let names = batch
-                .column(1)
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .unwrap()
-                .iter()
-                .collect::<Vec<_>>();
-            for (i, name) in names.iter().enumerate() {
-                let id = ids[i].unwrap();
-                let name = name.unwrap();
-                if id > 5 {
-                    assert_eq!(name, "foo");
-                } else {
-                    assert_eq!(name, &format!("{}", (b'a' + id as u8) as char));
-                }
-            }
-        }
+        assert!(matches!(
+            table
+                .query()
+                .order_by("i", SortOrder::Asc)
+                .execute_resumable(None)
+                .await,
+            Err(Error::InvalidInput { .. })
+        ));
     }
 
     #[tokio::test]
-    async fn test_update_all_types() {
+    async fn test_bulk_load() {
         let tmp_dir = tempdir().unwrap();
-        let dataset_path = tmp_dir.path().join("test.lance");
-        let uri = dataset_path.to_str().unwrap();
-        let conn = connect(uri)
-            .read_consistency_interval(Duration::from_secs(0))
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
             .execute()
             .await
             .unwrap();
-
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("int32", DataType::Int32, false),
-            Field::new("int64", DataType::Int64, false),
-            Field::new("uint32", DataType::UInt32, false),
-            Field::new("string", DataType::Utf8, false),
-            Field::new("large_string", DataType::LargeUtf8, false),
-            Field::new("float32", DataType::Float32, false),
-            Field::new("float64", DataType::Float64, false),
-            Field::new("bool", DataType::Boolean, false),
-            Field::new("date32", DataType::Date32, false),
-            Field::new(
-                "timestamp_ns",
-                DataType::Timestamp(TimeUnit::Nanosecond, None),
-                false,
-            ),
-            Field::new(
-                "timestamp_ms",
-                DataType::Timestamp(TimeUnit::Millisecond, None),
-                false,
-            ),
-            Field::new(
-                "vec_f32",
-                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
-                false,
-            ),
-            Field::new(
-                "vec_f64",
-                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), 2),
-                false,
-            ),
-        ]));
-
-        let record_batch_iter = RecordBatchIterator::new(
-            vec![RecordBatch::try_new(
-                schema.clone(),
-                vec![
-                    Arc::new(Int32Array::from_iter_values(0..10)),
-                    Arc::new(Int64Array::from_iter_values(0..10)),
-                    Arc::new(UInt32Array::from_iter_values(0..10)),
-                    Arc::new(StringArray::from_iter_values(vec![
-                        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j",
-                    ])),
-                    Arc::new(LargeStringArray::from_iter_values(vec![
-                        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j",
-                    ])),
-                    Arc::new(Float32Array::from_iter_values((0..10).map(|i| i as f32))),
-                    Arc::new(Float64Array::from_iter_values((0..10).map(|i| i as f64))),
-                    Arc::new(Into::<BooleanArray>::into(vec![
-                        true, false, true, false, true, false, true, false, true, false,
-                    ])),
-                    Arc::new(Date32Array::from_iter_values(0..10)),
-                    Arc::new(TimestampNanosecondArray::from_iter_values(0..10)),
-                    Arc::new(TimestampMillisecondArray::from_iter_values(0..10)),
-                    Arc::new(
-                        create_fixed_size_list(
-                            Float32Array::from_iter_values((0..20).map(|i| i as f32)),
-                            2,
-                        )
-                        .unwrap(),
-                    ),
-                    Arc::new(
-                        create_fixed_size_list(
-                            Float64Array::from_iter_values((0..20).map(|i| i as f64)),
-                            2,
-                        )
-                        .unwrap(),
-                    ),
-                ],
-            )
-            .unwrap()]
-            .into_iter()
-            .map(Ok),
-            schema.clone(),
-        );
-
-        let table = conn
-            .create_table("my_table", record_batch_iter)
+        table
+            .create_index(&["i"], Index::BTree(BTreeIndexBuilder::default()))
             .execute()
             .await
             .unwrap();
+        let version = table.version().await.unwrap();
 
-        // check it can do update for each type
-        let updates: Vec<(&str, &str)> = vec![
-            ("string", "'foo'"),
-            ("large_string", "'large_foo'"),
-            ("int32", "1"),
-            ("int64", "1"),
-            ("uint32", "1"),
-            ("float32", "1.0"),
-            ("float64", "1.0"),
-            ("bool", "true"),
-            ("date32", "1"),
-            ("timestamp_ns", "1"),
-            ("timestamp_ms", "1"),
-            ("vec_f32", "[1.0, 1.0]"),
-            ("vec_f64", "[1.0, 1.0]"),
-        ];
-
-        let mut update_op = table.update();
-        for (column, value) in updates {
-            update_op = update_op.column(column, value);
+        for offset in [10, 20] {
+            table
+                .add(merge_insert_test_batches(offset, 1))
+                .write_options(WriteOptions::default().bulk_load(true))
+                .execute()
+                .await
+                .unwrap();
         }
-        update_op.execute().await.unwrap();
-
-        let mut batches = table
-            .query()
-            .select(Select::columns(&[
-                "string",
-                "large_string",
-                "int32",
-                "int64",
-                "uint32",
-                "float32",
-                "float64",
-                "bool",
-                "date32",
-                "timestamp_ns",
-                "timestamp_ms",
-                "vec_f32",
-                "vec_f64",
-            ]))
+        // Staged data is not visible
+        assert_eq!(table.version().await.unwrap(), version);
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        let err = table
+            .add(merge_insert_test_batches(30, 1))
+            .mode(AddDataMode::Overwrite)
+            .write_options(WriteOptions::default().bulk_load(true))
             .execute()
             .await
-            .unwrap()
-            .try_collect::<Vec<_>>()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+
+        let stats = table.finish_bulk_load().await.unwrap();
+        assert_eq!(stats.fragments, 2);
+        assert_eq!(stats.rows, 20);
+        assert_eq!(stats.version, Some(version + 1));
+        assert_eq!(table.count_rows(None).await.unwrap(), 30);
+        assert_eq!(table.count_rows(Some("i >= 25".into())).await.unwrap(), 5);
+
+        assert_eq!(
+            table.finish_bulk_load().await.unwrap(),
+            BulkLoadStats::default()
+        );
+
+        // Discarding a load deletes its data files
+        let data_files = || {
+            std::fs::read_dir(tmp_dir.path().join("test.lance/data"))
+                .unwrap()
+                .count()
+        };
+        let files = data_files();
+        table
+            .add(merge_insert_test_batches(40, 1))
+            .write_options(WriteOptions::default().bulk_load(true))
+            .execute()
             .await
             .unwrap();
-        let batch = batches.pop().unwrap();
-
-        macro_rules! assert_column {
-            ($column:expr, $array_type:ty, $expected:expr) => {
-                let array = $column
-                    .as_any()
-                    .downcast_ref::<$array_type>()
-                    .unwrap()
-                    .iter()
-                    .collect::<Vec<_>>();
-                for v in array {
-                    assert_eq!(v, Some($expected));
-                }
-            };
+        assert_eq!(data_files(), files + 1);
+        assert_eq!(table.discard_bulk_load().await.unwrap(), 10);
+        assert_eq!(data_files(), files);
+        assert_eq!(table.count_rows(None).await.unwrap(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_reembed() {
+        struct Double {
+            fail: AtomicBool,
         }
 
-        assert_column!(batch.column(0), StringArray, "foo");
-        assert_column!(batch.column(1), LargeStringArray, "large_foo");
-        assert_column!(batch.column(2), Int32Array, 1);
-        assert_column!(batch.column(3), Int64Array, 1);
-        assert_column!(batch.column(4), UInt32Array, 1);
-        assert_column!(batch.column(5), Float32Array, 1.0);
-        assert_column!(batch.column(6), Float64Array, 1.0);
-        assert_column!(batch.column(7), BooleanArray, true);
-        assert_column!(batch.column(8), Date32Array, 1);
-        assert_column!(batch.column(9), TimestampNanosecondArray, 1);
-        assert_column!(batch.column(10), TimestampMillisecondArray, 1);
+        impl EmbeddingFunction for Double {
+            fn name(&self) -> &str {
+                "double"
+            }
 
-        let array = batch
-            .column(11)
-            .as_any()
-            .downcast_ref::<FixedSizeListArray>()
-            .unwrap()
-            .iter()
-            .collect::<Vec<_>>();
-        for v in array {
-            let v = v.unwrap();
-            let f32array = v.as_any().downcast_ref::<Float32Array>().unwrap();
-            for v in f32array {
-                assert_eq!(v, Some(1.0));
+            fn data_type(&self) -> DataType {
+                DataType::Float32
             }
-        }
 
-        let array = batch
-            .column(12)
-            .as_any()
-            .downcast_ref::<FixedSizeListArray>()
-            .unwrap()
-            .iter()
-            .collect::<Vec<_>>();
-        for v in array {
-            let v = v.unwrap();
-            let f64array = v.as_any().downcast_ref::<Float64Array>().unwrap();
-            for v in f64array {
-                assert_eq!(v, Some(1.0));
+            fn compute(&self, source: &dyn Array) -> Result<ArrayRef> {
+                let source = source.as_any().downcast_ref::<Int32Array>().unwrap();
+                if self.fail.load(Ordering::SeqCst) && source.values().iter().any(|i| *i >= 10) {
+                    return Err(Error::Runtime {
+                        message: "the model is unavailable".to_string(),
+                    });
+                }
+                Ok(Arc::new(Float32Array::from_iter_values(
+                    source.values().iter().map(|i| (i * 2) as f32),
+                )))
             }
         }
-    }
 
-    #[tokio::test]
-    async fn test_update_via_expr() {
         let tmp_dir = tempdir().unwrap();
-        let dataset_path = tmp_dir.path().join("test.lance");
-        let uri = dataset_path.to_str().unwrap();
-        let conn = connect(uri)
-            .read_consistency_interval(Duration::from_secs(0))
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
             .execute()
             .await
             .unwrap();
-        let tbl = conn
-            .create_table("my_table", make_test_batches())
+        table
+            .add(merge_insert_test_batches(10, 1))
             .execute()
             .await
             .unwrap();
-        assert_eq!(1, tbl.count_rows(Some("i == 0".to_string())).await.unwrap());
-        tbl.update().column("i", "i+1").execute().await.unwrap();
-        assert_eq!(0, tbl.count_rows(Some("i == 0".to_string())).await.unwrap());
-    }
+        table
+            .create_index(&["age"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+        let version = table.version().await.unwrap();
+        let function = Arc::new(Double {
+            fail: AtomicBool::new(true),
+        });
 
-    #[derive(Default, Debug)]
-    struct NoOpCacheWrapper {
-        called: AtomicBool,
+        assert!(matches!(
+            table.reembed("age", function.clone()).execute().await,
+            Err(Error::InvalidInput { .. })
+        ));
+        // Fails on the second fragment, the first one is kept for the next run
+        assert!(table
+            .reembed("age", function.clone())
+            .source_column("i")
+            .batch_size(3)
+            .execute()
+            .await
+            .is_err());
+        assert_eq!(table.version().await.unwrap(), version);
+
+        function.fail.store(false, Ordering::SeqCst);
+        let progress = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = progress.clone();
+        let report = table
+            .reembed("age", function.clone())
+            .source_column("i")
+            .batch_size(3)
+            .on_progress(move |p| seen.lock().unwrap().push(p))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(report.previous_version, version);
+        assert_eq!(report.fragments_resumed, 1);
+        assert_eq!(report.rows, 10);
+        assert_eq!(report.rebuilt_indices.len(), 1);
+        let progress = progress.lock().unwrap().clone();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].fragments_done, 2);
+        assert_eq!(progress[0].fragments_total, 2);
+
+        let schema = table.schema().await.unwrap();
+        assert_eq!(schema.fields().len(), 2);
+        assert_eq!(
+            schema.field_with_name("age").unwrap().data_type(),
+            &DataType::Float32
+        );
+        assert_eq!(
+            table.column_metadata("age").await.unwrap()[user_metadata::EMBEDDING_MODEL_KEY],
+            "double"
+        );
+        assert_eq!(table.count_rows(None).await.unwrap(), 20);
+        assert_eq!(
+            table
+                .count_rows(Some("age = 30.0 AND i = 15".to_string()))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(table.list_indices().await.unwrap().len(), 1);
+
+        // Keep the current embeddings to compare the models
+        let report = table
+            .reembed("age", function.clone())
+            .source_column("i")
+            .output_column("age_v2")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(report.fragments_resumed, 0);
+        assert_eq!(report.rows, 20);
+        assert_eq!(report.rebuilt_indices.len(), 1);
+        assert_eq!(table.schema().await.unwrap().fields().len(), 3);
+        assert_eq!(table.list_indices().await.unwrap().len(), 2);
+        assert_eq!(
+            table
+                .count_rows(Some("age = age_v2".to_string()))
+                .await
+                .unwrap(),
+            20
+        );
+        assert!(matches!(
+            table
+                .reembed("age", function)
+                .source_column("i")
+                .output_column("age_v2")
+                .execute()
+                .await,
+            Err(Error::InvalidInput { .. })
+        ));
     }
 
-    impl NoOpCacheWrapper {
-        fn called(&self) -> bool {
-            self.called.load(Ordering::Relaxed)
+    #[tokio::test]
+    async fn test_copy_to() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        table.delete("i < 3").await.unwrap();
+
+        let cloned = table.copy_to("copy").await.unwrap();
+        assert_eq!(cloned.name(), "copy");
+        assert_eq!(cloned.count_rows(None).await.unwrap(), 7);
+        cloned
+            .add(merge_insert_test_batches(10, 1))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(cloned.count_rows(None).await.unwrap(), 17);
+        assert_eq!(table.count_rows(None).await.unwrap(), 7);
+
+        let reopened = conn.open_table("copy").execute().await.unwrap();
+        assert_eq!(reopened.count_rows(None).await.unwrap(), 17);
+        assert!(matches!(
+            table.copy_to("copy").await,
+            Err(Error::TableAlreadyExists { .. })
+        ));
+
+        // On a local file system the data files are hard links, not copies
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let shared = std::fs::read_dir(tmp_dir.path().join("test.lance").join("data"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .filter(|name| tmp_dir.path().join("copy.lance/data").join(name).exists())
+                .collect::<Vec<_>>();
+            assert!(!shared.is_empty());
+            for name in shared {
+                let path = tmp_dir.path().join("copy.lance/data").join(name);
+                assert_eq!(std::fs::metadata(path).unwrap().nlink(), 2);
+            }
         }
+
+        // The clone does not depend on the files of the original table
+        conn.drop_table("test").await.unwrap();
+        assert_eq!(reopened.count_rows(Some("i < 3".into())).await.unwrap(), 0);
+        assert!(reopened.verify().await.unwrap().is_ok());
     }
 
-    impl WrappingObjectStore for NoOpCacheWrapper {
-        fn wrap(
-            &self,
-            original: Arc<dyn object_store::ObjectStore>,
-        ) -> Arc<dyn object_store::ObjectStore> {
-            self.called.store(true, Ordering::Relaxed);
-            original
-        }
+    #[tokio::test]
+    async fn test_prune_dry_run() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        table.delete("i < 3").await.unwrap();
+        table.delete("i < 5").await.unwrap();
+        let preview = table
+            .preview_prune(chrono::Duration::zero(), Some(true))
+            .await
+            .unwrap();
+        assert_eq!(preview.versions, vec![1, 2]);
+        assert!(preview.files.contains(&"_versions/1.manifest".to_string()));
+        // The deletion file of version 2 is replaced in version 3
+        assert!(preview.files.iter().any(|f| f.starts_with("_deletions/")));
+        assert!(!preview.files.iter().any(|f| f.starts_with("data/")));
+        assert!(preview.bytes > 0);
+        // Nothing was removed
+        table.checkout(1).await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        table.checkout_latest().await.unwrap();
+
+        let stats = table
+            .optimize(OptimizeAction::Prune {
+                older_than: chrono::Duration::zero(),
+                delete_unverified: Some(true),
+            })
+            .await
+            .unwrap();
+        assert_eq!(stats.prune.unwrap().old_versions, 2);
     }
 
     #[tokio::test]
-    async fn test_open_table_options() {
+    async fn test_constraints() {
         let tmp_dir = tempdir().unwrap();
-        let dataset_path = tmp_dir.path().join("test.lance");
-        let uri = dataset_path.to_str().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
         let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        let range = Constraint::Range {
+            min: Some(0.0),
+            max: Some(5.0),
+        };
+        table
+            .set_constraints("age", vec![Constraint::NotNull, range.clone()])
+            .await
+            .unwrap();
+        assert_eq!(
+            table.constraints().await.unwrap()["age"],
+            vec![Constraint::NotNull, range]
+        );
+        assert!(table.set_constraints("nope", vec![]).await.is_ok());
+        assert!(table
+            .set_constraints("nope", vec![Constraint::NotNull])
+            .await
+            .is_err());
 
-        let batches = make_test_batches();
+        let err = table
+            .add(merge_insert_test_batches(10, 9))
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("violates constraints"), "{}", err);
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        table
+            .add(merge_insert_test_batches(10, 5))
+            .execute()
+            .await
+            .unwrap();
 
-        conn.create_table("my_table", batches)
+        // Only the updated rows are checked
+        assert!(table
+            .update()
+            .only_if("i < 5")
+            .column("age", "age + 7")
+            .execute()
+            .await
+            .is_err());
+        table
+            .update()
+            .only_if("i < 5")
+            .column("age", "age + 1")
             .execute()
             .await
             .unwrap();
+        assert_eq!(
+            table.count_rows(Some("age = 1".to_string())).await.unwrap(),
+            5
+        );
 
-        let wrapper = Arc::new(NoOpCacheWrapper::default());
+        let mut merge_insert = table.merge_insert(&["i"]);
+        merge_insert.when_not_matched_insert_all();
+        assert!(merge_insert
+            .execute(Box::new(merge_insert_test_batches(20, 8)))
+            .await
+            .is_err());
+        assert_eq!(table.count_rows(None).await.unwrap(), 20);
 
-        let object_store_params = ObjectStoreParams {
-            object_store_wrapper: Some(wrapper.clone()),
-            ..Default::default()
-        };
-        let param = ReadParams {
-            store_options: Some(object_store_params),
-            ..Default::default()
-        };
-        assert!(!wrapper.called());
-        conn.open_table("my_table")
-            .lance_read_params(param)
+        // The existing rows must satisfy new constraints
+        let err = table
+            .set_constraints(
+                "age",
+                vec![Constraint::Range {
+                    min: Some(2.0),
+                    max: None,
+                }],
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("existing rows"), "{}", err);
+
+        table.set_constraints("age", vec![]).await.unwrap();
+        assert!(table.constraints().await.unwrap().is_empty());
+        table
+            .add(merge_insert_test_batches(30, 9))
             .execute()
             .await
             .unwrap();
-        assert!(wrapper.called());
     }
 
-    fn merge_insert_test_batches(
-        offset: i32,
-        age: i32,
-    ) -> impl RecordBatchReader + Send + Sync + 'static {
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("i", DataType::Int32, false),
-            Field::new("age", DataType::Int32, false),
-        ]));
-        RecordBatchIterator::new(
-            vec![RecordBatch::try_new(
-                schema.clone(),
-                vec![
-                    Arc::new(Int32Array::from_iter_values(offset..(offset + 10))),
-                    Arc::new(Int32Array::from_iter_values(iter::repeat(age).take(10))),
-                ],
-            )],
-            schema,
-        )
+    #[derive(Debug, Default)]
+    struct RecordingHook {
+        commits: std::sync::Mutex<Vec<hooks::CommitInfo>>,
+        fail: bool,
     }
 
-    fn make_test_batches() -> impl RecordBatchReader + Send + Sync + 'static {
-        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
-        RecordBatchIterator::new(
-            vec![RecordBatch::try_new(
-                schema.clone(),
-                vec![Arc::new(Int32Array::from_iter_values(0..10))],
-            )],
-            schema,
-        )
+    #[async_trait]
+    impl hooks::CommitHook for RecordingHook {
+        async fn after_commit(&self, commit: &hooks::CommitInfo) -> Result<()> {
+            self.commits.lock().unwrap().push(commit.clone());
+            if self.fail {
+                return Err(Error::Runtime {
+                    message: "catalog unavailable".to_string(),
+                });
+            }
+            Ok(())
+        }
     }
 
-    #[tokio::test]
-    async fn test_create_index() {
-        use arrow_array::RecordBatch;
-        use arrow_schema::{DataType, Field, Schema as ArrowSchema};
-        use rand;
-        use std::iter::repeat_with;
+    #[derive(Debug)]
+    struct TenantInterceptor;
 
-        use arrow_array::Float32Array;
+    #[async_trait]
+    impl interceptor::QueryInterceptor for TenantInterceptor {
+        async fn intercept(
+            &self,
+            _table: &str,
+            _operation: interceptor::InterceptedOperation,
+        ) -> Result<interceptor::Restrictions> {
+            Ok(interceptor::Restrictions {
+                filter: Some("age = 1".to_string()),
+                masks: HashMap::from([("age".to_string(), MaskingPolicy::Null)]),
+            })
+        }
+    }
 
+    #[tokio::test]
+    async fn test_query_interceptor() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
-        let conn = connect(uri).execute().await.unwrap();
-
-        let dimension = 16;
-        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
-            "embeddings",
-            DataType::FixedSizeList(
-                Arc::new(Field::new("item", DataType::Float32, true)),
-                dimension,
-            ),
-            false,
-        )]));
-
-        let mut rng = rand::thread_rng();
-        let float_arr = Float32Array::from(
-            repeat_with(|| rng.gen::<f32>())
-                .take(512 * dimension as usize)
-                .collect::<Vec<f32>>(),
-        );
-
-        let vectors = Arc::new(create_fixed_size_list(float_arr, dimension).unwrap());
-        let batches = RecordBatchIterator::new(
-            vec![RecordBatch::try_new(schema.clone(), vec![vectors.clone()]).unwrap()]
-                .into_iter()
-                .map(Ok),
-            schema,
-        );
-
-        let table = conn.create_table("test", batches).execute().await.unwrap();
+        let admin = connect(uri).execute().await.unwrap();
+        let all = admin
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        all.add(merge_insert_test_batches(10, 1))
+            .execute()
+            .await
+            .unwrap();
 
+        let tenant = connect(uri)
+            .query_interceptor(Arc::new(TenantInterceptor))
+            .execute()
+            .await
+            .unwrap()
+            .open_table("test")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(tenant.count_rows(None).await.unwrap(), 10);
         assert_eq!(
-            table
-                .as_native()
-                .unwrap()
-                .count_indexed_rows("my_index")
+            tenant
+                .count_rows(Some("i >= 15".to_string()))
                 .await
                 .unwrap(),
-            None
+            5
+        );
+        // The mask applies even though the mandatory filter uses the column
+        assert!(tenant
+            .count_rows(Some("age = 0".to_string()))
+            .await
+            .is_err());
+        let batches = tenant
+            .query()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+        assert!(batches
+            .iter()
+            .all(|b| b["age"].null_count() == b.num_rows()));
+
+        tenant.delete("i < 12").await.unwrap();
+        tenant
+            .update()
+            .column("i", "i + 100")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(all.count_rows(None).await.unwrap(), 18);
+        assert_eq!(
+            all.count_rows(Some("i >= 100".to_string())).await.unwrap(),
+            8
         );
         assert_eq!(
-            table
-                .as_native()
-                .unwrap()
-                .count_unindexed_rows("my_index")
+            all.count_rows(Some("age = 0 AND i < 10".to_string()))
                 .await
                 .unwrap(),
-            None
+            10
         );
+    }
 
-        table
-            .create_index(&["embeddings"], Index::Auto)
+    #[tokio::test]
+    async fn test_query_interceptor_merge_insert() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let admin = connect(uri).execute().await.unwrap();
+        let all = admin
+            .create_table("test", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        all.add(merge_insert_test_batches(10, 1))
             .execute()
             .await
             .unwrap();
 
-        let index_configs = table.list_indices().await.unwrap();
-        assert_eq!(index_configs.len(), 1);
-        let index = index_configs.into_iter().next().unwrap();
-        assert_eq!(index.index_type, crate::index::IndexType::IvfPq);
-        assert_eq!(index.columns, vec!["embeddings".to_string()]);
-        assert_eq!(table.count_rows(None).await.unwrap(), 512);
-        assert_eq!(table.name(), "test");
-
-        let indices = table.as_native().unwrap().load_indices().await.unwrap();
-        let index_uuid = &indices[0].index_uuid;
+        let tenant = connect(uri)
+            .query_interceptor(Arc::new(TenantInterceptor))
+            .execute()
+            .await
+            .unwrap()
+            .open_table("test")
+            .execute()
+            .await
+            .unwrap();
+        // i=5..15 matches five rows of each tenant
+        let mut merge_insert = tenant.merge_insert(&["i"]);
+        merge_insert
+            .when_matched_update_all(None)
+            .when_not_matched_by_source_delete(None);
+        merge_insert
+            .execute(Box::new(merge_insert_test_batches(5, 2)))
+            .await
+            .unwrap();
+        // Only the rows of the tenant (age = 1) are updated or deleted
+        assert_eq!(all.count_rows(None).await.unwrap(), 15);
         assert_eq!(
-            table
-                .as_native()
-                .unwrap()
-                .count_indexed_rows(index_uuid)
-                .await
-                .unwrap(),
-            Some(512)
+            all.count_rows(Some("age = 0".to_string())).await.unwrap(),
+            10
         );
         assert_eq!(
-            table
-                .as_native()
-                .unwrap()
-                .count_unindexed_rows(index_uuid)
+            all.count_rows(Some("age = 2 AND i >= 10 AND i < 15".to_string()))
                 .await
                 .unwrap(),
-            Some(0)
+            5
         );
+
+        assert!(tenant
+            .merge("i", "id")
+            .execute(merge_test_batches((0..20).map(Some).collect()))
+            .await
+            .is_err());
     }
 
-    fn create_fixed_size_list<T: Array>(values: T, list_size: i32) -> Result<FixedSizeListArray> {
-        let list_type = DataType::FixedSizeList(
-            Arc::new(Field::new("item", values.data_type().clone(), true)),
-            list_size,
-        );
-        let data = ArrayDataBuilder::new(list_type)
-            .len(values.len() / list_size as usize)
-            .add_child_data(values.into_data())
-            .build()
+    #[tokio::test]
+    async fn test_commit_hooks() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let hook = Arc::new(RecordingHook::default());
+        let conn = connect(uri)
+            .commit_hook(hook.clone())
+            .execute()
+            .await
             .unwrap();
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        table.add(make_test_batches()).execute().await.unwrap();
+        table.delete("i < 5").await.unwrap();
 
-        Ok(FixedSizeListArray::from(data))
-    }
-
-    fn some_sample_data() -> Box<dyn RecordBatchReader + Send> {
-        let batch = RecordBatch::try_new(
-            Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)])),
-            vec![Arc::new(Int32Array::from(vec![1]))],
-        )
-        .unwrap();
-        let schema = batch.schema().clone();
-        let batch = Ok(batch);
+        let commits = hook.commits.lock().unwrap().clone();
+        let summary = commits
+            .iter()
+            .map(|c| (c.operation.as_str(), c.version, c.rows))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("create", 1, None),
+                ("add", 2, Some(10)),
+                ("delete", 3, None)
+            ]
+        );
+        assert!(commits.iter().all(|c| c.table == "test"));
 
-        Box::new(RecordBatchIterator::new(vec![batch], schema))
+        // A failing hook is reported, but the commit is kept
+        let failing = Arc::new(RecordingHook {
+            fail: true,
+            ..Default::default()
+        });
+        let table = conn
+            .open_table("test")
+            .commit_hook(failing.clone())
+            .execute()
+            .await
+            .unwrap();
+        let err = table.add(make_test_batches()).execute().await.unwrap_err();
+        assert!(err.to_string().contains("catalog unavailable"), "{}", err);
+        assert_eq!(table.version().await.unwrap(), 4);
+        assert_eq!(hook.commits.lock().unwrap().len(), 4);
+        assert_eq!(failing.commits.lock().unwrap().len(), 1);
     }
 
     #[tokio::test]
-    async fn test_create_scalar_index() {
+    async fn test_temporal_validity() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
 
+        use chrono::TimeZone;
+        let time = |date: &str| {
+            let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+            chrono::Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        };
+        let micros = |date: &str| time(date).timestamp_micros();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "valid_from",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+            Field::new(
+                "valid_to",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+        ]));
+        // Row 1 changed on 2024-02-01, row 2 was added on 2024-01-15
         let batch = RecordBatch::try_new(
-            Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)])),
-            vec![Arc::new(Int32Array::from(vec![1]))],
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 2])),
+                Arc::new(arrow_array::TimestampMicrosecondArray::from(vec![
+                    micros("2024-01-01"),
+                    micros("2024-02-01"),
+                    micros("2024-01-15"),
+                ])),
+                Arc::new(arrow_array::TimestampMicrosecondArray::from(vec![
+                    Some(micros("2024-02-01")),
+                    None,
+                    None,
+                ])),
+            ],
         )
         .unwrap();
-        let conn = ConnectBuilder::new(uri).execute().await.unwrap();
         let table = conn
             .create_table(
-                "my_table",
-                RecordBatchIterator::new(vec![Ok(batch.clone())], batch.schema()),
+                "history",
+                RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
             )
             .execute()
             .await
             .unwrap();
 
-        // Can create an index on a scalar column (will default to btree)
-        table
-            .create_index(&["i"], Index::Auto)
-            .execute()
-            .await
-            .unwrap();
-
-        let index_configs = table.list_indices().await.unwrap();
-        assert_eq!(index_configs.len(), 1);
-        let index = index_configs.into_iter().next().unwrap();
-        assert_eq!(index.index_type, crate::index::IndexType::BTree);
-        assert_eq!(index.columns, vec!["i".to_string()]);
+        let query = table.query().as_of(time("2024-01-10"));
+        assert!(matches!(
+            query.execute().await,
+            Err(Error::InvalidInput { .. })
+        ));
 
-        // Can also specify btree
+        assert!(table
+            .set_temporal_validity(Some(TemporalValidity::new("valid_from", "id")))
+            .await
+            .is_err());
+        let validity = TemporalValidity::new("valid_from", "valid_to");
         table
-            .create_index(&["i"], Index::BTree(BTreeIndexBuilder::default()))
-            .execute()
+            .set_temporal_validity(Some(validity.clone()))
             .await
             .unwrap();
-
-        let index_configs = table.list_indices().await.unwrap();
-        assert_eq!(index_configs.len(), 1);
-        let index = index_configs.into_iter().next().unwrap();
-        assert_eq!(index.index_type, crate::index::IndexType::BTree);
-        assert_eq!(index.columns, vec!["i".to_string()]);
-    }
-
-    #[tokio::test]
-    async fn test_read_consistency_interval() {
-        let intervals = vec![
-            None,
-            Some(0),
-            Some(100), // 100 ms
-        ];
-
-        for interval in intervals {
-            let data = some_sample_data();
-
-            let tmp_dir = tempdir().unwrap();
-            let uri = tmp_dir.path().to_str().unwrap();
-
-            let conn1 = ConnectBuilder::new(uri).execute().await.unwrap();
-            let table1 = conn1
-                .create_empty_table("my_table", data.schema())
+        assert_eq!(table.temporal_validity().await.unwrap(), Some(validity));
+
+        for (date, filter, expected) in [
+            ("2023-12-31", None, 0),
+            ("2024-01-10", None, 1),
+            ("2024-01-20", None, 2),
+            ("2024-02-01", None, 2),
+            ("2024-02-01", Some("id = 1"), 1),
+        ] {
+            let mut query = table.query().as_of(time(date));
+            if let Some(filter) = filter {
+                query = query.only_if(filter);
+            }
+            let batches = query
                 .execute()
                 .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
                 .unwrap();
-
-            let mut conn2 = ConnectBuilder::new(uri);
-            if let Some(interval) = interval {
-                conn2 = conn2.read_consistency_interval(std::time::Duration::from_millis(interval));
-            }
-            let conn2 = conn2.execute().await.unwrap();
-            let table2 = conn2.open_table("my_table").execute().await.unwrap();
-
-            assert_eq!(table1.count_rows(None).await.unwrap(), 0);
-            assert_eq!(table2.count_rows(None).await.unwrap(), 0);
-
-            table1.add(data).execute().await.unwrap();
-            assert_eq!(table1.count_rows(None).await.unwrap(), 1);
-
-            match interval {
-                None => {
-                    assert_eq!(table2.count_rows(None).await.unwrap(), 0);
-                    table2.checkout_latest().await.unwrap();
-                    assert_eq!(table2.count_rows(None).await.unwrap(), 1);
-                }
-                Some(0) => {
-                    assert_eq!(table2.count_rows(None).await.unwrap(), 1);
-                }
-                Some(100) => {
-                    assert_eq!(table2.count_rows(None).await.unwrap(), 0);
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                    assert_eq!(table2.count_rows(None).await.unwrap(), 1);
-                }
-                _ => unreachable!(),
-            }
+            let num_rows = batches.iter().map(|b| b.num_rows()).sum::<usize>();
+            assert_eq!(num_rows, expected, "as of {}", date);
         }
+
+        table.set_temporal_validity(None).await.unwrap();
+        assert_eq!(table.temporal_validity().await.unwrap(), None);
     }
 
     #[tokio::test]
-    async fn test_time_travel_write() {
+    async fn test_masking_policies() {
         let tmp_dir = tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
 
-        let conn = ConnectBuilder::new(uri)
-            .read_consistency_interval(Duration::from_secs(0))
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("ssn", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..3)),
+                Arc::new(StringArray::from(vec!["111", "222", "333"])),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
             .execute()
             .await
             .unwrap();
-        let table = conn
-            .create_table("my_table", some_sample_data())
+
+        // Policies can only be changed with unmasked access
+        let err = table
+            .set_masking_policy("ssn", Some(MaskingPolicy::Hash))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PermissionDenied { .. }));
+
+        let admin = conn.open_table("test").unmasked().execute().await.unwrap();
+        assert!(admin
+            .set_masking_policy("nope", Some(MaskingPolicy::Hash))
+            .await
+            .is_err());
+        assert!(admin
+            .set_masking_policy("id", Some(MaskingPolicy::Hash))
+            .await
+            .is_err());
+        admin
+            .set_masking_policy("ssn", Some(MaskingPolicy::Hash))
+            .await
+            .unwrap();
+        assert_eq!(
+            admin.masking_policies().await.unwrap(),
+            HashMap::from([("ssn".to_string(), MaskingPolicy::Hash)])
+        );
+
+        let read_ssns = |table: Table| async move {
+            let batches = table
+                .query()
+                .execute()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            batches[0]["ssn"]
+                .as_string::<i32>()
+                .iter()
+                .map(|v| v.unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let reader = conn.open_table("test").execute().await.unwrap();
+        let masked = read_ssns(reader.clone()).await;
+        assert_eq!(masked.len(), 3);
+        assert!(masked.iter().all(|v| v.len() == 64));
+        assert_eq!(read_ssns(admin.clone()).await, vec!["111", "222", "333"]);
+
+        // Masked columns can not be used to probe for values
+        let err = reader
+            .query()
+            .only_if("ssn = '111'")
             .execute()
             .await
+            .err()
             .unwrap();
-        let version = table.version().await.unwrap();
-        table.add(some_sample_data()).execute().await.unwrap();
-        table.checkout(version).await.unwrap();
-        assert!(table.add(some_sample_data()).execute().await.is_err())
+        assert!(matches!(err, Error::PermissionDenied { .. }));
+        let err = reader
+            .count_rows(Some("ssn LIKE '1%'".to_string()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PermissionDenied { .. }));
+        assert_eq!(
+            reader.count_rows(Some("id > 0".to_string())).await.unwrap(),
+            2
+        );
+
+        admin.set_masking_policy("ssn", None).await.unwrap();
+        assert!(admin.masking_policies().await.unwrap().is_empty());
+        let reader = conn.open_table("test").execute().await.unwrap();
+        assert_eq!(read_ssns(reader).await, vec!["111", "222", "333"]);
     }
 }
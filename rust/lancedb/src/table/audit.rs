@@ -0,0 +1,160 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An audit log of the operations that modified a table
+//!
+//! [`AuditLog`] is a [commit hook](super::hooks) that records every commit
+//! made through a table handle: the operation, its parameters (e.g. the
+//! predicate of a delete), the context of the handle (see
+//! [`crate::connection::OpenTableBuilder::audit_context`]) and the new
+//! version.  Enable it for all tables of a connection with:
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use lancedb::table::audit::AuditLog;
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let db = lancedb::connect("/tmp/db")
+//!     .commit_hook(Arc::new(AuditLog::default()))
+//!     .execute()
+//!     .await
+//!     .unwrap();
+//! # });
+//! ```
+//!
+//! Each record is written as a small JSON object in the `_audit` directory of
+//! the table, named after the version, with the object store of the table, so
+//! the log works on any object store and is removed with the table.  [`crate::Table::history`] reads it back.
+//! Commits made without the hook, e.g. by other clients, are not recorded.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::TryStreamExt;
+use lance::io::ObjectStore;
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+use super::hooks::{CommitHook, CommitInfo};
+
+/// The directory of the audit records, relative to the table
+const AUDIT_DIR: &str = "_audit";
+
+/// A recorded operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// The version created by the operation
+    pub version: u64,
+    /// The operation, e.g. "add", "delete" or "create_index"
+    pub operation: String,
+    /// The parameters of the operation, if any, e.g. the predicate of a delete
+    pub details: Option<String>,
+    /// The context of the table handle that made the commit, if set
+    pub context: Option<String>,
+    /// The number of rows written, if known
+    pub rows: Option<usize>,
+    /// When the operation was recorded, as an RFC 3339 timestamp
+    pub recorded_at: String,
+}
+
+/// A commit hook that records every commit in the audit log of the table
+#[derive(Debug, Default)]
+pub struct AuditLog {}
+
+#[async_trait]
+impl CommitHook for AuditLog {
+    async fn after_commit(&self, commit: &CommitInfo) -> Result<()> {
+        let record = AuditRecord {
+            version: commit.version,
+            operation: commit.operation.clone(),
+            details: commit.details.clone(),
+            context: commit.context.clone(),
+            rows: commit.rows,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let bytes = serde_json::to_vec(&record).map_err(|e| Error::Runtime {
+            message: format!("failed to serialize the audit record: {}", e),
+        })?;
+        let (store, base) =
+            ObjectStore::from_uri_and_params(&commit.uri, &commit.store_params).await?;
+        store
+            .inner
+            .put(&record_path(&base, commit.version), Bytes::from(bytes))
+            .await?;
+        Ok(())
+    }
+}
+
+fn record_path(base: &Path, version: u64) -> Path {
+    // Zero padded so that the records list in version order
+    base.child(AUDIT_DIR).child(format!("{:020}.json", version))
+}
+
+/// Read the audit log of the table at `base`, oldest first
+pub(crate) async fn read_history(store: &ObjectStore, base: &Path) -> Result<Vec<AuditRecord>> {
+    let mut objects = store
+        .inner
+        .list(Some(&base.child(AUDIT_DIR)))
+        .try_collect::<Vec<_>>()
+        .await?;
+    objects.sort_by(|a, b| a.location.cmp(&b.location));
+    let mut records = Vec::with_capacity(objects.len());
+    for object in objects {
+        let bytes = store.inner.get(&object.location).await?.bytes().await?;
+        let record = serde_json::from_slice(&bytes).map_err(|e| Error::Runtime {
+            message: format!("the audit record {} is invalid: {}", object.location, e),
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use lance::io::ObjectStoreParams;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().join("test.lance");
+        let uri = uri.to_str().unwrap();
+        let (store, base) = ObjectStore::from_uri(uri).await.unwrap();
+        assert!(read_history(&store, &base).await.unwrap().is_empty());
+
+        let log = AuditLog::default();
+        for (version, operation) in [(10, "delete"), (9, "add")] {
+            log.after_commit(&CommitInfo {
+                table: "test".to_string(),
+                uri: uri.to_string(),
+                store_params: ObjectStoreParams::default(),
+                version,
+                operation: operation.to_string(),
+                rows: None,
+                details: Some("id > 5".to_string()),
+                context: Some("job-1".to_string()),
+            })
+            .await
+            .unwrap();
+        }
+        let history = read_history(&store, &base).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].version, 9);
+        assert_eq!(history[0].operation, "add");
+        assert_eq!(history[1].details.as_deref(), Some("id > 5"));
+        assert_eq!(history[1].context.as_deref(), Some("job-1"));
+    }
+}
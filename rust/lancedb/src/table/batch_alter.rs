@@ -0,0 +1,355 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::compute::concat_batches;
+use arrow_array::RecordBatch;
+use arrow_schema::{Schema, SchemaRef};
+use futures::TryStreamExt;
+use lance::dataset::fragment::FileFragment;
+use lance::dataset::scanner::DatasetRecordBatchStream;
+use lance::dataset::transaction::Operation;
+use lance::dataset::{ColumnAlteration, Dataset, NewColumnTransform};
+use lance::datatypes::{Field as LanceField, Schema as LanceSchema};
+use lance::io::ObjectStoreParams;
+
+use crate::error::{Error, Result};
+
+use super::TableInternal;
+
+/// A builder for a schema change that is committed as a single version
+///
+/// See [`super::Table::batch_alter`] for more context
+pub struct BatchAlterBuilder {
+    table: Arc<dyn TableInternal>,
+    pub(super) new_columns: Option<(NewColumnTransform, Option<Vec<String>>)>,
+    pub(super) alterations: Vec<ColumnAlteration>,
+    pub(super) drop_columns: Vec<String>,
+}
+
+impl BatchAlterBuilder {
+    pub(super) fn new(table: Arc<dyn TableInternal>) -> Self {
+        Self {
+            table,
+            new_columns: None,
+            alterations: Vec::new(),
+            drop_columns: Vec::new(),
+        }
+    }
+
+    /// Add new columns, see [`super::Table::add_columns`]
+    ///
+    /// Only one set of new columns can be added per batch, calling this again
+    /// replaces the previous set.
+    pub fn add_columns(
+        mut self,
+        transforms: NewColumnTransform,
+        read_columns: Option<Vec<String>>,
+    ) -> Self {
+        self.new_columns = Some((transforms, read_columns));
+        self
+    }
+
+    /// Rename columns or change their nullability, see [`super::Table::alter_columns`]
+    ///
+    /// Alterations may refer to columns added in the same batch.
+    pub fn alter_columns(
+        mut self,
+        alterations: impl IntoIterator<Item = ColumnAlteration>,
+    ) -> Self {
+        self.alterations.extend(alterations);
+        self
+    }
+
+    /// Remove columns, see [`super::Table::drop_columns`]
+    ///
+    /// Columns are dropped after the alterations are applied, so a renamed
+    /// column must be referred to by its new name.
+    pub fn drop_columns(mut self, columns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.drop_columns
+            .extend(columns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Apply all of the changes, creating a single new version of the table
+    ///
+    /// If any change is invalid then nothing is applied.
+    pub async fn execute(self) -> Result<()> {
+        if self.new_columns.is_none() && self.alterations.is_empty() && self.drop_columns.is_empty()
+        {
+            return Err(Error::InvalidInput {
+                message: "a batch alter must contain at least one change".to_string(),
+            });
+        }
+        self.table.clone().batch_alter(self).await
+    }
+}
+
+/// The values of new columns computed from SQL expressions, for one fragment
+///
+/// The values are streamed and handed out in the batch sizes of the updater,
+/// so a fragment is never held in memory as a whole.
+struct SqlValues {
+    stream: DatasetRecordBatchStream,
+    schema: SchemaRef,
+    pending: Option<RecordBatch>,
+}
+
+impl SqlValues {
+    async fn try_new(fragment: &FileFragment, expressions: &[(String, String)]) -> Result<Self> {
+        if fragment.metadata().deletion_file.is_some() {
+            return Err(Error::NotSupported {
+                message: "SQL columns can not be added in a batch alter while the table has \
+                          deleted rows, compact the table first or use Table::add_columns"
+                    .to_string(),
+            });
+        }
+        let mut scanner = fragment.scan();
+        scanner.project_with_transform(expressions)?;
+        let schema = scanner.schema().await?;
+        let stream = scanner.try_into_stream().await?;
+        Ok(Self {
+            stream,
+            schema,
+            pending: None,
+        })
+    }
+
+    /// The values of the next `num_rows` rows
+    async fn next(&mut self, num_rows: usize) -> Result<RecordBatch> {
+        let mut parts = Vec::new();
+        let mut needed = num_rows;
+        while needed > 0 {
+            let batch = match self.pending.take() {
+                Some(batch) => batch,
+                None => self
+                    .stream
+                    .try_next()
+                    .await?
+                    .ok_or_else(|| Error::Runtime {
+                        message: "the fragment has fewer rows than expected".to_string(),
+                    })?,
+            };
+            if batch.num_rows() > needed {
+                self.pending = Some(batch.slice(needed, batch.num_rows() - needed));
+                parts.push(batch.slice(0, needed));
+                needed = 0;
+            } else {
+                needed -= batch.num_rows();
+                parts.push(batch);
+            }
+        }
+        Ok(concat_batches(&self.schema, &parts)?)
+    }
+}
+
+/// The schema of a dataset with new columns
+///
+/// The new fields get ids above every id used by the schema and by the data
+/// files of the dataset.  The data files may still contain columns that were
+/// dropped, and their ids must not be given to the new columns.
+pub(super) fn merge_new_columns(dataset: &Dataset, new_columns: &Schema) -> Result<LanceSchema> {
+    let schema = dataset.schema();
+    let mut merged = schema.merge(new_columns)?;
+    let max_id = schema.max_field_id().unwrap_or(-1);
+    let max_file_id = dataset
+        .get_fragments()
+        .iter()
+        .flat_map(|fragment| fragment.metadata().files.clone())
+        .flat_map(|file| file.fields)
+        .max()
+        .unwrap_or(-1);
+    if max_file_id > max_id {
+        for field in merged.fields.iter_mut() {
+            shift_ids(field, max_id, max_file_id - max_id);
+        }
+    }
+    Ok(merged)
+}
+
+/// Add `offset` to the ids above `max_id` of a field and its children
+fn shift_ids(field: &mut LanceField, max_id: i32, offset: i32) {
+    if field.id > max_id {
+        field.id += offset;
+    }
+    if field.parent_id > max_id {
+        field.parent_id += offset;
+    }
+    for child in field.children.iter_mut() {
+        shift_ids(child, max_id, offset);
+    }
+}
+
+/// Rename a column or change its nullability in a lance schema
+pub(super) fn apply_alteration(
+    schema: &mut LanceSchema,
+    alteration: &ColumnAlteration,
+) -> Result<()> {
+    let field = schema
+        .field(&alteration.path)
+        .ok_or_else(|| Error::InvalidInput {
+            message: format!(
+                "cannot alter '{}': the column does not exist",
+                alteration.path
+            ),
+        })?;
+    let id = field.id;
+    if let Some(new_name) = &alteration.rename {
+        // Only the last component of the path is renamed
+        let sibling_path = match alteration.path.rsplit_once('.') {
+            Some((parent, _)) => format!("{}.{}", parent, new_name),
+            None => new_name.clone(),
+        };
+        if schema.field(&sibling_path).is_some() {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "cannot rename '{}' to '{}': a column with that name already exists",
+                    alteration.path, new_name
+                ),
+            });
+        }
+    }
+    if alteration.nullable == Some(false) && field.nullable {
+        return Err(Error::NotSupported {
+            message: format!(
+                "cannot make '{}' non-nullable, it may already contain nulls",
+                alteration.path
+            ),
+        });
+    }
+    let field = schema.mut_field_by_id(id).unwrap();
+    if let Some(new_name) = &alteration.rename {
+        field.name = new_name.clone();
+    }
+    if let Some(nullable) = alteration.nullable {
+        field.nullable = nullable;
+    }
+    Ok(())
+}
+
+/// Apply a batch alter to the dataset and commit it as a single new version
+///
+/// All of the changes are validated against the schema first.  Then new
+/// column values are written to new data files, and the new data files and
+/// the final schema are committed together.  Readers see either the old
+/// schema or the final schema, never an intermediate one.
+pub(super) async fn apply_batch_alter(
+    dataset: &Dataset,
+    uri: &str,
+    alter: BatchAlterBuilder,
+    store_params: Option<ObjectStoreParams>,
+) -> Result<Dataset> {
+    let read_version = dataset.version().version;
+    let schema = dataset.schema().clone();
+    let mut fragments = dataset
+        .get_fragments()
+        .iter()
+        .map(|f| f.metadata().clone())
+        .collect::<Vec<_>>();
+
+    // The schema with the new columns, and the columns to write
+    let mut new_columns = None;
+    if let Some((transforms, read_columns)) = alter.new_columns {
+        let (output_schema, read_columns) = match &transforms {
+            NewColumnTransform::SqlExpressions(expressions) => {
+                let mut scanner = dataset.scan();
+                scanner.project_with_transform(expressions)?;
+                // The values are computed by a scan, the updater only reads
+                // some column to learn the size of each batch
+                (scanner.schema().await?, vec![schema.fields[0].name.clone()])
+            }
+            NewColumnTransform::BatchUDF(udf) => (
+                udf.output_schema.clone(),
+                read_columns
+                    .unwrap_or_else(|| schema.fields.iter().map(|f| f.name.clone()).collect()),
+            ),
+        };
+        for field in output_schema.fields() {
+            if schema.field(field.name()).is_some() {
+                return Err(Error::InvalidInput {
+                    message: format!("cannot add '{}': the column already exists", field.name()),
+                });
+            }
+        }
+
+        let merged = merge_new_columns(dataset, output_schema.as_ref())?;
+        let write_schema = merged.project_by_schema(output_schema.as_ref())?;
+        new_columns = Some((transforms, read_columns, write_schema, merged));
+    }
+
+    let mut schema = match &new_columns {
+        Some((_, _, _, merged)) => merged.clone(),
+        None => schema,
+    };
+    for alteration in &alter.alterations {
+        apply_alteration(&mut schema, alteration)?;
+    }
+
+    if !alter.drop_columns.is_empty() {
+        let drop_names = alter.drop_columns.iter().collect::<HashSet<_>>();
+        if let Some(missing) = alter
+            .drop_columns
+            .iter()
+            .find(|name| schema.field(name).is_none())
+        {
+            return Err(Error::InvalidInput {
+                message: format!("cannot drop '{}': the column does not exist", missing),
+            });
+        }
+        if schema.fields.iter().all(|f| drop_names.contains(&f.name)) {
+            return Err(Error::InvalidInput {
+                message: "cannot drop every column of a table".to_string(),
+            });
+        }
+        let to_drop = schema.project(&alter.drop_columns)?;
+        schema = schema.exclude(to_drop)?;
+    }
+
+    // Only write the new columns once every change is known to be valid, so
+    // that an invalid batch leaves no data files behind
+    if let Some((transforms, read_columns, write_schema, merged)) = new_columns {
+        let mut new_fragments = Vec::with_capacity(fragments.len());
+        for fragment in dataset.get_fragments() {
+            let mut computed = match &transforms {
+                NewColumnTransform::SqlExpressions(expressions) => {
+                    Some(SqlValues::try_new(&fragment, expressions).await?)
+                }
+                NewColumnTransform::BatchUDF(_) => None,
+            };
+            let mut updater = fragment
+                .updater(
+                    Some(&read_columns),
+                    Some((write_schema.clone(), merged.clone())),
+                )
+                .await?;
+            while let Some(batch) = updater.next().await? {
+                let new_batch = match (&mut computed, &transforms) {
+                    (Some(values), _) => values.next(batch.num_rows()).await?,
+                    (None, NewColumnTransform::BatchUDF(udf)) => (udf.mapper)(batch)?,
+                    (None, NewColumnTransform::SqlExpressions(_)) => {
+                        unreachable!("SQL values are computed for each fragment")
+                    }
+                };
+                updater.update(new_batch).await?;
+            }
+            new_fragments.push(updater.finish().await?);
+        }
+        fragments = new_fragments;
+    }
+
+    let operation = Operation::Merge { fragments, schema };
+    Ok(Dataset::commit(uri, operation, Some(read_version), store_params, None).await?)
+}
@@ -0,0 +1,208 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lazy access to large binary values
+//!
+//! Columns with large values (images, audio, documents) are expensive to read
+//! and to hold in memory.  A query with [`crate::query::QueryBase::lazy_blobs`]
+//! does not read the blob columns.  Instead the results contain the row id of
+//! each row and [`super::Table::blob_refs`] turns them into [`BlobRef`]s, which
+//! read a single value when [`BlobRef::read`] is called.
+//!
+//! The references read the version of the table that the query read, so they
+//! stay valid while the table is modified, as long as that version is not
+//! pruned.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{cast::AsArray, types::UInt64Type, Array, RecordBatch};
+use arrow_schema::{DataType, Schema};
+use bytes::Bytes;
+use futures::StreamExt;
+use lance::dataset::Dataset;
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+use crate::query::late::ROW_ID_COLUMN;
+use crate::query::{Select, VectorQuery};
+
+/// The schema metadata key listing the lazy blob columns of query results
+pub(crate) const LAZY_BLOBS_KEY: &str = "lancedb:lazy_blobs";
+/// The schema metadata key holding the table version read by the query
+pub(crate) const LAZY_BLOBS_VERSION_KEY: &str = "lancedb:lazy_blobs_version";
+
+/// A reference to a binary value that is read on demand
+#[derive(Clone)]
+pub struct BlobRef {
+    dataset: Arc<Dataset>,
+    column: String,
+    row_id: u64,
+}
+
+impl std::fmt::Debug for BlobRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlobRef")
+            .field("column", &self.column)
+            .field("row_id", &self.row_id)
+            .field("version", &self.dataset.version().version)
+            .finish()
+    }
+}
+
+impl BlobRef {
+    /// The column of the value
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// The row id of the row holding the value
+    pub fn row_id(&self) -> u64 {
+        self.row_id
+    }
+
+    /// Read the value, None if it is null
+    pub async fn read(&self) -> Result<Option<Bytes>> {
+        let projection = self.dataset.schema().project(&[&self.column])?;
+        let batch = self.dataset.take_rows(&[self.row_id], &projection).await?;
+        let values = batch.column(0);
+        if values.is_empty() || values.is_null(0) {
+            return Ok(None);
+        }
+        let value = match values.data_type() {
+            DataType::LargeBinary => values.as_binary::<i64>().value(0),
+            _ => values.as_binary::<i32>().value(0),
+        };
+        Ok(Some(Bytes::copy_from_slice(value)))
+    }
+}
+
+/// Remove the lazy blob columns from the projection of the query
+///
+/// Returns the lazy columns that were selected, the query is left untouched
+/// if there are none.
+pub(crate) fn plan_lazy_blobs(
+    schema: &Schema,
+    query: &mut VectorQuery,
+    columns: &[String],
+) -> Result<Vec<String>> {
+    for column in columns {
+        let field = schema
+            .field_with_name(column)
+            .map_err(|_| Error::InvalidInput {
+                message: format!("the blob column '{}' does not exist", column),
+            })?;
+        if !matches!(field.data_type(), DataType::Binary | DataType::LargeBinary) {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the blob column '{}' must be binary, not {}",
+                    column,
+                    field.data_type()
+                ),
+            });
+        }
+    }
+    let Some(selected) = query.base.select.column_names(schema)? else {
+        return Err(Error::InvalidInput {
+            message: "lazy blobs cannot be combined with a dynamic projection".to_string(),
+        });
+    };
+    let (lazy, eager): (Vec<_>, Vec<_>) = selected
+        .into_iter()
+        .partition(|column| columns.contains(column));
+    if !lazy.is_empty() {
+        query.base.select = Select::Columns(eager);
+        query.base.with_row_id = true;
+    }
+    Ok(lazy)
+}
+
+/// Record the lazy blob columns and the version read in the schema of the results
+pub(crate) fn mark_lazy_blobs(
+    stream: SendableRecordBatchStream,
+    columns: &[String],
+    version: u64,
+) -> Result<SendableRecordBatchStream> {
+    let mut metadata = stream.schema().metadata().clone();
+    let columns = serde_json::to_string(columns).map_err(|e| Error::Runtime {
+        message: format!("failed to serialize the lazy blob columns: {}", e),
+    })?;
+    metadata.insert(LAZY_BLOBS_KEY.to_string(), columns);
+    metadata.insert(LAZY_BLOBS_VERSION_KEY.to_string(), version.to_string());
+    let schema = Arc::new(stream.schema().as_ref().clone().with_metadata(metadata));
+    let batch_schema = schema.clone();
+    Ok(Box::pin(SimpleRecordBatchStream {
+        schema,
+        stream: stream.map(move |batch: Result<RecordBatch>| -> Result<RecordBatch> {
+            Ok(batch?.with_schema(batch_schema.clone())?)
+        }),
+    }))
+}
+
+/// The version read by the query that returned the batch, if it has the lazy column
+pub(crate) fn lazy_blobs_version(metadata: &HashMap<String, String>, column: &str) -> Result<u64> {
+    let not_lazy = || Error::InvalidInput {
+        message: format!(
+            "the column '{}' was not read lazily, see QueryBase::lazy_blobs",
+            column
+        ),
+    };
+    let columns = metadata.get(LAZY_BLOBS_KEY).ok_or_else(not_lazy)?;
+    let columns: Vec<String> = serde_json::from_str(columns).map_err(|_| not_lazy())?;
+    if !columns.iter().any(|c| c == column) {
+        return Err(not_lazy());
+    }
+    metadata
+        .get(LAZY_BLOBS_VERSION_KEY)
+        .and_then(|version| version.parse().ok())
+        .ok_or_else(not_lazy)
+}
+
+/// Create a reference to the value of the column for every row of the batch
+pub(crate) fn blob_refs(
+    dataset: Arc<Dataset>,
+    batch: &RecordBatch,
+    column: &str,
+) -> Result<Vec<BlobRef>> {
+    let row_ids = batch
+        .column_by_name(ROW_ID_COLUMN)
+        .ok_or_else(|| Error::InvalidInput {
+            message: format!("the column '{}' is missing from the batch", ROW_ID_COLUMN),
+        })?
+        .as_primitive::<UInt64Type>();
+    Ok(row_ids
+        .values()
+        .iter()
+        .map(|row_id| BlobRef {
+            dataset: dataset.clone(),
+            column: column.to_string(),
+            row_id: *row_id,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_blobs_version() {
+        let mut metadata = HashMap::new();
+        assert!(lazy_blobs_version(&metadata, "image").is_err());
+        metadata.insert(LAZY_BLOBS_KEY.to_string(), r#"["image"]"#.to_string());
+        metadata.insert(LAZY_BLOBS_VERSION_KEY.to_string(), "3".to_string());
+        assert_eq!(lazy_blobs_version(&metadata, "image").unwrap(), 3);
+        assert!(lazy_blobs_version(&metadata, "audio").is_err());
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use arrow_array::{RecordBatch, RecordBatchIterator};
+use arrow_schema::SchemaRef;
+use log::warn;
+
+use crate::error::{Error, Result};
+use crate::runtime;
+use crate::units::{IntoDuration, IntoSize};
+
+use super::Table;
+
+/// Controls when a [`BufferedWriter`] commits the data it has accumulated
+///
+/// The buffer is flushed as soon as any one of the thresholds is reached.
+#[derive(Debug, Clone)]
+pub struct BufferedWriterConfig {
+    /// Flush once this many rows are buffered
+    pub max_rows: usize,
+    /// Flush once the buffered batches use this many bytes of memory
+    pub max_bytes: usize,
+    /// Flush once the oldest buffered row has waited this long
+    pub max_delay: Duration,
+}
+
+impl BufferedWriterConfig {
+    /// Set [`Self::max_bytes`], the size can also be a string such as `"64MiB"`
+    pub fn with_max_bytes(mut self, max_bytes: impl IntoSize) -> Result<Self> {
+        self.max_bytes = max_bytes.into_size()?;
+        Ok(self)
+    }
+
+    /// Set [`Self::max_delay`], the delay can also be a string such as `"500ms"`
+    pub fn with_max_delay(mut self, max_delay: impl IntoDuration) -> Result<Self> {
+        self.max_delay = max_delay.into_duration()?;
+        Ok(self)
+    }
+}
+
+impl Default for BufferedWriterConfig {
+    fn default() -> Self {
+        Self {
+            max_rows: 10_000,
+            max_bytes: 16 * 1024 * 1024,
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    schema: Option<SchemaRef>,
+    batches: Vec<RecordBatch>,
+    rows: usize,
+    bytes: usize,
+    oldest: Option<Instant>,
+}
+
+impl Buffer {
+    fn take(&mut self) -> Vec<RecordBatch> {
+        self.rows = 0;
+        self.bytes = 0;
+        self.oldest = None;
+        std::mem::take(&mut self.batches)
+    }
+
+    /// Put back batches that failed to be written, ahead of any new batches
+    fn restore(&mut self, mut batches: Vec<RecordBatch>) {
+        self.rows += batches.iter().map(|b| b.num_rows()).sum::<usize>();
+        self.bytes += batches
+            .iter()
+            .map(|b| b.get_array_memory_size())
+            .sum::<usize>();
+        self.oldest = Some(Instant::now());
+        batches.append(&mut self.batches);
+        self.batches = batches;
+    }
+}
+
+struct Shared {
+    table: Table,
+    config: BufferedWriterConfig,
+    buffer: Mutex<Buffer>,
+    // Serializes commits so that data is written in the order it was buffered
+    flush_lock: tokio::sync::Mutex<()>,
+    // The error from the last background flush, reported on the next call
+    background_error: Mutex<Option<String>>,
+}
+
+impl Shared {
+    fn is_due(&self) -> bool {
+        let buffer = self.buffer.lock().unwrap();
+        buffer
+            .oldest
+            .map(|oldest| oldest.elapsed() >= self.config.max_delay)
+            .unwrap_or(false)
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let _guard = self.flush_lock.lock().await;
+        let (schema, batches) = {
+            let mut buffer = self.buffer.lock().unwrap();
+            (buffer.schema.clone(), buffer.take())
+        };
+        let Some(schema) = schema else {
+            return Ok(());
+        };
+        if batches.is_empty() {
+            return Ok(());
+        }
+        let reader = RecordBatchIterator::new(batches.clone().into_iter().map(Ok), schema);
+        if let Err(err) = self.table.add(reader).execute().await {
+            self.buffer.lock().unwrap().restore(batches);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn take_background_error(&self) -> Result<()> {
+        match self.background_error.lock().unwrap().take() {
+            Some(message) => Err(Error::Runtime {
+                message: format!(
+                    "a background flush failed, the data is still buffered: {}",
+                    message
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        let rows = self.buffer.lock().map(|b| b.rows).unwrap_or(0);
+        if rows > 0 {
+            warn!(
+                "A BufferedWriter for table {} was dropped with {} unflushed rows",
+                self.table.name(),
+                rows
+            );
+        }
+    }
+}
+
+/// Accumulates small batches and writes them to a table as larger commits
+///
+/// Every call to [`Table::add`] creates a new version of the table and at least
+/// one new data file.  When data arrives as many tiny payloads (e.g. one row per
+/// event) this creates a large number of versions and small files which slows
+/// down queries.  A buffered writer collects the batches from many callers and
+/// writes them in a single commit once one of the thresholds in
+/// [`BufferedWriterConfig`] is reached.
+///
+/// The writer can be cloned cheaply and shared between tasks.  A background task
+/// flushes the buffer once `max_delay` has passed, even if no more data arrives.
+///
+/// Data that has been passed to [`Self::write`] is not durable until it has been
+/// flushed.  Call [`Self::flush`] (or [`Self::close`]) to make sure all buffered
+/// data is written.  Any data that is still buffered when the last clone of the
+/// writer is dropped is lost.
+///
+/// Created with [`Table::buffered_writer`].
+#[derive(Clone)]
+pub struct BufferedWriter {
+    shared: Arc<Shared>,
+}
+
+impl BufferedWriter {
+    pub(crate) fn new(table: Table, config: BufferedWriterConfig) -> Self {
+        let check_interval = (config.max_delay / 2).max(Duration::from_millis(10));
+        let shared = Arc::new(Shared {
+            table,
+            config,
+            buffer: Mutex::new(Buffer::default()),
+            flush_lock: tokio::sync::Mutex::new(()),
+            background_error: Mutex::new(None),
+        });
+        Self::spawn_timer(Arc::downgrade(&shared), check_interval);
+        Self { shared }
+    }
+
+    fn spawn_timer(shared: Weak<Shared>, check_interval: Duration) {
+        runtime::spawn(async move {
+            loop {
+                runtime::sleep(check_interval).await;
+                let Some(shared) = shared.upgrade() else {
+                    return;
+                };
+                if shared.is_due() {
+                    if let Err(err) = shared.flush().await {
+                        warn!("Background flush of a BufferedWriter failed: {}", err);
+                        shared
+                            .background_error
+                            .lock()
+                            .unwrap()
+                            .replace(err.to_string());
+                    }
+                }
+            }
+        });
+    }
+
+    /// Add a batch to the buffer
+    ///
+    /// If this causes the buffer to exceed `max_rows` or `max_bytes` then the
+    /// buffer is flushed before this method returns.
+    ///
+    /// All batches written to the same writer must have the same schema.
+    pub async fn write(&self, batch: RecordBatch) -> Result<()> {
+        self.shared.take_background_error()?;
+        let should_flush = {
+            let mut buffer = self.shared.buffer.lock().unwrap();
+            match &buffer.schema {
+                Some(schema) if schema != &batch.schema() => {
+                    return Err(Error::InvalidInput {
+                        message: format!(
+                            "all batches written to a BufferedWriter must have the same schema, expected {:?} but got {:?}",
+                            schema,
+                            batch.schema()
+                        ),
+                    });
+                }
+                Some(_) => {}
+                None => buffer.schema = Some(batch.schema()),
+            }
+            buffer.rows += batch.num_rows();
+            buffer.bytes += batch.get_array_memory_size();
+            buffer.oldest.get_or_insert_with(Instant::now);
+            buffer.batches.push(batch);
+            buffer.rows >= self.shared.config.max_rows
+                || buffer.bytes >= self.shared.config.max_bytes
+        };
+        if should_flush {
+            self.shared.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Write all buffered data to the table as a single commit
+    pub async fn flush(&self) -> Result<()> {
+        self.shared.take_background_error()?;
+        self.shared.flush().await
+    }
+
+    /// The number of rows that have been written but not yet flushed
+    pub fn buffered_rows(&self) -> usize {
+        self.shared.buffer.lock().unwrap().rows
+    }
+
+    /// Flush any buffered data and consume this handle
+    pub async fn close(self) -> Result<()> {
+        self.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Int32Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::connect;
+
+    fn batch(schema: &SchemaRef, values: std::ops::Range<i32>) -> RecordBatch {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(values))],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_buffered_writer() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let table = conn
+            .create_empty_table("events", schema.clone())
+            .execute()
+            .await
+            .unwrap();
+        let version = table.version().await.unwrap();
+
+        let writer = table.buffered_writer(BufferedWriterConfig {
+            max_rows: 100,
+            max_bytes: usize::MAX,
+            max_delay: Duration::from_secs(3600),
+        });
+        for i in 0..10 {
+            writer
+                .write(batch(&schema, i * 5..(i + 1) * 5))
+                .await
+                .unwrap();
+        }
+        assert_eq!(writer.buffered_rows(), 50);
+        assert_eq!(table.count_rows(None).await.unwrap(), 0);
+
+        // All the small writes become a single commit
+        writer.flush().await.unwrap();
+        assert_eq!(writer.buffered_rows(), 0);
+        assert_eq!(table.count_rows(None).await.unwrap(), 50);
+        assert_eq!(table.version().await.unwrap(), version + 1);
+
+        // Reaching max_rows flushes immediately
+        writer.write(batch(&schema, 0..100)).await.unwrap();
+        assert_eq!(table.count_rows(None).await.unwrap(), 150);
+
+        let other = Arc::new(Schema::new(vec![Field::new("j", DataType::Int32, false)]));
+        assert!(matches!(
+            writer.write(batch(&other, 0..1)).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        writer.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_buffered_writer_flushes_after_delay() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let table = conn
+            .create_empty_table("events", schema.clone())
+            .execute()
+            .await
+            .unwrap();
+
+        let writer = table.buffered_writer(BufferedWriterConfig {
+            max_delay: Duration::from_millis(50),
+            ..Default::default()
+        });
+        writer.write(batch(&schema, 0..3)).await.unwrap();
+        for _ in 0..100 {
+            if table.count_rows(None).await.unwrap() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(table.count_rows(None).await.unwrap(), 3);
+        assert_eq!(writer.buffered_rows(), 0);
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Loading large amounts of data into a table
+//!
+//! An add with [`crate::table::WriteOptions::bulk_load`] writes its data
+//! into a single large fragment but does not commit it.  The fragments are
+//! staged on the table handle until [`crate::Table::finish_bulk_load`]
+//! commits all of them as a single new version, then builds the declared
+//! indices and adds the new rows to the existing ones.  Skipping a commit,
+//! and the index work, for every add is what makes an initial load of many
+//! batches fast.
+//!
+//! The durability is relaxed: staged data is not visible to any reader and
+//! is lost if the process exits before the load is finished.  The data
+//! files of a lost load are not referenced by any version of the table and
+//! are removed by [`crate::Table::repair`].  Primary keys are only checked
+//! against the rows committed before the load.
+
+use std::sync::Mutex;
+
+use lance::dataset::WriteParams;
+use lance::table::format::Fragment;
+
+/// The number of rows per row group of the data files written by a bulk load
+///
+/// Larger groups mean fewer, larger writes to the object store.
+pub(crate) const BULK_LOAD_ROWS_PER_GROUP: usize = 8 * 1024;
+
+/// The outcome of [`crate::Table::finish_bulk_load`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BulkLoadStats {
+    /// The number of fragments that were committed
+    pub fragments: usize,
+    /// The number of rows that were committed
+    pub rows: usize,
+    /// The version with the loaded data, or None if nothing was staged
+    pub version: Option<u64>,
+}
+
+/// The write parameters of a bulk load
+pub(crate) fn bulk_load_params(params: WriteParams) -> WriteParams {
+    WriteParams {
+        max_rows_per_group: params.max_rows_per_group.max(BULK_LOAD_ROWS_PER_GROUP),
+        ..params
+    }
+}
+
+/// The fragments staged by a bulk load that is in progress
+#[derive(Debug, Default)]
+pub(crate) struct BulkLoadTracker {
+    staged: Mutex<(Vec<Fragment>, usize)>,
+}
+
+impl BulkLoadTracker {
+    pub fn stage(&self, fragment: Fragment, rows: usize) {
+        let mut staged = self.staged.lock().unwrap();
+        staged.0.push(fragment);
+        staged.1 += rows;
+    }
+
+    /// Take the staged fragments and the number of rows in them
+    pub fn take(&self) -> (Vec<Fragment>, usize) {
+        std::mem::take(&mut *self.staged.lock().unwrap())
+    }
+
+    /// Put fragments back after a failed commit so that it can be retried
+    pub fn restore(&self, fragments: Vec<Fragment>, rows: usize) {
+        let mut staged = self.staged.lock().unwrap();
+        staged.0.splice(0..0, fragments);
+        staged.1 += rows;
+    }
+
+    /// The number of staged rows
+    pub fn staged_rows(&self) -> usize {
+        self.staged.lock().unwrap().1
+    }
+}
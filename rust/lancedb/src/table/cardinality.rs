@@ -0,0 +1,329 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Approximate row counts and distinct value counts
+//!
+//! [`crate::Table::estimate_count`] skips the fragments that cannot match the
+//! filter using the [fragment statistics](super::fragment_stats), counts the
+//! matching rows exactly in a random subset of the remaining fragments and
+//! scales the count up to all of them.  The estimate is exact for small
+//! tables and for counts without a filter.
+//!
+//! [`crate::Table::estimate_distinct`] uses a HyperLogLog sketch, which has a
+//! typical error of about 2%.  [`crate::Table::compute_statistics`] stores a
+//! sketch of each column, so only the fragments written since then are read.
+//! Without a stored sketch the column is read completely (but no other
+//! column is).  Values of rows deleted after the sketch was computed are
+//! still counted.
+
+use std::collections::HashSet;
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::datatypes::{Float64Type, Int64Type, UInt64Type};
+use arrow_cast::cast;
+use arrow_schema::DataType;
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::table::fragment_stats::{prune_fragments, FragmentStatistics};
+
+/// The number of rows that are filtered to estimate a count
+const COUNT_SAMPLE_ROWS: usize = 100_000;
+/// The number of bits of the hash that select the register of a sketch
+const PRECISION: u32 = 11;
+const NUM_REGISTERS: usize = 1 << PRECISION;
+
+/// A HyperLogLog sketch of the distinct values of a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Sketch {
+    /// The fragments whose values have been added
+    pub fragments: Vec<u64>,
+    /// The registers, hex encoded
+    #[serde(with = "hex_registers")]
+    registers: Vec<u8>,
+}
+
+impl Default for Sketch {
+    fn default() -> Self {
+        Self {
+            fragments: Vec::new(),
+            registers: vec![0; NUM_REGISTERS],
+        }
+    }
+}
+
+impl Sketch {
+    fn add_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - PRECISION)) as usize;
+        let rank = ((hash << PRECISION).leading_zeros() + 1).min(64 - PRECISION + 1) as u8;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Add the values of an array, nulls are not counted
+    pub(crate) fn add_array(&mut self, array: &ArrayRef) -> Result<()> {
+        match array.data_type() {
+            DataType::Boolean => {
+                for value in array.as_boolean().iter().flatten() {
+                    self.add_hash(hash_bytes(&[value as u8]));
+                }
+            }
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                let array = cast(array, &DataType::Int64)?;
+                for value in array.as_primitive::<Int64Type>().iter().flatten() {
+                    self.add_hash(hash_bytes(&value.to_le_bytes()));
+                }
+            }
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                let array = cast(array, &DataType::UInt64)?;
+                for value in array.as_primitive::<UInt64Type>().iter().flatten() {
+                    self.add_hash(hash_bytes(&value.to_le_bytes()));
+                }
+            }
+            DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+                let array = cast(array, &DataType::Float64)?;
+                for value in array.as_primitive::<Float64Type>().iter().flatten() {
+                    // 0.0 and -0.0 are the same value
+                    let value = if value == 0.0 { 0.0 } else { value };
+                    self.add_hash(hash_bytes(&value.to_le_bytes()));
+                }
+            }
+            DataType::Utf8 | DataType::LargeUtf8 => {
+                let array = cast(array, &DataType::Utf8)?;
+                for value in array.as_string::<i32>().iter().flatten() {
+                    self.add_hash(hash_bytes(value.as_bytes()));
+                }
+            }
+            other => {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "distinct values can only be estimated for boolean, numeric and string \
+                         columns, not {}",
+                        other
+                    ),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+        self.fragments.extend(&other.fragments);
+    }
+
+    /// The estimated number of distinct values
+    pub(crate) fn estimate(&self) -> f64 {
+        let m = NUM_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum = self
+            .registers
+            .iter()
+            .map(|r| 2f64.powi(-(*r as i32)))
+            .sum::<f64>();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
+
+mod hex_registers {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(registers: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex = registers
+            .iter()
+            .map(|r| format!("{:02x}", r))
+            .collect::<String>();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() != super::NUM_REGISTERS * 2 {
+            return Err(D::Error::custom(
+                "the sketch has the wrong number of registers",
+            ));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// A hash that is stable across versions and platforms, so stored sketches
+/// can be merged with new ones
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    // FNV-1a, followed by the finalizer of splitmix64 to spread the bits
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// Estimate the number of rows matching the (normalized) filter
+pub(crate) async fn estimate_count(dataset: &Dataset, filter: &str) -> Result<usize> {
+    let kept = prune_fragments(dataset, filter)?
+        .map(|fragments| fragments.iter().map(|f| f.id).collect::<HashSet<_>>());
+    let fragments = dataset
+        .get_fragments()
+        .into_iter()
+        .filter(|f| {
+            kept.as_ref()
+                .map_or(true, |kept| kept.contains(&(f.id() as u64)))
+        })
+        .collect::<Vec<_>>();
+    let row_counts =
+        futures::future::try_join_all(fragments.iter().map(|f| f.count_rows())).await?;
+    let total_rows = row_counts.iter().sum::<usize>();
+
+    // Filter whole fragments, picked at random, until enough rows are sampled.
+    // The seed is fixed so the estimate does not change between calls.
+    let mut order = (0..fragments.len()).collect::<Vec<_>>();
+    order.shuffle(&mut StdRng::seed_from_u64(0));
+    let mut sampled = Vec::new();
+    let mut sampled_rows = 0;
+    for i in order {
+        if sampled_rows >= COUNT_SAMPLE_ROWS {
+            break;
+        }
+        sampled.push(fragments[i].metadata().clone());
+        sampled_rows += row_counts[i];
+    }
+    if sampled_rows == 0 {
+        return Ok(0);
+    }
+    let mut scanner = dataset.scan();
+    scanner.with_fragments(sampled);
+    scanner.filter(filter)?;
+    let matched = scanner.count_rows().await? as usize;
+    if sampled_rows >= total_rows {
+        return Ok(matched);
+    }
+    Ok((matched as f64 * total_rows as f64 / sampled_rows as f64).round() as usize)
+}
+
+/// Estimate the number of distinct non-null values of a column
+///
+/// The sketch stored in the statistics is used if there is one, unless
+/// `filter` is given.
+pub(crate) async fn estimate_distinct(
+    dataset: &Dataset,
+    column: &str,
+    filter: Option<&str>,
+) -> Result<usize> {
+    let field = dataset
+        .schema()
+        .field(column)
+        .ok_or_else(|| Error::InvalidInput {
+            message: format!("there is no column named '{}'", column),
+        })?;
+    let fragments = dataset.get_fragments();
+    let ids = fragments
+        .iter()
+        .map(|f| f.id() as u64)
+        .collect::<HashSet<_>>();
+    let stats = FragmentStatistics::from_metadata(&dataset.schema().metadata)?;
+    let stored = stats
+        .sketches
+        .get(column)
+        .filter(|_| filter.is_none() && stats.field_ids.get(column) == Some(&field.id))
+        // A sketch of fragments that were removed (e.g. by a compaction)
+        // would count the values of the removed fragments twice
+        .filter(|sketch| sketch.fragments.iter().all(|id| ids.contains(id)));
+    let mut sketch = Sketch::default();
+    if let Some(stored) = stored {
+        sketch.merge(stored);
+    }
+    let covered = sketch.fragments.iter().copied().collect::<HashSet<_>>();
+    let uncovered = fragments
+        .iter()
+        .filter(|f| !covered.contains(&(f.id() as u64)))
+        .map(|f| f.metadata().clone())
+        .collect::<Vec<_>>();
+    if !uncovered.is_empty() {
+        let mut scanner = dataset.scan();
+        scanner.with_fragments(uncovered);
+        scanner.project(&[column])?;
+        if let Some(filter) = filter {
+            scanner.filter(filter)?;
+        }
+        let mut batches = scanner.try_into_stream().await?;
+        while let Some(batch) = batches.try_next().await? {
+            sketch.add_array(batch.column(0))?;
+        }
+    }
+    let num_rows = dataset.count_rows().await?;
+    Ok((sketch.estimate().round() as usize).min(num_rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, StringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_sketch_estimate() {
+        let mut sketch = Sketch::default();
+        assert_eq!(sketch.estimate(), 0.0);
+
+        let values: ArrayRef = Arc::new(Int32Array::from_iter_values(
+            (0..100_000).map(|i| i % 20_000),
+        ));
+        sketch.add_array(&values).unwrap();
+        let estimate = sketch.estimate();
+        assert!((estimate - 20_000.0).abs() < 20_000.0 * 0.1, "{}", estimate);
+
+        // Nulls are not counted and merging is a union
+        let mut other = Sketch::default();
+        let strings: ArrayRef = Arc::new(StringArray::from(vec![Some("a"), None, Some("b")]));
+        other.add_array(&strings).unwrap();
+        assert_eq!(other.estimate().round(), 2.0);
+        other.add_array(&strings).unwrap();
+        assert_eq!(other.estimate().round(), 2.0);
+        sketch.merge(&other);
+        assert!(sketch.estimate() > estimate);
+    }
+
+    #[test]
+    fn test_sketch_round_trip() {
+        let mut sketch = Sketch::default();
+        let values: ArrayRef = Arc::new(Int32Array::from_iter_values(0..100));
+        sketch.add_array(&values).unwrap();
+        sketch.fragments = vec![0, 3];
+        let json = serde_json::to_string(&sketch).unwrap();
+        assert_eq!(serde_json::from_str::<Sketch>(&json).unwrap(), sketch);
+    }
+}
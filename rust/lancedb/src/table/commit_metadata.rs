@@ -0,0 +1,133 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Key/value metadata attached to the commits of a table
+//!
+//! A write with [`super::WriteOptions::commit_metadata`] records its metadata
+//! (e.g. the author, the job id or the reason of the change) next to the
+//! version it created, and [`crate::Table::list_versions`] returns it with
+//! the versions.  Pipelines use it to track where the data of each version
+//! came from.
+//!
+//! The Lance manifest of this Lance version has no room for user metadata,
+//! so each map is stored as a small JSON object in the `_commit_metadata`
+//! directory of the table, named after the version, with the object store of
+//! the table.  It is written after the data is committed, so a failure to
+//! write it is logged and does not fail the write.  The metadata of a version
+//! removed by [`crate::Table::optimize`] is no longer listed.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bytes::Bytes;
+use futures::TryStreamExt;
+use lance::io::ObjectStore;
+use object_store::path::Path;
+
+use crate::error::{Error, Result};
+
+/// The directory of the commit metadata, relative to the table
+const COMMIT_METADATA_DIR: &str = "_commit_metadata";
+
+fn metadata_path(base: &Path, version: u64) -> Path {
+    // Zero padded so that the files list in version order
+    base.child(COMMIT_METADATA_DIR)
+        .child(format!("{:020}.json", version))
+}
+
+/// Record the metadata of the commit that created `version`
+///
+/// `base` is the location of the table in `store`.
+pub(crate) async fn write_commit_metadata(
+    store: &ObjectStore,
+    base: &Path,
+    version: u64,
+    metadata: &BTreeMap<String, String>,
+) -> Result<()> {
+    if metadata.is_empty() {
+        return Ok(());
+    }
+    let bytes = serde_json::to_vec(metadata).map_err(|e| Error::Runtime {
+        message: format!("failed to serialize the commit metadata: {}", e),
+    })?;
+    store
+        .inner
+        .put(&metadata_path(base, version), Bytes::from(bytes))
+        .await?;
+    Ok(())
+}
+
+/// Read the metadata recorded for the commits of the table at `base`, by version
+pub(crate) async fn read_commit_metadata(
+    store: &ObjectStore,
+    base: &Path,
+) -> Result<HashMap<u64, BTreeMap<String, String>>> {
+    let objects = store
+        .inner
+        .list(Some(&base.child(COMMIT_METADATA_DIR)))
+        .try_collect::<Vec<_>>()
+        .await?;
+    let mut metadata = HashMap::with_capacity(objects.len());
+    for object in objects {
+        let invalid = |reason: String| Error::Runtime {
+            message: format!(
+                "the commit metadata {} is invalid: {}",
+                object.location, reason
+            ),
+        };
+        let version = object
+            .location
+            .filename()
+            .and_then(|name| name.strip_suffix(".json"))
+            .and_then(|version| version.parse::<u64>().ok())
+            .ok_or_else(|| invalid("unexpected file name".to_string()))?;
+        let bytes = store.inner.get(&object.location).await?.bytes().await?;
+        let map = serde_json::from_slice(&bytes).map_err(|e| invalid(e.to_string()))?;
+        metadata.insert(version, map);
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_commit_metadata() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().join("test.lance");
+        let (store, base) = ObjectStore::from_uri(uri.to_str().unwrap()).await.unwrap();
+        assert!(read_commit_metadata(&store, &base)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let metadata = BTreeMap::from([
+            ("author".to_string(), "alice".to_string()),
+            ("job_id".to_string(), "42".to_string()),
+        ]);
+        write_commit_metadata(&store, &base, 3, &metadata)
+            .await
+            .unwrap();
+        // Empty metadata is not recorded
+        write_commit_metadata(&store, &base, 4, &BTreeMap::new())
+            .await
+            .unwrap();
+
+        let recorded = read_commit_metadata(&store, &base).await.unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[&3], metadata);
+    }
+}
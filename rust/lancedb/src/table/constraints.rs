@@ -0,0 +1,498 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Column constraints checked on write
+//!
+//! Constraints are declared with [`super::Table::set_constraints`] and stored
+//! in the table properties, so they apply to every handle that writes to the
+//! table.  The data of `add` and `merge_insert` and the new values of
+//! `update` are checked before they are committed.  If any row violates a
+//! constraint the write fails with a report of the offending rows and nothing
+//! is committed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{
+    cast::AsArray,
+    types::{Float64Type, UInt64Type},
+    Array, ArrayRef, RecordBatch, RecordBatchReader,
+};
+use arrow_schema::{ArrowError, DataType, Field, SchemaRef};
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::query::filter::invalid_filter;
+use crate::query::late::ROW_ID_COLUMN;
+
+/// The schema metadata key used to store the constraints
+pub(crate) const CONSTRAINTS_KEY: &str = "lancedb:constraints";
+
+/// The number of offending rows listed per violation in an error message
+const REPORTED_ROWS: usize = 10;
+
+/// A rule that every value of a column must follow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Constraint {
+    /// The column may not contain nulls
+    NotNull,
+    /// The values must be within the bounds (inclusive)
+    ///
+    /// Only supported on numeric columns.  Nulls are not checked.
+    Range { min: Option<f64>, max: Option<f64> },
+    /// The values must match the regular expression
+    ///
+    /// The expression may match any part of the value, use `^` and `$` to
+    /// match the whole value.  Only supported on string columns.  Nulls are
+    /// not checked.
+    Matches { pattern: String },
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotNull => write!(f, "NOT NULL"),
+            Self::Range { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "BETWEEN {} AND {}", min, max),
+                (Some(min), None) => write!(f, ">= {}", min),
+                (None, Some(max)) => write!(f, "<= {}", max),
+                (None, None) => write!(f, "ANY"),
+            },
+            Self::Matches { pattern } => write!(f, "MATCHES '{}'", pattern),
+        }
+    }
+}
+
+impl Constraint {
+    /// Check that the constraint can be applied to the field
+    pub(crate) fn validate(&self, field: &Field) -> Result<()> {
+        let supported = match self {
+            Self::NotNull => true,
+            Self::Range { min, max } => {
+                if let (Some(min), Some(max)) = (min, max) {
+                    if min > max {
+                        return Err(Error::InvalidInput {
+                            message: format!("the range constraint {} is empty", self),
+                        });
+                    }
+                }
+                field.data_type().is_numeric()
+            }
+            Self::Matches { pattern } => {
+                Regex::new(pattern).map_err(|e| Error::InvalidInput {
+                    message: format!("invalid regular expression '{}': {}", pattern, e),
+                })?;
+                matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8)
+            }
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput {
+                message: format!(
+                    "the constraint {} can not be applied to the column '{}' of type {}",
+                    self,
+                    field.name(),
+                    field.data_type()
+                ),
+            })
+        }
+    }
+}
+
+/// The rows of a write that violate a constraint
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintViolation {
+    pub column: String,
+    pub constraint: Constraint,
+    /// The offending rows
+    ///
+    /// For `add` and `merge_insert` this is the position of the row in the
+    /// new data, for `update` and [`super::Table::set_constraints`] it is
+    /// the row id.
+    pub rows: Vec<u64>,
+}
+
+/// Where a checked reader stores the report of the violations it found
+pub(crate) type ViolationSlot = Arc<Mutex<Option<String>>>;
+
+/// The result of a write that consumed a checked reader
+///
+/// If the reader found violations they are returned instead of the error of
+/// the write, which only says that the reader failed.
+pub(crate) fn write_result<T>(result: lance::Result<T>, slot: Option<&ViolationSlot>) -> Result<T> {
+    if let Some(message) = slot.and_then(|slot| slot.lock().unwrap().take()) {
+        return Err(Error::InvalidInput { message });
+    }
+    Ok(result?)
+}
+
+/// Describe the violations in an error message
+pub(crate) fn violation_report(violations: &[ConstraintViolation]) -> String {
+    let violations = violations
+        .iter()
+        .map(|violation| {
+            let mut rows = violation
+                .rows
+                .iter()
+                .take(REPORTED_ROWS)
+                .map(|row| row.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if violation.rows.len() > REPORTED_ROWS {
+                rows.push_str(&format!(
+                    " and {} more",
+                    violation.rows.len() - REPORTED_ROWS
+                ));
+            }
+            format!(
+                "column '{}' {} is violated by rows {}",
+                violation.column, violation.constraint, rows
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("the data violates constraints: {}", violations.join("; "))
+}
+
+/// The constraints of a table, by column name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Constraints(pub BTreeMap<String, Vec<Constraint>>);
+
+impl Constraints {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(CONSTRAINTS_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the constraints: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        if self.0.is_empty() {
+            metadata.remove(CONSTRAINTS_KEY);
+            return Ok(());
+        }
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the constraints: {}", e),
+        })?;
+        metadata.insert(CONSTRAINTS_KEY.to_string(), value);
+        Ok(())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Only keep the constraints of the given columns
+    pub(crate) fn only(&self, columns: &[String]) -> Self {
+        Self(
+            self.0
+                .iter()
+                .filter(|(column, _)| columns.contains(column))
+                .map(|(column, constraints)| (column.clone(), constraints.clone()))
+                .collect(),
+        )
+    }
+}
+
+/// A constraint, ready to be checked
+struct Check {
+    column: String,
+    constraint: Constraint,
+    regex: Option<Regex>,
+}
+
+impl Check {
+    /// The indices of the values that violate the constraint
+    fn violations(&self, values: Option<&ArrayRef>, num_rows: usize) -> Result<Vec<usize>> {
+        let Some(values) = values else {
+            // A missing column is filled with nulls
+            return Ok(match self.constraint {
+                Constraint::NotNull => (0..num_rows).collect(),
+                _ => Vec::new(),
+            });
+        };
+        Ok(match &self.constraint {
+            Constraint::NotNull => (0..values.len()).filter(|i| values.is_null(*i)).collect(),
+            Constraint::Range { min, max } => {
+                let values = arrow_cast::cast(values, &DataType::Float64)?;
+                values
+                    .as_primitive::<Float64Type>()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, v)| {
+                        v.map(|v| {
+                            min.map(|min| v < min).unwrap_or(false)
+                                || max.map(|max| v > max).unwrap_or(false)
+                        })
+                        .unwrap_or(false)
+                    })
+                    .map(|(i, _)| i)
+                    .collect()
+            }
+            Constraint::Matches { .. } => {
+                // Compiled when the checker is created
+                let regex = self.regex.as_ref().unwrap();
+                let values = arrow_cast::cast(values, &DataType::Utf8)?;
+                values
+                    .as_string::<i32>()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, v)| v.map(|v| !regex.is_match(v)).unwrap_or(false))
+                    .map(|(i, _)| i)
+                    .collect()
+            }
+        })
+    }
+}
+
+/// Checks batches of data against the constraints of a table
+pub(crate) struct ConstraintChecker {
+    checks: Vec<Check>,
+}
+
+impl ConstraintChecker {
+    pub(crate) fn try_new(constraints: &Constraints) -> Result<Self> {
+        let mut checks = Vec::new();
+        for (column, constraints) in &constraints.0 {
+            for constraint in constraints {
+                let regex = match constraint {
+                    Constraint::Matches { pattern } => {
+                        Some(Regex::new(pattern).map_err(|e| Error::Schema {
+                            message: format!("invalid regular expression '{}': {}", pattern, e),
+                        })?)
+                    }
+                    _ => None,
+                };
+                checks.push(Check {
+                    column: column.clone(),
+                    constraint: constraint.clone(),
+                    regex,
+                });
+            }
+        }
+        Ok(Self { checks })
+    }
+
+    /// Check a batch
+    ///
+    /// The offending rows are reported as `row_numbers[i]` for the row at
+    /// index `i` of the batch.
+    pub(crate) fn check(
+        &self,
+        batch: &RecordBatch,
+        row_numbers: impl Fn(usize) -> u64,
+    ) -> Result<Vec<ConstraintViolation>> {
+        let mut violations = Vec::new();
+        for check in &self.checks {
+            let rows = check.violations(batch.column_by_name(&check.column), batch.num_rows())?;
+            if !rows.is_empty() {
+                violations.push(ConstraintViolation {
+                    column: check.column.clone(),
+                    constraint: check.constraint.clone(),
+                    rows: rows.into_iter().map(&row_numbers).collect(),
+                });
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Check the data as it is read
+    ///
+    /// The reader fails at the first batch with a violation.  The report of
+    /// the violations is stored in `report`, so the caller can return it
+    /// instead of the error of the write that consumed the reader.
+    pub(crate) fn check_reader(
+        self,
+        data: Box<dyn RecordBatchReader + Send>,
+        report: ViolationSlot,
+    ) -> Box<dyn RecordBatchReader + Send> {
+        Box::new(CheckedReader {
+            schema: data.schema(),
+            inner: data,
+            checker: self,
+            rows_read: 0,
+            report,
+        })
+    }
+}
+
+struct CheckedReader {
+    inner: Box<dyn RecordBatchReader + Send>,
+    schema: SchemaRef,
+    checker: ConstraintChecker,
+    rows_read: u64,
+    report: ViolationSlot,
+}
+
+impl Iterator for CheckedReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = match self.inner.next()? {
+            Ok(batch) => batch,
+            Err(err) => return Some(Err(err)),
+        };
+        let offset = self.rows_read;
+        self.rows_read += batch.num_rows() as u64;
+        match self.checker.check(&batch, |i| offset + i as u64) {
+            Ok(violations) if violations.is_empty() => Some(Ok(batch)),
+            Ok(violations) => {
+                let report = violation_report(&violations);
+                *self.report.lock().unwrap() = Some(report.clone());
+                Some(Err(ArrowError::InvalidArgumentError(report)))
+            }
+            Err(err) => Some(Err(ArrowError::ExternalError(Box::new(err)))),
+        }
+    }
+}
+
+impl RecordBatchReader for CheckedReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// Check values computed from the rows of a dataset
+///
+/// `columns` are the (column, expression) pairs to check and `filter`
+/// selects the rows.  The offending rows are reported by row id.
+pub(crate) async fn scan_violations(
+    dataset: &Dataset,
+    checker: &ConstraintChecker,
+    columns: &[(String, String)],
+    filter: Option<&str>,
+) -> Result<Vec<ConstraintViolation>> {
+    let mut scanner = dataset.scan();
+    scanner.project_with_transform(columns)?;
+    scanner.with_row_id();
+    if let Some(filter) = filter {
+        scanner
+            .filter(filter)
+            .map_err(|e| invalid_filter(filter, e))?;
+    }
+    let mut stream = scanner.try_into_stream().await?;
+    let mut violations: Vec<ConstraintViolation> = Vec::new();
+    while let Some(batch) = stream.try_next().await? {
+        let row_ids = batch
+            .column_by_name(ROW_ID_COLUMN)
+            .ok_or_else(|| Error::Runtime {
+                message: format!("the column '{}' is missing from the scan", ROW_ID_COLUMN),
+            })?
+            .as_primitive::<UInt64Type>()
+            .clone();
+        for violation in checker.check(&batch, |i| row_ids.value(i))? {
+            let existing = violations
+                .iter_mut()
+                .find(|v| v.column == violation.column && v.constraint == violation.constraint);
+            match existing {
+                Some(existing) => existing.rows.extend(violation.rows),
+                None => violations.push(violation),
+            }
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int32Array, StringArray};
+    use arrow_schema::Schema;
+
+    use super::*;
+
+    fn constraints() -> Constraints {
+        Constraints(BTreeMap::from([
+            (
+                "age".to_string(),
+                vec![
+                    Constraint::NotNull,
+                    Constraint::Range {
+                        min: Some(0.0),
+                        max: Some(150.0),
+                    },
+                ],
+            ),
+            (
+                "email".to_string(),
+                vec![Constraint::Matches {
+                    pattern: "^[^@]+@[^@]+$".to_string(),
+                }],
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_validate() {
+        let age = Field::new("age", DataType::Int32, true);
+        let email = Field::new("email", DataType::Utf8, true);
+        let range = Constraint::Range {
+            min: Some(0.0),
+            max: None,
+        };
+        assert!(range.validate(&age).is_ok());
+        assert!(range.validate(&email).is_err());
+        let matches = Constraint::Matches {
+            pattern: "(".to_string(),
+        };
+        assert!(matches.validate(&email).is_err());
+        assert!(Constraint::NotNull.validate(&email).is_ok());
+    }
+
+    #[test]
+    fn test_check() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("age", DataType::Int32, true),
+            Field::new("email", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![Some(30), None, Some(200), Some(-1)])),
+                Arc::new(StringArray::from(vec![
+                    Some("a@b.com"),
+                    Some("nope"),
+                    None,
+                    Some("c@d"),
+                ])),
+            ],
+        )
+        .unwrap();
+        let checker = ConstraintChecker::try_new(&constraints()).unwrap();
+        let violations = checker.check(&batch, |i| 10 + i as u64).unwrap();
+        let rows = violations
+            .iter()
+            .map(|v| (v.column.as_str(), v.rows.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                ("age", vec![11]),
+                ("age", vec![12, 13]),
+                ("email", vec![11])
+            ]
+        );
+        let report = violation_report(&violations);
+        assert!(report.contains("column 'age' BETWEEN 0 AND 150 is violated by rows 12, 13"));
+    }
+}
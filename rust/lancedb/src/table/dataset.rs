@@ -133,6 +133,15 @@ impl DatasetConsistencyWrapper {
         })))
     }
 
+    /// Create a new wrapper pinned to the version of the given dataset.
+    pub fn new_time_travel(dataset: Dataset) -> Self {
+        let version = dataset.version().version;
+        Self(Arc::new(RwLock::new(DatasetRef::TimeTravel {
+            dataset,
+            version,
+        })))
+    }
+
     /// Get an immutable reference to the dataset.
     pub async fn get(&self) -> Result<DatasetReadGuard<'_>> {
         self.ensure_up_to_date().await?;
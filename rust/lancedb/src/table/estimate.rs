@@ -0,0 +1,440 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rough cost estimates of queries, index builds and optimizations
+//!
+//! Estimates are computed from statistics of the table (the number of rows,
+//! the size of each fragment, the average size of each column in a sample of
+//! rows and the indices) and fixed assumptions about the throughput of the
+//! storage and the CPU.  They are meant to tell a cheap operation from an
+//! expensive one, e.g. to ask a user for confirmation before rebuilding an
+//! index, and can easily be off by an order of magnitude.
+
+use std::collections::HashMap;
+use std::ops::Add;
+use std::time::Duration;
+
+use arrow_schema::{DataType, SchemaRef};
+use lance::dataset::optimize::CompactionOptions;
+
+use crate::error::{Error, Result};
+use crate::index::{vector::suggested_num_partitions, Index, IndexBuilder, IndexType};
+use crate::query::{Query, Select, VectorQuery};
+use crate::table::OptimizeAction;
+use crate::utils::default_vector_column;
+
+/// The number of rows that are read to measure the size of each column
+pub(crate) const SAMPLE_ROWS: usize = 1024;
+
+/// The assumed throughput of reads from storage
+const READ_BYTES_PER_SECOND: f64 = 200.0 * 1024.0 * 1024.0;
+/// The assumed throughput of writes to storage
+const WRITE_BYTES_PER_SECOND: f64 = 100.0 * 1024.0 * 1024.0;
+/// The assumed rate of simple per row work, e.g. evaluating a filter or sorting
+const ROWS_PER_SECOND: f64 = 20_000_000.0;
+/// The assumed rate of distance computations, in vector values
+const VECTOR_VALUES_PER_SECOND: f64 = 1_000_000_000.0;
+/// The number of centroids of each sub vector of a PQ index
+const PQ_CENTROIDS: f64 = 256.0;
+
+/// The predicted cost of an operation
+///
+/// See the [module documentation](self) for how the estimate is made.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostEstimate {
+    /// The number of bytes read from and written to storage
+    pub io_bytes: u64,
+    /// The number of rows that are read (and, for writes, rewritten)
+    pub rows_scanned: u64,
+    /// A rough guess of how long the operation will take
+    pub duration: Duration,
+}
+
+impl Add for CostEstimate {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            io_bytes: self.io_bytes + other.io_bytes,
+            rows_scanned: self.rows_scanned + other.rows_scanned,
+            duration: self.duration + other.duration,
+        }
+    }
+}
+
+/// The work done by an operation, before it is converted to a [`CostEstimate`]
+#[derive(Default)]
+struct Work {
+    read_bytes: f64,
+    write_bytes: f64,
+    rows: f64,
+    vector_values: f64,
+}
+
+impl Work {
+    fn estimate(self) -> CostEstimate {
+        let seconds = self.read_bytes / READ_BYTES_PER_SECOND
+            + self.write_bytes / WRITE_BYTES_PER_SECOND
+            + self.rows / ROWS_PER_SECOND
+            + self.vector_values / VECTOR_VALUES_PER_SECOND;
+        CostEstimate {
+            io_bytes: (self.read_bytes + self.write_bytes) as u64,
+            rows_scanned: self.rows as u64,
+            duration: Duration::from_secs_f64(seconds),
+        }
+    }
+}
+
+/// The size of the PQ code of a vector, assuming the default number of sub vectors
+fn pq_code_bytes(dim: f64) -> f64 {
+    (dim / 16.0).ceil().max(1.0)
+}
+
+/// The statistics of an index that estimates are based on
+#[derive(Debug, Clone)]
+pub(crate) struct IndexStatistics {
+    pub columns: Vec<String>,
+    pub index_type: IndexType,
+    /// The number of IVF partitions, if this is a vector index
+    pub num_partitions: Option<usize>,
+    pub unindexed_rows: usize,
+}
+
+/// The statistics of a table that estimates are based on
+#[derive(Debug, Clone)]
+pub(crate) struct TableStatistics {
+    pub schema: SchemaRef,
+    pub num_rows: usize,
+    /// The number of rows in each fragment
+    pub fragment_rows: Vec<usize>,
+    /// The average size of a value of each top level column, in bytes
+    pub column_bytes: HashMap<String, f64>,
+    pub indices: Vec<IndexStatistics>,
+}
+
+impl TableStatistics {
+    fn bytes_of<'a>(&self, columns: impl IntoIterator<Item = &'a String>) -> f64 {
+        columns
+            .into_iter()
+            .filter_map(|column| self.column_bytes.get(column))
+            .sum()
+    }
+
+    fn row_bytes(&self) -> f64 {
+        self.column_bytes.values().sum()
+    }
+
+    /// The columns whose name appears in an SQL expression
+    fn referenced_columns(&self, expr: &str) -> Vec<&String> {
+        self.column_bytes
+            .keys()
+            .filter(|column| expr.contains(column.as_str()))
+            .collect()
+    }
+
+    fn selected_bytes(&self, select: &Select) -> f64 {
+        match select {
+            Select::All => self.row_bytes(),
+            Select::Columns(columns) => self.bytes_of(columns),
+            Select::AllExcept(excluded) => self.row_bytes() - self.bytes_of(excluded),
+            Select::Dynamic(exprs) => exprs
+                .iter()
+                .map(|(_, expr)| self.bytes_of(self.referenced_columns(expr)))
+                .sum(),
+        }
+    }
+
+    fn vector_dim(&self, column: &str) -> Result<usize> {
+        match self.schema.field_with_name(column)?.data_type() {
+            DataType::FixedSizeList(_, dim) => Ok(*dim as usize),
+            data_type => Err(Error::InvalidInput {
+                message: format!(
+                    "the column '{}' has type {} and is not a vector column",
+                    column, data_type
+                ),
+            }),
+        }
+    }
+
+    fn index_on(&self, column: &str) -> Option<&IndexStatistics> {
+        self.indices.iter().find(|index| index.columns == [column])
+    }
+}
+
+/// Estimate the cost of a plain query
+pub(crate) fn estimate_query(stats: &TableStatistics, query: &Query) -> CostEstimate {
+    let num_rows = stats.num_rows as f64;
+    let returned = query
+        .limit
+        .map_or(num_rows, |limit| num_rows.min(limit as f64));
+    let mut work = Work {
+        read_bytes: returned * stats.selected_bytes(&query.select),
+        rows: returned,
+        ..Default::default()
+    };
+    if let Some(filter) = &query.filter {
+        // Without knowing the selectivity we assume the filter reads every row
+        work.read_bytes += num_rows * stats.bytes_of(stats.referenced_columns(filter));
+        work.rows = num_rows;
+    }
+    work.estimate()
+}
+
+/// Estimate the cost of a vector search
+pub(crate) fn estimate_vector_query(
+    stats: &TableStatistics,
+    query: &VectorQuery,
+) -> Result<CostEstimate> {
+    let column = match &query.column {
+        Some(column) => column.clone(),
+        None => default_vector_column(
+            &stats.schema,
+            query.query_vector.as_ref().map(|v| v.len() as i32),
+        )?,
+    };
+    let dim = stats.vector_dim(&column)? as f64;
+    let vector_bytes = stats.column_bytes.get(&column).copied().unwrap_or_default();
+    let num_rows = stats.num_rows as f64;
+    let limit = query.base.limit.unwrap_or(10) as f64;
+    let refine = query.refine_factor.unwrap_or(1) as f64;
+
+    let index = stats
+        .index_on(&column)
+        .filter(|index| query.use_index && index.index_type == IndexType::IvfPq);
+    let mut work = match index {
+        Some(index) => {
+            let partitions = index
+                .num_partitions
+                .unwrap_or_else(|| suggested_num_partitions(stats.num_rows) as usize)
+                .max(1) as f64;
+            let unindexed = index.unindexed_rows as f64;
+            let indexed = (num_rows - unindexed).max(0.0);
+            let probed = indexed * (query.nprobes as f64).min(partitions) / partitions;
+            let code_bytes = pq_code_bytes(dim);
+            Work {
+                read_bytes: probed * code_bytes
+                    + unindexed * vector_bytes
+                    + limit * refine * vector_bytes,
+                rows: probed + unindexed,
+                vector_values: partitions * dim + probed * code_bytes + unindexed * dim,
+                ..Default::default()
+            }
+        }
+        None => Work {
+            read_bytes: num_rows * vector_bytes,
+            rows: num_rows,
+            vector_values: num_rows * dim,
+            ..Default::default()
+        },
+    };
+    work.read_bytes += limit * stats.selected_bytes(&query.base.select);
+    if let Some(filter) = &query.base.filter {
+        work.read_bytes += num_rows * stats.bytes_of(stats.referenced_columns(filter));
+        work.rows = work.rows.max(num_rows);
+    }
+    Ok(work.estimate())
+}
+
+fn ivf_pq_work(
+    stats: &TableStatistics,
+    column: &str,
+    rows: f64,
+    num_partitions: Option<usize>,
+    sample_rate: u32,
+    max_iterations: u32,
+) -> Result<Work> {
+    let dim = stats.vector_dim(column)? as f64;
+    let vector_bytes = stats.column_bytes.get(column).copied().unwrap_or_default();
+    let partitions = num_partitions
+        .unwrap_or_else(|| suggested_num_partitions(stats.num_rows) as usize)
+        .max(1) as f64;
+    let sample = rows.min(partitions * sample_rate as f64);
+    let iterations = max_iterations as f64;
+    let code_bytes = pq_code_bytes(dim);
+    Ok(Work {
+        read_bytes: rows * vector_bytes,
+        write_bytes: rows * code_bytes,
+        rows,
+        // Training the IVF and PQ centroids, then assigning every row
+        vector_values: sample * iterations * (partitions + PQ_CENTROIDS) * dim
+            + rows * (partitions + PQ_CENTROIDS) * dim,
+    })
+}
+
+fn btree_work(stats: &TableStatistics, column: &str, rows: f64) -> Work {
+    let value_bytes = stats.column_bytes.get(column).copied().unwrap_or_default();
+    Work {
+        read_bytes: rows * value_bytes,
+        write_bytes: rows * (value_bytes + 8.0),
+        rows: rows * rows.max(2.0).log2(),
+        ..Default::default()
+    }
+}
+
+/// Estimate the cost of building an index
+pub(crate) fn estimate_index(
+    stats: &TableStatistics,
+    builder: &IndexBuilder,
+) -> Result<CostEstimate> {
+    let [column] = builder.columns.as_slice() else {
+        return Err(Error::InvalidInput {
+            message: "indices can only be estimated on a single column".to_string(),
+        });
+    };
+    let rows = stats.num_rows as f64;
+    let is_vector = stats.vector_dim(column).is_ok();
+    let work = match &builder.index {
+        Index::IvfPq(ivf_pq) => ivf_pq_work(
+            stats,
+            column,
+            rows,
+            ivf_pq.num_partitions.map(|n| n as usize),
+            ivf_pq.sample_rate,
+            ivf_pq.max_iterations,
+        )?,
+        Index::Auto if is_vector => ivf_pq_work(stats, column, rows, None, 256, 50)?,
+        Index::BTree(_) | Index::Auto => {
+            stats.schema.field_with_name(column)?;
+            btree_work(stats, column, rows)
+        }
+    };
+    Ok(work.estimate())
+}
+
+fn compaction_work(stats: &TableStatistics, options: &CompactionOptions) -> Work {
+    let small = stats
+        .fragment_rows
+        .iter()
+        .filter(|rows| **rows < options.target_rows_per_fragment)
+        .collect::<Vec<_>>();
+    if small.len() < 2 {
+        return Work::default();
+    }
+    let rows = small.into_iter().sum::<usize>() as f64;
+    let bytes = rows * stats.row_bytes();
+    Work {
+        read_bytes: bytes,
+        write_bytes: bytes,
+        rows,
+        ..Default::default()
+    }
+}
+
+fn index_optimize_work(stats: &TableStatistics) -> Work {
+    let mut work = Work::default();
+    for index in &stats.indices {
+        let [column] = index.columns.as_slice() else {
+            continue;
+        };
+        let rows = index.unindexed_rows as f64;
+        if rows == 0.0 {
+            continue;
+        }
+        let value_bytes = stats.column_bytes.get(column).copied().unwrap_or_default();
+        work.read_bytes += rows * value_bytes;
+        work.rows += rows;
+        match index.index_type {
+            IndexType::IvfPq => {
+                let dim = stats.vector_dim(column).unwrap_or_default() as f64;
+                let partitions = index.num_partitions.unwrap_or(1) as f64;
+                work.write_bytes += rows * pq_code_bytes(dim);
+                work.vector_values += rows * (partitions + PQ_CENTROIDS) * dim;
+            }
+            IndexType::BTree => work.write_bytes += rows * (value_bytes + 8.0),
+        }
+    }
+    work
+}
+
+/// Estimate the cost of an optimization
+///
+/// Pruning old versions only deletes files and is estimated to be free.
+pub(crate) fn estimate_optimize(stats: &TableStatistics, action: &OptimizeAction) -> CostEstimate {
+    match action {
+        OptimizeAction::All => {
+            compaction_work(stats, &CompactionOptions::default()).estimate()
+                + index_optimize_work(stats).estimate()
+        }
+        OptimizeAction::Compact { options, .. } => compaction_work(stats, options).estimate(),
+        OptimizeAction::Prune { .. } => CostEstimate::default(),
+        OptimizeAction::Index(_) => index_optimize_work(stats).estimate(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::{Field, Schema};
+
+    use super::*;
+
+    fn statistics(indices: Vec<IndexStatistics>) -> TableStatistics {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 128),
+                true,
+            ),
+        ]));
+        TableStatistics {
+            schema,
+            num_rows: 1_000_000,
+            fragment_rows: vec![500_000, 400_000, 50_000, 50_000],
+            column_bytes: [("id".to_string(), 8.0), ("vector".to_string(), 512.0)].into(),
+            indices,
+        }
+    }
+
+    #[test]
+    fn test_estimate_compaction() {
+        let stats = statistics(vec![]);
+        let estimate = estimate_optimize(
+            &stats,
+            &OptimizeAction::Compact {
+                options: CompactionOptions {
+                    target_rows_per_fragment: 100_000,
+                    ..Default::default()
+                },
+                remap_options: None,
+            },
+        );
+        assert_eq!(estimate.rows_scanned, 100_000);
+        assert_eq!(estimate.io_bytes, 2 * 100_000 * 520);
+
+        let estimate = estimate_optimize(
+            &stats,
+            &OptimizeAction::Prune {
+                older_than: chrono::Duration::zero(),
+                delete_unverified: None,
+            },
+        );
+        assert_eq!(estimate, CostEstimate::default());
+    }
+
+    #[test]
+    fn test_estimate_index_optimize() {
+        let stats = statistics(vec![IndexStatistics {
+            columns: vec!["vector".to_string()],
+            index_type: IndexType::IvfPq,
+            num_partitions: Some(1000),
+            unindexed_rows: 1000,
+        }]);
+        let estimate = index_optimize_work(&stats).estimate();
+        assert_eq!(estimate.rows_scanned, 1000);
+        assert!(estimate.io_bytes >= 1000 * 512);
+        assert!(estimate.duration > Duration::ZERO);
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Schema evolution on write
+//!
+//! With [`Evolve::AddNewColumns`] (see [`super::WriteOptions::schema_evolution`])
+//! an append whose data has columns that are not in the table first adds those
+//! columns to the table, with a null value for every existing row, and then
+//! appends the data.  The new columns are committed as their own version,
+//! before the data.  If the data then fails to be written the new columns
+//! are dropped again, so a failed write does not change the schema.  Readers
+//! may see the columns in between.
+//!
+//! Only top level columns are added, and they must be nullable since the
+//! existing rows have no value for them.  Any other difference between the
+//! schemas (see [`super::schema_diff`]) still fails the write.
+
+use std::sync::Arc;
+
+use arrow_array::{new_null_array, RecordBatch};
+use arrow_schema::{Field, Schema};
+use lance::dataset::transaction::Operation;
+use lance::dataset::Dataset;
+use lance::io::ObjectStoreParams;
+
+use crate::error::{Error, Result};
+
+use super::batch_alter::merge_new_columns;
+use super::schema_diff::SchemaDiff;
+
+/// How an append handles columns that are not in the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Evolve {
+    /// Fail the write (the default)
+    #[default]
+    Strict,
+    /// Add the new columns to the table, see [`self`]
+    AddNewColumns,
+}
+
+/// The columns of the data to add to the table, fails if any can not be added
+pub(crate) fn new_columns(data: &Schema, diff: &SchemaDiff) -> Result<Vec<Field>> {
+    diff.extra_columns
+        .iter()
+        .map(|column| {
+            let field = data.field_with_name(column).map_err(|_| Error::Schema {
+                message: format!(
+                    "cannot add the column '{}', only top level columns can be added to a table",
+                    column
+                ),
+            })?;
+            if !field.is_nullable() {
+                return Err(Error::Schema {
+                    message: format!(
+                        "cannot add the column '{}', it must be nullable since the existing \
+                         rows have no value for it",
+                        column
+                    ),
+                });
+            }
+            Ok(field.clone())
+        })
+        .collect()
+}
+
+/// Add nullable columns to the table, with a null value for every row
+///
+/// The null values are written to new data files of each fragment, which are
+/// committed together with the new schema.
+pub(crate) async fn add_null_columns(
+    dataset: &Dataset,
+    uri: &str,
+    fields: Vec<Field>,
+    store_params: Option<ObjectStoreParams>,
+) -> Result<Dataset> {
+    let read_version = dataset.version().version;
+    let schema = dataset.schema().clone();
+    let new_schema = Arc::new(Schema::new(fields));
+    let final_schema = merge_new_columns(dataset, new_schema.as_ref())?;
+    let write_schema = final_schema.project_by_schema(new_schema.as_ref())?;
+    // The updater only reads some column to learn the size of each batch
+    let read_columns = vec![schema.fields[0].name.clone()];
+
+    let mut fragments = Vec::new();
+    for fragment in dataset.get_fragments() {
+        let mut updater = fragment
+            .updater(
+                Some(&read_columns),
+                Some((write_schema.clone(), final_schema.clone())),
+            )
+            .await?;
+        while let Some(batch) = updater.next().await? {
+            let columns = new_schema
+                .fields()
+                .iter()
+                .map(|field| new_null_array(field.data_type(), batch.num_rows()))
+                .collect();
+            updater
+                .update(RecordBatch::try_new(new_schema.clone(), columns)?)
+                .await?;
+        }
+        fragments.push(updater.finish().await?);
+    }
+
+    let operation = Operation::Merge {
+        fragments,
+        schema: final_schema,
+    };
+    Ok(Dataset::commit(uri, operation, Some(read_version), store_params, None).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::DataType;
+
+    use super::*;
+
+    #[test]
+    fn test_new_columns() {
+        let table = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+        let data = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("tag", DataType::Utf8, true),
+        ]);
+        let diff = SchemaDiff::between(&data, &table);
+        let fields = new_columns(&data, &diff).unwrap();
+        assert_eq!(fields, vec![Field::new("tag", DataType::Utf8, true)]);
+
+        let data = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("tag", DataType::Utf8, false),
+        ]);
+        let diff = SchemaDiff::between(&data, &table);
+        assert!(matches!(
+            new_columns(&data, &diff),
+            Err(Error::Schema { .. })
+        ));
+    }
+}
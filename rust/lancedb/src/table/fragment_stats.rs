@@ -0,0 +1,700 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Skipping fragments with per-fragment column statistics
+//!
+//! [`crate::Table::compute_statistics`] records the minimum, maximum and
+//! number of nulls of columns in every fragment.  A plain (non-vector) query
+//! with a filter then skips the fragments whose statistics show that no row
+//! can match, which speeds up filters on columns without a scalar index.
+//!
+//! Only the conditions of the filter that are joined with `AND` and compare a
+//! column with a literal (`=`, `<`, `<=`, `>`, `>=`, `IS NULL` and
+//! `IS NOT NULL`) are used.  Other conditions never skip a fragment, so the
+//! results are always the same as without statistics.
+//!
+//! Statistics are stored in the table properties and are not updated by
+//! writes.  Fragments written after the statistics were computed (including
+//! the fragments rewritten by a compaction) are never skipped until the
+//! statistics are computed again.  Statistics of a column that was dropped
+//! and added again are ignored.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::{max, max_boolean, max_string, min, min_boolean, min_string};
+use arrow::datatypes::{Float64Type, Int64Type, UInt64Type};
+use arrow_schema::DataType;
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use lance::table::format::Fragment;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::table::cardinality::Sketch;
+
+/// The schema metadata key used to store the fragment statistics
+pub(crate) const FRAGMENT_STATISTICS_KEY: &str = "lancedb:fragment_statistics";
+
+/// A minimum or maximum value of a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatisticValue {
+    Boolean(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+}
+
+impl PartialOrd for StatisticValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        use StatisticValue::*;
+        match (self, other) {
+            (Boolean(a), Boolean(b)) => a.partial_cmp(b),
+            (Int(a), Int(b)) => a.partial_cmp(b),
+            (UInt(a), UInt(b)) => a.partial_cmp(b),
+            (Int(a), UInt(b)) => (*a as i128).partial_cmp(&(*b as i128)),
+            (UInt(a), Int(b)) => (*a as i128).partial_cmp(&(*b as i128)),
+            (Float(a), Float(b)) => a.partial_cmp(b),
+            (Float(a), Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Float(a), UInt(b)) => a.partial_cmp(&(*b as f64)),
+            (Int(a), Float(b)) => (*a as f64).partial_cmp(b),
+            (UInt(a), Float(b)) => (*a as f64).partial_cmp(b),
+            (String(a), String(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl StatisticValue {
+    /// The value as an SQL literal, None if it can not be written as one
+    pub(crate) fn to_literal(&self) -> Option<String> {
+        match self {
+            Self::Boolean(value) => Some(value.to_string().to_uppercase()),
+            Self::Int(value) => Some(value.to_string()),
+            Self::UInt(value) => Some(value.to_string()),
+            // Debug formatting keeps the decimal point of whole numbers
+            Self::Float(value) if value.is_finite() => Some(format!("{:?}", value)),
+            Self::Float(_) => None,
+            Self::String(value) => Some(format!("'{}'", value.replace('\'', "''"))),
+        }
+    }
+}
+
+/// The statistics of a column in a fragment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStatistics {
+    /// The smallest value, None if all values are null
+    pub min: Option<StatisticValue>,
+    /// The largest value, None if all values are null
+    pub max: Option<StatisticValue>,
+    pub null_count: u64,
+    /// The number of rows that were not deleted
+    pub num_rows: u64,
+}
+
+impl ColumnStatistics {
+    fn merge(&mut self, other: Self) {
+        if let Some(value) = other.min {
+            if self.min.as_ref().map_or(true, |min| value < *min) {
+                self.min = Some(value);
+            }
+        }
+        if let Some(value) = other.max {
+            if self.max.as_ref().map_or(true, |max| value > *max) {
+                self.max = Some(value);
+            }
+        }
+        self.null_count += other.null_count;
+        self.num_rows += other.num_rows;
+    }
+}
+
+/// How many fragments a query reads, see [`crate::query::Query::fragment_pruning`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FragmentPruning {
+    /// The number of fragments in the table
+    pub total_fragments: usize,
+    /// The number of fragments skipped because of their statistics
+    pub pruned_fragments: usize,
+}
+
+/// The statistics stored in the table properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct FragmentStatistics {
+    /// The field id of each column when its statistics were computed
+    pub field_ids: BTreeMap<String, i32>,
+    /// The statistics of each column in each fragment
+    pub fragments: BTreeMap<u64, BTreeMap<String, ColumnStatistics>>,
+    /// A sketch of the distinct values of each column
+    #[serde(default)]
+    pub sketches: BTreeMap<String, Sketch>,
+}
+
+impl FragmentStatistics {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(FRAGMENT_STATISTICS_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the fragment statistics: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the fragment statistics: {}", e),
+        })?;
+        metadata.insert(FRAGMENT_STATISTICS_KEY.to_string(), value);
+        Ok(())
+    }
+
+    /// Compute the statistics of the columns for every fragment of the dataset
+    ///
+    /// The statistics of other columns are kept, statistics of fragments that
+    /// no longer exist are removed.
+    pub(crate) async fn compute(&mut self, dataset: &Dataset, columns: &[String]) -> Result<()> {
+        let fragments = dataset.get_fragments();
+        let ids = fragments.iter().map(|f| f.id() as u64).collect::<Vec<_>>();
+        self.fragments.retain(|id, _| ids.contains(id));
+        if columns.is_empty() {
+            return Ok(());
+        }
+        let projection = columns.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut sketches = columns
+            .iter()
+            .map(|column| (column.clone(), Sketch::default()))
+            .collect::<BTreeMap<_, _>>();
+        for fragment in fragments {
+            let mut stats: BTreeMap<String, ColumnStatistics> = BTreeMap::new();
+            let mut scanner = fragment.scan();
+            scanner.project(&projection)?;
+            let mut batches = scanner.try_into_stream().await?;
+            while let Some(batch) = batches.try_next().await? {
+                for column in columns {
+                    let Some(array) = batch.column_by_name(column) else {
+                        continue;
+                    };
+                    let batch_stats = array_statistics(array)?;
+                    if let Some(sketch) = sketches.get_mut(column) {
+                        sketch.add_array(array)?;
+                    }
+                    match stats.get_mut(column) {
+                        Some(existing) => existing.merge(batch_stats),
+                        None => {
+                            stats.insert(column.clone(), batch_stats);
+                        }
+                    }
+                }
+            }
+            self.fragments
+                .entry(fragment.id() as u64)
+                .or_default()
+                .extend(stats);
+        }
+        for sketch in sketches.values_mut() {
+            sketch.fragments = ids.clone();
+        }
+        self.sketches.extend(sketches);
+        for column in columns {
+            if let Some(field) = dataset.schema().field(column) {
+                self.field_ids.insert(column.clone(), field.id);
+            }
+        }
+        Ok(())
+    }
+
+    /// The ids of the fragments that may contain rows matching the filter
+    ///
+    /// Returns None if the statistics can not skip any fragment.
+    pub(crate) fn prune(&self, dataset: &Dataset, filter: &str) -> Option<Vec<u64>> {
+        let conditions = parse_conditions(filter)?;
+        // Only use the statistics of columns that still are the same column
+        let conditions = conditions
+            .into_iter()
+            .filter(|condition| {
+                let field = dataset.schema().field(&condition.column);
+                matches!(
+                    (field, self.field_ids.get(&condition.column)),
+                    (Some(field), Some(id)) if field.id == *id
+                )
+            })
+            .collect::<Vec<_>>();
+        if conditions.is_empty() {
+            return None;
+        }
+        Some(
+            dataset
+                .get_fragments()
+                .iter()
+                .map(|f| f.id() as u64)
+                .filter(|id| {
+                    let Some(stats) = self.fragments.get(id) else {
+                        return true;
+                    };
+                    conditions.iter().all(|condition| {
+                        stats
+                            .get(&condition.column)
+                            .map_or(true, |s| condition.may_match(s))
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The fragments that a scan with the (normalized) filter has to read
+///
+/// Returns None if all fragments have to be read.
+pub(crate) fn prune_fragments(dataset: &Dataset, filter: &str) -> Result<Option<Vec<Fragment>>> {
+    if !dataset
+        .schema()
+        .metadata
+        .contains_key(FRAGMENT_STATISTICS_KEY)
+    {
+        return Ok(None);
+    }
+    let stats = FragmentStatistics::from_metadata(&dataset.schema().metadata)?;
+    let Some(ids) = stats.prune(dataset, filter) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        dataset
+            .get_fragments()
+            .iter()
+            .filter(|f| ids.contains(&(f.id() as u64)))
+            .map(|f| f.metadata().clone())
+            .collect(),
+    ))
+}
+
+/// The statistics of an array, or an error if the type is not supported
+fn array_statistics(array: &ArrayRef) -> Result<ColumnStatistics> {
+    use arrow_cast::cast;
+    let (min_value, max_value) = match array.data_type() {
+        DataType::Boolean => {
+            let array = array.as_boolean();
+            (
+                min_boolean(array).map(StatisticValue::Boolean),
+                max_boolean(array).map(StatisticValue::Boolean),
+            )
+        }
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            let array = cast(array, &DataType::Int64)?;
+            let array = array.as_primitive::<Int64Type>();
+            (
+                min(array).map(StatisticValue::Int),
+                max(array).map(StatisticValue::Int),
+            )
+        }
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+            let array = cast(array, &DataType::UInt64)?;
+            let array = array.as_primitive::<UInt64Type>();
+            (
+                min(array).map(StatisticValue::UInt),
+                max(array).map(StatisticValue::UInt),
+            )
+        }
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+            let array = cast(array, &DataType::Float64)?;
+            let array = array.as_primitive::<Float64Type>();
+            (
+                min(array).map(StatisticValue::Float),
+                max(array).map(StatisticValue::Float),
+            )
+        }
+        DataType::Utf8 | DataType::LargeUtf8 => {
+            let array = cast(array, &DataType::Utf8)?;
+            let array = array.as_string::<i32>();
+            (
+                min_string(array).map(|s| StatisticValue::String(s.to_string())),
+                max_string(array).map(|s| StatisticValue::String(s.to_string())),
+            )
+        }
+        other => {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "statistics can only be computed for boolean, numeric and string columns, \
+                     not {}",
+                    other
+                ),
+            })
+        }
+    };
+    Ok(ColumnStatistics {
+        min: min_value,
+        max: max_value,
+        null_count: array.null_count() as u64,
+        num_rows: array.len() as u64,
+    })
+}
+
+/// True if statistics can be computed for columns of the type
+pub(crate) fn supports_statistics(data_type: &DataType) -> bool {
+    data_type.is_integer()
+        || data_type.is_floating()
+        || matches!(
+            data_type,
+            DataType::Boolean | DataType::Utf8 | DataType::LargeUtf8
+        )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparison {
+    Eq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    IsNull,
+    IsNotNull,
+}
+
+/// A condition on a single column that can be checked with statistics
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    column: String,
+    comparison: Comparison,
+    value: Option<StatisticValue>,
+}
+
+impl Condition {
+    /// False if no row with these statistics can match the condition
+    fn may_match(&self, stats: &ColumnStatistics) -> bool {
+        let (min, max) = match (&self.comparison, &stats.min, &stats.max) {
+            (Comparison::IsNull, _, _) => return stats.null_count > 0,
+            (Comparison::IsNotNull, _, _) => return stats.null_count < stats.num_rows,
+            // A comparison with null is never true
+            (_, None, _) | (_, _, None) => return false,
+            (_, Some(min), Some(max)) => (min, max),
+        };
+        // The parser only creates comparisons with a value
+        let Some(value) = &self.value else {
+            return true;
+        };
+        // Values that can not be compared (e.g. a NaN or a string compared
+        // with a number) never skip the fragment
+        let below_max = max.partial_cmp(value);
+        let above_min = min.partial_cmp(value);
+        match self.comparison {
+            Comparison::Eq => {
+                above_min != Some(Ordering::Greater) && below_max != Some(Ordering::Less)
+            }
+            Comparison::Lt => {
+                above_min != Some(Ordering::Greater) && above_min != Some(Ordering::Equal)
+            }
+            Comparison::LtEq => above_min != Some(Ordering::Greater),
+            Comparison::Gt => {
+                below_max != Some(Ordering::Less) && below_max != Some(Ordering::Equal)
+            }
+            Comparison::GtEq => below_max != Some(Ordering::Less),
+            Comparison::IsNull | Comparison::IsNotNull => true,
+        }
+    }
+}
+
+/// The conditions of a filter that must all be true
+///
+/// Conditions that can not be checked with statistics are left out.  Returns
+/// None if the filter has no such condition or combines conditions with `OR`.
+fn parse_conditions(filter: &str) -> Option<Vec<Condition>> {
+    let mut conditions = Vec::new();
+    collect_conditions(filter, &mut conditions)?;
+    if conditions.is_empty() {
+        None
+    } else {
+        Some(conditions)
+    }
+}
+
+fn collect_conditions(filter: &str, conditions: &mut Vec<Condition>) -> Option<()> {
+    for part in split_conjunction(filter)? {
+        let part = part.trim();
+        if let Some(inner) = strip_parentheses(part) {
+            // A nested filter with an OR can not be used but does not prevent
+            // the other conditions from being used
+            let mut nested = Vec::new();
+            if collect_conditions(inner, &mut nested).is_some() {
+                conditions.extend(nested);
+            }
+        } else if let Some(condition) = parse_condition(part) {
+            conditions.push(condition);
+        }
+    }
+    Some(())
+}
+
+/// Split the filter at the top level `AND`s, None if there is a top level `OR`
+fn split_conjunction(filter: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut start = 0;
+    let bytes = filter.as_bytes();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        if in_string {
+            in_string = c != b'\'';
+        } else if c == b'\'' {
+            in_string = true;
+        } else if c == b'(' {
+            depth += 1;
+        } else if c == b')' {
+            depth -= 1;
+        } else if depth == 0 && (pos == 0 || !is_word_byte(bytes[pos - 1])) {
+            let word_end = bytes[pos..]
+                .iter()
+                .position(|b| !is_word_byte(*b))
+                .map_or(bytes.len(), |len| pos + len);
+            let word = &filter[pos..word_end];
+            if word.eq_ignore_ascii_case("OR") {
+                return None;
+            }
+            if word.eq_ignore_ascii_case("AND") {
+                parts.push(&filter[start..pos]);
+                start = word_end;
+            }
+            if word_end > pos {
+                pos = word_end;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+    parts.push(&filter[start..]);
+    Some(parts)
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// The inside of a filter that is entirely wrapped in parentheses
+fn strip_parentheses(filter: &str) -> Option<&str> {
+    let inner = filter.strip_prefix('(')?.strip_suffix(')')?;
+    // Make sure the first parenthesis is closed by the last one, not earlier
+    // as in `(a > 1) AND (b > 2)`
+    let mut depth = 0;
+    let mut in_string = false;
+    for c in inner.bytes() {
+        if in_string {
+            in_string = c != b'\'';
+        } else if c == b'\'' {
+            in_string = true;
+        } else if c == b'(' {
+            depth += 1;
+        } else if c == b')' {
+            depth -= 1;
+            if depth < 0 {
+                return None;
+            }
+        }
+    }
+    Some(inner)
+}
+
+/// Parse `column <op> literal`, `column IS NULL` or `column IS NOT NULL`
+fn parse_condition(condition: &str) -> Option<Condition> {
+    let (column, rest) = parse_column(condition.trim())?;
+    let rest = rest.trim_start();
+    let words = rest.split_whitespace().collect::<Vec<_>>();
+    let null_comparison = match words.as_slice() {
+        [is, null] if is.eq_ignore_ascii_case("IS") && null.eq_ignore_ascii_case("NULL") => {
+            Some(Comparison::IsNull)
+        }
+        [is, not, null]
+            if is.eq_ignore_ascii_case("IS")
+                && not.eq_ignore_ascii_case("NOT")
+                && null.eq_ignore_ascii_case("NULL") =>
+        {
+            Some(Comparison::IsNotNull)
+        }
+        _ => None,
+    };
+    if let Some(comparison) = null_comparison {
+        return Some(Condition {
+            column,
+            comparison,
+            value: None,
+        });
+    }
+    let (comparison, literal) = [
+        ("==", Comparison::Eq),
+        ("<=", Comparison::LtEq),
+        (">=", Comparison::GtEq),
+        ("=", Comparison::Eq),
+        ("<", Comparison::Lt),
+        (">", Comparison::Gt),
+    ]
+    .into_iter()
+    .find_map(|(op, comparison)| rest.strip_prefix(op).map(|literal| (comparison, literal)))?;
+    // `<>` is not a comparison that can use statistics
+    if literal.starts_with('>') {
+        return None;
+    }
+    Some(Condition {
+        column,
+        comparison,
+        value: Some(parse_literal(literal.trim())?),
+    })
+}
+
+/// Parse a plain or backtick quoted column name at the start of the string
+fn parse_column(s: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = s.strip_prefix('`') {
+        let end = quoted.find('`')?;
+        let column = &quoted[..end];
+        // Escaped backticks are rare enough to not be worth handling
+        if quoted[end + 1..].starts_with('`') {
+            return None;
+        }
+        return Some((column.to_string(), &quoted[end + 1..]));
+    }
+    let end = s.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    if end == 0 || s.as_bytes()[0].is_ascii_digit() {
+        return None;
+    }
+    let column = &s[..end];
+    // Keywords (e.g. `NOT x = 1`) and functions are not columns
+    if ["NOT", "TRUE", "FALSE", "NULL"]
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(column))
+        || s[end..].trim_start().starts_with('(')
+        || s[end..].starts_with('.')
+    {
+        return None;
+    }
+    Some((column.to_string(), &s[end..]))
+}
+
+fn parse_literal(literal: &str) -> Option<StatisticValue> {
+    if let Some(string) = literal.strip_prefix('\'') {
+        let string = string.strip_suffix('\'')?;
+        // A quote inside the literal must be escaped as ''
+        if string.replace("''", "").contains('\'') {
+            return None;
+        }
+        return Some(StatisticValue::String(string.replace("''", "'")));
+    }
+    if literal.eq_ignore_ascii_case("TRUE") {
+        return Some(StatisticValue::Boolean(true));
+    }
+    if literal.eq_ignore_ascii_case("FALSE") {
+        return Some(StatisticValue::Boolean(false));
+    }
+    if let Ok(value) = literal.parse::<i64>() {
+        return Some(StatisticValue::Int(value));
+    }
+    if let Ok(value) = literal.parse::<u64>() {
+        return Some(StatisticValue::UInt(value));
+    }
+    match literal.parse::<f64>() {
+        Ok(value) if value.is_finite() => Some(StatisticValue::Float(value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, StringArray};
+
+    use super::*;
+
+    fn condition(column: &str, comparison: Comparison, value: StatisticValue) -> Condition {
+        Condition {
+            column: column.to_string(),
+            comparison,
+            value: Some(value),
+        }
+    }
+
+    #[test]
+    fn test_literal_round_trip() {
+        for value in [
+            StatisticValue::Boolean(true),
+            StatisticValue::Int(-3),
+            StatisticValue::UInt(u64::MAX),
+            StatisticValue::Float(2.0),
+            StatisticValue::String("it's".to_string()),
+        ] {
+            let literal = value.to_literal().unwrap();
+            assert_eq!(parse_literal(&literal), Some(value), "{}", literal);
+        }
+        assert_eq!(StatisticValue::Float(f64::NAN).to_literal(), None);
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        assert_eq!(
+            parse_conditions("id >= 5 AND (`name` = 'it''s' AND x IS NOT NULL)").unwrap(),
+            vec![
+                condition("id", Comparison::GtEq, StatisticValue::Int(5)),
+                condition(
+                    "name",
+                    Comparison::Eq,
+                    StatisticValue::String("it's".to_string())
+                ),
+                Condition {
+                    column: "x".to_string(),
+                    comparison: Comparison::IsNotNull,
+                    value: None,
+                },
+            ]
+        );
+        // Conditions that can not be checked are left out
+        assert_eq!(
+            parse_conditions("lower(name) = 'a' AND id < 2.5 AND (a = 1 OR b = 2)").unwrap(),
+            vec![condition("id", Comparison::Lt, StatisticValue::Float(2.5))]
+        );
+        assert_eq!(parse_conditions("id = 1 OR id = 2"), None);
+        assert_eq!(parse_conditions("id <> 1"), None);
+        assert_eq!(parse_conditions("name = 'AND OR'").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_may_match() {
+        let ints: ArrayRef = Arc::new(Int32Array::from(vec![Some(3), None, Some(7)]));
+        let stats = array_statistics(&ints).unwrap();
+        assert_eq!(stats.min, Some(StatisticValue::Int(3)));
+        assert_eq!(stats.max, Some(StatisticValue::Int(7)));
+        assert_eq!(stats.null_count, 1);
+        let may_match = |filter: &str| {
+            parse_conditions(filter)
+                .unwrap()
+                .iter()
+                .all(|c| c.may_match(&stats))
+        };
+        assert!(may_match("x = 3"));
+        assert!(!may_match("x = 8"));
+        assert!(!may_match("x < 3"));
+        assert!(may_match("x <= 3"));
+        assert!(!may_match("x > 7"));
+        assert!(may_match("x >= 6.5"));
+        assert!(may_match("x IS NULL"));
+        assert!(may_match("x = 'a'"));
+
+        let strings: ArrayRef = Arc::new(StringArray::from(vec!["b", "d"]));
+        let stats = array_statistics(&strings).unwrap();
+        assert!(!parse_conditions("s IS NULL").unwrap()[0].may_match(&stats));
+        assert!(!parse_conditions("s > 'd'").unwrap()[0].may_match(&stats));
+        assert!(parse_conditions("s > 'c'").unwrap()[0].may_match(&stats));
+    }
+}
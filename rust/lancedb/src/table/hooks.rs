@@ -0,0 +1,66 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hooks that run after each commit to a table
+//!
+//! External catalogs (e.g. a metastore that tracks the current version of
+//! each table) need to learn about every new version.  A [`CommitHook`] is
+//! called after each successful commit made through a table handle, with the
+//! new version and a summary of the operation.
+//!
+//! Hooks are configured for all tables of a connection with
+//! [`crate::connection::ConnectBuilder::commit_hook`] or for a single table
+//! with [`crate::connection::OpenTableBuilder::commit_hook`].
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use lance::io::ObjectStoreParams;
+
+use crate::error::Result;
+
+/// A summary of a commit to a table
+#[derive(Debug, Clone)]
+pub struct CommitInfo {
+    /// The name of the table
+    pub table: String,
+    /// The uri of the table
+    pub uri: String,
+    /// The parameters of the object store of the table
+    ///
+    /// Hooks that write next to the table must use them, the table may be on
+    /// a wrapped (e.g. encrypted) store.
+    pub store_params: ObjectStoreParams,
+    /// The version created by the commit
+    pub version: u64,
+    /// The operation that made the commit, e.g. "add", "delete" or "create_index"
+    pub operation: String,
+    /// The number of rows written, if known
+    pub rows: Option<usize>,
+    /// The parameters of the operation, if any, e.g. the predicate of a delete
+    pub details: Option<String>,
+    /// The context of the table handle, see
+    /// [`crate::connection::OpenTableBuilder::audit_context`]
+    pub context: Option<String>,
+}
+
+/// Called after each successful commit to a table
+///
+/// The commit has already happened when the hook runs, so a failing hook
+/// does not undo it.  The error is returned to the caller of the operation,
+/// which should treat the table as modified.
+#[async_trait]
+pub trait CommitHook: Send + Sync + Debug {
+    async fn after_commit(&self, commit: &CommitInfo) -> Result<()>;
+}
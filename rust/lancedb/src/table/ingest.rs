@@ -0,0 +1,399 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adding the contents of many files to a table
+//!
+//! [`crate::Table::add_files`] reads up to `parallelism` files at the same
+//! time.  Each file is read completely into memory and then staged with a
+//! [bulk load](super::bulk), so at most `parallelism` files are held in
+//! memory: a file is only read once a slot is free.  The staged files are
+//! committed every [`AddFilesBuilder::files_per_commit`] files, by default
+//! all of them are committed together.
+//!
+//! Files are read through the object store of their URI, so local paths and
+//! cloud storage URIs can be mixed.  The columns of CSV files are matched
+//! with the columns of the table by the names in the header row, which must
+//! name every column of the table.  JSON lines files are parsed with the
+//! schema of the table, Parquet files must have a schema the table accepts.
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+use arrow_array::{RecordBatch, RecordBatchIterator};
+use arrow_schema::{ArrowError, Schema, SchemaRef};
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use lance::io::ObjectStore;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::error::{Error, Result};
+
+use super::{Table, WriteOptions};
+
+/// The default number of files read at the same time
+pub const DEFAULT_INGEST_PARALLELISM: usize = 8;
+
+/// The format of the files passed to [`crate::Table::add_files`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    /// Apache Parquet
+    Parquet,
+    /// Comma separated values, with a header row
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+/// The outcome of [`crate::Table::add_files`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddFilesStats {
+    /// The number of files that were added
+    pub files: usize,
+    /// The number of rows that were added
+    pub rows: usize,
+    /// The versions created by the commits, in order
+    pub versions: Vec<u64>,
+}
+
+/// A builder for adding the contents of files to a table
+///
+/// See [`crate::Table::add_files`] for more context
+pub struct AddFilesBuilder {
+    table: Table,
+    paths: Vec<String>,
+    format: FileFormat,
+    parallelism: usize,
+    files_per_commit: Option<usize>,
+}
+
+impl AddFilesBuilder {
+    pub(super) fn new(table: Table, paths: Vec<String>, format: FileFormat) -> Self {
+        Self {
+            table,
+            paths,
+            format,
+            parallelism: DEFAULT_INGEST_PARALLELISM,
+            files_per_commit: None,
+        }
+    }
+
+    /// The number of files to read at the same time
+    ///
+    /// This also bounds the number of files held in memory.  The default is
+    /// [`DEFAULT_INGEST_PARALLELISM`].
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// Commit after every `files` files instead of once at the end
+    ///
+    /// Smaller commits make the data visible sooner and lose less work if
+    /// the ingestion fails, but create more versions.
+    pub fn files_per_commit(mut self, files: usize) -> Self {
+        self.files_per_commit = Some(files);
+        self
+    }
+
+    /// Read the files and add them to the table
+    ///
+    /// If a file can not be read or added, the files of the current commit
+    /// are discarded and the error is returned.  The commits made before
+    /// remain.
+    ///
+    /// Fails if data is already staged on the table handle by a bulk load,
+    /// finish or discard that load first.
+    pub async fn execute(mut self) -> Result<AddFilesStats> {
+        if self.parallelism == 0 || self.files_per_commit == Some(0) {
+            return Err(Error::InvalidInput {
+                message: "the parallelism and the files per commit must be at least 1".to_string(),
+            });
+        }
+        if let Some(native) = self.table.as_native() {
+            if native.bulk_load.staged_rows() > 0 {
+                return Err(Error::InvalidInput {
+                    message: "a bulk load is in progress on this table, finish or discard it before adding files".to_string(),
+                });
+            }
+        }
+        let schema = self.table.schema().await?;
+        let format = self.format;
+        let paths = std::mem::take(&mut self.paths);
+        let mut files = futures::stream::iter(paths)
+            .map(|path| {
+                let schema = schema.clone();
+                async move { read_file(&path, format, schema).await }
+            })
+            .buffered(self.parallelism);
+
+        let files_per_commit = self.files_per_commit.unwrap_or(usize::MAX);
+        let mut stats = AddFilesStats::default();
+        let mut staged = 0;
+        loop {
+            let staged_file = match files.try_next().await {
+                Ok(Some(batches)) => self.stage(batches, &schema).await,
+                Ok(None) => break,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = staged_file {
+                // Discard the files staged for this commit
+                self.table.discard_bulk_load().await?;
+                return Err(err);
+            }
+            stats.files += 1;
+            staged += 1;
+            if staged == files_per_commit {
+                self.commit(&mut stats).await?;
+                staged = 0;
+            }
+        }
+        if staged > 0 {
+            self.commit(&mut stats).await?;
+        }
+        Ok(stats)
+    }
+
+    async fn stage(&self, batches: Vec<RecordBatch>, schema: &SchemaRef) -> Result<()> {
+        let batch_schema = match batches.first() {
+            Some(batch) => batch.schema(),
+            None => schema.clone(),
+        };
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), batch_schema);
+        self.table
+            .add(reader)
+            .write_options(WriteOptions::default().bulk_load(true))
+            .execute()
+            .await
+    }
+
+    async fn commit(&self, stats: &mut AddFilesStats) -> Result<()> {
+        let committed = self.table.finish_bulk_load().await?;
+        stats.rows += committed.rows;
+        stats.versions.extend(committed.version);
+        Ok(())
+    }
+}
+
+/// Read all of the batches of a file
+async fn read_file(path: &str, format: FileFormat, schema: SchemaRef) -> Result<Vec<RecordBatch>> {
+    let (store, location) = ObjectStore::from_uri(path).await?;
+    let bytes = store.inner.get(&location).await?.bytes().await?;
+    let batches = match format {
+        FileFormat::Parquet => parse_parquet(bytes),
+        FileFormat::Csv => parse_csv(bytes, schema),
+        FileFormat::Jsonl => arrow::json::ReaderBuilder::new(schema)
+            .build(Cursor::new(bytes))
+            .and_then(|reader| reader.collect::<std::result::Result<Vec<_>, ArrowError>>()),
+    };
+    batches.map_err(|err| Error::InvalidInput {
+        message: format!("cannot read {:?} file '{}': {}", format, path, err),
+    })
+}
+
+/// Parse a CSV file, matching its columns with `schema` by the header row
+fn parse_csv(bytes: Bytes, schema: SchemaRef) -> std::result::Result<Vec<RecordBatch>, ArrowError> {
+    let (header, _) = arrow::csv::reader::Format::default()
+        .with_header(true)
+        .infer_schema(Cursor::new(&bytes), Some(0))?;
+    let fields = header
+        .fields()
+        .iter()
+        .map(|field| {
+            schema.field_with_name(field.name()).cloned().map_err(|_| {
+                ArrowError::SchemaError(format!("the table has no column '{}'", field.name()))
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let file_schema = Arc::new(Schema::new(fields));
+    let indices = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            file_schema.index_of(field.name()).map_err(|_| {
+                ArrowError::SchemaError(format!("the header has no column '{}'", field.name()))
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    if indices.len() != file_schema.fields().len() {
+        return Err(ArrowError::SchemaError(
+            "the header names a column more than once".to_string(),
+        ));
+    }
+    arrow::csv::ReaderBuilder::new(file_schema)
+        .with_header(true)
+        .build(Cursor::new(bytes))?
+        .map(|batch| {
+            let batch = batch?.project(&indices)?;
+            RecordBatch::try_new(schema.clone(), batch.columns().to_vec())
+        })
+        .collect()
+}
+
+fn parse_parquet(bytes: Bytes) -> std::result::Result<Vec<RecordBatch>, ArrowError> {
+    ParquetRecordBatchReaderBuilder::try_new(bytes)
+        .map_err(|err| ArrowError::ParquetError(err.to_string()))?
+        .build()
+        .map_err(|err| ArrowError::ParquetError(err.to_string()))?
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int32Array, StringArray};
+    use arrow_schema::{DataType, Field};
+    use parquet::arrow::ArrowWriter;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_file() {
+        let tmp_dir = tempdir().unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+
+        let csv = tmp_dir.path().join("a.csv");
+        std::fs::write(&csv, "id,name\n1,a\n2,\n").unwrap();
+        let jsonl = tmp_dir.path().join("b.jsonl");
+        std::fs::write(&jsonl, "{\"id\": 3, \"name\": \"c\"}\n{\"id\": 4}\n").unwrap();
+        let parquet = tmp_dir.path().join("c.parquet");
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![5])),
+                Arc::new(StringArray::from(vec!["e"])),
+            ],
+        )
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(
+            std::fs::File::create(&parquet).unwrap(),
+            schema.clone(),
+            None,
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        for (path, format, rows) in [
+            (csv, FileFormat::Csv, 2),
+            (jsonl, FileFormat::Jsonl, 2),
+            (parquet, FileFormat::Parquet, 1),
+        ] {
+            let batches = read_file(path.to_str().unwrap(), format, schema.clone())
+                .await
+                .unwrap();
+            assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), rows);
+            assert_eq!(batches[0].schema(), schema);
+        }
+
+        // The columns of a CSV file are matched by name
+        let swapped = tmp_dir.path().join("d.csv");
+        std::fs::write(&swapped, "name,id\nf,6\n").unwrap();
+        let batches = read_file(swapped.to_str().unwrap(), FileFormat::Csv, schema.clone())
+            .await
+            .unwrap();
+        assert_eq!(batches[0].schema(), schema);
+        assert_eq!(
+            batches[0].column(0).as_any().downcast_ref::<Int32Array>(),
+            Some(&Int32Array::from(vec![6]))
+        );
+        for header in ["id,other\n1,a\n", "id\n1\n", "id,name,id\n1,a,1\n"] {
+            let bad = tmp_dir.path().join("bad.csv");
+            std::fs::write(&bad, header).unwrap();
+            assert!(
+                read_file(bad.to_str().unwrap(), FileFormat::Csv, schema.clone())
+                    .await
+                    .is_err()
+            );
+        }
+
+        let missing = tmp_dir.path().join("missing.csv");
+        assert!(
+            read_file(missing.to_str().unwrap(), FileFormat::Csv, schema)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_files() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let conn = crate::connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_empty_table("test", schema)
+            .execute()
+            .await
+            .unwrap();
+
+        let paths = (0..3)
+            .map(|i| {
+                let path = tmp_dir.path().join(format!("{}.csv", i));
+                std::fs::write(&path, format!("id\n{}\n{}\n", i * 2, i * 2 + 1)).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect::<Vec<_>>();
+        let stats = table
+            .add_files(paths.clone(), FileFormat::Csv)
+            .parallelism(2)
+            .files_per_commit(2)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.rows, 6);
+        assert_eq!(stats.versions.len(), 2);
+        assert_eq!(table.count_rows(None).await.unwrap(), 6);
+
+        // A file that can not be read discards the files of its commit
+        let bad = tmp_dir.path().join("bad.csv");
+        std::fs::write(&bad, "id\nnot a number\n").unwrap();
+        let err = table
+            .add_files(
+                [paths[0].clone(), bad.to_str().unwrap().to_string()],
+                FileFormat::Csv,
+            )
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        assert_eq!(table.discard_bulk_load().await.unwrap(), 0);
+        assert_eq!(table.count_rows(None).await.unwrap(), 6);
+
+        // Data staged before is not committed or discarded with the files
+        let staged = RecordBatch::try_new(
+            table.schema().await.unwrap(),
+            vec![Arc::new(Int32Array::from(vec![100]))],
+        )
+        .unwrap();
+        table
+            .add(RecordBatchIterator::new(
+                vec![Ok(staged.clone())],
+                staged.schema(),
+            ))
+            .write_options(WriteOptions::default().bulk_load(true))
+            .execute()
+            .await
+            .unwrap();
+        assert!(table
+            .add_files(paths, FileFormat::Csv)
+            .execute()
+            .await
+            .is_err());
+        assert_eq!(table.discard_bulk_load().await.unwrap(), 1);
+    }
+}
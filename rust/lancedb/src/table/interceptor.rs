@@ -0,0 +1,99 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mandatory filters and column masks for every operation on a table
+//!
+//! A multi-tenant service often stores the rows of all tenants in one table
+//! and must make sure that no operation reads or modifies the rows of another
+//! tenant.  Instead of relying on every call site to add `tenant_id = ?`, a
+//! [`QueryInterceptor`] is configured once with
+//! [`crate::connection::ConnectBuilder::query_interceptor`] and is asked for
+//! the restrictions of each query, count, update, delete and merge insert on
+//! the tables of the connection.
+//!
+//! The mandatory filter is combined with the filter of the operation (if any)
+//! with `AND`.  The column masks are applied to query results in addition to
+//! the [masking policies](crate::table::masking) of the table, even through a
+//! handle with unmasked access.  Like the masking policies, filters and
+//! projections of the caller may not refer to a masked column.  The
+//! mandatory filter is trusted and may refer to masked columns.
+//!
+//! A merge insert only updates the matched rows, and only deletes the rows
+//! not matched by the source, that match the mandatory filter.  The interceptor
+//! does not apply to the rows inserted by `add` or `merge_insert`.  Merging
+//! columns changes every row of the table, so it is refused if the interceptor
+//! returns a mandatory filter for updates.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::table::masking::MaskingPolicy;
+
+/// The kind of operation that is intercepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterceptedOperation {
+    /// A plain query, vector search or row count
+    Query,
+    Update,
+    Delete,
+    /// A merge insert, the filter restricts the rows it updates or deletes
+    MergeInsert,
+}
+
+/// The restrictions that apply to an operation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Restrictions {
+    /// An SQL filter that every row read or modified by the operation must match
+    pub filter: Option<String>,
+    /// Masks applied to the columns of query results, by column name
+    pub masks: HashMap<String, MaskingPolicy>,
+}
+
+/// Decides the restrictions of each operation on the tables of a connection
+///
+/// For example, a service that runs each request in a task with the tenant in
+/// a task local could return `tenant_id = '<tenant>'` as the filter.  Return an
+/// error to refuse the operation altogether.
+#[async_trait]
+pub trait QueryInterceptor: Send + Sync + Debug {
+    async fn intercept(&self, table: &str, operation: InterceptedOperation)
+        -> Result<Restrictions>;
+}
+
+/// Combine the filter of an operation with a mandatory filter
+pub(crate) fn and_filters(filter: Option<&str>, mandatory: Option<&str>) -> Option<String> {
+    match (filter, mandatory) {
+        (Some(filter), Some(mandatory)) => Some(format!("({}) AND ({})", filter, mandatory)),
+        (filter, mandatory) => filter.or(mandatory).map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_and_filters() {
+        assert_eq!(and_filters(None, None), None);
+        assert_eq!(and_filters(Some("a > 1"), None).unwrap(), "a > 1");
+        assert_eq!(and_filters(None, Some("t = 2")).unwrap(), "t = 2");
+        assert_eq!(
+            and_filters(Some("a > 1 OR b"), Some("t = 2")).unwrap(),
+            "(a > 1 OR b) AND (t = 2)"
+        );
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Column masking policies
+//!
+//! A masking policy hides the values of a sensitive column from readers.  The
+//! policies are stored in the table properties (the schema metadata) so they
+//! apply to every handle that opens the table, regardless of the SDK.  Query
+//! results of a table opened without [`crate::connection::OpenTableBuilder::unmasked`]
+//! have the policies applied, and filters or projections that refer to a
+//! masked column are rejected.
+//!
+//! Masking is applied to query results only.  The vector search itself uses
+//! the original values, so a vector column should not be masked if the
+//! distances to it are also sensitive.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray, new_null_array, Array, ArrayRef, BinaryArray, LargeBinaryArray,
+    LargeStringArray, RecordBatch, StringArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+use crate::error::{Error, Result};
+
+/// The schema metadata key used to store the masking policies
+pub(crate) const MASKING_POLICIES_KEY: &str = "lancedb:masking_policies";
+
+/// How the values of a masked column are hidden
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaskingPolicy {
+    /// Replace every value with null
+    Null,
+    /// Replace every non-null value with a fixed string
+    ///
+    /// Only supported on string columns.
+    Redact { replacement: String },
+    /// Replace every non-null value with its SHA-256 digest
+    ///
+    /// Equal values have equal digests, so the column can still be joined or
+    /// grouped on.  String columns get the hex encoded digest and binary
+    /// columns the raw digest.
+    Hash,
+}
+
+impl MaskingPolicy {
+    /// Check that the policy can be applied to the field
+    pub(crate) fn validate(&self, field: &Field) -> Result<()> {
+        let supported = match self {
+            Self::Null => true,
+            Self::Redact { .. } => {
+                matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8)
+            }
+            Self::Hash => matches!(
+                field.data_type(),
+                DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary
+            ),
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput {
+                message: format!(
+                    "the masking policy {:?} can not be applied to the column '{}' of type {}",
+                    self,
+                    field.name(),
+                    field.data_type()
+                ),
+            })
+        }
+    }
+
+    fn masked_field(&self, field: &Field) -> Field {
+        match self {
+            Self::Null => field.clone().with_nullable(true),
+            _ => field.clone(),
+        }
+    }
+
+    fn apply(&self, array: &ArrayRef) -> Result<ArrayRef> {
+        let masked: ArrayRef = match (self, array.data_type()) {
+            (Self::Null, data_type) => new_null_array(data_type, array.len()),
+            (Self::Redact { replacement }, DataType::Utf8) => Arc::new(
+                array
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.map(|_| replacement.as_str()))
+                    .collect::<StringArray>(),
+            ),
+            (Self::Redact { replacement }, DataType::LargeUtf8) => Arc::new(
+                array
+                    .as_string::<i64>()
+                    .iter()
+                    .map(|v| v.map(|_| replacement.as_str()))
+                    .collect::<LargeStringArray>(),
+            ),
+            (Self::Hash, DataType::Utf8) => Arc::new(
+                array
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.map(|v| hex_digest(v.as_bytes())))
+                    .collect::<StringArray>(),
+            ),
+            (Self::Hash, DataType::LargeUtf8) => Arc::new(
+                array
+                    .as_string::<i64>()
+                    .iter()
+                    .map(|v| v.map(|v| hex_digest(v.as_bytes())))
+                    .collect::<LargeStringArray>(),
+            ),
+            (Self::Hash, DataType::Binary) => Arc::new(
+                array
+                    .as_binary::<i32>()
+                    .iter()
+                    .map(|v| v.map(|v| Sha256::digest(v).to_vec()))
+                    .collect::<BinaryArray>(),
+            ),
+            (Self::Hash, DataType::LargeBinary) => Arc::new(
+                array
+                    .as_binary::<i64>()
+                    .iter()
+                    .map(|v| v.map(|v| Sha256::digest(v).to_vec()))
+                    .collect::<LargeBinaryArray>(),
+            ),
+            (_, data_type) => {
+                return Err(Error::Runtime {
+                    message: format!(
+                        "the masking policy {:?} can not be applied to type {}",
+                        self, data_type
+                    ),
+                })
+            }
+        };
+        Ok(masked)
+    }
+}
+
+fn hex_digest(value: &[u8]) -> String {
+    Sha256::digest(value)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The masking policies of a table, by column name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MaskingPolicies(pub BTreeMap<String, MaskingPolicy>);
+
+impl MaskingPolicies {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(MASKING_POLICIES_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the masking policies: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        if self.0.is_empty() {
+            metadata.remove(MASKING_POLICIES_KEY);
+            return Ok(());
+        }
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the masking policies: {}", e),
+        })?;
+        metadata.insert(MASKING_POLICIES_KEY.to_string(), value);
+        Ok(())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Fail if a filter or projection refers to a masked column
+    pub(crate) fn check_not_referenced<'a>(
+        &self,
+        columns: impl IntoIterator<Item = &'a String>,
+        usage: &str,
+    ) -> Result<()> {
+        for column in columns {
+            if self.0.contains_key(column) {
+                return Err(Error::PermissionDenied {
+                    message: format!(
+                        "the column '{}' is masked and can not be used in a {}",
+                        column, usage
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn masked_schema(&self, schema: &Schema) -> SchemaRef {
+        let fields = schema
+            .fields()
+            .iter()
+            .map(|field| match self.0.get(field.name()) {
+                Some(policy) => Arc::new(policy.masked_field(field)),
+                None => field.clone(),
+            })
+            .collect::<Vec<_>>();
+        Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+    }
+
+    fn mask_batch(&self, schema: &SchemaRef, batch: RecordBatch) -> Result<RecordBatch> {
+        let columns = batch
+            .schema()
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, column)| match self.0.get(field.name()) {
+                Some(policy) => policy.apply(column),
+                None => Ok(column.clone()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+
+    /// Apply the policies to a single batch of results
+    pub(crate) fn mask_record_batch(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let schema = self.masked_schema(&batch.schema());
+        self.mask_batch(&schema, batch)
+    }
+
+    /// Apply the policies to the results of a query
+    pub(crate) fn mask_stream(
+        self,
+        stream: SendableRecordBatchStream,
+    ) -> SendableRecordBatchStream {
+        let schema = self.masked_schema(&stream.schema());
+        let batch_schema = schema.clone();
+        Box::pin(SimpleRecordBatchStream {
+            schema,
+            stream: stream.map(move |batch| self.mask_batch(&batch_schema, batch?)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Int32Array;
+    use futures::{stream, TryStreamExt};
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let name = Field::new("name", DataType::Utf8, false);
+        let age = Field::new("age", DataType::Int32, false);
+        assert!(MaskingPolicy::Null.validate(&age).is_ok());
+        assert!(MaskingPolicy::Hash.validate(&name).is_ok());
+        assert!(MaskingPolicy::Hash.validate(&age).is_err());
+        let redact = MaskingPolicy::Redact {
+            replacement: "***".to_string(),
+        };
+        assert!(redact.validate(&name).is_ok());
+        assert!(redact.validate(&age).is_err());
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let mut policies = MaskingPolicies::default();
+        policies.0.insert("ssn".to_string(), MaskingPolicy::Hash);
+        let mut metadata = HashMap::new();
+        policies.apply_to_metadata(&mut metadata).unwrap();
+        assert_eq!(MaskingPolicies::from_metadata(&metadata).unwrap(), policies);
+
+        MaskingPolicies::default()
+            .apply_to_metadata(&mut metadata)
+            .unwrap();
+        assert!(metadata.is_empty());
+        assert!(MaskingPolicies::from_metadata(&metadata)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mask_stream() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("age", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("alice"), None])),
+                Arc::new(Int32Array::from(vec![30, 40])),
+            ],
+        )
+        .unwrap();
+        let inner: SendableRecordBatchStream = Box::pin(SimpleRecordBatchStream {
+            schema,
+            stream: stream::iter(vec![Ok(batch)]),
+        });
+
+        let mut policies = MaskingPolicies::default();
+        policies.0.insert(
+            "name".to_string(),
+            MaskingPolicy::Redact {
+                replacement: "***".to_string(),
+            },
+        );
+        policies.0.insert("age".to_string(), MaskingPolicy::Null);
+        let masked = policies.mask_stream(inner);
+        assert!(masked
+            .schema()
+            .field_with_name("age")
+            .unwrap()
+            .is_nullable());
+
+        let batches = masked.try_collect::<Vec<_>>().await.unwrap();
+        let names = batches[0]["name"].as_string::<i32>();
+        assert_eq!(names.value(0), "***");
+        assert!(names.is_null(1));
+        assert_eq!(batches[0]["age"].null_count(), 2);
+        assert_eq!(
+            batches[0]["id"]
+                .as_primitive::<arrow_array::types::Int32Type>()
+                .value(1),
+            2
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        let array: ArrayRef = Arc::new(StringArray::from(vec!["a", "a", "b"]));
+        let hashed = MaskingPolicy::Hash.apply(&array).unwrap();
+        let hashed = hashed.as_string::<i32>();
+        assert_eq!(hashed.value(0), hashed.value(1));
+        assert_ne!(hashed.value(0), hashed.value(2));
+        assert_eq!(
+            hashed.value(0),
+            "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"
+        );
+    }
+}
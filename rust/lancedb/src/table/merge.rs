@@ -18,6 +18,7 @@ use arrow_array::RecordBatchReader;
 
 use crate::Result;
 
+use super::primary_key::DuplicateKeys;
 use super::TableInternal;
 
 /// A builder used to create and run a merge insert operation
@@ -25,12 +26,13 @@ use super::TableInternal;
 /// See [`super::Table::merge_insert`] for more context
 pub struct MergeInsertBuilder {
     table: Arc<dyn TableInternal>,
-    pub(super) on: Vec<String>,
-    pub(super) when_matched_update_all: bool,
-    pub(super) when_matched_update_all_filt: Option<String>,
-    pub(super) when_not_matched_insert_all: bool,
-    pub(super) when_not_matched_by_source_delete: bool,
-    pub(super) when_not_matched_by_source_delete_filt: Option<String>,
+    pub(crate) on: Vec<String>,
+    pub(crate) when_matched_update_all: bool,
+    pub(crate) when_matched_update_all_filt: Option<String>,
+    pub(crate) when_not_matched_insert_all: bool,
+    pub(crate) when_not_matched_by_source_delete: bool,
+    pub(crate) when_not_matched_by_source_delete_filt: Option<String>,
+    pub(super) duplicate_keys: DuplicateKeys,
 }
 
 impl MergeInsertBuilder {
@@ -43,6 +45,7 @@ impl MergeInsertBuilder {
             when_not_matched_insert_all: false,
             when_not_matched_by_source_delete: false,
             when_not_matched_by_source_delete_filt: None,
+            duplicate_keys: DuplicateKeys::default(),
         }
     }
 
@@ -94,6 +97,18 @@ impl MergeInsertBuilder {
         self
     }
 
+    /// What to do with rows of the new data that repeat a key of an earlier row
+    ///
+    /// By default duplicate keys are allowed.  Any other policy requires the
+    /// operation to match on the primary key of the table (see
+    /// [`super::Table::set_primary_key`]), so rows that match an existing key
+    /// are updated instead of duplicated.  The new data is read into memory
+    /// before the operation runs.
+    pub fn on_duplicate_keys(&mut self, policy: DuplicateKeys) -> &mut Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
     /// Executes the merge insert operation
     ///
     /// Nothing is returned but the [`super::Table`] is updated
@@ -0,0 +1,215 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, RecordBatch, RecordBatchIterator, RecordBatchReader,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+
+use crate::error::{Error, Result};
+
+use super::TableInternal;
+
+/// The column added to the new data to find the rows without a match
+pub(super) const MATCHED_COLUMN: &str = "_lancedb_merge_matched";
+
+/// Which rows of the table are kept by a merge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeJoinType {
+    /// All rows are kept, rows without a match get nulls in the new columns
+    #[default]
+    Left,
+    /// Rows without a match in the new data are deleted
+    Inner,
+}
+
+/// A builder used to merge new columns into a table
+///
+/// See [`super::Table::merge`] for more context
+pub struct MergeColumnsBuilder {
+    table: Arc<dyn TableInternal>,
+    pub(super) left_on: String,
+    pub(super) right_on: String,
+    pub(super) join_type: MergeJoinType,
+    pub(super) validate_keys: bool,
+}
+
+impl MergeColumnsBuilder {
+    pub(super) fn new(table: Arc<dyn TableInternal>, left_on: String, right_on: String) -> Self {
+        Self {
+            table,
+            left_on,
+            right_on,
+            join_type: MergeJoinType::default(),
+            validate_keys: false,
+        }
+    }
+
+    /// Set which rows of the table are kept, the default is [`MergeJoinType::Left`]
+    ///
+    /// An inner merge is applied as a merge followed by a delete, so it
+    /// creates more than one version of the table.
+    pub fn join_type(&mut self, join_type: MergeJoinType) -> &mut Self {
+        self.join_type = join_type;
+        self
+    }
+
+    /// Check the keys of the new data before merging
+    ///
+    /// The merge fails if a key in the new data is null, appears more than
+    /// once, or does not match any row of the table (such rows would be
+    /// silently ignored otherwise).  The new data is buffered in memory to
+    /// perform the check.
+    pub fn validate_keys(&mut self) -> &mut Self {
+        self.validate_keys = true;
+        self
+    }
+
+    /// Executes the merge
+    ///
+    /// Nothing is returned but the [`super::Table`] is updated
+    pub async fn execute(self, new_data: Box<dyn RecordBatchReader + Send>) -> Result<()> {
+        self.table.clone().merge_columns(self, new_data).await
+    }
+}
+
+fn key_rows(converter: &RowConverter, array: &ArrayRef) -> Result<Vec<OwnedRow>> {
+    let rows = converter.convert_columns(&[array.clone()])?;
+    Ok(rows.iter().map(|row| row.owned()).collect())
+}
+
+/// Read all of the new data and check its keys, see [`MergeColumnsBuilder::validate_keys`]
+///
+/// Returns the new data, which has been read into memory
+pub(super) async fn validate_merge_keys(
+    dataset: &Dataset,
+    left_on: &str,
+    right_on: &str,
+    new_data: Box<dyn RecordBatchReader + Send>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = new_data.schema();
+    let batches = new_data.collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let (right_idx, right_field) =
+        schema
+            .column_with_name(right_on)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!("the new data has no column '{}'", right_on),
+            })?;
+    let left_field = dataset
+        .schema()
+        .field(left_on)
+        .ok_or_else(|| Error::InvalidInput {
+            message: format!("the table has no column '{}'", left_on),
+        })?;
+    if &left_field.data_type() != right_field.data_type() {
+        return Err(Error::InvalidInput {
+            message: format!(
+                "cannot merge on '{}' ({}) and '{}' ({}), the key types differ",
+                left_on,
+                left_field.data_type(),
+                right_on,
+                right_field.data_type()
+            ),
+        });
+    }
+
+    let converter = RowConverter::new(vec![SortField::new(right_field.data_type().clone())])?;
+    let mut right_keys = HashSet::new();
+    for batch in &batches {
+        let keys = batch.column(right_idx);
+        if keys.null_count() > 0 {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the key column '{}' of the new data contains nulls",
+                    right_on
+                ),
+            });
+        }
+        for key in key_rows(&converter, keys)? {
+            if !right_keys.insert(key) {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the key column '{}' of the new data contains duplicate values",
+                        right_on
+                    ),
+                });
+            }
+        }
+    }
+
+    let mut scanner = dataset.scan();
+    scanner.project(&[left_on])?;
+    let mut left_batches = scanner.try_into_stream().await?;
+    while let Some(batch) = left_batches.try_next().await? {
+        for key in key_rows(&converter, batch.column(0))? {
+            right_keys.remove(&key);
+        }
+    }
+    if !right_keys.is_empty() {
+        return Err(Error::InvalidInput {
+            message: format!(
+                "{} key(s) of the new data do not match any row of the table",
+                right_keys.len()
+            ),
+        });
+    }
+
+    Ok(Box::new(RecordBatchIterator::new(
+        batches.into_iter().map(Ok),
+        schema,
+    )))
+}
+
+/// Add a column that is true for every row of the new data
+///
+/// After a left merge the column is null for the rows of the table that had
+/// no match.
+pub(super) fn with_matched_column(
+    new_data: Box<dyn RecordBatchReader + Send>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let mut fields = new_data
+        .schema()
+        .fields()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    if fields.iter().any(|f| f.name() == MATCHED_COLUMN) {
+        return Err(Error::InvalidInput {
+            message: format!("the column name '{}' is reserved", MATCHED_COLUMN),
+        });
+    }
+    fields.push(Arc::new(Field::new(
+        MATCHED_COLUMN,
+        DataType::Boolean,
+        true,
+    )));
+    let schema: SchemaRef = Arc::new(Schema::new(fields));
+    let batch_schema = schema.clone();
+    Ok(Box::new(RecordBatchIterator::new(
+        new_data.map(move |batch| {
+            let batch = batch?;
+            let mut columns = batch.columns().to_vec();
+            columns.push(Arc::new(BooleanArray::from(vec![true; batch.num_rows()])));
+            RecordBatch::try_new(batch_schema.clone(), columns)
+        }),
+        schema,
+    )))
+}
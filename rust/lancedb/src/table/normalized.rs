@@ -0,0 +1,363 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Normalized string columns
+//!
+//! A btree index created with
+//! [`crate::index::scalar::BTreeIndexBuilder::normalization`] is built on a
+//! hidden column that stores the normalized values of a string column, e.g.
+//! `_lower_name` holds `lower(name)`.  The hidden columns are recorded in the
+//! schema metadata.  Writes fill in the hidden column of new rows, queries
+//! leave it out of their results and filters that apply the normalization
+//! function to the source column are rewritten to read the hidden column, so
+//! that lance can use its index.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray, ArrayRef, GenericStringArray, OffsetSizeTrait, RecordBatch, RecordBatchIterator,
+    RecordBatchReader,
+};
+use arrow_schema::{DataType, Field, Schema};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::index::scalar::Normalization;
+use crate::query::filter::{is_ident_part, is_ident_start};
+
+/// The schema metadata key of the normalized columns
+pub(crate) const NORMALIZED_COLUMNS_KEY: &str = "lancedb:normalized_columns";
+
+/// The hidden column storing the normalized values of a column
+pub(crate) fn normalized_column_name(column: &str, normalization: Normalization) -> String {
+    format!("_{}_{}", normalization.function(), column)
+}
+
+/// The source of a normalized column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct NormalizedColumn {
+    pub column: String,
+    pub normalization: Normalization,
+}
+
+/// The normalized columns of a table, by the name of the hidden column
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct NormalizedColumns(pub BTreeMap<String, NormalizedColumn>);
+
+impl NormalizedColumns {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(NORMALIZED_COLUMNS_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the normalized columns: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the normalized columns: {}", e),
+        })?;
+        metadata.insert(NORMALIZED_COLUMNS_KEY.to_string(), value);
+        Ok(())
+    }
+
+    pub(crate) fn is_hidden(&self, column: &str) -> bool {
+        self.0.contains_key(column)
+    }
+
+    /// The assignments that keep the hidden columns in sync with an update
+    pub(crate) fn update_columns(&self, columns: &[(String, String)]) -> Vec<(String, String)> {
+        self.0
+            .iter()
+            .filter_map(|(hidden, normalized)| {
+                columns
+                    .iter()
+                    .find(|(column, _)| column == &normalized.column)
+                    .map(|(_, value)| {
+                        (
+                            hidden.clone(),
+                            format!("{}({})", normalized.normalization.function(), value),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// The hidden column read by a call like `lower(name)`, if there is one
+    fn find(&self, function: &str, column: &str, quoted: bool) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, normalized)| {
+                normalized
+                    .normalization
+                    .function()
+                    .eq_ignore_ascii_case(function)
+                    && if quoted {
+                        normalized.column == column
+                    } else {
+                        normalized.column.eq_ignore_ascii_case(column)
+                    }
+            })
+            .map(|(hidden, _)| hidden.as_str())
+    }
+
+    /// Rewrite the normalized column references of a filter to the hidden columns
+    ///
+    /// Only a direct call on a column is rewritten, `lower(name) = 'foo'`
+    /// becomes `` `_lower_name` = 'foo' ``.  String literals and every other
+    /// expression are copied as is.
+    pub(crate) fn rewrite_filter(&self, filter: &str) -> String {
+        if self.0.is_empty() {
+            return filter.to_string();
+        }
+        let chars = filter.chars().collect::<Vec<_>>();
+        let mut out = String::with_capacity(filter.len());
+        let mut pos = 0;
+        while pos < chars.len() {
+            let c = chars[pos];
+            if c == '\'' || c == '`' || c == '"' {
+                let end = quoted_end(&chars, pos);
+                out.extend(&chars[pos..end]);
+                pos = end;
+            } else if is_ident_start(c) {
+                let start = pos;
+                while pos < chars.len() && is_ident_part(chars[pos]) {
+                    pos += 1;
+                }
+                let word = chars[start..pos].iter().collect::<String>();
+                // A field of a struct column is not a function call
+                let call = if start > 0 && chars[start - 1] == '.' {
+                    None
+                } else {
+                    self.match_call(&chars, pos, &word)
+                };
+                match call {
+                    Some((end, hidden)) => {
+                        out.push_str(&format!("`{}`", hidden));
+                        pos = end;
+                    }
+                    None => out.push_str(&word),
+                }
+            } else {
+                out.push(c);
+                pos += 1;
+            }
+        }
+        out
+    }
+
+    /// Match `(column)` after a function name, returns the end and the hidden column
+    fn match_call(&self, chars: &[char], pos: usize, function: &str) -> Option<(usize, &str)> {
+        let skip_whitespace = |mut pos: usize| {
+            while pos < chars.len() && chars[pos].is_whitespace() {
+                pos += 1;
+            }
+            pos
+        };
+        let mut pos = skip_whitespace(pos);
+        if chars.get(pos) != Some(&'(') {
+            return None;
+        }
+        pos = skip_whitespace(pos + 1);
+        let (column, quoted) = match chars.get(pos) {
+            Some(&quote) if quote == '`' || quote == '"' => {
+                let end = quoted_end(chars, pos);
+                if end < pos + 2 || chars[end - 1] != quote {
+                    return None;
+                }
+                let column = chars[pos + 1..end - 1]
+                    .iter()
+                    .collect::<String>()
+                    .replace(&format!("{}{}", quote, quote), &quote.to_string());
+                pos = end;
+                (column, true)
+            }
+            Some(&first) if is_ident_start(first) => {
+                let start = pos;
+                while pos < chars.len() && is_ident_part(chars[pos]) {
+                    pos += 1;
+                }
+                (chars[start..pos].iter().collect::<String>(), false)
+            }
+            _ => return None,
+        };
+        pos = skip_whitespace(pos);
+        if chars.get(pos) != Some(&')') {
+            return None;
+        }
+        self.find(function, &column, quoted)
+            .map(|hidden| (pos + 1, hidden))
+    }
+}
+
+/// The end of the quoted string or identifier that starts at `pos`
+///
+/// A doubled quote is an escaped quote.  An unterminated quote runs to the end,
+/// the filter is rejected later on when it is parsed.
+fn quoted_end(chars: &[char], pos: usize) -> usize {
+    let quote = chars[pos];
+    let mut end = pos + 1;
+    while end < chars.len() {
+        if chars[end] == quote {
+            if chars.get(end + 1) == Some(&quote) {
+                end += 2;
+                continue;
+            }
+            return end + 1;
+        }
+        end += 1;
+    }
+    chars.len()
+}
+
+fn normalize_strings<O: OffsetSizeTrait>(
+    column: &ArrayRef,
+    normalization: Normalization,
+) -> ArrayRef {
+    Arc::new(
+        column
+            .as_string::<O>()
+            .iter()
+            .map(|value| value.map(|value| normalization.apply(value)))
+            .collect::<GenericStringArray<O>>(),
+    )
+}
+
+/// Fill in the hidden columns of new data, if the data does not have them yet
+///
+/// The hidden column of a source column that is missing from the data is
+/// null, as is the source column.
+pub(crate) fn with_normalized_columns(
+    data: Box<dyn RecordBatchReader + Send>,
+    normalized: &NormalizedColumns,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = data.schema();
+    let mut fields = schema.fields().to_vec();
+    let mut added = Vec::new();
+    for (hidden, normalized) in &normalized.0 {
+        if schema.field_with_name(hidden).is_ok() {
+            continue;
+        }
+        let (source, data_type) = match schema.index_of(&normalized.column) {
+            Ok(index) => (Some(index), schema.field(index).data_type().clone()),
+            Err(_) => (None, DataType::Utf8),
+        };
+        if !matches!(data_type, DataType::Utf8 | DataType::LargeUtf8) {
+            return Err(Error::Schema {
+                message: format!(
+                    "the column '{}' has a normalized index and must be a string column, not {}",
+                    normalized.column, data_type
+                ),
+            });
+        }
+        fields.push(Arc::new(Field::new(hidden, data_type, true)));
+        added.push((source, normalized.normalization));
+    }
+    if added.is_empty() {
+        return Ok(data);
+    }
+    let normalized_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+    let output_schema = normalized_schema.clone();
+    let batches = data.map(move |batch| {
+        let batch = batch?;
+        let mut columns = batch.columns().to_vec();
+        for (source, normalization) in &added {
+            let column = match source {
+                Some(index) => {
+                    let source = batch.column(*index);
+                    match source.data_type() {
+                        DataType::LargeUtf8 => normalize_strings::<i64>(source, *normalization),
+                        _ => normalize_strings::<i32>(source, *normalization),
+                    }
+                }
+                None => arrow_array::new_null_array(&DataType::Utf8, batch.num_rows()),
+            };
+            columns.push(column);
+        }
+        RecordBatch::try_new(normalized_schema.clone(), columns)
+    });
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::StringArray;
+
+    use super::*;
+
+    fn lower_name() -> NormalizedColumns {
+        NormalizedColumns(BTreeMap::from([(
+            "_lower_name".to_string(),
+            NormalizedColumn {
+                column: "name".to_string(),
+                normalization: Normalization::Lowercase,
+            },
+        )]))
+    }
+
+    #[test]
+    fn test_rewrite_filter() {
+        let normalized = lower_name();
+        assert_eq!(
+            normalized.rewrite_filter("lower(name) = 'foo'"),
+            "`_lower_name` = 'foo'"
+        );
+        assert_eq!(
+            normalized.rewrite_filter("LOWER( `name` ) IN ('a', 'b') AND id > 1"),
+            "`_lower_name` IN ('a', 'b') AND id > 1"
+        );
+        // Not a direct call on the column, or a different function
+        for filter in [
+            "lower(name || 'x') = 'foo'",
+            "upper(name) = 'FOO'",
+            "lower(other) = 'foo'",
+            "name = 'lower(name)'",
+            "s.lower(name) = 'foo'",
+        ] {
+            assert_eq!(normalized.rewrite_filter(filter), filter);
+        }
+    }
+
+    #[test]
+    fn test_with_normalized_columns() {
+        let schema = Arc::new(Schema::new(vec![Field::new("name", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![Some("Foo"), None]))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let normalized = with_normalized_columns(Box::new(reader), &lower_name()).unwrap();
+        let batches = normalized
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let hidden = batches[0]
+            .column_by_name("_lower_name")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(hidden.value(0), "foo");
+        assert!(hidden.is_null(1));
+
+        let columns = vec![("name".to_string(), "'Bar'".to_string())];
+        assert_eq!(
+            lower_name().update_columns(&columns),
+            vec![("_lower_name".to_string(), "lower('Bar')".to_string())]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pinning of the versions read by in-flight queries
+//!
+//! A query reads the files of the version that was current when it started.
+//! Compaction only adds new files, but pruning old versions deletes the files
+//! that are no longer referenced by the versions it keeps.  While a query
+//! stream is alive its version is pinned, and pruning keeps every version
+//! starting from the oldest pinned one.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use futures::StreamExt;
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+
+/// The number of live query streams reading each version
+#[derive(Debug, Default)]
+pub(crate) struct VersionPins {
+    pins: Mutex<BTreeMap<u64, usize>>,
+}
+
+impl VersionPins {
+    /// Pin the version until the returned guard is dropped
+    pub(crate) fn pin(self: &Arc<Self>, version: u64) -> VersionPin {
+        *self.pins.lock().unwrap().entry(version).or_default() += 1;
+        VersionPin {
+            pins: self.clone(),
+            version,
+        }
+    }
+
+    /// The oldest pinned version, if any
+    pub(crate) fn oldest(&self) -> Option<u64> {
+        self.pins.lock().unwrap().keys().next().copied()
+    }
+
+    /// The pinned versions, in ascending order
+    pub(crate) fn versions(&self) -> Vec<u64> {
+        self.pins.lock().unwrap().keys().copied().collect()
+    }
+}
+
+/// Keeps a version pinned while alive
+#[derive(Debug)]
+pub(crate) struct VersionPin {
+    pins: Arc<VersionPins>,
+    version: u64,
+}
+
+impl Drop for VersionPin {
+    fn drop(&mut self) {
+        let mut pins = self.pins.pins.lock().unwrap();
+        if let Some(count) = pins.get_mut(&self.version) {
+            *count -= 1;
+            if *count == 0 {
+                pins.remove(&self.version);
+            }
+        }
+    }
+}
+
+/// Keep the version pinned (if any) until the stream is dropped
+pub(crate) fn pin_while_streaming(
+    stream: SendableRecordBatchStream,
+    pin: Option<VersionPin>,
+) -> SendableRecordBatchStream {
+    match pin {
+        None => stream,
+        Some(pin) => {
+            let schema = stream.schema();
+            Box::pin(SimpleRecordBatchStream {
+                schema,
+                stream: stream.map(move |batch| {
+                    let _pin = &pin;
+                    batch
+                }),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_pins() {
+        let pins = Arc::new(VersionPins::default());
+        assert_eq!(pins.oldest(), None);
+
+        let first = pins.pin(3);
+        let second = pins.pin(3);
+        let newer = pins.pin(5);
+        assert_eq!(pins.oldest(), Some(3));
+        assert_eq!(pins.versions(), vec![3, 5]);
+
+        drop(first);
+        assert_eq!(pins.oldest(), Some(3));
+        drop(second);
+        assert_eq!(pins.oldest(), Some(5));
+        drop(newer);
+        assert!(pins.versions().is_empty());
+    }
+}
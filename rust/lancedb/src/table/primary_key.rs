@@ -0,0 +1,495 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Primary keys and point lookups
+//!
+//! A table can declare one of its columns as the primary key with
+//! [`super::Table::set_primary_key`].  The column is backed by a btree index,
+//! so [`super::Table::get`] and [`super::Table::get_many`] find rows by key
+//! with an index lookup instead of a scan.  The key column is stored in the
+//! table properties so every handle of the table uses the same column.
+//!
+//! By default declaring a primary key does not prevent duplicate keys, and if
+//! a key appears more than once lookups return one of the rows.  Writes can
+//! reject or skip rows with duplicate keys with [`DuplicateKeys`], see
+//! [`super::AddDataBuilder::on_duplicate_keys`] and
+//! [`super::merge::MergeInsertBuilder::on_duplicate_keys`].
+//!
+//! The primary key also serves as the external id of the rows, for example
+//! the id a vector has in another system.  [`super::Table::get_by_ids`],
+//! [`super::Table::update_by_ids`] and [`super::Table::delete_by_ids`] work
+//! on batches of ids, and [`super::Table::row_ids_of`] and
+//! [`super::Table::ids_of_rows`] map between ids and the addresses of rows:
+//! ids to addresses with the btree index, addresses to ids by reading the key
+//! column of the rows.
+
+use std::collections::{HashMap, HashSet};
+
+use arrow::compute::filter_record_batch;
+use arrow_array::{
+    cast::AsArray, types::Int64Type, Array, ArrayRef, BooleanArray, RecordBatch, RecordBatchReader,
+    UInt32Array,
+};
+use arrow_schema::{ArrowError, DataType, Schema, SchemaRef};
+
+use super::constraints::ViolationSlot;
+use crate::arrow::take_record_batch;
+use crate::error::{Error, Result};
+
+/// The schema metadata key used to store the primary key column
+pub(crate) const PRIMARY_KEY_KEY: &str = "lancedb:primary_key";
+
+/// The number of keys looked up with a single filter
+pub(crate) const KEY_LOOKUP_BATCH_SIZE: usize = 1024;
+
+/// The value of a primary key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KeyValue {
+    /// A key of an integer column
+    Int(i64),
+    /// A key of a string column
+    String(String),
+}
+
+impl From<i64> for KeyValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<i32> for KeyValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<&str> for KeyValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for KeyValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl KeyValue {
+    fn to_sql(&self) -> String {
+        match self {
+            Self::Int(value) => value.to_string(),
+            Self::String(value) => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+
+    fn check_type(&self, column: &str, data_type: &DataType) -> Result<()> {
+        let matches = match self {
+            Self::Int(_) => data_type.is_integer(),
+            Self::String(_) => matches!(data_type, DataType::Utf8 | DataType::LargeUtf8),
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput {
+                message: format!(
+                    "the key {:?} does not match the type {} of the column '{}'",
+                    self, data_type, column
+                ),
+            })
+        }
+    }
+}
+
+/// What a write does with rows whose primary key is already taken
+///
+/// A key is taken if it is in the table or in an earlier row of the data
+/// that is written.  Rows with a null key are rejected unless duplicates are
+/// allowed.  The keys are checked before the write is committed, so a
+/// concurrent write through another handle can still add the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Write the rows anyway (the default)
+    #[default]
+    Allow,
+    /// Fail the write if any row has a duplicate key
+    Reject,
+    /// Skip the rows with a duplicate key, keeping the first row of each key
+    Dedupe,
+}
+
+/// The keys in a key column, fails if any of them is null
+pub(crate) fn key_values(array: &ArrayRef, column: &str) -> Result<Vec<KeyValue>> {
+    if array.null_count() > 0 {
+        return Err(Error::InvalidInput {
+            message: format!("the primary key column '{}' contains nulls", column),
+        });
+    }
+    if array.data_type().is_integer() {
+        let values = arrow_cast::cast(array, &DataType::Int64)?;
+        let values = values.as_primitive::<Int64Type>();
+        if values.null_count() > 0 {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the primary key column '{}' contains keys that do not fit in an i64",
+                    column
+                ),
+            });
+        }
+        Ok(values.values().iter().map(|v| KeyValue::Int(*v)).collect())
+    } else {
+        let values = arrow_cast::cast(array, &DataType::Utf8)?;
+        Ok(values
+            .as_string::<i32>()
+            .iter()
+            .map(|v| KeyValue::String(v.unwrap_or_default().to_string()))
+            .collect())
+    }
+}
+
+/// Rejects or removes the rows whose key is taken, see [`DuplicateKeys`]
+///
+/// The data is checked one batch at a time as it is written.  Only the keys
+/// of the rows seen so far are kept, not the rows.  If a batch fails the
+/// check the message is stored in the violation slot, so the caller can
+/// return it instead of the error of the write that consumed the data.
+pub(crate) struct UniqueKeys {
+    column: String,
+    policy: DuplicateKeys,
+    seen: HashSet<KeyValue>,
+    report: ViolationSlot,
+}
+
+impl UniqueKeys {
+    pub(crate) fn new(column: String, policy: DuplicateKeys, report: ViolationSlot) -> Self {
+        Self {
+            column,
+            policy,
+            seen: HashSet::new(),
+            report,
+        }
+    }
+
+    fn keys(&self, batch: &RecordBatch) -> Result<Vec<KeyValue>> {
+        let keys = batch
+            .column_by_name(&self.column)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!(
+                    "the data is missing the primary key column '{}'",
+                    self.column
+                ),
+            })?;
+        key_values(keys, &self.column)
+    }
+
+    /// The distinct keys of the batch that are not in an earlier batch
+    ///
+    /// Only these keys need to be looked up in the table.
+    pub(crate) fn unseen_keys(&self, batch: &RecordBatch) -> Result<Vec<KeyValue>> {
+        let mut unseen = HashSet::new();
+        Ok(self
+            .keys(batch)
+            .map_err(|err| self.reported(err))?
+            .into_iter()
+            .filter(|key| !self.seen.contains(key) && unseen.insert(key.clone()))
+            .collect())
+    }
+
+    /// Check the next batch of the data
+    ///
+    /// `existing` holds the keys of the batch that are already in the table.
+    pub(crate) fn check(
+        &mut self,
+        batch: RecordBatch,
+        existing: &HashSet<KeyValue>,
+    ) -> Result<RecordBatch> {
+        self.check_batch(batch, existing)
+            .map_err(|err| self.reported(err))
+    }
+
+    fn check_batch(
+        &mut self,
+        batch: RecordBatch,
+        existing: &HashSet<KeyValue>,
+    ) -> Result<RecordBatch> {
+        if self.policy == DuplicateKeys::Allow {
+            return Ok(batch);
+        }
+        let mut keep = Vec::with_capacity(batch.num_rows());
+        for key in self.keys(&batch)? {
+            let is_new = !existing.contains(&key) && !self.seen.contains(&key);
+            if !is_new && self.policy == DuplicateKeys::Reject {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the key {:?} of the primary key column '{}' is not unique",
+                        key, self.column
+                    ),
+                });
+            }
+            keep.push(is_new);
+            self.seen.insert(key);
+        }
+        if keep.iter().all(|keep| *keep) {
+            Ok(batch)
+        } else {
+            Ok(filter_record_batch(&batch, &BooleanArray::from(keep))?)
+        }
+    }
+
+    fn reported(&self, err: Error) -> Error {
+        if let Error::InvalidInput { message } = &err {
+            *self.report.lock().unwrap() = Some(message.clone());
+        }
+        err
+    }
+
+    /// Check the data as it is read, against the keys within the data only
+    pub(crate) fn check_reader(
+        self,
+        data: Box<dyn RecordBatchReader + Send>,
+    ) -> Box<dyn RecordBatchReader + Send> {
+        Box::new(UniqueKeysReader {
+            schema: data.schema(),
+            inner: data,
+            keys: self,
+        })
+    }
+}
+
+struct UniqueKeysReader {
+    inner: Box<dyn RecordBatchReader + Send>,
+    schema: SchemaRef,
+    keys: UniqueKeys,
+}
+
+impl Iterator for UniqueKeysReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = match self.inner.next()? {
+            Ok(batch) => batch,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(
+            self.keys
+                .check(batch, &HashSet::new())
+                .map_err(|err| match err {
+                    Error::InvalidInput { message } => ArrowError::InvalidArgumentError(message),
+                    err => ArrowError::ExternalError(Box::new(err)),
+                }),
+        )
+    }
+}
+
+impl RecordBatchReader for UniqueKeysReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// The rows of the batch in the order of the keys, one row per key
+///
+/// Keys that are not in the batch, and repeated keys, are skipped.
+pub(crate) fn take_in_key_order(
+    batch: &RecordBatch,
+    column: &str,
+    keys: &[KeyValue],
+) -> Result<RecordBatch> {
+    let array = batch
+        .column_by_name(column)
+        .ok_or_else(|| Error::InvalidInput {
+            message: format!("the rows are missing the primary key column '{}'", column),
+        })?;
+    let mut rows = HashMap::with_capacity(batch.num_rows());
+    for (row, key) in key_values(array, column)?.into_iter().enumerate() {
+        rows.entry(key).or_insert(row as u32);
+    }
+    let mut seen = HashSet::with_capacity(keys.len());
+    let indices = keys
+        .iter()
+        .filter(|key| seen.insert(*key))
+        .filter_map(|key| rows.get(key).copied())
+        .collect::<UInt32Array>();
+    Ok(take_record_batch(batch, &indices)?)
+}
+
+pub(crate) fn primary_key_from_metadata(metadata: &HashMap<String, String>) -> Option<String> {
+    metadata.get(PRIMARY_KEY_KEY).cloned()
+}
+
+pub(crate) fn apply_to_metadata(column: Option<&str>, metadata: &mut HashMap<String, String>) {
+    match column {
+        Some(column) => {
+            metadata.insert(PRIMARY_KEY_KEY.to_string(), column.to_string());
+        }
+        None => {
+            metadata.remove(PRIMARY_KEY_KEY);
+        }
+    }
+}
+
+/// Check that the column can be a primary key: a top level integer or string column
+pub(crate) fn validate(schema: &Schema, column: &str) -> Result<()> {
+    let field = schema
+        .field_with_name(column)
+        .map_err(|_| Error::InvalidInput {
+            message: format!("the primary key column '{}' does not exist", column),
+        })?;
+    let data_type = field.data_type();
+    if !data_type.is_integer() && !matches!(data_type, DataType::Utf8 | DataType::LargeUtf8) {
+        return Err(Error::InvalidInput {
+            message: format!(
+                "the primary key column '{}' must be an integer or a string, not {}",
+                column, data_type
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// The filter selecting the rows with any of the keys
+pub(crate) fn lookup_filter(schema: &Schema, column: &str, keys: &[KeyValue]) -> Result<String> {
+    let data_type = schema.field_with_name(column)?.data_type();
+    for key in keys {
+        key.check_type(column, data_type)?;
+    }
+    Ok(match keys {
+        [key] => format!("`{}` = {}", column, key.to_sql()),
+        keys => format!(
+            "`{}` IN ({})",
+            column,
+            keys.iter()
+                .map(KeyValue::to_sql)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::Int32Array;
+    use arrow_schema::Field;
+
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float32, true),
+        ])
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&schema(), "id").is_ok());
+        assert!(validate(&schema(), "name").is_ok());
+        assert!(validate(&schema(), "score").is_err());
+        assert!(validate(&schema(), "nope").is_err());
+    }
+
+    #[test]
+    fn test_unique_keys() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, true)]));
+        let batch = |ids: Vec<Option<i32>>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(ids))]).unwrap()
+        };
+        let check = |policy, batches: Vec<RecordBatch>| {
+            let report = ViolationSlot::default();
+            let mut keys = UniqueKeys::new("id".to_string(), policy, report.clone());
+            let existing = HashSet::from([KeyValue::Int(3)]);
+            let checked = batches
+                .into_iter()
+                .map(|batch| keys.check(batch, &existing))
+                .collect::<Result<Vec<_>>>();
+            (checked, report.lock().unwrap().take())
+        };
+        let batches = || {
+            vec![
+                batch(vec![Some(1), Some(2), Some(1)]),
+                batch(vec![Some(3), Some(2)]),
+            ]
+        };
+
+        let (allowed, _) = check(DuplicateKeys::Allow, batches());
+        assert_eq!(
+            allowed.unwrap().iter().map(|b| b.num_rows()).sum::<usize>(),
+            5
+        );
+
+        let (deduped, _) = check(DuplicateKeys::Dedupe, batches());
+        let deduped = deduped.unwrap();
+        assert_eq!(deduped[0], batch(vec![Some(1), Some(2)]));
+        assert_eq!(deduped[1].num_rows(), 0);
+
+        let (rejected, report) = check(DuplicateKeys::Reject, batches());
+        assert!(rejected.is_err());
+        assert!(report.unwrap().contains("is not unique"));
+        assert!(
+            check(DuplicateKeys::Reject, vec![batch(vec![Some(4), Some(5)])])
+                .0
+                .is_ok()
+        );
+        assert!(
+            check(DuplicateKeys::Dedupe, vec![batch(vec![Some(4), None])])
+                .0
+                .is_err()
+        );
+
+        let mut keys = UniqueKeys::new(
+            "id".to_string(),
+            DuplicateKeys::Dedupe,
+            ViolationSlot::default(),
+        );
+        let first = batch(vec![Some(1), Some(2), Some(1)]);
+        assert_eq!(keys.unseen_keys(&first).unwrap().len(), 2);
+        keys.check(first, &HashSet::new()).unwrap();
+        assert_eq!(
+            keys.unseen_keys(&batch(vec![Some(2), Some(7)])).unwrap(),
+            vec![KeyValue::Int(7)]
+        );
+    }
+
+    #[test]
+    fn test_take_in_key_order() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(vec![1, 2, 3]))]).unwrap();
+        let ordered =
+            take_in_key_order(&batch, "id", &[3.into(), 42.into(), 1.into(), 3.into()]).unwrap();
+        assert_eq!(
+            ordered["id"]
+                .as_primitive::<arrow_array::types::Int32Type>()
+                .values()
+                .to_vec(),
+            vec![3, 1]
+        );
+    }
+
+    #[test]
+    fn test_lookup_filter() {
+        assert_eq!(
+            lookup_filter(&schema(), "id", &[5.into()]).unwrap(),
+            "`id` = 5"
+        );
+        assert_eq!(
+            lookup_filter(&schema(), "name", &["a".into(), "it's".into()]).unwrap(),
+            "`name` IN ('a', 'it''s')"
+        );
+        assert!(lookup_filter(&schema(), "id", &["a".into()]).is_err());
+    }
+}
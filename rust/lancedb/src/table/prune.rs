@@ -0,0 +1,166 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Previewing the files removed by pruning old versions
+//!
+//! [`crate::Table::preview_prune`] lists the versions and files that
+//! [`crate::table::OptimizeAction::Prune`] would remove instead of removing
+//! them.  The
+//! preview follows the rules of the cleanup of Lance:
+//!
+//! * Every version older than `older_than` is removed, except the latest.
+//! * Data files, deletion files and indices only referenced by removed
+//!   versions are removed.
+//! * Files not referenced by any version (e.g. left behind by a failed write)
+//!   are removed if they are older than `older_than` and, unless
+//!   `delete_unverified` is set, older than 7 days.
+//!
+//! The preview is computed from a listing of the table, so files written
+//! between the preview and the actual prune are not part of it.
+
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use lance::io::ObjectStore;
+use lance_index::DatasetIndexExt;
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// The directories of a table that are cleaned up by a prune
+const PRUNED_DIRS: [&str; 4] = ["data", "_deletions", "_indices", "_versions"];
+
+/// What a prune would remove, see [`crate::Table::preview_prune`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PrunePreview {
+    /// The versions that would be removed
+    pub versions: Vec<u64>,
+    /// The paths of the files that would be removed, relative to the table
+    pub files: Vec<String>,
+    /// The total size of the files that would be removed
+    pub bytes: u64,
+}
+
+/// The key of a file in the sets of referenced files
+///
+/// Deletion files are keyed without their extension since the extension
+/// depends on the type of the deletion file, and indices by their directory.
+fn file_key(relative: &str) -> String {
+    if let Some(name) = relative.strip_prefix("_deletions/") {
+        let stem = name.split_once('.').map(|(stem, _)| stem).unwrap_or(name);
+        format!("_deletions/{}.", stem)
+    } else if let Some(name) = relative.strip_prefix("_indices/") {
+        let uuid = name.split_once('/').map(|(uuid, _)| uuid).unwrap_or(name);
+        format!("_indices/{}/", uuid)
+    } else {
+        relative.to_string()
+    }
+}
+
+/// The keys of the files referenced by a version
+async fn referenced_files(dataset: &Dataset) -> Result<Vec<String>> {
+    let mut files = vec![format!("_versions/{}.manifest", dataset.version().version)];
+    for fragment in dataset.get_fragments() {
+        let metadata = fragment.metadata();
+        files.extend(metadata.files.iter().map(|f| format!("data/{}", f.path)));
+        if let Some(deletion) = &metadata.deletion_file {
+            files.push(format!(
+                "_deletions/{}-{}-{}.",
+                metadata.id, deletion.read_version, deletion.id
+            ));
+        }
+    }
+    for index in dataset.load_indices().await?.iter() {
+        files.push(format!("_indices/{}/", index.uuid));
+    }
+    Ok(files)
+}
+
+/// List what a prune with the given options would remove
+///
+/// `base` is the path of the table in `object_store`.
+pub(crate) async fn preview_prune(
+    dataset: &Dataset,
+    object_store: &ObjectStore,
+    base: &Path,
+    older_than: Duration,
+    delete_unverified: bool,
+) -> Result<PrunePreview> {
+    let now = Utc::now();
+    let cutoff = now - older_than;
+    let unverified_cutoff = if delete_unverified {
+        cutoff
+    } else {
+        cutoff.min(now - Duration::try_days(7).unwrap())
+    };
+
+    let mut preview = PrunePreview::default();
+    let versions = dataset.versions().await?;
+    let latest = versions.iter().map(|v| v.version).max().unwrap_or_default();
+    let mut kept = HashSet::new();
+    let mut removed = HashSet::new();
+    for version in versions {
+        let dataset = dataset.checkout_version(version.version).await?;
+        let files = referenced_files(&dataset).await?;
+        if version.version != latest && version.timestamp < cutoff {
+            preview.versions.push(version.version);
+            removed.extend(files);
+        } else {
+            kept.extend(files);
+        }
+    }
+
+    let store = &object_store.inner;
+    for dir in PRUNED_DIRS {
+        let mut files = store.list(Some(&base.child(dir)));
+        while let Some(meta) = files.try_next().await? {
+            let Some(relative) = meta.location.prefix_match(base) else {
+                continue;
+            };
+            let relative = relative.map(|p| p.as_ref().to_string()).collect::<Vec<_>>();
+            let relative = relative.join("/");
+            let key = file_key(&relative);
+            let remove = if kept.contains(&key) {
+                false
+            } else if removed.contains(&key) {
+                true
+            } else {
+                // Unknown files in the versions directory are never removed
+                dir != "_versions" && meta.last_modified < unverified_cutoff
+            };
+            if remove {
+                preview.bytes += meta.size as u64;
+                preview.files.push(relative);
+            }
+        }
+    }
+    preview.files.sort();
+    Ok(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_key() {
+        assert_eq!(file_key("data/abc.lance"), "data/abc.lance");
+        assert_eq!(file_key("_deletions/3-2-17.arrow"), "_deletions/3-2-17.");
+        assert_eq!(file_key("_indices/1234/index.idx"), "_indices/1234/");
+        assert_eq!(file_key("_versions/3.manifest"), "_versions/3.manifest");
+    }
+}
@@ -0,0 +1,452 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recomputing a vector column with a new embedding model
+//!
+//! [`crate::Table::reembed`] computes the embeddings of every row again from
+//! a source column, one fragment at a time, and writes them to new data
+//! files.  All of the new files are committed as a single new version, so
+//! readers never see a column with embeddings of two different models.  The
+//! embeddings either replace the column (the default) or are written to a
+//! new column with [`ReembedBuilder::output_column`], which keeps the old
+//! embeddings around to compare the models.  The name of the model is
+//! recorded in the metadata of the column, see
+//! [`super::user_metadata::EMBEDDING_MODEL_KEY`].
+//!
+//! Re-embedding a large table can take hours, so the fragments that were
+//! completed are recorded in the `_reembed` directory of the table.  Running
+//! the same re-embedding again (same column, output column and model)
+//! resumes from there, as long as the table was not changed in between.  If
+//! it was, the re-embedding starts over.  Data files written by an abandoned
+//! re-embedding are not referenced by any version, [`crate::Table::repair`]
+//! removes them.
+//!
+//! The index of the column, if any, is rebuilt with the parameters it was
+//! created with once the new embeddings are committed.  If the rebuild fails
+//! the table is restored to the version the re-embedding started from.
+
+use std::sync::Arc;
+
+use arrow::compute::concat;
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use bytes::Bytes;
+use lance::dataset::transaction::Operation;
+use lance::dataset::{ColumnAlteration, Dataset};
+use lance::io::{ObjectStore, ObjectStoreParams};
+use lance::table::format::Fragment;
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+use super::batch_alter::{apply_alteration, merge_new_columns};
+use super::user_metadata::EMBEDDING_MODEL_KEY;
+use super::TableInternal;
+
+/// The directory of the re-embedding checkpoints, relative to the table
+const REEMBED_DIR: &str = "_reembed";
+
+/// The default number of rows passed to the embedding function at once
+pub const DEFAULT_REEMBED_BATCH_SIZE: usize = 256;
+
+/// A model that computes embeddings, see [`crate::Table::reembed`]
+pub trait EmbeddingFunction: Send + Sync {
+    /// The name of the model, recorded in the metadata of the column
+    fn name(&self) -> &str;
+
+    /// The type of the embeddings, typically a fixed size list of floats
+    fn data_type(&self) -> DataType;
+
+    /// Compute one embedding for each value of `source`
+    fn compute(&self, source: &dyn Array) -> Result<ArrayRef>;
+}
+
+/// The progress of a re-embedding, passed to [`ReembedBuilder::on_progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReembedProgress {
+    /// The number of fragments re-embedded so far, including resumed ones
+    pub fragments_done: usize,
+    /// The number of fragments to re-embed
+    pub fragments_total: usize,
+    /// The number of rows re-embedded so far by this run
+    pub rows_done: usize,
+}
+
+/// The outcome of a re-embedding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReembedReport {
+    /// The version the re-embedding started from
+    ///
+    /// Checking out and restoring this version undoes the re-embedding.
+    pub previous_version: u64,
+    /// The version with the new embeddings
+    pub version: u64,
+    /// The number of fragments completed by an earlier run
+    pub fragments_resumed: usize,
+    /// The number of rows re-embedded by this run
+    pub rows: usize,
+    /// The names of the indices that were rebuilt
+    pub rebuilt_indices: Vec<String>,
+}
+
+type ProgressCallback = Arc<dyn Fn(ReembedProgress) + Send + Sync>;
+
+/// A builder for a re-embedding
+///
+/// See [`super::Table::reembed`] for more context
+pub struct ReembedBuilder {
+    table: Arc<dyn TableInternal>,
+    pub(crate) column: String,
+    pub(crate) function: Arc<dyn EmbeddingFunction>,
+    pub(crate) source_column: Option<String>,
+    pub(crate) output_column: Option<String>,
+    pub(crate) batch_size: usize,
+    pub(crate) progress: Option<ProgressCallback>,
+}
+
+impl ReembedBuilder {
+    pub(super) fn new(
+        table: Arc<dyn TableInternal>,
+        column: String,
+        function: Arc<dyn EmbeddingFunction>,
+    ) -> Self {
+        Self {
+            table,
+            column,
+            function,
+            source_column: None,
+            output_column: None,
+            batch_size: DEFAULT_REEMBED_BATCH_SIZE,
+            progress: None,
+        }
+    }
+
+    /// The column the embeddings are computed from, e.g. the text or the image
+    ///
+    /// This is required.
+    pub fn source_column(mut self, column: impl Into<String>) -> Self {
+        self.source_column = Some(column.into());
+        self
+    }
+
+    /// Write the embeddings to a new column instead of replacing the column
+    pub fn output_column(mut self, column: impl Into<String>) -> Self {
+        self.output_column = Some(column.into());
+        self
+    }
+
+    /// The number of rows passed to the embedding function at once
+    ///
+    /// Defaults to [`DEFAULT_REEMBED_BATCH_SIZE`].
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Call `callback` after each fragment is re-embedded
+    ///
+    /// The callback is called from the task running the re-embedding and
+    /// should return quickly.
+    pub fn on_progress(
+        mut self,
+        callback: impl Fn(ReembedProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Run the re-embedding, or resume it if an earlier run did not complete
+    pub async fn execute(self) -> Result<ReembedReport> {
+        if self.source_column.is_none() {
+            return Err(Error::InvalidInput {
+                message: "a re-embedding needs the source column of the embeddings".to_string(),
+            });
+        }
+        if self.batch_size == 0 {
+            return Err(Error::InvalidInput {
+                message: "the batch size of a re-embedding must be positive".to_string(),
+            });
+        }
+        self.table.clone().reembed(self).await
+    }
+
+    /// The column the embeddings are written to
+    pub(crate) fn target_column(&self) -> &str {
+        self.output_column.as_deref().unwrap_or(&self.column)
+    }
+}
+
+/// The fragments completed by a re-embedding, to resume it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Checkpoint {
+    /// The version the re-embedding reads
+    version: u64,
+    model: String,
+    source_column: String,
+    output_column: Option<String>,
+    /// The completed fragments, with the data files of their new embeddings
+    fragments: Vec<Fragment>,
+}
+
+impl Checkpoint {
+    fn matches(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.model == other.model
+            && self.source_column == other.source_column
+            && self.output_column == other.output_column
+    }
+}
+
+fn checkpoint_path(base: &Path, column: &str) -> Path {
+    base.child(REEMBED_DIR).child(format!("{}.json", column))
+}
+
+async fn read_checkpoint(store: &ObjectStore, path: &Path) -> Result<Option<Checkpoint>> {
+    let bytes = match store.inner.get(path).await {
+        Ok(result) => result.bytes().await?,
+        Err(object_store::Error::NotFound { .. }) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    // A checkpoint that can not be read is not resumed
+    Ok(serde_json::from_slice(&bytes).ok())
+}
+
+async fn write_checkpoint(store: &ObjectStore, path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let bytes = serde_json::to_vec(checkpoint).map_err(|e| Error::Runtime {
+        message: format!("failed to serialize the re-embedding checkpoint: {}", e),
+    })?;
+    store.inner.put(path, Bytes::from(bytes)).await?;
+    Ok(())
+}
+
+/// Compute the embeddings of `source`, `batch_size` values at a time
+fn embed(
+    function: &dyn EmbeddingFunction,
+    source: &ArrayRef,
+    batch_size: usize,
+) -> Result<ArrayRef> {
+    let mut embeddings = Vec::with_capacity(source.len().div_ceil(batch_size));
+    let mut offset = 0;
+    while offset < source.len() {
+        let length = batch_size.min(source.len() - offset);
+        let computed = function.compute(source.slice(offset, length).as_ref())?;
+        if computed.len() != length || computed.data_type() != &function.data_type() {
+            return Err(Error::Runtime {
+                message: format!(
+                    "the embedding function '{}' returned {} values of type {} for {} rows, \
+                     expected {}",
+                    function.name(),
+                    computed.len(),
+                    computed.data_type(),
+                    length,
+                    function.data_type()
+                ),
+            });
+        }
+        embeddings.push(computed);
+        offset += length;
+    }
+    if embeddings.len() == 1 {
+        return Ok(embeddings.pop().unwrap());
+    }
+    let embeddings = embeddings.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
+    Ok(concat(&embeddings)?)
+}
+
+/// Re-embed every fragment and commit the new embeddings as a single new version
+///
+/// Returns the new dataset and the number of fragments that were resumed and
+/// of rows that were re-embedded.
+pub(super) async fn reembed_fragments(
+    dataset: &Dataset,
+    uri: &str,
+    reembed: &ReembedBuilder,
+    store_params: ObjectStoreParams,
+) -> Result<(Dataset, usize, usize)> {
+    let read_version = dataset.version().version;
+    let source_column = reembed.source_column.clone().unwrap();
+    let schema = dataset.schema().clone();
+    let column = schema
+        .field(&reembed.column)
+        .ok_or_else(|| Error::InvalidInput {
+            message: format!(
+                "cannot re-embed '{}': the column does not exist",
+                reembed.column
+            ),
+        })?;
+    if schema.field(&source_column).is_none() {
+        return Err(Error::InvalidInput {
+            message: format!(
+                "cannot re-embed '{}': the source column '{}' does not exist",
+                reembed.column, source_column
+            ),
+        });
+    }
+    if let Some(output_column) = &reembed.output_column {
+        if schema.field(output_column).is_some() {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "cannot re-embed '{}' into '{}': the column already exists",
+                    reembed.column, output_column
+                ),
+            });
+        }
+    }
+
+    // An in place re-embedding writes a temporary column that replaces the
+    // old one in the commit
+    let written_column = match &reembed.output_column {
+        Some(output_column) => output_column.clone(),
+        None => format!("_reembed_{}", reembed.column),
+    };
+    let mut metadata = column.metadata.clone();
+    metadata.insert(
+        EMBEDDING_MODEL_KEY.to_string(),
+        reembed.function.name().to_string(),
+    );
+    let new_schema = Arc::new(Schema::new(vec![Field::new(
+        &written_column,
+        reembed.function.data_type(),
+        true,
+    )
+    .with_metadata(metadata)]));
+    let final_schema = merge_new_columns(dataset, new_schema.as_ref())?;
+    let write_schema = final_schema.project_by_schema(new_schema.as_ref())?;
+
+    // The checkpoint is kept next to the data, on the store of the table
+    let (store, base) = ObjectStore::from_uri_and_params(uri, &store_params).await?;
+    let path = checkpoint_path(&base, reembed.target_column());
+    let mut checkpoint = Checkpoint {
+        version: read_version,
+        model: reembed.function.name().to_string(),
+        source_column: source_column.clone(),
+        output_column: reembed.output_column.clone(),
+        fragments: Vec::new(),
+    };
+    if let Some(previous) = read_checkpoint(&store, &path).await? {
+        if previous.matches(&checkpoint) {
+            checkpoint = previous;
+        }
+    }
+    let fragments_resumed = checkpoint.fragments.len();
+
+    let fragments = dataset.get_fragments();
+    let mut progress = ReembedProgress {
+        fragments_done: fragments_resumed,
+        fragments_total: fragments.len(),
+        rows_done: 0,
+    };
+    let read_columns = vec![source_column.clone()];
+    for fragment in fragments {
+        let id = fragment.id() as u64;
+        if checkpoint.fragments.iter().any(|f| f.id == id) {
+            continue;
+        }
+        let mut updater = fragment
+            .updater(
+                Some(&read_columns),
+                Some((write_schema.clone(), final_schema.clone())),
+            )
+            .await?;
+        while let Some(batch) = updater.next().await? {
+            let source = batch.column_by_name(&source_column).unwrap();
+            let embeddings = embed(reembed.function.as_ref(), source, reembed.batch_size)?;
+            progress.rows_done += batch.num_rows();
+            updater
+                .update(RecordBatch::try_new(new_schema.clone(), vec![embeddings])?)
+                .await?;
+        }
+        checkpoint.fragments.push(updater.finish().await?);
+        write_checkpoint(&store, &path, &checkpoint).await?;
+        progress.fragments_done += 1;
+        if let Some(callback) = &reembed.progress {
+            callback(progress);
+        }
+    }
+
+    let mut schema = final_schema;
+    if reembed.output_column.is_none() {
+        let old = schema.project(&[&reembed.column])?;
+        schema = schema.exclude(old)?;
+        apply_alteration(
+            &mut schema,
+            &ColumnAlteration::new(written_column).rename(reembed.column.clone()),
+        )?;
+    }
+    // Keep the fragments in the order of the dataset
+    let mut fragments = checkpoint.fragments;
+    fragments.sort_by_key(|f| f.id);
+    let operation = Operation::Merge { fragments, schema };
+    let dataset =
+        Dataset::commit(uri, operation, Some(read_version), Some(store_params), None).await?;
+    match store.inner.delete(&path).await {
+        Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+        Err(err) => return Err(err.into()),
+    }
+    Ok((dataset, fragments_resumed, progress.rows_done))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Float32Array, StringArray};
+
+    use super::*;
+
+    struct Length;
+
+    impl EmbeddingFunction for Length {
+        fn name(&self) -> &str {
+            "length"
+        }
+
+        fn data_type(&self) -> DataType {
+            DataType::Float32
+        }
+
+        fn compute(&self, source: &dyn Array) -> Result<ArrayRef> {
+            let source = source.as_any().downcast_ref::<StringArray>().unwrap();
+            Ok(Arc::new(Float32Array::from_iter_values(
+                source.iter().map(|s| s.unwrap_or_default().len() as f32),
+            )))
+        }
+    }
+
+    #[test]
+    fn test_embed_in_batches() {
+        let source: ArrayRef = Arc::new(StringArray::from(vec!["a", "bb", "ccc", "dddd", "e"]));
+        let embeddings = embed(&Length, &source, 2).unwrap();
+        assert_eq!(
+            embeddings.as_any().downcast_ref::<Float32Array>().unwrap(),
+            &Float32Array::from(vec![1.0, 2.0, 3.0, 4.0, 1.0])
+        );
+
+        struct Wrong;
+        impl EmbeddingFunction for Wrong {
+            fn name(&self) -> &str {
+                "wrong"
+            }
+            fn data_type(&self) -> DataType {
+                DataType::Float64
+            }
+            fn compute(&self, source: &dyn Array) -> Result<ArrayRef> {
+                Length.compute(source)
+            }
+        }
+        assert!(matches!(
+            embed(&Wrong, &source, 2),
+            Err(Error::Runtime { .. })
+        ));
+    }
+}
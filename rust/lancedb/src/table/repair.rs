@@ -0,0 +1,136 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Repairing a table after a writer crashed
+//!
+//! A writer that crashes in the middle of a write leaves data files behind
+//! that are not referenced by any version of the table.  These files are
+//! harmless but take up space.  A crash during a commit, or a bug in a
+//! writer, can also leave the latest version referencing files that are
+//! missing or corrupt, in which case every query of the table fails.
+//!
+//! [`crate::Table::repair`] runs [`crate::Table::verify`] and fixes what it
+//! can, as configured by [`RepairOptions`].  Every step keeps the history of
+//! the table: a rollback commits a new version with the contents of the last
+//! consistent version and a rebuilt index replaces the broken one.
+
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use lance::io::ObjectStore;
+use object_store::path::Path;
+use object_store::ObjectMeta;
+
+use crate::error::Result;
+use crate::table::verify::{IntegrityReport, ProblemKind};
+
+/// Options for [`crate::Table::repair`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RepairOptions {
+    /// Delete data files that are not referenced by any version of the table
+    ///
+    /// By default, this is true
+    pub remove_orphaned_files: bool,
+    /// Only delete orphaned files older than this
+    ///
+    /// A file written by a write that is still in progress is not referenced
+    /// yet, so files newer than 7 days are kept by default.  If you are sure
+    /// that no write is in progress you can lower this.
+    pub orphan_grace_period: Duration,
+    /// If the current version references missing or corrupt data, restore the
+    /// latest version that does not
+    ///
+    /// By default, this is true
+    pub rollback: bool,
+    /// Rebuild the indices that can not be opened
+    ///
+    /// The index is rebuilt with the parameters it was created with through
+    /// this handle, or with [`crate::index::Index::Auto`] otherwise.
+    ///
+    /// By default, this is true
+    pub rebuild_indices: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            remove_orphaned_files: true,
+            orphan_grace_period: Duration::try_days(7).unwrap(),
+            rollback: true,
+            rebuild_indices: true,
+        }
+    }
+}
+
+/// What [`crate::Table::repair`] did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    /// The result of verifying the table before the repair
+    pub integrity: IntegrityReport,
+    /// The version that was restored, if the table was rolled back
+    pub restored_version: Option<u64>,
+    /// The paths of the orphaned files that were deleted
+    pub removed_files: Vec<String>,
+    /// The number of bytes freed by deleting orphaned files
+    pub bytes_removed: u64,
+    /// The names of the indices that were rebuilt
+    pub rebuilt_indices: Vec<String>,
+}
+
+/// True if the problem is with the data of the version rather than an index
+pub(crate) fn is_data_problem(kind: ProblemKind) -> bool {
+    matches!(
+        kind,
+        ProblemKind::MissingFile
+            | ProblemKind::CorruptFile
+            | ProblemKind::RowCountMismatch
+            | ProblemKind::InvalidManifest
+    )
+}
+
+/// The data files that are not referenced by any version of the dataset and
+/// are older than the grace period
+///
+/// `base` is the path of the table in `object_store`.
+pub(crate) async fn find_orphaned_files(
+    dataset: &Dataset,
+    object_store: &ObjectStore,
+    base: &Path,
+    grace_period: Duration,
+) -> Result<Vec<ObjectMeta>> {
+    let mut referenced = HashSet::new();
+    for version in dataset.versions().await? {
+        let dataset = dataset.checkout_version(version.version).await?;
+        for fragment in dataset.get_fragments() {
+            referenced.extend(fragment.metadata().files.iter().map(|f| f.path.clone()));
+        }
+    }
+    let data_dir = base.child("data");
+    let cutoff = Utc::now() - grace_period;
+    let mut orphaned = Vec::new();
+    let mut files = object_store.inner.list(Some(&data_dir));
+    while let Some(meta) = files.try_next().await? {
+        let Some(relative) = meta.location.prefix_match(&data_dir) else {
+            continue;
+        };
+        let relative = relative.map(|p| p.as_ref().to_string()).collect::<Vec<_>>();
+        if !referenced.contains(&relative.join("/")) && meta.last_modified < cutoff {
+            orphaned.push(meta);
+        }
+    }
+    Ok(orphaned)
+}
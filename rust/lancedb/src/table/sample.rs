@@ -0,0 +1,110 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Uniform random samples of the rows of a table
+//!
+//! [`crate::Table::sample`] picks the positions of the sampled rows first and
+//! then reads only those rows, so the cost depends on the size of the sample
+//! and the number of fragments it touches rather than on the size of the
+//! table.  Every row has the same chance to be in the sample and no row is
+//! sampled twice.
+//!
+//! If the query interceptor restricts the rows that can be read, the row ids
+//! of the matching rows are scanned first.  This reads the columns of the
+//! filter but still only reads the other columns of the sampled rows.
+
+use arrow_array::{cast::AsArray, types::UInt64Type, RecordBatch};
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::error::Result;
+use crate::query::late::ROW_ID_COLUMN;
+
+/// Pick `n` distinct positions out of `num_rows`, in increasing order
+fn sample_positions(num_rows: usize, n: usize, seed: Option<u64>) -> Vec<u64> {
+    if n >= num_rows {
+        return (0..num_rows as u64).collect();
+    }
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut positions = rand::seq::index::sample(&mut rng, num_rows, n)
+        .into_iter()
+        .map(|i| i as u64)
+        .collect::<Vec<_>>();
+    positions.sort_unstable();
+    positions
+}
+
+/// Read a uniform random sample of up to `n` rows of the dataset
+///
+/// Only rows matching `filter` are sampled if it is given.  The rows are
+/// returned in the order of the table.
+pub(crate) async fn sample_dataset(
+    dataset: &Dataset,
+    n: usize,
+    seed: Option<u64>,
+    filter: Option<&str>,
+) -> Result<RecordBatch> {
+    let projection = dataset.schema();
+    let Some(filter) = filter else {
+        let num_rows = dataset.count_rows().await?;
+        let positions = sample_positions(num_rows, n, seed);
+        return Ok(dataset.take(&positions, projection).await?);
+    };
+
+    let mut scanner = dataset.scan();
+    // Lance can not scan without any column, read the smallest one we know of
+    let first_column = projection.fields[0].name.as_str();
+    scanner.project(&[first_column])?;
+    scanner.with_row_id();
+    scanner.filter(filter)?;
+    let row_ids = scanner
+        .try_into_stream()
+        .await?
+        .map_ok(|batch| {
+            batch[ROW_ID_COLUMN]
+                .as_primitive::<UInt64Type>()
+                .values()
+                .to_vec()
+        })
+        .try_concat()
+        .await?;
+    let row_ids = sample_positions(row_ids.len(), n, seed)
+        .into_iter()
+        .map(|position| row_ids[position as usize])
+        .collect::<Vec<_>>();
+    Ok(dataset.take_rows(&row_ids, projection).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_positions() {
+        let positions = sample_positions(1000, 10, Some(42));
+        assert_eq!(positions.len(), 10);
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert!(positions.iter().all(|p| *p < 1000));
+        assert_eq!(positions, sample_positions(1000, 10, Some(42)));
+        assert_ne!(positions, sample_positions(1000, 10, Some(43)));
+
+        assert_eq!(sample_positions(3, 10, Some(42)), vec![0, 1, 2]);
+        assert!(sample_positions(0, 10, None).is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Differences between the schema of some data and the schema of a table
+//!
+//! [`super::Table::check_schema_compatible`] compares a schema with the
+//! schema of a table, and [`super::Table::add`] makes the same comparison
+//! before it appends data, so a mismatch is reported with every column that
+//! differs instead of the first error of the write.
+//!
+//! Columns are compared by name, the order of the columns does not matter.
+//! The fields of struct columns are compared one by one and named with their
+//! path, e.g. `location.lat`.
+
+use std::fmt;
+
+use arrow_schema::{DataType, Field, Fields, Schema};
+
+/// A column whose type is different in the data and in the table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub column: String,
+    /// The type of the column in the data
+    pub data_type: DataType,
+    /// The type of the column in the table
+    pub table_type: DataType,
+}
+
+/// The differences between the schema of some data and the schema of a table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDiff {
+    /// Columns of the table that are not in the data
+    pub missing_columns: Vec<String>,
+    /// Columns of the data that are not in the table
+    pub extra_columns: Vec<String>,
+    /// Columns with a different type in the data and in the table
+    pub type_mismatches: Vec<TypeMismatch>,
+    /// Columns that are nullable in the data but not in the table
+    ///
+    /// These only fail a write if the data contains nulls in the column.
+    pub nullability_issues: Vec<String>,
+}
+
+impl SchemaDiff {
+    /// Compare the schema of some data with the schema of a table
+    pub fn between(data: &Schema, table: &Schema) -> Self {
+        let mut diff = Self::default();
+        diff.compare(None, data.fields(), table.fields());
+        diff
+    }
+
+    /// Whether data with this schema can be added to the table
+    ///
+    /// Nullability issues are not counted, see [`Self::nullability_issues`].
+    pub fn is_compatible(&self) -> bool {
+        self.missing_columns.is_empty()
+            && self.extra_columns.is_empty()
+            && self.type_mismatches.is_empty()
+    }
+
+    /// Whether the schemas are the same, apart from the order of the columns
+    pub fn is_empty(&self) -> bool {
+        self.is_compatible() && self.nullability_issues.is_empty()
+    }
+
+    fn compare(&mut self, parent: Option<&str>, data: &Fields, table: &Fields) {
+        let path = |field: &Field| match parent {
+            Some(parent) => format!("{}.{}", parent, field.name()),
+            None => field.name().clone(),
+        };
+        for field in table {
+            if !data.iter().any(|f| f.name() == field.name()) {
+                self.missing_columns.push(path(field));
+            }
+        }
+        for field in data {
+            let Some(existing) = table.iter().find(|f| f.name() == field.name()) else {
+                self.extra_columns.push(path(field));
+                continue;
+            };
+            match (field.data_type(), existing.data_type()) {
+                (DataType::Struct(data), DataType::Struct(table)) => {
+                    self.compare(Some(&path(field)), data, table)
+                }
+                (data_type, table_type) if data_type != table_type => {
+                    self.type_mismatches.push(TypeMismatch {
+                        column: path(field),
+                        data_type: data_type.clone(),
+                        table_type: table_type.clone(),
+                    })
+                }
+                _ => {}
+            }
+            if field.is_nullable() && !existing.is_nullable() {
+                self.nullability_issues.push(path(field));
+            }
+        }
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut problems = Vec::new();
+        for column in &self.missing_columns {
+            problems.push(format!("the column '{}' is missing from the data", column));
+        }
+        for column in &self.extra_columns {
+            problems.push(format!("the column '{}' is not in the table", column));
+        }
+        for mismatch in &self.type_mismatches {
+            problems.push(format!(
+                "the column '{}' is {} in the data but {} in the table",
+                mismatch.column, mismatch.data_type, mismatch.table_type
+            ));
+        }
+        for column in &self.nullability_issues {
+            problems.push(format!(
+                "the column '{}' is nullable in the data but not in the table",
+                column
+            ));
+        }
+        if problems.is_empty() {
+            write!(f, "the schemas match")
+        } else {
+            write!(f, "{}", problems.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(lat: DataType) -> Field {
+        Field::new(
+            "location",
+            DataType::Struct(Fields::from(vec![
+                Field::new("lat", lat, false),
+                Field::new("lon", DataType::Float64, false),
+            ])),
+            true,
+        )
+    }
+
+    #[test]
+    fn test_schema_diff() {
+        let table = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            location(DataType::Float64),
+        ]);
+        let same = Schema::new(vec![
+            location(DataType::Float64),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("id", DataType::Int64, false),
+        ]);
+        assert!(SchemaDiff::between(&same, &table).is_empty());
+
+        let data = Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("tag", DataType::Utf8, true),
+            location(DataType::Float32),
+        ]);
+        let diff = SchemaDiff::between(&data, &table);
+        assert!(!diff.is_compatible());
+        assert_eq!(diff.missing_columns, vec!["name"]);
+        assert_eq!(diff.extra_columns, vec!["tag"]);
+        assert_eq!(
+            diff.type_mismatches,
+            vec![
+                TypeMismatch {
+                    column: "id".to_string(),
+                    data_type: DataType::Int32,
+                    table_type: DataType::Int64,
+                },
+                TypeMismatch {
+                    column: "location.lat".to_string(),
+                    data_type: DataType::Float32,
+                    table_type: DataType::Float64,
+                },
+            ]
+        );
+        assert_eq!(diff.nullability_issues, vec!["id"]);
+        assert!(diff
+            .to_string()
+            .contains("the column 'id' is Int32 in the data but Int64 in the table"));
+
+        let nullable = Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+            location(DataType::Float64),
+        ]);
+        let diff = SchemaDiff::between(&nullable, &table);
+        assert!(diff.is_compatible());
+        assert!(!diff.is_empty());
+    }
+}
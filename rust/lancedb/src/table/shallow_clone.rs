@@ -0,0 +1,181 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shallow clones
+//!
+//! [`super::Table::copy_to`] makes a new table whose first version has the
+//! data files of the current version of the source table, without copying
+//! the data:
+//!
+//! * On a local file system the data files are hard links to the files of
+//!   the source.  The clone does not depend on the source afterwards.
+//! * On an object store the manifest of the clone lists the data files of the
+//!   source as they are.  The paths of the sources are recorded in the schema
+//!   metadata of the clone, which is opened with
+//!   [`crate::io::shallow_clone::ShallowCloneWrapper`] to read the files it
+//!   does not have from its sources.  The source records the version that
+//!   was cloned, and pruning the source keeps that version, and so the
+//!   files, for as long as the clone exists.  Dropping the source (or a
+//!   clone that is itself the source of another clone) breaks its clones.
+//!
+//! Deletion files are small and are always copied.
+
+use std::collections::{BTreeMap, HashMap};
+
+use futures::TryStreamExt;
+use object_store::{path::Path, ObjectStore};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The schema metadata key of the sources of a clone
+pub(crate) const CLONED_FROM_KEY: &str = "lancedb:cloned_from";
+
+/// The schema metadata key of the clones of a table
+pub(crate) const CLONES_KEY: &str = "lancedb:clones";
+
+fn parse_path(path: &str) -> Result<Path> {
+    Path::parse(path).map_err(|e| Error::Runtime {
+        message: format!(
+            "invalid path '{}' in the shallow clone metadata: {}",
+            path, e
+        ),
+    })
+}
+
+/// The paths of the tables a clone reads data files from, the direct source first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ClonedFrom(pub Vec<String>);
+
+impl ClonedFrom {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(CLONED_FROM_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the sources of the clone: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the sources of the clone: {}", e),
+        })?;
+        metadata.insert(CLONED_FROM_KEY.to_string(), value);
+        Ok(())
+    }
+
+    /// The sources of a clone of `table`, a clone reads from the sources of its source
+    pub(crate) fn clone_of(&self, table: &Path) -> Self {
+        let mut sources = vec![table.to_string()];
+        sources.extend(self.0.iter().cloned());
+        Self(sources)
+    }
+
+    pub(crate) fn paths(&self) -> Result<Vec<Path>> {
+        self.0.iter().map(|path| parse_path(path)).collect()
+    }
+}
+
+/// The version each clone of a table was made from, by the path of the clone
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Clones(pub BTreeMap<String, u64>);
+
+impl Clones {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(CLONES_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the clones of the table: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the clones of the table: {}", e),
+        })?;
+        metadata.insert(CLONES_KEY.to_string(), value);
+        Ok(())
+    }
+
+    /// The clones of a table are not the clones of its clone
+    pub(crate) fn remove_from_metadata(metadata: &mut HashMap<String, String>) {
+        metadata.remove(CLONES_KEY);
+    }
+
+    /// The oldest version read by a clone that still exists
+    pub(crate) async fn oldest_version(&self, store: &dyn ObjectStore) -> Result<Option<u64>> {
+        let mut oldest: Option<u64> = None;
+        for (path, version) in &self.0 {
+            // A dropped clone no longer needs the files
+            let versions = parse_path(path)?.child("_versions");
+            if store.list(Some(&versions)).try_next().await?.is_some() {
+                oldest = Some(oldest.map_or(*version, |oldest| oldest.min(*version)));
+            }
+        }
+        Ok(oldest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clone_metadata() {
+        let mut metadata = HashMap::new();
+        assert!(ClonedFrom::from_metadata(&metadata).unwrap().0.is_empty());
+        let cloned_from = ClonedFrom::default().clone_of(&Path::from("db/a.lance"));
+        let cloned_from = cloned_from.clone_of(&Path::from("db/b.lance"));
+        cloned_from.apply_to_metadata(&mut metadata).unwrap();
+        assert_eq!(
+            ClonedFrom::from_metadata(&metadata)
+                .unwrap()
+                .paths()
+                .unwrap(),
+            vec![Path::from("db/b.lance"), Path::from("db/a.lance")]
+        );
+
+        let clones = Clones(BTreeMap::from([
+            ("db/c.lance".to_string(), 3),
+            ("db/dropped.lance".to_string(), 1),
+            ("db/d.lance".to_string(), 5),
+        ]));
+        clones.apply_to_metadata(&mut metadata).unwrap();
+        let clones = Clones::from_metadata(&metadata).unwrap();
+        Clones::remove_from_metadata(&mut metadata);
+        assert!(Clones::from_metadata(&metadata).unwrap().0.is_empty());
+        let store = InMemory::new();
+        for clone in ["db/c.lance", "db/d.lance"] {
+            store
+                .put(
+                    &Path::from(format!("{}/_versions/1.manifest", clone)),
+                    Bytes::new(),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(clones.oldest_version(&store).await.unwrap(), Some(3));
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Soft deletes
+//!
+//! Once soft deletes are enabled with [`super::Table::set_soft_delete`],
+//! [`super::Table::delete`] no longer removes rows.  It sets the hidden
+//! [`DELETED_COLUMN`] of the matching rows to true instead, and
+//! [`super::Table::undelete`] sets it back to false.  Queries and
+//! [`super::Table::count_rows`] skip the deleted rows and leave the column
+//! out of their results, unless the query asks for the deleted rows with
+//! [`crate::query::QueryBase::with_deleted_rows`].  Such a query can filter
+//! on the column, e.g. `_deleted = true` to find the deleted rows.
+//!
+//! Appended rows that do not have the column are stored as not deleted.
+//! Deleted rows still take up space and are only removed when soft deletes
+//! are disabled again.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{BooleanArray, RecordBatch, RecordBatchIterator, RecordBatchReader};
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::error::{Error, Result};
+
+/// The schema metadata key that marks a table with soft deletes
+pub(crate) const SOFT_DELETE_KEY: &str = "lancedb:soft_delete";
+
+/// The column marking the rows that were deleted
+pub const DELETED_COLUMN: &str = "_deleted";
+
+/// The filter matching the rows that were not deleted
+pub(crate) const LIVE_FILTER: &str = "(`_deleted` IS NULL OR `_deleted` = false)";
+
+/// Whether the table has soft deletes enabled
+pub(crate) fn is_enabled(metadata: &HashMap<String, String>) -> bool {
+    metadata.get(SOFT_DELETE_KEY).map(String::as_str) == Some("true")
+}
+
+/// Check that an existing deleted column can be used for soft deletes
+pub(crate) fn check_column(schema: &Schema) -> Result<bool> {
+    match schema.field_with_name(DELETED_COLUMN) {
+        Ok(field) if field.data_type() == &DataType::Boolean => Ok(true),
+        Ok(field) => Err(Error::InvalidInput {
+            message: format!(
+                "cannot enable soft deletes, the column '{}' already exists and has type {} \
+                 instead of boolean",
+                DELETED_COLUMN,
+                field.data_type()
+            ),
+        }),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Mark the rows of new data as not deleted, if they are not marked yet
+pub(crate) fn with_deleted_column(
+    data: Box<dyn RecordBatchReader + Send>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = data.schema();
+    if schema.field_with_name(DELETED_COLUMN).is_ok() {
+        return Ok(data);
+    }
+    let mut fields = schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        DELETED_COLUMN,
+        DataType::Boolean,
+        true,
+    )));
+    let marked_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+    let output_schema = marked_schema.clone();
+    let batches = data.map(move |batch| {
+        let batch = batch?;
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(BooleanArray::from(vec![false; batch.num_rows()])));
+        RecordBatch::try_new(marked_schema.clone(), columns)
+    });
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, Int32Array};
+
+    use super::*;
+
+    #[test]
+    fn test_with_deleted_column() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(vec![1, 2]))])
+                .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let marked = with_deleted_column(Box::new(reader)).unwrap();
+        assert!(check_column(&marked.schema()).unwrap());
+        let batches = marked.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let deleted = batches[0].column(1).as_boolean();
+        assert_eq!(deleted.true_count(), 0);
+        assert_eq!(deleted.len(), 2);
+
+        assert!(!check_column(&schema).unwrap());
+        let wrong = Schema::new(vec![Field::new(DELETED_COLUMN, DataType::Int32, false)]);
+        assert!(check_column(&wrong).is_err());
+    }
+}
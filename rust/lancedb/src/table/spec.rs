@@ -0,0 +1,460 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A portable, JSON description of a table
+//!
+//! A [`TableSpec`] captures everything needed to recreate an (empty) table:
+//! the schema, the indices, the table properties (schema metadata) and the
+//! embedding function configuration.  It does not contain any data.
+//!
+//! The JSON format does not depend on Rust type names so that specs can be
+//! checked into source control, reviewed, and consumed by any of the SDKs.
+//! Types are written as objects with a `type` tag, for example:
+//!
+//! ```json
+//! {"name": "vector", "nullable": true, "type": {
+//!     "type": "fixed_size_list", "size": 128,
+//!     "item": {"name": "item", "nullable": true, "type": {"type": "float32"}}}}
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use serde::{Deserialize, Serialize};
+
+use crate::connection::IDEMPOTENCY_TOKEN_KEY;
+use crate::error::{Error, Result};
+use crate::index::scalar::BTreeIndexBuilder;
+use crate::index::vector::IvfPqIndexBuilder;
+use crate::index::{Index, IndexType, PendingIndices, PENDING_INDICES_KEY};
+
+/// The version of the spec format written by this library
+pub const TABLE_SPEC_VERSION: u32 = 1;
+
+/// The schema metadata key the SDKs use to store embedding function configuration
+pub const EMBEDDING_FUNCTIONS_KEY: &str = "embedding_functions";
+
+/// A portable description of a table's definition
+///
+/// Created with [`super::Table::to_spec`] and turned back into a table with
+/// [`crate::Connection::create_table_from_spec`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableSpec {
+    /// The version of the spec format, see [`TABLE_SPEC_VERSION`]
+    pub spec_version: u32,
+    pub name: String,
+    pub fields: Vec<FieldSpec>,
+    /// The indices on the table, both built and declared
+    #[serde(default)]
+    pub indices: Vec<IndexSpec>,
+    /// The number of rows at which declared indices are built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_index_threshold: Option<usize>,
+    /// The configuration of the embedding functions, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_functions: Option<serde_json::Value>,
+    /// Any other table level metadata
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+/// A column (or nested child) of a table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: TypeSpec,
+    pub nullable: bool,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+/// The data type of a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TypeSpec {
+    Null,
+    Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Float16,
+    Float32,
+    Float64,
+    String,
+    LargeString,
+    Binary,
+    LargeBinary,
+    FixedSizeBinary {
+        size: i32,
+    },
+    Date32,
+    Date64,
+    Timestamp {
+        /// One of `s`, `ms`, `us` or `ns`
+        unit: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timezone: Option<String>,
+    },
+    Decimal128 {
+        precision: u8,
+        scale: i8,
+    },
+    List {
+        item: Box<FieldSpec>,
+    },
+    LargeList {
+        item: Box<FieldSpec>,
+    },
+    FixedSizeList {
+        item: Box<FieldSpec>,
+        size: i32,
+    },
+    Struct {
+        fields: Vec<FieldSpec>,
+    },
+}
+
+/// An index on one or more columns
+///
+/// Indices are recreated with the default parameters for their type unless
+/// the index was declared with explicit parameters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexSpec {
+    pub columns: Vec<String>,
+    pub index: Index,
+    /// True if the index had been built on the source table, false if it was
+    /// only declared
+    #[serde(default)]
+    pub built: bool,
+}
+
+impl PartialEq for IndexSpec {
+    fn eq(&self, other: &Self) -> bool {
+        // Index has no PartialEq, compare the JSON representation
+        self.columns == other.columns
+            && self.built == other.built
+            && serde_json::to_value(&self.index).ok() == serde_json::to_value(&other.index).ok()
+    }
+}
+
+fn time_unit_to_str(unit: &TimeUnit) -> &'static str {
+    match unit {
+        TimeUnit::Second => "s",
+        TimeUnit::Millisecond => "ms",
+        TimeUnit::Microsecond => "us",
+        TimeUnit::Nanosecond => "ns",
+    }
+}
+
+fn time_unit_from_str(unit: &str) -> Result<TimeUnit> {
+    match unit {
+        "s" => Ok(TimeUnit::Second),
+        "ms" => Ok(TimeUnit::Millisecond),
+        "us" => Ok(TimeUnit::Microsecond),
+        "ns" => Ok(TimeUnit::Nanosecond),
+        _ => Err(Error::InvalidInput {
+            message: format!("unknown time unit '{}' in table spec", unit),
+        }),
+    }
+}
+
+impl TypeSpec {
+    fn try_from_arrow(data_type: &DataType) -> Result<Self> {
+        Ok(match data_type {
+            DataType::Null => Self::Null,
+            DataType::Boolean => Self::Bool,
+            DataType::Int8 => Self::Int8,
+            DataType::Int16 => Self::Int16,
+            DataType::Int32 => Self::Int32,
+            DataType::Int64 => Self::Int64,
+            DataType::UInt8 => Self::Uint8,
+            DataType::UInt16 => Self::Uint16,
+            DataType::UInt32 => Self::Uint32,
+            DataType::UInt64 => Self::Uint64,
+            DataType::Float16 => Self::Float16,
+            DataType::Float32 => Self::Float32,
+            DataType::Float64 => Self::Float64,
+            DataType::Utf8 => Self::String,
+            DataType::LargeUtf8 => Self::LargeString,
+            DataType::Binary => Self::Binary,
+            DataType::LargeBinary => Self::LargeBinary,
+            DataType::FixedSizeBinary(size) => Self::FixedSizeBinary { size: *size },
+            DataType::Date32 => Self::Date32,
+            DataType::Date64 => Self::Date64,
+            DataType::Timestamp(unit, timezone) => Self::Timestamp {
+                unit: time_unit_to_str(unit).to_string(),
+                timezone: timezone.as_ref().map(|tz| tz.to_string()),
+            },
+            DataType::Decimal128(precision, scale) => Self::Decimal128 {
+                precision: *precision,
+                scale: *scale,
+            },
+            DataType::List(item) => Self::List {
+                item: Box::new(FieldSpec::try_from_arrow(item)?),
+            },
+            DataType::LargeList(item) => Self::LargeList {
+                item: Box::new(FieldSpec::try_from_arrow(item)?),
+            },
+            DataType::FixedSizeList(item, size) => Self::FixedSizeList {
+                item: Box::new(FieldSpec::try_from_arrow(item)?),
+                size: *size,
+            },
+            DataType::Struct(fields) => Self::Struct {
+                fields: fields
+                    .iter()
+                    .map(|f| FieldSpec::try_from_arrow(f))
+                    .collect::<Result<_>>()?,
+            },
+            other => {
+                return Err(Error::NotSupported {
+                    message: format!("the data type {} cannot be written to a table spec", other),
+                })
+            }
+        })
+    }
+
+    fn to_arrow(&self) -> Result<DataType> {
+        Ok(match self {
+            Self::Null => DataType::Null,
+            Self::Bool => DataType::Boolean,
+            Self::Int8 => DataType::Int8,
+            Self::Int16 => DataType::Int16,
+            Self::Int32 => DataType::Int32,
+            Self::Int64 => DataType::Int64,
+            Self::Uint8 => DataType::UInt8,
+            Self::Uint16 => DataType::UInt16,
+            Self::Uint32 => DataType::UInt32,
+            Self::Uint64 => DataType::UInt64,
+            Self::Float16 => DataType::Float16,
+            Self::Float32 => DataType::Float32,
+            Self::Float64 => DataType::Float64,
+            Self::String => DataType::Utf8,
+            Self::LargeString => DataType::LargeUtf8,
+            Self::Binary => DataType::Binary,
+            Self::LargeBinary => DataType::LargeBinary,
+            Self::FixedSizeBinary { size } => DataType::FixedSizeBinary(*size),
+            Self::Date32 => DataType::Date32,
+            Self::Date64 => DataType::Date64,
+            Self::Timestamp { unit, timezone } => DataType::Timestamp(
+                time_unit_from_str(unit)?,
+                timezone.as_deref().map(Arc::from),
+            ),
+            Self::Decimal128 { precision, scale } => DataType::Decimal128(*precision, *scale),
+            Self::List { item } => DataType::List(Arc::new(item.to_arrow()?)),
+            Self::LargeList { item } => DataType::LargeList(Arc::new(item.to_arrow()?)),
+            Self::FixedSizeList { item, size } => {
+                DataType::FixedSizeList(Arc::new(item.to_arrow()?), *size)
+            }
+            Self::Struct { fields } => DataType::Struct(
+                fields
+                    .iter()
+                    .map(|f| f.to_arrow())
+                    .collect::<Result<Fields>>()?,
+            ),
+        })
+    }
+}
+
+impl FieldSpec {
+    fn try_from_arrow(field: &Field) -> Result<Self> {
+        Ok(Self {
+            name: field.name().clone(),
+            data_type: TypeSpec::try_from_arrow(field.data_type())?,
+            nullable: field.is_nullable(),
+            metadata: field.metadata().clone(),
+        })
+    }
+
+    fn to_arrow(&self) -> Result<Field> {
+        Ok(
+            Field::new(&self.name, self.data_type.to_arrow()?, self.nullable)
+                .with_metadata(self.metadata.clone()),
+        )
+    }
+}
+
+impl TableSpec {
+    /// Create a spec from a table's schema and its built indices
+    ///
+    /// Indices that were declared but not yet built are read from the schema
+    /// metadata.
+    pub(crate) fn new(
+        name: &str,
+        schema: &Schema,
+        built_indices: Vec<(IndexType, Vec<String>)>,
+    ) -> Result<Self> {
+        let fields = schema
+            .fields()
+            .iter()
+            .map(|f| FieldSpec::try_from_arrow(f))
+            .collect::<Result<Vec<_>>>()?;
+        let mut properties = schema.metadata().clone();
+        properties.remove(PENDING_INDICES_KEY);
+        properties.remove(IDEMPOTENCY_TOKEN_KEY);
+        let embedding_functions = properties
+            .remove(EMBEDDING_FUNCTIONS_KEY)
+            .map(|value| {
+                serde_json::from_str(&value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the embedding function metadata: {}", e),
+                })
+            })
+            .transpose()?;
+
+        let mut indices = built_indices
+            .into_iter()
+            .map(|(index_type, columns)| IndexSpec {
+                columns,
+                index: match index_type {
+                    IndexType::IvfPq => Index::IvfPq(IvfPqIndexBuilder::default()),
+                    IndexType::BTree => Index::BTree(BTreeIndexBuilder::default()),
+                },
+                built: true,
+            })
+            .collect::<Vec<_>>();
+        let pending = PendingIndices::from_schema(schema)?;
+        let pending_index_threshold = pending.as_ref().map(|p| p.threshold);
+        for declared in pending.into_iter().flat_map(|p| p.indices) {
+            // A declared index is satisfied by any built index on the same columns
+            if !indices.iter().any(|i| i.columns == declared.columns) {
+                indices.push(IndexSpec {
+                    columns: declared.columns,
+                    index: declared.index,
+                    built: false,
+                });
+            }
+        }
+
+        Ok(Self {
+            spec_version: TABLE_SPEC_VERSION,
+            name: name.to_string(),
+            fields,
+            indices,
+            pending_index_threshold,
+            embedding_functions,
+            properties,
+        })
+    }
+
+    /// Parse a spec from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        let spec: Self = serde_json::from_str(json).map_err(|e| Error::InvalidInput {
+            message: format!("invalid table spec: {}", e),
+        })?;
+        if spec.spec_version > TABLE_SPEC_VERSION {
+            return Err(Error::NotSupported {
+                message: format!(
+                    "table spec version {} is newer than the supported version {}",
+                    spec.spec_version, TABLE_SPEC_VERSION
+                ),
+            });
+        }
+        Ok(spec)
+    }
+
+    /// Write the spec as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::Other {
+            message: format!("failed to serialize table spec: {}", e),
+            source: None,
+        })
+    }
+
+    /// The Arrow schema described by this spec, including the table properties
+    pub fn schema(&self) -> Result<SchemaRef> {
+        let fields = self
+            .fields
+            .iter()
+            .map(|f| f.to_arrow())
+            .collect::<Result<Vec<_>>>()?;
+        let mut metadata = self.properties.clone();
+        if let Some(embedding_functions) = &self.embedding_functions {
+            metadata.insert(
+                EMBEDDING_FUNCTIONS_KEY.to_string(),
+                embedding_functions.to_string(),
+            );
+        }
+        Ok(Arc::new(Schema::new(fields).with_metadata(metadata)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_json_round_trip() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 4),
+                true,
+            ),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                true,
+            ),
+            Field::new(
+                "meta",
+                DataType::Struct(Fields::from(vec![Field::new("tag", DataType::Utf8, true)])),
+                true,
+            )
+            .with_metadata([("k".to_string(), "v".to_string())].into()),
+        ])
+        .with_metadata(
+            [
+                ("owner".to_string(), "search-team".to_string()),
+                (
+                    EMBEDDING_FUNCTIONS_KEY.to_string(),
+                    r#"[{"name":"openai","source_column":"text"}]"#.to_string(),
+                ),
+            ]
+            .into(),
+        );
+        let spec = TableSpec::new(
+            "items",
+            &schema,
+            vec![(IndexType::BTree, vec!["id".to_string()])],
+        )
+        .unwrap();
+        assert_eq!(spec.properties.len(), 1);
+        assert_eq!(
+            spec.embedding_functions.as_ref().unwrap()[0]["name"],
+            "openai"
+        );
+
+        let json = spec.to_json().unwrap();
+        assert!(json.contains(r#""type": "fixed_size_list""#));
+        let parsed = TableSpec::from_json(&json).unwrap();
+        assert_eq!(parsed, spec);
+        assert_eq!(parsed.schema().unwrap().as_ref(), &schema);
+    }
+
+    #[test]
+    fn test_spec_rejects_newer_version() {
+        let json = r#"{"spec_version": 99, "name": "t", "fields": []}"#;
+        assert!(matches!(
+            TableSpec::from_json(json),
+            Err(Error::NotSupported { .. })
+        ));
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Temporal validity columns
+//!
+//! Tables that keep the history of their rows (slowly changing dimensions)
+//! mark each row with the time range in which it was valid.  Once the validity
+//! columns are declared with [`super::Table::set_temporal_validity`] a query can
+//! use [`crate::query::QueryBase::as_of`] to only see the rows that were valid
+//! at a point in time.
+//!
+//! A row is valid at time `t` if `valid_from <= t` and either `valid_to` is
+//! null (the row is still current) or `t < valid_to`.  The columns are stored
+//! in the table properties so every handle of the table uses the same columns.
+
+use std::collections::HashMap;
+
+use arrow_schema::{DataType, Schema};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The schema metadata key used to store the temporal validity columns
+pub(crate) const TEMPORAL_VALIDITY_KEY: &str = "lancedb:temporal_validity";
+
+/// The columns holding the time range in which each row is valid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemporalValidity {
+    /// The column holding the first instant the row is valid, must not be null
+    pub valid_from: String,
+    /// The column holding the first instant the row is no longer valid, null
+    /// if the row is still valid
+    pub valid_to: String,
+}
+
+impl TemporalValidity {
+    /// Declare the validity columns
+    pub fn new(valid_from: impl Into<String>, valid_to: impl Into<String>) -> Self {
+        Self {
+            valid_from: valid_from.into(),
+            valid_to: valid_to.into(),
+        }
+    }
+
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Option<Self>> {
+        metadata
+            .get(TEMPORAL_VALIDITY_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the temporal validity columns: {}", e),
+                })
+            })
+            .transpose()
+    }
+
+    pub(crate) fn apply_to_metadata(
+        validity: Option<&Self>,
+        metadata: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        match validity {
+            Some(validity) => {
+                let value = serde_json::to_string(validity).map_err(|e| Error::Schema {
+                    message: format!("failed to serialize the temporal validity columns: {}", e),
+                })?;
+                metadata.insert(TEMPORAL_VALIDITY_KEY.to_string(), value);
+            }
+            None => {
+                metadata.remove(TEMPORAL_VALIDITY_KEY);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that both columns exist and are timestamps
+    pub(crate) fn validate(&self, schema: &Schema) -> Result<()> {
+        for column in [&self.valid_from, &self.valid_to] {
+            let field = schema
+                .field_with_name(column)
+                .map_err(|_| Error::InvalidInput {
+                    message: format!("the validity column '{}' does not exist", column),
+                })?;
+            if !matches!(field.data_type(), DataType::Timestamp(_, _)) {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the validity column '{}' must be a timestamp, not {}",
+                        column,
+                        field.data_type()
+                    ),
+                });
+            }
+        }
+        if self.valid_from == self.valid_to {
+            return Err(Error::InvalidInput {
+                message: "the valid_from and valid_to columns must be different".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The filter selecting the rows that were valid at the given time
+    pub(crate) fn filter(&self, as_of: &DateTime<Utc>) -> String {
+        let ts = format!("TIMESTAMP '{}'", as_of.format("%Y-%m-%d %H:%M:%S%.6f"));
+        format!(
+            "`{}` <= {} AND (`{}` IS NULL OR `{}` > {})",
+            self.valid_from, ts, self.valid_to, self.valid_to, ts
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{Field, TimeUnit};
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let schema = Schema::new(vec![
+            Field::new(
+                "from",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+            Field::new("to", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new("id", DataType::Int32, false),
+        ]);
+        assert!(TemporalValidity::new("from", "to")
+            .validate(&schema)
+            .is_ok());
+        assert!(TemporalValidity::new("from", "id")
+            .validate(&schema)
+            .is_err());
+        assert!(TemporalValidity::new("from", "nope")
+            .validate(&schema)
+            .is_err());
+        assert!(TemporalValidity::new("from", "from")
+            .validate(&schema)
+            .is_err());
+    }
+
+    #[test]
+    fn test_filter_and_metadata() {
+        let validity = TemporalValidity::new("from", "to");
+        let as_of = Utc.with_ymd_and_hms(2024, 3, 1, 12, 30, 0).unwrap();
+        assert_eq!(
+            validity.filter(&as_of),
+            "`from` <= TIMESTAMP '2024-03-01 12:30:00.000000' AND \
+             (`to` IS NULL OR `to` > TIMESTAMP '2024-03-01 12:30:00.000000')"
+        );
+
+        let mut metadata = HashMap::new();
+        TemporalValidity::apply_to_metadata(Some(&validity), &mut metadata).unwrap();
+        assert_eq!(
+            TemporalValidity::from_metadata(&metadata).unwrap(),
+            Some(validity)
+        );
+        TemporalValidity::apply_to_metadata(None, &mut metadata).unwrap();
+        assert!(metadata.is_empty());
+    }
+}
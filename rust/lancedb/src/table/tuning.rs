@@ -0,0 +1,111 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tuned defaults for vector searches
+//!
+//! [`super::Table::tune_search`] measures the recall of searches with several
+//! values of `nprobes` and `refine_factor` (see [`crate::eval`]) and stores
+//! the fastest setting that reaches a target recall in the table properties.
+//! Vector searches on the column then use these values unless
+//! [`crate::query::VectorQuery::nprobes`] or
+//! [`crate::query::VectorQuery::refine_factor`] is called.
+//!
+//! The defaults are removed when a new vector index is created on the column,
+//! since they were tuned for the old index.  Adding data to the index (e.g.
+//! with [`super::OptimizeAction::Index`]) keeps them.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The schema metadata key used to store the search defaults
+pub(crate) const SEARCH_DEFAULTS_KEY: &str = "lancedb:search_defaults";
+
+/// The values of `nprobes` tried by [`super::Table::tune_search`]
+pub(crate) const TUNED_NPROBES: [usize; 7] = [1, 5, 10, 20, 50, 100, 200];
+/// The values of `refine_factor` tried by [`super::Table::tune_search`]
+pub(crate) const TUNED_REFINE_FACTORS: [Option<u32>; 3] = [None, Some(5), Some(20)];
+
+/// The default settings of vector searches on a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchDefaults {
+    pub nprobes: usize,
+    pub refine_factor: Option<u32>,
+    /// The recall that was measured with these settings
+    pub recall: f64,
+    /// The number of results of the queries the recall was measured with
+    pub k: usize,
+}
+
+/// The search defaults of every column, by column name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SearchDefaultsMap(pub HashMap<String, SearchDefaults>);
+
+impl SearchDefaultsMap {
+    pub(crate) fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        metadata
+            .get(SEARCH_DEFAULTS_KEY)
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| Error::Schema {
+                    message: format!("failed to parse the search defaults: {}", e),
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn apply_to_metadata(&self, metadata: &mut HashMap<String, String>) -> Result<()> {
+        if self.0.is_empty() {
+            metadata.remove(SEARCH_DEFAULTS_KEY);
+            return Ok(());
+        }
+        let value = serde_json::to_string(self).map_err(|e| Error::Schema {
+            message: format!("failed to serialize the search defaults: {}", e),
+        })?;
+        metadata.insert(SEARCH_DEFAULTS_KEY.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let mut defaults = SearchDefaultsMap::default();
+        defaults.0.insert(
+            "vector".to_string(),
+            SearchDefaults {
+                nprobes: 10,
+                refine_factor: Some(5),
+                recall: 0.95,
+                k: 10,
+            },
+        );
+        let mut metadata = HashMap::new();
+        defaults.apply_to_metadata(&mut metadata).unwrap();
+        assert_eq!(
+            SearchDefaultsMap::from_metadata(&metadata).unwrap(),
+            defaults
+        );
+
+        SearchDefaultsMap::default()
+            .apply_to_metadata(&mut metadata)
+            .unwrap();
+        assert!(metadata.is_empty());
+    }
+}
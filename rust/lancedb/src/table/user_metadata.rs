@@ -0,0 +1,158 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Descriptive metadata of tables and columns
+//!
+//! [`crate::Table::set_metadata`] and [`crate::Table::set_column_metadata`]
+//! store key/value pairs in the schema metadata of the table and of its
+//! fields, so that catalogs and UIs can show descriptions, owners or the
+//! embedding model of a vector column.  Any key can be used, the keys below
+//! are the ones LanceDB tools look for.  Keys starting with `lancedb:` are
+//! reserved for the settings LanceDB stores in the same place.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{RecordBatchIterator, RecordBatchReader};
+use arrow_schema::Schema;
+
+use super::constraints::CONSTRAINTS_KEY;
+use super::masking::MASKING_POLICIES_KEY;
+use super::normalized::NORMALIZED_COLUMNS_KEY;
+use super::primary_key::PRIMARY_KEY_KEY;
+use super::shallow_clone::{CLONED_FROM_KEY, CLONES_KEY};
+use super::soft_delete::SOFT_DELETE_KEY;
+use super::temporal::TEMPORAL_VALIDITY_KEY;
+use super::tuning::SEARCH_DEFAULTS_KEY;
+use crate::error::{Error, Result};
+
+/// A human readable description of the table or column
+pub const DESCRIPTION_KEY: &str = "description";
+/// The person or team that owns the table or column
+pub const OWNER_KEY: &str = "owner";
+/// The model that computed the embeddings of a vector column
+pub const EMBEDDING_MODEL_KEY: &str = "embedding_model";
+
+const RESERVED_PREFIX: &str = "lancedb:";
+
+/// The settings that are kept when a table is overwritten
+///
+/// The other entries, such as the pending indices or the statistics of the
+/// fragments, describe the data that is replaced.
+const TABLE_SETTINGS: &[&str] = &[
+    MASKING_POLICIES_KEY,
+    CONSTRAINTS_KEY,
+    PRIMARY_KEY_KEY,
+    SOFT_DELETE_KEY,
+    TEMPORAL_VALIDITY_KEY,
+    NORMALIZED_COLUMNS_KEY,
+    SEARCH_DEFAULTS_KEY,
+    CLONED_FROM_KEY,
+    CLONES_KEY,
+];
+
+/// The entries of `metadata` that were set by users
+pub(crate) fn user_metadata(metadata: &HashMap<String, String>) -> HashMap<String, String> {
+    metadata
+        .iter()
+        .filter(|(key, _)| !key.starts_with(RESERVED_PREFIX))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// Set (or, if `value` is None, remove) a user entry of `metadata`
+pub(crate) fn set_user_metadata(
+    metadata: &mut HashMap<String, String>,
+    key: &str,
+    value: Option<&str>,
+) -> Result<()> {
+    if key.is_empty() || key.starts_with(RESERVED_PREFIX) {
+        return Err(Error::InvalidInput {
+            message: format!(
+                "invalid metadata key '{}', keys must not be empty or start with '{}'",
+                key, RESERVED_PREFIX
+            ),
+        });
+    }
+    match value {
+        Some(value) => metadata.insert(key.to_string(), value.to_string()),
+        None => metadata.remove(key),
+    };
+    Ok(())
+}
+
+/// Keep the settings of a table in the schema of the data that overwrites it
+///
+/// The settings refer to the columns of the table, so the data must have
+/// every column of the table if there are any.
+pub(crate) fn with_table_settings(
+    data: Box<dyn RecordBatchReader + Send>,
+    table_schema: &Schema,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let settings = TABLE_SETTINGS
+        .iter()
+        .filter_map(|key| {
+            table_schema
+                .metadata()
+                .get(*key)
+                .map(|value| (key.to_string(), value.clone()))
+        })
+        .collect::<HashMap<_, _>>();
+    if settings.is_empty() {
+        return Ok(data);
+    }
+    let schema = data.schema();
+    if let Some(missing) = table_schema
+        .fields()
+        .iter()
+        .find(|field| schema.field_with_name(field.name()).is_err())
+    {
+        return Err(Error::Schema {
+            message: format!(
+                "cannot overwrite the table without the column '{}', the settings of the \
+                 table are kept and may refer to it",
+                missing.name()
+            ),
+        });
+    }
+    let mut metadata = schema.metadata().clone();
+    metadata.extend(settings);
+    let schema = Arc::new(schema.as_ref().clone().with_metadata(metadata));
+    let batch_schema = schema.clone();
+    Ok(Box::new(RecordBatchIterator::new(
+        data.map(move |batch| batch.and_then(|batch| batch.with_schema(batch_schema.clone()))),
+        schema,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_metadata() {
+        let mut metadata = HashMap::from([("lancedb:soft_delete".to_string(), "true".to_string())]);
+        set_user_metadata(&mut metadata, DESCRIPTION_KEY, Some("clicks")).unwrap();
+        set_user_metadata(&mut metadata, OWNER_KEY, Some("growth")).unwrap();
+        set_user_metadata(&mut metadata, OWNER_KEY, None).unwrap();
+        assert_eq!(
+            user_metadata(&metadata),
+            HashMap::from([(DESCRIPTION_KEY.to_string(), "clicks".to_string())])
+        );
+        assert_eq!(metadata.len(), 2);
+
+        assert!(set_user_metadata(&mut metadata, "", Some("x")).is_err());
+        assert!(set_user_metadata(&mut metadata, "lancedb:soft_delete", None).is_err());
+    }
+}
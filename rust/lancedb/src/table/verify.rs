@@ -0,0 +1,250 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verifying the integrity of the files of a table
+//!
+//! The Lance format does not store checksums of its data files, so the data
+//! is verified by reading and decoding every row of every fragment.  A file
+//! that is missing, truncated or has been corrupted in a way that breaks the
+//! decoding is reported.  Corruption that still decodes (e.g. a flipped bit
+//! in a float value) can not be detected.
+//!
+//! In addition the manifest is checked for duplicate fragments and indices
+//! on missing columns, and each index is opened and its row counts are
+//! compared with the table.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use lance::io::ObjectStore;
+use lance_index::DatasetIndexExt;
+use object_store::path::Path;
+
+use crate::error::Result;
+use crate::index::vector::VectorIndexStatistics;
+
+/// The kind of problem found by [`crate::Table::verify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// A file referenced by the manifest does not exist
+    MissingFile,
+    /// A file exists but can not be read or decoded
+    CorruptFile,
+    /// A fragment does not contain the number of rows the manifest records
+    RowCountMismatch,
+    /// The manifest is inconsistent with itself
+    InvalidManifest,
+    /// An index can not be opened
+    UnreadableIndex,
+    /// An index covers more rows than the table has
+    IndexMismatch,
+}
+
+/// A problem found by [`crate::Table::verify`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityProblem {
+    pub kind: ProblemKind,
+    /// The fragment with the problem, if the problem is in a fragment
+    pub fragment_id: Option<u64>,
+    /// The file or index with the problem, if known
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl Display for IntegrityProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.kind)?;
+        if let Some(fragment_id) = self.fragment_id {
+            write!(f, " in fragment {}", fragment_id)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The result of [`crate::Table::verify`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// The version of the table that was verified
+    pub version: u64,
+    pub fragments_checked: usize,
+    pub files_checked: usize,
+    pub rows_checked: usize,
+    pub indices_checked: usize,
+    pub problems: Vec<IntegrityProblem>,
+}
+
+impl IntegrityReport {
+    /// True if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn report(
+        &mut self,
+        kind: ProblemKind,
+        fragment_id: Option<u64>,
+        path: Option<String>,
+        message: impl Into<String>,
+    ) {
+        self.problems.push(IntegrityProblem {
+            kind,
+            fragment_id,
+            path,
+            message: message.into(),
+        });
+    }
+}
+
+/// Verify the fragments and indices of a version of a table
+///
+/// `base` is the path of the table in `object_store`.
+pub(crate) async fn verify_dataset(
+    dataset: &Dataset,
+    object_store: &ObjectStore,
+    base: &Path,
+) -> Result<IntegrityReport> {
+    let mut report = IntegrityReport {
+        version: dataset.version().version,
+        ..Default::default()
+    };
+    let data_dir = base.child("data");
+
+    let mut fragment_ids = HashSet::new();
+    let mut physical_rows = 0;
+    for fragment in dataset.get_fragments() {
+        let metadata = fragment.metadata();
+        let id = metadata.id;
+        report.fragments_checked += 1;
+        if !fragment_ids.insert(id) {
+            report.report(
+                ProblemKind::InvalidManifest,
+                Some(id),
+                None,
+                "the fragment appears more than once in the manifest",
+            );
+            continue;
+        }
+
+        let mut missing = false;
+        for file in &metadata.files {
+            let path = data_dir.child(file.path.as_str());
+            report.files_checked += 1;
+            match object_store.size(&path).await {
+                Ok(0) => {
+                    report.report(
+                        ProblemKind::CorruptFile,
+                        Some(id),
+                        Some(path.to_string()),
+                        "the file is empty",
+                    );
+                    missing = true;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    report.report(
+                        ProblemKind::MissingFile,
+                        Some(id),
+                        Some(path.to_string()),
+                        e.to_string(),
+                    );
+                    missing = true;
+                }
+            }
+        }
+        if missing {
+            continue;
+        }
+
+        let expected = match fragment.count_rows().await {
+            Ok(rows) => rows,
+            Err(e) => {
+                report.report(
+                    ProblemKind::CorruptFile,
+                    Some(id),
+                    None,
+                    format!("the rows or deletions can not be counted: {}", e),
+                );
+                continue;
+            }
+        };
+        let read = async {
+            let mut rows = 0;
+            let mut stream = fragment.scan().try_into_stream().await?;
+            while let Some(batch) = stream.try_next().await? {
+                rows += batch.num_rows();
+            }
+            lance::Result::Ok(rows)
+        };
+        match read.await {
+            Ok(rows) => {
+                report.rows_checked += rows;
+                if rows != expected {
+                    report.report(
+                        ProblemKind::RowCountMismatch,
+                        Some(id),
+                        None,
+                        format!("expected {} rows but read {}", expected, rows),
+                    );
+                }
+            }
+            Err(e) => report.report(ProblemKind::CorruptFile, Some(id), None, e.to_string()),
+        }
+        physical_rows += metadata.physical_rows.unwrap_or(expected);
+    }
+
+    for index in dataset.load_indices().await?.iter() {
+        report.indices_checked += 1;
+        let name = Some(index.name.clone());
+        if let Some(field_id) = index
+            .fields
+            .iter()
+            .find(|id| dataset.schema().field_by_id(**id).is_none())
+        {
+            report.report(
+                ProblemKind::InvalidManifest,
+                None,
+                name,
+                format!("the index refers to the missing field {}", field_id),
+            );
+            continue;
+        }
+        let statistics = match dataset.index_statistics(&index.name).await {
+            Ok(statistics) => statistics,
+            Err(e) => {
+                report.report(ProblemKind::UnreadableIndex, None, name, e.to_string());
+                continue;
+            }
+        };
+        // Scalar indices report other statistics
+        if let Ok(statistics) = serde_json::from_str::<VectorIndexStatistics>(&statistics) {
+            if statistics.num_indexed_rows > physical_rows {
+                report.report(
+                    ProblemKind::IndexMismatch,
+                    None,
+                    name,
+                    format!(
+                        "the index covers {} rows but the table only has {}",
+                        statistics.num_indexed_rows, physical_rows
+                    ),
+                );
+            }
+        }
+    }
+    Ok(report)
+}
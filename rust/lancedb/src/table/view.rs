@@ -0,0 +1,532 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Filtered views of a table
+//!
+//! A view, created with [`crate::Connection::create_view`], is a slice of a
+//! base table: the rows that match a filter, with some of the columns.  It is
+//! opened with [`crate::Connection::open_table`] and queried like a table,
+//! which makes it a simple way to hand a tenant or a domain its own slice of
+//! a shared table.
+//!
+//! A virtual view stores no data.  Its filter and projection are added to
+//! every query, so it always shows the current rows of the base table.  A
+//! materialized view (see [`crate::connection::CreateViewBuilder::materialized`])
+//! is a table with a copy of the rows of the view, which is faster to query
+//! when the filter is selective but only changes when it is refreshed with
+//! [`crate::Connection::refresh_view`].
+//!
+//! Views are read only.  Writes through a virtual view fail, and a
+//! materialized view is replaced by every refresh.  The definitions are
+//! stored as small JSON objects in the `_views` directory of the database.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_schema::{Schema, SchemaRef};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::Duration;
+use lance::io::ObjectStore;
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::arrow::SendableRecordBatchStream;
+use crate::connection::NoData;
+use crate::error::{Error, Result};
+use crate::index::{metadata::IndexMetadata, IndexBuilder, IndexConfig};
+use crate::io::{metrics::IoStats, tiering::TieringReport};
+use crate::query::{
+    filter_cache::FilterCacheMetrics, Query, QueryExecutionOptions, Select, VectorQuery,
+};
+
+use super::audit::AuditRecord;
+use super::batch_alter::BatchAlterBuilder;
+use super::blob::BlobRef;
+use super::bulk::BulkLoadStats;
+use super::constraints::Constraint;
+use super::estimate::TableStatistics;
+use super::fragment_stats::FragmentPruning;
+use super::interceptor::and_filters;
+use super::masking::MaskingPolicy;
+use super::merge::MergeInsertBuilder;
+use super::merge_columns::MergeColumnsBuilder;
+use super::primary_key::KeyValue;
+use super::prune::PrunePreview;
+use super::reembed::{ReembedBuilder, ReembedReport};
+use super::repair::{RepairOptions, RepairReport};
+use super::temporal::TemporalValidity;
+use super::tuning::SearchDefaults;
+use super::verify::IntegrityReport;
+use super::write_stats::WriteStats;
+use super::{
+    AddDataBuilder, ColumnAlteration, NativeTable, NewColumnTransform, OptimizeAction,
+    OptimizeStats, TableInternal, UpdateBuilder, Version,
+};
+
+/// The directory of the view definitions, relative to the database
+const VIEWS_DIR: &str = "_views";
+
+/// The rows and columns of the base table that a view shows
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewDefinition {
+    /// An SQL filter, only the rows that match it are in the view
+    pub filter: Option<String>,
+    /// The columns of the view, all of the columns of the base table if None
+    pub projection: Option<Vec<String>>,
+}
+
+/// A view as stored in the database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct StoredView {
+    pub base_table: String,
+    pub definition: ViewDefinition,
+    pub materialized: bool,
+}
+
+fn view_path(base: &Path, name: &str) -> Path {
+    base.child(VIEWS_DIR).child(format!("{}.json", name))
+}
+
+/// Read the definition of the view `name`, None if there is no such view
+pub(crate) async fn read_view(
+    store: &ObjectStore,
+    base: &Path,
+    name: &str,
+) -> Result<Option<StoredView>> {
+    let bytes = match store.inner.get(&view_path(base, name)).await {
+        Ok(result) => result.bytes().await?,
+        Err(object_store::Error::NotFound { .. }) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let view = serde_json::from_slice(&bytes).map_err(|e| Error::Runtime {
+        message: format!("the definition of the view '{}' is invalid: {}", name, e),
+    })?;
+    Ok(Some(view))
+}
+
+/// Store (or, if `view` is None, remove) the definition of the view `name`
+pub(crate) async fn write_view(
+    store: &ObjectStore,
+    base: &Path,
+    name: &str,
+    view: Option<&StoredView>,
+) -> Result<()> {
+    let path = view_path(base, name);
+    let Some(view) = view else {
+        return match store.inner.delete(&path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err.into()),
+        };
+    };
+    let bytes = serde_json::to_vec(view).map_err(|e| Error::Runtime {
+        message: format!(
+            "failed to serialize the definition of the view '{}': {}",
+            name, e
+        ),
+    })?;
+    store.inner.put(&path, Bytes::from(bytes)).await?;
+    Ok(())
+}
+
+impl ViewDefinition {
+    /// Check that the projection only refers to columns of the base table
+    pub(crate) fn validate(&self, base_schema: &Schema) -> Result<()> {
+        for column in self.projection.iter().flatten() {
+            if base_schema.field_with_name(column).is_err() {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the projection of the view refers to '{}', which is not a column of \
+                         the base table",
+                        column
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Restrict a query of the view to the rows and columns of the view
+    pub(crate) fn apply(&self, query: &Query) -> Result<Query> {
+        let mut query = query.clone();
+        query.filter = and_filters(query.filter.as_deref(), self.filter.as_deref());
+        let Some(projection) = &self.projection else {
+            return Ok(query);
+        };
+        let check_in_view = |column: &String| -> Result<()> {
+            if projection.contains(column) {
+                Ok(())
+            } else {
+                Err(Error::InvalidInput {
+                    message: format!("'{}' is not a column of the view", column),
+                })
+            }
+        };
+        query.select = match &query.select {
+            Select::All => Select::Columns(projection.clone()),
+            Select::AllExcept(excluded) => Select::Columns(
+                projection
+                    .iter()
+                    .filter(|column| !excluded.contains(column))
+                    .cloned()
+                    .collect(),
+            ),
+            Select::Columns(columns) => {
+                columns.iter().try_for_each(check_in_view)?;
+                Select::Columns(columns.clone())
+            }
+            Select::Dynamic(_) => {
+                return Err(Error::NotSupported {
+                    message: "dynamic selections are not supported on a view with a projection"
+                        .to_string(),
+                })
+            }
+        };
+        for column in query
+            .late_materialization
+            .iter()
+            .chain(&query.lazy_blobs)
+            .flatten()
+        {
+            check_in_view(column)?;
+        }
+        Ok(query)
+    }
+}
+
+/// A virtual view, the handle returned when a virtual view is opened
+#[derive(Debug)]
+pub(crate) struct ViewTable {
+    name: String,
+    base: Arc<dyn TableInternal>,
+    definition: ViewDefinition,
+}
+
+impl ViewTable {
+    pub fn new(name: String, base: Arc<dyn TableInternal>, definition: ViewDefinition) -> Self {
+        Self {
+            name,
+            base,
+            definition,
+        }
+    }
+
+    fn read_only<T>(&self, operation: &str) -> Result<T> {
+        Err(Error::NotSupported {
+            message: format!(
+                "cannot {} through the view '{}', views are read only",
+                operation, self.name
+            ),
+        })
+    }
+
+    fn filter(&self, filter: Option<String>) -> Option<String> {
+        and_filters(filter.as_deref(), self.definition.filter.as_deref())
+    }
+}
+
+impl std::fmt::Display for ViewTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "View({}, base={})", self.name, self.base)
+    }
+}
+
+#[async_trait]
+impl TableInternal for ViewTable {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn as_native(&self) -> Option<&NativeTable> {
+        None
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    async fn schema(&self) -> Result<SchemaRef> {
+        let schema = self.base.schema().await?;
+        let Some(projection) = &self.definition.projection else {
+            return Ok(schema);
+        };
+        let fields = projection
+            .iter()
+            .map(|column| Ok(schema.field_with_name(column)?.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arc::new(Schema::new_with_metadata(
+            fields,
+            schema.metadata().clone(),
+        )))
+    }
+    async fn count_rows(&self, filter: Option<String>) -> Result<usize> {
+        self.base.count_rows(self.filter(filter)).await
+    }
+    async fn plain_query(
+        &self,
+        query: &Query,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        let query = self.definition.apply(query)?;
+        self.base.plain_query(&query, options).await
+    }
+    async fn vector_query(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        let mut query = query.clone();
+        query.base = self.definition.apply(&query.base)?;
+        self.base.vector_query(&query, options).await
+    }
+    async fn add(
+        &self,
+        _add: AddDataBuilder<NoData>,
+        _data: Box<dyn arrow_array::RecordBatchReader + Send>,
+    ) -> Result<()> {
+        self.read_only("add data")
+    }
+    async fn delete(&self, _predicate: &str) -> Result<()> {
+        self.read_only("delete rows")
+    }
+    async fn delete_by_values(&self, _column: &str, _values: &[KeyValue]) -> Result<usize> {
+        self.read_only("delete rows")
+    }
+    async fn update(&self, _update: UpdateBuilder) -> Result<()> {
+        self.read_only("update rows")
+    }
+    async fn finish_bulk_load(&self) -> Result<BulkLoadStats> {
+        self.read_only("load data")
+    }
+    async fn discard_bulk_load(&self) -> Result<usize> {
+        self.read_only("load data")
+    }
+    async fn history(&self) -> Result<Vec<AuditRecord>> {
+        self.base.history().await
+    }
+    async fn create_index(&self, _index: IndexBuilder) -> Result<()> {
+        self.read_only("create an index")
+    }
+    async fn list_indices(&self) -> Result<Vec<IndexConfig>> {
+        self.base.list_indices().await
+    }
+    async fn merge_insert(
+        &self,
+        _params: MergeInsertBuilder,
+        _new_data: Box<dyn arrow_array::RecordBatchReader + Send>,
+    ) -> Result<()> {
+        self.read_only("merge data")
+    }
+    async fn merge_columns(
+        &self,
+        _params: MergeColumnsBuilder,
+        _new_data: Box<dyn arrow_array::RecordBatchReader + Send>,
+    ) -> Result<()> {
+        self.read_only("merge columns")
+    }
+    async fn optimize(&self, _action: OptimizeAction) -> Result<OptimizeStats> {
+        self.read_only("optimize the table")
+    }
+    async fn preview_prune(
+        &self,
+        _older_than: Duration,
+        _delete_unverified: Option<bool>,
+    ) -> Result<PrunePreview> {
+        self.read_only("prune the table")
+    }
+    async fn add_columns(
+        &self,
+        _transforms: NewColumnTransform,
+        _read_columns: Option<Vec<String>>,
+    ) -> Result<()> {
+        self.read_only("add columns")
+    }
+    async fn alter_columns(&self, _alterations: &[ColumnAlteration]) -> Result<()> {
+        self.read_only("alter columns")
+    }
+    async fn drop_columns(&self, _columns: &[&str]) -> Result<()> {
+        self.read_only("drop columns")
+    }
+    async fn batch_alter(&self, _alter: BatchAlterBuilder) -> Result<()> {
+        self.read_only("alter the schema")
+    }
+    async fn masking_policies(&self) -> Result<HashMap<String, MaskingPolicy>> {
+        self.base.masking_policies().await
+    }
+    async fn set_masking_policy(
+        &self,
+        _column: &str,
+        _policy: Option<MaskingPolicy>,
+    ) -> Result<()> {
+        self.read_only("change a masking policy")
+    }
+    async fn temporal_validity(&self) -> Result<Option<TemporalValidity>> {
+        self.base.temporal_validity().await
+    }
+    async fn set_temporal_validity(&self, _validity: Option<TemporalValidity>) -> Result<()> {
+        self.read_only("change the temporal validity")
+    }
+    async fn soft_delete(&self) -> Result<bool> {
+        self.base.soft_delete().await
+    }
+    async fn set_soft_delete(&self, _enabled: bool) -> Result<()> {
+        self.read_only("change soft deletes")
+    }
+    async fn undelete(&self, _predicate: &str) -> Result<()> {
+        self.read_only("undelete rows")
+    }
+    async fn primary_key(&self) -> Result<Option<String>> {
+        self.base.primary_key().await
+    }
+    async fn set_primary_key(&self, _column: Option<&str>) -> Result<()> {
+        self.read_only("change the primary key")
+    }
+    async fn constraints(&self) -> Result<HashMap<String, Vec<Constraint>>> {
+        self.base.constraints().await
+    }
+    async fn set_constraints(&self, _column: &str, _constraints: Vec<Constraint>) -> Result<()> {
+        self.read_only("change constraints")
+    }
+    async fn version(&self) -> Result<u64> {
+        self.base.version().await
+    }
+    async fn list_versions(&self) -> Result<Vec<Version>> {
+        self.base.list_versions().await
+    }
+    async fn checkout(&self, version: u64) -> Result<()> {
+        self.base.checkout(version).await
+    }
+    async fn checkout_latest(&self) -> Result<()> {
+        self.base.checkout_latest().await
+    }
+    async fn restore(&self) -> Result<()> {
+        self.read_only("restore a version")
+    }
+    async fn snapshot(&self) -> Result<Arc<dyn TableInternal>> {
+        Ok(Arc::new(Self::new(
+            self.name.clone(),
+            self.base.snapshot().await?,
+            self.definition.clone(),
+        )))
+    }
+    async fn copy_to(&self, _name: &str) -> Result<Arc<dyn TableInternal>> {
+        self.read_only("clone the table")
+    }
+    fn write_stats(&self) -> Result<WriteStats> {
+        self.base.write_stats()
+    }
+    fn io_stats(&self) -> Result<IoStats> {
+        self.base.io_stats()
+    }
+    async fn tiering_report(&self) -> Result<TieringReport> {
+        self.base.tiering_report().await
+    }
+    async fn ids_of_rows(&self, row_ids: &[u64]) -> Result<Vec<KeyValue>> {
+        self.base.ids_of_rows(row_ids).await
+    }
+    async fn scan_fragments(&self, version: Option<u64>) -> Result<(u64, Vec<u64>)> {
+        self.base.scan_fragments(version).await
+    }
+    fn filter_cache_metrics(&self) -> Result<Option<FilterCacheMetrics>> {
+        self.base.filter_cache_metrics()
+    }
+    async fn statistics(&self) -> Result<TableStatistics> {
+        // The statistics of the base table would describe rows outside the view
+        Err(Error::NotSupported {
+            message: "table statistics are not supported on views".to_string(),
+        })
+    }
+    async fn verify(&self) -> Result<IntegrityReport> {
+        self.base.verify().await
+    }
+    async fn repair(&self, _options: RepairOptions) -> Result<RepairReport> {
+        self.read_only("repair the table")
+    }
+    async fn reembed(&self, _reembed: ReembedBuilder) -> Result<ReembedReport> {
+        self.read_only("re-embed a column")
+    }
+    async fn index_metadata(&self, column: &str) -> Result<IndexMetadata> {
+        self.base.index_metadata(column).await
+    }
+    async fn compute_statistics(&self, _columns: &[&str]) -> Result<()> {
+        self.read_only("compute statistics")
+    }
+    async fn fragment_pruning(&self, query: &Query) -> Result<FragmentPruning> {
+        let query = self.definition.apply(query)?;
+        self.base.fragment_pruning(&query).await
+    }
+    async fn sample(&self, _n: usize, _seed: Option<u64>) -> Result<RecordBatch> {
+        Err(Error::NotSupported {
+            message: "sampling is not supported on views".to_string(),
+        })
+    }
+    async fn estimate_count(&self, filter: Option<String>) -> Result<usize> {
+        self.base.estimate_count(self.filter(filter)).await
+    }
+    async fn estimate_distinct(&self, _column: &str) -> Result<usize> {
+        Err(Error::NotSupported {
+            message: "distinct estimates are not supported on views".to_string(),
+        })
+    }
+    async fn search_defaults(&self) -> Result<HashMap<String, SearchDefaults>> {
+        self.base.search_defaults().await
+    }
+    async fn set_search_defaults(
+        &self,
+        _column: &str,
+        _defaults: Option<SearchDefaults>,
+    ) -> Result<()> {
+        self.read_only("change search defaults")
+    }
+    async fn metadata(&self, column: Option<&str>) -> Result<HashMap<String, String>> {
+        self.base.metadata(column).await
+    }
+    async fn set_metadata(
+        &self,
+        _column: Option<&str>,
+        _key: &str,
+        _value: Option<&str>,
+    ) -> Result<()> {
+        self.read_only("change metadata")
+    }
+    async fn blob_refs(&self, batch: &RecordBatch, column: &str) -> Result<Vec<BlobRef>> {
+        self.base.blob_refs(batch, column).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{DataType, Field};
+
+    use super::*;
+
+    #[test]
+    fn test_validate_definition() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("tenant", DataType::Utf8, false),
+        ]);
+        let definition = ViewDefinition {
+            filter: Some("tenant = 'a'".to_string()),
+            projection: Some(vec!["id".to_string()]),
+        };
+        definition.validate(&schema).unwrap();
+        let definition = ViewDefinition {
+            filter: None,
+            projection: Some(vec!["name".to_string()]),
+        };
+        assert!(matches!(
+            definition.validate(&schema),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
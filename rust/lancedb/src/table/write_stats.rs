@@ -0,0 +1,296 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_schema::{ArrowError, SchemaRef};
+use log::warn;
+
+use crate::data::precision::VectorCastStats;
+
+/// Commits with fewer rows than this are considered small
+pub const SMALL_WRITE_ROWS: usize = 1_000;
+/// The number of recent commits that are considered when deciding to warn
+const RECENT_WINDOW: usize = 100;
+/// Warn when at least this many of the recent commits are small
+const WARN_THRESHOLD: usize = 50;
+
+/// Statistics about the writes made through a table handle
+///
+/// These are tracked in memory, for the lifetime of the table handle, and only
+/// cover writes made with [`super::Table::add`] through this handle.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteStats {
+    /// The number of commits
+    pub commits: u64,
+    /// The total number of rows written
+    pub rows: u64,
+    /// The number of commits with fewer than [`SMALL_WRITE_ROWS`] rows
+    pub small_commits: u64,
+    /// The number of recent commits that were considered (at most 100)
+    pub recent_commits: usize,
+    /// How many of the recent commits were small
+    pub recent_small_commits: usize,
+    /// The precision lost by casting vectors to the type of the table, by column
+    ///
+    /// See [`crate::data::precision::VectorPrecisionPolicy`]
+    pub vector_casts: BTreeMap<String, VectorCastStats>,
+}
+
+impl WriteStats {
+    /// A warning, if the table is receiving too many small commits
+    ///
+    /// Each commit creates a new version of the table and at least one new data
+    /// file.  Many small commits cause queries to slow down over time because
+    /// they need to open many small files.
+    pub fn small_write_warning(&self) -> Option<String> {
+        if self.recent_small_commits < WARN_THRESHOLD {
+            return None;
+        }
+        Some(format!(
+            "{} of the last {} commits wrote fewer than {} rows ({} rows per commit on average). \
+                Consider batching writes with Table::buffered_writer and compacting the \
+                table with Table::optimize",
+            self.recent_small_commits,
+            self.recent_commits,
+            SMALL_WRITE_ROWS,
+            self.rows / self.commits.max(1),
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    stats: WriteStats,
+    recent: VecDeque<bool>,
+    // The value of small_commits when we last logged a warning
+    warned_at: Option<u64>,
+}
+
+/// Records the size of each commit made by a table
+#[derive(Debug, Default)]
+pub(crate) struct WriteStatsTracker {
+    state: Mutex<TrackerState>,
+}
+
+impl WriteStatsTracker {
+    pub fn record(&self, table_name: &str, rows: usize) {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let small = rows < SMALL_WRITE_ROWS;
+        state.recent.push_back(small);
+        if state.recent.len() > RECENT_WINDOW {
+            state.recent.pop_front();
+        }
+        let recent_small_commits = state.recent.iter().filter(|small| **small).count();
+        let recent_commits = state.recent.len();
+        let stats = &mut state.stats;
+        stats.commits += 1;
+        stats.rows += rows as u64;
+        stats.small_commits += small as u64;
+        stats.recent_commits = recent_commits;
+        stats.recent_small_commits = recent_small_commits;
+
+        // Log once when the threshold is first crossed and then once per window
+        let small_commits = stats.small_commits;
+        if let Some(warning) = stats.small_write_warning() {
+            let due = state
+                .warned_at
+                .map(|at| small_commits - at >= RECENT_WINDOW as u64)
+                .unwrap_or(true);
+            if due {
+                warn!("Table {}: {}", table_name, warning);
+                state.warned_at = Some(small_commits);
+            }
+        }
+    }
+
+    pub fn record_vector_casts(&self, casts: &BTreeMap<String, VectorCastStats>) {
+        let mut state = self.state.lock().unwrap();
+        for (column, stats) in casts {
+            state
+                .stats
+                .vector_casts
+                .entry(column.clone())
+                .or_default()
+                .merge(stats);
+        }
+    }
+
+    pub fn stats(&self) -> WriteStats {
+        self.state.lock().unwrap().stats.clone()
+    }
+}
+
+/// Counts the rows that pass through a reader
+pub(crate) struct CountingReader {
+    inner: Box<dyn RecordBatchReader + Send>,
+    rows: Arc<AtomicUsize>,
+}
+
+impl CountingReader {
+    pub fn new(inner: Box<dyn RecordBatchReader + Send>) -> (Self, Arc<AtomicUsize>) {
+        let rows = Arc::new(AtomicUsize::new(0));
+        (
+            Self {
+                inner,
+                rows: rows.clone(),
+            },
+            rows,
+        )
+    }
+}
+
+impl Iterator for CountingReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = self.inner.next();
+        if let Some(Ok(batch)) = &batch {
+            self.rows.fetch_add(batch.num_rows(), Ordering::Relaxed);
+        }
+        batch
+    }
+}
+
+impl RecordBatchReader for CountingReader {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use arrow_array::{Int32Array, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::connect;
+    use crate::data::precision::VectorPrecisionPolicy;
+
+    #[test]
+    fn test_small_write_warning() {
+        let tracker = WriteStatsTracker::default();
+        for _ in 0..WARN_THRESHOLD - 1 {
+            tracker.record("t", 1);
+        }
+        tracker.record("t", 10_000);
+        assert!(tracker.stats().small_write_warning().is_none());
+
+        tracker.record("t", 1);
+        let stats = tracker.stats();
+        assert_eq!(stats.commits, WARN_THRESHOLD as u64 + 1);
+        assert_eq!(stats.small_commits, WARN_THRESHOLD as u64);
+        assert!(stats
+            .small_write_warning()
+            .unwrap()
+            .contains("buffered_writer"));
+
+        // Small commits age out of the window
+        for _ in 0..RECENT_WINDOW {
+            tracker.record("t", 10_000);
+        }
+        let stats = tracker.stats();
+        assert_eq!(stats.recent_commits, RECENT_WINDOW);
+        assert_eq!(stats.recent_small_commits, 0);
+        assert!(stats.small_write_warning().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_stats() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri)
+            .read_consistency_interval(Duration::from_secs(0))
+            .execute()
+            .await
+            .unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let table = conn
+            .create_empty_table("events", schema.clone())
+            .execute()
+            .await
+            .unwrap();
+        for i in 0..3 {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(0..i + 1))],
+            )
+            .unwrap();
+            table
+                .add(RecordBatchIterator::new(vec![Ok(batch)], schema.clone()))
+                .execute()
+                .await
+                .unwrap();
+        }
+        let stats = table.write_stats().unwrap();
+        assert_eq!(stats.commits, 3);
+        assert_eq!(stats.rows, 6);
+        assert_eq!(stats.small_commits, 3);
+    }
+
+    #[tokio::test]
+    async fn test_vector_casts() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let vector_type =
+            |item: DataType| DataType::FixedSizeList(Arc::new(Field::new("item", item, true)), 2);
+        let table_schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            vector_type(DataType::Float32),
+            true,
+        )]));
+        let table = conn
+            .create_empty_table("embeddings", table_schema.clone())
+            .execute()
+            .await
+            .unwrap();
+
+        let data = || {
+            let schema = Arc::new(Schema::new(vec![Field::new(
+                "vector",
+                vector_type(DataType::Float64),
+                true,
+            )]));
+            let vectors = arrow_array::FixedSizeListArray::new(
+                Arc::new(Field::new("item", DataType::Float64, true)),
+                2,
+                Arc::new(arrow_array::Float64Array::from(vec![0.1, 0.5])),
+                None,
+            );
+            let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema)
+        };
+        let rejected = table
+            .add(data())
+            .vector_precision(VectorPrecisionPolicy::Error)
+            .execute()
+            .await;
+        assert!(matches!(rejected, Err(crate::Error::InvalidInput { .. })));
+
+        table.add(data()).execute().await.unwrap();
+        assert_eq!(table.schema().await.unwrap(), table_schema);
+        let stats = table.write_stats().unwrap().vector_casts["vector"].clone();
+        assert_eq!(stats.values, 2);
+        assert_eq!(stats.lossy_values, 1);
+        assert!(stats.max_abs_error > 0.0);
+    }
+}
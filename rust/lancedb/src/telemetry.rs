@@ -0,0 +1,370 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracing spans and metrics
+//!
+//! LanceDb emits [`tracing`] spans at the `debug` level for every query and
+//! write.  Install any `tracing` subscriber to collect them.  The spans are:
+//!
+//! | Span                   | Fields                        |
+//! |------------------------|-------------------------------|
+//! | `lancedb.query`        | `table`, `kind`               |
+//! | `lancedb.add`          | `table`, `version`, `rows`    |
+//! | `lancedb.update`       | `table`, `version`            |
+//! | `lancedb.merge_insert` | `table`, `version`            |
+//! | `lancedb.delete`       | `table`, `version`            |
+//! | `lancedb.create_index` | `table`, `columns`            |
+//! | `lancedb.optimize`     | `table`, `action`             |
+//! | `lancedb.add_columns`, `lancedb.alter_columns`, `lancedb.drop_columns` | `table`, `version` |
+//! | `lancedb.remote.request` | `method`, `url`, `attempt`, `status` |
+//! | `lancedb.request`      | `request_id`, `tags`          |
+//!
+//! To link these spans to the application request that caused them, run the
+//! operation in a [`RequestContext`]:
+//!
+//! ```
+//! # use lancedb::telemetry::RequestContext;
+//! # async fn handle(table: lancedb::Table) {
+//! let rows = RequestContext::new("req-1234")
+//!     .tag("tenant", "acme")
+//!     .scope(table.count_rows(None))
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+//!
+//! Every span of the operation is then a child of a `lancedb.request` span
+//! with the request id and the tags, and requests to LanceDb Cloud carry them
+//! in the `x-request-id` and `x-lancedb-tags` headers.  The context is not
+//! inherited by background tasks, e.g. the timed flushes of a
+//! [`crate::table::buffered::BufferedWriter`].
+//!
+//! Each operation also emits an event with its latency (`elapsed_ms`) once it
+//! completes.  Queries report the number of rows read once the result stream
+//! is exhausted or dropped.
+//!
+//! If the `metrics` feature is enabled the same operations are also reported
+//! through the [`metrics`](https://docs.rs/metrics) facade, install a recorder
+//! (e.g. a Prometheus exporter) to collect them:
+//!
+//! | Metric                                | Type      | Labels                |
+//! |---------------------------------------|-----------|-----------------------|
+//! | `lancedb_queries_total`               | counter   | `table`, `kind`       |
+//! | `lancedb_query_duration_seconds`      | histogram | `table`, `kind`       |
+//! | `lancedb_query_rows_total`            | counter   | `table`, `kind`       |
+//! | `lancedb_writes_total`                | counter   | `table`, `operation`  |
+//! | `lancedb_write_duration_seconds`      | histogram | `table`, `operation`  |
+//! | `lancedb_rows_written_total`          | counter   | `table`, `operation`  |
+//! | `lancedb_optimize_runs_total`         | counter   | `table`, `action`     |
+//! | `lancedb_optimize_duration_seconds`   | histogram | `table`, `action`     |
+//! | `lancedb_remote_requests_total`       | counter   | `method`, `status`    |
+//! | `lancedb_remote_request_duration_seconds` | histogram | `method`, `status` |
+//!
+//! `status` is the HTTP status code, or `error` if no response was received.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use tracing::Instrument;
+
+use crate::arrow::{SendableRecordBatchStream, SimpleRecordBatchStream};
+
+thread_local! {
+    static CURRENT_REQUEST: RefCell<Option<Arc<RequestContext>>> = const { RefCell::new(None) };
+}
+
+/// The application request that an operation is part of
+///
+/// See [the module documentation](self) for an example.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    request_id: String,
+    tags: Vec<(String, String)>,
+}
+
+impl RequestContext {
+    pub fn new(request_id: impl Into<String>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Attach a tag, such as the tenant or the endpoint of the request
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// The tags formatted as `key=value` pairs separated by commas
+    pub(crate) fn tags_string(&self) -> String {
+        self.tags
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// The context of the operation that is running, if any
+    pub fn current() -> Option<Self> {
+        CURRENT_REQUEST.with(|current| current.borrow().as_ref().map(|c| c.as_ref().clone()))
+    }
+
+    /// Run `future` in this context
+    pub fn scope<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+        let span = tracing::debug_span!(
+            "lancedb.request",
+            request_id = %self.request_id,
+            tags = %self.tags_string(),
+        );
+        RequestScope {
+            context: Arc::new(self),
+            future,
+        }
+        .instrument(span)
+    }
+}
+
+/// Restores the previous context once a poll is done, even if it panics
+struct RestoreRequest(Option<Arc<RequestContext>>);
+
+impl Drop for RestoreRequest {
+    fn drop(&mut self) {
+        CURRENT_REQUEST.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+#[pin_project::pin_project]
+struct RequestScope<F> {
+    context: Arc<RequestContext>,
+    #[pin]
+    future: F,
+}
+
+impl<F: Future> Future for RequestScope<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let previous = CURRENT_REQUEST.with(|current| current.replace(Some(this.context.clone())));
+        let _restore = RestoreRequest(previous);
+        this.future.poll(cx)
+    }
+}
+
+/// Record the version of the table on the current span
+pub(crate) fn record_version(version: u64) {
+    tracing::Span::current().record("version", version);
+}
+
+/// Report a completed write
+pub(crate) fn record_write(
+    table: &str,
+    operation: &'static str,
+    rows: Option<usize>,
+    elapsed: Duration,
+) {
+    let elapsed_ms = elapsed.as_millis() as u64;
+    match rows {
+        Some(rows) => tracing::debug!(elapsed_ms, rows, "{} finished", operation),
+        None => tracing::debug!(elapsed_ms, "{} finished", operation),
+    }
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [
+            ("table", table.to_string()),
+            ("operation", operation.to_string()),
+        ];
+        metrics::counter!("lancedb_writes_total", &labels).increment(1);
+        metrics::histogram!("lancedb_write_duration_seconds", &labels)
+            .record(elapsed.as_secs_f64());
+        if let Some(rows) = rows {
+            metrics::counter!("lancedb_rows_written_total", &labels).increment(rows as u64);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = table;
+}
+
+/// Report a completed optimize run
+pub(crate) fn record_optimize(table: &str, action: &'static str, elapsed: Duration) {
+    tracing::debug!(elapsed_ms = elapsed.as_millis() as u64, "optimize finished");
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [("table", table.to_string()), ("action", action.to_string())];
+        metrics::counter!("lancedb_optimize_runs_total", &labels).increment(1);
+        metrics::histogram!("lancedb_optimize_duration_seconds", &labels)
+            .record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (table, action);
+}
+
+/// Report a completed request to LanceDb Cloud
+#[cfg(feature = "remote")]
+pub(crate) fn record_remote_request(method: &str, status: Option<u16>, elapsed: Duration) {
+    let elapsed_ms = elapsed.as_millis() as u64;
+    match status {
+        Some(status) => tracing::debug!(elapsed_ms, status, "request finished"),
+        None => tracing::debug!(elapsed_ms, "request failed"),
+    }
+    #[cfg(feature = "metrics")]
+    {
+        let status = status
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "error".to_string());
+        let labels = [("method", method.to_string()), ("status", status)];
+        metrics::counter!("lancedb_remote_requests_total", &labels).increment(1);
+        metrics::histogram!("lancedb_remote_request_duration_seconds", &labels)
+            .record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = method;
+}
+
+/// Reports the rows read and the latency of a query once its stream is done
+struct QueryReport {
+    table: String,
+    kind: &'static str,
+    start: Instant,
+    rows: usize,
+    span: tracing::Span,
+}
+
+impl Drop for QueryReport {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let _entered = self.span.enter();
+        tracing::debug!(
+            table = %self.table,
+            kind = self.kind,
+            elapsed_ms = elapsed.as_millis() as u64,
+            rows = self.rows,
+            "query finished"
+        );
+        #[cfg(feature = "metrics")]
+        {
+            let labels = [
+                ("table", self.table.clone()),
+                ("kind", self.kind.to_string()),
+            ];
+            metrics::counter!("lancedb_queries_total", &labels).increment(1);
+            metrics::histogram!("lancedb_query_duration_seconds", &labels)
+                .record(elapsed.as_secs_f64());
+            metrics::counter!("lancedb_query_rows_total", &labels).increment(self.rows as u64);
+        }
+    }
+}
+
+/// Wrap the results of a query so the query is reported once they are consumed
+///
+/// `start` should be the time at which the query started, so that planning is
+/// included in the latency.
+pub(crate) fn instrument_query_stream(
+    stream: SendableRecordBatchStream,
+    table: &str,
+    kind: &'static str,
+    start: Instant,
+) -> SendableRecordBatchStream {
+    let mut report = QueryReport {
+        table: table.to_string(),
+        kind,
+        start,
+        rows: 0,
+        span: tracing::Span::current(),
+    };
+    let schema = stream.schema();
+    Box::pin(SimpleRecordBatchStream {
+        schema,
+        stream: stream.map(move |batch| {
+            // Borrow the whole report, capturing only the row count would drop
+            // (and report) it right away
+            let report = &mut report;
+            if let Ok(batch) = &batch {
+                report.rows += batch.num_rows();
+            }
+            batch
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::{stream, TryStreamExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_instrumented_stream_passes_batches_through() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        let batches: Vec<crate::Result<RecordBatch>> = vec![Ok(batch.clone()), Ok(batch)];
+        let inner: SendableRecordBatchStream = Box::pin(SimpleRecordBatchStream {
+            schema: schema.clone(),
+            stream: stream::iter(batches),
+        });
+
+        let stream = instrument_query_stream(inner, "test", "plain", Instant::now());
+        assert_eq!(stream.schema(), schema);
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_request_context() {
+        assert!(RequestContext::current().is_none());
+        let context = RequestContext::new("outer")
+            .tag("tenant", "acme")
+            .tag("a", "b");
+        assert_eq!(context.tags_string(), "tenant=acme,a=b");
+
+        let seen = context
+            .clone()
+            .scope(async {
+                tokio::task::yield_now().await;
+                let outer = RequestContext::current();
+                let inner = RequestContext::new("inner")
+                    .scope(async { RequestContext::current() })
+                    .await;
+                (outer, inner, RequestContext::current())
+            })
+            .await;
+        assert_eq!(seen.0.as_ref(), Some(&context));
+        assert_eq!(seen.1.unwrap().request_id(), "inner");
+        assert_eq!(seen.2.as_ref(), Some(&context));
+        assert!(RequestContext::current().is_none());
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Human friendly durations and sizes
+//!
+//! Options that take a duration or a size accept either a typed value or a
+//! string, which is convenient when options come from a configuration file.
+//!
+//! Durations are one or more components, each a number followed by a unit,
+//! e.g. `500ms`, `1.5s` or `1h 30m`.  The units are `ns`, `us`, `ms`, `s`,
+//! `m`, `h` and `d` (long forms such as `secs` or `minutes` are also accepted).
+//!
+//! Sizes are a number optionally followed by a unit, e.g. `4096`, `512MB` or
+//! `1.5GiB`.  `KB`, `MB`, `GB` and `TB` are powers of 1000, `KiB`, `MiB`, `GiB`
+//! and `TiB` are powers of 1024.  A number without a unit is a number of bytes.
+//!
+//! Units are case insensitive.
+
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// A value that can be converted into a [`Duration`]
+pub trait IntoDuration {
+    fn into_duration(self) -> Result<Duration>;
+}
+
+impl IntoDuration for Duration {
+    fn into_duration(self) -> Result<Duration> {
+        Ok(self)
+    }
+}
+
+impl IntoDuration for &str {
+    fn into_duration(self) -> Result<Duration> {
+        parse_duration(self)
+    }
+}
+
+impl IntoDuration for String {
+    fn into_duration(self) -> Result<Duration> {
+        parse_duration(&self)
+    }
+}
+
+/// A value that can be converted into a size in bytes
+pub trait IntoSize {
+    fn into_size(self) -> Result<usize>;
+}
+
+impl IntoSize for usize {
+    fn into_size(self) -> Result<usize> {
+        Ok(self)
+    }
+}
+
+impl IntoSize for &str {
+    fn into_size(self) -> Result<usize> {
+        parse_size(self)
+    }
+}
+
+impl IntoSize for String {
+    fn into_size(self) -> Result<usize> {
+        parse_size(&self)
+    }
+}
+
+/// Split a leading number off of `s`, returning the number and the rest
+fn split_number(s: &str) -> Option<(f64, &str)> {
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let value = s[..end].parse::<f64>().ok()?;
+    Some((value, &s[end..]))
+}
+
+/// Parse a duration such as `500ms` or `1h 30m`, see the [module docs](self)
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let invalid = |reason: &str| Error::InvalidInput {
+        message: format!("invalid duration '{}': {}", s, reason),
+    };
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid("the duration is empty"));
+    }
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let (value, after) =
+            split_number(rest).ok_or_else(|| invalid("expected a number followed by a unit"))?;
+        let after = after.trim_start();
+        let unit_end = after
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(after.len());
+        let nanos_per_unit = match after[..unit_end].to_lowercase().as_str() {
+            "ns" | "nanos" | "nanoseconds" => 1.0,
+            "us" | "µs" | "micros" | "microseconds" => 1e3,
+            "ms" | "millis" | "milliseconds" => 1e6,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1e9,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60e9,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3600e9,
+            "d" | "day" | "days" => 86400e9,
+            "" => return Err(invalid("a unit is required, e.g. '10s' or '500ms'")),
+            unit => return Err(invalid(&format!("unknown unit '{}'", unit))),
+        };
+        let nanos = (value * nanos_per_unit).round();
+        if nanos > u64::MAX as f64 {
+            return Err(invalid("the duration is out of range"));
+        }
+        total = total
+            .checked_add(Duration::from_nanos(nanos as u64))
+            .ok_or_else(|| invalid("the duration is out of range"))?;
+        rest = after[unit_end..].trim_start();
+    }
+    Ok(total)
+}
+
+/// Parse a size such as `512MB` or `1.5GiB` into bytes, see the [module docs](self)
+pub fn parse_size(s: &str) -> Result<usize> {
+    let invalid = |reason: &str| Error::InvalidInput {
+        message: format!("invalid size '{}': {}", s, reason),
+    };
+    let trimmed = s.trim();
+    let (value, unit) = split_number(trimmed)
+        .ok_or_else(|| invalid("expected a number optionally followed by a unit"))?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        unit => return Err(invalid(&format!("unknown unit '{}'", unit))),
+    };
+    let bytes = value * multiplier as f64;
+    if bytes.fract() != 0.0 {
+        return Err(invalid("the size is not a whole number of bytes"));
+    }
+    if bytes > usize::MAX as f64 {
+        return Err(invalid("the size is out of range"));
+    }
+    Ok(bytes as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(
+            parse_duration(" 10 MIN ").unwrap(),
+            Duration::from_secs(600)
+        );
+        assert_eq!(parse_duration("1h 30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(172800));
+        assert_eq!(parse_duration("0s").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("250us").unwrap(), Duration::from_micros(250));
+
+        for invalid in ["", "10", "ms", "10 parsecs", "-1s", "1.2.3s"] {
+            let err = parse_duration(invalid).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidInput { .. }),
+                "unexpected error for '{}': {:?}",
+                invalid,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("512MB").unwrap(), 512_000_000);
+        assert_eq!(parse_size("512 mib").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(parse_size("1.5GiB").unwrap(), 3 << 29);
+        assert_eq!(parse_size("10b").unwrap(), 10);
+
+        for invalid in ["", "MB", "10 furlongs", "0.5B", "-1KB"] {
+            let err = parse_size(invalid).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidInput { .. }),
+                "unexpected error for '{}': {:?}",
+                invalid,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_into() {
+        assert_eq!(
+            Duration::from_secs(3).into_duration().unwrap(),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            String::from("3s").into_duration().unwrap(),
+            Duration::from_secs(3)
+        );
+        assert_eq!(1024usize.into_size().unwrap(), 1024);
+        assert_eq!("1KiB".into_size().unwrap(), 1024);
+    }
+}